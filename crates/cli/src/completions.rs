@@ -83,4 +83,21 @@ mod test {
     let output = String::from_utf8(output).expect("should be valid");
     assert!(output.contains("ast_grep"));
   }
+
+  #[test]
+  fn test_subcommands_and_values() {
+    let mut output = vec![];
+    let arg = CompletionsArg {
+      shell: Some(Shell::Bash),
+    };
+    run_shell_completion_impl::<App, _>(arg, &mut output).expect("should succeed");
+    let output = String::from_utf8(output).expect("should be valid");
+    // the bin name is the test binary's name here
+    assert!(output.contains("__scan)"));
+    assert!(output.contains("__test)"));
+    // value enums and languages
+    assert!(output.contains("--json"));
+    assert!(output.contains("never"));
+    assert!(output.contains("rs rust"));
+  }
 }
@@ -1,16 +1,20 @@
 use crate::lang::{CustomLang, LanguageGlobs, SerializableInjection, SgLang};
-use crate::utils::{ErrorContext as EC, RuleTrace};
+use crate::utils::{DuplicateRules, ErrorContext as EC, RuleOverwrite, RuleTrace};
 
 use anyhow::{Context, Result};
 use ast_grep_config::{
-  from_str, from_yaml_string, DeserializeEnv, GlobalRules, RuleCollection, RuleConfig,
+  find_unknown_keys, from_str, remove_unknown_keys, DeserializeEnv, GlobalRules, ReferentRuleError,
+  RuleCollection, RuleConfig, RuleCoreError, SerializableRuleConfig, Severity, UnknownKey,
 };
-use ast_grep_language::config_file_type;
+use ast_grep_language::{config_file_type, Language};
+use globset::Glob;
 use ignore::WalkBuilder;
 use regex::Regex;
 use serde::{Deserialize, Serialize};
+use serde_yaml::{with::singleton_map_recursive::deserialize, Deserializer, Value};
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
 use std::fs::read_to_string;
 use std::path::{Path, PathBuf};
 
@@ -18,7 +22,8 @@ use std::path::{Path, PathBuf};
 #[serde(rename_all = "camelCase")]
 pub struct TestConfig {
   pub test_dir: PathBuf,
-  /// Specify the directory containing snapshots. The path is relative to `test_dir`
+  /// Specify the directory containing snapshots. The path is relative to the config file.
+  /// Default to `__snapshots__` under `test_dir`.
   #[serde(skip_serializing_if = "Option::is_none")]
   pub snapshot_dir: Option<PathBuf>,
 }
@@ -52,11 +57,22 @@ pub struct AstGrepConfig {
   /// injection config for embedded languages
   #[serde(default, skip_serializing_if = "Vec::is_empty")]
   pub language_injections: Vec<SerializableInjection>,
+  /// terminal output configuration
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub output: Option<OutputConfig>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct OutputConfig {
+  /// default of `--color-theme`, like `match:red+bold,path:cyan`
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub color_theme: Option<String>,
 }
 
 pub fn find_rules(
   config_path: Option<PathBuf>,
-  rule_filter: Option<&Regex>,
+  overwrite: &RuleOverwrite,
 ) -> Result<(RuleCollection<SgLang>, RuleTrace)> {
   let config_path =
     find_config_path_with_default(config_path, None).context(EC::ReadConfiguration)?;
@@ -66,7 +82,26 @@ pub fn find_rules(
     .parent()
     .expect("config file must have parent directory");
   let global_rules = find_util_rules(base_dir, sg_config.util_dirs)?;
-  read_directory_yaml(base_dir, sg_config.rule_dirs, global_rules, rule_filter)
+  read_directory_yaml(base_dir, sg_config.rule_dirs, global_rules, overwrite)
+}
+
+/// Find the id and the file path of every rule in `ruleDirs`.
+pub fn find_rule_paths(config_path: Option<PathBuf>) -> Result<Vec<(String, PathBuf)>> {
+  let config_path =
+    find_config_path_with_default(config_path, None).context(EC::ReadConfiguration)?;
+  let config_str = read_to_string(&config_path).context(EC::ReadConfiguration)?;
+  let sg_config: AstGrepConfig = from_str(&config_str).context(EC::ParseConfiguration)?;
+  let base_dir = config_path
+    .parent()
+    .expect("config file must have parent directory");
+  let mut ret = vec![];
+  for path in find_rule_files(base_dir, sg_config.rule_dirs)? {
+    let yaml = read_to_string(&path).with_context(|| EC::ReadRule(path.clone()))?;
+    let configs =
+      parse_rule_yaml(&yaml, &path, false).with_context(|| EC::ParseRule(path.clone()))?;
+    ret.extend(configs.into_iter().map(|(_, c)| (c.id, path.clone())));
+  }
+  Ok(ret)
 }
 
 pub fn register_custom_language(config_path: Option<PathBuf>) -> Result<()> {
@@ -79,7 +114,7 @@ pub fn register_custom_language(config_path: Option<PathBuf>) -> Result<()> {
   let sg_config: AstGrepConfig = from_str(&config_str).context(EC::ParseConfiguration)?;
   path.pop();
   if let Some(custom_langs) = sg_config.custom_languages {
-    SgLang::register_custom_language(path, custom_langs);
+    SgLang::register_custom_language(path, custom_langs).context(EC::RegisterCustomLanguage)?;
   }
   if let Some(globs) = sg_config.language_globs {
     SgLang::register_globs(globs)?;
@@ -88,6 +123,18 @@ pub fn register_custom_language(config_path: Option<PathBuf>) -> Result<()> {
   Ok(())
 }
 
+/// Read `output.colorTheme` of the project configuration, if any.
+pub fn read_color_theme(config_path: Option<PathBuf>) -> Result<Option<String>> {
+  let Ok(path) = find_config_path_with_default(config_path, None) else {
+    return Ok(None); // do not report error if no sgconfig.yml is found
+  };
+  let Ok(config_str) = read_to_string(path) else {
+    return Ok(None);
+  };
+  let sg_config: AstGrepConfig = from_str(&config_str).context(EC::ParseConfiguration)?;
+  Ok(sg_config.output.and_then(|o| o.color_theme))
+}
+
 fn build_util_walker(base_dir: &Path, util_dirs: Option<Vec<PathBuf>>) -> Option<WalkBuilder> {
   let mut util_dirs = util_dirs?.into_iter();
   let first = util_dirs.next()?;
@@ -131,13 +178,45 @@ fn read_directory_yaml(
   base_dir: &Path,
   rule_dirs: Vec<PathBuf>,
   global_rules: GlobalRules<SgLang>,
-  rule_filter: Option<&Regex>,
+  overwrite: &RuleOverwrite,
 ) -> Result<(RuleCollection<SgLang>, RuleTrace)> {
   let mut configs = vec![];
+  for path in find_rule_files(base_dir, rule_dirs)? {
+    let yaml = read_to_string(&path).with_context(|| EC::ReadRule(path.clone()))?;
+    let new_configs = parse_rule_yaml(&yaml, &path, overwrite.no_strict_yaml)
+      .with_context(|| EC::ParseRule(path.clone()))?;
+    configs.extend(new_configs.into_iter().map(|(l, c)| (path.clone(), l, c)));
+  }
+  let configs = check_duplicate_ids(configs, overwrite.duplicate_rules)?;
+  let total_rule_count = configs.len();
+
+  let configs = if let Some(filter) = &overwrite.rule_filter {
+    filter_rule_by_regex(configs, filter)?
+  } else {
+    configs
+  };
+  let (configs, severity_excluded_rule_count, off_rules) =
+    compile_rules(configs, &global_rules, overwrite)?;
+  let collection = RuleCollection::try_new(configs).context(EC::GlobPattern)?;
+  let effective_rule_count = collection.total_rule_count();
+  let trace = RuleTrace {
+    effective_rule_count,
+    skipped_rule_count: total_rule_count - effective_rule_count,
+    severity_excluded_rule_count,
+    off_rules,
+    ..Default::default()
+  };
+  Ok((collection, trace))
+}
+
+pub fn find_rule_files(base_dir: &Path, rule_dirs: Vec<PathBuf>) -> Result<Vec<PathBuf>> {
+  let mut files = vec![];
   for dir in rule_dirs {
     let dir_path = base_dir.join(dir);
+    // sort files so that rule loading order is deterministic
     let walker = WalkBuilder::new(&dir_path)
       .types(config_file_type())
+      .sort_by_file_name(|a, b| a.cmp(b))
       .build();
     for dir in walker {
       let config_file = dir.with_context(|| EC::WalkRuleDir(dir_path.clone()))?;
@@ -149,34 +228,19 @@ fn read_directory_yaml(
       {
         continue;
       }
-      let path = config_file.path();
-      let new_configs = read_rule_file(path, Some(&global_rules))?;
-      configs.extend(new_configs);
+      files.push(config_file.path().to_path_buf());
     }
   }
-  let total_rule_count = configs.len();
-
-  let configs = if let Some(filter) = rule_filter {
-    filter_rule_by_regex(configs, filter)?
-  } else {
-    configs
-  };
-  let collection = RuleCollection::try_new(configs).context(EC::GlobPattern)?;
-  let effective_rule_count = collection.total_rule_count();
-  let trace = RuleTrace {
-    effective_rule_count,
-    skipped_rule_count: total_rule_count - effective_rule_count,
-  };
-  Ok((collection, trace))
+  Ok(files)
 }
 
 fn filter_rule_by_regex(
-  configs: Vec<RuleConfig<SgLang>>,
+  configs: Vec<(PathBuf, SerializableRuleConfig<SgLang>)>,
   filter: &Regex,
-) -> Result<Vec<RuleConfig<SgLang>>> {
+) -> Result<Vec<(PathBuf, SerializableRuleConfig<SgLang>)>> {
   let selected: Vec<_> = configs
     .into_iter()
-    .filter(|c| filter.is_match(&c.id))
+    .filter(|(_, c)| filter.is_match(&c.id))
     .collect();
 
   if selected.is_empty() {
@@ -186,17 +250,560 @@ fn filter_rule_by_regex(
   }
 }
 
+/// Parse rules in a YAML string without compiling them.
+/// Multiple rules can be separated by `---`.
+/// Returns the rules with the line of their `id`. `path` is used for error reporting.
+fn parse_rule_yaml(
+  yaml: &str,
+  path: &Path,
+  no_strict_yaml: bool,
+) -> Result<Vec<(usize, SerializableRuleConfig<SgLang>)>> {
+  let docs = split_yaml_documents(yaml);
+  let mut lenient_docs = check_unknown_keys(&docs, path, no_strict_yaml)?;
+  let id_lines: Vec<_> = docs
+    .iter()
+    .map(|(offset, doc)| offset + find_location(doc, "id:").map_or(1, |(line, _)| line))
+    .collect();
+  let mut ret = vec![];
+  for (i, doc) in Deserializer::from_str(yaml).enumerate() {
+    let line = id_lines.get(i).copied().unwrap_or(1);
+    let config = match lenient_docs.get_mut(i).and_then(Option::take) {
+      Some(value) => deserialize(value)?,
+      None => deserialize(doc)?,
+    };
+    ret.push((line, config));
+  }
+  Ok(ret)
+}
+
+/// Report misspelled keys in rule documents as an error.
+/// With `--no-strict-yaml`, they are reported as warnings and the returned documents
+/// have unknown keys removed. A document without unknown keys is returned as `None`.
+fn check_unknown_keys(
+  docs: &[(usize, String)],
+  path: &Path,
+  no_strict_yaml: bool,
+) -> Result<Vec<Option<Value>>> {
+  let mut problems = vec![];
+  let mut lenient_docs = vec![];
+  for (offset, doc) in docs {
+    // syntax errors are reported by deserialization
+    let Ok(mut value) = from_str::<Value>(doc) else {
+      lenient_docs.push(None);
+      continue;
+    };
+    let unknown_keys = remove_unknown_keys(&mut value);
+    for unknown in &unknown_keys {
+      let (line, column, message) = unknown_key_problem(doc, unknown);
+      problems.push(RuleProblem {
+        path: path.to_path_buf(),
+        line: offset + line,
+        column,
+        message,
+      });
+    }
+    lenient_docs.push((!unknown_keys.is_empty()).then_some(value));
+  }
+  if problems.is_empty() {
+    return Ok(lenient_docs);
+  }
+  if !no_strict_yaml {
+    let messages: Vec<_> = problems.iter().map(|p| p.to_string()).collect();
+    return Err(anyhow::anyhow!(messages.join("\n")));
+  }
+  for problem in problems {
+    eprintln!("⚠️  {problem}");
+  }
+  Ok(lenient_docs)
+}
+
+/// Find unknown keys in a rule document. Returns 1-based line, column and message of each key.
+fn unknown_key_problems(doc: &str) -> Vec<(usize, usize, String)> {
+  let Ok(value) = from_str::<Value>(doc) else {
+    return vec![];
+  };
+  find_unknown_keys(&value)
+    .iter()
+    .map(|unknown| unknown_key_problem(doc, unknown))
+    .collect()
+}
+
+fn unknown_key_problem(doc: &str, unknown: &UnknownKey) -> (usize, usize, String) {
+  let key = &unknown.key;
+  let (line, column) = find_location(doc, &format!("{key}:")).unwrap_or((1, 1));
+  let mut message = format!("unknown key `{key}`");
+  if !unknown.parent.is_empty() {
+    message.push_str(&format!(" in `{}`", unknown.parent));
+  }
+  message.push('.');
+  if let Some(suggestion) = unknown.suggestion {
+    message.push_str(&format!(" Did you mean `{suggestion}`?"));
+  }
+  (line, column, message)
+}
+
+/// Report rules sharing the same id, unless `--allow-duplicate-rules` is passed.
+/// With `last-wins`, only the last loaded rule of the same id is kept.
+fn check_duplicate_ids(
+  configs: Vec<(PathBuf, usize, SerializableRuleConfig<SgLang>)>,
+  duplicate_rules: Option<DuplicateRules>,
+) -> Result<Vec<(PathBuf, SerializableRuleConfig<SgLang>)>> {
+  let mut last_index = HashMap::new();
+  for (i, (_, _, config)) in configs.iter().enumerate() {
+    last_index.insert(config.id.as_str(), i);
+  }
+  if last_index.len() == configs.len() {
+    return Ok(configs.into_iter().map(|(p, _, c)| (p, c)).collect());
+  }
+  if duplicate_rules.is_none() {
+    let mut duplicates: Vec<(String, Vec<String>)> = vec![];
+    for (path, line, config) in &configs {
+      let location = format!("{}:{line}", path.display());
+      if let Some((_, locations)) = duplicates.iter_mut().find(|(id, _)| id == &config.id) {
+        locations.push(location);
+      } else {
+        duplicates.push((config.id.clone(), vec![location]));
+      }
+    }
+    duplicates.retain(|(_, locations)| locations.len() > 1);
+    return Err(anyhow::anyhow!(EC::DuplicateRuleId(duplicates)));
+  }
+  let last_index: HashSet<_> = last_index.into_values().collect();
+  let deduped = configs
+    .into_iter()
+    .enumerate()
+    .filter(|(i, _)| last_index.contains(i))
+    .map(|(_, (p, _, c))| (p, c))
+    .collect();
+  Ok(deduped)
+}
+
+/// Apply CLI severity overwrites and compile the rules selected by `--only-severity`.
+/// Returns the compiled rules, the number of rules excluded by severity
+/// and the ids of rules with severity off.
+fn compile_rules(
+  configs: Vec<(PathBuf, SerializableRuleConfig<SgLang>)>,
+  global_rules: &GlobalRules<SgLang>,
+  overwrite: &RuleOverwrite,
+) -> Result<(Vec<RuleConfig<SgLang>>, usize, Vec<String>)> {
+  let mut compiled = vec![];
+  let mut excluded = 0;
+  let mut off_rules = vec![];
+  for (path, mut config) in configs {
+    overwrite.find(&config.id).overwrite(&mut config);
+    if !overwrite.is_severity_selected(&config.severity) {
+      excluded += 1;
+      continue;
+    }
+    if matches!(config.severity, Severity::Off) {
+      off_rules.push(config.id.clone());
+      // RuleCollection drops rules that are off
+      if overwrite.include_off {
+        config.severity = Severity::Hint;
+      }
+    }
+    let rule = RuleConfig::try_from(config, global_rules).with_context(|| EC::ParseRule(path))?;
+    compiled.push(rule);
+  }
+  Ok((compiled, excluded, off_rules))
+}
+
+/// Read rules from a YAML string, `path` is used for error reporting.
+pub fn read_rule_yaml(
+  yaml: &str,
+  path: &Path,
+  overwrite: &RuleOverwrite,
+) -> Result<(Vec<RuleConfig<SgLang>>, usize)> {
+  let configs = parse_rule_yaml(yaml, path, overwrite.no_strict_yaml)
+    .with_context(|| EC::ParseRule(path.to_path_buf()))?;
+  let configs = configs
+    .into_iter()
+    .map(|(l, c)| (path.to_path_buf(), l, c))
+    .collect();
+  let configs = check_duplicate_ids(configs, overwrite.duplicate_rules)?;
+  let (rules, excluded, _) = compile_rules(configs, &Default::default(), overwrite)?;
+  Ok((rules, excluded))
+}
+
 pub fn read_rule_file(
   path: &Path,
-  global_rules: Option<&GlobalRules<SgLang>>,
-) -> Result<Vec<RuleConfig<SgLang>>> {
+  overwrite: &RuleOverwrite,
+) -> Result<(Vec<RuleConfig<SgLang>>, usize)> {
   let yaml = read_to_string(path).with_context(|| EC::ReadRule(path.to_path_buf()))?;
-  let parsed = if let Some(globals) = global_rules {
-    from_yaml_string(&yaml, globals)
+  read_rule_yaml(&yaml, path, overwrite)
+}
+
+/// Rules read from a project without stopping at broken rules.
+pub struct LoadedRules {
+  /// Rules that can be compiled, with the file defining them.
+  pub rules: Vec<(PathBuf, RuleConfig<SgLang>)>,
+  /// Files or rules that cannot be read, parsed or compiled.
+  pub failures: Vec<(PathBuf, anyhow::Error)>,
+  /// The project configuration file.
+  pub config_path: PathBuf,
+  /// Rule directories joined with the project root.
+  pub rule_dirs: Vec<PathBuf>,
+  /// Utility rules in `utilDirs`, referenced by rules with `matches`.
+  pub global_rules: GlobalRules<SgLang>,
+}
+
+/// Find the ids of utility rules in `utilDirs`, sorted and deduplicated.
+pub fn find_util_ids(config_path: Option<PathBuf>) -> Result<Vec<String>> {
+  let config_path =
+    find_config_path_with_default(config_path, None).context(EC::ReadConfiguration)?;
+  let config_str = read_to_string(&config_path).context(EC::ReadConfiguration)?;
+  let sg_config: AstGrepConfig = from_str(&config_str).context(EC::ParseConfiguration)?;
+  let base_dir = config_path
+    .parent()
+    .expect("config file must have parent directory");
+  let Some(mut walker) = build_util_walker(base_dir, sg_config.util_dirs) else {
+    return Ok(vec![]);
+  };
+  let mut ids = vec![];
+  for entry in walker.types(config_file_type()).build() {
+    let entry = entry.with_context(|| EC::WalkRuleDir(base_dir.to_path_buf()))?;
+    let path = entry.path();
+    if !path.is_file() {
+      continue;
+    }
+    let yaml = read_to_string(path).with_context(|| EC::ReadRule(path.to_path_buf()))?;
+    let util: Value = from_str(&yaml).with_context(|| EC::ParseRule(path.to_path_buf()))?;
+    if let Some(id) = util.get("id").and_then(Value::as_str) {
+      ids.push(id.to_string());
+    }
+  }
+  ids.sort();
+  ids.dedup();
+  Ok(ids)
+}
+
+/// Read every rule in `ruleDirs` and apply CLI overwrites like severity,
+/// collecting failures instead of returning the first one.
+pub fn load_rules_leniently(
+  config_path: Option<PathBuf>,
+  overwrite: &RuleOverwrite,
+) -> Result<LoadedRules> {
+  let config_path =
+    find_config_path_with_default(config_path, None).context(EC::ReadConfiguration)?;
+  let config_str = read_to_string(&config_path).context(EC::ReadConfiguration)?;
+  let sg_config: AstGrepConfig = from_str(&config_str).context(EC::ParseConfiguration)?;
+  let base_dir = config_path
+    .parent()
+    .expect("config file must have parent directory");
+  let global_rules = find_util_rules(base_dir, sg_config.util_dirs)?;
+  let rule_dirs = sg_config
+    .rule_dirs
+    .iter()
+    .map(|d| base_dir.join(d))
+    .collect();
+  let mut loaded = LoadedRules {
+    rules: vec![],
+    failures: vec![],
+    config_path: config_path.clone(),
+    rule_dirs,
+    global_rules,
+  };
+  for path in find_rule_files(base_dir, sg_config.rule_dirs)? {
+    let configs = read_to_string(&path)
+      .with_context(|| EC::ReadRule(path.clone()))
+      .and_then(|yaml| {
+        parse_rule_yaml(&yaml, &path, overwrite.no_strict_yaml)
+          .with_context(|| EC::ParseRule(path.clone()))
+      });
+    let configs = match configs {
+      Ok(configs) => configs,
+      Err(error) => {
+        loaded.failures.push((path, error));
+        continue;
+      }
+    };
+    for (_, mut config) in configs {
+      overwrite.find(&config.id).overwrite(&mut config);
+      let id = config.id.clone();
+      match RuleConfig::try_from(config, &loaded.global_rules) {
+        Ok(rule) => loaded.rules.push((path.clone(), rule)),
+        Err(error) => {
+          let error = anyhow::Error::from(error).context(format!("Cannot compile rule `{id}`"));
+          loaded.failures.push((path.clone(), error));
+        }
+      }
+    }
+  }
+  Ok(loaded)
+}
+
+/// A problem found in a rule file by `sg scan --check-rules`.
+pub struct RuleProblem {
+  pub path: PathBuf,
+  /// 1-based line in the rule file
+  pub line: usize,
+  /// 1-based column in the rule file
+  pub column: usize,
+  pub message: String,
+}
+
+impl fmt::Display for RuleProblem {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let path = self.path.display();
+    write!(f, "{path}:{}:{}: {}", self.line, self.column, self.message)
+  }
+}
+
+/// Result of compiling rules without scanning code.
+pub struct RuleCheck {
+  pub rule_count: usize,
+  pub problems: Vec<RuleProblem>,
+}
+
+/// Compile every rule and collect all problems instead of stopping at the first one.
+/// Rules are read from `rule_file`, `inline_rules` or the rule directories of sgconfig.yml.
+pub fn check_rules(
+  config_path: Option<PathBuf>,
+  rule_file: Option<&Path>,
+  inline_rules: Option<&str>,
+) -> Result<RuleCheck> {
+  let (sources, global_rules) = if let Some(path) = rule_file {
+    let yaml = read_to_string(path).with_context(|| EC::ReadRule(path.to_path_buf()))?;
+    (vec![(path.to_path_buf(), yaml)], GlobalRules::default())
+  } else if let Some(text) = inline_rules {
+    let source = (PathBuf::from("INLINE_RULES"), text.to_string());
+    (vec![source], GlobalRules::default())
+  } else {
+    let config_path =
+      find_config_path_with_default(config_path, None).context(EC::ReadConfiguration)?;
+    let config_str = read_to_string(&config_path).context(EC::ReadConfiguration)?;
+    let sg_config: AstGrepConfig = from_str(&config_str).context(EC::ParseConfiguration)?;
+    let base_dir = config_path
+      .parent()
+      .expect("config file must have parent directory");
+    let global_rules = find_util_rules(base_dir, sg_config.util_dirs)?;
+    let mut sources = vec![];
+    for path in find_rule_files(base_dir, sg_config.rule_dirs)? {
+      let yaml = read_to_string(&path).with_context(|| EC::ReadRule(path.clone()))?;
+      sources.push((path, yaml));
+    }
+    (sources, global_rules)
+  };
+  let mut check = RuleCheck {
+    rule_count: 0,
+    problems: vec![],
+  };
+  for (path, yaml) in sources {
+    for (offset, doc) in split_yaml_documents(&yaml) {
+      check.rule_count += 1;
+      for (line, column, message) in check_rule_document(&doc, &global_rules) {
+        check.problems.push(RuleProblem {
+          path: path.clone(),
+          line: offset + line,
+          column,
+          message,
+        });
+      }
+    }
+  }
+  Ok(check)
+}
+
+/// Check every rule in a rule file like `sg scan --check-rules`.
+/// Returns 1-based line, column and message of each problem.
+pub fn check_rule_yaml(
+  yaml: &str,
+  global_rules: &GlobalRules<SgLang>,
+) -> Vec<(usize, usize, String)> {
+  let mut problems = vec![];
+  for (offset, doc) in split_yaml_documents(yaml) {
+    for (line, column, message) in check_rule_document(&doc, global_rules) {
+      problems.push((offset + line, column, message));
+    }
+  }
+  problems
+}
+
+/// Check the syntax and fields of a project configuration like `sgconfig.yml`.
+/// Returns 1-based line, column and message of each problem.
+pub fn check_config_yaml(yaml: &str) -> Vec<(usize, usize, String)> {
+  match from_str::<AstGrepConfig>(yaml) {
+    Ok(_) => vec![],
+    Err(err) => vec![yaml_error_problem(&err)],
+  }
+}
+
+/// Split a YAML stream into documents separated by `---`.
+/// Returns the line offset of every non-empty document and its text.
+fn split_yaml_documents(yaml: &str) -> Vec<(usize, String)> {
+  let mut docs = vec![];
+  let mut offset = 0;
+  let mut lines = vec![];
+  for (i, line) in yaml.lines().enumerate() {
+    if line == "---" || line.starts_with("--- ") {
+      push_yaml_document(&mut docs, offset, &lines);
+      lines.clear();
+      offset = i;
+      // the document can start on the same line as the separator
+      lines.push(&line[3..]);
+    } else {
+      lines.push(line);
+    }
+  }
+  push_yaml_document(&mut docs, offset, &lines);
+  docs
+}
+
+fn push_yaml_document(docs: &mut Vec<(usize, String)>, offset: usize, lines: &[&str]) {
+  let is_empty = lines.iter().all(|l| {
+    let l = l.trim();
+    l.is_empty() || l.starts_with('#')
+  });
+  if !is_empty {
+    docs.push((offset, lines.join("\n")));
+  }
+}
+
+/// Check one rule document. Returns 1-based line, column and message of each problem.
+fn check_rule_document(
+  doc: &str,
+  global_rules: &GlobalRules<SgLang>,
+) -> Vec<(usize, usize, String)> {
+  let mut problems = unknown_key_problems(doc);
+  let config: SerializableRuleConfig<SgLang> = match from_str(doc) {
+    Ok(config) => config,
+    // unknown keys in rule objects are also rejected by deserialization
+    Err(_) if !problems.is_empty() => return problems,
+    Err(err) => return vec![yaml_error_problem(&err)],
+  };
+  let (id_line, id_column) = find_location(doc, "id:").unwrap_or((1, 1));
+  let id = config.id.clone();
+  let lang = config.language;
+  let mut report = |(line, column), message: String| {
+    problems.push((line, column, format!("rule `{id}`: {message}")));
+  };
+  let globs = config.files.iter().chain(&config.ignores).flatten();
+  for glob in globs {
+    if let Err(err) = Glob::new(glob) {
+      let location = find_location(doc, glob).unwrap_or((id_line, id_column));
+      report(location, err.to_string());
+    }
+  }
+  if let Ok(value) = from_str::<Value>(doc) {
+    let mut patterns = vec![];
+    collect_patterns(&value, &mut patterns);
+    for pattern in patterns {
+      if pattern_has_error(pattern, lang) {
+        let first_line = pattern.lines().next().unwrap_or_default().trim();
+        let location = find_location(doc, first_line).unwrap_or((id_line, id_column));
+        report(
+          location,
+          format!("pattern `{pattern}` contains an ERROR node in {lang}."),
+        );
+      }
+    }
+  }
+  if let Err(err) = RuleConfig::try_from(config, global_rules) {
+    let err = anyhow::Error::from(err);
+    // point undefined meta variables at their use, the yaml itself is parsed fine
+    let undefined_var = err.chain().find_map(|e| match e.downcast_ref() {
+      Some(RuleCoreError::UndefinedMetaVar(var, section)) => Some((e, var, *section)),
+      _ => None,
+    });
+    if let Some((e, var, section)) = undefined_var {
+      let location = find_meta_var_location(doc, var, section);
+      let message = e.to_string().trim_end_matches('.').to_string();
+      report(location.unwrap_or((id_line, id_column)), message);
+      return problems;
+    }
+    // point undefined utils at the `matches` referencing them
+    let undefined = err.chain().find_map(|e| match e.downcast_ref() {
+      Some(ReferentRuleError::UndefinedUtil(util)) => Some(util),
+      _ => None,
+    });
+    let location = undefined
+      .and_then(|util| find_location(doc, &format!("matches: {util}")))
+      .map(|(line, column)| (line, column + "matches: ".len()));
+    let causes: Vec<_> = err
+      .chain()
+      .map(|e| e.to_string().trim_end_matches('.').to_string())
+      .collect();
+    report(location.unwrap_or((id_line, id_column)), causes.join(": "));
+  }
+  problems
+}
+
+fn yaml_error_problem(err: &serde_yaml::Error) -> (usize, usize, String) {
+  let message = err.to_string();
+  let Some(location) = err.location() else {
+    return (1, 1, message);
+  };
+  let (line, column) = (location.line(), location.column());
+  // location is reported separately, and it is relative to the document
+  let message = message.replace(&format!(" at line {line} column {column}"), "");
+  (line, column, message)
+}
+
+/// Find the first line containing `needle` in `doc`.
+fn find_location(doc: &str, needle: &str) -> Option<(usize, usize)> {
+  if needle.is_empty() {
+    return None;
+  }
+  doc
+    .lines()
+    .enumerate()
+    .find_map(|(i, line)| Some((i + 1, line.find(needle)? + 1)))
+}
+
+/// Find where meta variable `var` is used in the top level `section` of a rule.
+/// Falls back to the section key if the variable cannot be found in it.
+fn find_meta_var_location(doc: &str, var: &str, section: &str) -> Option<(usize, usize)> {
+  let key = format!("{section}:");
+  let start = doc.lines().position(|line| line.starts_with(&key))?;
+  // fix templates use `$VAR` while constraints and transform use `VAR` as key
+  let needle = if section == "fix" {
+    format!("${var}")
   } else {
-    from_yaml_string(&yaml, &Default::default())
+    format!("{var}:")
   };
-  parsed.with_context(|| EC::ParseRule(path.to_path_buf()))
+  let is_var_char = |c: char| c.is_alphanumeric() || c == '_';
+  let used = doc.lines().enumerate().skip(start).find_map(|(i, line)| {
+    let column = line.match_indices(&needle).find_map(|(col, _)| {
+      let rest = &line[col + needle.len()..];
+      let whole = section != "fix" || !rest.starts_with(is_var_char);
+      whole.then_some(col)
+    })?;
+    Some((i + 1, column + 1))
+  });
+  Some(used.unwrap_or((start + 1, 1)))
+}
+
+/// Collect pattern strings and contextual pattern contexts in a rule.
+fn collect_patterns<'a>(value: &'a Value, patterns: &mut Vec<&'a str>) {
+  match value {
+    Value::Mapping(map) => {
+      for (key, value) in map {
+        match (key.as_str(), value) {
+          (Some("metadata"), _) => continue,
+          (Some("pattern"), Value::String(pattern)) => patterns.push(pattern),
+          (Some("pattern"), Value::Mapping(contextual)) => {
+            if let Some(context) = contextual.get("context").and_then(Value::as_str) {
+              patterns.push(context);
+            }
+          }
+          _ => collect_patterns(value, patterns),
+        }
+      }
+    }
+    Value::Sequence(seq) => {
+      for value in seq {
+        collect_patterns(value, patterns);
+      }
+    }
+    _ => (),
+  }
+}
+
+fn pattern_has_error(pattern: &str, lang: SgLang) -> bool {
+  let processed = lang.pre_process_pattern(pattern);
+  let grep = lang.ast_grep(processed);
+  let has_error = grep.root().dfs().any(|n| n.is_error());
+  has_error
 }
 
 /// Returns the base_directory where config is and config object.
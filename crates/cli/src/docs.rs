@@ -0,0 +1,311 @@
+mod schema;
+
+use crate::config::{load_rules_leniently, register_custom_language};
+use crate::lang::SgLang;
+use crate::utils::{ErrorContext as EC, RuleOverwrite, SeverityArg};
+use crate::verify::{find_examples, RuleExamples};
+
+use anyhow::{Context, Result};
+use ast_grep_config::{RuleConfig, SerializableRuleConfig, Severity};
+use ast_grep_core::Language;
+use clap::{Args, ValueEnum};
+use serde::Serialize;
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write as _;
+use std::io::Write;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+/// Bumped whenever a field of the JSON rule catalog changes.
+const SCHEMA_VERSION: u32 = 1;
+/// Number of valid and invalid test cases rendered as examples of a rule.
+const EXAMPLE_COUNT: usize = 2;
+
+#[derive(Args)]
+pub struct DocsArg {
+  /// Path to ast-grep root config, default is sgconfig.yml.
+  #[clap(short, long, value_name = "CONFIG_FILE")]
+  config: Option<PathBuf>,
+
+  /// Output format of the generated docs.
+  #[clap(long, default_value = "markdown", value_name = "FORMAT")]
+  format: DocsFormat,
+
+  /// Print the JSON schema of rule files instead of the docs.
+  /// The schema includes custom languages and the ids of utility rules.
+  #[clap(long, conflicts_with = "format")]
+  schema: bool,
+
+  /// Write the output to the file instead of StdOut.
+  #[clap(long, value_name = "FILE")]
+  output_file: Option<PathBuf>,
+
+  /// Truncate examples longer than the number of lines.
+  #[clap(long, default_value = "10", value_name = "NUM")]
+  example_lines: usize,
+
+  /// severity related options
+  #[clap(flatten)]
+  severity: SeverityArg,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum DocsFormat {
+  /// Human readable markdown document.
+  Markdown,
+  /// A JSON object with the schema version and an array of rule descriptors,
+  /// for other programs to consume.
+  Json,
+}
+
+/// Print docs of all rules to stdout, ordered by rule id.
+/// Rules that fail to load are reported after the docs of other rules.
+pub fn run_docs(arg: DocsArg) -> Result<()> {
+  register_custom_language(arg.config.clone())?;
+  if arg.schema {
+    let schema = schema::project_schema(arg.config)? + "\n";
+    return write_output(arg.output_file.as_deref(), &schema);
+  }
+  let overwrite = RuleOverwrite::new(&arg.severity, None)?;
+  let loaded = load_rules_leniently(arg.config.clone(), &overwrite)?;
+  let mut rules: Vec<_> = loaded
+    .rules
+    .iter()
+    .filter(|(_, rule)| overwrite.is_severity_selected(&rule.severity))
+    .map(|(path, rule)| (path.as_path(), rule))
+    .collect();
+  rules.sort_by(|a, b| a.1.id.cmp(&b.1.id));
+  let output = match arg.format {
+    DocsFormat::Markdown => {
+      // broken rule files are reported below, do not fail on their inline tests
+      let examples = find_examples(arg.config).unwrap_or_else(|e| {
+        eprintln!("Warning: cannot load rule tests as examples: {e:#}");
+        HashMap::new()
+      });
+      let markdown = MarkdownDocs {
+        examples,
+        max_lines: arg.example_lines,
+      };
+      markdown.render(&rules)
+    }
+    DocsFormat::Json => render_json(&rules)? + "\n",
+  };
+  write_output(arg.output_file.as_deref(), &output)?;
+  if loaded.failures.is_empty() {
+    return Ok(());
+  }
+  eprintln!("Failed to load {} rule(s):", loaded.failures.len());
+  for (path, error) in &loaded.failures {
+    eprintln!("  {}: {error:#}", path.display());
+  }
+  Err(anyhow::anyhow!(EC::InvalidRules(loaded.failures.len())))
+}
+
+fn write_output(output_file: Option<&Path>, output: &str) -> Result<()> {
+  if let Some(path) = output_file {
+    return std::fs::write(path, output).with_context(|| EC::WriteFile(path.to_path_buf()));
+  }
+  let mut stdout = std::io::stdout().lock();
+  write!(stdout, "{output}")?;
+  stdout.flush()?;
+  Ok(())
+}
+
+fn severity_name(severity: &Severity) -> &'static str {
+  match severity {
+    Severity::Hint => "hint",
+    Severity::Info => "info",
+    Severity::Warning => "warning",
+    Severity::Error => "error",
+    Severity::Off => "off",
+  }
+}
+
+/// A rule in the JSON catalog. Fields are serialized in declaration order.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct RuleDescriptor<'a> {
+  id: &'a str,
+  severity: &'static str,
+  language: String,
+  message: &'a str,
+  note: Option<&'a str>,
+  metadata: BTreeMap<&'a str, &'a str>,
+  fix_available: bool,
+  source_path: String,
+  files: Option<&'a [String]>,
+  ignores: Option<&'a [String]>,
+}
+
+impl<'a> RuleDescriptor<'a> {
+  fn new(path: &Path, rule: &'a SerializableRuleConfig<SgLang>) -> Self {
+    let metadata = rule
+      .metadata
+      .iter()
+      .flatten()
+      .map(|(k, v)| (k.as_str(), v.as_str()))
+      .collect();
+    Self {
+      id: &rule.id,
+      severity: severity_name(&rule.severity),
+      language: rule.language.to_string(),
+      message: &rule.message,
+      note: rule.note.as_deref(),
+      metadata,
+      fix_available: rule.core.fix.is_some(),
+      source_path: path.to_string_lossy().into_owned(),
+      files: rule.files.as_deref(),
+      ignores: rule.ignores.as_deref(),
+    }
+  }
+}
+
+/// The JSON catalog. The version is stated once for all rules.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct RuleCatalog<'a> {
+  schema_version: u32,
+  rules: Vec<RuleDescriptor<'a>>,
+}
+
+type Rules<'a> = [(&'a Path, &'a RuleConfig<SgLang>)];
+
+fn render_json(rules: &Rules) -> Result<String> {
+  let catalog = RuleCatalog {
+    schema_version: SCHEMA_VERSION,
+    rules: rules
+      .iter()
+      .map(|(path, rule)| RuleDescriptor::new(path, rule))
+      .collect(),
+  };
+  Ok(serde_json::to_string_pretty(&catalog)?)
+}
+
+struct MarkdownDocs {
+  examples: HashMap<String, RuleExamples>,
+  /// Examples longer than this are truncated.
+  max_lines: usize,
+}
+
+impl MarkdownDocs {
+  fn render(&self, rules: &Rules) -> String {
+    let mut out = String::from("# Rules\n");
+    for (_, rule) in rules {
+      render_rule(&mut out, rule);
+      self.render_examples(&mut out, rule);
+    }
+    out
+  }
+
+  fn render_examples(&self, out: &mut String, rule: &RuleConfig<SgLang>) {
+    let Some(examples) = self.examples.get(&rule.id) else {
+      let _ = writeln!(out, "\n_No examples available._");
+      return;
+    };
+    let lang = rule.language.to_string().to_lowercase();
+    for (code, _) in examples.invalid.iter().take(EXAMPLE_COUNT) {
+      let _ = writeln!(out, "\nIncorrect code:\n");
+      let matches = find_matches(rule, code);
+      self.code_block(out, &lang, code, &matches);
+    }
+    for code in examples.valid.iter().take(EXAMPLE_COUNT) {
+      let _ = writeln!(out, "\nCorrect code:\n");
+      self.code_block(out, &lang, code, &[]);
+    }
+    if rule.matcher.fixer.is_none() {
+      return;
+    }
+    let fixed = examples.invalid.iter().find_map(|(code, fixed)| {
+      let fixed = fixed.as_ref()?;
+      Some((code, fixed))
+    });
+    if let Some((before, after)) = fixed {
+      let _ = writeln!(out, "\nBefore fix:\n");
+      self.code_block(out, &lang, before, &[]);
+      let _ = writeln!(out, "\nAfter fix:\n");
+      self.code_block(out, &lang, after, &[]);
+    }
+  }
+
+  /// Render the code with the matched ranges underlined, truncated to `max_lines`.
+  fn code_block(&self, out: &mut String, lang: &str, code: &str, matches: &[Range<usize>]) {
+    let code = code.trim_end();
+    let lines: Vec<_> = code.split('\n').collect();
+    let truncated = lines.len() > self.max_lines;
+    let kept = lines[..lines.len().min(self.max_lines)].join("\n");
+    let _ = writeln!(out, "```{lang}");
+    if !kept.is_empty() {
+      out.push_str(&highlight(&kept, matches));
+    }
+    if truncated {
+      let _ = writeln!(out, "…");
+    }
+    let _ = writeln!(out, "```");
+  }
+}
+
+fn find_matches(rule: &RuleConfig<SgLang>, code: &str) -> Vec<Range<usize>> {
+  let root = rule.language.ast_grep(code);
+  let matches = root.root().find_all(&rule.matcher);
+  matches.map(|m| m.range()).collect()
+}
+
+/// Insert a line of carets under every line with matched code.
+fn highlight(code: &str, matches: &[Range<usize>]) -> String {
+  let mut out = String::new();
+  let mut offset = 0;
+  for line in code.split('\n') {
+    let line_range = offset..offset + line.len();
+    offset += line.len() + 1;
+    out.push_str(line);
+    out.push('\n');
+    let mut marks: Vec<char> = vec![];
+    for m in matches {
+      let start = m.start.max(line_range.start);
+      let end = m.end.min(line_range.end);
+      if start >= end {
+        continue;
+      }
+      // columns are counted in chars
+      let col = line[..start - line_range.start].chars().count();
+      let width = line[start - line_range.start..end - line_range.start]
+        .chars()
+        .count();
+      if marks.len() < col + width {
+        marks.resize(col + width, ' ');
+      }
+      marks[col..col + width].fill('^');
+    }
+    if !marks.is_empty() {
+      out.extend(marks);
+      out.push('\n');
+    }
+  }
+  out
+}
+
+fn render_rule(out: &mut String, rule: &SerializableRuleConfig<SgLang>) {
+  let globs = |globs: &[String]| {
+    let quoted: Vec<_> = globs.iter().map(|g| format!("`{g}`")).collect();
+    quoted.join(", ")
+  };
+  // writing to String never fails
+  let _ = writeln!(out, "\n## {}\n", rule.id);
+  if !rule.message.is_empty() {
+    let _ = writeln!(out, "{}\n", rule.message.trim());
+  }
+  let _ = writeln!(out, "- Severity: {}", severity_name(&rule.severity));
+  let _ = writeln!(out, "- Language: {}", rule.language);
+  let fix = if rule.core.fix.is_some() { "yes" } else { "no" };
+  let _ = writeln!(out, "- Fix available: {fix}");
+  if let Some(files) = &rule.files {
+    let _ = writeln!(out, "- Files: {}", globs(files));
+  }
+  if let Some(ignores) = &rule.ignores {
+    let _ = writeln!(out, "- Ignores: {}", globs(ignores));
+  }
+  if let Some(note) = &rule.note {
+    let _ = writeln!(out, "\n{}", note.trim());
+  }
+}
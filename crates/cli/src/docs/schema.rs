@@ -0,0 +1,43 @@
+use crate::config::find_util_ids;
+use crate::lang::SgLang;
+
+use anyhow::{Context, Result};
+use ast_grep_config::rule_schema;
+use schemars::schema::{InstanceType, RootSchema, Schema, SchemaObject};
+
+use std::path::PathBuf;
+
+/// JSON schema of rule files in the project.
+/// Custom languages must be registered before calling this.
+pub fn project_schema(config: Option<PathBuf>) -> Result<String> {
+  let mut schema = rule_schema::<SgLang>()?;
+  let util_ids = find_util_ids(config)?;
+  suggest_util_ids(&mut schema, util_ids)?;
+  Ok(serde_json::to_string_pretty(&schema)?)
+}
+
+/// Suggest the ids of utility rules for `matches` while allowing local utils in rule files.
+fn suggest_util_ids(schema: &mut RootSchema, util_ids: Vec<String>) -> Result<()> {
+  if util_ids.is_empty() {
+    return Ok(());
+  }
+  let Some(Schema::Object(rule)) = schema.definitions.get_mut("SerializableRule") else {
+    anyhow::bail!("SerializableRule's type is not object!");
+  };
+  let props = &mut rule.object().properties;
+  let Schema::Object(matches) = props.get_mut("matches").context("must have matches")? else {
+    anyhow::bail!("matches's type is not object!");
+  };
+  let suggested = SchemaObject {
+    enum_values: Some(util_ids.into_iter().map(Into::into).collect()),
+    ..Default::default()
+  };
+  let any_string = SchemaObject {
+    instance_type: Some(InstanceType::String.into()),
+    ..Default::default()
+  };
+  let subschemas = matches.subschemas();
+  subschemas.all_of = None;
+  subschemas.any_of = Some(vec![suggested.into(), any_string.into()]);
+  Ok(())
+}
@@ -0,0 +1,171 @@
+use crate::lang::SgLang;
+use crate::print::ColorArg;
+use crate::utils::{dump_node, DumpNode, ErrorContext as EC};
+
+use ansi_term::Style;
+use anyhow::{Context, Result};
+use ast_grep_language::Language;
+use clap::{Args, ValueEnum};
+
+use std::io::{Read, Write};
+use std::ops::Range;
+use std::path::PathBuf;
+
+#[derive(Args)]
+pub struct DumpAstArg {
+  /// The file to parse. Its language is inferred from the file extension unless `--lang` is set.
+  #[clap(value_name = "FILE", required_unless_present = "stdin")]
+  file: Option<PathBuf>,
+
+  /// Read the code from StdIn. Requires `--lang`.
+  #[clap(long, conflicts_with = "file", requires = "lang")]
+  stdin: bool,
+
+  /// The language of the code.
+  #[clap(short, long, hide_possible_values = true)]
+  lang: Option<SgLang>,
+
+  /// Output format of the tree.
+  #[clap(long, default_value = "text", value_name = "FORMAT")]
+  format: DumpFormat,
+
+  /// Only print the unique kinds of named nodes in the tree.
+  #[clap(long)]
+  kind_only: bool,
+
+  /// Only dump the innermost node enclosing the lines, e.g. `3` or `3:5`.
+  /// Lines are 1-based and inclusive.
+  #[clap(long, value_name = "START[:END]", value_parser = parse_range, conflicts_with = "bytes")]
+  lines: Option<(usize, usize)>,
+
+  /// Only dump the innermost node enclosing the byte offsets, e.g. `42` or `42:60`.
+  /// Offsets are 0-based and the end is exclusive.
+  #[clap(long, value_name = "START[:END]", value_parser = parse_range)]
+  bytes: Option<(usize, usize)>,
+
+  /// Controls output color.
+  #[clap(long, default_value = "auto", value_name = "WHEN")]
+  color: ColorArg,
+}
+
+impl DumpAstArg {
+  pub fn color(&self) -> ColorArg {
+    self.color
+  }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum DumpFormat {
+  /// Indented tree of named nodes. Positions are printed as 0-based `line,column`.
+  Text,
+  /// JSON object of the tree for other programs to consume.
+  Json,
+}
+
+fn parse_range(s: &str) -> Result<(usize, usize)> {
+  let (start, end) = s.split_once(':').unwrap_or((s, s));
+  let start: usize = start.trim().parse()?;
+  let end: usize = end.trim().parse()?;
+  if end < start {
+    anyhow::bail!("end must not be less than start");
+  }
+  Ok((start, end))
+}
+
+/// Print the parse tree of the file or StdIn.
+pub fn run_dump_ast(arg: DumpAstArg) -> Result<()> {
+  let (src, lang) = read_source(&arg)?;
+  let root = lang.ast_grep(&src);
+  let mut node = dump_node(root.root().get_ts_node());
+  if let Some(range) = byte_range(&arg, &src)? {
+    let invalid = format!("bytes {}:{}", range.start, range.end);
+    node = node
+      .enclosing(&range)
+      .ok_or_else(|| anyhow::anyhow!(EC::InvalidRange(invalid)))?;
+  }
+  node.retain_named();
+  let output = render(&node, &arg)?;
+  let mut stdout = std::io::stdout().lock();
+  write!(stdout, "{output}")?;
+  stdout.flush()?;
+  Ok(())
+}
+
+fn read_source(arg: &DumpAstArg) -> Result<(String, SgLang)> {
+  let Some(path) = &arg.file else {
+    let lang = arg.lang.ok_or(anyhow::anyhow!(EC::LanguageNotSpecified))?;
+    let mut src = String::new();
+    std::io::stdin().read_to_string(&mut src)?;
+    return Ok((src, lang));
+  };
+  let lang = match arg.lang {
+    Some(lang) => lang,
+    None => {
+      SgLang::from_path(path).ok_or(anyhow::anyhow!(EC::UnknownFileLanguage(path.clone())))?
+    }
+  };
+  let src = std::fs::read_to_string(path).with_context(|| EC::ReadFile(path.clone()))?;
+  Ok((src, lang))
+}
+
+/// Convert `--lines` or `--bytes` to a byte range in the source.
+fn byte_range(arg: &DumpAstArg, src: &str) -> Result<Option<Range<usize>>> {
+  if let Some((start, end)) = arg.bytes {
+    if end > src.len() {
+      let range = format!("bytes {start}:{end}");
+      return Err(anyhow::anyhow!(EC::InvalidRange(range)));
+    }
+    return Ok(Some(start..end));
+  }
+  let Some((start, end)) = arg.lines else {
+    return Ok(None);
+  };
+  let invalid = || anyhow::anyhow!(EC::InvalidRange(format!("lines {start}:{end}")));
+  let mut offset = 0;
+  let mut line_ranges = vec![];
+  // indentation and line breaks are not part of the line content
+  for line in src.split_inclusive('\n') {
+    let content = line.trim_end();
+    let indent = content.len() - content.trim_start().len();
+    line_ranges.push(offset + indent..offset + content.len());
+    offset += line.len();
+  }
+  if start == 0 {
+    return Err(invalid());
+  }
+  let first = line_ranges.get(start - 1).ok_or_else(invalid)?;
+  let last = line_ranges.get(end - 1).ok_or_else(invalid)?;
+  Ok(Some(first.start..last.end))
+}
+
+fn render(node: &DumpNode, arg: &DumpAstArg) -> Result<String> {
+  let colored = arg.color.should_use_color();
+  let output = match (arg.format, arg.kind_only) {
+    (DumpFormat::Text, false) => node.ast(colored),
+    (DumpFormat::Text, true) => {
+      let style = if colored {
+        Style::new().bold()
+      } else {
+        Style::new()
+      };
+      let kinds = node.kinds().into_iter();
+      kinds.map(|k| format!("{}\n", style.paint(k))).collect()
+    }
+    (DumpFormat::Json, false) => serde_json::to_string_pretty(node)? + "\n",
+    (DumpFormat::Json, true) => serde_json::to_string_pretty(&node.kinds())? + "\n",
+  };
+  Ok(output)
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  #[test]
+  fn test_parse_range() {
+    assert_eq!(parse_range("3").expect("should parse"), (3, 3));
+    assert_eq!(parse_range("3:5").expect("should parse"), (3, 5));
+    assert!(parse_range("5:3").is_err());
+    assert!(parse_range("a:3").is_err());
+  }
+}
@@ -0,0 +1,428 @@
+use crate::config::{find_rules, read_rule_file, register_custom_language};
+use crate::lang::SgLang;
+use crate::print::ColorArg;
+use crate::utils::{ErrorContext as EC, RuleOverwrite};
+
+use ansi_term::{Color, Style};
+use anyhow::{Context, Result};
+use ast_grep_config::{DeserializeEnv, Rule, RuleConfig, SerializableRule};
+use ast_grep_core::{Matcher, Node, StrDoc};
+use ast_grep_language::Language;
+use clap::Args;
+use serde::Serialize;
+
+use std::fmt::Write as _;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+#[derive(Args)]
+pub struct InspectArg {
+  /// The rule to inspect, either a rule file or the id of a rule in the project.
+  #[clap(short, long, value_name = "FILE|ID")]
+  rule: String,
+
+  /// The file to evaluate the rule against.
+  #[clap(short, long, value_name = "FILE")]
+  target: PathBuf,
+
+  /// Path to ast-grep root config, default is sgconfig.yml.
+  #[clap(short, long, value_name = "CONFIG_FILE")]
+  config: Option<PathBuf>,
+
+  /// Output the evaluation trace in JSON format.
+  #[clap(long)]
+  json: bool,
+
+  /// Controls output color.
+  #[clap(long, default_value = "auto", value_name = "WHEN")]
+  color: ColorArg,
+}
+
+impl InspectArg {
+  pub fn color(&self) -> ColorArg {
+    self.color
+  }
+}
+
+/// Evaluate a rule against the target file and print how every sub-rule behaves.
+pub fn run_inspect(arg: InspectArg) -> Result<()> {
+  register_custom_language(arg.config.clone())?;
+  let mut overwrite = RuleOverwrite::default();
+  // off rules can still be inspected
+  overwrite.include_off = true;
+  if Path::new(&arg.rule).is_file() {
+    let (rules, _) = read_rule_file(Path::new(&arg.rule), &overwrite)?;
+    let rule = rules
+      .first()
+      .ok_or_else(|| anyhow::anyhow!(EC::RuleNotFound(arg.rule.clone())))?;
+    inspect(rule, &arg)
+  } else {
+    let (rules, _) = find_rules(arg.config.clone(), &overwrite)?;
+    let rule = rules
+      .get_rule(&arg.rule)
+      .ok_or_else(|| anyhow::anyhow!(EC::RuleNotFound(arg.rule.clone())))?;
+    inspect(rule, &arg)
+  }
+}
+
+fn inspect(rule: &RuleConfig<SgLang>, arg: &InspectArg) -> Result<()> {
+  let src =
+    std::fs::read_to_string(&arg.target).with_context(|| EC::ReadFile(arg.target.clone()))?;
+  let root = rule.language.ast_grep(&src);
+  let plan = Plan::new(rule)?;
+  let candidates: Vec<_> = root
+    .root()
+    .dfs()
+    .filter(|node| plan.is_candidate(node))
+    .map(|node| plan.evaluate(&node))
+    .collect();
+  let matches: Vec<_> = root
+    .root()
+    .find_all(&rule.matcher)
+    .map(|nm| Excerpt::new(&nm))
+    .collect();
+  let report = InspectReport {
+    rule_id: &rule.id,
+    target: arg.target.to_string_lossy().into_owned(),
+    candidates,
+    matches,
+  };
+  let output = if arg.json {
+    serde_json::to_string_pretty(&report)? + "\n"
+  } else {
+    report.render(arg.color.should_use_color())
+  };
+  let mut stdout = std::io::stdout().lock();
+  write!(stdout, "{output}")?;
+  stdout.flush()?;
+  Ok(())
+}
+
+/// A compiled sub-rule and its own sub-rules.
+struct Step {
+  label: String,
+  /// positive sub-rules decide which nodes are candidates
+  positive: bool,
+  matcher: Rule<SgLang>,
+  children: Vec<Step>,
+}
+
+/// Sub-rules are evaluated separately, so meta variables are not shared between them.
+struct Plan<'r> {
+  rule: &'r RuleConfig<SgLang>,
+  /// the rule without constraints
+  core: Rule<SgLang>,
+  steps: Vec<Step>,
+}
+
+impl<'r> Plan<'r> {
+  fn new(rule: &'r RuleConfig<SgLang>) -> Result<Self> {
+    // the env contains both local and global utils of the rule
+    let env = rule.matcher.get_env(rule.language);
+    Ok(Self {
+      rule,
+      core: env.deserialize_rule(rule.rule.clone())?,
+      steps: plan_steps(&rule.rule, &env)?,
+    })
+  }
+
+  fn is_candidate(&self, node: &Node<StrDoc<SgLang>>) -> bool {
+    self
+      .steps
+      .iter()
+      .any(|step| step.positive && step.matcher.match_node(node.clone()).is_some())
+  }
+
+  fn evaluate(&self, node: &Node<StrDoc<SgLang>>) -> Candidate {
+    let mut trace: Vec<_> = self.steps.iter().map(|s| s.evaluate(node)).collect();
+    let matched = self.rule.matcher.match_node(node.clone()).is_some();
+    if self.rule.constraints.is_some() && self.core.match_node(node.clone()).is_some() {
+      trace.push(EvalTrace {
+        rule: "constraints".into(),
+        matched,
+        sub_rules: vec![],
+      });
+    }
+    Candidate {
+      node: Excerpt::new(node),
+      matched,
+      trace,
+    }
+  }
+}
+
+impl Step {
+  fn evaluate(&self, node: &Node<StrDoc<SgLang>>) -> EvalTrace {
+    EvalTrace {
+      rule: self.label.clone(),
+      matched: self.matcher.match_node(node.clone()).is_some(),
+      sub_rules: self.children.iter().map(|c| c.evaluate(node)).collect(),
+    }
+  }
+}
+
+macro_rules! only {
+  ($rule: expr, $field: ident) => {
+    SerializableRule {
+      $field: $rule.$field.clone(),
+      ..Default::default()
+    }
+  };
+}
+
+/// Split a rule into one step per field, in the order of the rule schema.
+fn plan_steps(rule: &SerializableRule, env: &DeserializeEnv<SgLang>) -> Result<Vec<Step>> {
+  let mut steps = vec![];
+  let mut leaf = |name: &str, value: String, single: SerializableRule, positive: bool| {
+    steps.push(Step {
+      label: format!("{name}: {value}"),
+      positive,
+      matcher: env.deserialize_rule(single)?,
+      children: vec![],
+    });
+    Ok::<_, anyhow::Error>(())
+  };
+  if rule.pattern.is_present() {
+    leaf(
+      "pattern",
+      describe(&rule.pattern),
+      only!(rule, pattern),
+      true,
+    )?;
+  }
+  if rule.kind.is_present() {
+    leaf("kind", describe(&rule.kind), only!(rule, kind), true)?;
+  }
+  if rule.regex.is_present() {
+    leaf("regex", describe(&rule.regex), only!(rule, regex), true)?;
+  }
+  if rule.nth_child.is_present() {
+    let value = describe(&rule.nth_child);
+    leaf("nthChild", value, only!(rule, nth_child), false)?;
+  }
+  if rule.inside.is_present() {
+    leaf("inside", describe(&rule.inside), only!(rule, inside), false)?;
+  }
+  if rule.has.is_present() {
+    leaf("has", describe(&rule.has), only!(rule, has), false)?;
+  }
+  if rule.precedes.is_present() {
+    let value = describe(&rule.precedes);
+    leaf("precedes", value, only!(rule, precedes), false)?;
+  }
+  if rule.follows.is_present() {
+    let value = describe(&rule.follows);
+    leaf("follows", value, only!(rule, follows), false)?;
+  }
+  let composites = [
+    ("all", only!(rule, all), rule.all.clone()),
+    ("any", only!(rule, any), rule.any.clone()),
+  ];
+  for (name, single, items) in composites {
+    let Some(items) = Option::<Vec<SerializableRule>>::from(items) else {
+      continue;
+    };
+    let children = items
+      .iter()
+      .map(|item| plan_item(item, env))
+      .collect::<Result<_>>()?;
+    steps.push(Step {
+      label: name.into(),
+      positive: true,
+      matcher: env.deserialize_rule(single)?,
+      children,
+    });
+  }
+  if let Some(not) = Option::<Box<SerializableRule>>::from(rule.not.clone()) {
+    steps.push(Step {
+      label: "not".into(),
+      positive: false,
+      matcher: env.deserialize_rule(only!(rule, not))?,
+      children: vec![plan_item(&not, env)?],
+    });
+  }
+  if rule.matches.is_present() {
+    let value = describe(&rule.matches);
+    steps.push(Step {
+      label: format!("matches: {value}"),
+      positive: true,
+      matcher: env.deserialize_rule(only!(rule, matches))?,
+      children: vec![],
+    });
+  }
+  Ok(steps)
+}
+
+/// An item of all/any/not. Items with one field are shown as that field.
+fn plan_item(rule: &SerializableRule, env: &DeserializeEnv<SgLang>) -> Result<Step> {
+  let mut children = plan_steps(rule, env)?;
+  if children.len() == 1 {
+    return Ok(children.remove(0));
+  }
+  Ok(Step {
+    label: "rule".into(),
+    positive: true,
+    matcher: env.deserialize_rule(rule.clone())?,
+    children,
+  })
+}
+
+/// Compact one line description of a rule field.
+fn describe<T: Serialize>(value: &T) -> String {
+  match serde_json::to_value(value) {
+    Ok(serde_json::Value::String(s)) => s,
+    Ok(serde_json::Value::Object(mut map)) => {
+      map.retain(|_, v| !v.is_null());
+      serde_json::Value::Object(map).to_string()
+    }
+    Ok(value) => value.to_string(),
+    Err(_) => String::new(),
+  }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct InspectReport<'r> {
+  rule_id: &'r str,
+  target: String,
+  candidates: Vec<Candidate>,
+  matches: Vec<Excerpt>,
+}
+
+#[derive(Serialize)]
+struct Candidate {
+  node: Excerpt,
+  matched: bool,
+  trace: Vec<EvalTrace>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct EvalTrace {
+  rule: String,
+  matched: bool,
+  #[serde(skip_serializing_if = "Vec::is_empty")]
+  sub_rules: Vec<EvalTrace>,
+}
+
+/// zero-based line and column
+#[derive(Serialize)]
+struct Pos {
+  line: usize,
+  column: usize,
+}
+
+#[derive(Serialize)]
+struct Excerpt {
+  start: Pos,
+  end: Pos,
+  text: String,
+}
+
+impl Excerpt {
+  fn new(node: &Node<StrDoc<SgLang>>) -> Self {
+    let (line, column) = node.start_pos();
+    let start = Pos { line, column };
+    let (line, column) = node.end_pos();
+    let end = Pos { line, column };
+    Self {
+      start,
+      end,
+      text: node.text().to_string(),
+    }
+  }
+
+  /// First line of the text, shortened for display.
+  fn short_text(&self) -> String {
+    const MAX_CHARS: usize = 40;
+    let first_line = self.text.lines().next().unwrap_or("").trim();
+    let mut short: String = first_line.chars().take(MAX_CHARS).collect();
+    if short.len() < self.text.trim().len() {
+      short.push('…');
+    }
+    short
+  }
+
+  fn location(&self) -> String {
+    let (start, end) = (&self.start, &self.end);
+    format!(
+      "{}:{}-{}:{}",
+      start.line + 1,
+      start.column + 1,
+      end.line + 1,
+      end.column + 1
+    )
+  }
+}
+
+impl InspectReport<'_> {
+  fn render(&self, colored: bool) -> String {
+    let styles = Styles::new(colored);
+    let mut out = String::new();
+    // writing to String never fails
+    let _ = writeln!(out, "Inspect rule `{}` on {}", self.rule_id, self.target);
+    if self.candidates.is_empty() {
+      out.push_str("No node matches a positive sub-rule.\n");
+    }
+    for candidate in &self.candidates {
+      let node = &candidate.node;
+      let verdict = if candidate.matched {
+        styles.pass.paint("MATCHED")
+      } else {
+        styles.fail.paint("REJECTED")
+      };
+      let location = styles.location.paint(node.location());
+      let _ = writeln!(out, "\n{location} `{}` {verdict}", node.short_text());
+      for trace in &candidate.trace {
+        trace.render(&mut out, &styles, 1);
+      }
+    }
+    if self.matches.is_empty() {
+      out.push_str("\nNo node matched.\n");
+      return out;
+    }
+    let _ = writeln!(out, "\nMatched {} node(s):", self.matches.len());
+    for excerpt in &self.matches {
+      let location = styles.location.paint(excerpt.location());
+      let _ = writeln!(out, "  {location} `{}`", excerpt.short_text());
+    }
+    out
+  }
+}
+
+impl EvalTrace {
+  fn render(&self, out: &mut String, styles: &Styles, depth: usize) {
+    let mark = if self.matched {
+      styles.pass.paint("✔")
+    } else {
+      styles.fail.paint("✘")
+    };
+    let _ = writeln!(out, "{}{mark} {}", "  ".repeat(depth), self.rule);
+    for sub in &self.sub_rules {
+      sub.render(out, styles, depth + 1);
+    }
+  }
+}
+
+struct Styles {
+  pass: Style,
+  fail: Style,
+  location: Style,
+}
+
+impl Styles {
+  fn new(colored: bool) -> Self {
+    if !colored {
+      return Self {
+        pass: Style::new(),
+        fail: Style::new(),
+        location: Style::new(),
+      };
+    }
+    Self {
+      pass: Color::Green.normal(),
+      fail: Color::Red.normal(),
+      location: Style::new().bold(),
+    }
+  }
+}
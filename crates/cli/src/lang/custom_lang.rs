@@ -1,4 +1,4 @@
-use ast_grep_dynamic::{DynamicLang, Registration};
+use ast_grep_dynamic::{DynamicLang, DynamicLangError, Registration};
 use serde::{Deserialize, Serialize};
 
 use std::collections::HashMap;
@@ -16,13 +16,15 @@ pub struct CustomLang {
 }
 
 impl CustomLang {
-  pub fn register(base: PathBuf, langs: HashMap<String, CustomLang>) {
+  pub fn register(
+    base: PathBuf,
+    langs: HashMap<String, CustomLang>,
+  ) -> Result<(), DynamicLangError> {
     let registrations = langs
       .into_iter()
       .map(|(name, custom)| to_registration(name, custom, &base))
       .collect();
-    // TODO, add error handling
-    unsafe { DynamicLang::register(registrations).expect("TODO") }
+    unsafe { DynamicLang::register(registrations) }
   }
 }
 
@@ -1,17 +1,67 @@
 use super::SgLang;
 use ignore::types::{Types, TypesBuilder};
-use std::collections::HashMap;
-use std::path::Path;
+use serde::de::{MapAccess, Visitor};
+use serde::ser::SerializeMap;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::HashSet;
+use std::fmt;
+use std::path::{Path, PathBuf};
 use std::ptr::{addr_of, addr_of_mut};
 use std::str::FromStr;
+use std::sync::Mutex;
 
 use crate::utils::ErrorContext as EC;
 use anyhow::{Context, Result};
 
 // both use vec since lang will be small
+// a file matched by globs of several languages belongs to the first one
 static mut LANG_GLOBS: Vec<(SgLang, Types)> = vec![];
+/// paths already warned to match globs of several languages, created at the first warning
+static CONFLICT_WARNED: Mutex<Option<HashSet<PathBuf>>> = Mutex::new(None);
 
-pub type LanguageGlobs = HashMap<String, Vec<String>>;
+/// Globs of languages in the order of sgconfig.yml, which decides the language
+/// of a file matched by globs of several languages.
+#[derive(Clone, Default, Debug, PartialEq)]
+pub struct LanguageGlobs(Vec<(String, Vec<String>)>);
+
+impl LanguageGlobs {
+  pub fn add(&mut self, lang: String, glob: String) {
+    match self.0.iter_mut().find(|(l, _)| *l == lang) {
+      Some((_, globs)) => globs.push(glob),
+      None => self.0.push((lang, vec![glob])),
+    }
+  }
+}
+
+impl Serialize for LanguageGlobs {
+  fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+    let mut map = serializer.serialize_map(Some(self.0.len()))?;
+    for (lang, globs) in &self.0 {
+      map.serialize_entry(lang, globs)?;
+    }
+    map.end()
+  }
+}
+
+impl<'de> Deserialize<'de> for LanguageGlobs {
+  fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+    struct GlobsVisitor;
+    impl<'de> Visitor<'de> for GlobsVisitor {
+      type Value = LanguageGlobs;
+      fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a map from language to globs")
+      }
+      fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut globs = vec![];
+        while let Some(entry) = map.next_entry()? {
+          globs.push(entry);
+        }
+        Ok(LanguageGlobs(globs))
+      }
+    }
+    deserializer.deserialize_map(GlobsVisitor)
+  }
+}
 
 pub unsafe fn register(regs: LanguageGlobs) -> Result<()> {
   debug_assert!(LANG_GLOBS.is_empty());
@@ -20,12 +70,20 @@ pub unsafe fn register(regs: LanguageGlobs) -> Result<()> {
   Ok(())
 }
 
+/// Register globs of `--lang-glob`, which take precedence over languageGlobs in sgconfig.yml.
+pub unsafe fn register_cli(regs: LanguageGlobs) -> Result<()> {
+  let cli_globs = register_impl(regs)?;
+  let config_globs = std::mem::take(&mut *addr_of_mut!(LANG_GLOBS));
+  let lang_globs = prepend_globs(cli_globs, config_globs);
+  _ = std::mem::replace(&mut *addr_of_mut!(LANG_GLOBS), lang_globs);
+  Ok(())
+}
+
 fn register_impl(regs: LanguageGlobs) -> Result<Vec<(SgLang, Types)>> {
   let mut lang_globs = vec![];
-  for (lang, globs) in regs {
+  for (lang, globs) in regs.0 {
     let lang = SgLang::from_str(&lang).with_context(|| EC::UnrecognizableLanguage(lang))?;
     // Note: we have to use lang.to_string() for normalized language name
-    // TODO: add test
     let lang_name = lang.to_string();
     let types = build_types(&lang_name, globs)?;
     lang_globs.push((lang, types));
@@ -33,6 +91,23 @@ fn register_impl(regs: LanguageGlobs) -> Result<Vec<(SgLang, Types)>> {
   Ok(lang_globs)
 }
 
+/// Languages in both lists keep the position of the first list, with globs of both.
+fn prepend_globs(
+  mut first: Vec<(SgLang, Types)>,
+  second: Vec<(SgLang, Types)>,
+) -> Vec<(SgLang, Types)> {
+  for (lang, types) in second {
+    match first.iter_mut().find(|(l, _)| *l == lang) {
+      Some((_, existing)) => {
+        let merged = merge_types([existing.clone(), types].into_iter());
+        *existing = merged;
+      }
+      None => first.push((lang, types)),
+    }
+  }
+  first
+}
+
 fn build_types(lang: &str, globs: Vec<String>) -> Result<Types> {
   let mut builder = TypesBuilder::new();
   for glob in globs {
@@ -88,13 +163,58 @@ pub fn merge_globs(lang: &SgLang, type1: Types) -> Types {
   builder.build().expect("file type must be valid")
 }
 
-pub fn from_path(p: &Path) -> Option<SgLang> {
-  for (lang, types) in unsafe { &*addr_of!(LANG_GLOBS) } {
-    if types.matched(p, false).is_whitelist() {
-      return Some(*lang);
+/// ripgrep's default file types, extended with the globs of every language.
+/// A language adds its globs to the types named by its aliases, e.g. `ts` and `typescript`.
+pub fn all_file_types() -> TypesBuilder {
+  let mut builder = TypesBuilder::new();
+  builder.add_defaults();
+  for lang in SgLang::all_langs() {
+    let globs: Vec<_> = lang
+      .file_types()
+      .definitions()
+      .iter()
+      .flat_map(|def| def.globs().to_vec())
+      .collect();
+    for name in lang.type_names() {
+      let defined: Vec<_> = builder
+        .definitions()
+        .into_iter()
+        .filter(|def| def.name() == name)
+        .flat_map(|def| def.globs().to_vec())
+        .collect();
+      for glob in globs.iter().filter(|g| !defined.contains(g)) {
+        builder.add(&name, glob).expect(&name);
+      }
     }
   }
-  None
+  builder
+}
+
+pub fn from_path(p: &Path) -> Option<SgLang> {
+  let lang_globs = unsafe { &*addr_of!(LANG_GLOBS) };
+  let mut matched = lang_globs
+    .iter()
+    .filter(|(_, types)| types.matched(p, false).is_whitelist())
+    .map(|(lang, _)| *lang);
+  let lang = matched.next()?;
+  if let Some(other) = matched.next() {
+    warn_conflict(p, lang, other);
+  }
+  Some(lang)
+}
+
+fn warn_conflict(p: &Path, lang: SgLang, other: SgLang) {
+  let mut warned = CONFLICT_WARNED.lock().expect("should not fail");
+  if !warned
+    .get_or_insert_with(HashSet::new)
+    .insert(p.to_path_buf())
+  {
+    return;
+  }
+  eprintln!(
+    "⚠️  {} matches languageGlobs of both {lang} and {other}. {lang} is used because it is listed first.",
+    p.display()
+  );
 }
 
 #[cfg(test)]
@@ -113,8 +233,15 @@ html: ['*.vue', '*.svelte']";
   #[test]
   fn test_parse_globs() {
     let globs = get_globs();
-    assert_eq!(globs["js"], &[".eslintrc"]);
-    assert_eq!(globs["html"], &["*.vue", "*.svelte"]);
+    let js = ("js".to_string(), vec![".eslintrc".to_string()]);
+    let html = ("html".to_string(), vec!["*.vue".into(), "*.svelte".into()]);
+    // config order is kept
+    assert_eq!(globs.0, [js, html]);
+    let yaml = serde_yaml::to_string(&globs).expect("should serialize");
+    assert_eq!(
+      from_str::<LanguageGlobs>(&yaml).expect("should parse"),
+      globs
+    );
   }
 
   #[test]
@@ -128,7 +255,7 @@ html: ['*.vue', '*.svelte']";
   #[test]
   fn test_invalid_language() {
     let mut globs = get_globs();
-    globs.insert("php-exp".into(), vec!["bestlang".into()]);
+    globs.add("php-exp".into(), "bestlang".into());
     let ret = register_impl(globs);
     let err = ret.expect_err("should wrong");
     assert!(matches!(
@@ -146,6 +273,38 @@ html: ['*.vue', '*.svelte']";
     assert!(rust_types.matched("a.rs", false).is_whitelist());
   }
 
+  #[test]
+  fn test_all_file_types() {
+    let mut builder = all_file_types();
+    let defs = builder.definitions();
+    let ts = defs
+      .iter()
+      .find(|d| d.name() == "ts")
+      .expect("should exist");
+    // each glob is added once
+    let count = ts.globs().iter().filter(|g| *g == "*.ts").count();
+    assert_eq!(count, 1);
+    // aliases of ast-grep languages are new types
+    assert!(defs.iter().any(|d| d.name() == "typescript"));
+    builder.select("typescript");
+    let types = builder.build().expect("should build");
+    assert!(types.matched("a.ts", false).is_whitelist());
+    assert!(types.matched("a.rs", false).is_ignore());
+  }
+
+  #[test]
+  fn test_prepend_globs() -> Result<()> {
+    let config: LanguageGlobs = from_str("html: ['*.vue']\njs: ['*.tpl']")?;
+    let cli: LanguageGlobs = from_str("ts: ['*.tpl']\nhtml: ['*.svelte']")?;
+    let merged = prepend_globs(register_impl(cli)?, register_impl(config)?);
+    let langs: Vec<_> = merged.iter().map(|(l, _)| l.to_string()).collect();
+    assert_eq!(langs, ["TypeScript", "Html", "JavaScript"]);
+    let html = &merged[1].1;
+    assert!(html.matched("a.vue", false).is_whitelist());
+    assert!(html.matched("a.svelte", false).is_whitelist());
+    Ok(())
+  }
+
   #[test]
   fn test_merge_with_globs() -> Result<()> {
     let globs = get_globs();
@@ -1,6 +1,7 @@
 mod custom_lang;
 mod injection;
 mod lang_globs;
+mod shebang;
 
 use anyhow::Result;
 use ast_grep_core::{
@@ -9,7 +10,13 @@ use ast_grep_core::{
 };
 use ast_grep_dynamic::DynamicLang;
 use ast_grep_language::{Language, SupportLang};
-use ignore::types::Types;
+use clap::builder::{PossibleValue, StringValueParser, TypedValueParser, ValueParserFactory};
+use ignore::types::{Types, TypesBuilder};
+use schemars::{
+  gen::SchemaGenerator,
+  schema::{InstanceType, Schema, SchemaObject},
+  JsonSchema,
+};
 use serde::{Deserialize, Serialize};
 
 use std::borrow::Cow;
@@ -40,8 +47,9 @@ impl SgLang {
   }
 
   // register_globs must be called after register_custom_language
-  pub fn register_custom_language(base: PathBuf, langs: HashMap<String, CustomLang>) {
-    CustomLang::register(base, langs)
+  pub fn register_custom_language(base: PathBuf, langs: HashMap<String, CustomLang>) -> Result<()> {
+    CustomLang::register(base, langs)?;
+    Ok(())
   }
 
   // TODO: add tests
@@ -53,10 +61,59 @@ impl SgLang {
     Ok(())
   }
 
+  /// Register globs of `--lang-glob` after sgconfig.yml is loaded.
+  pub fn register_cli_globs(langs: LanguageGlobs) -> Result<()> {
+    unsafe { lang_globs::register_cli(langs) }
+  }
+
+  /// The language of the path if it is decided by languageGlobs or `--lang-glob`.
+  pub fn from_lang_globs(path: &Path) -> Option<Self> {
+    lang_globs::from_path(path)
+  }
+
+  /// The language of a script by its shebang line, e.g. `#!/usr/bin/env python3`.
+  /// Files containing NUL bytes are regarded as binary and return None.
+  pub fn from_shebang(path: &Path) -> Option<Self> {
+    shebang::from_path(path)
+  }
+
   pub fn register_injections(injections: Vec<SerializableInjection>) -> Result<()> {
     unsafe { injection::register_injetables(injections) }
   }
 
+  /// File types accepted by `--type`, see [lang_globs::all_file_types].
+  pub fn all_file_types() -> TypesBuilder {
+    lang_globs::all_file_types()
+  }
+
+  /// Names of the file types of custom languages, to tell them apart in `--type-list`.
+  pub fn custom_type_names() -> Vec<String> {
+    let customs = DynamicLang::all_langs().into_iter().map(Self::Custom);
+    customs.flat_map(|lang| lang.type_names()).collect()
+  }
+
+  /// Names accepted by [FromStr]. A custom language is only accepted by its name.
+  pub fn aliases(&self) -> Vec<String> {
+    match self {
+      Builtin(b) => b.aliases().iter().map(|s| s.to_string()).collect(),
+      Custom(c) => vec![c.name().to_string()],
+    }
+  }
+
+  pub fn is_custom(&self) -> bool {
+    matches!(self, Custom(_))
+  }
+
+  /// Names of the file type of the language, which must be alphanumeric.
+  fn type_names(&self) -> Vec<String> {
+    self
+      .aliases()
+      .into_iter()
+      .map(|n| n.to_lowercase())
+      .filter(|n: &String| n != "all" && n.chars().all(char::is_alphanumeric))
+      .collect()
+  }
+
   pub fn all_langs() -> Vec<Self> {
     let builtin = SupportLang::all_langs().iter().copied().map(Self::Builtin);
     let customs = DynamicLang::all_langs().into_iter().map(Self::Custom);
@@ -133,6 +190,72 @@ impl FromStr for SgLang {
   }
 }
 
+/// Parse [SgLang] in command line arguments.
+/// Builtin language aliases are listed as possible values for shell completion,
+/// but custom languages are accepted as well.
+#[derive(Clone)]
+pub struct SgLangParser;
+
+impl TypedValueParser for SgLangParser {
+  type Value = SgLang;
+
+  fn parse_ref(
+    &self,
+    cmd: &clap::Command,
+    arg: Option<&clap::Arg>,
+    value: &std::ffi::OsStr,
+  ) -> Result<Self::Value, clap::Error> {
+    StringValueParser::new()
+      .try_map(|s| SgLang::from_str(&s))
+      .parse_ref(cmd, arg, value)
+  }
+
+  fn possible_values(&self) -> Option<Box<dyn Iterator<Item = PossibleValue> + '_>> {
+    let aliases = SupportLang::all_langs().iter().flat_map(|l| l.aliases());
+    Some(Box::new(aliases.map(|alias| PossibleValue::new(*alias))))
+  }
+}
+
+impl ValueParserFactory for SgLang {
+  type Parser = SgLangParser;
+  fn value_parser() -> Self::Parser {
+    SgLangParser
+  }
+}
+
+/// Names of builtin and registered custom languages accepted in rule files.
+/// Custom languages must be registered before the schema is generated.
+impl JsonSchema for SgLang {
+  fn schema_name() -> String {
+    String::from("Language")
+  }
+  fn schema_id() -> Cow<'static, str> {
+    Cow::Borrowed("Language")
+  }
+  fn json_schema(_gen: &mut SchemaGenerator) -> Schema {
+    let mut names = vec![];
+    for lang in SupportLang::all_langs() {
+      names.push(lang.to_string());
+      names.extend(lang.aliases().iter().map(|a| a.to_string()));
+    }
+    let mut customs: Vec<_> = DynamicLang::all_langs()
+      .into_iter()
+      .map(|c| c.name().to_string())
+      .collect();
+    // registration order of custom languages is not stable
+    customs.sort();
+    names.extend(customs);
+    let mut seen = std::collections::HashSet::new();
+    names.retain(|name| seen.insert(name.clone()));
+    SchemaObject {
+      instance_type: Some(InstanceType::String.into()),
+      enum_values: Some(names.into_iter().map(Into::into).collect()),
+      ..Default::default()
+    }
+    .into()
+  }
+}
+
 impl From<SupportLang> for SgLang {
   fn from(value: SupportLang) -> Self {
     Self::Builtin(value)
@@ -185,6 +308,13 @@ impl Language for SgLang {
     }
   }
 
+  fn comment_delimiters(&self) -> (&'static str, &'static str) {
+    match self {
+      Builtin(b) => b.comment_delimiters(),
+      Custom(c) => c.comment_delimiters(),
+    }
+  }
+
   fn injectable_languages(&self) -> Option<&'static [&'static str]> {
     injection::injectable_languages(*self)
   }
@@ -0,0 +1,94 @@
+//! Infer the language of files without extension by their shebang line,
+//! e.g. `#!/usr/bin/env python3` or `#!/bin/bash`.
+use super::SgLang;
+use ast_grep_language::SupportLang;
+
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+/// Only the head of a file is read, a longer shebang line is not recognized.
+const SHEBANG_LIMIT: u64 = 256;
+
+pub fn from_path(path: &Path) -> Option<SgLang> {
+  let mut head = Vec::with_capacity(SHEBANG_LIMIT as usize);
+  let file = File::open(path).ok()?;
+  file.take(SHEBANG_LIMIT).read_to_end(&mut head).ok()?;
+  // binary files are not scripts
+  if head.contains(&0) {
+    return None;
+  }
+  from_head(&head).map(SgLang::Builtin)
+}
+
+fn from_head(head: &[u8]) -> Option<SupportLang> {
+  let head = head.strip_prefix(b"#!")?;
+  let line = head.split(|b| *b == b'\n').next()?;
+  let line = std::str::from_utf8(line).ok()?;
+  let mut words = line.split_whitespace();
+  let mut interpreter = file_name(words.next()?);
+  if interpreter == "env" {
+    // skip options and variables of env, like `env -S` or `env FOO=1`
+    interpreter = words.find(|w| !w.starts_with('-') && !w.contains('='))?;
+    interpreter = file_name(interpreter);
+  }
+  from_interpreter(interpreter)
+}
+
+fn file_name(path: &str) -> &str {
+  path.rsplit('/').next().unwrap_or(path)
+}
+
+fn from_interpreter(interpreter: &str) -> Option<SupportLang> {
+  use SupportLang::*;
+  // python3.11 and lua5.4 are python and lua
+  let name = interpreter.trim_end_matches(|c: char| c.is_ascii_digit() || c == '.');
+  let lang = match name {
+    "sh" | "bash" | "zsh" | "ksh" | "dash" | "ash" => Bash,
+    "python" | "pypy" => Python,
+    "node" | "nodejs" => JavaScript,
+    "ts-node" => TypeScript,
+    "ruby" => Ruby,
+    "php" => Php,
+    "lua" | "luajit" => Lua,
+    "elixir" => Elixir,
+    "swift" => Swift,
+    "scala" => Scala,
+    "runghc" | "runhaskell" => Haskell,
+    _ => return None,
+  };
+  Some(lang)
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  fn detect(head: &str) -> Option<SupportLang> {
+    from_head(head.as_bytes())
+  }
+
+  #[test]
+  fn test_shebang() {
+    assert_eq!(detect("#!/bin/bash\necho 1"), Some(SupportLang::Bash));
+    assert_eq!(detect("#!/bin/sh -e\n"), Some(SupportLang::Bash));
+    assert_eq!(detect("#! /usr/bin/python3.11"), Some(SupportLang::Python));
+    assert_eq!(
+      detect("#!/usr/bin/env node\n"),
+      Some(SupportLang::JavaScript)
+    );
+    assert_eq!(
+      detect("#!/usr/bin/env -S FOO=1 ruby -w\n"),
+      Some(SupportLang::Ruby)
+    );
+    assert_eq!(detect("#!/usr/bin/env lua5.4"), Some(SupportLang::Lua));
+  }
+
+  #[test]
+  fn test_no_shebang() {
+    assert_eq!(detect("echo 1\n#!/bin/bash"), None);
+    assert_eq!(detect("#!/usr/bin/env\n"), None);
+    assert_eq!(detect("#!/usr/bin/perl"), None);
+    assert_eq!(detect(""), None);
+  }
+}
@@ -0,0 +1,162 @@
+//! List languages of ast-grep, see `sg languages`.
+//! Names, aliases and globs are read from [SgLang], which is used in matching,
+//! so custom languages and languageGlobs in sgconfig.yml are included.
+use crate::config::register_custom_language;
+use crate::lang::SgLang;
+
+use anyhow::Result;
+use ast_grep_language::Language;
+use clap::Parser;
+use serde::Serialize;
+
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+#[derive(Parser)]
+pub struct LanguagesArg {
+  /// Print the languages as a JSON array for tools, like editor extensions.
+  #[clap(long)]
+  json: bool,
+
+  /// Path to ast-grep root config, default is sgconfig.yml.
+  #[clap(short, long, value_name = "CONFIG_FILE")]
+  config: Option<PathBuf>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct LanguageInfo {
+  name: String,
+  /// names accepted by `--lang` and `language` of rules
+  aliases: Vec<String>,
+  /// globs of files inferred as the language, including languageGlobs
+  globs: Vec<String>,
+  meta_var_char: char,
+  /// the char replacing meta_var_char in patterns, if the language needs one
+  #[serde(skip_serializing_if = "Option::is_none")]
+  expando_char: Option<char>,
+  custom: bool,
+}
+
+impl LanguageInfo {
+  fn new(lang: SgLang) -> Self {
+    let globs = lang
+      .file_types()
+      .definitions()
+      .iter()
+      .flat_map(|def| def.globs().to_vec())
+      .collect();
+    let meta_var_char = lang.meta_var_char();
+    let expando_char = lang.expando_char();
+    Self {
+      name: lang.to_string(),
+      aliases: lang.aliases(),
+      globs,
+      meta_var_char,
+      expando_char: (expando_char != meta_var_char).then_some(expando_char),
+      custom: lang.is_custom(),
+    }
+  }
+}
+
+pub fn run_languages(arg: LanguagesArg) -> Result<()> {
+  register_custom_language(arg.config.clone())?;
+  run_languages_impl(arg, &mut io::stdout().lock())
+}
+
+fn run_languages_impl(arg: LanguagesArg, output: &mut impl Write) -> Result<()> {
+  let infos: Vec<_> = SgLang::all_langs()
+    .into_iter()
+    .map(LanguageInfo::new)
+    .collect();
+  if arg.json {
+    serde_json::to_writer_pretty(&mut *output, &infos)?;
+    writeln!(output)?;
+    return Ok(());
+  }
+  let rows: Vec<[String; 4]> = infos
+    .into_iter()
+    .map(|info| {
+      let name = if info.custom {
+        format!("{} (custom)", info.name)
+      } else {
+        info.name
+      };
+      let expando = info.expando_char.map(String::from).unwrap_or_default();
+      [
+        name,
+        info.aliases.join(", "),
+        expando,
+        info.globs.join(", "),
+      ]
+    })
+    .collect();
+  let header = ["Name", "Aliases", "Expando", "Globs"].map(String::from);
+  let widths: Vec<_> = (0..3)
+    .map(|i| {
+      let cells = rows.iter().chain([&header]);
+      cells.map(|r| r[i].chars().count()).max().unwrap_or(0)
+    })
+    .collect();
+  for [name, aliases, expando, globs] in std::iter::once(header).chain(rows) {
+    writeln!(
+      output,
+      "{name:<w0$}  {aliases:<w1$}  {expando:<w2$}  {globs}",
+      w0 = widths[0],
+      w1 = widths[1],
+      w2 = widths[2],
+    )?;
+  }
+  Ok(())
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  fn list(json: bool) -> String {
+    let arg = LanguagesArg { json, config: None };
+    let mut output = vec![];
+    run_languages_impl(arg, &mut output).expect("should list");
+    String::from_utf8(output).expect("should be utf8")
+  }
+
+  #[test]
+  fn test_list_languages() {
+    let table = list(false);
+    let mut lines = table.lines();
+    let header = lines.next().expect("should have header");
+    assert!(header.starts_with("Name"));
+    let rust = lines
+      .find(|l| l.starts_with("Rust "))
+      .expect("should list rust");
+    assert!(rust.contains("rs, rust"));
+    assert!(rust.contains("*.rs"));
+    // expando column starts at the same position in every line
+    let expando = header.find("Expando").expect("should have expando");
+    let python = table
+      .lines()
+      .find(|l| l.starts_with("Python "))
+      .expect("should list python");
+    assert!(python[expando..].starts_with('µ'));
+  }
+
+  #[test]
+  fn test_list_languages_json() {
+    let json = list(true);
+    let infos: Vec<serde_json::Value> = serde_json::from_str(&json).expect("should parse");
+    let bash = infos
+      .iter()
+      .find(|l| l["name"] == "Bash")
+      .expect("should list bash");
+    assert_eq!(bash["aliases"], serde_json::json!(["bash"]));
+    assert_eq!(bash["metaVarChar"], "$");
+    assert!(bash.get("expandoChar").is_none());
+    assert_eq!(bash["custom"], false);
+    let python = infos
+      .iter()
+      .find(|l| l["name"] == "Python")
+      .expect("should list python");
+    assert_eq!(python["expandoChar"], "µ");
+  }
+}
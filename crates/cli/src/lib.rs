@@ -1,6 +1,10 @@
 mod completions;
 mod config;
+mod docs;
+mod dump_ast;
+mod inspect;
 mod lang;
+mod languages;
 mod lsp;
 mod new;
 mod print;
@@ -13,8 +17,13 @@ use anyhow::Result;
 use clap::{Parser, Subcommand};
 
 use completions::{run_shell_completion, CompletionsArg};
+use docs::{run_docs, DocsArg};
+use dump_ast::{run_dump_ast, DumpAstArg};
+use inspect::{run_inspect, InspectArg};
+use languages::{run_languages, LanguagesArg};
 use lsp::{run_language_server, LspArg};
 use new::{run_create_new, NewArg};
+use print::ColorArg;
 use run::{register_custom_language_if_is_run, run_with_pattern, RunArg};
 use scan::{run_with_config, ScanArg};
 use utils::exit_with_error;
@@ -44,9 +53,9 @@ struct App {
 #[derive(Subcommand)]
 enum Commands {
   /// Run one time search or rewrite in command line. (default command)
-  Run(RunArg),
+  Run(Box<RunArg>),
   /// Scan and rewrite code by configuration.
-  Scan(ScanArg),
+  Scan(Box<ScanArg>),
   /// Test ast-grep rules.
   Test(TestArg),
   /// Create new ast-grep project or items like rules/tests.
@@ -55,13 +64,53 @@ enum Commands {
   Lsp(LspArg),
   /// Generate shell completion script.
   Completions(CompletionsArg),
-  /// Generate rule docs for current configuration. (Not Implemented Yet)
-  Docs,
+  /// Generate rule docs for current configuration.
+  Docs(DocsArg),
+  /// Print the tree-sitter AST of a file to look up node kinds and fields.
+  DumpAst(DumpAstArg),
+  /// Explain why a rule does or does not match nodes in a file.
+  Inspect(InspectArg),
+  /// List supported languages with their aliases and file globs.
+  Languages(LanguagesArg),
+}
+
+impl Commands {
+  /// The `--color` of the command, errors of the command are colored by it too.
+  fn color(&self) -> ColorArg {
+    match self {
+      Commands::Run(arg) => arg.color(),
+      Commands::Scan(arg) => arg.color(),
+      Commands::Test(arg) => arg.color(),
+      Commands::DumpAst(arg) => arg.color(),
+      Commands::Inspect(arg) => arg.color(),
+      _ => ColorArg::Auto,
+    }
+  }
+
+  fn execute(self) -> Result<()> {
+    match self {
+      Commands::Run(arg) => run_with_pattern(*arg),
+      Commands::Scan(arg) => run_with_config(*arg),
+      Commands::Test(arg) => run_test_rule(arg),
+      Commands::New(arg) => run_create_new(arg),
+      Commands::Lsp(arg) => run_language_server(arg),
+      Commands::Completions(arg) => run_shell_completion::<App>(arg),
+      Commands::Docs(arg) => run_docs(arg),
+      Commands::DumpAst(arg) => run_dump_ast(arg),
+      Commands::Inspect(arg) => run_inspect(arg),
+      Commands::Languages(arg) => run_languages(arg),
+    }
+  }
 }
 
 pub fn execute_main() -> Result<()> {
-  match main_with_args(std::env::args()) {
-    Err(error) => exit_with_error(error),
+  let command = match parse_command(std::env::args().collect()) {
+    Ok(command) => command,
+    Err(error) => return exit_with_error(error, ColorArg::Auto),
+  };
+  let color = command.color();
+  match command.execute() {
+    Err(error) => exit_with_error(error, color),
     ok => ok,
   }
 }
@@ -79,25 +128,19 @@ fn try_default_run(args: &[String]) -> Result<Option<RunArg>> {
   }
 }
 
-// this wrapper function is for testing
-pub fn main_with_args(args: impl Iterator<Item = String>) -> Result<()> {
-  let args: Vec<_> = args.collect();
+fn parse_command(args: Vec<String>) -> Result<Commands> {
   register_custom_language_if_is_run(&args)?;
   if let Some(arg) = try_default_run(&args)? {
-    return run_with_pattern(arg);
+    return Ok(Commands::Run(Box::new(arg)));
   }
-
   let app = App::try_parse_from(args)?;
   // TODO: add test for app parse
-  match app.command {
-    Commands::Run(arg) => run_with_pattern(arg),
-    Commands::Scan(arg) => run_with_config(arg),
-    Commands::Test(arg) => run_test_rule(arg),
-    Commands::New(arg) => run_create_new(arg),
-    Commands::Lsp(arg) => run_language_server(arg),
-    Commands::Completions(arg) => run_shell_completion::<App>(arg),
-    Commands::Docs => todo!("todo, generate rule docs based on current config"),
-  }
+  Ok(app.command)
+}
+
+// this wrapper function is for testing
+pub fn main_with_args(args: impl Iterator<Item = String>) -> Result<()> {
+  parse_command(args.collect())?.execute()
 }
 
 #[cfg(test)]
@@ -181,11 +224,18 @@ mod test_cli {
     ok("run -p test --selector identifier"); // pattern + selector
     ok("run -p test --selector identifier -l js");
     ok("run -p test --follow");
+    ok("run -p test --no-ignore src/"); // path after --no-ignore is not its value
+    ok("run -p test --no-ignore=dot --no-ignore=hidden");
+    error("run -p test --no-ignore=unknown");
     ok("run -p test --globs '*.js'");
     ok("run -p test --globs '*.{js, ts}'");
     ok("run -p test --globs '*.js' --globs '*.ts'");
     ok("run -p fubuki -j8");
     ok("run -p test --threads 12");
+    ok("run -p test --max-file-size 500K");
+    ok("run -p test --max-file-size 1024");
+    ok("run -p test --skip-minified --minified-line-threshold 300");
+    ok("run --type-list"); // pattern is not required
     error("run test");
     error("run --debug-query test"); // missing lang
     error("run -r Test dir");
@@ -197,6 +247,7 @@ mod test_cli {
     error("run -p test -l rs --debug-query=not");
     error("run -p test --selector");
     error("run -p test --threads");
+    error("run -p test --max-file-size 2X");
   }
 
   #[test]
@@ -218,6 +269,19 @@ mod test_cli {
     ok("scan --globs '*.js' --globs '*.ts'");
     ok("scan -j 12");
     ok("scan --threads 12");
+    ok("scan --no-prefilter");
+    ok("scan --max-file-size 2M");
+    ok("scan --skip-minified");
+    ok("scan --only-severity error");
+    ok("scan --only-severity error --only-severity warning");
+    ok("scan --warning=some-rule --only-severity warning");
+    ok("scan --exit-code-for error=2 --exit-code-for warning=1");
+    ok("scan --diff");
+    ok("scan --diff --color always");
+    ok("scan --group-by rule");
+    ok("scan --no-summary");
+    ok("scan --strict-rules");
+    ok("scan --group-by file --json");
     error("scan -i --json dir"); // conflict
     error("scan --report-style rich --json dir"); // conflict
     error("scan -r test.yml --inline-rules '{}'"); // conflict
@@ -228,6 +292,24 @@ mod test_cli {
     error("scan --json= not-pretty"); // wrong json flag
     error("scan -j");
     error("scan --threads");
+    error("scan --only-severity off");
+    error("scan --exit-code-for error=0");
+    error("scan --exit-code-for error=256");
+    error("scan --exit-code-for off=1");
+    error("scan --diff -i");
+    error("scan --diff -U");
+    error("scan --diff --json");
+    error("scan --group-by severity");
+    ok("scan --group-by rule -i");
+    ok("scan --stdin --stdin-filepath a.ts --print-fixed");
+    error("scan --stdin-filepath a.ts");
+    error("scan --print-fixed");
+    error("scan --stdin --print-fixed --json");
+    ok("scan --check-rules -r rule.yml");
+    error("scan --check-rules -i");
+    ok("scan --allow-duplicate-rules last-wins");
+    error("scan --allow-duplicate-rules first-wins");
+    ok("scan --no-strict-yaml");
   }
 
   #[test]
@@ -237,7 +319,101 @@ mod test_cli {
     ok("test --skip-snapshot-tests");
     ok("test -U");
     ok("test --update-all");
+    ok("test --update-snapshots");
     error("test --update-all --skip-snapshot-tests");
+    ok("test --interactive");
+    ok("test -f test-rule --case Some");
+    error("test -f (");
+    ok("test --coverage --json");
+    ok("test --include-off");
+    ok("test --color never");
+    ok("test --allow-unused-tests");
+    error("test --color rainbow");
+    ok("test --watch -U");
+    error("test --watch --json");
+    ok("test test-rule other-rule -U");
+    error("test test-rule -f test");
+    ok("test --prune-snapshots -U");
+    error("test --prune-snapshots --list-stale-snapshots");
+    error("test --list-stale-snapshots -f test-rule");
+    ok("test --json=stream");
+    error("test --json=yaml");
+    ok("test --reporter junit");
+    ok("test --reporter junit=out.xml");
+    error("test --reporter tap");
+    error("test --reporter junit --json");
+    ok("test --error-on-uncovered");
+    ok("test -j 4");
+    error("test --threads many");
+    error("test --coverage -f test-rule");
+    error("test --json -i");
+    error("test -i -U");
+    error("test -i --skip-snapshot-tests");
+  }
+
+  #[test]
+  fn test_new() {
+    ok("new");
+    ok("new project -y");
+    ok("new rule my-rule -l rs --force");
+    ok("new test my-rule --force");
+    ok("new util my-util --lang ts -y");
+    error("new rule my-rule extra");
+  }
+
+  #[test]
+  fn test_docs() {
+    ok("docs");
+    ok("docs -c sgconfig.yml");
+    ok("docs --format json");
+    ok("docs --format json --error=no-console");
+    ok("docs --example-lines 5");
+    ok("docs --schema");
+    ok("docs --schema --output-file rule.json");
+    error("docs --format html");
+    error("docs --schema --format json");
+    error("docs rules");
+  }
+
+  #[test]
+  fn test_dump_ast() {
+    ok("dump-ast test.ts");
+    ok("dump-ast --stdin -l ts");
+    ok("dump-ast test.ts --format json --kind-only");
+    ok("dump-ast test.ts --lines 3:5");
+    ok("dump-ast test.ts --bytes 10 --color never");
+    error("dump-ast");
+    error("dump-ast --stdin");
+    error("dump-ast test.ts --stdin -l ts");
+    error("dump-ast test.ts --lines 5:3");
+    error("dump-ast test.ts --lines 3 --bytes 10");
+  }
+
+  #[test]
+  fn test_inspect() {
+    ok("inspect --rule rule.yml --target test.ts");
+    ok("inspect -r no-console -t test.ts -c sgconfig.yml --json");
+    error("inspect --rule rule.yml");
+    error("inspect --target test.ts");
+  }
+
+  #[test]
+  fn test_languages() {
+    ok("languages");
+    ok("languages --json");
+    ok("languages -c sgconfig.yml");
+    error("languages --json pretty");
+  }
+
+  #[test]
+  fn test_lsp() {
+    ok("lsp");
+    ok("lsp --port 9257 --client-process-id 42");
+    ok("lsp --socket /tmp/sg.sock -c sgconfig.yml");
+    error("lsp --port 9257 --socket /tmp/sg.sock");
+    error("lsp --port 70000");
+    error("lsp --client-process-id 0");
+    error("lsp --client-process-id 4294967295");
   }
 
   #[test]
@@ -245,6 +421,8 @@ mod test_cli {
     ok("completions");
     ok("completions zsh");
     ok("completions fish");
+    ok("completions powershell");
+    ok("completions elvish");
     error("completions not-shell");
     error("completions --shell fish");
   }
@@ -1,15 +1,42 @@
-use crate::config::{find_config_path_with_default, find_rules, register_custom_language};
-use crate::utils::ErrorContext as EC;
+use crate::config::{
+  check_config_yaml, check_rule_yaml, find_config_path_with_default, load_rules_leniently,
+  register_custom_language,
+};
+use crate::lang::SgLang;
+use crate::utils::{ErrorContext as EC, NoIgnore, RuleOverwrite};
 use anyhow::{Context, Result};
-use ast_grep_lsp::{Backend, LspService, Server};
+use ast_grep_config::{GlobalRules, RuleCollection};
+use ast_grep_lsp::{Backend, ClientSocket, LspService, ProjectRules, RuleChecker, Server};
 use clap::Args;
-use std::path::PathBuf;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::TcpListener;
+
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
 
 #[derive(Args)]
 pub struct LspArg {
   /// Path to ast-grep root config, default is sgconfig.yml.
+  /// It overrides `configPath` in the client's initializationOptions.
   #[clap(short, long, value_name = "CONFIG_FILE")]
   config: Option<PathBuf>,
+
+  /// Listen on the TCP port of localhost instead of using stdio.
+  #[clap(long, value_name = "PORT", conflicts_with = "socket")]
+  port: Option<u16>,
+
+  /// Listen on the Unix domain socket path (named pipe on Windows) instead of using stdio.
+  #[clap(long, value_name = "PATH")]
+  socket: Option<PathBuf>,
+
+  /// Exit the language server when the client process with the PID exits.
+  #[clap(
+    long,
+    value_name = "PID",
+    value_parser = clap::value_parser!(u32).range(1..=i32::MAX as i64),
+  )]
+  client_process_id: Option<u32>,
 }
 
 fn find_config_base(config: Option<PathBuf>) -> Result<PathBuf> {
@@ -18,26 +45,230 @@ fn find_config_base(config: Option<PathBuf>) -> Result<PathBuf> {
   Ok(config_path)
 }
 
+type Service = (LspService<Backend<SgLang>>, ClientSocket);
+
+fn error_chain(e: anyhow::Error) -> String {
+  // convert anyhow::Error to String with chain of causes
+  e.chain()
+    .map(|e| e.to_string())
+    .collect::<Vec<_>>()
+    .join(". ")
+}
+
+/// Called again when rule files change. Custom languages are registered only once,
+/// because languages in use cannot be unloaded. Changing them requires a restart.
+/// A failed registration is tried again at the next load.
+fn load_project(config: Option<PathBuf>) -> Result<ProjectRules<SgLang>> {
+  static REGISTERED: std::sync::Mutex<bool> = std::sync::Mutex::new(false);
+  {
+    let mut registered = REGISTERED.lock().expect("should not fail");
+    if !*registered {
+      register_custom_language(config.clone())?;
+      *registered = true;
+    }
+  }
+  let base = find_config_base(config.clone())?;
+  let loaded = load_rules_leniently(config, &RuleOverwrite::default())?;
+  let rule_paths = loaded
+    .rules
+    .iter()
+    .map(|(path, rule)| (rule.id.clone(), path.clone()))
+    .collect();
+  let rule_checker = rule_checker(loaded.config_path, loaded.rule_dirs, loaded.global_rules);
+  let rules = loaded.rules.into_iter().map(|(_, rule)| rule).collect();
+  let rules = RuleCollection::try_new(rules).context(EC::GlobPattern)?;
+  let broken_files = loaded
+    .failures
+    .into_iter()
+    .map(|(path, e)| (path, error_chain(e)))
+    .collect();
+  Ok(ProjectRules {
+    base,
+    rules,
+    broken_files,
+    rule_paths,
+    rule_checker: Some(rule_checker),
+  })
+}
+
+/// Check the project configuration and YAML files in rule directories like `sg scan --check-rules`.
+fn rule_checker(
+  config_path: PathBuf,
+  rule_dirs: Vec<PathBuf>,
+  global_rules: GlobalRules<SgLang>,
+) -> RuleChecker {
+  // document paths from the client are absolute
+  let absolute = |path: PathBuf| match std::env::current_dir() {
+    Ok(dir) => dir.join(path),
+    Err(_) => path,
+  };
+  let config_path = absolute(config_path);
+  let rule_dirs: Vec<_> = rule_dirs.into_iter().map(absolute).collect();
+  Box::new(move |path: &Path, yaml: &str| {
+    if path == config_path {
+      return Some(check_config_yaml(yaml));
+    }
+    let is_yaml = path
+      .extension()
+      .map_or(false, |ext| ext == "yml" || ext == "yaml");
+    let in_rule_dirs = rule_dirs.iter().any(|dir| path.starts_with(dir));
+    (is_yaml && in_rule_dirs).then(|| check_rule_yaml(yaml, &global_rules))
+  })
+}
+
+/// List files in the workspace folders with the same ignore rules as `sg scan`.
+/// The walk stops once the scan is cancelled.
+fn walk_workspace(folders: &[PathBuf], cancelled: &AtomicBool) -> Vec<PathBuf> {
+  if folders.is_empty() {
+    return vec![];
+  }
+  NoIgnore::default()
+    .walk(folders)
+    .build()
+    .take_while(|_| !cancelled.load(Ordering::Relaxed))
+    .filter_map(|entry| entry.ok())
+    .filter(|entry| entry.file_type().map_or(false, |t| t.is_file()))
+    .map(|entry| entry.into_path())
+    .collect()
+}
+
+fn build_service(arg: &LspArg) -> Service {
+  let config = arg.config.clone();
+  let builder = LspService::build(|client| {
+    let loader = Box::new(|config| load_project(config).map_err(error_chain));
+    let is_ignored = |base: &Path, path: &Path| NoIgnore::default().is_ignored_under(base, path);
+    Backend::with_loader(client, config, loader)
+      .with_file_walker(Box::new(walk_workspace))
+      .with_ignore_filter(Box::new(is_ignored))
+  });
+  Backend::register_custom_methods(builder).finish()
+}
+
+/// Serve the language server over any async read/write pair.
+async fn serve<I, O>(input: I, output: O, (service, socket): Service) -> Result<()>
+where
+  I: AsyncRead + Unpin,
+  O: AsyncWrite,
+{
+  Server::new(input, output, socket).serve(service).await;
+  Ok(())
+}
+
+fn listen_error(addr: String) -> impl FnOnce(std::io::Error) -> anyhow::Error {
+  move |e| {
+    let kind = e.kind();
+    anyhow::Error::from(e).context(EC::ListenLanguageServer(addr, kind))
+  }
+}
+
+async fn serve_tcp(port: u16, service: Service) -> Result<()> {
+  let addr = format!("127.0.0.1:{port}");
+  let listener = TcpListener::bind(&addr)
+    .await
+    .map_err(listen_error(addr.clone()))?;
+  let local_addr = listener.local_addr()?;
+  eprintln!("Language server is listening on {local_addr}");
+  let (stream, _) = listener.accept().await.map_err(listen_error(addr))?;
+  let (input, output) = tokio::io::split(stream);
+  serve(input, output, service).await
+}
+
+#[cfg(unix)]
+async fn serve_socket(path: &std::path::Path, service: Service) -> Result<()> {
+  let addr = path.display().to_string();
+  let listener = tokio::net::UnixListener::bind(path).map_err(listen_error(addr.clone()))?;
+  eprintln!("Language server is listening on {addr}");
+  let accepted = listener.accept().await.map_err(listen_error(addr));
+  let result = match accepted {
+    Ok((stream, _)) => {
+      let (input, output) = tokio::io::split(stream);
+      serve(input, output, service).await
+    }
+    Err(e) => Err(e),
+  };
+  // the socket file is created by bind and is not removed automatically
+  let _ = std::fs::remove_file(path);
+  result
+}
+
+#[cfg(windows)]
+async fn serve_socket(path: &std::path::Path, service: Service) -> Result<()> {
+  use tokio::net::windows::named_pipe::ServerOptions;
+  let addr = path.display().to_string();
+  let server = ServerOptions::new()
+    .first_pipe_instance(true)
+    .create(path)
+    .map_err(listen_error(addr.clone()))?;
+  eprintln!("Language server is listening on {addr}");
+  server.connect().await.map_err(listen_error(addr))?;
+  let (input, output) = tokio::io::split(server);
+  serve(input, output, service).await
+}
+
+#[cfg(unix)]
+fn is_process_alive(pid: u32) -> bool {
+  // kill treats 0 and negative pids as process groups, which are not a client process
+  let pid = match libc::pid_t::try_from(pid) {
+    Ok(pid) if pid > 0 => pid,
+    _ => return false,
+  };
+  // signal 0 only checks the existence of the process
+  // SAFETY: kill with signal 0 does not affect the target process
+  let ret = unsafe { libc::kill(pid, 0) };
+  ret == 0 || std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+#[cfg(windows)]
+fn is_process_alive(pid: u32) -> bool {
+  let filter = format!("PID eq {pid}");
+  let output = std::process::Command::new("tasklist")
+    .args(["/FI", &filter, "/FO", "CSV", "/NH"])
+    .output();
+  // assume the client is alive if tasklist is unavailable
+  output.map_or(true, |o| {
+    tasklist_has_pid(&String::from_utf8_lossy(&o.stdout), pid)
+  })
+}
+
+/// Whether the CSV output of tasklist, like `"sg.exe","1234",..`, lists the pid in its PID column.
+/// Without tasks, tasklist prints an informational message instead.
+#[cfg(any(windows, test))]
+fn tasklist_has_pid(output: &str, pid: u32) -> bool {
+  let pid = pid.to_string();
+  output.lines().any(|line| {
+    // fields are quoted, and the image name may contain commas
+    let mut fields = line.trim().trim_matches('"').split("\",\"");
+    fields.nth(1) == Some(pid.as_str())
+  })
+}
+
+/// Exit the process when the client dies so the server is not orphaned.
+fn watch_client_process(pid: u32) {
+  const POLL_INTERVAL: Duration = Duration::from_secs(3);
+  tokio::spawn(async move {
+    loop {
+      if !is_process_alive(pid) {
+        eprintln!("Client process {pid} exited. Shutting down language server.");
+        std::process::exit(0);
+      }
+      tokio::time::sleep(POLL_INTERVAL).await;
+    }
+  });
+}
+
 async fn run_language_server_impl(arg: LspArg) -> Result<()> {
   // env_logger::init();
-  register_custom_language(arg.config.clone())?;
-  let stdin = tokio::io::stdin();
-  let stdout = tokio::io::stdout();
-  let config_base = find_config_base(arg.config.clone())?;
-  let config_result = find_rules(arg.config, None);
-  let config_result_std: std::result::Result<_, String> = config_result
-    .map_err(|e| {
-      // convert anyhow::Error to String with chain of causes
-      e.chain()
-        .map(|e| e.to_string())
-        .collect::<Vec<_>>()
-        .join(". ")
-    })
-    .map(|r| r.0);
-  let (service, socket) =
-    LspService::build(|client| Backend::new(client, config_base, config_result_std)).finish();
-  Server::new(stdin, stdout, socket).serve(service).await;
-  Ok(())
+  if let Some(pid) = arg.client_process_id {
+    watch_client_process(pid);
+  }
+  let service = build_service(&arg);
+  if let Some(port) = arg.port {
+    serve_tcp(port, service).await
+  } else if let Some(path) = &arg.socket {
+    serve_socket(path, service).await
+  } else {
+    serve(tokio::io::stdin(), tokio::io::stdout(), service).await
+  }
 }
 
 pub fn run_language_server(arg: LspArg) -> Result<()> {
@@ -55,7 +286,77 @@ mod test {
   #[test]
   #[ignore = "test lsp later"]
   fn test_lsp_start() {
-    let arg = LspArg { config: None };
+    let arg = LspArg {
+      config: None,
+      port: None,
+      socket: None,
+      client_process_id: None,
+    };
     assert!(run_language_server(arg).is_err())
   }
+
+  #[test]
+  fn test_ignored_document() {
+    let dir = tempfile::tempdir().expect("should create");
+    let root = dir.path();
+    std::fs::create_dir_all(root.join("dist")).expect("should create");
+    std::fs::write(root.join(".ignore"), "dist/").expect("should write");
+    std::fs::write(root.join("dist/a.ts"), "").expect("should write");
+    std::fs::write(root.join("a.ts"), "").expect("should write");
+    let no_ignore = NoIgnore::default();
+    assert!(no_ignore.is_ignored_under(root, &root.join("dist/a.ts")));
+    assert!(!no_ignore.is_ignored_under(root, &root.join("a.ts")));
+    // unsaved files and files outside of the root
+    assert!(!no_ignore.is_ignored_under(root, &root.join("b.ts")));
+    assert!(!no_ignore.is_ignored_under(root, Path::new("/outside/dist/a.ts")));
+  }
+
+  #[test]
+  fn test_rule_checker() {
+    let dir = tempfile::tempdir().expect("should create");
+    let root = dir.path();
+    std::fs::create_dir_all(root.join("rules")).expect("should create");
+    std::fs::create_dir_all(root.join("utils")).expect("should create");
+    let config = root.join("sgconfig.yml");
+    std::fs::write(&config, "ruleDirs: [rules]\nutilDirs: [utils]").expect("should write");
+    let util = "id: is-log\nlanguage: TypeScript\nrule: { pattern: log($A) }";
+    std::fs::write(root.join("utils/is-log.yml"), util).expect("should write");
+    let project = load_project(Some(config.clone())).expect("should load");
+    let check = project.rule_checker.expect("should check rule files");
+    let rule = root.join("rules/a.yml");
+    let valid = "id: a\nlanguage: TypeScript\nrule: { matches: is-log }";
+    assert_eq!(check(&rule, valid), Some(vec![]));
+    let undefined = "id: a\nlanguage: TypeScript\nrule:\n  matches: missing";
+    let problems = check(&rule, undefined).expect("should check");
+    assert_eq!((problems[0].0, problems[0].1), (4, 12));
+    assert!(problems[0].2.contains("Rule `missing` is not defined"));
+    let problems = check(&config, "ruleDirs: rules").expect("should check");
+    assert!(problems[0].2.contains("ruleDirs: invalid type"));
+    assert_eq!(check(&root.join("a.yml"), valid), None);
+    assert_eq!(check(&root.join("rules/a.ts"), valid), None);
+  }
+
+  #[test]
+  fn test_process_alive() {
+    assert!(is_process_alive(std::process::id()));
+  }
+
+  #[test]
+  fn test_tasklist_has_pid() {
+    let output = "\r\n\"sg.exe\",\"1234\",\"Console\",\"1\",\"10,240 K\"\r\n";
+    assert!(tasklist_has_pid(output, 1234));
+    assert!(!tasklist_has_pid(output, 123));
+    assert!(!tasklist_has_pid(output, 1));
+    let comma = "\"a,b.exe\",\"42\",\"Console\",\"1\",\"1,024 K\"";
+    assert!(tasklist_has_pid(comma, 42));
+    let none = "INFO: No tasks are running which match the specified criteria.\r\n";
+    assert!(!tasklist_has_pid(none, 1234));
+  }
+
+  #[test]
+  #[cfg(unix)]
+  fn test_invalid_process_id() {
+    assert!(!is_process_alive(0));
+    assert!(!is_process_alive(u32::MAX));
+  }
 }
@@ -11,7 +11,7 @@ use inquire::validator::ValueRequiredValidator;
 
 use std::fmt::Display;
 use std::fs::{self, File};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 #[derive(Parser)]
 pub struct NewArg {
@@ -30,16 +30,24 @@ pub struct NewArg {
   ///
   /// You need to provide all required arguments via command line if this flag is true.
   /// Please see the command description for the what arguments are required.
+  /// `new` is also non-interactive if stdin is not a terminal, e.g. in scripts.
   #[arg(short, long, global = true)]
   yes: bool,
+  /// Overwrite existing files instead of refusing to create them.
+  #[arg(long, global = true)]
+  force: bool,
   /// Create new project/items in the folder specified by this argument.
   #[arg(short, long, global = true, default_value = ".")]
   base_dir: PathBuf,
 }
 
 impl NewArg {
+  fn is_interactive(&self) -> bool {
+    !self.yes && atty::is(atty::Stream::Stdin)
+  }
+
   fn ask_dir_and_create(&self, prompt: &str, default: &str) -> Result<PathBuf> {
-    let dir = if self.yes {
+    let dir = if !self.is_interactive() {
       default.to_owned()
     } else {
       inquire::Text::new(prompt).with_default(default).prompt()?
@@ -54,14 +62,14 @@ impl NewArg {
   }
 
   fn confirm(&self, prompt: &str) -> Result<bool> {
-    if self.yes {
+    if !self.is_interactive() {
       return Ok(true);
     }
     Ok(inquire::Confirm::new(prompt).with_default(true).prompt()?)
   }
 
   fn ask_entity_type(&self) -> Result<Entity> {
-    if self.yes {
+    if !self.is_interactive() {
       self
         .entity
         .clone()
@@ -80,26 +88,43 @@ impl NewArg {
   fn choose_language(&self) -> Result<SgLang> {
     if let Some(lang) = self.lang {
       Ok(lang)
-    } else if self.yes {
+    } else if !self.is_interactive() {
       Err(anyhow::anyhow!(EC::InsufficientCLIArgument("lang")))
     } else {
       Ok(inquire::Select::new("Choose rule's language", SgLang::all_langs()).prompt()?)
     }
   }
 
-  fn ask_name(&self, entity: &'static str) -> Result<String> {
+  fn ask_name(&self, prompt: &str) -> Result<String> {
     if let Some(name) = &self.name {
       Ok(name.to_string())
-    } else if self.yes {
+    } else if !self.is_interactive() {
       Err(anyhow::anyhow!(EC::InsufficientCLIArgument("name")))
     } else {
       Ok(
-        inquire::Text::new(&format!("What is your {entity}'s name?"))
+        inquire::Text::new(prompt)
           .with_validator(ValueRequiredValidator::default())
           .prompt()?,
       )
     }
   }
+
+  /// Ask which dir to use if multiple ones are configured. The first one is used non-interactively.
+  fn select_dir(&self, prompt: &str, dirs: &[PathBuf]) -> Result<PathBuf> {
+    if dirs.len() <= 1 || !self.is_interactive() {
+      return Ok(dirs[0].clone());
+    }
+    let options = dirs.iter().map(|p| p.display()).collect();
+    let display = inquire::Select::new(prompt, options).prompt()?;
+    Ok(PathBuf::from(display.to_string()))
+  }
+
+  fn check_file_not_exist(&self, path: &Path) -> Result<()> {
+    if path.exists() && !self.force {
+      return Err(anyhow::anyhow!(EC::FileAlreadyExist(path.to_path_buf())));
+    }
+    Ok(())
+  }
 }
 
 /// The ast-grep item type to create.
@@ -162,6 +187,9 @@ type FoundConfig = (PathBuf, AstGrepConfig);
 fn run_create_entity(entity: Entity, arg: NewArg) -> Result<()> {
   // check if we are under a project dir
   if let Some(found) = read_config_from_dir(&arg.base_dir)? {
+    if entity == Entity::Project && arg.force {
+      return create_new_project(arg);
+    }
     return do_create_entity(entity, found, arg);
   }
   // check if we creating a project
@@ -177,7 +205,10 @@ fn do_create_entity(entity: Entity, found: FoundConfig, arg: NewArg) -> Result<(
   // ask user what destination to create if multiple dirs exist
   match entity {
     Entity::Rule => create_new_rule(found, arg),
-    Entity::Test => create_new_test(found.1.test_configs, arg.name),
+    Entity::Test => {
+      let name = arg.ask_name("What is the rule's id that you want to test?")?;
+      create_new_test(&found, name, &arg)
+    }
     Entity::Util => create_new_util(found, arg),
     Entity::Project => Err(anyhow::anyhow!(EC::ProjectAlreadyExist)),
   }
@@ -219,8 +250,10 @@ fn create_new_project(arg: NewArg) -> Result<()> {
     custom_languages: None,      // advanced feature, skip now
     language_globs: None,        // advanced feature, skip now
     language_injections: vec![], // advanced feature
+    output: None,
   };
   let config_path = arg.base_dir.join("sgconfig.yml");
+  arg.check_file_not_exist(&config_path)?;
   let f = File::create(config_path)?;
   serde_yaml::to_writer(f, &root_config)?;
   println!("Your new ast-grep project has been created!");
@@ -236,33 +269,31 @@ message: Add your rule message here....
 severity: error # error, warning, info, hint
 language: {lang}
 rule:
-  pattern: Your Rule Pattern here...
+  # Add your rule here, for example:
+  # pattern: console.log($ARG)
 # utils: Extract repeated rule as local utility here.
-# note: Add detailed explanation for the rule."#
+# note: Add detailed explanation for the rule.
+"#
   )
 }
 
 fn create_new_rule(found: FoundConfig, arg: NewArg) -> Result<()> {
-  let (base_dir, sg_config) = found;
-  let name = arg.ask_name("rule")?;
-  let rule_dir = if sg_config.rule_dirs.len() > 1 {
-    let dirs = sg_config.rule_dirs.iter().map(|p| p.display()).collect();
-    let display =
-      inquire::Select::new("Which rule dir do you want to save your rule?", dirs).prompt()?;
-    base_dir.join(display.to_string())
-  } else {
-    base_dir.join(&sg_config.rule_dirs[0])
-  };
+  let (base_dir, sg_config) = &found;
+  let name = arg.ask_name("What is your rule's name?")?;
+  let prompt = "Which rule dir do you want to save your rule?";
+  let rule_dir = base_dir.join(arg.select_dir(prompt, &sg_config.rule_dirs)?);
   let path = rule_dir.join(format!("{name}.yml"));
-  if path.exists() {
-    return Err(anyhow::anyhow!(EC::FileAlreadyExist(path)));
-  }
+  arg.check_file_not_exist(&path)?;
   let lang = arg.choose_language()?;
   fs::write(&path, default_rule(&name, lang))?;
   println!("Created rules at {}", path.display());
-  let need_test = arg.confirm("Do you also need to create a test for the rule?")?;
-  if need_test {
-    create_new_test(sg_config.test_configs, Some(name))?;
+  // only offer a test if test dirs are configured
+  let has_test_dir = sg_config
+    .test_configs
+    .as_ref()
+    .map_or(false, |t| !t.is_empty());
+  if has_test_dir && arg.confirm("Do you also need to create a test for the rule?")? {
+    create_new_test(&found, name, &arg)?;
   }
   Ok(())
 }
@@ -278,31 +309,18 @@ invalid:
   )
 }
 
-fn create_new_test(test_configs: Option<Vec<TestConfig>>, name: Option<String>) -> Result<()> {
-  let Some(tests) = test_configs else {
+fn create_new_test(found: &FoundConfig, name: String, arg: &NewArg) -> Result<()> {
+  let (base_dir, sg_config) = found;
+  let Some(tests) = &sg_config.test_configs else {
     return Err(anyhow::anyhow!(EC::NoTestDirConfigured));
   };
   if tests.is_empty() {
     return Err(anyhow::anyhow!(EC::NoTestDirConfigured));
   }
-  let test_dir = if tests.len() > 1 {
-    let dirs = tests.iter().map(|t| t.test_dir.display()).collect();
-    let display = inquire::Select::new("Which test dir do you want to use?", dirs).prompt()?;
-    PathBuf::from(display.to_string())
-  } else {
-    tests[0].test_dir.clone()
-  };
-  let name = if let Some(name) = name {
-    name
-  } else {
-    inquire::Text::new("What is the rule's id that you want to test?")
-      .with_validator(ValueRequiredValidator::default())
-      .prompt()?
-  };
+  let dirs: Vec<_> = tests.iter().map(|t| t.test_dir.clone()).collect();
+  let test_dir = base_dir.join(arg.select_dir("Which test dir do you want to use?", &dirs)?);
   let path = test_dir.join(format!("{name}-test.yml"));
-  if path.exists() {
-    return Err(anyhow::anyhow!(EC::FileAlreadyExist(path)));
-  }
+  arg.check_file_not_exist(&path)?;
   fs::write(&path, default_test(&name))?;
   println!("Created test at {}", path.display());
   Ok(())
@@ -326,19 +344,11 @@ fn create_new_util(found: FoundConfig, arg: NewArg) -> Result<()> {
   if utils.is_empty() {
     return Err(anyhow::anyhow!(EC::NoUtilDirConfigured));
   }
-  let util_dir = if utils.len() > 1 {
-    let dirs = utils.iter().map(|p| p.display()).collect();
-    let display =
-      inquire::Select::new("Which util dir do you want to save your rule?", dirs).prompt()?;
-    base_dir.join(display.to_string())
-  } else {
-    base_dir.join(&utils[0])
-  };
-  let name = arg.ask_name("util")?;
+  let prompt = "Which util dir do you want to save your rule?";
+  let util_dir = base_dir.join(arg.select_dir(prompt, &utils)?);
+  let name = arg.ask_name("What is your util's name?")?;
   let path = util_dir.join(format!("{name}.yml"));
-  if path.exists() {
-    return Err(anyhow::anyhow!(EC::FileAlreadyExist(path)));
-  }
+  arg.check_file_not_exist(&path)?;
   let lang = arg.choose_language()?;
   fs::write(&path, default_util(&name, lang))?;
   println!("Created util at {}", path.display());
@@ -358,6 +368,7 @@ mod test {
       name: None,
       lang: None,
       yes: true,
+      force: false,
       base_dir: tempdir.to_path_buf(),
     };
     run_create_new(arg)?;
@@ -371,6 +382,7 @@ mod test {
       name: Some("test-rule".into()),
       lang: Some(SupportLang::Rust.into()),
       yes: true,
+      force: false,
       base_dir: temp.to_path_buf(),
     };
     run_create_new(arg).unwrap();
@@ -384,6 +396,7 @@ mod test {
       name: Some("test-utils".into()),
       lang: Some(SupportLang::Rust.into()),
       yes: true,
+      force: false,
       base_dir: temp.to_path_buf(),
     };
     run_create_new(arg).unwrap();
@@ -400,6 +413,52 @@ mod test {
     Ok(())
   }
 
+  fn new_arg(entity: Entity, name: &str, temp: &Path) -> NewArg {
+    NewArg {
+      entity: Some(entity),
+      name: Some(name.into()),
+      lang: Some(SupportLang::Rust.into()),
+      yes: false,
+      force: false,
+      base_dir: temp.to_path_buf(),
+    }
+  }
+
+  #[test]
+  fn test_create_test() -> Result<()> {
+    let dir = TempDir::new()?;
+    create_project(dir.path())?;
+    // non-interactive without --yes since stdin is not a terminal in tests
+    run_create_new(new_arg(Entity::Test, "test-rule", dir.path()))?;
+    let test = fs::read_to_string(dir.path().join("rule-tests/test-rule-test.yml"))?;
+    assert!(test.starts_with("id: test-rule\n"));
+    Ok(())
+  }
+
+  #[test]
+  fn test_refuse_overwrite() -> Result<()> {
+    let dir = TempDir::new()?;
+    create_project(dir.path())?;
+    create_rule(dir.path())?;
+    let path = dir.path().join("rules/test-rule.yml");
+    fs::write(&path, "edited")?;
+    let ret = run_create_new(new_arg(Entity::Rule, "test-rule", dir.path()));
+    assert!(ret.is_err());
+    assert_eq!(fs::read_to_string(&path)?, "edited");
+    let mut arg = new_arg(Entity::Rule, "test-rule", dir.path());
+    arg.force = true;
+    run_create_new(arg)?;
+    let rule = fs::read_to_string(&path)?;
+    assert!(rule.contains("id: test-rule\n"));
+    assert!(rule.contains("rule:\n  # Add your rule here"));
+    let project = new_arg(Entity::Project, "", dir.path());
+    assert!(run_create_new(project).is_err());
+    let mut project = new_arg(Entity::Project, "", dir.path());
+    project.force = true;
+    run_create_new(project)?;
+    Ok(())
+  }
+
   #[test]
   fn test_create_util() -> Result<()> {
     let dir = TempDir::new()?;
@@ -0,0 +1,197 @@
+//! Resolve `--color` to a concrete choice for one output stream.
+//! Matches go to stdout while errors go to stderr, and only one of them may be
+//! a terminal, so colors are decided per stream. `auto` follows the informal
+//! standards of `NO_COLOR` and `CLICOLOR_FORCE`, see https://no-color.org.
+use super::{ColorArg, ColorChoice};
+
+use std::env;
+
+/// The stream that colored output is written to.
+#[derive(Clone, Copy)]
+pub enum ColorStream {
+  Stdout,
+  Stderr,
+}
+
+/// Terminal and environment variables deciding `--color auto`.
+pub struct ColorEnv {
+  /// whether the stream is a terminal
+  tty: bool,
+  term: Option<String>,
+  no_color: Option<String>,
+  clicolor_force: Option<String>,
+  /// TERM is usually unset on Windows consoles, which still support colors
+  windows: bool,
+  /// Windows console without support of ANSI escape codes
+  legacy_console: bool,
+}
+
+impl ColorEnv {
+  pub fn detect(stream: ColorStream) -> Self {
+    let tty = match stream {
+      ColorStream::Stdout => atty::is(atty::Stream::Stdout),
+      ColorStream::Stderr => atty::is(atty::Stream::Stderr),
+    };
+    Self {
+      tty,
+      term: env::var("TERM").ok(),
+      no_color: env::var("NO_COLOR").ok(),
+      clicolor_force: env::var("CLICOLOR_FORCE").ok(),
+      windows: cfg!(windows),
+      legacy_console: tty && !enable_ansi_support(),
+    }
+  }
+}
+
+#[cfg(windows)]
+fn enable_ansi_support() -> bool {
+  ansi_term::enable_ansi_support().is_ok()
+}
+
+#[cfg(not(windows))]
+fn enable_ansi_support() -> bool {
+  true
+}
+
+/// Decide colors of a stream. Colors are ANSI escape codes, the result is never `Auto`.
+///
+/// For `auto`, a non-empty `NO_COLOR` disables colors, then `CLICOLOR_FORCE`
+/// other than `0` enables colors even if the stream is piped. Otherwise colors are
+/// used for terminals, unless TERM is `dumb` or the console cannot show colors.
+pub fn resolve(color: ColorArg, env: &ColorEnv) -> ColorChoice {
+  let auto = match color {
+    ColorArg::Always => return ColorChoice::Always,
+    ColorArg::Ansi => return ColorChoice::AlwaysAnsi,
+    ColorArg::Never => return ColorChoice::Never,
+    ColorArg::Auto => env,
+  };
+  let set = |var: &Option<String>| var.as_deref().map_or(false, |v| !v.is_empty());
+  if set(&auto.no_color) {
+    return ColorChoice::Never;
+  }
+  if set(&auto.clicolor_force) && auto.clicolor_force.as_deref() != Some("0") {
+    return ColorChoice::AlwaysAnsi;
+  }
+  let term_allows_color = match auto.term.as_deref() {
+    Some("dumb") => false,
+    Some(_) => true,
+    None => auto.windows,
+  };
+  if auto.tty && term_allows_color && !auto.legacy_console {
+    ColorChoice::AlwaysAnsi
+  } else {
+    ColorChoice::Never
+  }
+}
+
+/// Returns true if we should attempt to write colored output to stdout.
+pub fn should_use_color(color: &ColorChoice) -> bool {
+  match *color {
+    ColorChoice::Always | ColorChoice::AlwaysAnsi => true,
+    ColorChoice::Never => false,
+    ColorChoice::Auto => ColorArg::Auto.resolve(ColorStream::Stdout) != ColorChoice::Never,
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  fn terminal() -> ColorEnv {
+    ColorEnv {
+      tty: true,
+      term: Some("xterm-256color".into()),
+      no_color: None,
+      clicolor_force: None,
+      windows: false,
+      legacy_console: false,
+    }
+  }
+
+  fn auto(env: ColorEnv) -> ColorChoice {
+    resolve(ColorArg::Auto, &env)
+  }
+
+  #[test]
+  fn test_auto_terminal() {
+    assert_eq!(auto(terminal()), ColorChoice::AlwaysAnsi);
+    let piped = ColorEnv {
+      tty: false,
+      ..terminal()
+    };
+    assert_eq!(auto(piped), ColorChoice::Never);
+  }
+
+  #[test]
+  fn test_term() {
+    let dumb = ColorEnv {
+      term: Some("dumb".into()),
+      ..terminal()
+    };
+    assert_eq!(auto(dumb), ColorChoice::Never);
+    let unset = ColorEnv {
+      term: None,
+      ..terminal()
+    };
+    assert_eq!(auto(unset), ColorChoice::Never);
+    // Windows consoles do not set TERM
+    let windows = ColorEnv {
+      term: None,
+      windows: true,
+      ..terminal()
+    };
+    assert_eq!(auto(windows), ColorChoice::AlwaysAnsi);
+    let legacy = ColorEnv {
+      term: None,
+      windows: true,
+      legacy_console: true,
+      ..terminal()
+    };
+    assert_eq!(auto(legacy), ColorChoice::Never);
+  }
+
+  #[test]
+  fn test_no_color() {
+    let no_color = ColorEnv {
+      no_color: Some("1".into()),
+      clicolor_force: Some("1".into()),
+      ..terminal()
+    };
+    assert_eq!(auto(no_color), ColorChoice::Never);
+    // an empty NO_COLOR is ignored
+    let empty = ColorEnv {
+      no_color: Some("".into()),
+      ..terminal()
+    };
+    assert_eq!(auto(empty), ColorChoice::AlwaysAnsi);
+  }
+
+  #[test]
+  fn test_clicolor_force() {
+    let forced = ColorEnv {
+      tty: false,
+      term: Some("dumb".into()),
+      clicolor_force: Some("1".into()),
+      ..terminal()
+    };
+    assert_eq!(auto(forced), ColorChoice::AlwaysAnsi);
+    let zero = ColorEnv {
+      tty: false,
+      clicolor_force: Some("0".into()),
+      ..terminal()
+    };
+    assert_eq!(auto(zero), ColorChoice::Never);
+  }
+
+  #[test]
+  fn test_explicit_choice_ignores_env() {
+    let env = ColorEnv {
+      tty: false,
+      no_color: Some("1".into()),
+      ..terminal()
+    };
+    assert_eq!(resolve(ColorArg::Always, &env), ColorChoice::Always);
+    assert_eq!(resolve(ColorArg::Ansi, &env), ColorChoice::AlwaysAnsi);
+    assert_eq!(resolve(ColorArg::Never, &terminal()), ColorChoice::Never);
+  }
+}
@@ -1,6 +1,7 @@
+use super::choose_color;
 use super::{Diff, Printer};
 use crate::lang::SgLang;
-use ast_grep_config::{RuleConfig, Severity};
+use ast_grep_config::{LabelStyle, RuleConfig, Severity};
 use ast_grep_core::DisplayContext;
 
 use ansi_term::{Color, Style};
@@ -8,17 +9,27 @@ use anyhow::Result;
 use clap::ValueEnum;
 use codespan_reporting::diagnostic::{self, Diagnostic, Label};
 use codespan_reporting::files::SimpleFile;
-use codespan_reporting::term::termcolor::{ColorChoice, StandardStream, WriteColor};
+use codespan_reporting::term::termcolor::{Buffer, ColorChoice, StandardStream, WriteColor};
 use codespan_reporting::term::{self, DisplayStyle};
 use similar::{ChangeTag, DiffOp, TextDiff};
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
 
 use std::borrow::Cow;
+use std::collections::BTreeMap;
 use std::fmt::Display;
 use std::io::Write;
+use std::ops::Range;
 use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Mutex;
 
+mod frame;
+mod highlight;
+mod side_by_side;
 mod test;
+mod theme;
+
+pub use theme::ColorTheme;
 
 use ast_grep_core::{NodeMatch as SgNodeMatch, StrDoc};
 type NodeMatch<'a, L> = SgNodeMatch<'a, StrDoc<L>>;
@@ -51,6 +62,66 @@ pub enum Heading {
   Never,
 }
 
+#[derive(Clone, Copy, Default, ValueEnum)]
+pub enum DiffStyle {
+  /// Print removed lines before added lines.
+  #[default]
+  Inline,
+  /// Print the original on the left and the replacement on the right.
+  /// Inline diffs are printed if the output is not a terminal or the terminal is narrow.
+  SideBySide,
+}
+
+impl DiffStyle {
+  /// The terminal width to print side by side, None if diffs should be inline.
+  fn side_by_side_width(self, screen: Option<(usize, usize)>) -> Option<usize> {
+    match (self, screen) {
+      (DiffStyle::SideBySide, Some((width, _))) if width >= side_by_side::MIN_WIDTH => Some(width),
+      _ => None,
+    }
+  }
+}
+
+/// Layout of diffs, see `print_diff`.
+pub(super) struct DiffOptions {
+  /// unchanged lines around changes
+  pub context: usize,
+  /// terminal width if diffs are printed side by side
+  pub side_by_side: Option<usize>,
+  pub tab_width: usize,
+}
+
+impl DiffOptions {
+  pub(super) fn new(context: usize, style: DiffStyle, screen: Option<(usize, usize)>) -> Self {
+    Self {
+      context,
+      side_by_side: style.side_by_side_width(screen),
+      tab_width: term::Config::default().tab_width,
+    }
+  }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum GroupBy {
+  /// Print findings file by file with the number of findings in each file.
+  File,
+  /// Print findings under the header of their rule after the scan finishes.
+  Rule,
+}
+
+/// at most this many findings are printed for one group of `--group-by`, the rest are summarized
+const MAX_GROUP_FINDINGS: usize = 100;
+/// width of the separator between files if the output is not a terminal
+const SEPARATOR_WIDTH: usize = 80;
+
+/// Buffered output of one group, either a file or a rule.
+struct Group {
+  header: String,
+  count: usize,
+  shown: usize,
+  chunks: Vec<Buffer>,
+}
+
 impl Heading {
   fn should_print(&self) -> bool {
     use Heading as H;
@@ -60,36 +131,108 @@ impl Heading {
       H::Auto => atty::is(atty::Stream::Stdout),
     }
   }
+
+  /// Decide `auto` now, before stdout is redirected to a pager.
+  pub fn resolve(self) -> Self {
+    match self {
+      Heading::Auto if self.should_print() => Heading::Always,
+      Heading::Auto => Heading::Never,
+      heading => heading,
+    }
+  }
 }
 
 pub struct ColoredPrinter<W: WriteColor + Send + Sync> {
   writer: Mutex<W>,
   config: term::Config,
   styles: PrintStyles,
+  color: ColorChoice,
+  theme: ColorTheme,
   heading: Heading,
   context: (u16, u16),
+  /// line between context blocks, None uses the default of the output mode
+  context_separator: Option<String>,
+  /// separates the path, line and column of locations
+  field_separator: String,
+  /// wrap rule notes to this width, None means no wrapping
+  note_width: Option<usize>,
+  /// terminal size to draw file separators and repeat file headings, None if not a terminal
+  screen: Option<(usize, usize)>,
+  diff_style: DiffStyle,
+  /// context lines of diffs changed in an interactive session, overriding `context`
+  diff_context: Mutex<Option<usize>>,
+  /// highlight the syntax of printed code, only if colors are used
+  highlight: bool,
+  group_by: Option<GroupBy>,
+  groups: Mutex<BTreeMap<String, Group>>,
+  /// whether a file group is printed, later files are separated from it
+  printed_file: AtomicBool,
+  /// heading of the file being printed without `--group-by` and the lines printed under it
+  file_heading: Mutex<Option<(String, usize)>>,
 }
 impl ColoredPrinter<StandardStream> {
   pub fn stdout<C: Into<ColorChoice>>(color: C) -> Self {
     let color = color.into();
-    ColoredPrinter::new(StandardStream::stdout(color)).color(color)
+    let mut printer = ColoredPrinter::new(StandardStream::stdout(color)).color(color);
+    // only wrap notes for terminal, piped output should be kept as is
+    printer.screen = terminal_screen();
+    printer.note_width = printer
+      .screen
+      .map(|(width, _)| width.saturating_sub(NOTE_INDENT));
+    printer
   }
 }
 
+/// Width and height of the terminal, None if stdout is not a terminal.
+pub(super) fn terminal_screen() -> Option<(usize, usize)> {
+  if !atty::is(atty::Stream::Stdout) {
+    return None;
+  }
+  crossterm::terminal::size()
+    .ok()
+    .map(|(width, height)| (width as usize, height as usize))
+}
+
 impl<W: WriteColor + Send + Sync> ColoredPrinter<W> {
   pub fn new(writer: W) -> Self {
     Self {
       writer: Mutex::new(writer),
       styles: PrintStyles::from(ColorChoice::Auto),
+      color: ColorChoice::Auto,
+      theme: ColorTheme::default(),
       config: term::Config::default(),
       heading: Heading::Auto,
       context: (0, 0),
+      context_separator: None,
+      field_separator: ":".into(),
+      note_width: None,
+      screen: None,
+      diff_style: DiffStyle::Inline,
+      diff_context: Mutex::new(None),
+      highlight: false,
+      group_by: None,
+      groups: Mutex::new(BTreeMap::new()),
+      printed_file: AtomicBool::new(false),
+      file_heading: Mutex::new(None),
     }
   }
 
   pub fn color<C: Into<ColorChoice>>(mut self, color: C) -> Self {
-    let color = color.into();
-    self.styles = PrintStyles::from(color);
+    self.color = color.into();
+    self.styles = PrintStyles::new(self.color, &self.theme);
+    self
+  }
+
+  /// Expand tabs in matched lines to this tab stop, default 4.
+  pub fn tab_width(mut self, tab_width: usize) -> Self {
+    self.config.tab_width = tab_width.max(1);
+    self
+  }
+
+  /// Override the default colors. The theme is ignored if colors are not used.
+  pub fn color_theme(mut self, theme: ColorTheme) -> Self {
+    self.theme = theme;
+    self.styles = PrintStyles::new(self.color, &self.theme);
     self
   }
 
@@ -113,16 +256,245 @@ impl<W: WriteColor + Send + Sync> ColoredPrinter<W> {
     self
   }
 
+  /// Override the line between context blocks, an empty separator prints no line.
+  pub fn context_separator(mut self, separator: Option<String>) -> Self {
+    self.context_separator = separator;
+    self
+  }
+
+  /// Separate the path, line and column of locations, default `:`.
+  pub fn field_separator(mut self, separator: String) -> Self {
+    self.field_separator = separator;
+    self
+  }
+
+  /// Print diffs side by side if the terminal is wide enough.
+  pub fn diff_style(mut self, style: DiffStyle) -> Self {
+    self.diff_style = style;
+    self
+  }
+
+  /// Highlight the syntax of printed code. It is ignored if colors are not used,
+  /// so colors should be chosen before.
+  pub fn highlight(mut self, highlight: bool) -> Self {
+    self.highlight = highlight && choose_color::should_use_color(&self.color);
+    self
+  }
+
+  pub fn group_by(mut self, group_by: Option<GroupBy>) -> Self {
+    self.group_by = group_by;
+    self
+  }
+
+  fn new_buffer(&self) -> Buffer {
+    let writer = self.writer.lock().expect("should not fail");
+    if writer.supports_color() {
+      Buffer::ansi()
+    } else {
+      Buffer::no_color()
+    }
+  }
+
+  /// Findings are buffered by group only if `--group-by` is used,
+  /// otherwise they are streamed as they come.
+  fn grouping(&self) -> Option<GroupBy> {
+    self.group_by
+  }
+
+  /// Rich diagnostics with heading are printed under the heading of their file,
+  /// which shows the finding count of all rules in the file.
+  fn prints_file_heading(&self) -> bool {
+    let rich = matches!(self.config.display_style, DisplayStyle::Rich);
+    self.group_by.is_none() && rich && self.heading.should_print()
+  }
+
+  /// Whether output is buffered before writing, either in a group or under a file heading.
+  fn buffered(&self) -> bool {
+    self.grouping().is_some() || self.file_heading.lock().expect("should not fail").is_some()
+  }
+
+  /// Write the buffered output of `count` findings, see [Self::buffered].
+  fn write_buffer(
+    &self,
+    path: &Path,
+    rule: &RuleConfig<SgLang>,
+    count: usize,
+    chunk: Buffer,
+  ) -> Result<()> {
+    let Some(group_by) = self.grouping() else {
+      let mut heading = self.file_heading.lock().expect("should not fail");
+      let mut writer = self.writer.lock().expect("should not fail");
+      if let Some((header, lines)) = &mut *heading {
+        self.write_lines(&mut *writer, header, lines, chunk.as_slice())?;
+      } else {
+        writer.write_all(chunk.as_slice())?;
+      }
+      return Ok(());
+    };
+    let mut groups = self.groups.lock().expect("should not fail");
+    let (key, header) = match group_by {
+      GroupBy::File => {
+        let path = path.display().to_string();
+        // files are consumed one by one, flush the previous file
+        if !groups.contains_key(&path) {
+          self.flush_groups(&mut groups)?;
+        }
+        (path.clone(), self.styles.file_path.paint(path).to_string())
+      }
+      GroupBy::Rule => (rule.id.clone(), rule_header(rule, &self.styles.rule)),
+    };
+    let group = groups.entry(key).or_insert_with(|| Group {
+      header,
+      count: 0,
+      shown: 0,
+      chunks: vec![],
+    });
+    group.count += count;
+    if group.shown < MAX_GROUP_FINDINGS {
+      group.shown += count;
+      group.chunks.push(chunk);
+    }
+    Ok(())
+  }
+
+  fn flush_groups(&self, groups: &mut BTreeMap<String, Group>) -> Result<()> {
+    let by_file = matches!(self.grouping(), Some(GroupBy::File));
+    let mut writer = self.writer.lock().expect("should not fail");
+    for group in std::mem::take(groups).into_values() {
+      let Group {
+        header,
+        count,
+        shown,
+        chunks,
+      } = group;
+      let noun = if count == 1 { "finding" } else { "findings" };
+      if !by_file {
+        writeln!(writer, "{header} ({count} {noun})")?;
+        for chunk in chunks {
+          writer.write_all(chunk.as_slice())?;
+        }
+      } else {
+        let header = format!("{header} — {count} {noun}");
+        self.write_file_header(&mut writer, &header)?;
+        let mut lines = 0;
+        for chunk in &chunks {
+          self.write_lines(&mut *writer, &header, &mut lines, chunk.as_slice())?;
+        }
+      }
+      if count > shown {
+        writeln!(writer, "... and {} more\n", count - shown)?;
+      }
+    }
+    Ok(())
+  }
+
+  /// Write findings of one file after `lines` lines under its heading. The heading is
+  /// repeated whenever the findings fill a screen, so that the file is always visible.
+  fn write_lines(
+    &self,
+    writer: &mut W,
+    header: &str,
+    lines: &mut usize,
+    chunk: &[u8],
+  ) -> Result<()> {
+    let repeat_after = match self.screen {
+      Some((_, height)) if self.heading.should_print() => height.saturating_sub(1).max(1),
+      _ => usize::MAX,
+    };
+    for line in chunk.split_inclusive(|b| *b == b'\n') {
+      if *lines == repeat_after {
+        writeln!(writer, "{header} (continued)")?;
+        *lines = 0;
+      }
+      writer.write_all(line)?;
+      *lines += 1;
+    }
+    Ok(())
+  }
+
+  /// Write the separator between files and the heading of the file.
+  fn write_file_header(&self, writer: &mut W, header: &str) -> Result<()> {
+    if self.printed_file.swap(true, Ordering::Relaxed) {
+      let width = self.screen.map_or(SEPARATOR_WIDTH, |(width, _)| width);
+      let separator = self.styles.line_num.paint("─".repeat(width));
+      writeln!(writer, "{separator}")?;
+    }
+    writeln!(writer, "{header}")?;
+    Ok(())
+  }
+
+  /// Print rich diagnostics with context lines or field separators,
+  /// which codespan-reporting cannot print.
+  fn print_rule_frames(
+    &self,
+    matches: Vec<NodeMatch<SgLang>>,
+    path: &Path,
+    rule: &RuleConfig<SgLang>,
+  ) -> Result<()> {
+    let options = frame::FrameOptions {
+      context: (self.context.0 as usize, self.context.1 as usize),
+      tab_width: self.config.tab_width,
+      note_width: self.note_width,
+      highlight: self.highlight,
+      context_separator: self.context_separator.as_deref(),
+      field_separator: &self.field_separator,
+    };
+    let name = adjust_dir_separator(path);
+    let count = matches.len();
+    if self.buffered() {
+      let mut buffer = self.new_buffer();
+      frame::print_frames(matches, &name, rule, &options, &self.styles, &mut buffer)?;
+      self.write_buffer(path, rule, count, buffer)
+    } else {
+      let mut writer = self.writer.lock().expect("should not fail");
+      frame::print_frames(matches, &name, rule, &options, &self.styles, &mut *writer)
+    }
+  }
+
+  /// Separate context blocks by the user's separator or the default of the output mode.
+  fn write_context_separator(&self, writer: &mut W, default: &str) -> Result<()> {
+    match self.context_separator.as_deref() {
+      Some("") => {}
+      Some(separator) => writeln!(writer, "{separator}")?,
+      None => writeln!(writer, "{default}")?,
+    }
+    Ok(())
+  }
+
+  /// Paint a block of printed source with syntax highlighting, None if it is disabled.
+  fn highlight_block(
+    &self,
+    nm: &NodeMatch<SgLang>,
+    block: Range<usize>,
+    matched: &[Range<usize>],
+  ) -> Option<String> {
+    if !self.highlight {
+      return None;
+    }
+    let root = nm.root();
+    let highlights = highlight::Highlights::new(root.root(), block.clone());
+    let tab_width = self.config.tab_width;
+    Some(highlights.paint(root.get_text(), block, matched, tab_width))
+  }
+
   fn context_span(&self) -> usize {
     (self.context.0 + self.context.1) as usize
   }
 
-  fn diff_context(&self) -> usize {
-    if self.context.0 == 0 {
+  fn diff_options(&self) -> DiffOptions {
+    DiffOptions {
+      tab_width: self.config.tab_width,
+      ..DiffOptions::new(self.current_diff_context(), self.diff_style, self.screen)
+    }
+  }
+
+  fn current_diff_context(&self) -> usize {
+    let changed = *self.diff_context.lock().expect("should not fail");
+    changed.unwrap_or(if self.context.0 == 0 {
       3
     } else {
       self.context.0 as usize
-    }
+    })
   }
 }
 
@@ -134,7 +506,6 @@ impl<W: WriteColor + Send + Sync> Printer for ColoredPrinter<W> {
     rule: &RuleConfig<SgLang>,
   ) -> Result<()> {
     let config = &self.config;
-    let mut writer = self.writer.lock().expect("should not fail");
     let severity = match rule.severity {
       Severity::Error => diagnostic::Severity::Error,
       Severity::Warning => diagnostic::Severity::Warning,
@@ -142,21 +513,42 @@ impl<W: WriteColor + Send + Sync> Printer for ColoredPrinter<W> {
       Severity::Hint => diagnostic::Severity::Help,
       Severity::Off => unreachable!("turned-off rule should not have match."),
     };
+    let path = Path::new(file.name().as_ref());
+    let rich = matches!(config.display_style, DisplayStyle::Rich);
+    // codespan-reporting always separates the location fields by `:`
+    let custom_separator = self.field_separator != ":";
+    if rich && (self.context_span() > 0 || self.highlight || custom_separator) {
+      return self.print_rule_frames(matches.collect(), path, rule);
+    }
     for m in matches {
       let range = m.range();
       let mut labels = vec![Label::primary((), range)];
-      if let Some(secondary_nodes) = m.get_env().get_labels("secondary") {
-        labels.extend(secondary_nodes.iter().map(|n| {
-          let range = n.range();
-          Label::secondary((), range)
-        }));
-      }
+      labels.extend(rule.get_labels(&m).into_iter().map(|label| {
+        let range = label.range();
+        let ret = match label.style {
+          LabelStyle::Primary => Label::primary((), range),
+          LabelStyle::Secondary => Label::secondary((), range),
+        };
+        ret.with_message(label.message.unwrap_or_default())
+      }));
+      let notes = rule
+        .note
+        .iter()
+        .map(|note| render_note(note, self.note_width))
+        .collect();
       let diagnostic = Diagnostic::new(severity)
         .with_code(&rule.id)
         .with_message(rule.get_message(&m))
-        .with_notes(rule.note.iter().cloned().collect())
+        .with_notes(notes)
         .with_labels(labels);
-      term::emit(&mut *writer, config, &file, &diagnostic)?;
+      if self.buffered() {
+        let mut buffer = self.new_buffer();
+        term::emit(&mut buffer, config, &file, &diagnostic)?;
+        self.write_buffer(path, rule, 1, buffer)?;
+      } else {
+        let mut writer = self.writer.lock().expect("should not fail");
+        term::emit(&mut *writer, config, &file, &diagnostic)?;
+      }
     }
     Ok(())
   }
@@ -171,56 +563,197 @@ impl<W: WriteColor + Send + Sync> Printer for ColoredPrinter<W> {
 
   fn print_diffs<'a>(&self, diffs: Diffs!('a), path: &Path) -> Result<()> {
     let writer = &mut *self.writer.lock().expect("should success");
-    let context = self.diff_context();
-    print_diffs(diffs, path, &self.styles, writer, context)
+    let options = self.diff_options();
+    print_diffs(diffs, path, &self.styles, writer, &options)
   }
   fn print_rule_diffs(
     &self,
     diffs: Vec<(Diff<'_>, &RuleConfig<SgLang>)>,
     path: &Path,
   ) -> Result<()> {
-    let writer = &mut *self.writer.lock().expect("should success");
-    let context = self.diff_context();
-    let mut start = 0;
-    print_prelude(path, &self.styles, writer)?;
-    for (diff, rule) in diffs {
-      let range = &diff.range;
-      // skip overlapping diff
-      if range.start < start {
-        continue;
+    let options = self.diff_options();
+    match (self.buffered(), diffs.first()) {
+      (true, Some(&(_, rule))) => {
+        let count = diffs.len();
+        let mut buffer = self.new_buffer();
+        print_rule_diffs(diffs, path, &self.styles, &mut buffer, &options)?;
+        self.write_buffer(path, rule, count, buffer)
       }
-      start = range.end;
-      print_rule_title(rule, &diff.node_match, &self.styles.rule, writer)?;
-      let source = diff.get_root_text();
-      let new_str = format!(
-        "{}{}{}",
-        &source[..range.start],
-        diff.replacement,
-        &source[start..],
-      );
-      print_diff(source, &new_str, &self.styles, writer, context)?;
-      if let Some(note) = &rule.note {
-        writeln!(writer, "{}", self.styles.rule.note.paint("Note:"))?;
-        writeln!(writer, "{note}")?;
+      _ => {
+        let writer = &mut *self.writer.lock().expect("should success");
+        print_rule_diffs(diffs, path, &self.styles, writer, &options)
       }
     }
+  }
+
+  fn before_file(&self, path: &Path, count: usize) -> Result<()> {
+    if !self.prints_file_heading() || count == 0 {
+      return Ok(());
+    }
+    let mut heading = self.file_heading.lock().expect("should not fail");
+    let mut writer = self.writer.lock().expect("should not fail");
+    let noun = if count == 1 { "finding" } else { "findings" };
+    let path = self.styles.file_path.paint(path.display().to_string());
+    let header = format!("{path} — {count} {noun}");
+    self.write_file_header(&mut writer, &header)?;
+    *heading = Some((header, 0));
     Ok(())
   }
+
+  fn after_print(&self) -> Result<()> {
+    let mut groups = self.groups.lock().expect("should not fail");
+    self.flush_groups(&mut groups)
+  }
+
+  fn diff_context(&self) -> Option<usize> {
+    Some(self.current_diff_context())
+  }
+
+  fn set_diff_context(&self, context: usize) {
+    *self.diff_context.lock().expect("should not fail") = Some(context);
+  }
 }
 
-fn print_rule_title<W: WriteColor>(
-  rule: &RuleConfig<SgLang>,
-  nm: &NodeMatch<SgLang>,
-  style: &RuleStyle,
+fn print_rule_diffs<W: WriteColor>(
+  diffs: Vec<(Diff<'_>, &RuleConfig<SgLang>)>,
+  path: &Path,
+  styles: &PrintStyles,
   writer: &mut W,
+  options: &DiffOptions,
 ) -> Result<()> {
-  let (level, level_style) = match rule.severity {
+  let mut start = 0;
+  print_prelude(path, styles, writer)?;
+  for (diff, rule) in diffs {
+    let range = &diff.range;
+    // skip overlapping diff
+    if range.start < start {
+      continue;
+    }
+    start = range.end;
+    print_rule_title(rule, &diff.node_match, &styles.rule, writer)?;
+    let source = diff.get_root_text();
+    let new_str = format!(
+      "{}{}{}",
+      &source[..range.start],
+      diff.replacement,
+      &source[start..],
+    );
+    print_diff(source, &new_str, styles, writer, options)?;
+    if let Some(note) = &rule.note {
+      writeln!(writer, "{}", styles.rule.note.paint("Note:"))?;
+      writeln!(writer, "{note}")?;
+    }
+  }
+  Ok(())
+}
+
+/// width taken by the gutter and the `= ` prefix of notes in diagnostics
+const NOTE_INDENT: usize = 8;
+
+/// Render markdown in rule notes as plain text for terminal.
+fn render_note(note: &str, width: Option<usize>) -> String {
+  let mut lines = vec![];
+  for line in note.trim_end().lines() {
+    let trimmed = line.trim_start();
+    // skip code fences but keep the code inside
+    if trimmed.starts_with("```") {
+      continue;
+    }
+    let plain = strip_markdown_line(line);
+    match width {
+      Some(width) => wrap_line(&plain, width, &mut lines),
+      None => lines.push(plain),
+    }
+  }
+  lines.join("\n")
+}
+
+fn strip_markdown_line(line: &str) -> String {
+  let mut text = line.trim_start();
+  let mut ret = String::from(&line[..line.len() - text.len()]);
+  // heading and blockquote markers
+  let marker = text.trim_start_matches(['#', '>']);
+  if marker.len() < text.len() && (marker.is_empty() || marker.starts_with(' ')) {
+    text = marker.trim_start();
+  }
+  if let Some(rest) = text.strip_prefix("* ").or_else(|| text.strip_prefix("+ ")) {
+    ret.push_str("- ");
+    text = rest;
+  }
+  let mut in_code = false;
+  let mut chars = text.chars().peekable();
+  while let Some(c) = chars.next() {
+    match c {
+      '`' => in_code = !in_code,
+      _ if in_code => ret.push(c),
+      '*' | '_' if chars.peek() == Some(&c) => {
+        chars.next();
+      }
+      // [text](url) => text (url)
+      '[' => {
+        let rest: String = chars.clone().collect();
+        let link = rest.split_once("](").and_then(|(label, tail)| {
+          let (url, _) = tail.split_once(')')?;
+          Some((label.to_string(), url.to_string()))
+        });
+        if let Some((label, url)) = link {
+          ret.push_str(&format!("{label} ({url})"));
+          let skipped = label.chars().count() + url.chars().count() + 3;
+          for _ in 0..skipped {
+            chars.next();
+          }
+        } else {
+          ret.push(c);
+        }
+      }
+      _ => ret.push(c),
+    }
+  }
+  ret
+}
+
+fn wrap_line(line: &str, width: usize, lines: &mut Vec<String>) {
+  let indent = &line[..line.len() - line.trim_start().len()];
+  let mut current = String::from(indent);
+  for word in line.split_whitespace() {
+    let has_word = current.len() > indent.len();
+    if has_word && current.width() + 1 + word.width() > width {
+      lines.push(std::mem::replace(&mut current, String::from(indent)));
+    } else if has_word {
+      current.push(' ');
+    }
+    current.push_str(word);
+  }
+  lines.push(current);
+}
+
+fn severity_style(severity: &Severity, style: &RuleStyle) -> (&'static str, Style) {
+  match severity {
     Severity::Error => ("error", style.error),
     Severity::Warning => ("warning", style.warning),
     Severity::Info => ("note", style.info),
     Severity::Hint => ("help", style.hint),
     Severity::Off => unreachable!("turned-off rule should not have match."),
-  };
+  }
+}
+
+fn rule_header(rule: &RuleConfig<SgLang>, style: &RuleStyle) -> String {
+  let (level, level_style) = severity_style(&rule.severity, style);
+  let header = level_style.paint(format!("{level}[{}]:", &rule.id));
+  if rule.message.is_empty() {
+    header.to_string()
+  } else {
+    format!("{header} {}", style.message.paint(&rule.message))
+  }
+}
+
+fn print_rule_title<W: WriteColor>(
+  rule: &RuleConfig<SgLang>,
+  nm: &NodeMatch<SgLang>,
+  style: &RuleStyle,
+  writer: &mut W,
+) -> Result<()> {
+  let (level, level_style) = severity_style(&rule.severity, style);
   let header = format!("{level}[{}]:", &rule.id);
   let header = level_style.paint(header);
   let message = style.message.paint(rule.get_message(nm));
@@ -326,6 +859,7 @@ fn print_matches_with_heading<'a, W: WriteColor + Send + Sync>(
   printer: &ColoredPrinter<W>,
 ) -> Result<()> {
   let styles = &printer.styles;
+  let tab_width = printer.config.tab_width;
   let writer = &mut *printer.writer.lock().expect("cannot get printer lock");
   print_prelude(path, styles, writer)?;
   let Some(first_match) = matches.next() else {
@@ -336,8 +870,13 @@ fn print_matches_with_heading<'a, W: WriteColor + Send + Sync>(
   let mut merger = MatchMerger::new(&first_match, printer.context);
 
   let display = merger.display(&first_match);
-  let mut ret = display.leading.to_string();
-  styles.push_matched_to_ret(&mut ret, &display.matched)?;
+  // tabs are expanded before the matches are painted
+  let mut tabs = TabExpander::new(tab_width);
+  let mut ret = tabs.expand(display.leading).into_owned();
+  styles.push_matched_to_ret(&mut ret, &tabs.expand(&display.matched))?;
+  // byte offset of the printed block and the matches in it, for syntax highlighting
+  let mut block_start = first_match.range().start - display.leading.len();
+  let mut block_matches = vec![first_match.range()];
 
   for nm in matches {
     if merger.check_overlapping(&nm) {
@@ -346,31 +885,40 @@ fn print_matches_with_heading<'a, W: WriteColor + Send + Sync>(
     let display = merger.display(&nm);
     // merge adjacent matches
     if let Some(last_end_offset) = merger.merge_adjacent(&nm) {
-      ret.push_str(&source[last_end_offset..nm.range().start]);
-      styles.push_matched_to_ret(&mut ret, &display.matched)?;
+      ret.push_str(&tabs.expand(&source[last_end_offset..nm.range().start]));
+      styles.push_matched_to_ret(&mut ret, &tabs.expand(&display.matched))?;
+      block_matches.push(nm.range());
       continue;
     }
-    ret.push_str(merger.last_trailing);
+    ret.push_str(&tabs.expand(merger.last_trailing));
+    let block = block_start..merger.last_end_offset + merger.last_trailing.len();
+    if let Some(painted) = printer.highlight_block(&first_match, block, &block_matches) {
+      ret = painted;
+    }
     let lines = ret.lines().count();
     let mut num = merger.last_start_line;
     let width = (lines + num).checked_ilog10().unwrap_or(0) as usize + 1;
-    let line_num = styles.line_num.paint(format!("{num}"));
-    write!(writer, "{line_num:>width$}│")?; // initial line num
     print_highlight(ret.lines(), width, &mut num, writer, styles)?;
     writeln!(writer)?; // end match new line
     if printer.context_span() > 0 {
-      writeln!(writer, "{:╴>width$}┤", "")?; // make separation
+      let separator = format!("{:╴>width$}┤", "");
+      printer.write_context_separator(writer, &separator)?; // make separation
     }
     merger.conclude_match(&nm);
-    ret = display.leading.to_string();
-    styles.push_matched_to_ret(&mut ret, &display.matched)?;
+    tabs = TabExpander::new(tab_width);
+    ret = tabs.expand(display.leading).into_owned();
+    styles.push_matched_to_ret(&mut ret, &tabs.expand(&display.matched))?;
+    block_start = nm.range().start - display.leading.len();
+    block_matches = vec![nm.range()];
+  }
+  ret.push_str(&tabs.expand(merger.last_trailing));
+  let block = block_start..merger.last_end_offset + merger.last_trailing.len();
+  if let Some(painted) = printer.highlight_block(&first_match, block, &block_matches) {
+    ret = painted;
   }
-  ret.push_str(merger.last_trailing);
   let lines = ret.lines().count();
   let mut num = merger.last_start_line;
   let width = (lines + num).checked_ilog10().unwrap_or(0) as usize + 1;
-  let line_num = styles.line_num.paint(format!("{num}"));
-  write!(writer, "{line_num:>width$}│")?; // initial line num
   print_highlight(ret.lines(), width, &mut num, writer, styles)?;
   writeln!(writer)?; // end match new line
   writeln!(writer)?; // end
@@ -385,6 +933,7 @@ fn print_matches_with_prefix<'a, W: WriteColor + Send + Sync>(
   let styles = &printer.styles;
   let writer = &mut *printer.writer.lock().expect("cannot get printer lock");
   let path = path.display();
+  let sep = &printer.field_separator;
   let Some(first_match) = matches.next() else {
     return Ok(());
   };
@@ -408,10 +957,10 @@ fn print_matches_with_prefix<'a, W: WriteColor + Send + Sync>(
     ret.push_str(merger.last_trailing);
     for (n, line) in ret.lines().enumerate() {
       let num = merger.last_start_line + n;
-      writeln!(writer, "{path}:{num}:{line}")?;
+      writeln!(writer, "{path}{sep}{num}{sep}{line}")?;
     }
     if printer.context_span() > 0 {
-      writeln!(writer, "--")?; // make separation
+      printer.write_context_separator(writer, "--")?; // make separation
     }
     merger.conclude_match(&nm);
     ret = display.leading.to_string();
@@ -420,7 +969,7 @@ fn print_matches_with_prefix<'a, W: WriteColor + Send + Sync>(
   ret.push_str(merger.last_trailing);
   for (n, line) in ret.lines().enumerate() {
     let num = merger.last_start_line + n;
-    writeln!(writer, "{path}:{num}:{line}")?;
+    writeln!(writer, "{path}{sep}{num}{sep}{line}")?;
   }
   Ok(())
 }
@@ -430,7 +979,7 @@ fn print_diffs<'a, W: WriteColor>(
   path: &Path,
   styles: &PrintStyles,
   writer: &mut W,
-  context: usize,
+  options: &DiffOptions,
 ) -> Result<()> {
   print_prelude(path, styles, writer)?;
   let Some(first_diff) = diffs.next() else {
@@ -451,10 +1000,11 @@ fn print_diffs<'a, W: WriteColor>(
     start = range.end;
   }
   new_str.push_str(&source[start..]);
-  print_diff(source, &new_str, styles, writer, context)?;
+  print_diff(source, &new_str, styles, writer, options)?;
   Ok(())
 }
 
+/// Print painted lines, whose tabs are expanded already.
 fn print_highlight<'a, W: Write>(
   mut lines: impl Iterator<Item = &'a str>,
   width: usize,
@@ -462,18 +1012,82 @@ fn print_highlight<'a, W: Write>(
   writer: &mut W,
   styles: &PrintStyles,
 ) -> Result<()> {
-  if let Some(line) = lines.next() {
-    write!(writer, "{line}")?;
-  }
+  // pad before painting, escape sequences would count towards the width
+  let line_num = styles.line_num.paint(format!("{num:>width$}"));
+  let line = lines.next().unwrap_or_default();
+  write!(writer, "{line_num}│{line}")?;
   for line in lines {
     writeln!(writer)?;
     *num += 1;
-    let line_num = styles.line_num.paint(format!("{num}"));
-    write!(writer, "{line_num:>width$}│{line}")?;
+    let line_num = styles.line_num.paint(format!("{num:>width$}"));
+    write!(writer, "{line_num}│{line}")?;
   }
   Ok(())
 }
 
+/// Replace tabs in a line of plain text with spaces up to the next tab stop.
+fn expand_tabs(line: &str, tab_width: usize) -> Cow<'_, str> {
+  TabExpander::new(tab_width).expand(line)
+}
+
+/// Replaces tabs with spaces up to the next tab stop so that printed lines align like
+/// the source. Text is expanded before it is painted, and the column carries over the
+/// pieces of a line painted in different styles. Characters take their display width,
+/// like CJK characters taking two columns.
+struct TabExpander {
+  tab_width: usize,
+  column: usize,
+}
+
+impl TabExpander {
+  fn new(tab_width: usize) -> Self {
+    Self {
+      tab_width,
+      column: 0,
+    }
+  }
+
+  /// Move past the character, returns the spaces to replace a tab, at least one.
+  fn advance(&mut self, c: char) -> usize {
+    match c {
+      '\t' => {
+        let spaces = self.tab_width - self.column % self.tab_width;
+        self.column += spaces;
+        spaces
+      }
+      '\n' => {
+        self.column = 0;
+        0
+      }
+      _ => {
+        self.column += c.width().unwrap_or(0);
+        0
+      }
+    }
+  }
+
+  fn push(&mut self, out: &mut String, c: char) {
+    match self.advance(c) {
+      0 => out.push(c),
+      spaces => out.extend(std::iter::repeat(' ').take(spaces)),
+    }
+  }
+
+  fn expand<'a>(&mut self, text: &'a str) -> Cow<'a, str> {
+    if !text.contains('\t') {
+      for c in text.chars() {
+        self.advance(c);
+      }
+      return Cow::Borrowed(text);
+    }
+    let mut ret = String::with_capacity(text.len());
+    for c in text.chars() {
+      self.push(&mut ret, c);
+    }
+    Cow::Owned(ret)
+  }
+}
+
 fn index_display(index: Option<usize>, style: Style, width: usize) -> impl Display {
   let index_str = match index {
     None => format!("{:width$}", ""),
@@ -496,14 +1110,22 @@ fn compute_header(group: &[DiffOp]) -> String {
   )
 }
 
-pub fn print_diff(
+pub(super) fn print_diff(
   old: &str,
   new: &str,
   styles: &PrintStyles,
   writer: &mut impl Write,
-  context: usize,
+  options: &DiffOptions,
 ) -> Result<()> {
-  let diff = TextDiff::from_lines(old, new);
+  // compare without CR so lines of CRLF files differing only in line endings are equal
+  let old = old.replace("\r\n", "\n");
+  let new = new.replace("\r\n", "\n");
+  let context = options.context;
+  if let Some(width) = options.side_by_side {
+    let layout = (width, options.tab_width);
+    return side_by_side::print_side_by_side(&old, &new, styles, writer, context, layout);
+  }
+  let diff = TextDiff::from_lines(&old, &new);
   for group in diff.grouped_ops(context) {
     let op = group.last().unwrap();
     let old_width = op.old_range().end.checked_ilog10().unwrap_or(0) as usize + 1;
@@ -600,6 +1222,14 @@ impl PrintStyles {
   fn no_color() -> Self {
     Self::default()
   }
+  fn new(color: ColorChoice, theme: &ColorTheme) -> Self {
+    if !choose_color::should_use_color(&color) {
+      return Self::no_color();
+    }
+    let mut styles = Self::colored();
+    theme.apply(&mut styles);
+    styles
+  }
 
   fn push_matched_to_ret(&self, ret: &mut String, matched: &str) -> Result<()> {
     use std::fmt::Write;
@@ -619,50 +1249,8 @@ impl PrintStyles {
 }
 impl From<ColorChoice> for PrintStyles {
   fn from(color: ColorChoice) -> Self {
-    if choose_color::should_use_color(&color) {
-      Self::colored()
-    } else {
-      Self::no_color()
-    }
+    Self::new(color, &ColorTheme::default())
   }
 }
 
 // copied from termcolor
-pub(crate) mod choose_color {
-  use super::ColorChoice;
-  use std::env;
-  /// Returns true if we should attempt to write colored output.
-  pub fn should_use_color(color: &ColorChoice) -> bool {
-    match *color {
-      // TODO: we should check if ansi is supported on windows console
-      ColorChoice::Always => true,
-      ColorChoice::AlwaysAnsi => true,
-      ColorChoice::Never => false,
-      // NOTE tty check is added
-      ColorChoice::Auto => atty::is(atty::Stream::Stdout) && env_allows_color(),
-    }
-  }
-
-  fn env_allows_color() -> bool {
-    match env::var_os("TERM") {
-      // On Windows, if TERM isn't set, then we should not automatically
-      // assume that colors aren't allowed. This is unlike Unix environments
-      None => {
-        if !cfg!(windows) {
-          return false;
-        }
-      }
-      Some(k) => {
-        if k == "dumb" {
-          return false;
-        }
-      }
-    }
-    // If TERM != dumb, then the only way we don't allow colors at this
-    // point is if NO_COLOR is set.
-    if env::var_os("NO_COLOR").is_some() {
-      return false;
-    }
-    true
-  }
-}
@@ -0,0 +1,245 @@
+//! Code frames of rich diagnostics with context lines, see `sg scan --context`.
+//! The frames look like those of codespan-reporting, which cannot print context.
+//! Context lines are dimmed and frames of nearby findings are merged.
+use super::highlight::Highlights;
+use super::{expand_tabs, print_rule_title, render_note, severity_style, NodeMatch, PrintStyles};
+use crate::lang::SgLang;
+use ast_grep_config::{LabelStyle, RuleConfig};
+
+use ansi_term::Style;
+use anyhow::Result;
+use codespan_reporting::term::termcolor::WriteColor;
+use unicode_width::UnicodeWidthChar;
+
+use std::borrow::Cow;
+use std::ops::Range;
+
+/// A caret line under a source line, a byte range in the line.
+struct Mark<'a> {
+  line: usize,
+  range: Range<usize>,
+  primary: bool,
+  message: &'a str,
+}
+
+/// Findings printed in one frame, with the lines of the frame.
+struct Frame<'a> {
+  matches: Vec<NodeMatch<'a, SgLang>>,
+  lines: Range<usize>,
+}
+
+pub(super) struct FrameOptions<'a> {
+  pub context: (usize, usize),
+  pub tab_width: usize,
+  pub note_width: Option<usize>,
+  /// highlight the syntax of all lines instead of dimming context lines
+  pub highlight: bool,
+  /// printed between frames if given, frames are separated by blank lines anyway
+  pub context_separator: Option<&'a str>,
+  pub field_separator: &'a str,
+}
+
+pub(super) fn print_frames<W: WriteColor>(
+  matches: Vec<NodeMatch<'_, SgLang>>,
+  path: &str,
+  rule: &RuleConfig<SgLang>,
+  options: &FrameOptions<'_>,
+  styles: &PrintStyles,
+  writer: &mut W,
+) -> Result<()> {
+  let Some(first) = matches.first() else {
+    return Ok(());
+  };
+  let root = first.root();
+  let source = root.get_text();
+  let source_lines: Vec<_> = source
+    .strip_suffix('\n')
+    .unwrap_or(source)
+    .split('\n')
+    .collect();
+  let line_starts: Vec<_> = source_lines
+    .iter()
+    .scan(0, |offset, line| {
+      let start = *offset;
+      *offset += line.len() + 1;
+      Some(start)
+    })
+    .collect();
+  let separator = options.context_separator.filter(|s| !s.is_empty());
+  for (i, frame) in merge_frames(matches, options.context, source_lines.len())
+    .into_iter()
+    .enumerate()
+  {
+    if let Some(separator) = separator.filter(|_| i > 0) {
+      writeln!(writer, "{separator}")?;
+    }
+    for nm in &frame.matches {
+      print_rule_title(rule, nm, &styles.rule, writer)?;
+    }
+    let lines = &source_lines[frame.lines.clone()];
+    let painted = options.highlight.then(|| {
+      let last = frame.lines.end - 1;
+      let block = line_starts[frame.lines.start]..line_starts[last] + source_lines[last].len();
+      let matched: Vec<_> = frame.matches.iter().map(|m| m.range()).collect();
+      let highlights = Highlights::new(root.root(), block.clone());
+      highlights.paint(source, block, &matched, options.tab_width)
+    });
+    let painted_lines: Vec<_> = painted.iter().flat_map(|p| p.split('\n')).collect();
+    let marks = collect_marks(&frame.matches, rule, source);
+    let matched: Vec<_> = frame
+      .matches
+      .iter()
+      .map(|m| m.start_pos().0..m.end_pos().0 + 1)
+      .collect();
+    let width = frame.lines.end.checked_ilog10().unwrap_or(0) as usize + 1;
+    let gutter = styles.line_num;
+    let (line, column) = first_position(&frame.matches[0], source);
+    let sep = options.field_separator;
+    writeln!(writer, "{:width$} ┌─ {path}{sep}{line}{sep}{column}", "")?;
+    writeln!(writer, "{:width$} │", "")?;
+    for (i, text) in lines.iter().enumerate() {
+      let num = frame.lines.start + i;
+      let text = text.strip_suffix('\r').unwrap_or(text);
+      let painted = painted_lines
+        .get(i)
+        .map(|p| p.strip_suffix('\r').unwrap_or(p));
+      // painted lines have their tabs expanded already
+      let expanded = painted.map_or_else(|| expand_tabs(text, options.tab_width), Cow::Borrowed);
+      let line_num = format!("{:>width$}", num + 1);
+      if text.is_empty() {
+        writeln!(writer, "{} │", gutter.paint(line_num))?;
+      } else if painted.is_some() || matched.iter().any(|r| r.contains(&num)) {
+        writeln!(writer, "{} │ {expanded}", gutter.paint(line_num))?;
+      } else {
+        let context = styles.line_num.paint(expanded.as_ref());
+        writeln!(writer, "{} │ {context}", gutter.paint(line_num))?;
+      }
+      for mark in marks.iter().filter(|m| m.line == num) {
+        let style = mark_style(rule, mark.primary, styles);
+        let start = text_width(&text[..mark.range.start], options.tab_width);
+        let end = text_width(&text[..mark.range.end], options.tab_width).max(start + 1);
+        let ch = if mark.primary { "^" } else { "-" };
+        let carets = style.paint(ch.repeat(end - start));
+        let message = style.paint(mark.message);
+        let caret_line = format!("{:start$}{carets} {message}", "");
+        writeln!(writer, "{:width$} │ {}", "", caret_line.trim_end())?;
+      }
+    }
+    if let Some(note) = &rule.note {
+      writeln!(writer, "{:width$} │", "")?;
+      let note = render_note(note, options.note_width);
+      let mut note_lines = note.lines();
+      if let Some(line) = note_lines.next() {
+        writeln!(writer, "{:width$} = {line}", "")?;
+      }
+      for line in note_lines {
+        writeln!(writer, "{:width$}   {line}", "")?;
+      }
+    }
+    writeln!(writer)?;
+  }
+  Ok(())
+}
+
+/// Findings share a frame if their lines with context overlap or are adjacent.
+fn merge_frames<'a>(
+  matches: Vec<NodeMatch<'a, SgLang>>,
+  (before, after): (usize, usize),
+  line_count: usize,
+) -> Vec<Frame<'a>> {
+  let mut frames: Vec<Frame> = vec![];
+  for nm in matches {
+    let start = nm.start_pos().0.saturating_sub(before);
+    let end = (nm.end_pos().0 + after + 1).min(line_count);
+    match frames.last_mut() {
+      Some(frame) if start <= frame.lines.end => {
+        frame.lines.end = frame.lines.end.max(end);
+        frame.matches.push(nm);
+      }
+      _ => frames.push(Frame {
+        matches: vec![nm],
+        lines: start..end,
+      }),
+    }
+  }
+  frames
+}
+
+/// Split the ranges of findings and their labels into marks of every line.
+fn collect_marks<'a>(
+  matches: &[NodeMatch<'_, SgLang>],
+  rule: &'a RuleConfig<SgLang>,
+  source: &str,
+) -> Vec<Mark<'a>> {
+  let mut marks = vec![];
+  for nm in matches {
+    push_marks(&mut marks, source, nm.range(), true, "");
+    for label in rule.get_labels(nm) {
+      let primary = matches!(label.style, LabelStyle::Primary);
+      let message = label.message.unwrap_or_default();
+      push_marks(&mut marks, source, label.range(), primary, message);
+    }
+  }
+  marks
+}
+
+fn push_marks<'a>(
+  marks: &mut Vec<Mark<'a>>,
+  source: &str,
+  range: Range<usize>,
+  primary: bool,
+  message: &'a str,
+) {
+  let mut line = source[..range.start].matches('\n').count();
+  let mut line_start = source[..range.start].rfind('\n').map_or(0, |i| i + 1);
+  loop {
+    let line_end = source[line_start..]
+      .find('\n')
+      .map_or(source.len(), |i| line_start + i);
+    let text_end = if source[..line_end].ends_with('\r') {
+      line_end - 1
+    } else {
+      line_end
+    };
+    let start = range.start.max(line_start) - line_start;
+    let end = range.end.min(text_end).max(range.start.max(line_start)) - line_start;
+    let last = range.end <= line_end + 1 || line_end == source.len();
+    // the message is shown under the last line of the range
+    let message = if last { message } else { "" };
+    marks.push(Mark {
+      line,
+      range: start..end,
+      primary,
+      message,
+    });
+    if last {
+      break;
+    }
+    line += 1;
+    line_start = line_end + 1;
+  }
+}
+
+/// Display width of the text, tabs move to the next tab stop.
+fn text_width(text: &str, tab_width: usize) -> usize {
+  text.chars().fold(0, |column, c| match c {
+    '\t' => column + tab_width - column % tab_width,
+    _ => column + c.width().unwrap_or(0),
+  })
+}
+
+/// One-based line and column in characters, like codespan-reporting.
+fn first_position(nm: &NodeMatch<'_, SgLang>, source: &str) -> (usize, usize) {
+  let start = nm.range().start;
+  let line_start = source[..start].rfind('\n').map_or(0, |i| i + 1);
+  let column = source[line_start..start].chars().count();
+  (nm.start_pos().0 + 1, column + 1)
+}
+
+fn mark_style(rule: &RuleConfig<SgLang>, primary: bool, styles: &PrintStyles) -> Style {
+  if primary {
+    severity_style(&rule.severity, &styles.rule).1
+  } else {
+    styles.rule.info
+  }
+}
@@ -0,0 +1,136 @@
+//! Syntax highlighting of printed code, see `--highlight`.
+//! Tokens are colored by the kinds of leaf nodes in the already parsed tree,
+//! whose names are shared by most tree-sitter grammars, so no highlight query
+//! is needed. Only nodes overlapping the printed lines are visited.
+use super::TabExpander;
+use crate::lang::SgLang;
+
+use ansi_term::{Color, Style};
+use ast_grep_core::{Node as SgNode, StrDoc};
+
+use std::ops::Range;
+
+type Node<'r> = SgNode<'r, StrDoc<SgLang>>;
+
+/// Node kinds of literal constants, other than strings and numbers.
+const CONSTANTS: &[&str] = &[
+  "true",
+  "false",
+  "null",
+  "nil",
+  "none",
+  "undefined",
+  "boolean",
+];
+
+/// Colored spans of the tokens in a range of the source.
+pub(super) struct Highlights {
+  /// sorted and non-overlapping
+  spans: Vec<(Range<usize>, Style)>,
+}
+
+impl Highlights {
+  pub fn new(root: Node<'_>, range: Range<usize>) -> Self {
+    let mut spans = vec![];
+    collect_spans(root, &range, &mut spans);
+    Self { spans }
+  }
+
+  /// Paint the source in the range. Matched ranges are emphasized with bold and
+  /// underline on top of the token colors, so that they stand out in the code.
+  /// Styles are reset at every line end so that lines can be printed separately.
+  /// Tabs are expanded before painting, so escape sequences do not shift the tab stops.
+  pub fn paint(
+    &self,
+    source: &str,
+    range: Range<usize>,
+    matched: &[Range<usize>],
+    tab_width: usize,
+  ) -> String {
+    let mut ret = String::new();
+    let mut run = String::new();
+    let mut run_style = Style::new();
+    let mut spans = self.spans.iter().peekable();
+    let mut tabs = TabExpander::new(tab_width);
+    for (i, c) in source[range.clone()].char_indices() {
+      let offset = range.start + i;
+      // line endings are not painted, so that CRLF lines can be split as usual
+      if c == '\n' || c == '\r' {
+        push_run(&mut ret, &mut run, run_style);
+        tabs.push(&mut ret, c);
+        continue;
+      }
+      while spans.peek().map_or(false, |(r, _)| r.end <= offset) {
+        spans.next();
+      }
+      let mut style = match spans.peek() {
+        Some((r, style)) if r.start <= offset => *style,
+        _ => Style::new(),
+      };
+      if matched.iter().any(|m| m.contains(&offset)) {
+        style = style.bold().underline();
+      }
+      if style != run_style {
+        push_run(&mut ret, &mut run, run_style);
+      }
+      run_style = style;
+      tabs.push(&mut run, c);
+    }
+    push_run(&mut ret, &mut run, run_style);
+    ret
+  }
+}
+
+/// Paint the pending text of the same style, empty text would leave bare escape codes.
+fn push_run(ret: &mut String, run: &mut String, style: Style) {
+  if !run.is_empty() {
+    ret.push_str(&style.paint(std::mem::take(run)).to_string());
+  }
+}
+
+fn collect_spans(node: Node<'_>, range: &Range<usize>, spans: &mut Vec<(Range<usize>, Style)>) {
+  let node_range = node.range();
+  if node_range.end <= range.start || node_range.start >= range.end {
+    return;
+  }
+  // strings and comments are colored as a whole, including their children
+  if let Some(style) = token_style(&node) {
+    spans.push((node_range, style));
+    return;
+  }
+  for child in node.children() {
+    collect_spans(child, range, spans);
+  }
+}
+
+/// Style of a token by its node kind. Anonymous words are keywords like `if` or `return`.
+fn token_style(node: &Node<'_>) -> Option<Style> {
+  let kind = node.kind();
+  let kind = kind.to_ascii_lowercase();
+  let style = if kind.contains("comment") {
+    Color::Fixed(244).italic()
+  } else if kind.contains("string") || kind.contains("char_literal") || kind == "regex" {
+    Style::new().fg(Color::Green)
+  } else if kind.contains("number")
+    || kind.contains("integer")
+    || kind.contains("float")
+    || CONSTANTS.contains(&kind.as_str())
+  {
+    Style::new().fg(Color::Cyan)
+  } else if kind.contains("type_identifier") || kind.ends_with("primitive_type") {
+    Style::new().fg(Color::Yellow)
+  } else if !node.is_named() && is_word(&node.text()) {
+    Style::new().fg(Color::Purple)
+  } else {
+    return None;
+  };
+  Some(style)
+}
+
+fn is_word(text: &str) -> bool {
+  let mut chars = text.chars();
+  chars
+    .next()
+    .map_or(false, |c| c.is_alphabetic() || c == '_')
+    && chars.all(|c| c.is_alphanumeric() || c == '_')
+}
@@ -0,0 +1,214 @@
+//! Side-by-side diffs, see `--diff-style side-by-side`.
+//! The original is on the left and the replacement on the right. Removed and
+//! added lines between two unchanged lines are paired row by row, and the
+//! changed words of a paired row are emphasized.
+use super::{compute_header, PrintStyles};
+
+use ansi_term::{Color, Style};
+use anyhow::Result;
+use similar::{ChangeTag, DiffTag, TextDiff};
+use unicode_width::UnicodeWidthChar;
+
+use std::io::Write;
+use std::ops::Range;
+
+/// Narrower terminals fall back to inline diffs, the columns would be too cramped.
+pub(in crate::print) const MIN_WIDTH: usize = 80;
+
+/// Parts of a line, emphasized if changed on the other side.
+type Segments = Vec<(bool, String)>;
+
+/// One side of a row. A line without counterpart leaves the other side empty.
+struct Cell {
+  index: usize,
+  tag: ChangeTag,
+  segments: Segments,
+}
+
+pub(super) fn print_side_by_side(
+  old: &str,
+  new: &str,
+  styles: &PrintStyles,
+  writer: &mut impl Write,
+  context: usize,
+  (width, tab_width): (usize, usize),
+) -> Result<()> {
+  let diff = TextDiff::from_lines(old, new);
+  let old_lines = diff.old_slices();
+  let new_lines = diff.new_slices();
+  for group in diff.grouped_ops(context) {
+    let op = group.last().unwrap();
+    let last_line = op.old_range().end.max(op.new_range().end);
+    let num_width = last_line.checked_ilog10().unwrap_or(0) as usize + 1;
+    // the two halves are separated by `│`, each has a line number and a sign
+    let text_width = ((width - 1) / 2).saturating_sub(num_width + 2);
+    writeln!(writer, "{}", Color::Blue.paint(compute_header(&group)))?;
+    let mut rows = vec![];
+    let (mut deleted, mut inserted) = (0..0, 0..0);
+    for op in &group {
+      let (tag, old_range, new_range) = op.as_tag_tuple();
+      if tag != DiffTag::Equal {
+        deleted = extend(deleted, old_range);
+        inserted = extend(inserted, new_range);
+        continue;
+      }
+      pair_changes(&mut rows, (old_lines, deleted), (new_lines, inserted));
+      (deleted, inserted) = (0..0, 0..0);
+      for (i, j) in old_range.zip(new_range) {
+        let line = trim_line(old_lines[i]);
+        let segments = vec![(false, line.to_string())];
+        let left = Cell {
+          index: i,
+          tag: ChangeTag::Equal,
+          segments: segments.clone(),
+        };
+        let right = Cell {
+          index: j,
+          tag: ChangeTag::Equal,
+          segments,
+        };
+        rows.push((Some(left), Some(right)));
+      }
+    }
+    pair_changes(&mut rows, (old_lines, deleted), (new_lines, inserted));
+    for (left, right) in rows {
+      let cell_width = (num_width, text_width, tab_width);
+      let left = render_cell(left.as_ref(), styles, cell_width, true);
+      let right = render_cell(right.as_ref(), styles, cell_width, false);
+      writeln!(writer, "{left}│{right}")?;
+    }
+  }
+  Ok(())
+}
+
+/// Changed lines are contiguous, so the ranges of one change only grow.
+fn extend(range: Range<usize>, other: Range<usize>) -> Range<usize> {
+  if range.is_empty() {
+    other
+  } else {
+    range.start..other.end.max(range.end)
+  }
+}
+
+/// Pair removed and added lines row by row, the rest have an empty counterpart.
+fn pair_changes(
+  rows: &mut Vec<(Option<Cell>, Option<Cell>)>,
+  (old_lines, deleted): (&[&str], Range<usize>),
+  (new_lines, inserted): (&[&str], Range<usize>),
+) {
+  let count = deleted.len().max(inserted.len());
+  for n in 0..count {
+    let old = (n < deleted.len()).then(|| deleted.start + n);
+    let new = (n < inserted.len()).then(|| inserted.start + n);
+    let (left, right) = match (old, new) {
+      (Some(i), Some(j)) => diff_words(trim_line(old_lines[i]), trim_line(new_lines[j])),
+      (Some(i), None) => (vec![(false, trim_line(old_lines[i]).into())], vec![]),
+      (None, Some(j)) => (vec![], vec![(false, trim_line(new_lines[j]).into())]),
+      (None, None) => unreachable!("n is less than one of the lengths"),
+    };
+    let left = old.map(|index| Cell {
+      index,
+      tag: ChangeTag::Delete,
+      segments: left,
+    });
+    let right = new.map(|index| Cell {
+      index,
+      tag: ChangeTag::Insert,
+      segments: right,
+    });
+    rows.push((left, right));
+  }
+}
+
+/// Emphasize the words that differ between a removed line and its added counterpart.
+fn diff_words(old: &str, new: &str) -> (Segments, Segments) {
+  let diff = TextDiff::from_words(old, new);
+  let (mut left, mut right) = (vec![], vec![]);
+  for change in diff.iter_all_changes() {
+    let value = change.value().to_string();
+    match change.tag() {
+      ChangeTag::Equal => {
+        left.push((false, value.clone()));
+        right.push((false, value));
+      }
+      ChangeTag::Delete => left.push((true, value)),
+      ChangeTag::Insert => right.push((true, value)),
+    }
+  }
+  (left, right)
+}
+
+fn trim_line(line: &str) -> &str {
+  let line = line.strip_suffix('\n').unwrap_or(line);
+  line.strip_suffix('\r').unwrap_or(line)
+}
+
+/// Render the line number, sign and text of a cell in the given display width.
+/// Tabs are expanded and text wider than the cell is cut with `…`.
+/// The left cell is padded so that the separator is aligned.
+fn render_cell(
+  cell: Option<&Cell>,
+  styles: &PrintStyles,
+  (num_width, text_width, tab_width): (usize, usize, usize),
+  pad: bool,
+) -> String {
+  let Some(cell) = cell else {
+    let blank = if pad { num_width + 2 + text_width } else { 0 };
+    return " ".repeat(blank);
+  };
+  let (sign, style, emphasis, line_num) = match cell.tag {
+    ChangeTag::Delete => ("-", styles.delete, styles.delete_emphasis, styles.delete),
+    ChangeTag::Insert => ("+", styles.insert, styles.insert_emphasis, styles.insert),
+    ChangeTag::Equal => (" ", Style::new(), Style::new(), styles.line_num),
+  };
+  // expand tabs and measure the text first to know if it should be cut
+  let mut chars = vec![];
+  let mut column = 0;
+  for (emphasized, text) in &cell.segments {
+    for c in text.chars() {
+      if c == '\t' {
+        let spaces = tab_width - column % tab_width;
+        chars.extend(std::iter::repeat((*emphasized, ' ', 1)).take(spaces));
+        column += spaces;
+      } else {
+        let width = c.width().unwrap_or(0);
+        chars.push((*emphasized, c, width));
+        column += width;
+      }
+    }
+  }
+  let cut = column > text_width;
+  let limit = if cut { text_width - 1 } else { text_width };
+  let mut ret = format!(
+    "{} {}",
+    line_num.paint(format!("{:>num_width$}", cell.index + 1)),
+    style.paint(sign)
+  );
+  let mut column = 0;
+  let mut run = String::new();
+  let mut run_emphasized = false;
+  for (emphasized, c, width) in chars {
+    if column + width > limit {
+      break;
+    }
+    if emphasized != run_emphasized && !run.is_empty() {
+      let s = if run_emphasized { emphasis } else { style };
+      ret.push_str(&s.paint(std::mem::take(&mut run)).to_string());
+    }
+    run_emphasized = emphasized;
+    run.push(c);
+    column += width;
+  }
+  if !run.is_empty() {
+    let s = if run_emphasized { emphasis } else { style };
+    ret.push_str(&s.paint(run).to_string());
+  }
+  if cut {
+    ret.push_str(&style.paint("…").to_string());
+    column += 1;
+  }
+  if pad {
+    ret.push_str(&" ".repeat(text_width.saturating_sub(column)));
+  }
+  ret
+}
@@ -1,11 +1,13 @@
 #![cfg(test)]
 
 use super::*;
+use crate::print::LineEnding;
 use ast_grep_config::{from_yaml_string, Fixer, GlobalRules};
 use ast_grep_language::{Language, SupportLang};
 use codespan_reporting::term::termcolor::Buffer;
 
 use std::fmt::Write;
+use std::ops::Range;
 
 fn make_test_printer() -> ColoredPrinter<Buffer> {
   ColoredPrinter::new(Buffer::no_color()).color(ColorChoice::Never)
@@ -82,6 +84,27 @@ fn test_print_matches_without_heading() {
   }
 }
 
+#[test]
+fn test_print_separators() {
+  let source = "Some(1)\nfoo\nbar\nbaz\nSome(2)\n";
+  let print = |context_separator: Option<&str>| {
+    let printer = make_test_printer()
+      .heading(Heading::Never)
+      .context((1, 1))
+      .context_separator(context_separator.map(String::from))
+      .field_separator("\t".into());
+    let grep = SgLang::from(SupportLang::Tsx).ast_grep(source);
+    let matches = grep.root().find_all("Some($A)");
+    printer.print_matches(matches, "test.tsx".as_ref()).unwrap();
+    get_text(&printer)
+  };
+  let first = "test.tsx\t1\tSome(1)\ntest.tsx\t2\tfoo\n";
+  let second = "test.tsx\t4\tbaz\ntest.tsx\t5\tSome(2)\n";
+  assert_eq!(print(None), format!("{first}--\n{second}"));
+  assert_eq!(print(Some("\0")), format!("{first}\0\n{second}"));
+  assert_eq!(print(Some("")), format!("{first}{second}"));
+}
+
 #[test]
 fn test_print_rules() {
   let globals = GlobalRules::default();
@@ -117,6 +140,246 @@ rule:
   }
 }
 
+#[test]
+fn test_print_rule_labels_and_note() {
+  let globals = GlobalRules::default();
+  let printer = make_test_printer().style(ReportStyle::Rich);
+  let source = "let a = 123".to_string();
+  let grep = SgLang::from(SupportLang::TypeScript).ast_grep(&source);
+  let file = SimpleFile::new(Cow::Borrowed("test.tsx"), &source);
+  let rule = from_yaml_string(
+    r"
+id: test-id
+message: test rule
+severity: info
+language: TypeScript
+rule:
+  pattern: let $A = $B
+note: Use **const** for `$A`. See [docs](https://ast-grep.github.io)
+labels:
+  A:
+    style: primary
+    message: declared here
+  B:
+    style: secondary
+    message: initial value",
+    &globals,
+  )
+  .expect("should parse")
+  .pop()
+  .unwrap();
+  let matches = grep.root().find_all(&rule.matcher);
+  printer.print_rule(matches, file, &rule).expect("test only");
+  let text = get_text(&printer);
+  assert!(text.contains("declared here"), "{text}");
+  assert!(text.contains("initial value"), "{text}");
+  assert!(
+    text.contains("Use const for $A. See docs (https://ast-grep.github.io)"),
+    "{text}"
+  );
+}
+
+#[test]
+fn test_short_style_omits_note() {
+  let globals = GlobalRules::default();
+  let printer = make_test_printer().style(ReportStyle::Short);
+  let source = "let a = 123".to_string();
+  let grep = SgLang::from(SupportLang::TypeScript).ast_grep(&source);
+  let file = SimpleFile::new(Cow::Borrowed("test.tsx"), &source);
+  let rule = from_yaml_string(
+    r"
+id: test-id
+message: test rule
+language: TypeScript
+rule: { pattern: let $A = $B }
+note: a long note",
+    &globals,
+  )
+  .expect("should parse")
+  .pop()
+  .unwrap();
+  let matches = grep.root().find_all(&rule.matcher);
+  printer.print_rule(matches, file, &rule).expect("test only");
+  let text = get_text(&printer);
+  assert!(text.contains("test rule"));
+  assert!(!text.contains("a long note"));
+}
+
+#[test]
+fn test_render_note() {
+  let note = "# Fix\n* use `__init__` and __bold__\n```\ncode\n```";
+  assert_eq!(
+    render_note(note, None),
+    "Fix\n- use __init__ and bold\ncode"
+  );
+  assert_eq!(render_note("#[derive] > a", None), "#[derive] > a");
+  let wrapped = render_note("aaa bbb ccc ddd\n  eee fff", Some(8));
+  assert_eq!(wrapped, "aaa bbb\nccc ddd\n  eee\n  fff");
+}
+
+fn make_rule(id: &str, pattern: &str) -> RuleConfig<SgLang> {
+  let globals = GlobalRules::default();
+  let rule = format!(
+    "{{id: {id}, message: {id} message, language: TypeScript, rule: {{pattern: {pattern}}}}}"
+  );
+  from_yaml_string(&rule, &globals)
+    .expect("should parse")
+    .pop()
+    .unwrap()
+}
+
+fn print_file(
+  printer: &ColoredPrinter<Buffer>,
+  path: &str,
+  source: &str,
+  rules: &[&RuleConfig<SgLang>],
+) {
+  let grep = SgLang::from(SupportLang::TypeScript).ast_grep(source);
+  let source = source.to_string();
+  let count = rules
+    .iter()
+    .map(|rule| grep.root().find_all(&rule.matcher).count())
+    .sum();
+  printer
+    .before_file(path.as_ref(), count)
+    .expect("test only");
+  for rule in rules {
+    let file = SimpleFile::new(Cow::Borrowed(path), &source);
+    let matches = grep.root().find_all(&rule.matcher);
+    printer.print_rule(matches, file, rule).expect("test only");
+  }
+}
+
+#[test]
+fn test_group_by_rule() {
+  let printer = make_test_printer()
+    .style(ReportStyle::Short)
+    .group_by(Some(GroupBy::Rule));
+  let rule_a = make_rule("rule-a", "a");
+  let rule_b = make_rule("rule-b", "b");
+  print_file(&printer, "1.ts", "a; b", &[&rule_b, &rule_a]);
+  print_file(&printer, "2.ts", "a; a", &[&rule_b, &rule_a]);
+  // nothing is printed before the scan finishes
+  assert_eq!(get_text(&printer), "");
+  printer.after_print().expect("test only");
+  let text = get_text(&printer);
+  let a = text
+    .find("help[rule-a]: rule-a message (3 findings)")
+    .expect("should have rule-a");
+  let b = text
+    .find("help[rule-b]: rule-b message (1 finding)")
+    .expect("should have rule-b");
+  assert!(a < b);
+  assert!(text[a..b].contains("1.ts:1:1"));
+  assert!(text[a..b].contains("2.ts:1:4"));
+  assert!(text[b..].contains("1.ts:1:4"));
+}
+
+#[test]
+fn test_group_by_file() {
+  let printer = make_test_printer()
+    .style(ReportStyle::Short)
+    .group_by(Some(GroupBy::File));
+  let rule_a = make_rule("rule-a", "a");
+  let rule_b = make_rule("rule-b", "b");
+  print_file(&printer, "2.ts", "a; b", &[&rule_a, &rule_b]);
+  print_file(&printer, "1.ts", "a", &[&rule_a, &rule_b]);
+  printer.after_print().expect("test only");
+  let text = get_text(&printer);
+  let first = text
+    .find("2.ts — 2 findings")
+    .expect("should have file header");
+  let second = text
+    .find("1.ts — 1 finding")
+    .expect("should have file header");
+  // files keep the scan order
+  assert!(first < second);
+}
+
+#[test]
+fn test_file_heading() {
+  let printer = make_test_printer()
+    .style(ReportStyle::Rich)
+    .heading(Heading::Always);
+  let rule_a = make_rule("rule-a", "a");
+  let rule_b = make_rule("rule-b", "b");
+  // findings of both rules interleave in the file
+  print_file(&printer, "2.ts", "a; b; a; b", &[&rule_a, &rule_b]);
+  print_file(&printer, "1.ts", "b", &[&rule_a, &rule_b]);
+  printer.after_print().expect("test only");
+  let text = get_text(&printer);
+  let separator = "─".repeat(SEPARATOR_WIDTH);
+  let lines: Vec<_> = text.lines().collect();
+  assert_eq!(lines[0], "2.ts — 4 findings");
+  let sep = lines.iter().position(|l| *l == separator).expect("{text}");
+  assert_eq!(lines[sep + 1], "1.ts — 1 finding");
+  assert_eq!(lines.iter().filter(|l| **l == separator).count(), 1);
+  // rich diagnostics without heading are printed as they come
+  let printer = make_test_printer().style(ReportStyle::Rich);
+  print_file(&printer, "2.ts", "a; b", &[&rule_a, &rule_b]);
+  printer.after_print().expect("test only");
+  let text = get_text(&printer);
+  assert!(!text.contains("finding"), "{text}");
+  assert!(!text.contains(&separator), "{text}");
+}
+
+#[test]
+fn test_repeat_file_heading() {
+  let rule = make_rule("rule-a", "a");
+  for (heading, repeated) in [(Heading::Always, true), (Heading::Never, false)] {
+    let mut printer = make_test_printer()
+      .style(ReportStyle::Short)
+      .group_by(Some(GroupBy::File))
+      .heading(heading);
+    printer.screen = Some((20, 5));
+    print_file(&printer, "1.ts", "a;a;a;a;a;a", &[&rule]);
+    printer.after_print().expect("test only");
+    let text = get_text(&printer);
+    let lines: Vec<_> = text.lines().collect();
+    if repeated {
+      // every screen starts with the heading
+      assert_eq!(lines[0], "1.ts — 6 findings");
+      assert_eq!(lines[5], "1.ts — 6 findings (continued)");
+      assert_eq!(lines.len(), 8, "{text}");
+    } else {
+      assert!(!text.contains("continued"), "{text}");
+    }
+  }
+}
+
+#[test]
+fn test_group_cap() {
+  let printer = make_test_printer()
+    .style(ReportStyle::Short)
+    .group_by(Some(GroupBy::Rule));
+  let rule = make_rule("rule-a", "a");
+  let source = "a;".repeat(MAX_GROUP_FINDINGS + 5);
+  print_file(&printer, "1.ts", &source, &[&rule]);
+  printer.after_print().expect("test only");
+  let text = get_text(&printer);
+  assert!(text.contains("(105 findings)"));
+  assert!(text.contains("... and 5 more"));
+  assert_eq!(text.matches("help[rule-a]").count(), MAX_GROUP_FINDINGS + 1);
+}
+
+#[test]
+fn test_stream_file_heading() {
+  let printer = make_test_printer()
+    .style(ReportStyle::Rich)
+    .heading(Heading::Always);
+  let rule = make_rule("rule-a", "a");
+  let source = "a;".repeat(MAX_GROUP_FINDINGS + 50);
+  print_file(&printer, "1.ts", &source, &[&rule]);
+  // findings are streamed without waiting for the scan to finish
+  let text = get_text(&printer);
+  assert!(text.starts_with("1.ts — 150 findings\n"), "{text}");
+  // and no finding is dropped without --group-by
+  assert_eq!(text.matches("help[rule-a]").count(), 150);
+  assert!(!text.contains("more"));
+  printer.after_print().expect("test only");
+  assert_eq!(get_text(&printer), text);
+}
+
 // source, pattern, rewrite, debug note
 type DiffCase<'a> = (&'a str, &'a str, &'a str, &'a str);
 
@@ -158,12 +421,112 @@ fn test_print_diffs() {
     let fixer = Fixer::from_str(rewrite, &lang).expect("should work");
     let grep = lang.ast_grep(source);
     let matches = grep.root().find_all(pattern);
-    let diffs = matches.map(|n| Diff::generate(n, &pattern, &fixer));
+    let diffs = matches.map(|n| Diff::generate(n, &pattern, &fixer, LineEnding::Lf));
     printer.print_diffs(diffs, "test.tsx".as_ref()).unwrap();
     assert!(get_text(&printer).contains(rewrite), "{note}");
   }
 }
 
+#[test]
+fn test_print_crlf_diffs() {
+  let printer = make_test_printer().heading(Heading::Always);
+  let lang = SgLang::from(SupportLang::Tsx);
+  let fixer = Fixer::from_str("bar($A)\nbaz($A)", &lang).expect("should work");
+  let grep = lang.ast_grep("a()\r\nfoo(1)\r\nb()\r\n");
+  let diffs: Vec<_> = grep
+    .root()
+    .find_all("foo($A)")
+    .map(|n| Diff::generate(n, &"foo($A)", &fixer, LineEnding::of(grep.source())))
+    .collect();
+  assert_eq!(diffs[0].replacement, "bar(1)\r\nbaz(1)");
+  printer
+    .print_diffs(diffs.into_iter(), "test.tsx".as_ref())
+    .unwrap();
+  let text = get_text(&printer);
+  // unchanged lines are not shown as modified and no CR is printed
+  assert!(text.contains("1 1│ a()\n"), "{text:?}");
+  assert!(text.contains("3 4│ b()\n"), "{text:?}");
+  assert!(!text.contains('\r'), "{text:?}");
+}
+
+#[test]
+fn test_change_diff_context() {
+  let print = |printer: &ColoredPrinter<Buffer>| {
+    let lang = SgLang::from(SupportLang::Tsx);
+    let fixer = Fixer::from_str("bar($A)", &lang).expect("should work");
+    let grep = lang.ast_grep("a()\nb()\nfoo(1)\nc()\nd()\n");
+    let diffs = grep
+      .root()
+      .find_all("foo($A)")
+      .map(|n| Diff::generate(n, &"foo($A)", &fixer, LineEnding::Lf));
+    printer.print_diffs(diffs, "test.tsx".as_ref()).unwrap();
+  };
+  let printer = make_test_printer().heading(Heading::Never);
+  assert_eq!(printer.diff_context(), Some(3));
+  printer.set_diff_context(1);
+  assert_eq!(printer.diff_context(), Some(1));
+  print(&printer);
+  let text = get_text(&printer);
+  assert!(text.contains("@@ -1,3 +1,3 @@"), "{text}");
+  assert!(!text.contains("a()"), "{text}");
+  // a context larger than the file prints the whole file
+  let printer = make_test_printer().heading(Heading::Never);
+  printer.set_diff_context(100);
+  print(&printer);
+  assert!(get_text(&printer).contains("1 1│ a()\n"));
+}
+
+fn print_side_by_side(source: &str, screen: Option<(usize, usize)>) -> String {
+  let mut printer = make_test_printer()
+    .heading(Heading::Never)
+    .diff_style(DiffStyle::SideBySide);
+  printer.screen = screen;
+  let lang = SgLang::from(SupportLang::Tsx);
+  let fixer = Fixer::from_str("bar($$$A)", &lang).expect("should work");
+  let grep = lang.ast_grep(source);
+  let diffs = grep
+    .root()
+    .find_all("foo($$$A)")
+    .map(|n| Diff::generate(n, &"foo($$$A)", &fixer, LineEnding::Lf));
+  printer.print_diffs(diffs, "test.tsx".as_ref()).unwrap();
+  get_text(&printer)
+}
+
+#[test]
+fn test_side_by_side_diff() {
+  let text = print_side_by_side("a()\nfoo(1, x)\nb()\n", Some((80, 24)));
+  // each half is 39 columns, the line number, the sign and 36 columns of text
+  // hunk headers are always blue, like inline diffs
+  let header = Color::Blue.paint("@@ -0,3 +0,3 @@");
+  let expected = format!(
+    "test.tsx\n{header}\n{:<39}│1  a()\n{:<39}│2 +bar(1, x)\n{:<39}│3  b()\n",
+    "1  a()", "2 -foo(1, x)", "3  b()",
+  );
+  assert_eq!(text, expected);
+  // narrow terminals and piped output print inline diffs
+  let inline = print_side_by_side("a()\nfoo(1, x)\nb()\n", None);
+  assert!(inline.contains("2  │-foo(1, x)"), "{inline}");
+  assert_eq!(
+    print_side_by_side("a()\nfoo(1, x)\nb()\n", Some((60, 24))),
+    inline
+  );
+}
+
+#[test]
+fn test_side_by_side_wide_chars() {
+  let long = "x".repeat(50);
+  let source = format!("let s = '中文';\nfoo('中文', 1)\nfoo({long})\n");
+  let text = print_side_by_side(&source, Some((80, 24)));
+  for line in text.lines().skip(2) {
+    let (left, _) = line.split_once('│').expect("should have separator");
+    assert_eq!(left.width(), 39, "{line}");
+  }
+  assert!(
+    text.contains(&format!("│3 +bar({}…\n", "x".repeat(31))),
+    "{text}"
+  );
+}
+
 fn test_overlap_print_impl(heading: Heading) {
   let src = "
     Some(1)
@@ -240,7 +603,7 @@ fix: '{rewrite}'"
     let matcher = rule.get_matcher(&globals).expect("should parse");
     let fixer = matcher.fixer.as_ref().expect("should have fixer");
     let matches = grep.root().find_all(&matcher);
-    let diffs = matches.map(|n| (Diff::generate(n, &pattern, fixer), &rule));
+    let diffs = matches.map(|n| (Diff::generate(n, &pattern, fixer, LineEnding::Lf), &rule));
     printer
       .print_rule_diffs(diffs.collect(), Path::new("test.tsx"))
       .expect("test only");
@@ -284,3 +647,220 @@ fn test_before_after() {
     }
   }
 }
+
+fn print_with_theme(color: ColorChoice, theme: &str) -> String {
+  let theme = theme.parse().expect("theme should parse");
+  let printer = ColoredPrinter::new(Buffer::ansi())
+    .color(color)
+    .color_theme(theme)
+    .heading(Heading::Always);
+  let grep = SgLang::from(SupportLang::Tsx).ast_grep("let a = 123");
+  let matches = grep.root().find_all("123");
+  printer.print_matches(matches, "test.tsx".as_ref()).unwrap();
+  get_text(&printer)
+}
+
+#[test]
+fn test_color_theme() {
+  let red = print_with_theme(ColorChoice::Always, "match:red+bold");
+  let blue = print_with_theme(ColorChoice::Always, "match:blue+underline,path:green");
+  assert!(red.contains("\u{1b}[1;31m123\u{1b}[0m"), "{red:?}");
+  assert!(red.contains("\u{1b}[3;36mtest.tsx"), "{red:?}");
+  assert!(blue.contains("\u{1b}[4;34m123\u{1b}[0m"), "{blue:?}");
+  assert!(blue.contains("\u{1b}[32mtest.tsx"), "{blue:?}");
+  let never = print_with_theme(ColorChoice::Never, "match:blue+underline,path:green");
+  assert_eq!(never, "test.tsx\n1│let a = 123\n\n");
+}
+
+#[test]
+fn test_invalid_color_theme() {
+  let err = "match:red,matched:blue".parse::<ColorTheme>().unwrap_err();
+  let msg = err.to_string();
+  assert!(msg.contains("unknown style key `matched`"), "{msg}");
+  assert!(msg.contains("match, path, line, diff.add"), "{msg}");
+  assert!("match:rouge".parse::<ColorTheme>().is_err());
+  assert!("match".parse::<ColorTheme>().is_err());
+  let theme: ColorTheme = "diff.add:#00ff00+on-22,diff.del:none".parse().unwrap();
+  assert_ne!(theme, ColorTheme::default());
+}
+
+// CJK characters and emoji take two columns each and the tab moves to the next stop
+const WIDE_SOURCE: &str = "let s = '你好'; let e = '👨‍👩‍👧'; \tfoo(1)";
+// display width of the source before the tab
+const WIDE_PREFIX: usize = 34;
+
+#[test]
+fn test_wide_char_carets() {
+  for (tab_width, stop) in [(4, 36), (8, 40)] {
+    let printer = make_test_printer()
+      .tab_width(tab_width)
+      .style(ReportStyle::Rich);
+    let rule = make_rule("wide", "foo($A)");
+    print_file(&printer, "test.tsx", WIDE_SOURCE, &[&rule]);
+    let text = get_text(&printer);
+    // reported column counts characters
+    assert!(text.contains("test.tsx:1:33"), "{text}");
+    let carets = format!("  │ {}^^^^^^", " ".repeat(stop));
+    assert!(text.lines().any(|l| l == carets), "{text}");
+  }
+}
+
+#[test]
+fn test_wide_char_highlight() {
+  for (tab_width, stop) in [(4, 36), (8, 40)] {
+    let printer = make_test_printer()
+      .tab_width(tab_width)
+      .heading(Heading::Always);
+    let grep = SgLang::from(SupportLang::Tsx).ast_grep(WIDE_SOURCE);
+    let matches = grep.root().find_all("foo($A)");
+    printer.print_matches(matches, "test.tsx".as_ref()).unwrap();
+    let spaces = " ".repeat(stop - WIDE_PREFIX);
+    let line = WIDE_SOURCE.replace('\t', &spaces);
+    assert_eq!(get_text(&printer), format!("test.tsx\n1│{line}\n\n"));
+  }
+}
+
+#[test]
+fn test_colored_gutter_padding() {
+  let printer = ColoredPrinter::new(Buffer::ansi())
+    .color(ColorChoice::Always)
+    .heading(Heading::Always);
+  let source = format!("{}Some(1,\n2)", "\n".repeat(8));
+  let grep = SgLang::from(SupportLang::Tsx).ast_grep(&source);
+  let matches = grep.root().find_all("Some($$$)");
+  printer.print_matches(matches, "test.tsx".as_ref()).unwrap();
+  let text = get_text(&printer);
+  assert!(text.contains("\u{1b}[2m 9\u{1b}[0m│"), "{text:?}");
+  assert!(text.contains("\u{1b}[2m10\u{1b}[0m│"), "{text:?}");
+}
+
+const CONTEXT_SOURCE: &str = "function outer() {
+  let x = 1;
+  foo(1);
+  foo(2);
+  return x;
+}
+
+
+
+function other() {
+  foo(3);
+}
+";
+
+#[test]
+fn test_rule_context_frames() {
+  let printer = make_test_printer().style(ReportStyle::Rich).context((1, 1));
+  let rule = make_rule("no-foo", "foo($A)");
+  print_file(&printer, "ctx.ts", CONTEXT_SOURCE, &[&rule]);
+  let expected = "\
+help[no-foo]: no-foo message
+help[no-foo]: no-foo message
+  ┌─ ctx.ts:3:3
+  │
+2 │   let x = 1;
+3 │   foo(1);
+  │   ^^^^^^
+4 │   foo(2);
+  │   ^^^^^^
+5 │   return x;
+
+help[no-foo]: no-foo message
+   ┌─ ctx.ts:11:3
+   │
+10 │ function other() {
+11 │   foo(3);
+   │   ^^^^^^
+12 │ }
+
+";
+  assert_eq!(get_text(&printer), expected);
+}
+
+#[test]
+fn test_short_style_ignores_context() {
+  let printer = make_test_printer()
+    .style(ReportStyle::Short)
+    .context((1, 1));
+  let rule = make_rule("no-foo", "foo($A)");
+  print_file(&printer, "ctx.ts", CONTEXT_SOURCE, &[&rule]);
+  let text = get_text(&printer);
+  assert_eq!(text.lines().count(), 3, "{text}");
+  assert!(!text.contains("let x"), "{text}");
+}
+
+fn paint(source: &str, matched: &[Range<usize>]) -> String {
+  let grep = SgLang::from(SupportLang::TypeScript).ast_grep(source);
+  let range = 0..source.len();
+  highlight::Highlights::new(grep.root(), range.clone()).paint(source, range, matched, 4)
+}
+
+#[test]
+fn test_highlight_tokens() {
+  let text = paint("let a = 'x' // c", &[]);
+  let keyword = Color::Purple.paint("let");
+  let string = Color::Green.paint("'x'");
+  let comment = Color::Fixed(244).italic().paint("// c");
+  assert_eq!(text, format!("{keyword} a = {string} {comment}"));
+}
+
+#[test]
+fn test_highlight_expand_tabs() {
+  // tab stops are counted in the source text, not in the painted text
+  let text = paint("let\ta = '\t'", &[]);
+  let keyword = Color::Purple.paint("let");
+  let string = Color::Green.paint("'   '");
+  assert_eq!(text, format!("{keyword} a = {string}"));
+}
+
+#[test]
+fn test_highlight_match_per_line() {
+  let text = paint("f(1,\r\n2)", std::slice::from_ref(&(0..8)));
+  let matched = Style::new().bold().underline();
+  let number = Color::Cyan.bold().underline();
+  // styles end before line endings so lines can be printed separately
+  let expected = format!(
+    "{}{}{}\r\n{}{}",
+    matched.paint("f("),
+    number.paint("1"),
+    matched.paint(","),
+    number.paint("2"),
+    matched.paint(")"),
+  );
+  assert_eq!(text, expected);
+}
+
+#[test]
+fn test_highlight_matches() {
+  let print = |color| {
+    let printer = ColoredPrinter::new(Buffer::no_color())
+      .color(color)
+      .heading(Heading::Always)
+      .highlight(true);
+    let grep = SgLang::from(SupportLang::TypeScript).ast_grep("let a = 1\nfoo(a)\n");
+    let matches = grep.root().find_all("foo($A)");
+    printer.print_matches(matches, "a.ts".as_ref()).unwrap();
+    get_text(&printer)
+  };
+  let text = print(ColorChoice::Always);
+  let matched = Style::new().bold().underline().paint("foo(a)");
+  assert!(text.contains(&format!("│{matched}\n")), "{text:?}");
+  // highlighting is ignored without colors
+  let text = print(ColorChoice::Never);
+  assert_eq!(text, "a.ts\n2│foo(a)\n\n");
+}
+
+#[test]
+fn test_highlight_rule_frames() {
+  let printer = ColoredPrinter::new(Buffer::no_color())
+    .color(ColorChoice::Always)
+    .style(ReportStyle::Rich)
+    .highlight(true);
+  let rule = make_rule("no-foo", "foo($A)");
+  print_file(&printer, "ctx.ts", CONTEXT_SOURCE, &[&rule]);
+  let text = get_text(&printer);
+  // frames are printed without context, with the syntax of matched lines highlighted
+  let number = Color::Cyan.bold().underline().paint("1");
+  assert!(text.contains(&format!("{number}")), "{text:?}");
+  assert!(!text.contains("let x"), "{text:?}");
+}
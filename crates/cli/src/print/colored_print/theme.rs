@@ -0,0 +1,135 @@
+//! Color theme of the terminal printers, see `--color-theme`.
+//! A theme is a comma-separated list of `key:style` like `match:red+bold,path:cyan`.
+use super::PrintStyles;
+
+use ansi_term::{Color, Style};
+use anyhow::{bail, Context, Error, Result};
+
+use std::str::FromStr;
+
+/// Keys of the styles that a theme can override.
+const STYLE_KEYS: &[&str] = &[
+  "match",
+  "path",
+  "line",
+  "diff.add",
+  "diff.add.emphasis",
+  "diff.del",
+  "diff.del.emphasis",
+  "error",
+  "warning",
+  "info",
+  "hint",
+  "message",
+  "note",
+];
+
+const COLORS: &[(&str, Color)] = &[
+  ("black", Color::Black),
+  ("red", Color::Red),
+  ("green", Color::Green),
+  ("yellow", Color::Yellow),
+  ("blue", Color::Blue),
+  ("purple", Color::Purple),
+  ("magenta", Color::Purple),
+  ("cyan", Color::Cyan),
+  ("white", Color::White),
+];
+
+/// Style overrides applied on top of the default colored styles.
+#[derive(Clone, Default, Debug, PartialEq)]
+pub struct ColorTheme {
+  styles: Vec<(&'static str, Style)>,
+}
+
+impl ColorTheme {
+  pub(super) fn apply(&self, styles: &mut PrintStyles) {
+    for (key, style) in &self.styles {
+      let target = match *key {
+        "match" => &mut styles.matched,
+        "path" => &mut styles.file_path,
+        "line" => &mut styles.line_num,
+        "diff.add" => &mut styles.insert,
+        "diff.add.emphasis" => &mut styles.insert_emphasis,
+        "diff.del" => &mut styles.delete,
+        "diff.del.emphasis" => &mut styles.delete_emphasis,
+        "error" => &mut styles.rule.error,
+        "warning" => &mut styles.rule.warning,
+        "info" => &mut styles.rule.info,
+        "hint" => &mut styles.rule.hint,
+        "message" => &mut styles.rule.message,
+        "note" => &mut styles.rule.note,
+        _ => unreachable!("theme keys are validated when parsed"),
+      };
+      *target = *style;
+    }
+  }
+}
+
+impl FromStr for ColorTheme {
+  type Err = Error;
+  fn from_str(spec: &str) -> Result<Self> {
+    let mut styles = vec![];
+    for item in spec.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+      let Some((key, style)) = item.split_once(':') else {
+        bail!("`{item}` should be in the form of `key:style`");
+      };
+      let key = key.trim();
+      let Some(key) = STYLE_KEYS.iter().find(|k| **k == key) else {
+        bail!(
+          "unknown style key `{key}`, valid keys are: {}",
+          STYLE_KEYS.join(", ")
+        );
+      };
+      let style = parse_style(style.trim()).with_context(|| format!("invalid style of `{key}`"))?;
+      styles.push((*key, style));
+    }
+    Ok(Self { styles })
+  }
+}
+
+/// Parse `+`-separated attributes: colors, `on-<color>` backgrounds and modifiers.
+/// `none` resets the style to plain text.
+fn parse_style(spec: &str) -> Result<Style> {
+  let mut style = Style::new();
+  for attr in spec.split('+').map(str::trim) {
+    style = match attr {
+      "none" => Style::new(),
+      "bold" => style.bold(),
+      "dim" => style.dimmed(),
+      "italic" => style.italic(),
+      "underline" => style.underline(),
+      "reverse" => style.reverse(),
+      "strikethrough" => style.strikethrough(),
+      _ => match attr.strip_prefix("on-") {
+        Some(bg) => style.on(parse_color(bg)?),
+        None => style.fg(parse_color(attr)?),
+      },
+    };
+  }
+  Ok(style)
+}
+
+/// Parse a color name, an ANSI 256 color number or a `#rrggbb` hex color.
+fn parse_color(color: &str) -> Result<Color> {
+  if let Some((_, c)) = COLORS.iter().find(|(name, _)| *name == color) {
+    return Ok(*c);
+  }
+  if let Ok(n) = color.parse::<u8>() {
+    return Ok(Color::Fixed(n));
+  }
+  if let Some(hex) = color
+    .strip_prefix('#')
+    .filter(|h| h.len() == 6 && h.is_ascii())
+  {
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16);
+    if let (Ok(r), Ok(g), Ok(b)) = (channel(0), channel(2), channel(4)) {
+      return Ok(Color::RGB(r, g, b));
+    }
+  }
+  let names: Vec<_> = COLORS.iter().map(|(name, _)| *name).collect();
+  bail!(
+    "unknown color or attribute `{color}`, use one of {}, a number from 0 to 255 or #rrggbb",
+    names.join(", ")
+  )
+}
@@ -0,0 +1,394 @@
+use super::colored_print::{print_diff, terminal_screen, DiffOptions, DiffStyle, PrintStyles};
+use super::{Diff, Printer};
+use crate::lang::SgLang;
+use ast_grep_config::RuleConfig;
+
+use anyhow::Result;
+use ast_grep_core::{NodeMatch as SgNodeMatch, StrDoc};
+use codespan_reporting::files::SimpleFile;
+use codespan_reporting::term::termcolor::{
+  Color, ColorChoice, ColorSpec, StandardStream, WriteColor,
+};
+use similar::{ChangeTag, TextDiff};
+
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+type NodeMatch<'a, L> = SgNodeMatch<'a, StrDoc<L>>;
+
+// add this macro because neither trait_alias nor type_alias_impl is supported.
+macro_rules! Matches {
+  ($lt: lifetime) => { impl Iterator<Item = NodeMatch<$lt, SgLang>> };
+}
+macro_rules! Diffs {
+  ($lt: lifetime) => { impl Iterator<Item = Diff<$lt>> };
+}
+
+/// Prints all fixes as one unified diff without modifying files.
+/// The output can be applied by `git apply`, unless it is printed side by side.
+/// Fixes are collected until all files are scanned, because a file with injected languages
+/// sends fixes of every language separately, but needs one diff.
+pub struct DiffPrinter<W: WriteColor + Send + Sync> {
+  writer: Mutex<W>,
+  files: Mutex<BTreeMap<PathBuf, FileFixes>>,
+  styles: PrintStyles,
+  diff_style: DiffStyle,
+  /// terminal size to print side by side, None if not a terminal
+  screen: Option<(usize, usize)>,
+}
+
+impl DiffPrinter<StandardStream> {
+  pub fn stdout<C: Into<ColorChoice>>(color: C) -> Self {
+    let color = color.into();
+    let mut printer = Self::new(StandardStream::stdout(color));
+    printer.styles = PrintStyles::from(color);
+    printer.screen = terminal_screen();
+    printer
+  }
+}
+
+impl<W: WriteColor + Send + Sync> DiffPrinter<W> {
+  pub fn new(writer: W) -> Self {
+    Self {
+      writer: Mutex::new(writer),
+      files: Mutex::new(BTreeMap::new()),
+      styles: PrintStyles::from(ColorChoice::Never),
+      diff_style: DiffStyle::Inline,
+      screen: None,
+    }
+  }
+
+  /// Print side by side in a wide terminal. Other output stays a unified diff.
+  pub fn diff_style(mut self, style: DiffStyle) -> Self {
+    self.diff_style = style;
+    self
+  }
+}
+
+/// The original text of a file and the fixes of all rules and languages in it.
+struct FileFixes {
+  old: String,
+  /// range, replacement and rule id of every fix
+  fixes: Vec<(Range<usize>, String, String)>,
+}
+
+impl<W: WriteColor + Send + Sync> Printer for DiffPrinter<W> {
+  // matches without fix do not change code
+  fn print_rule<'a>(
+    &self,
+    _matches: Matches!('a),
+    _file: SimpleFile<Cow<str>, &String>,
+    _rule: &RuleConfig<SgLang>,
+  ) -> Result<()> {
+    Ok(())
+  }
+
+  fn print_matches<'a>(&self, _matches: Matches!('a), _path: &Path) -> Result<()> {
+    Ok(())
+  }
+
+  fn print_diffs<'a>(&self, diffs: Diffs!('a), path: &Path) -> Result<()> {
+    let diffs: Vec<_> = diffs.map(|d| (d, None)).collect();
+    self.print_file_diff(diffs, path)
+  }
+
+  fn print_rule_diffs(
+    &self,
+    diffs: Vec<(Diff<'_>, &RuleConfig<SgLang>)>,
+    path: &Path,
+  ) -> Result<()> {
+    let diffs = diffs.into_iter().map(|(d, r)| (d, Some(r))).collect();
+    self.print_file_diff(diffs, path)
+  }
+
+  fn after_print(&self) -> Result<()> {
+    let files = std::mem::take(&mut *self.files.lock().expect("should not fail"));
+    let mut writer = self.writer.lock().expect("should not fail");
+    let options = DiffOptions::new(3, self.diff_style, self.screen);
+    for (path, file) in files {
+      let fixes = file.fixes.iter().map(|(range, replacement, rule_id)| Fix {
+        range: range.clone(),
+        replacement,
+        rule_id,
+      });
+      let (new, _) = apply_fixes(&file.old, fixes.collect(), &path);
+      if options.side_by_side.is_some() && file.old != new {
+        print_file_header(&path, &mut *writer)?;
+        print_diff(&file.old, &new, &self.styles, &mut *writer, &options)?;
+      } else {
+        print_unified_diff(&file.old, &new, &path, &mut *writer)?;
+      }
+    }
+    Ok(())
+  }
+}
+
+impl<W: WriteColor + Send + Sync> DiffPrinter<W> {
+  fn print_file_diff(
+    &self,
+    diffs: Vec<(Diff<'_>, Option<&RuleConfig<SgLang>>)>,
+    path: &Path,
+  ) -> Result<()> {
+    let Some((first, _)) = diffs.first() else {
+      return Ok(());
+    };
+    let mut files = self.files.lock().expect("should not fail");
+    let file = files
+      .entry(path.to_path_buf())
+      .or_insert_with(|| FileFixes {
+        old: first.get_root_text().to_string(),
+        fixes: vec![],
+      });
+    for (diff, rule) in &diffs {
+      let fix = Fix::new(diff, *rule);
+      let entry = (
+        fix.range,
+        fix.replacement.to_string(),
+        fix.rule_id.to_string(),
+      );
+      file.fixes.push(entry);
+    }
+    Ok(())
+  }
+}
+
+/// A replacement of a range in the source text, produced by the fix of a rule.
+pub struct Fix<'a> {
+  pub range: Range<usize>,
+  pub replacement: &'a str,
+  /// rule id to report overlapping fixes
+  pub rule_id: &'a str,
+}
+
+impl<'a> Fix<'a> {
+  pub fn new(diff: &'a Diff<'_>, rule: Option<&'a RuleConfig<SgLang>>) -> Self {
+    Self {
+      range: diff.range.clone(),
+      replacement: &diff.replacement,
+      rule_id: rule.map_or("rewrite", |r| r.id.as_str()),
+    }
+  }
+}
+
+/// Apply all non-overlapping fixes to the source text of one file.
+/// A fix overlapping with a previous one is skipped with a message in stderr.
+/// Returns the new text and the number of applied fixes.
+pub fn apply_fixes(old: &str, mut fixes: Vec<Fix<'_>>, path: &Path) -> (String, usize) {
+  fixes.sort_by_key(|f| (f.range.start, f.range.end));
+  let mut new = String::with_capacity(old.len());
+  let mut start = 0;
+  let mut applied = 0;
+  let mut last_rule = "";
+  for fix in &fixes {
+    if fix.range.start < start {
+      eprintln!(
+        "Skipped fix of rule `{}` in {} because it overlaps with the fix of rule `{}`.",
+        fix.rule_id,
+        path.display(),
+        last_rule,
+      );
+      continue;
+    }
+    new.push_str(&old[start..fix.range.start]);
+    new.push_str(fix.replacement);
+    start = fix.range.end;
+    applied += 1;
+    last_rule = fix.rule_id;
+  }
+  new.push_str(&old[start..]);
+  (new, applied)
+}
+
+fn print_unified_diff(
+  old: &str,
+  new: &str,
+  path: &Path,
+  writer: &mut impl WriteColor,
+) -> Result<()> {
+  if old == new {
+    return Ok(());
+  }
+  let diff = TextDiff::from_lines(old, new);
+  print_file_header(path, writer)?;
+  for hunk in diff.unified_diff().context_radius(3).iter_hunks() {
+    writer.set_color(ColorSpec::new().set_fg(Some(Color::Cyan)))?;
+    writeln!(writer, "{}", hunk.header())?;
+    writer.reset()?;
+    for change in hunk.iter_changes() {
+      let (sign, color) = match change.tag() {
+        ChangeTag::Delete => ('-', Some(Color::Red)),
+        ChangeTag::Insert => ('+', Some(Color::Green)),
+        ChangeTag::Equal => (' ', None),
+      };
+      writer.set_color(ColorSpec::new().set_fg(color))?;
+      write!(writer, "{sign}{}", change.value())?;
+      writer.reset()?;
+      if change.missing_newline() {
+        writeln!(writer)?;
+        writeln!(writer, "\\ No newline at end of file")?;
+      }
+    }
+  }
+  Ok(())
+}
+
+fn print_file_header(path: &Path, writer: &mut impl WriteColor) -> Result<()> {
+  let path = path.to_string_lossy().replace('\\', "/");
+  writer.set_color(ColorSpec::new().set_bold(true))?;
+  writeln!(writer, "diff --git a/{path} b/{path}")?;
+  writeln!(writer, "--- a/{path}")?;
+  writeln!(writer, "+++ b/{path}")?;
+  writer.reset()?;
+  Ok(())
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::print::LineEnding;
+  use ast_grep_config::{from_yaml_string, GlobalRules};
+  use ast_grep_language::{Language, SupportLang};
+  use codespan_reporting::term::termcolor::Buffer;
+
+  fn get_text(printer: &DiffPrinter<Buffer>) -> String {
+    let buffer = printer.writer.lock().expect("should work");
+    String::from_utf8(buffer.as_slice().to_vec()).expect("should be valid utf8")
+  }
+
+  fn make_rule(id: &str, pattern: &str, fix: &str) -> RuleConfig<SgLang> {
+    let globals = GlobalRules::default();
+    let rule =
+      format!("{{id: {id}, language: TypeScript, rule: {{pattern: '{pattern}'}}, fix: '{fix}'}}");
+    from_yaml_string(&rule, &globals)
+      .expect("should parse")
+      .pop()
+      .unwrap()
+  }
+
+  #[test]
+  fn test_print_unified_diff() {
+    let printer = DiffPrinter::new(Buffer::no_color());
+    let src = "let a = 1\nlet b = 2\nlet c = 3";
+    let grep = SgLang::from(SupportLang::TypeScript).ast_grep(src);
+    let rule = make_rule("to-const", "let $A = $B", "const $A = $B");
+    let fixer = rule.matcher.fixer.as_ref().expect("should have fix");
+    let diffs = grep
+      .root()
+      .find_all(&rule.matcher)
+      .map(|m| {
+        (
+          Diff::generate(m, &rule.matcher, fixer, LineEnding::Lf),
+          &rule,
+        )
+      })
+      .collect();
+    printer
+      .print_rule_diffs(diffs, Path::new("src/a.ts"))
+      .expect("should print");
+    printer.after_print().expect("should print");
+    let expected = "\
+diff --git a/src/a.ts b/src/a.ts
+--- a/src/a.ts
++++ b/src/a.ts
+@@ -1,3 +1,3 @@
+-let a = 1
+-let b = 2
+-let c = 3
+\\ No newline at end of file
++const a = 1
++const b = 2
++const c = 3
+\\ No newline at end of file
+";
+    assert_eq!(get_text(&printer), expected);
+  }
+
+  #[test]
+  fn test_print_side_by_side_in_terminal() {
+    let print = |screen| {
+      let mut printer = DiffPrinter::new(Buffer::no_color()).diff_style(DiffStyle::SideBySide);
+      printer.screen = screen;
+      let grep = SgLang::from(SupportLang::TypeScript).ast_grep("let a = 1\n");
+      let rule = make_rule("to-const", "let $A = $B", "const $A = $B");
+      let fixer = rule.matcher.fixer.as_ref().expect("should have fix");
+      let diffs = grep
+        .root()
+        .find_all(&rule.matcher)
+        .map(|m| {
+          (
+            Diff::generate(m, &rule.matcher, fixer, LineEnding::Lf),
+            &rule,
+          )
+        })
+        .collect();
+      printer
+        .print_rule_diffs(diffs, Path::new("a.ts"))
+        .expect("should print");
+      printer.after_print().expect("should print");
+      get_text(&printer)
+    };
+    let text = print(Some((80, 24)));
+    assert!(text.starts_with("diff --git a/a.ts b/a.ts\n"), "{text}");
+    assert!(
+      text.contains(&format!("{:<39}│1 +const a = 1\n", "1 -let a = 1")),
+      "{text}"
+    );
+    // piped output stays a unified diff
+    assert!(print(None).contains("\n-let a = 1\n+const a = 1\n"));
+  }
+
+  #[test]
+  fn test_skip_overlapping_fix() {
+    let printer = DiffPrinter::new(Buffer::no_color());
+    let src = "Some(Some(1))\n";
+    let grep = SgLang::from(SupportLang::TypeScript).ast_grep(src);
+    let rule = make_rule("unwrap", "Some($A)", "$A");
+    let fixer = rule.matcher.fixer.as_ref().expect("should have fix");
+    let diffs = grep
+      .root()
+      .find_all(&rule.matcher)
+      .map(|m| {
+        (
+          Diff::generate(m, &rule.matcher, fixer, LineEnding::Lf),
+          &rule,
+        )
+      })
+      .collect();
+    printer
+      .print_rule_diffs(diffs, Path::new("a.ts"))
+      .expect("should print");
+    printer.after_print().expect("should print");
+    let text = get_text(&printer);
+    assert!(text.contains("-Some(Some(1))\n+Some(1)\n"), "{text}");
+  }
+
+  #[test]
+  fn test_one_diff_per_file() {
+    let printer = DiffPrinter::new(Buffer::no_color());
+    let src = "let a = 1\nlet b = 2\n";
+    let grep = SgLang::from(SupportLang::TypeScript).ast_grep(src);
+    let rule = make_rule("to-const", "let $A = $B", "const $A = $B");
+    let fixer = rule.matcher.fixer.as_ref().expect("should have fix");
+    // fixes of injected languages are sent separately for the same file
+    for m in grep.root().find_all(&rule.matcher) {
+      let diffs = vec![(
+        Diff::generate(m, &rule.matcher, fixer, LineEnding::Lf),
+        &rule,
+      )];
+      printer
+        .print_rule_diffs(diffs, Path::new("a.ts"))
+        .expect("should print");
+    }
+    assert_eq!(get_text(&printer), "");
+    printer.after_print().expect("should print");
+    let text = get_text(&printer);
+    assert_eq!(text.matches("diff --git").count(), 1, "{text}");
+    assert!(
+      text.contains("-let a = 1\n-let b = 2\n+const a = 1\n+const b = 2\n"),
+      "{text}"
+    );
+  }
+}
@@ -1,14 +1,29 @@
-use super::{Diff, Printer};
+mod editor;
+mod filter;
+mod session;
+mod undo;
+
+use super::json_print::fingerprint;
+use super::{apply_fixes, ColorArg, Diff, Fix, PathStyle, Printer};
 use crate::lang::SgLang;
 use crate::utils;
 use crate::utils::ErrorContext as EC;
+use editor::Position;
+use filter::{Filtering, HiddenDiff, RuleFilter};
+pub use session::print_session_report;
+use session::{Decision, Record, Session};
+use undo::{History, Undo};
 
+use ansi_term::Style;
 use anyhow::{Context, Result};
 use ast_grep_config::RuleConfig;
-use ast_grep_core::{NodeMatch as SgNodeMatch, StrDoc};
+use ast_grep_core::{AstGrep, Language, NodeMatch as SgNodeMatch, StrDoc};
 use codespan_reporting::files::SimpleFile;
 
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::collections::BTreeMap;
+use std::ops::Range;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Mutex;
 
 type NodeMatch<'a, L> = SgNodeMatch<'a, StrDoc<L>>;
 
@@ -27,6 +42,50 @@ pub struct InteractivePrinter<P: Printer> {
   accept_all: AtomicBool,
   from_stdin: bool,
   inner: P,
+  files_modified: AtomicUsize,
+  edits_applied: AtomicUsize,
+  /// fixes written in the session, to undo them
+  history: Mutex<History>,
+  /// review findings rule by rule, see `--group-by rule`
+  rule_session: Option<Mutex<RuleSession>>,
+  /// decisions recorded in a file, see `--session`
+  session: Option<Session>,
+  /// files whose fixes are skipped because they changed after scanning
+  stale_files: Mutex<Vec<PathBuf>>,
+  /// review only some rules, see the `/` key
+  filtering: Mutex<Filtering>,
+  /// style of the path in the status line, see --path-style
+  path_style: PathStyle,
+  /// whether the status line is painted, resolved from --color for stdout
+  colored: bool,
+}
+
+#[derive(Default, Clone, Copy, PartialEq)]
+enum RuleAction {
+  #[default]
+  Review,
+  ApplyAll,
+  Skip,
+}
+
+/// Accepted edits of one file in a rule session.
+struct PendingEdits {
+  source: String,
+  lang: SgLang,
+  edits: Vec<AcceptedFix>,
+}
+
+/// State of an interactive session that reviews findings rule by rule.
+/// Edits are written after the session so edits of different rules in one file do not conflict.
+#[derive(Default)]
+struct RuleSession {
+  rule_id: String,
+  message: String,
+  remaining: usize,
+  action: RuleAction,
+  /// applied and skipped fixes of every rule
+  stats: BTreeMap<String, (usize, usize)>,
+  pending: BTreeMap<PathBuf, PendingEdits>,
 }
 
 impl<P: Printer> InteractivePrinter<P> {
@@ -38,16 +97,68 @@ impl<P: Printer> InteractivePrinter<P> {
         accept_all: AtomicBool::new(accept_all),
         from_stdin,
         inner,
+        files_modified: AtomicUsize::new(0),
+        edits_applied: AtomicUsize::new(0),
+        history: Mutex::default(),
+        rule_session: None,
+        session: None,
+        stale_files: Mutex::default(),
+        filtering: Mutex::default(),
+        path_style: PathStyle::default(),
+        colored: false,
       })
     }
   }
 
+  /// Print the file path of the status line in the style.
+  pub fn path_style(mut self, style: PathStyle) -> Self {
+    self.path_style = style;
+    self
+  }
+
+  /// Paint the status line if --color resolves to colors for stdout.
+  pub fn color(mut self, color: ColorArg) -> Self {
+    self.colored = color.should_use_color();
+    self
+  }
+
+  /// Review findings rule by rule. Findings must be sent after `before_rule`.
+  pub fn group_by_rule(mut self, by_rule: bool) -> Self {
+    self.rule_session = by_rule.then(Mutex::default);
+    self
+  }
+
+  /// Record decisions in the session file and skip findings decided in previous sessions.
+  pub fn session(mut self, path: Option<&Path>) -> Result<Self> {
+    self.session = path.map(Session::open).transpose()?;
+    Ok(self)
+  }
+
+  /// Whether the finding was applied or skipped in a previous session.
+  /// An applied finding is found again if its fix is missing from the file, then it is reviewed again.
+  fn previous_decision(&self, fix: &AcceptedFix) -> Option<Decision> {
+    self.session.as_ref()?.previous(&fix.fingerprint)
+  }
+
+  fn record_decision(&self, path: &Path, fix: &AcceptedFix, decision: Decision) -> Result<()> {
+    let Some(session) = &self.session else {
+      return Ok(());
+    };
+    session.record(&Record {
+      fingerprint: fix.fingerprint.clone(),
+      decision,
+      rule_id: fix.rule_id.clone(),
+      file: path.to_string_lossy().into_owned(),
+      line: fix.line,
+    })
+  }
+
   fn prompt_edit(&self) -> char {
     if self.accept_all.load(Ordering::SeqCst) {
       return 'a';
     }
-    const EDIT_PROMPT: &str = "Accept change? (Yes[y], No[n], Accept All[a], Quit[q], Edit[e])";
-    utils::prompt(EDIT_PROMPT, "ynaqe", Some('n')).expect("Error happened during prompt")
+    const EDIT_PROMPT: &str = "Accept change? (Yes[y], No[n], Accept File[f], Skip File[s], Accept All[a], Defer[d], Previous[p], Undo[u], Filter[/], More context[+], Less context[-], Quit[q], Edit[e])";
+    utils::prompt(EDIT_PROMPT, "ynfsadpu/+-qe", Some('n')).expect("Error happened during prompt")
   }
 
   fn prompt_view(&self) -> char {
@@ -58,18 +169,344 @@ impl<P: Printer> InteractivePrinter<P> {
     utils::prompt(VIEW_PROMPT, "qe", Some('\n')).expect("cannot fail")
   }
 
-  fn rewrite_action(&self, diffs: Vec<Diff<'_>>, path: &PathBuf) -> Result<()> {
-    if diffs.is_empty() {
-      return Ok(());
+  fn prompt_gone(&self) -> char {
+    const GONE_PROMPT: &str = "Next[enter], Quit[q]";
+    utils::prompt(GONE_PROMPT, "q", Some('\n')).expect("cannot fail")
+  }
+
+  fn view_action(&self, resp: char, path: &Path, pos: Position) -> Result<()> {
+    match resp {
+      'q' => Err(anyhow::anyhow!("Exit interactive editing")),
+      'e' => self.edit_file(path, pos),
+      _ => Ok(()),
+    }
+  }
+
+  /// Write fixes to the file, or print the new content if the code is from stdin.
+  /// Written fixes are recorded to undo them later.
+  /// Returns false without writing if the file is no longer `source`, whose offsets the fixes use.
+  fn write_fixes(
+    &self,
+    path: &Path,
+    lang: SgLang,
+    source: &str,
+    fixes: &[AcceptedFix],
+  ) -> Result<bool> {
+    if fixes.is_empty() {
+      return Ok(true);
     }
-    let new_content = apply_rewrite(diffs);
+    if !self.from_stdin {
+      let current = read_source(path)?;
+      if current != source {
+        return Ok(false);
+      }
+    }
+    let refs = fixes
+      .iter()
+      .map(|fix| Fix {
+        range: fix.range.clone(),
+        replacement: &fix.replacement,
+        rule_id: &fix.rule_id,
+      })
+      .collect();
+    let (new_content, applied) = apply_fixes(source, refs, path);
     if self.from_stdin {
       println!("{new_content}");
-      Ok(())
+      return Ok(true);
+    }
+    utils::write_source(path, &new_content).with_context(|| EC::WriteFile(path.to_path_buf()))?;
+    let mut history = self.history.lock().expect("should not fail");
+    if !history.has_file(path) {
+      self.files_modified.fetch_add(1, Ordering::AcqRel);
+    }
+    let written = history.record(path, lang, source, fixes);
+    self.edits_applied.fetch_add(applied, Ordering::AcqRel);
+    for fix in written {
+      self.record_decision(path, fix, Decision::Applied)?;
+    }
+    Ok(true)
+  }
+
+  /// Skip the fixes of a file changed after scanning, and list it in the summary.
+  fn skip_stale(&self, path: &Path) {
+    eprintln!("{}", stale_warning(path));
+    let mut stale = self.stale_files.lock().expect("should not fail");
+    if !stale.iter().any(|p| p == path) {
+      stale.push(path.to_path_buf());
+    }
+  }
+
+  /// Revert the last written fix, the summary only counts the remaining fixes.
+  fn undo(&self) -> Result<Undo> {
+    let mut history = self.history.lock().expect("should not fail");
+    let undone = history.undo()?;
+    if let Undo::Reverted(fix) = &undone {
+      self.edits_applied.fetch_sub(1, Ordering::AcqRel);
+      if !history.has_file(&fix.path) {
+        self.files_modified.fetch_sub(1, Ordering::AcqRel);
+      }
+    }
+    Ok(undone)
+  }
+
+  /// Open the file at the position, the alternate screen must have been left.
+  /// Code from stdin has no file to edit.
+  fn edit_file(&self, path: &Path, pos: Position) -> Result<()> {
+    if self.from_stdin {
+      return Err(anyhow::anyhow!(EC::StdInIsNotInteractive));
+    }
+    editor::open_in_editor(path, pos)
+  }
+
+  /// Change the context lines of diffs for the rest of the session.
+  /// Returns the message to show with the diff.
+  fn resize_context(&self, grow: bool, lines: usize) -> String {
+    let Some(context) = self.inner.diff_context() else {
+      return "The diff is printed without context.".into();
+    };
+    let context = resize(context, grow, lines);
+    self.inner.set_diff_context(context);
+    if context >= lines {
+      "Showing the whole file.".into()
     } else {
-      std::fs::write(path, new_content).with_context(|| EC::WriteFile(path.clone()))
+      let plural = if context == 1 { "" } else { "s" };
+      format!("Showing {context} line{plural} of context.")
     }
   }
+
+  fn is_hidden(&self, rule_id: &str) -> bool {
+    let filtering = self.filtering.lock().expect("should not fail");
+    filtering.is_hidden(rule_id)
+  }
+
+  fn hide(&self, path: &Path, diff: &Diff<'_>, rule_id: &str) {
+    let hidden = HiddenDiff::new(path.to_path_buf(), diff, rule_id);
+    let mut filtering = self.filtering.lock().expect("should not fail");
+    filtering.hidden.push(hidden);
+  }
+
+  /// Review only the rules matching the pattern, an empty pattern clears the filter.
+  /// Returns if a filter is active.
+  fn set_filter(&self, pattern: &str) -> Result<bool> {
+    let pattern = pattern.trim();
+    let filter = if pattern.is_empty() {
+      None
+    } else {
+      Some(RuleFilter::new(pattern)?)
+    };
+    let mut filtering = self.filtering.lock().expect("should not fail");
+    filtering.filter = filter;
+    Ok(filtering.filter.is_some())
+  }
+
+  /// Shown with every diff while a filter is active.
+  fn filter_status(&self) -> Option<String> {
+    let filtering = self.filtering.lock().expect("should not fail");
+    let filter = filtering.filter.as_ref()?;
+    Some(format!(
+      "Filter `{}`, {} hidden",
+      filter.pattern(),
+      filtering.hidden.len()
+    ))
+  }
+
+  /// Review the diffs hidden by a filter, once the filter is cleared.
+  /// Returns if all diffs are accepted.
+  fn review_hidden(&self) -> Result<bool> {
+    let mut all = false;
+    loop {
+      let files = {
+        let mut filtering = self.filtering.lock().expect("should not fail");
+        if filtering.filter.is_some() || filtering.hidden.is_empty() {
+          return Ok(all);
+        }
+        filtering.take_hidden()
+      };
+      for (path, hidden) in files {
+        let grep = reparse(&path, hidden[0].lang)?;
+        // matches changed by fixes written in the meantime are dropped like overlapping ones
+        let mut diffs: Vec<_> = hidden
+          .iter()
+          .filter_map(|h| Some((h.restore(&grep)?, DiffRule::Id(&h.rule_id))))
+          .collect();
+        diffs.sort_by_key(|(d, _)| d.range.start);
+        all = review_diffs(self, &path, diffs)? || all;
+      }
+    }
+  }
+
+  /// Ask to review the diffs still hidden by the filter at the end of the session.
+  fn finish_filtering(&self) -> Result<()> {
+    let (pattern, count) = {
+      let filtering = self.filtering.lock().expect("should not fail");
+      match &filtering.filter {
+        Some(filter) if !filtering.hidden.is_empty() => {
+          (filter.pattern().to_string(), filtering.hidden.len())
+        }
+        _ => return Ok(()),
+      }
+    };
+    if !self.accept_all.load(Ordering::SeqCst) {
+      let resp = utils::run_in_alternate_screen(|| {
+        println!("{count} matches hidden by filter `{pattern}` are not reviewed.");
+        const HIDDEN_PROMPT: &str = "Review them[r], Quit[q]";
+        utils::prompt(HIDDEN_PROMPT, "rq", Some('r'))
+      })?;
+      if resp == 'q' {
+        return Ok(());
+      }
+    }
+    self.set_filter("")?;
+    self.review_hidden()?;
+    Ok(())
+  }
+}
+
+impl<P: Printer> InteractivePrinter<P> {
+  fn prompt_rule_edit(&self, session: &RuleSession) -> char {
+    if self.accept_all.load(Ordering::SeqCst) {
+      return 'a';
+    }
+    const RULE_PROMPT: &str =
+      "Accept change? (Yes[y], No[n], Apply rule[A], Skip rule[S], Accept All[a], Quit[q])";
+    loop {
+      let resp = utils::prompt(RULE_PROMPT, "ynASaq", Some('n')).expect("cannot fail");
+      if resp != 'A' {
+        return resp;
+      }
+      let confirm = format!(
+        "Apply the fix to all {} remaining matches of rule `{}`? (Yes[y], No[n])",
+        session.remaining, session.rule_id
+      );
+      if utils::prompt(&confirm, "yn", Some('n')).expect("cannot fail") == 'y' {
+        return 'A';
+      }
+    }
+  }
+
+  fn print_rule_header(&self, session: &RuleSession) {
+    println!(
+      "Rule {}: {} ({} remaining)",
+      session.rule_id, session.message, session.remaining
+    );
+  }
+
+  fn review_rule_diffs(
+    &self,
+    session: &Mutex<RuleSession>,
+    diffs: Vec<(Diff<'_>, &RuleConfig<SgLang>)>,
+    path: &Path,
+  ) -> Result<()> {
+    let mut session = session.lock().expect("should not fail");
+    for (diff, rule) in diffs {
+      let fix = AcceptedFix::new(&diff, &rule.id, path);
+      if matches!(
+        self.previous_decision(&fix),
+        Some(Decision::Applied | Decision::Skipped)
+      ) {
+        session.remaining = session.remaining.saturating_sub(1);
+        continue;
+      }
+      let accept = match session.action {
+        RuleAction::Skip => false,
+        RuleAction::ApplyAll => true,
+        RuleAction::Review => {
+          let resp = utils::run_in_alternate_screen(|| {
+            self.print_rule_header(&session);
+            self
+              .inner
+              .print_rule_diffs(vec![(diff.clone(), rule)], path)?;
+            Ok(self.prompt_rule_edit(&session))
+          })?;
+          match resp {
+            'y' => true,
+            'a' => {
+              self.accept_all.store(true, Ordering::SeqCst);
+              true
+            }
+            'A' => {
+              session.action = RuleAction::ApplyAll;
+              true
+            }
+            'S' => {
+              session.action = RuleAction::Skip;
+              false
+            }
+            'q' => {
+              self.finish_rule_session(&mut session)?;
+              return Err(anyhow::anyhow!("Exit interactive editing"));
+            }
+            _ => false,
+          }
+        }
+      };
+      session.remaining = session.remaining.saturating_sub(1);
+      let stats = session.stats.entry(rule.id.clone()).or_default();
+      if accept {
+        stats.0 += 1;
+        let pending = session
+          .pending
+          .entry(path.to_path_buf())
+          .or_insert_with(|| PendingEdits {
+            source: diff.get_root_text().to_string(),
+            lang: *diff.node_match.lang(),
+            edits: vec![],
+          });
+        pending.edits.push(fix);
+      } else {
+        stats.1 += 1;
+        self.record_decision(path, &fix, Decision::Skipped)?;
+      }
+    }
+    Ok(())
+  }
+
+  fn review_rule_matches(
+    &self,
+    session: &Mutex<RuleSession>,
+    matches: Vec<NodeMatch<'_, SgLang>>,
+    file: SimpleFile<Cow<str>, &String>,
+    rule: &RuleConfig<SgLang>,
+  ) -> Result<()> {
+    let mut session = session.lock().expect("should not fail");
+    if matches.is_empty()
+      || session.action != RuleAction::Review
+      || self.accept_all.load(Ordering::SeqCst)
+    {
+      session.remaining = session.remaining.saturating_sub(matches.len());
+      return Ok(());
+    }
+    let count = matches.len();
+    let resp = utils::run_in_alternate_screen(|| {
+      self.print_rule_header(&session);
+      self.inner.print_rule(matches.into_iter(), file, rule)?;
+      const VIEW_PROMPT: &str = "Next[enter], Skip rule[S], Quit[q]";
+      utils::prompt(VIEW_PROMPT, "Sq", Some('\n'))
+    })?;
+    session.remaining = session.remaining.saturating_sub(count);
+    match resp {
+      'S' => session.action = RuleAction::Skip,
+      'q' => {
+        self.finish_rule_session(&mut session)?;
+        return Err(anyhow::anyhow!("Exit interactive editing"));
+      }
+      _ => (),
+    }
+    Ok(())
+  }
+
+  /// Write accepted edits and report applied/skipped fixes of every rule.
+  fn finish_rule_session(&self, session: &mut RuleSession) -> Result<()> {
+    for (path, pending) in std::mem::take(&mut session.pending) {
+      if !self.write_fixes(&path, pending.lang, &pending.source, &pending.edits)? {
+        self.skip_stale(&path);
+      }
+    }
+    for (rule_id, (applied, skipped)) in std::mem::take(&mut session.stats) {
+      eprintln!("Rule `{rule_id}`: {applied} applied, {skipped} skipped.");
+    }
+    Ok(())
+  }
 }
 
 impl<P: Printer> Printer for InteractivePrinter<P> {
@@ -79,35 +516,40 @@ impl<P: Printer> Printer for InteractivePrinter<P> {
     file: SimpleFile<Cow<str>, &String>,
     rule: &RuleConfig<SgLang>,
   ) -> Result<()> {
-    utils::run_in_alternate_screen(|| {
-      let matches: Vec<_> = matches.collect();
-      let first_match = match matches.first() {
-        Some(n) => n.start_pos().0,
-        None => return Ok(()),
-      };
-      let file_path = PathBuf::from(file.name().to_string());
+    if let Some(session) = &self.rule_session {
+      return self.review_rule_matches(session, matches.collect(), file, rule);
+    }
+    // matches without fix have nothing to decide, they are not kept for later
+    if self.is_hidden(&rule.id) {
+      return Ok(());
+    }
+    let matches: Vec<_> = matches.collect();
+    let Some(first_match) = matches.first().map(|n| Position::of(n)) else {
+      return Ok(());
+    };
+    let file_path = PathBuf::from(file.name().to_string());
+    let resp = utils::run_in_alternate_screen(|| {
       self.inner.print_rule(matches.into_iter(), file, rule)?;
-      let resp = self.prompt_view();
-      if resp == 'q' {
-        Err(anyhow::anyhow!("Exit interactive editing"))
-      } else if resp == 'e' {
-        open_in_editor(&file_path, first_match)?;
-        Ok(())
-      } else {
-        Ok(())
-      }
-    })
+      Ok(self.prompt_view())
+    })?;
+    self.view_action(resp, &file_path, first_match)
   }
 
   fn print_matches<'a>(&self, matches: Matches!('a), path: &Path) -> Result<()> {
-    utils::run_in_alternate_screen(|| print_matches_and_confirm_next(self, matches, path))
+    let matches: Vec<_> = matches.collect();
+    let Some(first_match) = matches.first().map(|n| Position::of(n)) else {
+      return Ok(());
+    };
+    let resp = utils::run_in_alternate_screen(|| {
+      self.inner.print_matches(matches.into_iter(), path)?;
+      Ok(self.prompt_view())
+    })?;
+    self.view_action(resp, path, first_match)
   }
 
   fn print_diffs<'a>(&self, diffs: Diffs!('a), path: &Path) -> Result<()> {
-    let path = path.to_path_buf();
-    let (confirmed, all) =
-      print_diffs_interactive(self, &path, diffs.map(|d| (d, None)).collect())?;
-    self.rewrite_action(confirmed, &path)?;
+    let diffs = diffs.map(|d| (d, DiffRule::Rewrite)).collect();
+    let all = review_diffs(self, path, diffs)? | self.review_hidden()?;
     if all {
       self.accept_all.store(true, Ordering::SeqCst);
     }
@@ -118,137 +560,718 @@ impl<P: Printer> Printer for InteractivePrinter<P> {
     diffs: Vec<(Diff<'_>, &RuleConfig<SgLang>)>,
     path: &Path,
   ) -> Result<()> {
-    let path = path.to_path_buf();
-    let (confirmed, all) = print_diffs_interactive(
-      self,
-      &path,
-      diffs.into_iter().map(|(d, r)| (d, Some(r))).collect(),
-    )?;
-    self.rewrite_action(confirmed, &path)?;
+    if let Some(session) = &self.rule_session {
+      return self.review_rule_diffs(session, diffs, path);
+    }
+    let diffs = diffs
+      .into_iter()
+      .map(|(d, r)| (d, DiffRule::Config(r)))
+      .collect();
+    let all = review_diffs(self, path, diffs)? | self.review_hidden()?;
     if all {
       self.accept_all.store(true, Ordering::SeqCst);
     }
     Ok(())
   }
+
+  fn before_rule(&self, rule: &RuleConfig<SgLang>, count: usize) -> Result<()> {
+    if let Some(session) = &self.rule_session {
+      let mut session = session.lock().expect("should not fail");
+      session.rule_id = rule.id.clone();
+      session.message = rule.message.clone();
+      session.remaining = count;
+      session.action = RuleAction::Review;
+    }
+    Ok(())
+  }
+
+  fn after_print(&self) -> Result<()> {
+    if let Some(session) = &self.rule_session {
+      let mut session = session.lock().expect("should not fail");
+      self.finish_rule_session(&mut session)?;
+    }
+    self.finish_filtering()
+  }
+
+  fn stale_files(&self) -> Vec<PathBuf> {
+    self.stale_files.lock().expect("should not fail").clone()
+  }
+
+  fn applied_edits(&self) -> Option<(usize, usize)> {
+    if self.from_stdin {
+      return None;
+    }
+    let files = self.files_modified.load(Ordering::Acquire);
+    let edits = self.edits_applied.load(Ordering::Acquire);
+    Some((files, edits))
+  }
 }
 
-fn print_diffs_interactive<'a>(
-  interactive: &InteractivePrinter<impl Printer>,
-  path: &Path,
-  diffs: Vec<(Diff<'a>, Option<&RuleConfig<SgLang>>)>,
-) -> Result<(Vec<Diff<'a>>, bool)> {
-  let mut confirmed = vec![];
-  let mut all = interactive.accept_all.load(Ordering::SeqCst);
-  let mut end = 0;
-  for (diff, rule) in diffs {
-    if diff.range.start < end {
-      continue;
+/// A fix accepted in the review of a file.
+struct AcceptedFix {
+  range: Range<usize>,
+  replacement: String,
+  rule_id: String,
+  /// kind of the matched node and its range relative to the start of the fix,
+  /// to find the match again after undo
+  node: (String, Range<usize>),
+  /// to record the decision in the session file
+  fingerprint: String,
+  line: usize,
+}
+
+impl AcceptedFix {
+  fn new(diff: &Diff<'_>, rule_id: &str, path: &Path) -> Self {
+    let node = diff.node_match.range();
+    let start = diff.range.start;
+    let fingerprint = fingerprint(rule_id, &path.to_string_lossy(), &diff.node_match);
+    Self {
+      range: diff.range.clone(),
+      replacement: diff.replacement.to_string(),
+      rule_id: rule_id.to_string(),
+      fingerprint,
+      line: diff.node_match.start_pos().0 + 1,
+      node: (
+        diff.node_match.kind().to_string(),
+        node.start.saturating_sub(start)..node.end.saturating_sub(start),
+      ),
     }
-    let confirm = all || {
-      let (accept_curr, accept_all) =
-        print_diff_and_prompt_action(interactive, path, (diff.clone(), rule))?;
-      all = accept_all;
-      accept_curr
-    };
-    if confirm {
-      end = diff.range.end;
-      confirmed.push(diff);
+  }
+}
+
+/// The rule of a diff to review.
+#[derive(Clone, Copy)]
+enum DiffRule<'r> {
+  /// `run --rewrite`
+  Rewrite,
+  Config(&'r RuleConfig<SgLang>),
+  /// a diff hidden by a filter is reviewed later with only its rule id
+  Id(&'r str),
+}
+
+impl<'r> DiffRule<'r> {
+  fn id(&self) -> &'r str {
+    match self {
+      Self::Rewrite => "rewrite",
+      Self::Config(rule) => &rule.id,
+      Self::Id(id) => id,
+    }
+  }
+
+  fn config(&self) -> Option<&'r RuleConfig<SgLang>> {
+    match self {
+      Self::Config(rule) => Some(rule),
+      _ => None,
     }
   }
-  Ok((confirmed, all))
 }
-/// returns if accept_current and accept_all
-fn print_diff_and_prompt_action(
+
+/// Reply to a diff prompt.
+enum Reply {
+  Accept,
+  AcceptAll,
+  Skip,
+  /// accept the remaining diffs of the file
+  AcceptFile,
+  /// skip the remaining diffs of the file
+  SkipFile,
+  /// skip the diff and review it again in the next session
+  Defer,
+  /// review the previous diff again
+  Previous,
+  /// revert the last accepted fix
+  Undo,
+  /// open the file at the position of the match
+  Edit(Position),
+  /// review only the rules matching the pattern, None if the prompt is cancelled
+  Filter(Option<String>),
+}
+
+/// Lines around the original position where an edited match is searched.
+const NEAR_LINES: usize = 10;
+
+/// A reviewed diff of a file, kept to step back to it.
+struct Reviewed {
+  index: usize,
+  /// the diffs skipped for overlapping the diff, if it is accepted
+  accepted: Option<Vec<usize>>,
+}
+
+/// Review the diffs of one file and write the accepted ones. Returns if all diffs are accepted.
+/// Fixes of one file are written together, so accepting the rest of the file at once
+/// is the same as accepting the remaining diffs one by one.
+///
+/// Editing the file first writes the fixes accepted so far, so the editor shows them.
+/// Then the file is read again and every remaining match, including the edited one,
+/// is located in the new content before it is shown.
+///
+/// Reviewed diffs are kept so that the user can step back and decide again.
+/// Undo takes back the last accepted fix of the file and reviews its diff again.
+/// Without accepted fixes in the file, the last fix written in the session is reverted.
+///
+/// Fixes are only written if the file has not changed since it was scanned.
+/// Otherwise the user can re-scan the file, and `--update-all` skips it.
+///
+/// Diffs of rules outside the filter are hidden and reviewed after the filter is cleared.
+fn review_diffs(
   interactive: &InteractivePrinter<impl Printer>,
   path: &Path,
-  (diff, rule): (Diff, Option<&RuleConfig<SgLang>>),
-) -> Result<(bool, bool)> {
-  let printer = &interactive.inner;
-  utils::run_in_alternate_screen(|| {
-    if let Some(rule) = rule {
-      printer.print_rule_diffs(vec![(diff.clone(), rule)], path)?;
+  diffs: Vec<(Diff<'_>, DiffRule<'_>)>,
+) -> Result<bool> {
+  let all = interactive.accept_all.load(Ordering::SeqCst);
+  if diffs.is_empty() {
+    return Ok(all);
+  }
+  let mut review = FileReview::new(interactive, path, &diffs, all);
+  // the file is re-scanned and reviewed again if it changes on disk before the fixes are written
+  loop {
+    while let Some(i) = review.queue.pop() {
+      while !review.review(i)? {}
+    }
+    if review.write()? {
+      return Ok(review.all);
+    }
+  }
+}
+
+const RESCANNED: &str = "The file has changed on disk and is re-scanned, decide again.";
+
+fn stale_warning(path: &Path) -> String {
+  format!(
+    "WARNING: {} has changed on disk after it was scanned. Its fixes are not applied.",
+    path.display()
+  )
+}
+
+/// The state of reviewing the diffs of one file, see [review_diffs].
+/// Every reply to a diff has a method, which returns false to show the diff again.
+struct FileReview<'d, P: Printer> {
+  interactive: &'d InteractivePrinter<P>,
+  path: &'d Path,
+  diffs: &'d [(Diff<'d>, DiffRule<'d>)],
+  lang: SgLang,
+  original: &'d str,
+  /// the content read after the user edits the file
+  edited: Option<AstGrep<StrDoc<SgLang>>>,
+  /// fixes of the accepted diffs in `reviewed`, in the same order
+  accepted: Vec<AcceptedFix>,
+  reviewed: Vec<Reviewed>,
+  /// diffs to review, the next one is at the end
+  queue: Vec<usize>,
+  /// end of the last accepted fix, diffs overlapping it are skipped
+  end: usize,
+  all: bool,
+  accept_file: bool,
+  notice: Option<String>,
+}
+
+impl<'d, P: Printer> FileReview<'d, P> {
+  fn new(
+    interactive: &'d InteractivePrinter<P>,
+    path: &'d Path,
+    diffs: &'d [(Diff<'d>, DiffRule<'d>)],
+    all: bool,
+  ) -> Self {
+    let first = &diffs[0].0;
+    Self {
+      interactive,
+      path,
+      diffs,
+      lang: *first.node_match.lang(),
+      original: first.get_root_text(),
+      edited: None,
+      accepted: vec![],
+      reviewed: vec![],
+      queue: (0..diffs.len()).rev().collect(),
+      end: 0,
+      all,
+      accept_file: false,
+      notice: None,
+    }
+  }
+
+  fn source(&self) -> &str {
+    self
+      .edited
+      .as_ref()
+      .map_or(self.original, |grep| grep.source())
+  }
+
+  fn review(&mut self, i: usize) -> Result<bool> {
+    let Some((reply, fix)) = self.prompt(i)? else {
+      return Ok(true);
+    };
+    match reply {
+      Reply::Accept | Reply::AcceptAll | Reply::AcceptFile => Ok(self.accept(i, fix, reply)),
+      Reply::Skip => self.skip(i, fix, Decision::Skipped),
+      Reply::Defer => self.skip(i, fix, Decision::Deferred),
+      Reply::SkipFile => self.skip_file(fix),
+      Reply::Previous => Ok(self.previous(i)),
+      Reply::Undo => self.undo(i),
+      Reply::Edit(pos) => self.edit(i, pos),
+      Reply::Filter(pattern) => Ok(self.filter(i, pattern)),
+    }
+  }
+
+  /// Show the diff and ask for a reply, unless it is decided already.
+  /// Returns None for a diff that is gone, hidden, overlapping or skipped in a previous session.
+  fn prompt(&mut self, i: usize) -> Result<Option<(Reply, AcceptedFix)>> {
+    let (diff, rule) = &self.diffs[i];
+    let interactive = self.interactive;
+    let Some(current) = locate(diff, &self.edited) else {
+      if !self.all && !self.accept_file {
+        report_gone(interactive, self.path, diff)?;
+      }
+      return Ok(None);
+    };
+    if !self.all && interactive.is_hidden(rule.id()) {
+      interactive.hide(self.path, &current, rule.id());
+      return Ok(None);
+    }
+    if current.range.start < self.end {
+      let last = self
+        .reviewed
+        .iter_mut()
+        .rev()
+        .find_map(|r| r.accepted.as_mut());
+      if let Some(overlapping) = last {
+        overlapping.push(i);
+      }
+      return Ok(None);
+    }
+    let fix = AcceptedFix::new(&current, rule.id(), self.path);
+    let previous = interactive.previous_decision(&fix);
+    if previous == Some(Decision::Skipped) {
+      return Ok(None);
+    }
+    if previous == Some(Decision::Applied) && self.notice.is_none() {
+      self.notice = Some("It was applied in a previous session, but the fix is missing.".into());
+    }
+    if self.all || self.accept_file {
+      return Ok(Some((Reply::Accept, fix)));
+    }
+    let shown = interactive.path_style.display(self.path);
+    let count = self.diffs.len();
+    let mut status = format!("Match {} of {count} in {}.", i + 1, shown.display());
+    if let Some(filter) = interactive.filter_status() {
+      status = format!("{status} {filter}.");
+    }
+    if let Some(notice) = self.notice.take() {
+      status = format!("{status} {notice}");
+    }
+    let diff = (current, rule.config());
+    let reply = print_diff_and_prompt_action(interactive, self.path, diff, Some(status))?;
+    Ok(Some((reply, fix)))
+  }
+
+  fn accept(&mut self, i: usize, fix: AcceptedFix, reply: Reply) -> bool {
+    self.all = self.all || matches!(reply, Reply::AcceptAll);
+    self.accept_file = self.accept_file || matches!(reply, Reply::AcceptFile);
+    self.end = fix.range.end;
+    self.accepted.push(fix);
+    self.reviewed.push(Reviewed {
+      index: i,
+      accepted: Some(vec![]),
+    });
+    true
+  }
+
+  fn skip(&mut self, i: usize, fix: AcceptedFix, decision: Decision) -> Result<bool> {
+    self
+      .interactive
+      .record_decision(self.path, &fix, decision)?;
+    self.reviewed.push(Reviewed {
+      index: i,
+      accepted: None,
+    });
+    Ok(true)
+  }
+
+  /// Skip the diff and the remaining diffs of the file.
+  fn skip_file(&mut self, fix: AcceptedFix) -> Result<bool> {
+    let interactive = self.interactive;
+    interactive.record_decision(self.path, &fix, Decision::Skipped)?;
+    for &j in self.queue.iter().rev() {
+      let (diff, rule) = &self.diffs[j];
+      let current = locate(diff, &self.edited).filter(|c| c.range.start >= self.end);
+      let Some(current) = current else {
+        continue;
+      };
+      if interactive.is_hidden(rule.id()) {
+        interactive.hide(self.path, &current, rule.id());
+        continue;
+      }
+      let fix = AcceptedFix::new(&current, rule.id(), self.path);
+      interactive.record_decision(self.path, &fix, Decision::Skipped)?;
+    }
+    self.queue.clear();
+    Ok(true)
+  }
+
+  /// Review the previous diff again, after the current one.
+  fn previous(&mut self, i: usize) -> bool {
+    let Some(prev) = self.reviewed.pop() else {
+      self.notice = Some("No previous match in this file.".into());
+      return false;
+    };
+    self.queue.push(i);
+    if let Some(overlapping) = prev.accepted {
+      self.accepted.pop();
+      self.end = self.accepted.last().map_or(0, |f| f.range.end);
+      self.queue.extend(overlapping.into_iter().rev());
+      self.notice = Some("The fix was accepted, decide again.".into());
     } else {
-      printer.print_diffs(std::iter::once(diff.clone()), path)?;
+      self.notice = Some("The match was skipped, decide again.".into());
+    }
+    self.queue.push(prev.index);
+    true
+  }
+
+  /// Take back the last accepted fix of the file, or the last fix written in the session.
+  fn undo(&mut self, i: usize) -> Result<bool> {
+    let last = self.reviewed.iter().rposition(|r| r.accepted.is_some());
+    if let Some(last) = last {
+      let undone = self.reviewed.remove(last);
+      self.accepted.pop();
+      self.end = self.accepted.last().map_or(0, |f| f.range.end);
+      self.queue.push(i);
+      self
+        .queue
+        .extend(undone.accepted.into_iter().flatten().rev());
+      self.queue.push(undone.index);
+      self.notice = Some("The fix is undone.".into());
+      return Ok(true);
     }
-    match interactive.prompt_edit() {
-      'y' => Ok((true, false)),
-      'a' => Ok((true, true)),
-      'e' => {
-        let pos = diff.node_match.start_pos().0;
-        open_in_editor(path, pos)?;
-        Ok((false, false))
+    let (accept_all, reverted) = undo_and_review(self.interactive, &mut self.notice)?;
+    self.all = self.all || accept_all;
+    // fixes written before editing this file may be reverted
+    if reverted.as_deref() == Some(self.path) {
+      self.edited = Some(reparse(self.path, self.lang)?);
+    }
+    Ok(false)
+  }
+
+  /// Write the accepted fixes and open the file in the editor.
+  fn edit(&mut self, i: usize, pos: Position) -> Result<bool> {
+    let interactive = self.interactive;
+    if !interactive.write_fixes(self.path, self.lang, self.source(), &self.accepted)?
+      && self.prompt_stale()?
+    {
+      self.queue.push(i);
+      self.rescan()?;
+      return Ok(true);
+    }
+    // written fixes can only be undone, skipped matches can still be revisited
+    self.accepted.clear();
+    self.reviewed.retain(|r| r.accepted.is_none());
+    self.end = 0;
+    interactive.edit_file(self.path, pos)?;
+    self.edited = Some(reparse(self.path, self.lang)?);
+    Ok(false)
+  }
+
+  /// Review only the rules matching the pattern. The diff is shown again unless it is hidden.
+  fn filter(&mut self, i: usize, pattern: Option<String>) -> bool {
+    let Some(pattern) = pattern else {
+      return false;
+    };
+    let interactive = self.interactive;
+    self.notice = Some(match interactive.set_filter(&pattern) {
+      Err(e) => format!("Invalid filter `{pattern}`: {e}."),
+      Ok(false) => "The filter is cleared, hidden matches are reviewed after this file.".into(),
+      Ok(true) => {
+        let remaining: Vec<_> = std::iter::once(&i).chain(self.queue.iter()).collect();
+        let shown = remaining
+          .iter()
+          .filter(|&&&j| !interactive.is_hidden(self.diffs[j].1.id()))
+          .count();
+        format!(
+          "{shown} of {} remaining matches in this file are shown.",
+          remaining.len()
+        )
       }
+    });
+    false
+  }
+
+  /// Write the accepted fixes after all diffs are reviewed.
+  /// Returns false if the file has changed on disk and is re-scanned to review again.
+  fn write(&mut self) -> Result<bool> {
+    let (path, lang) = (self.path, self.lang);
+    let written = self
+      .interactive
+      .write_fixes(path, lang, self.source(), &self.accepted)?;
+    if written || !self.prompt_stale()? {
+      return Ok(true);
+    }
+    self.rescan()?;
+    Ok(false)
+  }
+
+  /// Ask what to do with the accepted fixes of a file changed after scanning.
+  /// Returns true to re-scan the file. With all changes accepted, the file is skipped.
+  fn prompt_stale(&self) -> Result<bool> {
+    let interactive = self.interactive;
+    if self.all {
+      interactive.skip_stale(self.path);
+      return Ok(false);
+    }
+    let resp = utils::run_in_alternate_screen(|| {
+      println!("{}", stale_warning(self.path));
+      const STALE_PROMPT: &str = "Re-scan the file[r], Skip its fixes[s], Quit[q]";
+      utils::prompt(STALE_PROMPT, "rsq", Some('r'))
+    })?;
+    match resp {
       'q' => Err(anyhow::anyhow!("Exit interactive editing")),
-      'n' => Ok((false, false)),
-      _ => Ok((false, false)),
+      'r' => Ok(true),
+      _ => {
+        interactive.skip_stale(self.path);
+        Ok(false)
+      }
     }
-  })
+  }
+
+  /// Read the file again and put the accepted diffs back to the queue,
+  /// to find them in the re-scanned file and decide again.
+  fn rescan(&mut self) -> Result<()> {
+    self.edited = Some(reparse(self.path, self.lang)?);
+    self.end = 0;
+    let mut indices: Vec<_> = self
+      .reviewed
+      .iter()
+      .filter_map(|r| Some(std::iter::once(r.index).chain(r.accepted.clone()?)))
+      .flatten()
+      .collect();
+    indices.sort_unstable();
+    self.queue.extend(indices.into_iter().rev());
+    self.reviewed.retain(|r| r.accepted.is_none());
+    self.accepted.clear();
+    self.notice = Some(RESCANNED.into());
+    Ok(())
+  }
+}
+
+/// The diff in the content edited by the user. None if the match is gone.
+fn locate<'g>(diff: &Diff<'g>, edited: &'g Option<AstGrep<StrDoc<SgLang>>>) -> Option<Diff<'g>> {
+  match edited {
+    Some(grep) => relocate_diff(diff, grep),
+    None => Some(diff.clone()),
+  }
+}
+
+fn read_source(path: &Path) -> Result<String> {
+  utils::read_source(path).with_context(|| EC::ReadFile(path.to_path_buf()))
+}
+
+fn reparse(path: &Path, lang: SgLang) -> Result<AstGrep<StrDoc<SgLang>>> {
+  Ok(lang.ast_grep(read_source(path)?))
 }
 
-fn print_matches_and_confirm_next<'a>(
+/// Revert the last fix written in the session and review its diff again.
+/// Returns if all diffs are accepted and the path of the reverted file.
+fn undo_and_review(
+  interactive: &InteractivePrinter<impl Printer>,
+  notice: &mut Option<String>,
+) -> Result<(bool, Option<PathBuf>)> {
+  let mut reverted = None;
+  loop {
+    let fix = match interactive.undo()? {
+      Undo::Nothing => {
+        *notice = Some("Nothing to undo.".into());
+        return Ok((false, reverted));
+      }
+      Undo::Blocked(reason) => {
+        *notice = Some(reason);
+        return Ok((false, reverted));
+      }
+      Undo::Reverted(fix) => fix,
+    };
+    let path = fix.path.clone();
+    reverted = Some(path.clone());
+    let grep = reparse(&path, fix.lang())?;
+    let Some(diff) = fix.restore(&grep) else {
+      *notice = Some(format!("Reverted the fix in {}.", path.display()));
+      return Ok((false, reverted));
+    };
+    let accepted = AcceptedFix::new(&diff, &fix.rule_id, &path);
+    let undone = format!("Reverted the fix of rule `{}`.", fix.rule_id);
+    let reply = print_diff_and_prompt_action(interactive, &path, (diff, None), Some(undone))?;
+    match reply {
+      Reply::Accept | Reply::AcceptAll | Reply::AcceptFile => {
+        if !interactive.write_fixes(&path, fix.lang(), grep.source(), &[accepted])? {
+          interactive.skip_stale(&path);
+          *notice = Some(stale_warning(&path));
+        }
+        return Ok((matches!(reply, Reply::AcceptAll), reverted));
+      }
+      Reply::Skip | Reply::SkipFile | Reply::Previous => {
+        interactive.record_decision(&path, &accepted, Decision::Skipped)?;
+        return Ok((false, reverted));
+      }
+      Reply::Defer => {
+        interactive.record_decision(&path, &accepted, Decision::Deferred)?;
+        return Ok((false, reverted));
+      }
+      Reply::Undo => continue,
+      Reply::Edit(pos) => {
+        interactive.edit_file(&path, pos)?;
+        return Ok((false, reverted));
+      }
+      // the reverted match stays undecided like after editing
+      Reply::Filter(pattern) => {
+        if let Err(e) = interactive.set_filter(pattern.as_deref().unwrap_or_default()) {
+          *notice = Some(format!("Invalid filter: {e}."));
+        }
+        return Ok((false, reverted));
+      }
+    }
+  }
+}
+
+/// Find the match of a diff in the edited content: a node of the same kind and text
+/// nearest to the original position. The diff keeps its replacement, which is
+/// generated from the same text. Returns None if the match is edited or removed.
+fn relocate_diff<'g>(diff: &Diff<'_>, grep: &'g AstGrep<StrDoc<SgLang>>) -> Option<Diff<'g>> {
+  Anchor::of(diff).find(grep, diff.replacement.to_string())
+}
+
+/// Where the match of a diff was, to find it again after the file changes.
+struct Anchor {
+  kind: String,
+  text: String,
+  /// zero-based line of the node
+  line: usize,
+  node: Range<usize>,
+  /// the fix may cover more than the node, like a trailing comma
+  range: Range<usize>,
+  /// the text replaced by the fix
+  original: String,
+}
+
+impl Anchor {
+  fn of(diff: &Diff<'_>) -> Self {
+    let node = diff.node_match.get_node();
+    let original = diff.get_root_text().get(diff.range.clone());
+    Self {
+      kind: node.kind().to_string(),
+      text: node.text().to_string(),
+      line: node.start_pos().0,
+      node: node.range(),
+      range: diff.range.clone(),
+      original: original.unwrap_or_default().to_string(),
+    }
+  }
+
+  fn find<'g>(&self, grep: &'g AstGrep<StrDoc<SgLang>>, replacement: String) -> Option<Diff<'g>> {
+    let old = &self.node;
+    let found = grep
+      .root()
+      .dfs()
+      .filter(|n| n.start_pos().0.abs_diff(self.line) <= NEAR_LINES)
+      .filter(|n| n.kind() == self.kind.as_str() && n.text() == self.text)
+      .min_by_key(|n| n.range().start.abs_diff(old.start))?;
+    let new = found.range();
+    let range = new.start.checked_sub(old.start - self.range.start)?
+      ..new.end + self.range.end.saturating_sub(old.end);
+    if grep.source().get(range.clone()) != Some(self.original.as_str()) {
+      return None;
+    }
+    Some(Diff {
+      node_match: found.into(),
+      replacement: Cow::Owned(replacement),
+      range,
+    })
+  }
+}
+
+fn report_gone(
   interactive: &InteractivePrinter<impl Printer>,
-  matches: Matches!('a),
   path: &Path,
+  diff: &Diff<'_>,
 ) -> Result<()> {
-  let printer = &interactive.inner;
-  let matches: Vec<_> = matches.collect();
-  let first_match = match matches.first() {
-    Some(n) => n.start_pos().0,
-    None => return Ok(()),
-  };
-  printer.print_matches(matches.into_iter(), path)?;
-  let resp = interactive.prompt_view();
+  let line = diff.node_match.start_pos().0 + 1;
+  let resp = utils::run_in_alternate_screen(|| {
+    println!(
+      "The match at {}:{line} is gone after editing.",
+      path.display()
+    );
+    Ok(interactive.prompt_gone())
+  })?;
   if resp == 'q' {
     Err(anyhow::anyhow!("Exit interactive editing"))
-  } else if resp == 'e' {
-    open_in_editor(path, first_match)?;
-    Ok(())
   } else {
     Ok(())
   }
 }
 
-fn apply_rewrite(diffs: Vec<Diff>) -> String {
-  let mut new_content = String::new();
-  let Some(first) = diffs.first() else {
-    return new_content;
-  };
-  let old_content = first.get_root_text();
-  let mut start = 0;
-  for diff in diffs {
-    let range = diff.range;
-    new_content.push_str(&old_content[start..range.start]);
-    new_content.push_str(&diff.replacement);
-    start = range.end;
-  }
-  // add trailing statements
-  new_content.push_str(&old_content[start..]);
-  new_content
+fn print_diff_and_prompt_action(
+  interactive: &InteractivePrinter<impl Printer>,
+  path: &Path,
+  (diff, rule): (Diff, Option<&RuleConfig<SgLang>>),
+  notice: Option<String>,
+) -> Result<Reply> {
+  let printer = &interactive.inner;
+  let lines = diff.get_root_text().lines().count();
+  let mut resized = None;
+  utils::run_in_alternate_screen(|| loop {
+    if let Some(notice) = &notice {
+      if interactive.colored {
+        println!("{}", Style::new().bold().paint(notice));
+      } else {
+        println!("{notice}");
+      }
+    }
+    if let Some(resized) = &resized {
+      println!("{resized}");
+    }
+    if let Some(rule) = rule {
+      printer.print_rule_diffs(vec![(diff.clone(), rule)], path)?;
+    } else {
+      printer.print_diffs(std::iter::once(diff.clone()), path)?;
+    }
+    let resp = interactive.prompt_edit();
+    if let '+' | '-' = resp {
+      // the diff is printed again from the loaded content
+      resized = Some(interactive.resize_context(resp == '+', lines));
+      utils::clear()?;
+      continue;
+    }
+    break match resp {
+      'y' => Ok(Reply::Accept),
+      'a' => Ok(Reply::AcceptAll),
+      'f' => Ok(Reply::AcceptFile),
+      's' => Ok(Reply::SkipFile),
+      'd' => Ok(Reply::Defer),
+      'p' => Ok(Reply::Previous),
+      'u' => Ok(Reply::Undo),
+      '/' => {
+        const FILTER_PROMPT: &str = "Review rules (id prefix or glob, empty to clear): ";
+        Ok(Reply::Filter(utils::prompt_line(FILTER_PROMPT)?))
+      }
+      'e' => Ok(Reply::Edit(Position::of(diff.node_match.get_node()))),
+      'q' => Err(anyhow::anyhow!("Exit interactive editing")),
+      _ => Ok(Reply::Skip),
+    };
+  })
 }
 
-/// start_line is zero-based
-fn open_in_editor(path: &Path, start_line: usize) -> Result<()> {
-  let editor = std::env::var("EDITOR").unwrap_or_else(|_| String::from("vim"));
-  let exit = std::process::Command::new(editor)
-    .arg(path)
-    .arg(format!("+{}", start_line + 1))
-    .spawn()
-    .context(EC::OpenEditor)?
-    .wait()
-    .context(EC::OpenEditor)?;
-  if exit.success() {
-    Ok(())
+/// Double or halve the context lines of diffs, from none up to the whole file.
+fn resize(context: usize, grow: bool, lines: usize) -> usize {
+  let context = context.min(lines);
+  if grow {
+    (context * 2).clamp(1, lines.max(1))
   } else {
-    Err(anyhow::anyhow!(EC::OpenEditor))
+    context / 2
   }
 }
 
 #[cfg(test)]
 mod test {
   use super::*;
+  use crate::print::LineEnding;
   use ast_grep_config::{from_yaml_string, Fixer, GlobalRules};
   use ast_grep_core::traversal::Visitor;
-  use ast_grep_core::{AstGrep, Matcher, StrDoc};
+  use ast_grep_core::{Matcher, StrDoc};
   use ast_grep_language::SupportLang;
 
   fn make_rule(rule: &str) -> RuleConfig<SgLang> {
@@ -278,10 +1301,16 @@ language: TypeScript
     Visitor::new(&matcher)
       .reentrant(false)
       .visit(root)
-      .map(|nm| Diff::generate(nm, &matcher, fixer))
+      .map(|nm| Diff::generate(nm, &matcher, fixer, LineEnding::Lf))
       .collect()
   }
 
+  fn rewrite(diffs: Vec<Diff>) -> String {
+    let source = diffs[0].get_root_text();
+    let fixes = diffs.iter().map(|d| Fix::new(d, None)).collect();
+    apply_fixes(source, fixes, Path::new("test.ts")).0
+  }
+
   #[test]
   fn test_apply_rewrite() {
     let root = AstGrep::new("let a = () => c++", SupportLang::TypeScript.into());
@@ -297,7 +1326,7 @@ fix: ($B, lifecycle.update(['$A']))",
     let mut matcher = config.matcher;
     let fixer = matcher.fixer.take().unwrap();
     let diffs = make_diffs(&root, matcher, &fixer);
-    let ret = apply_rewrite(diffs);
+    let ret = rewrite(diffs);
     assert_eq!(ret, "let a = () => (c++, lifecycle.update(['c']))");
   }
 
@@ -309,7 +1338,7 @@ fix: ($B, lifecycle.update(['$A']))",
       "Some($A)",
       &Fixer::from_str("$A", &SupportLang::TypeScript.into()).expect("fixer must compile"),
     );
-    let ret = apply_rewrite(diffs);
+    let ret = rewrite(diffs);
     assert_eq!("Some(1)", ret);
   }
 
@@ -322,19 +1351,190 @@ fix: ($B, lifecycle.update(['$A']))",
       "Some($A)",
       &Fixer::from_str("$A", &SupportLang::TypeScript.into()).expect("fixer must compile"),
     );
-    let ret = apply_rewrite(diffs);
+    let ret = rewrite(diffs);
     assert_eq!("\n\n\n1", ret);
   }
 
+  #[test]
+  fn test_write_fixes_of_file() {
+    use crate::print::ColoredPrinter;
+    use codespan_reporting::term::termcolor::Buffer;
+    let dir = tempfile::tempdir().expect("should create");
+    let path = dir.path().join("test.ts");
+    let source = "Some(1); Some(Some(2))";
+    std::fs::write(&path, source).expect("should write");
+    let grep = AstGrep::new(source, SupportLang::TypeScript.into());
+    let fixer = Fixer::from_str("$A", &SupportLang::TypeScript.into()).expect("fixer must compile");
+    let matcher = "Some($A)";
+    // nested matches overlap, only the outer one is applied
+    let diffs: Vec<_> = grep
+      .root()
+      .find_all(matcher)
+      .map(|nm| Diff::generate(nm, &matcher, &fixer, LineEnding::Lf))
+      .collect();
+    assert_eq!(diffs.len(), 3);
+    let printer = ColoredPrinter::new(Buffer::no_color());
+    let interactive = InteractivePrinter::new(printer, true, false).expect("should create");
+    interactive
+      .print_diffs(diffs.into_iter(), &path)
+      .expect("should write");
+    let fixed = std::fs::read_to_string(&path).expect("should read");
+    assert_eq!(fixed, "1; Some(2)");
+    assert_eq!(interactive.applied_edits(), Some((1, 2)));
+  }
+
+  #[test]
+  fn test_skip_stale_file() {
+    use crate::print::ColoredPrinter;
+    use codespan_reporting::term::termcolor::Buffer;
+    let dir = tempfile::tempdir().expect("should create");
+    let path = dir.path().join("test.ts");
+    let grep = AstGrep::new("Some(1)", SupportLang::TypeScript.into());
+    // the file changes after it is scanned
+    std::fs::write(&path, "let a = Some(1)").expect("should write");
+    let fixer = Fixer::from_str("$A", &SupportLang::TypeScript.into()).expect("fixer must compile");
+    let diffs = make_diffs(&grep, "Some($A)", &fixer);
+    let printer = ColoredPrinter::new(Buffer::no_color());
+    let interactive = InteractivePrinter::new(printer, true, false).expect("should create");
+    interactive
+      .print_diffs(diffs.into_iter(), &path)
+      .expect("should skip");
+    let content = std::fs::read_to_string(&path).expect("should read");
+    assert_eq!(content, "let a = Some(1)");
+    assert_eq!(interactive.applied_edits(), Some((0, 0)));
+    assert_eq!(interactive.stale_files(), vec![path]);
+  }
+
+  fn nested_diffs(grep: &AstGrep<StrDoc<SgLang>>) -> Vec<(Diff<'_>, DiffRule<'_>)> {
+    let fixer = Fixer::from_str("$A", &SupportLang::TypeScript.into()).expect("fixer must compile");
+    let matcher = "Some($A)";
+    grep
+      .root()
+      .find_all(matcher)
+      .map(|nm| Diff::generate(nm, &matcher, &fixer, LineEnding::Lf))
+      .map(|diff| (diff, DiffRule::Rewrite))
+      .collect()
+  }
+
+  #[test]
+  fn test_previous_at_first_match_of_file() {
+    use crate::print::ColoredPrinter;
+    use codespan_reporting::term::termcolor::Buffer;
+    let grep = AstGrep::new("Some(1); Some(2)", SupportLang::TypeScript.into());
+    let diffs = nested_diffs(&grep);
+    let printer = ColoredPrinter::new(Buffer::no_color());
+    let interactive = InteractivePrinter::new(printer, false, false).expect("should create");
+    let mut review = FileReview::new(&interactive, Path::new("test.ts"), &diffs, false);
+    assert_eq!(review.queue.pop(), Some(0));
+    // matches of previous files are not reviewed again, the first match is shown again
+    assert!(!review.previous(0));
+    assert_eq!(
+      review.notice.as_deref(),
+      Some("No previous match in this file.")
+    );
+    assert_eq!(review.queue, vec![1]);
+    let fix = AcceptedFix::new(&diffs[0].0, "rewrite", review.path);
+    assert!(review.skip(0, fix, Decision::Skipped).expect("should skip"));
+    assert_eq!(review.queue.pop(), Some(1));
+    assert!(review.previous(1));
+    assert_eq!(review.queue, vec![1, 0]);
+    assert!(review.reviewed.is_empty());
+  }
+
+  #[test]
+  fn test_previous_after_accept() {
+    use crate::print::ColoredPrinter;
+    use codespan_reporting::term::termcolor::Buffer;
+    let grep = AstGrep::new("Some(Some(1)); Some(2)", SupportLang::TypeScript.into());
+    let diffs = nested_diffs(&grep);
+    assert_eq!(diffs.len(), 3);
+    let printer = ColoredPrinter::new(Buffer::no_color());
+    let interactive = InteractivePrinter::new(printer, false, false).expect("should create");
+    let mut review = FileReview::new(&interactive, Path::new("test.ts"), &diffs, false);
+    assert_eq!(review.queue.pop(), Some(0));
+    let fix = AcceptedFix::new(&diffs[0].0, "rewrite", review.path);
+    assert!(review.accept(0, fix, Reply::Accept));
+    assert_eq!(review.end, 13);
+    // the nested match overlaps the accepted fix
+    assert_eq!(review.queue.pop(), Some(1));
+    assert!(review.prompt(1).expect("should not prompt").is_none());
+    assert_eq!(review.queue.pop(), Some(2));
+    // going back takes back the accepted fix and the overlapping match
+    assert!(review.previous(2));
+    assert!(review.accepted.is_empty());
+    assert!(review.reviewed.is_empty());
+    assert_eq!(review.end, 0);
+    assert_eq!(review.queue, vec![2, 1, 0]);
+    assert_eq!(
+      review.notice.as_deref(),
+      Some("The fix was accepted, decide again.")
+    );
+  }
+
+  fn relocate(old: &str, new: &str) -> Option<(Range<usize>, String)> {
+    let old = AstGrep::new(old, SupportLang::TypeScript.into());
+    let fixer = Fixer::from_str("$A", &SupportLang::TypeScript.into()).expect("fixer must compile");
+    let diffs = make_diffs(&old, "Some($A)", &fixer);
+    let new = AstGrep::new(new, SupportLang::TypeScript.into());
+    let diff = relocate_diff(&diffs[0], &new)?;
+    let text = diff.get_root_text()[diff.range.clone()].to_string();
+    assert_eq!(text, diff.node_match.text());
+    Some((diff.range, diff.replacement.to_string()))
+  }
+
+  #[test]
+  fn test_resize_context() {
+    assert_eq!(resize(3, true, 100), 6);
+    assert_eq!(resize(0, true, 100), 1);
+    assert_eq!(resize(60, true, 100), 100);
+    assert_eq!(resize(100, false, 100), 50);
+    assert_eq!(resize(1, false, 100), 0);
+    assert_eq!(resize(0, false, 100), 0);
+    // context beyond a short file is the whole file
+    assert_eq!(resize(3, true, 2), 2);
+    assert_eq!(resize(3, false, 2), 1);
+    assert_eq!(resize(3, true, 0), 1);
+  }
+
+  #[test]
+  fn test_relocate_diff() {
+    let moved = relocate("a; Some(1)", "let b = 2;\na; Some(1)");
+    assert_eq!(moved, Some((14..21, "1".into())));
+    // the nearest one is picked
+    let nearest = relocate("Some(1)\nSome(1)", "\nSome(1)\nSome(1)");
+    assert_eq!(nearest, Some((1..8, "1".into())));
+    assert_eq!(relocate("Some(1)", "Some(2)"), None);
+    assert_eq!(relocate("Some(1)", "foo"), None);
+  }
+
+  #[test]
+  fn test_relocate_diff_nearby() {
+    let far = format!("{}Some(1)", "\n".repeat(NEAR_LINES + 1));
+    assert_eq!(relocate("Some(1)", &far), None);
+    let near = format!("{}Some(1)", "\n".repeat(NEAR_LINES));
+    let start = NEAR_LINES;
+    assert_eq!(
+      relocate("Some(1)", &near),
+      Some((start..start + 7, "1".into()))
+    );
+  }
+
+  const POS: Position = Position {
+    line: 2,
+    column: 1,
+    byte_column: 1,
+  };
+
   fn test_open_editor_respect_editor_env() {
+    std::env::remove_var("VISUAL");
     std::env::set_var("EDITOR", "echo");
-    let exit = open_in_editor(&PathBuf::from("Cargo.toml"), 1);
+    let exit = editor::open_in_editor(&PathBuf::from("Cargo.toml"), POS);
     assert!(exit.is_ok());
   }
 
   fn test_open_editor_error_handling() {
     std::env::set_var("EDITOR", "NOT_EXIST_XXXXX");
-    let exit = open_in_editor(&PathBuf::from("Cargo.toml"), 1);
+    let exit = editor::open_in_editor(&PathBuf::from("Cargo.toml"), POS);
     let error = exit.expect_err("should be error");
     let error = error.downcast_ref::<EC>().expect("should be error context");
     assert!(matches!(error, EC::OpenEditor));
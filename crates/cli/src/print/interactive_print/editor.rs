@@ -0,0 +1,188 @@
+//! Open a file in the user's editor at a match, see the `e` key of interactive mode.
+//! Editors take the cursor position in different ways, so the arguments are
+//! chosen by the editor's program name. Unknown editors get the common `+line`.
+use crate::lang::SgLang;
+use crate::utils::ErrorContext as EC;
+
+use anyhow::{Context, Result};
+use ast_grep_core::{Node as SgNode, StrDoc};
+
+use std::ffi::OsString;
+use std::path::Path;
+use std::process::Command;
+
+type Node<'r> = SgNode<'r, StrDoc<SgLang>>;
+
+/// One-based cursor position of a match.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(super) struct Position {
+  pub line: usize,
+  /// counted in characters
+  pub column: usize,
+  /// counted in bytes, vim's cursor() expects byte columns
+  pub byte_column: usize,
+}
+
+impl Position {
+  pub fn of(node: &Node<'_>) -> Self {
+    let source = node.root().get_text();
+    let start = node.range().start;
+    let line_start = source[..start].rfind('\n').map_or(0, |i| i + 1);
+    Self {
+      line: node.start_pos().0 + 1,
+      column: source[line_start..start].chars().count() + 1,
+      byte_column: start - line_start + 1,
+    }
+  }
+}
+
+/// VISUAL takes precedence over EDITOR like in git. The fallback is vim, or notepad on Windows.
+fn editor_command(visual: Option<String>, editor: Option<String>) -> String {
+  let fallback = if cfg!(windows) { "notepad" } else { "vim" };
+  [visual, editor]
+    .into_iter()
+    .flatten()
+    .find(|cmd| !cmd.trim().is_empty())
+    .unwrap_or_else(|| fallback.to_string())
+}
+
+/// Split the editor command like `code --wait` and append the path with the position.
+fn editor_args(command: &str, path: &Path, pos: Position) -> (String, Vec<OsString>) {
+  let mut words = command.split_whitespace().map(String::from);
+  let program = words.next().unwrap_or_default();
+  let mut args: Vec<OsString> = words.map(OsString::from).collect();
+  let name = Path::new(&program)
+    .file_stem()
+    .map(|s| s.to_string_lossy().to_lowercase())
+    .unwrap_or_default();
+  let Position {
+    line,
+    column,
+    byte_column,
+  } = pos;
+  let with_position = |suffix: String| {
+    let mut path = path.as_os_str().to_owned();
+    path.push(suffix);
+    path
+  };
+  match name.as_str() {
+    "vim" | "nvim" | "gvim" | "mvim" => {
+      args.push(format!("+call cursor({line}, {byte_column})").into());
+      args.push(path.into());
+    }
+    "nano" | "pico" => {
+      args.push(format!("+{line},{column}").into());
+      args.push(path.into());
+    }
+    "emacs" | "emacsclient" | "kak" | "micro" => {
+      args.push(format!("+{line}:{column}").into());
+      args.push(path.into());
+    }
+    "code" | "code-insiders" | "codium" | "vscodium" | "cursor" => {
+      args.push("--goto".into());
+      args.push(with_position(format!(":{line}:{column}")));
+    }
+    "subl" | "sublime_text" | "hx" | "helix" | "zed" => {
+      args.push(with_position(format!(":{line}:{column}")));
+    }
+    "notepad" => args.push(path.into()),
+    _ => {
+      args.push(format!("+{line}").into());
+      args.push(path.into());
+    }
+  }
+  (program, args)
+}
+
+/// Run the editor and wait for it. The caller must have left the alternate screen.
+pub(super) fn open_in_editor(path: &Path, pos: Position) -> Result<()> {
+  let visual = std::env::var("VISUAL").ok();
+  let editor = std::env::var("EDITOR").ok();
+  let (program, args) = editor_args(&editor_command(visual, editor), path, pos);
+  let mut command = Command::new(program);
+  command.args(args);
+  // the editor needs the terminal as input even if stdin is piped
+  #[cfg(unix)]
+  if !atty::is(atty::Stream::Stdin) {
+    if let Ok(tty) = std::fs::File::open("/dev/tty") {
+      command.stdin(tty);
+    }
+  }
+  // keys are read in raw mode, which must not leak into the editor
+  let _ = crossterm::terminal::disable_raw_mode();
+  let exit = command
+    .spawn()
+    .context(EC::OpenEditor)?
+    .wait()
+    .context(EC::OpenEditor)?;
+  if exit.success() {
+    Ok(())
+  } else {
+    Err(anyhow::anyhow!(EC::OpenEditor))
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use ast_grep_language::{Language, SupportLang};
+
+  const POS: Position = Position {
+    line: 3,
+    column: 5,
+    byte_column: 7,
+  };
+
+  fn args(command: &str) -> Vec<String> {
+    let (program, args) = editor_args(command, Path::new("a.ts"), POS);
+    std::iter::once(program)
+      .chain(args.iter().map(|a| a.to_string_lossy().into_owned()))
+      .collect()
+  }
+
+  #[test]
+  fn test_editor_command() {
+    let cmd =
+      |v: Option<&str>, e: Option<&str>| editor_command(v.map(Into::into), e.map(Into::into));
+    assert_eq!(cmd(Some("nvim"), Some("nano")), "nvim");
+    assert_eq!(cmd(None, Some("nano")), "nano");
+    assert_eq!(cmd(Some(" "), Some("nano")), "nano");
+    let fallback = if cfg!(windows) { "notepad" } else { "vim" };
+    assert_eq!(cmd(None, None), fallback);
+  }
+
+  #[test]
+  fn test_editor_args() {
+    assert_eq!(args("vim"), ["vim", "+call cursor(3, 7)", "a.ts"]);
+    assert_eq!(
+      args("/usr/bin/nvim"),
+      ["/usr/bin/nvim", "+call cursor(3, 7)", "a.ts"]
+    );
+    assert_eq!(args("nano"), ["nano", "+3,5", "a.ts"]);
+    assert_eq!(
+      args("emacsclient -t"),
+      ["emacsclient", "-t", "+3:5", "a.ts"]
+    );
+    assert_eq!(
+      args("code --wait"),
+      ["code", "--wait", "--goto", "a.ts:3:5"]
+    );
+    assert_eq!(args("hx"), ["hx", "a.ts:3:5"]);
+    assert_eq!(args("ed"), ["ed", "+3", "a.ts"]);
+  }
+
+  #[test]
+  fn test_position() {
+    let grep = SgLang::from(SupportLang::TypeScript).ast_grep("let a = 1\n  é; b");
+    let node = grep.root().find("b").expect("should match");
+    let pos = Position::of(node.get_node());
+    assert_eq!(
+      pos,
+      Position {
+        line: 2,
+        column: 6,
+        byte_column: 7,
+      }
+    );
+  }
+}
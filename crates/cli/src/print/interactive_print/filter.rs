@@ -0,0 +1,141 @@
+//! Filter the interactive review by rule id, see the `/` key.
+//! Diffs of other rules are hidden until the filter is cleared. Files are reviewed
+//! one by one, so a hidden diff keeps where its match was and is found again
+//! in the file when it is reviewed later.
+use super::Anchor;
+use crate::lang::SgLang;
+use crate::print::Diff;
+
+use anyhow::Result;
+use ast_grep_core::{AstGrep, StrDoc};
+use globset::{Glob, GlobMatcher};
+
+use std::path::PathBuf;
+
+/// Rule ids to review. A pattern with glob characters is a glob, otherwise a prefix.
+pub(super) struct RuleFilter {
+  pattern: String,
+  glob: Option<GlobMatcher>,
+}
+
+impl RuleFilter {
+  pub fn new(pattern: &str) -> Result<Self> {
+    let glob = if pattern.contains(['*', '?', '[', '{']) {
+      Some(Glob::new(pattern)?.compile_matcher())
+    } else {
+      None
+    };
+    Ok(Self {
+      pattern: pattern.to_string(),
+      glob,
+    })
+  }
+
+  pub fn pattern(&self) -> &str {
+    &self.pattern
+  }
+
+  pub fn matches(&self, rule_id: &str) -> bool {
+    match &self.glob {
+      Some(glob) => glob.is_match(rule_id),
+      None => rule_id.starts_with(&self.pattern),
+    }
+  }
+}
+
+/// A diff hidden by the filter, to review after the filter is cleared.
+pub(super) struct HiddenDiff {
+  pub path: PathBuf,
+  pub lang: SgLang,
+  pub rule_id: String,
+  anchor: Anchor,
+  replacement: String,
+}
+
+impl HiddenDiff {
+  pub fn new(path: PathBuf, diff: &Diff<'_>, rule_id: &str) -> Self {
+    Self {
+      path,
+      lang: *diff.node_match.lang(),
+      rule_id: rule_id.to_string(),
+      anchor: Anchor::of(diff),
+      replacement: diff.replacement.to_string(),
+    }
+  }
+
+  /// Find the match in the current content of the file. Returns None if a fix
+  /// written after the diff was hidden changed the match.
+  pub fn restore<'g>(&self, grep: &'g AstGrep<StrDoc<SgLang>>) -> Option<Diff<'g>> {
+    self.anchor.find(grep, self.replacement.clone())
+  }
+}
+
+/// The active filter and the diffs it hides.
+#[derive(Default)]
+pub(super) struct Filtering {
+  pub filter: Option<RuleFilter>,
+  pub hidden: Vec<HiddenDiff>,
+}
+
+impl Filtering {
+  pub fn is_hidden(&self, rule_id: &str) -> bool {
+    self.filter.as_ref().map_or(false, |f| !f.matches(rule_id))
+  }
+
+  /// Take the hidden diffs grouped by file, in the order they are hidden.
+  pub fn take_hidden(&mut self) -> Vec<(PathBuf, Vec<HiddenDiff>)> {
+    let mut files: Vec<(PathBuf, Vec<HiddenDiff>)> = vec![];
+    for hidden in std::mem::take(&mut self.hidden) {
+      match files.iter_mut().find(|(path, _)| *path == hidden.path) {
+        Some((_, diffs)) => diffs.push(hidden),
+        None => files.push((hidden.path.clone(), vec![hidden])),
+      }
+    }
+    files
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::print::LineEnding;
+  use ast_grep_config::Fixer;
+  use ast_grep_language::SupportLang;
+
+  #[test]
+  fn test_rule_filter() {
+    let prefix = RuleFilter::new("no-").expect("should create");
+    assert!(prefix.matches("no-console"));
+    assert!(!prefix.matches("prefer-const"));
+    let glob = RuleFilter::new("*-const").expect("should create");
+    assert!(glob.matches("prefer-const"));
+    assert!(!glob.matches("no-console"));
+    assert_eq!(glob.pattern(), "*-const");
+    assert!(RuleFilter::new("[a").is_err());
+  }
+
+  #[test]
+  fn test_restore_hidden_diff() {
+    let lang = SgLang::from(SupportLang::TypeScript);
+    let old = AstGrep::new("a; Some(1)", lang);
+    let fixer = Fixer::from_str("$A", &lang).expect("fixer must compile");
+    let nm = old.root().find("Some($A)").expect("should match");
+    let diff = Diff::generate(nm, &"Some($A)", &fixer, LineEnding::Lf);
+    let hidden = HiddenDiff::new("a.ts".into(), &diff, "test");
+    let mut filtering = Filtering {
+      filter: Some(RuleFilter::new("other").expect("should create")),
+      hidden: vec![hidden],
+    };
+    assert!(filtering.is_hidden("test"));
+    let files = filtering.take_hidden();
+    assert!(filtering.hidden.is_empty());
+    let hidden = &files[0].1[0];
+    // a fix written before the match moves it
+    let new = AstGrep::new("b; Some(1)", lang);
+    let restored = hidden.restore(&new).expect("should restore");
+    assert_eq!(restored.range, 3..10);
+    assert_eq!(restored.replacement, "1");
+    let changed = AstGrep::new("a; Some(2)", lang);
+    assert!(hidden.restore(&changed).is_none());
+  }
+}
@@ -0,0 +1,188 @@
+//! Persist decisions of interactive sessions, see `--session` and `--session-report`.
+//! Every decision is appended to the session file as one line of JSON and flushed,
+//! so quitting at any time loses nothing. A later decision of the same finding,
+//! identified by its fingerprint, replaces the earlier ones.
+use crate::utils::ErrorContext as EC;
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(super) enum Decision {
+  Applied,
+  Skipped,
+  /// shown again in the next session
+  Deferred,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct Record {
+  pub fingerprint: String,
+  pub decision: Decision,
+  pub rule_id: String,
+  pub file: String,
+  /// one-based line of the match
+  pub line: usize,
+}
+
+pub(super) struct Session {
+  path: PathBuf,
+  /// the last decisions of previous sessions by fingerprint
+  previous: HashMap<String, Decision>,
+  file: Mutex<File>,
+}
+
+impl Session {
+  pub fn open(path: &Path) -> Result<Self> {
+    let previous = read_records(path)?
+      .into_iter()
+      .map(|r| (r.fingerprint, r.decision))
+      .collect();
+    let file = OpenOptions::new()
+      .create(true)
+      .append(true)
+      .open(path)
+      .with_context(|| EC::WriteFile(path.to_path_buf()))?;
+    Ok(Self {
+      path: path.to_path_buf(),
+      previous,
+      file: Mutex::new(file),
+    })
+  }
+
+  /// Decision of the finding in previous sessions.
+  pub fn previous(&self, fingerprint: &str) -> Option<Decision> {
+    self.previous.get(fingerprint).copied()
+  }
+
+  pub fn record(&self, record: &Record) -> Result<()> {
+    let mut line = serde_json::to_string(record)?;
+    line.push('\n');
+    let mut file = self.file.lock().expect("should not fail");
+    file
+      .write_all(line.as_bytes())
+      .and_then(|_| file.flush())
+      .with_context(|| EC::WriteFile(self.path.clone()))
+  }
+}
+
+/// Records of a session file in order. A missing file has no records.
+fn read_records(path: &Path) -> Result<Vec<Record>> {
+  let file = match File::open(path) {
+    Ok(file) => file,
+    Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+    Err(e) => return Err(e).with_context(|| EC::ReadFile(path.to_path_buf())),
+  };
+  let mut records = vec![];
+  for line in BufReader::new(file).lines() {
+    let line = line.with_context(|| EC::ReadFile(path.to_path_buf()))?;
+    if line.trim().is_empty() {
+      continue;
+    }
+    let record = serde_json::from_str(&line).with_context(|| EC::ReadFile(path.to_path_buf()))?;
+    records.push(record);
+  }
+  Ok(records)
+}
+
+/// Count the last decision of every finding by rule.
+fn tally(records: Vec<Record>) -> BTreeMap<String, [usize; 3]> {
+  let mut last = HashMap::new();
+  for record in records {
+    last.insert(record.fingerprint, (record.rule_id, record.decision));
+  }
+  let mut tally = BTreeMap::new();
+  for (rule_id, decision) in last.into_values() {
+    let counts: &mut [usize; 3] = tally.entry(rule_id).or_default();
+    counts[decision as usize] += 1;
+  }
+  tally
+}
+
+/// Print the decisions of a session file by rule, see `--session-report`.
+pub fn print_session_report(path: &Path) -> Result<()> {
+  if !path.exists() {
+    return Err(anyhow::anyhow!(EC::ReadFile(path.to_path_buf())));
+  }
+  let tally = tally(read_records(path)?);
+  let mut total = [0; 3];
+  for (rule_id, [applied, skipped, deferred]) in &tally {
+    println!("Rule `{rule_id}`: {applied} applied, {skipped} skipped, {deferred} deferred.");
+    total[0] += applied;
+    total[1] += skipped;
+    total[2] += deferred;
+  }
+  let [applied, skipped, deferred] = total;
+  println!("Total: {applied} applied, {skipped} skipped, {deferred} deferred.");
+  Ok(())
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  fn record(fingerprint: &str, decision: Decision, rule_id: &str) -> Record {
+    Record {
+      fingerprint: fingerprint.into(),
+      decision,
+      rule_id: rule_id.into(),
+      file: "a.ts".into(),
+      line: 1,
+    }
+  }
+
+  #[test]
+  fn test_resume_session() {
+    let dir = tempfile::tempdir().expect("should create");
+    let path = dir.path().join("session.jsonl");
+    let session = Session::open(&path).expect("should open");
+    assert_eq!(session.previous("a"), None);
+    session
+      .record(&record("a", Decision::Skipped, "r"))
+      .expect("should record");
+    session
+      .record(&record("b", Decision::Deferred, "r"))
+      .expect("should record");
+    // a later decision wins
+    session
+      .record(&record("b", Decision::Applied, "r"))
+      .expect("should record");
+    drop(session);
+    let session = Session::open(&path).expect("should open");
+    assert_eq!(session.previous("a"), Some(Decision::Skipped));
+    assert_eq!(session.previous("b"), Some(Decision::Applied));
+    let content = std::fs::read_to_string(&path).expect("should read");
+    assert_eq!(content.lines().count(), 3);
+    assert!(content.starts_with(r#"{"fingerprint":"a","decision":"skipped","ruleId":"r""#));
+  }
+
+  #[test]
+  fn test_tally() {
+    let records = vec![
+      record("a", Decision::Skipped, "r1"),
+      record("a", Decision::Applied, "r1"),
+      record("b", Decision::Deferred, "r1"),
+      record("c", Decision::Skipped, "r2"),
+    ];
+    let tally = tally(records);
+    assert_eq!(tally["r1"], [1, 0, 1]);
+    assert_eq!(tally["r2"], [0, 1, 0]);
+  }
+
+  #[test]
+  fn test_invalid_session_file() {
+    let dir = tempfile::tempdir().expect("should create");
+    let path = dir.path().join("session.jsonl");
+    std::fs::write(&path, "not json\n").expect("should write");
+    assert!(Session::open(&path).is_err());
+    assert!(print_session_report(&dir.path().join("missing.jsonl")).is_err());
+  }
+}
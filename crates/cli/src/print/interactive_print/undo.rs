@@ -0,0 +1,249 @@
+//! Undo fixes written in an interactive session, see the `u` key.
+//! Every written fix is recorded with its range in the file, so that it can be
+//! reverted later. Reverting or writing a fix moves the later fixes of the same
+//! file, whose ranges are adjusted accordingly.
+use super::AcceptedFix;
+use crate::lang::SgLang;
+use crate::print::Diff;
+use crate::utils;
+use crate::utils::ErrorContext as EC;
+
+use anyhow::{Context, Result};
+use ast_grep_core::{AstGrep, StrDoc};
+
+use std::borrow::Cow;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+/// A fix written to a file.
+pub(super) struct AppliedFix {
+  pub path: PathBuf,
+  /// range of the replacement in the file, or of the original text after undo
+  pub range: Range<usize>,
+  pub replacement: String,
+  pub original: String,
+  pub rule_id: String,
+  lang: SgLang,
+  /// kind of the matched node and its range relative to the start of the original text
+  node: (String, Range<usize>),
+}
+
+impl AppliedFix {
+  /// Find the matched node in the reverted file to review the fix again.
+  pub fn restore<'g>(&self, grep: &'g AstGrep<StrDoc<SgLang>>) -> Option<Diff<'g>> {
+    let (kind, node) = &self.node;
+    let start = self.range.start;
+    let node = start + node.start..start + node.end;
+    let found = grep
+      .root()
+      .dfs()
+      .find(|n| n.range() == node && n.kind() == kind.as_str())?;
+    Some(Diff {
+      node_match: found.into(),
+      replacement: Cow::Owned(self.replacement.clone()),
+      range: self.range.clone(),
+    })
+  }
+
+  pub fn lang(&self) -> SgLang {
+    self.lang
+  }
+}
+
+pub(super) enum Undo {
+  Nothing,
+  /// the fix cannot be reverted, with the reason
+  Blocked(String),
+  Reverted(AppliedFix),
+}
+
+#[derive(Default)]
+pub(super) struct History {
+  applied: Vec<AppliedFix>,
+}
+
+impl History {
+  pub fn has_file(&self, path: &Path) -> bool {
+    self.applied.iter().any(|a| a.path == path)
+  }
+
+  /// Record fixes written to the file. Like `apply_fixes`, a fix overlapping
+  /// a previous one is not written and not recorded.
+  /// Returns the recorded fixes.
+  pub fn record<'f>(
+    &mut self,
+    path: &Path,
+    lang: SgLang,
+    source: &str,
+    fixes: &'f [AcceptedFix],
+  ) -> Vec<&'f AcceptedFix> {
+    let mut fixes: Vec<_> = fixes.iter().collect();
+    fixes.sort_by_key(|f| (f.range.start, f.range.end));
+    let mut written = vec![];
+    let mut recorded = vec![];
+    let mut shifts = vec![];
+    let mut end = 0;
+    let mut shift = 0;
+    for fix in fixes {
+      if fix.range.start < end {
+        continue;
+      }
+      end = fix.range.end;
+      let start = offset(fix.range.start, shift);
+      written.push(AppliedFix {
+        path: path.to_path_buf(),
+        range: start..start + fix.replacement.len(),
+        replacement: fix.replacement.clone(),
+        original: source[fix.range.clone()].to_string(),
+        rule_id: fix.rule_id.clone(),
+        lang,
+        node: fix.node.clone(),
+      });
+      let delta = fix.replacement.len() as isize - fix.range.len() as isize;
+      shifts.push((fix.range.end, delta));
+      shift += delta;
+      recorded.push(fix);
+    }
+    for applied in self.applied.iter_mut().filter(|a| a.path == path) {
+      let delta = shifts
+        .iter()
+        .filter(|(end, _)| *end <= applied.range.start)
+        .map(|(_, delta)| delta)
+        .sum();
+      applied.range = offset(applied.range.start, delta)..offset(applied.range.end, delta);
+    }
+    self.applied.extend(written);
+    recorded
+  }
+
+  /// Revert the last written fix, unless its file has changed since.
+  pub fn undo(&mut self) -> Result<Undo> {
+    let Some(fix) = self.applied.pop() else {
+      return Ok(Undo::Nothing);
+    };
+    let path = &fix.path;
+    let content = utils::read_source(path).with_context(|| EC::ReadFile(path.clone()))?;
+    if content.get(fix.range.clone()) != Some(fix.replacement.as_str()) {
+      return Ok(Undo::Blocked(format!(
+        "Cannot undo the fix of rule `{}` because {} has changed since it was written.",
+        fix.rule_id,
+        path.display()
+      )));
+    }
+    let Range { start, end } = fix.range;
+    let reverted = format!("{}{}{}", &content[..start], fix.original, &content[end..]);
+    utils::write_source(path, &reverted).with_context(|| EC::WriteFile(path.clone()))?;
+    let delta = fix.original.len() as isize - fix.replacement.len() as isize;
+    for applied in self.applied.iter_mut() {
+      if applied.path == *path && applied.range.start >= end {
+        applied.range = offset(applied.range.start, delta)..offset(applied.range.end, delta);
+      }
+    }
+    Ok(Undo::Reverted(AppliedFix {
+      range: start..start + fix.original.len(),
+      ..fix
+    }))
+  }
+}
+
+fn offset(pos: usize, delta: isize) -> usize {
+  (pos as isize + delta) as usize
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use ast_grep_language::SupportLang;
+
+  fn fix(range: Range<usize>, replacement: &str) -> AcceptedFix {
+    AcceptedFix {
+      node: ("call_expression".into(), 0..range.len()),
+      range,
+      replacement: replacement.into(),
+      rule_id: "test".into(),
+      fingerprint: String::new(),
+      line: 1,
+    }
+  }
+
+  fn write(history: &mut History, path: &Path, fixes: &[AcceptedFix]) {
+    let source = std::fs::read_to_string(path).expect("should read");
+    let refs = fixes
+      .iter()
+      .map(|f| crate::print::Fix {
+        range: f.range.clone(),
+        replacement: &f.replacement,
+        rule_id: &f.rule_id,
+      })
+      .collect();
+    let (new, _) = crate::print::apply_fixes(&source, refs, path);
+    std::fs::write(path, new).expect("should write");
+    history.record(path, SupportLang::TypeScript.into(), &source, fixes);
+  }
+
+  fn undo(history: &mut History, path: &Path) -> String {
+    let undone = history.undo().expect("should undo");
+    assert!(matches!(undone, Undo::Reverted(_)));
+    std::fs::read_to_string(path).expect("should read")
+  }
+
+  #[test]
+  fn test_undo_in_reverse_order() {
+    let dir = tempfile::tempdir().expect("should create");
+    let path = dir.path().join("test.ts");
+    std::fs::write(&path, "Some(1); Some(22)").expect("should write");
+    let mut history = History::default();
+    write(&mut history, &path, &[fix(0..7, "1"), fix(9..17, "22")]);
+    assert_eq!(undo(&mut history, &path), "1; Some(22)");
+    assert_eq!(undo(&mut history, &path), "Some(1); Some(22)");
+    assert!(matches!(history.undo(), Ok(Undo::Nothing)));
+  }
+
+  #[test]
+  fn test_undo_with_shifted_offsets() {
+    let dir = tempfile::tempdir().expect("should create");
+    let path = dir.path().join("test.ts");
+    std::fs::write(&path, "Some(1); Some(22)").expect("should write");
+    let mut history = History::default();
+    write(&mut history, &path, &[fix(9..17, "22")]);
+    // a later fix before the first one moves it
+    write(&mut history, &path, &[fix(0..7, "1")]);
+    assert!(history.has_file(&path));
+    let undone = history.undo().expect("should undo");
+    let Undo::Reverted(reverted) = undone else {
+      panic!("should revert");
+    };
+    assert_eq!(reverted.range, 0..7);
+    assert_eq!(undo(&mut history, &path), "Some(1); Some(22)");
+    assert!(!history.has_file(&path));
+  }
+
+  #[test]
+  fn test_undo_blocked_by_change() {
+    let dir = tempfile::tempdir().expect("should create");
+    let path = dir.path().join("test.ts");
+    std::fs::write(&path, "Some(1)").expect("should write");
+    let mut history = History::default();
+    write(&mut history, &path, &[fix(0..7, "1")]);
+    std::fs::write(&path, "2").expect("should write");
+    assert!(matches!(history.undo(), Ok(Undo::Blocked(_))));
+    assert_eq!(std::fs::read_to_string(&path).expect("should read"), "2");
+  }
+
+  #[test]
+  fn test_restore_diff() {
+    let dir = tempfile::tempdir().expect("should create");
+    let path = dir.path().join("test.ts");
+    std::fs::write(&path, "a; Some(1)").expect("should write");
+    let mut history = History::default();
+    write(&mut history, &path, &[fix(3..10, "1")]);
+    let Ok(Undo::Reverted(reverted)) = history.undo() else {
+      panic!("should revert");
+    };
+    let grep = AstGrep::new("a; Some(1)", reverted.lang());
+    let diff = reverted.restore(&grep).expect("should restore");
+    assert_eq!(diff.range, 3..10);
+    assert_eq!(diff.node_match.text(), "Some(1)");
+    assert_eq!(diff.replacement, "1");
+  }
+}
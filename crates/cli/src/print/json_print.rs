@@ -1,5 +1,5 @@
 use crate::lang::SgLang;
-use ast_grep_config::{RuleConfig, Severity};
+use ast_grep_config::{Label, LabelStyle, RuleConfig, Severity};
 use ast_grep_core::{meta_var::MetaVariable, Node as SgNode, NodeMatch as SgNodeMatch, StrDoc};
 
 type NodeMatch<'a, L> = SgNodeMatch<'a, StrDoc<L>>;
@@ -7,7 +7,7 @@ type Node<'a, L> = SgNode<'a, StrDoc<L>>;
 
 use std::collections::HashMap;
 
-use super::{Diff, Printer};
+use super::{apply_fixes, BatchOutput, Diff, Fix, Printer};
 use anyhow::Result;
 use clap::ValueEnum;
 use codespan_reporting::files::SimpleFile;
@@ -30,14 +30,21 @@ macro_rules! Diffs {
 #[derive(Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 struct Position {
+  /// zero-based line
   line: usize,
+  /// zero-based column in characters, not bytes or display width.
+  /// A UTF-8 BOM does not count, like in editors.
   column: usize,
 }
 
 #[derive(Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 struct Range {
-  /// inclusive start, exclusive end
+  /// inclusive start, exclusive end, in bytes from the start of the UTF-8 source text.
+  /// A UTF-8 BOM counts, so for UTF-8 files the offsets are where fixes splice the file
+  /// on disk. UTF-16 files are transcoded to UTF-8 first, so their offsets refer to the
+  /// transcoded text, not the file on disk.
+  /// See [crate::utils::Encoding] for how source files are decoded.
   byte_offset: std::ops::Range<usize>,
   start: Position,
   end: Position,
@@ -48,6 +55,9 @@ struct Range {
 struct LabelJSON<'a> {
   text: &'a str,
   range: Range,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  message: Option<&'a str>,
+  style: LabelStyle,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -138,31 +148,46 @@ fn from_env<'a>(nm: &NodeMatch<'a, SgLang>) -> Option<MetaVariables<'a>> {
 }
 
 fn get_range(n: &Node<'_, SgLang>) -> Range {
-  let start_pos = n.start_pos();
-  let end_pos = n.end_pos();
+  let source = n.root().get_text();
+  let range = n.range();
   Range {
-    byte_offset: n.range(),
     start: Position {
-      line: start_pos.0,
-      column: start_pos.1,
+      line: n.start_pos().0,
+      column: char_column(source, range.start),
     },
     end: Position {
-      line: end_pos.0,
-      column: end_pos.1,
+      line: n.end_pos().0,
+      column: char_column(source, range.end),
     },
+    byte_offset: range,
   }
 }
 
+/// Column of the byte offset in characters. tree-sitter reports columns in bytes.
+fn char_column(source: &str, offset: usize) -> usize {
+  let line = match source[..offset].rfind('\n') {
+    Some(i) => &source[i + 1..offset],
+    None => source[..offset].trim_start_matches('\u{feff}'),
+  };
+  line.chars().count()
+}
+
 impl<'a> MatchJSON<'a> {
   fn new(nm: NodeMatch<'a, SgLang>, path: &'a str, context: (u16, u16)) -> Self {
     let display = nm.display_context(context.0 as usize, context.1 as usize);
-    let lines = format!("{}{}{}", display.leading, display.matched, display.trailing);
+    // like columns, lines do not include a UTF-8 BOM
+    let leading = if display.start_line == 0 {
+      display.leading.trim_start_matches('\u{feff}')
+    } else {
+      display.leading
+    };
+    let lines = format!("{}{}{}", leading, display.matched, display.trailing);
     MatchJSON {
       file: Cow::Borrowed(path),
       text: nm.text(),
       lines,
       char_count: CharCount {
-        leading: display.leading.chars().count(),
+        leading: leading.chars().count(),
         trailing: display.trailing.chars().count(),
       },
       language: *nm.lang(),
@@ -180,18 +205,78 @@ impl<'a> MatchJSON<'a> {
     ret
   }
 }
-fn get_labels<'a>(nm: &NodeMatch<'a, SgLang>) -> Option<Vec<MatchNode<'a>>> {
-  let env = nm.get_env();
-  let labels = env.get_labels("secondary")?;
-  Some(
-    labels
-      .iter()
-      .map(|l| MatchNode {
-        text: l.text(),
-        range: get_range(l),
-      })
-      .collect(),
-  )
+fn get_labels<'a>(
+  nm: &NodeMatch<'a, SgLang>,
+  rule: &'a RuleConfig<SgLang>,
+) -> Option<Vec<LabelJSON<'a>>> {
+  let labels = rule.get_labels(nm);
+  if labels.is_empty() {
+    return None;
+  }
+  Some(labels.into_iter().map(label_to_json).collect())
+}
+
+fn label_to_json<'a>(label: Label<'a, 'a, StrDoc<SgLang>>) -> LabelJSON<'a> {
+  let source = label.start_node.root().get_text();
+  let range = label.range();
+  LabelJSON {
+    text: &source[range.clone()],
+    range: Range {
+      start: Position {
+        line: label.start_node.start_pos().0,
+        column: char_column(source, range.start),
+      },
+      end: Position {
+        line: label.end_node.end_pos().0,
+        column: char_column(source, range.end),
+      },
+      byte_offset: range,
+    },
+    message: label.message,
+    style: label.style,
+  }
+}
+
+/// Version of the JSON format of rule findings.
+/// Version 1 is the format without `schemaVersion` and `fingerprint`.
+const SCHEMA_VERSION: u32 = 2;
+
+/// A stable hash to identify the same finding across runs.
+///
+/// The fingerprint is the 64-bit FNV-1a hash, printed as 16 hex digits, of these fields
+/// separated by NUL bytes: the rule id, the file path with `/` as separator, the text
+/// before the match on its first line, the matched text, the text after the match on
+/// its last line and the occurrence index, i.e. how many times the same lines appear
+/// earlier in the file. Line numbers and byte offsets are excluded so that edits
+/// elsewhere in the file do not change the fingerprint, while the occurrence index
+/// tells apart findings on duplicated lines.
+pub(super) fn fingerprint(rule_id: &str, path: &str, nm: &NodeMatch<SgLang>) -> String {
+  const FNV_OFFSET: u64 = 0xcbf29ce484222325;
+  const FNV_PRIME: u64 = 0x100000001b3;
+  let display = nm.display_context(0, 0);
+  let path = path.replace('\\', "/");
+  let source = nm.root().get_text();
+  let range = nm.range();
+  let start = range.start - display.leading.len();
+  let lines = &source[start..range.end + display.trailing.len()];
+  let occurrence = source[..start].matches(lines).count().to_string();
+  let fields = [
+    rule_id,
+    &path,
+    display.leading,
+    &display.matched,
+    display.trailing,
+    &occurrence,
+  ];
+  let mut hash = FNV_OFFSET;
+  for (i, field) in fields.iter().enumerate() {
+    let separator: &[u8] = if i == 0 { &[] } else { &[0] };
+    for byte in separator.iter().chain(field.as_bytes()) {
+      hash ^= *byte as u64;
+      hash = hash.wrapping_mul(FNV_PRIME);
+    }
+  }
+  format!("{hash:016x}")
 }
 
 #[derive(Serialize, Deserialize)]
@@ -204,12 +289,15 @@ struct RuleMatchJSON<'a> {
   note: Option<String>,
   message: String,
   #[serde(skip_serializing_if = "Option::is_none")]
-  labels: Option<Vec<MatchNode<'a>>>,
+  labels: Option<Vec<LabelJSON<'a>>>,
+  fingerprint: String,
+  schema_version: u32,
 }
 impl<'a> RuleMatchJSON<'a> {
   fn new(nm: NodeMatch<'a, SgLang>, path: &'a str, rule: &'a RuleConfig<SgLang>) -> Self {
     let message = rule.get_message(&nm);
-    let labels = get_labels(&nm);
+    let labels = get_labels(&nm, rule);
+    let fingerprint = fingerprint(&rule.id, path, &nm);
     let matched = MatchJSON::new(nm, path, (0, 0));
     Self {
       matched,
@@ -218,12 +306,15 @@ impl<'a> RuleMatchJSON<'a> {
       note: rule.note.clone(),
       message,
       labels,
+      fingerprint,
+      schema_version: SCHEMA_VERSION,
     }
   }
   fn diff(diff: Diff<'a>, path: &'a str, rule: &'a RuleConfig<SgLang>) -> Self {
     let nm = &diff.node_match;
     let message = rule.get_message(nm);
-    let labels = get_labels(nm);
+    let labels = get_labels(nm, rule);
+    let fingerprint = fingerprint(&rule.id, path, nm);
     let matched = MatchJSON::diff(diff, path, (0, 0));
     Self {
       matched,
@@ -232,6 +323,8 @@ impl<'a> RuleMatchJSON<'a> {
       note: rule.note.clone(),
       message,
       labels,
+      fingerprint,
+      schema_version: SCHEMA_VERSION,
     }
   }
 }
@@ -257,6 +350,97 @@ pub struct JSONPrinter<W: Write + Send + Sync> {
   context: (u16, u16),
   // indicate if any matches happened
   matched: AtomicBool,
+  /// print the source after applying all fixes once for every file with fixes
+  include_rewritten: bool,
+  /// fixes of the current file if `include_rewritten` is set
+  pending: Mutex<Option<PendingFile>>,
+}
+
+/// files larger than this are not rewritten for `rewrittenSource`
+const MAX_REWRITTEN_SIZE: usize = 1024 * 1024;
+
+/// Fixes of one file, buffered until all of them are known.
+struct PendingFile {
+  path: String,
+  /// None if the file has no fixes yet or is too large to rewrite
+  source: Option<String>,
+  /// the file is larger than [MAX_REWRITTEN_SIZE], its source is not buffered
+  too_large: bool,
+  /// range, replacement and rule id of every fix
+  fixes: Vec<(std::ops::Range<usize>, String, String)>,
+}
+
+/// Printed once after the matches of a file with fixes, see `--json-include-rewritten`.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct RewrittenJSON {
+  file: String,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  rewritten_source: Option<String>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  rewritten_source_notice: Option<String>,
+}
+
+impl PendingFile {
+  fn new(path: String) -> Self {
+    Self {
+      path,
+      source: None,
+      too_large: false,
+      fixes: vec![],
+    }
+  }
+
+  fn add_fix(&mut self, diff: &Diff, rule_id: &str) {
+    if self.too_large {
+      return;
+    }
+    if self.source.is_none() {
+      let source = diff.get_root_text();
+      if source.len() > MAX_REWRITTEN_SIZE {
+        self.too_large = true;
+        return;
+      }
+      self.source = Some(source.to_string());
+    }
+    let replacement = diff.replacement.to_string();
+    self
+      .fixes
+      .push((diff.range.clone(), replacement, rule_id.to_string()));
+  }
+
+  /// The rewritten source of the file, None if the file has no fixes.
+  fn into_json(self) -> Option<RewrittenJSON> {
+    let Self {
+      path,
+      source,
+      too_large,
+      fixes,
+    } = self;
+    if too_large {
+      let notice = format!("file is larger than {MAX_REWRITTEN_SIZE} bytes");
+      return Some(RewrittenJSON {
+        file: path,
+        rewritten_source: None,
+        rewritten_source_notice: Some(notice),
+      });
+    }
+    let source = source?;
+    let fixes = fixes
+      .iter()
+      .map(|(range, replacement, rule_id)| Fix {
+        range: range.clone(),
+        replacement,
+        rule_id,
+      })
+      .collect();
+    let (rewritten, _) = apply_fixes(&source, fixes, Path::new(&path));
+    Some(RewrittenJSON {
+      file: path,
+      rewritten_source: Some(rewritten),
+      rewritten_source_notice: None,
+    })
+  }
 }
 impl JSONPrinter<Stdout> {
   pub fn stdout(style: JsonStyle) -> Self {
@@ -272,6 +456,8 @@ impl<W: Write + Send + Sync> JSONPrinter<W> {
       output: Mutex::new(output),
       context: (0, 0),
       matched: AtomicBool::new(false),
+      include_rewritten: false,
+      pending: Mutex::new(None),
     }
   }
 
@@ -280,6 +466,40 @@ impl<W: Write + Send + Sync> JSONPrinter<W> {
     self
   }
 
+  pub fn include_rewritten(mut self, include_rewritten: bool) -> Self {
+    self.include_rewritten = include_rewritten;
+    self
+  }
+
+  /// Print docs of the file, and buffer the fixes if the rewritten source is needed.
+  /// The rewritten source is printed when the matches of the next file or the end come.
+  fn print_file_docs<'a, S: Serialize>(
+    &self,
+    path: &str,
+    docs: impl Iterator<Item = S>,
+    fixes: impl Iterator<Item = (&'a Diff<'a>, &'a str)>,
+  ) -> Result<()> {
+    if !self.include_rewritten {
+      return self.print_docs(docs);
+    }
+    let mut pending = self.pending.lock().expect("should work");
+    if pending.as_ref().map_or(false, |p| p.path != path) {
+      self.flush_pending(&mut pending)?;
+    }
+    let file = pending.get_or_insert_with(|| PendingFile::new(path.to_string()));
+    for (diff, rule_id) in fixes {
+      file.add_fix(diff, rule_id);
+    }
+    self.print_docs(docs)
+  }
+
+  fn flush_pending(&self, pending: &mut Option<PendingFile>) -> Result<()> {
+    match pending.take().and_then(PendingFile::into_json) {
+      Some(rewritten) => self.print_docs(std::iter::once(rewritten)),
+      None => Ok(()),
+    }
+  }
+
   fn print_docs<S: Serialize>(&self, mut docs: impl Iterator<Item = S>) -> Result<()> {
     let Some(doc) = docs.next() else {
       return Ok(());
@@ -331,19 +551,27 @@ impl<W: Write + Send + Sync> Printer for JSONPrinter<W> {
   ) -> Result<()> {
     let path = file.name();
     let jsons = matches.map(|nm| RuleMatchJSON::new(nm, path, rule));
-    self.print_docs(jsons)
+    self.print_file_docs(path, jsons, std::iter::empty())
   }
 
   fn print_matches<'a>(&self, matches: Matches!('a), path: &Path) -> Result<()> {
     let path = path.to_string_lossy();
     let jsons = matches.map(|nm| MatchJSON::new(nm, &path, self.context));
-    self.print_docs(jsons)
+    self.print_file_docs(&path, jsons, std::iter::empty())
   }
 
   fn print_diffs<'a>(&self, diffs: Diffs!('a), path: &Path) -> Result<()> {
     let path = path.to_string_lossy();
-    let jsons = diffs.map(|diff| MatchJSON::diff(diff, &path, self.context));
-    self.print_docs(jsons)
+    if !self.include_rewritten {
+      let jsons = diffs.map(|diff| MatchJSON::diff(diff, &path, self.context));
+      return self.print_docs(jsons);
+    }
+    let diffs: Vec<_> = diffs.collect();
+    let fixes = diffs.iter().map(|diff| (diff, "rewrite"));
+    let jsons = diffs
+      .iter()
+      .map(|diff| MatchJSON::diff(diff.clone(), &path, self.context));
+    self.print_file_docs(&path, jsons, fixes)
   }
   fn print_rule_diffs(
     &self,
@@ -351,10 +579,17 @@ impl<W: Write + Send + Sync> Printer for JSONPrinter<W> {
     path: &Path,
   ) -> Result<()> {
     let path = path.to_string_lossy();
+    if !self.include_rewritten {
+      let jsons = diffs
+        .into_iter()
+        .map(|(diff, rule)| RuleMatchJSON::diff(diff, &path, rule));
+      return self.print_docs(jsons);
+    }
+    let fixes = diffs.iter().map(|(diff, rule)| (diff, rule.id.as_str()));
     let jsons = diffs
-      .into_iter()
-      .map(|(diff, rule)| RuleMatchJSON::diff(diff, &path, rule));
-    self.print_docs(jsons)
+      .iter()
+      .map(|(diff, rule)| RuleMatchJSON::diff(diff.clone(), &path, rule));
+    self.print_file_docs(&path, jsons, fixes)
   }
 
   fn before_print(&self) -> Result<()> {
@@ -367,6 +602,7 @@ impl<W: Write + Send + Sync> Printer for JSONPrinter<W> {
   }
 
   fn after_print(&self) -> Result<()> {
+    self.flush_pending(&mut self.pending.lock().expect("should work"))?;
     if self.style == JsonStyle::Stream {
       return Ok(());
     }
@@ -378,11 +614,17 @@ impl<W: Write + Send + Sync> Printer for JSONPrinter<W> {
     writeln!(&mut lock, "]")?;
     Ok(())
   }
+
+  fn print_record(&self, record: &BatchOutput) -> Result<()> {
+    self.print_docs(std::iter::once(record))
+  }
 }
 
 #[cfg(test)]
 mod test {
   use super::*;
+  use crate::print::{apply_fixes, Fix, LineEnding};
+  use crate::print::{BatchError, BatchSummary};
   use ast_grep_config::{from_yaml_string, Fixer, GlobalRules};
   use ast_grep_language::{Language, SupportLang};
 
@@ -468,7 +710,7 @@ mod test {
       let grep = lang.ast_grep(source);
       let matches = grep.root().find_all(pattern);
       let fixer = Fixer::from_str(replace, &lang).expect("should work");
-      let diffs = matches.map(|m| Diff::generate(m, &pattern, &fixer));
+      let diffs = matches.map(|m| Diff::generate(m, &pattern, &fixer, LineEnding::Lf));
       printer.before_print().unwrap();
       printer.print_diffs(diffs, "test.tsx".as_ref()).unwrap();
       printer.after_print().unwrap();
@@ -522,6 +764,96 @@ rule:
     }
   }
 
+  #[test]
+  fn test_labels_json() {
+    let globals = GlobalRules::default();
+    let rule = from_yaml_string(
+      r"
+id: test
+message: test rule
+language: TypeScript
+rule: { pattern: console.log($$$ARGS) }
+labels:
+  ARGS:
+    style: secondary
+    message: arguments",
+      &globals,
+    )
+    .unwrap()
+    .pop()
+    .unwrap();
+    let source = "console.log(1, 2)".to_string();
+    let printer = make_test_printer(JsonStyle::Compact);
+    let grep = SgLang::from(SupportLang::TypeScript).ast_grep(&source);
+    let matches = grep.root().find_all(&rule.matcher);
+    printer.before_print().unwrap();
+    let file = SimpleFile::new(Cow::Borrowed("test.ts"), &source);
+    printer.print_rule(matches, file, &rule).unwrap();
+    printer.after_print().unwrap();
+    let json_str = get_text(&printer);
+    let json: Vec<RuleMatchJSON> = serde_json::from_str(&json_str).unwrap();
+    let labels = json[0].labels.as_ref().expect("should have labels");
+    assert_eq!(labels[0].text, "1, 2");
+    assert_eq!(labels[0].range.byte_offset, 12..16);
+    assert_eq!(labels[0].message, Some("arguments"));
+  }
+
+  fn get_fingerprint(source: &str, pattern: &str) -> String {
+    let source = source.to_string();
+    let printer = make_test_printer(JsonStyle::Compact);
+    let grep = SgLang::from(SupportLang::TypeScript).ast_grep(&source);
+    let rule = make_rule(pattern);
+    let matches = grep.root().find_all(&rule.matcher);
+    printer.before_print().unwrap();
+    let file = SimpleFile::new(Cow::Borrowed("src/test.ts"), &source);
+    printer.print_rule(matches, file, &rule).unwrap();
+    printer.after_print().unwrap();
+    let json_str = get_text(&printer);
+    let json: Vec<RuleMatchJSON> = serde_json::from_str(&json_str).unwrap();
+    assert_eq!(json[0].schema_version, SCHEMA_VERSION);
+    json[0].fingerprint.clone()
+  }
+
+  #[test]
+  fn test_fingerprint_ignore_line() {
+    let expected = get_fingerprint("let a = 1\nlet b = Some(1)", "Some($A)");
+    let shifted = get_fingerprint("let a = 1\n\n\n\nlet b = Some(1)", "Some($A)");
+    assert_eq!(expected, shifted);
+    assert_eq!(expected.len(), 16);
+  }
+
+  #[test]
+  fn test_fingerprint_change_text() {
+    let expected = get_fingerprint("let b = Some(1)", "Some($A)");
+    assert_ne!(expected, get_fingerprint("let b = Some(2)", "Some($A)"));
+    // surrounding text on the same line is part of the fingerprint
+    assert_ne!(expected, get_fingerprint("let c = Some(1)", "Some($A)"));
+  }
+
+  #[test]
+  fn test_fingerprint_duplicate_lines() {
+    let source = "let b = Some(1)\nlet b = Some(1)".to_string();
+    let printer = make_test_printer(JsonStyle::Compact);
+    let grep = SgLang::from(SupportLang::TypeScript).ast_grep(&source);
+    let rule = make_rule("Some($A)");
+    let matches = grep.root().find_all(&rule.matcher);
+    printer.before_print().unwrap();
+    let file = SimpleFile::new(Cow::Borrowed("src/test.ts"), &source);
+    printer.print_rule(matches, file, &rule).unwrap();
+    printer.after_print().unwrap();
+    let json_str = get_text(&printer);
+    let json: Vec<RuleMatchJSON> = serde_json::from_str(&json_str).unwrap();
+    assert_eq!(json.len(), 2);
+    assert_ne!(json[0].fingerprint, json[1].fingerprint);
+    // the first occurrence keeps the fingerprint of a single line
+    assert_eq!(
+      json[0].fingerprint,
+      get_fingerprint("let b = Some(1)", "Some($A)")
+    );
+    let shifted = get_fingerprint("let a = 1\nlet b = Some(1)\nlet b = Some(1)", "Some($A)");
+    assert_eq!(json[0].fingerprint, shifted);
+  }
+
   #[test]
   fn test_single_matched_json() {
     let printer = make_test_printer(JsonStyle::Pretty);
@@ -541,6 +873,139 @@ rule:
     assert_eq!(actual["A"].text, "123");
   }
 
+  #[test]
+  fn test_char_column_json() {
+    let printer = make_test_printer(JsonStyle::Compact);
+    let lang = SgLang::from(SupportLang::Tsx);
+    let grep = lang.ast_grep("let a = '你好';\nlet e = '👨‍👩‍👧'; \tfoo(1)");
+    let matches = grep.root().find_all("foo($A)");
+    printer.before_print().unwrap();
+    printer.print_matches(matches, "test.tsx".as_ref()).unwrap();
+    printer.after_print().unwrap();
+    let json_str = get_text(&printer);
+    let json: Vec<MatchJSON> = serde_json::from_str(&json_str).unwrap();
+    let range = &json[0].range;
+    assert_eq!((range.start.line, range.start.column), (1, 18));
+    assert_eq!((range.end.line, range.end.column), (1, 24));
+    assert_eq!(range.byte_offset, 49..55);
+  }
+
+  #[test]
+  fn test_bom_byte_offset_json() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("test.tsx");
+    std::fs::write(&path, "\u{feff}let a = '你好'; foo(1)").unwrap();
+    // read the file like the walker does, instead of building the text in memory
+    let source = crate::utils::read_source(&path).unwrap();
+    let lang = SgLang::from(SupportLang::Tsx);
+    let grep = lang.ast_grep(&source);
+    let fixer = Fixer::from_str("bar($A)", &lang).expect("should work");
+    let diffs: Vec<_> = grep
+      .root()
+      .find_all("foo($A)")
+      .map(|m| Diff::generate(m, &"foo($A)", &fixer, LineEnding::Lf))
+      .collect();
+    let fixes = diffs.iter().map(|d| Fix::new(d, None)).collect();
+    let (fixed, _) = apply_fixes(&source, fixes, "test.tsx".as_ref());
+    let printer = make_test_printer(JsonStyle::Compact);
+    printer.before_print().unwrap();
+    printer
+      .print_diffs(diffs.into_iter(), "test.tsx".as_ref())
+      .unwrap();
+    printer.after_print().unwrap();
+    let json_str = get_text(&printer);
+    let json: Vec<MatchJSON> = serde_json::from_str(&json_str).unwrap();
+    let range = &json[0].range;
+    let bytes = std::fs::read(&path).unwrap();
+    // 3 bytes of BOM and 6 bytes of 你好 before the match
+    assert_eq!(range.byte_offset, 21..27);
+    assert_eq!(&bytes[range.byte_offset.clone()], b"foo(1)");
+    assert_eq!(json[0].replacement_offsets, Some(21..27));
+    let mut spliced = bytes.clone();
+    spliced.splice(range.byte_offset.clone(), *b"bar(1)");
+    assert_eq!(spliced, fixed.as_bytes());
+    // the BOM is not a column
+    assert_eq!((range.start.column, range.end.column), (14, 20));
+    let single = &json[0].meta_variables.as_ref().unwrap().single;
+    assert_eq!(&bytes[single["A"].range.byte_offset.clone()], b"1");
+  }
+
+  fn make_fix_rule(id: &str, pattern: &str, fix: &str) -> RuleConfig<SgLang> {
+    let rule =
+      format!("{{id: {id}, language: TypeScript, rule: {{pattern: '{pattern}'}}, fix: '{fix}'}}");
+    from_yaml_string(&rule, &GlobalRules::default())
+      .unwrap()
+      .pop()
+      .unwrap()
+  }
+
+  fn print_fixes(
+    printer: &JSONPrinter<Test>,
+    path: &str,
+    source: &str,
+    rules: &[&RuleConfig<SgLang>],
+  ) {
+    let grep = SgLang::from(SupportLang::TypeScript).ast_grep(source);
+    for rule in rules {
+      let fixer = rule.matcher.fixer.as_ref().unwrap();
+      let diffs = grep
+        .root()
+        .find_all(&rule.matcher)
+        .map(|m| {
+          (
+            Diff::generate(m, &rule.matcher, fixer, LineEnding::Lf),
+            *rule,
+          )
+        })
+        .collect();
+      printer.print_rule_diffs(diffs, path.as_ref()).unwrap();
+    }
+  }
+
+  #[test]
+  fn test_include_rewritten() {
+    let printer = make_test_printer(JsonStyle::Compact).include_rewritten(true);
+    let rule_a = make_fix_rule("a", "foo($A)", "bar($A)");
+    let rule_b = make_fix_rule("b", "let $A = 1", "const $A = 1;");
+    // fixes of different rules in one file, and an overlapping fix
+    let overlap = make_fix_rule("c", "foo(1)", "baz()");
+    printer.before_print().unwrap();
+    print_fixes(
+      &printer,
+      "a.ts",
+      "let a = 1; foo(1)",
+      &[&rule_a, &rule_b, &overlap],
+    );
+    print_fixes(&printer, "b.ts", "foo(2)", &[&rule_a]);
+    printer.after_print().unwrap();
+    let json_str = get_text(&printer);
+    let json: Vec<serde_json::Value> = serde_json::from_str(&json_str).unwrap();
+    assert_eq!(json.len(), 6);
+    for doc in [&json[0], &json[1], &json[2], &json[4]] {
+      assert!(doc.get("rewrittenSource").is_none());
+    }
+    // one record for each file after its matches
+    assert_eq!(json[3]["file"], "a.ts");
+    assert_eq!(json[3]["rewrittenSource"], "const a = 1; bar(1)");
+    assert_eq!(json[5]["file"], "b.ts");
+    assert_eq!(json[5]["rewrittenSource"], "bar(2)");
+  }
+
+  #[test]
+  fn test_include_rewritten_large_file() {
+    let printer = make_test_printer(JsonStyle::Compact).include_rewritten(true);
+    let rule = make_fix_rule("a", "foo($A)", "bar($A)");
+    let source = format!("foo(1);{}", " ".repeat(MAX_REWRITTEN_SIZE));
+    printer.before_print().unwrap();
+    print_fixes(&printer, "a.ts", &source, &[&rule]);
+    printer.after_print().unwrap();
+    let json: Vec<serde_json::Value> = serde_json::from_str(&get_text(&printer)).unwrap();
+    assert_eq!(json.len(), 2);
+    assert_eq!(json[0]["replacement"], "bar(1)");
+    assert!(json[1].get("rewrittenSource").is_none());
+    assert!(json[1]["rewrittenSourceNotice"].is_string());
+  }
+
   #[test]
   fn test_multi_matched_json() {
     let printer = make_test_printer(JsonStyle::Compact);
@@ -578,6 +1043,41 @@ rule:
     }
   }
 
+  #[test]
+  fn test_print_record() {
+    let printer = make_test_printer(JsonStyle::Stream);
+    let grep = SgLang::from(SupportLang::Tsx).ast_grep("let a = 123");
+    printer.before_print().unwrap();
+    let error = BatchError {
+      line: 1,
+      path: None,
+      error: "invalid record".into(),
+    };
+    printer.print_record(&BatchOutput::Error(error)).unwrap();
+    let matches = grep.root().find_all("123");
+    printer.print_matches(matches, "test.tsx".as_ref()).unwrap();
+    printer.after_print().unwrap();
+    let summary = BatchSummary {
+      records: 2,
+      matched: 1,
+      errors: 1,
+    };
+    printer
+      .print_record(&BatchOutput::Summary(summary))
+      .unwrap();
+    let json_str = get_text(&printer);
+    let docs: Vec<serde_json::Value> = json_str
+      .lines()
+      .map(|l| serde_json::from_str(l).unwrap())
+      .collect();
+    assert_eq!(docs.len(), 3, "{json_str}");
+    let error = serde_json::json!({"error": {"line": 1, "error": "invalid record"}});
+    assert_eq!(docs[0], error);
+    assert_eq!(docs[1]["text"], "123");
+    let summary = serde_json::json!({"summary": {"records": 2, "matched": 1, "errors": 1}});
+    assert_eq!(docs[2], summary);
+  }
+
   use crate::verify::test::get_rule_config;
   const TRANSFORM_TEXT: &str = "
 transform:
@@ -1,7 +1,10 @@
+mod choose_color;
 mod cloud_print;
 mod colored_print;
+mod diff_print;
 mod interactive_print;
 mod json_print;
+mod path_style;
 
 use crate::lang::SgLang;
 use ast_grep_config::{Fixer, RuleConfig};
@@ -9,16 +12,20 @@ use ast_grep_core::{Matcher, NodeMatch as SgNodeMatch, StrDoc};
 
 use anyhow::Result;
 use clap::ValueEnum;
+use serde::Serialize;
 
 use std::borrow::Cow;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
+pub use choose_color::ColorStream;
 pub use cloud_print::{CloudPrinter, Platform};
 pub use codespan_reporting::files::SimpleFile;
 pub use codespan_reporting::term::termcolor::ColorChoice;
-pub use colored_print::{print_diff, ColoredPrinter, Heading, PrintStyles, ReportStyle};
-pub use interactive_print::InteractivePrinter;
+pub use colored_print::{ColorTheme, ColoredPrinter, DiffStyle, GroupBy, Heading, ReportStyle};
+pub use diff_print::{apply_fixes, DiffPrinter, Fix};
+pub use interactive_print::{print_session_report, InteractivePrinter};
 pub use json_print::{JSONPrinter, JsonStyle};
+pub use path_style::{PathStyle, PathStylePrinter};
 
 type NodeMatch<'a, L> = SgNodeMatch<'a, StrDoc<L>>;
 
@@ -54,6 +61,41 @@ pub trait Printer: Sync + Send {
   fn after_print(&self) -> Result<()> {
     Ok(())
   }
+  /// Run before the findings of one rule when findings are sent rule by rule.
+  /// `count` is the number of findings of the rule in all files.
+  #[inline]
+  fn before_rule(&self, _rule: &RuleConfig<SgLang>, _count: usize) -> Result<()> {
+    Ok(())
+  }
+  /// Run before the findings of one file when findings are sent file by file.
+  /// `count` is the number of findings in the file.
+  #[inline]
+  fn before_file(&self, _path: &Path, _count: usize) -> Result<()> {
+    Ok(())
+  }
+  /// Unchanged lines printed around the changes of diffs, None if diffs are printed without context.
+  #[inline]
+  fn diff_context(&self) -> Option<usize> {
+    None
+  }
+  /// Print diffs with the context lines from now on, see the `+` and `-` keys of interactive mode.
+  #[inline]
+  fn set_diff_context(&self, _context: usize) {}
+  /// Files whose fixes are skipped because they changed on disk after scanning.
+  fn stale_files(&self) -> Vec<PathBuf> {
+    vec![]
+  }
+  /// Number of files modified and edits applied, None if the printer does not rewrite code.
+  #[inline]
+  fn applied_edits(&self) -> Option<(usize, usize)> {
+    None
+  }
+  /// Print a record other than findings, like the summary of `--stdin-batch`.
+  /// Only JSON printers print records, other printers ignore them.
+  #[inline]
+  fn print_record(&self, _record: &BatchOutput) -> Result<()> {
+    Ok(())
+  }
 }
 
 #[derive(Clone)]
@@ -66,14 +108,16 @@ pub struct Diff<'n> {
 }
 
 impl<'n> Diff<'n> {
+  /// Generate the fix of a match. The line ending is computed once per file by [LineEnding::of].
   pub fn generate(
     node_match: NodeMatch<'n, SgLang>,
     matcher: &impl Matcher<SgLang>,
     rewrite: &Fixer<SgLang>,
+    line_ending: LineEnding,
   ) -> Self {
     let edit = node_match.make_edit(matcher, rewrite);
     let replacement = String::from_utf8(edit.inserted_text).unwrap();
-    let replacement = Cow::Owned(replacement);
+    let replacement = Cow::Owned(line_ending.apply(replacement));
     Self {
       node_match,
       replacement,
@@ -89,11 +133,72 @@ impl<'n> Diff<'n> {
   }
 }
 
+/// Line ending of the replacements in a file, so rewrites do not mix LF and CRLF lines.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LineEnding {
+  Lf,
+  CrLf,
+}
+
+impl LineEnding {
+  /// The dominant line ending of the source. It scans the whole file,
+  /// so compute it once per file rather than once per fix.
+  pub fn of(source: &str) -> Self {
+    let crlf_count = source.matches("\r\n").count();
+    let lf_count = source.matches('\n').count() - crlf_count;
+    if crlf_count > lf_count {
+      Self::CrLf
+    } else {
+      Self::Lf
+    }
+  }
+
+  fn apply(self, replacement: String) -> String {
+    if !replacement.contains('\n') {
+      return replacement;
+    }
+    let lf = replacement.replace("\r\n", "\n");
+    match self {
+      Self::Lf => lf,
+      Self::CrLf => lf.replace('\n', "\r\n"),
+    }
+  }
+}
+
+/// Output of `--stdin-batch` other than findings, printed as `{"error": ..}` or `{"summary": ..}`.
+/// Files that cannot be read are also printed as `{"errors": [..]}` after the findings.
+/// Fields are serialized in declaration order.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum BatchOutput {
+  Error(BatchError),
+  Summary(BatchSummary),
+  Errors(Vec<String>),
+}
+
+/// Printed for a record that cannot be processed. Path is absent if the line is not a record.
+#[derive(Serialize)]
+pub struct BatchError {
+  pub line: usize,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub path: Option<PathBuf>,
+  pub error: String,
+}
+
+#[derive(Default, Serialize)]
+pub struct BatchSummary {
+  /// records read from StdIn, including malformed ones
+  pub records: usize,
+  /// records with at least one result
+  pub matched: usize,
+  pub errors: usize,
+}
+
 #[derive(ValueEnum, Clone, Copy)]
 pub enum ColorArg {
-  /// Try to use colors, but don't force the issue. If the output is piped to another program,
-  /// or the console isn't available on Windows, or if TERM=dumb, or if `NO_COLOR` is defined,
-  /// for example, then don't use colors.
+  /// Use colors if the output is a terminal. Colors are disabled if the output is piped
+  /// to another program, if TERM=dumb, if `NO_COLOR` is set, or if the Windows console
+  /// cannot show colors. `CLICOLOR_FORCE` enables colors even if the output is piped.
   Auto,
   /// Try very hard to emit colors. This includes emitting ANSI colors
   /// on Windows if the console API is unavailable (not implemented yet).
@@ -106,26 +211,20 @@ pub enum ColorArg {
 }
 
 impl ColorArg {
+  /// Whether output to stdout should be colored.
   pub fn should_use_color(self) -> bool {
-    use colored_print::choose_color::should_use_color;
-    should_use_color(&self.into())
+    self.resolve(ColorStream::Stdout) != ColorChoice::Never
+  }
+
+  /// Decide colors of the output stream by the terminal and the environment.
+  pub fn resolve(self, stream: ColorStream) -> ColorChoice {
+    choose_color::resolve(self, &choose_color::ColorEnv::detect(stream))
   }
 }
 
+/// Colors of stdout, where printers write to.
 impl From<ColorArg> for ColorChoice {
   fn from(arg: ColorArg) -> ColorChoice {
-    use ColorArg::*;
-    match arg {
-      Auto => {
-        if atty::is(atty::Stream::Stdout) {
-          ColorChoice::Auto
-        } else {
-          ColorChoice::Never
-        }
-      }
-      Always => ColorChoice::Always,
-      Ansi => ColorChoice::AlwaysAnsi,
-      Never => ColorChoice::Never,
-    }
+    arg.resolve(ColorStream::Stdout)
   }
 }
@@ -0,0 +1,213 @@
+//! Print paths relative to the current directory, absolute, or relative to another
+//! directory, see `--path-style`. Only printed paths change, files are still read
+//! and written by the paths found in the walk.
+use super::{BatchOutput, Diff, NodeMatch, Printer, SimpleFile};
+use crate::lang::SgLang;
+use ast_grep_config::RuleConfig;
+
+use anyhow::Result;
+
+use std::borrow::Cow;
+use std::path::{Component, Path, PathBuf};
+use std::str::FromStr;
+
+macro_rules! Matches {
+  ($lt: lifetime) => { impl Iterator<Item = NodeMatch<$lt, SgLang>> };
+}
+macro_rules! Diffs {
+  ($lt: lifetime) => { impl Iterator<Item = Diff<$lt>> };
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum PathStyle {
+  /// Paths as they are found, relative to the current directory unless given absolute.
+  #[default]
+  Relative,
+  /// Canonical absolute paths.
+  Absolute,
+  /// Paths relative to the canonical directory.
+  From(PathBuf),
+}
+
+impl FromStr for PathStyle {
+  type Err = String;
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "relative" => Ok(Self::Relative),
+      "absolute" => Ok(Self::Absolute),
+      _ => {
+        let Some(dir) = s.strip_prefix("from:") else {
+          return Err(format!(
+            "`{s}` should be `relative`, `absolute` or `from:<DIR>`"
+          ));
+        };
+        let base = Path::new(dir)
+          .canonicalize()
+          .map_err(|e| format!("cannot find directory `{dir}`: {e}"))?;
+        Ok(Self::From(strip_verbatim(base)))
+      }
+    }
+  }
+}
+
+impl PathStyle {
+  /// The path to print. Paths that do not exist, like STDIN, are printed as they are.
+  pub fn display<'p>(&self, path: &'p Path) -> Cow<'p, Path> {
+    if *self == Self::Relative {
+      return Cow::Borrowed(path);
+    }
+    let Ok(absolute) = path.canonicalize() else {
+      return Cow::Borrowed(path);
+    };
+    let absolute = strip_verbatim(absolute);
+    match self {
+      Self::From(base) => Cow::Owned(relative_to(&absolute, base)),
+      _ => Cow::Owned(absolute),
+    }
+  }
+}
+
+// canonicalize returns paths like \\?\C:\src on Windows, which are hard to read
+#[cfg(windows)]
+fn strip_verbatim(path: PathBuf) -> PathBuf {
+  const VERBATIM_PREFIX: &str = r#"\\?\"#;
+  match path.to_str().and_then(|p| p.strip_prefix(VERBATIM_PREFIX)) {
+    Some(p) => PathBuf::from(p),
+    None => path,
+  }
+}
+
+#[cfg(not(windows))]
+fn strip_verbatim(path: PathBuf) -> PathBuf {
+  path
+}
+
+/// Both paths are absolute. `..` is kept for the components of base not shared with path.
+fn relative_to(path: &Path, base: &Path) -> PathBuf {
+  let mut path_comps = path.components().peekable();
+  let mut base_comps = base.components().peekable();
+  // paths on different drives of Windows have nothing in common
+  if path_comps.peek() != base_comps.peek() {
+    return path.to_path_buf();
+  }
+  while let (Some(a), Some(b)) = (path_comps.peek(), base_comps.peek()) {
+    if a != b {
+      break;
+    }
+    path_comps.next();
+    base_comps.next();
+  }
+  let mut ret: PathBuf = base_comps.map(|_| Component::ParentDir).collect();
+  ret.extend(path_comps);
+  if ret.as_os_str().is_empty() {
+    ret.push(Component::CurDir);
+  }
+  ret
+}
+
+/// Print paths in the path style. The inner printer of an interactive session
+/// is wrapped, so that fixes are still written to the walked paths.
+pub struct PathStylePrinter<P: Printer> {
+  inner: P,
+  style: PathStyle,
+}
+
+impl<P: Printer> PathStylePrinter<P> {
+  pub fn new(inner: P, style: PathStyle) -> Self {
+    Self { inner, style }
+  }
+}
+
+impl<P: Printer> Printer for PathStylePrinter<P> {
+  fn print_rule<'a>(
+    &self,
+    matches: Matches!('a),
+    file: SimpleFile<Cow<str>, &String>,
+    rule: &RuleConfig<SgLang>,
+  ) -> Result<()> {
+    let name = self.style.display(Path::new(file.name().as_ref()));
+    let name = Cow::Owned(name.to_string_lossy().into_owned());
+    let file = SimpleFile::new(name, *file.source());
+    self.inner.print_rule(matches, file, rule)
+  }
+  fn print_matches<'a>(&self, matches: Matches!('a), path: &Path) -> Result<()> {
+    self.inner.print_matches(matches, &self.style.display(path))
+  }
+  fn print_diffs<'a>(&self, diffs: Diffs!('a), path: &Path) -> Result<()> {
+    self.inner.print_diffs(diffs, &self.style.display(path))
+  }
+  fn print_rule_diffs(
+    &self,
+    diffs: Vec<(Diff<'_>, &RuleConfig<SgLang>)>,
+    path: &Path,
+  ) -> Result<()> {
+    self
+      .inner
+      .print_rule_diffs(diffs, &self.style.display(path))
+  }
+  fn before_print(&self) -> Result<()> {
+    self.inner.before_print()
+  }
+  fn after_print(&self) -> Result<()> {
+    self.inner.after_print()
+  }
+  fn before_rule(&self, rule: &RuleConfig<SgLang>, count: usize) -> Result<()> {
+    self.inner.before_rule(rule, count)
+  }
+  fn before_file(&self, path: &Path, count: usize) -> Result<()> {
+    self.inner.before_file(&self.style.display(path), count)
+  }
+  fn diff_context(&self) -> Option<usize> {
+    self.inner.diff_context()
+  }
+  fn set_diff_context(&self, context: usize) {
+    self.inner.set_diff_context(context)
+  }
+  fn stale_files(&self) -> Vec<PathBuf> {
+    self.inner.stale_files()
+  }
+  fn applied_edits(&self) -> Option<(usize, usize)> {
+    self.inner.applied_edits()
+  }
+  fn print_record(&self, record: &BatchOutput) -> Result<()> {
+    self.inner.print_record(record)
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  #[test]
+  fn test_parse_path_style() {
+    assert_eq!("relative".parse(), Ok(PathStyle::Relative));
+    assert_eq!("absolute".parse(), Ok(PathStyle::Absolute));
+    let cwd = std::env::current_dir().expect("should exist");
+    let cwd = strip_verbatim(cwd.canonicalize().expect("should exist"));
+    assert_eq!("from:.".parse(), Ok(PathStyle::From(cwd)));
+    assert!("from:not-exist-dir".parse::<PathStyle>().is_err());
+    assert!("other".parse::<PathStyle>().is_err());
+  }
+
+  #[test]
+  #[cfg(unix)]
+  fn test_relative_to() {
+    let rel = |path: &str, base: &str| relative_to(Path::new(path), Path::new(base));
+    assert_eq!(rel("/a/b/c.ts", "/a"), Path::new("b/c.ts"));
+    assert_eq!(rel("/a/b/c.ts", "/a/d/e"), Path::new("../../b/c.ts"));
+    assert_eq!(rel("/a/b", "/a/b"), Path::new("."));
+  }
+
+  #[test]
+  fn test_display_path() {
+    let path = Path::new("Cargo.toml");
+    assert_eq!(PathStyle::Relative.display(path), path);
+    let absolute = PathStyle::Absolute.display(path);
+    assert!(absolute.is_absolute());
+    assert!(absolute.ends_with("cli/Cargo.toml"));
+    let parent = "from:..".parse::<PathStyle>().expect("should parse");
+    assert_eq!(parent.display(path), Path::new("cli/Cargo.toml"));
+    let stdin = Path::new("STDIN");
+    assert_eq!(PathStyle::Absolute.display(stdin), stdin);
+  }
+}
@@ -9,19 +9,22 @@ use ignore::WalkParallel;
 
 use crate::config::register_custom_language;
 use crate::lang::SgLang;
-use crate::print::{ColoredPrinter, Diff, Heading, InteractivePrinter, JSONPrinter, Printer};
+use crate::print::{
+  print_session_report, BatchOutput, ColorArg, ColoredPrinter, Diff, Heading, InteractivePrinter,
+  JSONPrinter, JsonStyle, LineEnding, PathStylePrinter, Printer,
+};
 use crate::utils::ErrorContext as EC;
-use crate::utils::{filter_file_pattern, InputArgs, MatchUnit, OutputArgs};
-use crate::utils::{DebugFormat, FileTrace, RunTrace};
-use crate::utils::{Items, PathWorker, StdInWorker, Worker};
+use crate::utils::{filter_file_pattern, Dedupe, InputArgs, MatchUnit, OutputArgs};
+use crate::utils::{print_type_list, DebugFormat, FileTrace, Pager, RunTrace};
+use crate::utils::{Items, PathWorker, StdInBatchWorker, StdInWorker, Worker};
 
 // NOTE: have to register custom lang before clap read arg
-// RunArg has a field of SgLang
+// RunArg and DumpAstArg have a field of SgLang
 pub fn register_custom_language_if_is_run(args: &[String]) -> Result<()> {
   let Some(arg) = args.get(1) else {
     return Ok(());
   };
-  if arg.starts_with('-') || arg == "run" {
+  if arg.starts_with('-') || arg == "run" || arg == "dump-ast" {
     register_custom_language(None)?;
   }
   Ok(())
@@ -66,8 +69,13 @@ impl ValueEnum for Strictness {
 #[derive(Parser)]
 pub struct RunArg {
   // search pattern related options
-  /// AST pattern to match.
-  #[clap(short, long)]
+  /// AST pattern to match. It is not required with --type-list or --session-report.
+  #[clap(
+    short,
+    long,
+    required_unless_present_any = ["type_list", "session_report"],
+    default_value = ""
+  )]
   pattern: String,
 
   /// AST kind to extract sub-part of pattern to match.
@@ -82,7 +90,7 @@ pub struct RunArg {
   rewrite: Option<String>,
 
   /// The language of the pattern query.
-  #[clap(short, long, help(lang_help()), long_help=LANG_HELP_LONG)]
+  #[clap(short, long, help(lang_help()), long_help=LANG_HELP_LONG, hide_possible_values = true)]
   lang: Option<SgLang>,
 
   /// Print query pattern's tree-sitter AST. Requires lang be set explicitly.
@@ -152,6 +160,10 @@ pub struct RunArg {
 }
 
 impl RunArg {
+  pub fn color(&self) -> ColorArg {
+    self.output.color
+  }
+
   fn build_pattern(&self, lang: SgLang) -> Result<Pattern<SgLang>> {
     let pattern = if let Some(sel) = &self.selector {
       Pattern::contextual(&self.pattern, sel, lang)
@@ -169,26 +181,52 @@ impl RunArg {
 
 // Every run will include Search or Replace
 // Search or Replace by arguments `pattern` and `rewrite` passed from CLI
-pub fn run_with_pattern(arg: RunArg) -> Result<()> {
+pub fn run_with_pattern(mut arg: RunArg) -> Result<()> {
+  arg.input.register_lang_globs()?;
+  if arg.input.type_list {
+    return print_type_list();
+  }
+  if let Some(path) = &arg.output.session_report {
+    return print_session_report(path);
+  }
+  // results of batch records are always NDJSON
+  if arg.input.stdin_batch {
+    arg.output.json = Some(JsonStyle::Stream);
+  }
   let context = if arg.context != 0 {
     (arg.context, arg.context)
   } else {
     (arg.before, arg.after)
   };
+  let path_style = arg.output.path_style.clone();
   if let Some(json) = arg.output.json {
-    let printer = JSONPrinter::stdout(json).context(context);
+    let printer = JSONPrinter::stdout(json)
+      .context(context)
+      .include_rewritten(arg.output.json_include_rewritten);
+    let printer = PathStylePrinter::new(printer, path_style);
     return run_pattern_with_printer(arg, printer);
   }
   let printer = ColoredPrinter::stdout(arg.output.color)
-    .heading(arg.heading)
-    .context(context);
+    .color_theme(arg.output.color_theme(None)?)
+    .tab_width(arg.output.tab_width.into())
+    .highlight(arg.output.highlight && atty::is(atty::Stream::Stdout))
+    .heading(arg.heading.resolve())
+    .context(context)
+    .diff_style(arg.output.diff_style)
+    .context_separator(arg.output.context_separator.clone())
+    .field_separator(arg.output.field_separator.clone());
+  let printer = PathStylePrinter::new(printer, path_style.clone());
   let interactive = arg.output.needs_interactive();
   if interactive {
     let from_stdin = arg.input.stdin;
-    let printer = InteractivePrinter::new(printer, arg.output.update_all, from_stdin)?;
+    let printer = InteractivePrinter::new(printer, arg.output.update_all, from_stdin)?
+      .path_style(path_style)
+      .color(arg.output.color)
+      .session(arg.output.session.as_deref())?;
     run_pattern_with_printer(arg, printer)
   } else {
-    run_pattern_with_printer(arg, printer)
+    let pager = Pager::start(&arg.output)?;
+    pager.run(|| run_pattern_with_printer(arg, printer))
   }
 }
 
@@ -196,15 +234,28 @@ fn run_pattern_with_printer(arg: RunArg, printer: impl Printer + 'static) -> Res
   if arg.input.stdin {
     RunWithSpecificLang::new(arg, printer)?.run_std_in()
   } else if arg.lang.is_some() {
-    RunWithSpecificLang::new(arg, printer)?.run_path()
+    let worker = RunWithSpecificLang::new(arg, printer)?;
+    if worker.arg.input.stdin_batch {
+      worker.run_std_in_batch()
+    } else {
+      worker.run_path()
+    }
   } else {
-    let trace = arg.output.tracing.run_trace();
-    RunWithInferredLang {
+    let trace = arg
+      .output
+      .tracing
+      .run_trace()
+      .no_messages(arg.output.no_messages);
+    let worker = RunWithInferredLang {
       arg,
       printer,
       trace,
+    };
+    if worker.arg.input.stdin_batch {
+      worker.run_std_in_batch()
+    } else {
+      worker.run_path()
     }
-    .run_path()
   }
 }
 
@@ -219,6 +270,7 @@ impl<P: Printer> Worker for RunWithInferredLang<P> {
   fn consume_items(&self, items: Items<Self::Item>) -> Result<()> {
     let rewrite = &self.arg.rewrite;
     let printer = &self.printer;
+    let dedupe = self.arg.output.dedupe;
     printer.before_print()?;
     for (match_unit, lang) in items {
       let rewrite = rewrite
@@ -226,19 +278,22 @@ impl<P: Printer> Worker for RunWithInferredLang<P> {
         .map(|s| Fixer::from_str(s, &lang))
         .transpose();
       match rewrite {
-        Ok(r) => match_one_file(printer, &match_unit, &r)?,
+        Ok(r) => match_one_file(printer, &match_unit, &r, dedupe)?,
         Err(e) => {
-          match_one_file(printer, &match_unit, &None)?;
+          match_one_file(printer, &match_unit, &None, dedupe)?;
           eprintln!("⚠️  Rewriting was skipped because pattern fails to parse. Error detail:");
           eprintln!("╰▻ {e}");
         }
       }
     }
+    let file_trace = &self.trace.file_trace;
+    self.arg.output.print_io_errors(printer, file_trace)?;
     printer.after_print()?;
     // TODO: better handle output format
     if let Some(trace) = self.trace.print(self.arg.output.json.is_some()) {
       eprintln!("{}", trace);
     }
+    self.arg.output.report_io_errors(&self.trace.file_trace)?;
     Ok(())
   }
 }
@@ -247,12 +302,15 @@ impl<P: Printer> PathWorker for RunWithInferredLang<P> {
   fn build_walk(&self) -> Result<WalkParallel> {
     self.arg.input.walk()
   }
+  fn get_input(&self) -> &InputArgs {
+    &self.arg.input
+  }
   fn get_trace(&self) -> &FileTrace {
     &self.trace.file_trace
   }
 
   fn produce_item(&self, path: &Path) -> Option<Vec<Self::Item>> {
-    let lang = SgLang::from_path(path)?;
+    let lang = self.arg.input.infer_lang(path, self.get_trace())?;
     let matcher = self.arg.build_pattern(lang).ok()?;
     // match sub region
     if let Some(sub_langs) = lang.injectable_sg_langs() {
@@ -260,10 +318,32 @@ impl<P: Printer> PathWorker for RunWithInferredLang<P> {
         let pattern = self.arg.build_pattern(l).ok()?;
         Some((l, pattern))
       });
-      filter_file_pattern(path, lang, Some(matcher), matchers)
+      filter_file_pattern(path, lang, Some(matcher), matchers, self.get_trace())
     } else {
-      filter_file_pattern(path, lang, Some(matcher), std::iter::empty())
+      let matchers = std::iter::empty();
+      filter_file_pattern(path, lang, Some(matcher), matchers, self.get_trace())
+    }
+  }
+}
+
+impl<P: Printer> StdInBatchWorker for RunWithInferredLang<P> {
+  fn parse_record(&self, path: PathBuf, src: String) -> Result<Option<Self::Item>> {
+    let lang = SgLang::from_path(&path)
+      .ok_or_else(|| anyhow::anyhow!(EC::UnknownFileLanguage(path.clone())))?;
+    let matcher = self.arg.build_pattern(lang)?;
+    let grep = lang.ast_grep(src);
+    if grep.root().find(&matcher).is_none() {
+      return Ok(None);
     }
+    let unit = MatchUnit {
+      path,
+      matcher,
+      grep,
+    };
+    Ok(Some((unit, lang)))
+  }
+  fn print_record(&self, record: &BatchOutput) -> Result<()> {
+    self.printer.print_record(record)
   }
 }
 
@@ -287,7 +367,11 @@ impl<Printer> RunWithSpecificLang<Printer> {
     } else {
       None
     };
-    let stats = arg.output.tracing.run_trace();
+    let stats = arg
+      .output
+      .tracing
+      .run_trace()
+      .no_messages(arg.output.no_messages);
     Ok(Self {
       arg,
       printer,
@@ -303,16 +387,20 @@ impl<P: Printer> Worker for RunWithSpecificLang<P> {
 
   fn consume_items(&self, items: Items<Self::Item>) -> Result<()> {
     let printer = &self.printer;
+    let dedupe = self.arg.output.dedupe;
     printer.before_print()?;
     let mut has_matches = false;
     for match_unit in items {
-      match_one_file(printer, &match_unit, &self.rewrite)?;
+      match_one_file(printer, &match_unit, &self.rewrite, dedupe)?;
       has_matches = true;
     }
+    let file_trace = &self.stats.file_trace;
+    self.arg.output.print_io_errors(printer, file_trace)?;
     printer.after_print()?;
     if let Some(stats) = self.stats.print(self.arg.output.json.is_some()) {
       eprintln!("{}", stats);
     }
+    self.arg.output.report_io_errors(&self.stats.file_trace)?;
     if !has_matches && self.pattern.has_error() {
       Err(anyhow::anyhow!(EC::PatternHasError))
     } else {
@@ -324,7 +412,10 @@ impl<P: Printer> Worker for RunWithSpecificLang<P> {
 impl<P: Printer> PathWorker for RunWithSpecificLang<P> {
   fn build_walk(&self) -> Result<WalkParallel> {
     let lang = self.arg.lang.expect("must present");
-    Ok(self.arg.input.walk_lang(lang))
+    self.arg.input.walk_lang(lang)
+  }
+  fn get_input(&self) -> &InputArgs {
+    &self.arg.input
   }
   fn get_trace(&self) -> &FileTrace {
     &self.stats.file_trace
@@ -334,32 +425,51 @@ impl<P: Printer> PathWorker for RunWithSpecificLang<P> {
     let pattern = self.pattern.clone();
     let lang = arg.lang.expect("must present");
     let path_lang = SgLang::from_path(path)?;
+    let trace = self.get_trace();
     let ret = if path_lang == lang {
-      filter_file_pattern(path, lang, Some(pattern), std::iter::empty())?
+      filter_file_pattern(path, lang, Some(pattern), std::iter::empty(), trace)?
     } else {
-      filter_file_pattern(path, path_lang, None, std::iter::once((lang, pattern)))?
+      let matchers = std::iter::once((lang, pattern));
+      filter_file_pattern(path, path_lang, None, matchers, trace)?
     };
     Some(ret.into_iter().map(|n| n.0).collect())
   }
 }
 
-impl<P: Printer> StdInWorker for RunWithSpecificLang<P> {
-  fn parse_stdin(&self, src: String) -> Option<Self::Item> {
+impl<P: Printer> RunWithSpecificLang<P> {
+  fn match_source(&self, path: PathBuf, src: String) -> Option<MatchUnit<Pattern<SgLang>>> {
     let lang = self.arg.lang.expect("must present");
     let grep = lang.ast_grep(src);
     let has_match = grep.root().find(&self.pattern).is_some();
     has_match.then(|| MatchUnit {
-      path: PathBuf::from("STDIN"),
+      path,
       matcher: self.pattern.clone(),
       grep,
     })
   }
 }
 
+impl<P: Printer> StdInWorker for RunWithSpecificLang<P> {
+  fn parse_stdin(&self, src: String) -> Option<Self::Item> {
+    self.match_source(PathBuf::from("STDIN"), src)
+  }
+}
+
+// --lang takes precedence over the language of record paths
+impl<P: Printer> StdInBatchWorker for RunWithSpecificLang<P> {
+  fn parse_record(&self, path: PathBuf, src: String) -> Result<Option<Self::Item>> {
+    Ok(self.match_source(path, src))
+  }
+  fn print_record(&self, record: &BatchOutput) -> Result<()> {
+    self.printer.print_record(record)
+  }
+}
+
 fn match_one_file(
   printer: &impl Printer,
   match_unit: &MatchUnit<impl Matcher<SgLang>>,
   rewrite: &Option<Fixer<SgLang>>,
+  dedupe: Dedupe,
 ) -> Result<()> {
   let MatchUnit {
     path,
@@ -367,9 +477,11 @@ fn match_one_file(
     matcher,
   } = match_unit;
 
-  let matches = grep.root().find_all(matcher);
+  let matches = grep.root().find_all(matcher).collect();
+  let matches = dedupe.apply(matches, |m| m.range()).into_iter();
   if let Some(rewrite) = rewrite {
-    let diffs = matches.map(|m| Diff::generate(m, matcher, rewrite));
+    let line_ending = LineEnding::of(grep.source());
+    let diffs = matches.map(|m| Diff::generate(m, matcher, rewrite, line_ending));
     printer.print_diffs(diffs, path)
   } else {
     printer.print_matches(matches, path)
@@ -379,7 +491,7 @@ fn match_one_file(
 #[cfg(test)]
 mod test {
   use super::*;
-  use crate::print::ColorArg;
+  use crate::print::{ColorArg, DiffStyle, PathStyle};
   use ast_grep_language::SupportLang;
 
   fn default_run_arg() -> RunArg {
@@ -393,18 +505,48 @@ mod test {
       strictness: None,
       input: InputArgs {
         no_ignore: vec![],
+        hidden: false,
+        ignore_file: vec![],
         stdin: false,
+        stdin_batch: false,
+        files_from: None,
+        files_from0: None,
         follow: false,
+        one_file_system: false,
         paths: vec![PathBuf::from(".")],
         globs: vec![],
+        no_glob_normalization: false,
+        file_type: vec![],
+        type_not: vec![],
+        type_list: false,
+        lang_glob: vec![],
+        no_shebang_detection: false,
         threads: 0,
+        force_exclude: false,
+        max_file_size: None,
+        skip_minified: false,
+        minified_line_threshold: 500,
       },
       output: OutputArgs {
         color: ColorArg::Never,
+        color_theme: None,
+        highlight: false,
+        tab_width: 4,
+        diff_style: DiffStyle::Inline,
+        context_separator: None,
+        field_separator: ":".into(),
+        path_style: PathStyle::Relative,
+        pager: None,
         interactive: false,
         json: None,
+        json_include_rewritten: false,
         update_all: false,
+        session: None,
+        session_report: None,
         tracing: Default::default(),
+        no_messages: false,
+        strict_io: false,
+        dedupe: Default::default(),
       },
       before: 0,
       after: 0,
@@ -1,25 +1,34 @@
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::io::Write;
 use std::path::{Path, PathBuf};
+use std::sync::Mutex;
 
 use anyhow::{Context, Result};
 use ast_grep_config::{
-  from_yaml_string, CombinedScan, PreScan, RuleCollection, RuleConfig, SerializableRule,
-  SerializableRuleConfig, SerializableRuleCore, Severity,
+  CombinedScan, PreScan, RuleCollection, RuleConfig, SerializableRule, SerializableRuleConfig,
+  SerializableRuleCore, Severity,
 };
-use ast_grep_core::{NodeMatch, StrDoc};
-use clap::Args;
+use ast_grep_core::{Language, NodeMatch, StrDoc};
+use clap::{Args, ValueEnum};
 use ignore::WalkParallel;
 use regex::Regex;
 
-use crate::config::{find_rules, read_rule_file, register_custom_language};
+use crate::config::{
+  check_rules, find_rules, read_rule_file, read_rule_yaml, register_custom_language,
+};
 use crate::lang::SgLang;
 use crate::print::{
-  CloudPrinter, ColoredPrinter, Diff, InteractivePrinter, JSONPrinter, Platform, Printer,
-  ReportStyle, SimpleFile,
+  apply_fixes, print_session_report, BatchOutput, CloudPrinter, ColorArg, ColoredPrinter, Diff,
+  DiffPrinter, Fix, GroupBy, Heading, InteractivePrinter, JSONPrinter, JsonStyle, LineEnding,
+  PathStylePrinter, Platform, Printer, ReportStyle, SimpleFile,
 };
 use crate::utils::ErrorContext as EC;
-use crate::utils::{filter_file_interactive, InputArgs, OutputArgs, SeverityArg};
-use crate::utils::{FileTrace, RuleTrace, ScanTrace};
-use crate::utils::{Items, PathWorker, StdInWorker, Worker};
+use crate::utils::{
+  filter_file_interactive, Dedupe, DuplicateRules, InputArgs, OutputArgs, RuleOverwrite,
+};
+use crate::utils::{print_type_list, Pager, ScanSummary, SeverityArg, SeverityLevel};
+use crate::utils::{FileTrace, Prefilter, PrefilterTrace, RuleTrace, ScanTrace};
+use crate::utils::{Items, PathWorker, StdInBatchWorker, StdInWorker, Worker};
 
 type AstGrep = ast_grep_core::AstGrep<StrDoc<SgLang>>;
 
@@ -50,15 +59,127 @@ pub struct ScanArg {
   #[clap(long, conflicts_with = "rule", value_name = "REGEX")]
   filter: Option<Regex>,
 
+  /// Allow rules with the same id and resolve them by POLICY instead of reporting an error.
+  ///
+  /// `last-wins` keeps the rule loaded last. Rule directories are loaded in the order of
+  /// `ruleDirs` in sgconfig.yml, and files in a directory are loaded in file name order.
+  #[clap(long, value_name = "POLICY")]
+  allow_duplicate_rules: Option<DuplicateRules>,
+
+  /// Report unknown keys in rule YAML as warnings instead of errors.
+  ///
+  /// By default, a misspelled key like `severty` in a rule file is an error.
+  #[clap(long)]
+  no_strict_yaml: bool,
+
+  /// Disable the literal prefilter and run every rule against every file.
+  ///
+  /// By default ast-grep extracts literals that must appear in the source for a rule to match,
+  /// and skips the rule for files without any of them. This flag is for debugging the prefilter.
+  #[clap(long)]
+  no_prefilter: bool,
+
+  /// Check that all rules compile without scanning any code.
+  ///
+  /// Rule problems like invalid patterns, unknown utils, invalid globs or undefined
+  /// metavariables in fix are reported with the rule file location.
+  /// ast-grep exits with a non-zero code if any problem is found.
+  #[clap(
+    long,
+    conflicts_with_all = ["interactive", "update_all", "json", "format", "diff", "stdin", "stdin_batch"],
+  )]
+  check_rules: bool,
+
   /// Output warning/error messages in GitHub Action format.
   ///
   /// Currently, only GitHub is supported.
-  #[clap(long, conflicts_with = "json", conflicts_with = "interactive")]
+  #[clap(
+    long,
+    conflicts_with = "json",
+    conflicts_with = "interactive",
+    conflicts_with = "stdin_batch"
+  )]
   format: Option<Platform>,
 
   #[clap(long, default_value = "rich", conflicts_with = "json")]
   report_style: ReportStyle,
 
+  /// Group findings by file or by rule in the terminal output.
+  ///
+  /// `rule` prints findings after the scan completes, and large groups are truncated.
+  /// With --interactive, `rule` reviews findings rule by rule: press `A` to apply the fix to
+  /// all remaining matches of the current rule, or `S` to skip the rule.
+  /// This flag does not affect JSON output.
+  #[clap(long, value_name = "GROUP")]
+  group_by: Option<GroupBy>,
+
+  /// Controls whether to print the file name as heading of rich diagnostics.
+  ///
+  /// With heading, findings of every file are printed under the file name and the
+  /// number of findings in the file, and files are separated by a line.
+  /// The heading is repeated when findings of a file take more than a screen.
+  /// The default value `auto` is to use heading when printing to a terminal.
+  /// This flag has no effect with --interactive or other report styles than `rich`.
+  #[clap(long, default_value = "auto", value_name = "WHEN")]
+  heading: Heading,
+
+  /// Show NUM lines around each finding in rich diagnostics.
+  ///
+  /// Context lines are dimmed, and findings of a rule with overlapping context
+  /// are printed in one code frame. Other report styles ignore this flag.
+  #[clap(short = 'C', long, default_value = "0", value_name = "NUM")]
+  context: u16,
+
+  /// Do not print the scan summary to stderr after the scan finishes.
+  #[clap(long)]
+  no_summary: bool,
+
+  /// Report an error instead of a warning when no rule applies to the languages of scanned files.
+  #[clap(long)]
+  strict_rules: bool,
+
+  /// Print the fixes of all rules as a unified diff without modifying files.
+  ///
+  /// The output can be applied by `git apply`. Fixes overlapping with a previous one
+  /// in the same file are skipped.
+  #[clap(
+    long,
+    conflicts_with_all = ["interactive", "update_all", "json", "format", "stdin_batch"],
+  )]
+  diff: bool,
+
+  /// Treat the code from StdIn as the file at PATH.
+  ///
+  /// The path is used to infer the language and to apply the `files` and `ignores`
+  /// globs of rules. The file does not need to exist. All applicable rules in the
+  /// project configuration are run unless --rule or --inline-rules is specified.
+  #[clap(long, requires = "stdin", value_name = "PATH")]
+  stdin_filepath: Option<PathBuf>,
+
+  /// Print the code from StdIn with all fixes applied instead of the findings.
+  ///
+  /// Fixes overlapping with a previous one are skipped.
+  #[clap(
+    long,
+    requires = "stdin",
+    conflicts_with_all = ["interactive", "update_all", "json", "format", "diff"],
+  )]
+  print_fixed: bool,
+
+  /// Exit with CODE when findings of SEVERITY are reported, e.g. `--exit-code-for warning=1`.
+  ///
+  /// This flag can be repeated. ast-grep exits with the code of the highest severity finding
+  /// that has a code. Error findings exit with 1 unless configured, and other severities
+  /// keep exiting with 0. CODE must be between 1 and 255. A later flag for the same severity
+  /// takes precedence over an earlier one.
+  #[clap(
+    long,
+    action = clap::ArgAction::Append,
+    value_name = "SEVERITY=CODE",
+    value_parser = parse_exit_code,
+  )]
+  exit_code_for: Vec<(SeverityLevel, u8)>,
+
   /// severity related options
   #[clap(flatten)]
   severity: SeverityArg,
@@ -71,32 +192,151 @@ pub struct ScanArg {
   output: OutputArgs,
 }
 
-pub fn run_with_config(arg: ScanArg) -> Result<()> {
+impl ScanArg {
+  pub fn color(&self) -> ColorArg {
+    self.output.color
+  }
+}
+
+fn parse_exit_code(s: &str) -> Result<(SeverityLevel, u8), String> {
+  let (severity, code) = s
+    .split_once('=')
+    .ok_or_else(|| format!("expected SEVERITY=CODE but found `{s}`"))?;
+  let severity = SeverityLevel::from_str(severity.trim(), true)?;
+  let code: u8 = code
+    .trim()
+    .parse()
+    .map_err(|_| format!("exit code `{code}` must be an integer between 1 and 255"))?;
+  if code == 0 {
+    return Err("exit code 0 is reserved for scans without findings".into());
+  }
+  Ok((severity, code))
+}
+
+/// Count findings by severity to decide the exit code of scan.
+#[derive(Default)]
+struct FindingCount([usize; 4]);
+
+impl FindingCount {
+  fn add(&mut self, severity: &Severity, count: usize) {
+    if let Some(level) = SeverityLevel::from_severity(severity) {
+      let slot = &mut self.0[level as usize];
+      *slot = slot.saturating_add(count);
+    }
+  }
+
+  fn into_result(self, exit_codes: &[(SeverityLevel, u8)]) -> Result<()> {
+    for level in SeverityLevel::DESCENDING {
+      let count = self.0[level as usize];
+      if count == 0 {
+        continue;
+      }
+      let code = exit_codes.iter().rev().find(|(l, _)| *l == level);
+      if let Some((_, code)) = code {
+        return Err(anyhow::anyhow!(EC::DiagnosticExitCode(level, count, *code)));
+      }
+      if level == SeverityLevel::Error {
+        return Err(anyhow::anyhow!(EC::DiagnosticError(count)));
+      }
+    }
+    Ok(())
+  }
+}
+
+pub fn run_with_config(mut arg: ScanArg) -> Result<()> {
   register_custom_language(arg.config.clone())?;
+  arg.input.register_lang_globs()?;
+  if arg.input.type_list {
+    return print_type_list();
+  }
+  if let Some(path) = &arg.output.session_report {
+    return print_session_report(path);
+  }
+  if arg.check_rules {
+    return run_check_rules(arg);
+  }
+  // findings of batch records are always NDJSON
+  if arg.input.stdin_batch {
+    arg.output.json = Some(JsonStyle::Stream);
+  }
+  let path_style = arg.output.path_style.clone();
   if let Some(_format) = &arg.format {
-    let printer = CloudPrinter::stdout();
+    let printer = PathStylePrinter::new(CloudPrinter::stdout(), path_style);
     return run_scan(arg, printer);
   }
   if let Some(json) = arg.output.json {
-    let printer = JSONPrinter::stdout(json);
+    let printer = JSONPrinter::stdout(json).include_rewritten(arg.output.json_include_rewritten);
+    let printer = PathStylePrinter::new(printer, path_style);
     return run_scan(arg, printer);
   }
-  let printer = ColoredPrinter::stdout(arg.output.color).style(arg.report_style);
+  if arg.diff {
+    let printer = DiffPrinter::stdout(arg.output.color).diff_style(arg.output.diff_style);
+    let printer = PathStylePrinter::new(printer, path_style);
+    let pager = Pager::start(&arg.output)?;
+    return pager.run(|| run_scan(arg, printer));
+  }
+  if arg.output.field_separator != ":" {
+    match arg.report_style {
+      ReportStyle::Rich => {}
+      ReportStyle::Medium => return Err(anyhow::anyhow!(EC::FieldSeparatorUnsupported("medium"))),
+      ReportStyle::Short => return Err(anyhow::anyhow!(EC::FieldSeparatorUnsupported("short"))),
+    }
+  }
+  let printer = ColoredPrinter::stdout(arg.output.color)
+    .color_theme(arg.output.color_theme(arg.config.clone())?)
+    .tab_width(arg.output.tab_width.into())
+    .highlight(arg.output.highlight && atty::is(atty::Stream::Stdout))
+    .context((arg.context, arg.context))
+    .diff_style(arg.output.diff_style)
+    .context_separator(arg.output.context_separator.clone())
+    .field_separator(arg.output.field_separator.clone())
+    .style(arg.report_style);
   let interactive = arg.output.needs_interactive();
   if interactive {
-    let from_stdin = arg.input.stdin;
-    let printer = InteractivePrinter::new(printer, arg.output.update_all, from_stdin)?;
+    // fixes of StdIn cannot be applied to files, use --print-fixed instead
+    if arg.input.stdin {
+      return Err(anyhow::anyhow!(EC::StdInIsNotInteractive));
+    }
+    // interactive session itself goes file by file, or rule by rule
+    let by_rule = matches!(arg.group_by, Some(GroupBy::Rule));
+    // findings are shown file by file already, and cannot wait for the heading
+    let printer = printer.heading(Heading::Never);
+    let printer = PathStylePrinter::new(printer, path_style.clone());
+    let printer = InteractivePrinter::new(printer, arg.output.update_all, false)?
+      .path_style(path_style)
+      .color(arg.output.color)
+      .group_by_rule(by_rule)
+      .session(arg.output.session.as_deref())?;
     run_scan(arg, printer)
   } else {
-    run_scan(arg, printer)
+    let printer = printer
+      .group_by(arg.group_by)
+      .heading(arg.heading.resolve());
+    let printer = PathStylePrinter::new(printer, path_style);
+    let pager = Pager::start(&arg.output)?;
+    pager.run(|| run_scan(arg, printer))
   }
 }
 
+fn run_check_rules(arg: ScanArg) -> Result<()> {
+  let check = check_rules(arg.config, arg.rule.as_deref(), arg.inline_rules.as_deref())?;
+  for problem in &check.problems {
+    println!("{problem}");
+  }
+  if !check.problems.is_empty() {
+    return Err(anyhow::anyhow!(EC::InvalidRules(check.problems.len())));
+  }
+  println!("{} rule(s) checked, no problem found.", check.rule_count);
+  Ok(())
+}
+
 fn run_scan<P: Printer + 'static>(arg: ScanArg, printer: P) -> Result<()> {
   if arg.input.stdin {
     let worker = ScanWithRule::try_new(arg, printer)?;
     // TODO: report a soft error if rules have different languages
     worker.run_std_in()
+  } else if arg.input.stdin_batch {
+    ScanWithRule::try_new(arg, printer)?.run_std_in_batch()
   } else {
     let worker = ScanWithConfig::try_new(arg, printer)?;
     worker.run_path()
@@ -107,61 +347,136 @@ struct ScanWithConfig<Printer> {
   arg: ScanArg,
   printer: Printer,
   configs: RuleCollection<SgLang>,
+  prefilter: Option<Prefilter>,
   trace: ScanTrace,
+  summary: ScanSummary,
+  /// languages of files found during the walk
+  file_langs: Mutex<HashSet<SgLang>>,
 }
 impl<P: Printer> ScanWithConfig<P> {
   fn try_new(mut arg: ScanArg, printer: P) -> Result<Self> {
     let mut rule_trace = RuleTrace::default();
+    let mut overwrite = RuleOverwrite::new(&arg.severity, arg.filter.take())?;
+    overwrite.duplicate_rules = arg.allow_duplicate_rules;
+    overwrite.no_strict_yaml = arg.no_strict_yaml;
     let configs = if let Some(path) = &arg.rule {
-      let rules = read_rule_file(path, None)?;
+      let (rules, excluded) = read_rule_file(path, &overwrite)?;
+      rule_trace.severity_excluded_rule_count = excluded;
       RuleCollection::try_new(rules).context(EC::GlobPattern)?
     } else if let Some(text) = &arg.inline_rules {
-      let rules = from_yaml_string(text, &Default::default())
-        .with_context(|| EC::ParseRule("INLINE_RULES".into()))?;
+      let (rules, excluded) = read_rule_yaml(text, Path::new("INLINE_RULES"), &overwrite)?;
+      rule_trace.severity_excluded_rule_count = excluded;
       RuleCollection::try_new(rules).context(EC::GlobPattern)?
     } else {
-      let (configs, r_stats) = find_rules(arg.config.take(), arg.filter.as_ref())?;
+      let (configs, r_stats) = find_rules(arg.config.take(), &overwrite)?;
       rule_trace = r_stats;
       configs
     };
+    let prefilter = if arg.no_prefilter {
+      None
+    } else {
+      Prefilter::new(configs.iter())
+    };
+    if let Some(prefilter) = &prefilter {
+      rule_trace.prefilter = PrefilterTrace::new(prefilter.rule_ids().cloned());
+    }
     let trace = arg.output.tracing.scan_trace(rule_trace);
+    let trace = trace.no_messages(arg.output.no_messages);
     Ok(Self {
       arg,
       printer,
       configs,
+      prefilter,
       trace,
+      summary: ScanSummary::default(),
+      file_langs: Mutex::default(),
     })
   }
+
+  /// Check that at least one rule targets the languages of scanned files.
+  fn check_rule_languages(&self) -> Result<()> {
+    let file_langs = self.file_langs.lock().expect("should not fail");
+    if file_langs.is_empty() {
+      return Ok(());
+    }
+    let rule_langs: HashSet<_> = self.configs.iter().map(|r| r.language).collect();
+    let applicable = file_langs.iter().any(|lang| {
+      rule_langs.contains(lang)
+        || lang
+          .injectable_sg_langs()
+          .map_or(false, |mut langs| langs.any(|l| rule_langs.contains(&l)))
+    });
+    if applicable {
+      return Ok(());
+    }
+    let names = |langs: &mut dyn Iterator<Item = &SgLang>| {
+      let names: BTreeSet<_> = langs.map(|l| l.to_string()).collect();
+      if names.is_empty() {
+        "no language".to_string()
+      } else {
+        names.into_iter().collect::<Vec<_>>().join(", ")
+      }
+    };
+    let rule_names = names(&mut rule_langs.iter());
+    let file_names = names(&mut file_langs.iter());
+    if self.arg.strict_rules {
+      return Err(anyhow::anyhow!(EC::NoApplicableRule(
+        rule_names, file_names
+      )));
+    }
+    eprintln!(
+      "⚠️  No rule applies to the scanned files. Rule languages: {rule_names}. File languages: {file_names}."
+    );
+    Ok(())
+  }
 }
-impl<P: Printer> Worker for ScanWithConfig<P> {
-  type Item = (PathBuf, AstGrep, PreScan);
-  fn consume_items(&self, items: Items<Self::Item>) -> Result<()> {
-    self.printer.before_print()?;
-    let mut error_count = 0usize;
+impl<P: Printer> ScanWithConfig<P> {
+  fn scan_by_file(
+    &self,
+    items: Items<(PathBuf, AstGrep, PreScan)>,
+    finding_count: &mut FindingCount,
+  ) -> Result<()> {
     for (path, grep, pre_scan) in items {
       let file_content = grep.source().to_string();
       let path = &path;
       let rules = self.configs.get_rule_from_lang(path, *grep.lang());
       let combined = CombinedScan::new(rules);
-      let interactive = self.arg.output.needs_interactive();
+      let separate_fix = self.arg.output.needs_interactive() || self.arg.diff;
       // exclude_fix rule because we already have diff inspection before
-      let scanned = combined.scan(&grep, pre_scan, /* separate_fix*/ interactive);
-      if interactive {
-        let diffs = scanned
-          .diffs
-          .into_iter()
-          .map(|(idx, nm)| {
-            let rule = combined.get_rule(idx);
-            (nm, rule)
-          })
-          .collect();
-        match_rule_diff_on_file(path, diffs, &self.printer)?;
+      let scanned = combined.scan(&grep, pre_scan, separate_fix);
+      let dedupe = self.arg.output.dedupe;
+      let diffs: Vec<_> = dedupe_diffs(scanned.diffs, dedupe)
+        .into_iter()
+        .map(|(idx, nm)| {
+          let rule = combined.get_rule(idx);
+          // fixes printed by --diff are findings, but interactive fixes are reviewed
+          if self.arg.diff {
+            finding_count.add(&rule.severity, 1);
+          }
+          self.summary.add_findings(path, rule, 1);
+          (nm, rule)
+        })
+        .collect();
+      let matches: Vec<_> = scanned
+        .matches
+        .into_iter()
+        .map(|(idx, matches)| {
+          let rule = combined.get_rule(idx);
+          let matches = dedupe.apply(matches, |m| m.range());
+          finding_count.add(&rule.severity, matches.len());
+          self.summary.add_findings(path, rule, matches.len());
+          (rule, matches)
+        })
+        .collect();
+      let count = diffs.len()
+        + matches.iter().map(|(_, m)| m.len()).sum::<usize>()
+        + scanned.unused_suppressions.len();
+      self.printer.before_file(path, count)?;
+      if separate_fix {
+        let line_ending = LineEnding::of(&file_content);
+        match_rule_diff_on_file(path, diffs, line_ending, &self.printer)?;
       }
-      for (idx, matches) in scanned.matches {
-        let rule = combined.get_rule(idx);
-        if matches!(rule.severity, Severity::Error) {
-          error_count = error_count.saturating_add(matches.len());
-        }
+      for (rule, matches) in matches {
         match_rule_on_file(path, matches, rule, &file_content, &self.printer)?;
       }
       print_unused_suppressions(
@@ -171,18 +486,147 @@ impl<P: Printer> Worker for ScanWithConfig<P> {
         &self.printer,
       )?;
     }
+    Ok(())
+  }
+
+  /// Scan all files before printing so that findings can be sent rule by rule.
+  fn scan_by_rule(
+    &self,
+    items: Items<(PathBuf, AstGrep, PreScan)>,
+    finding_count: &mut FindingCount,
+  ) -> Result<()> {
+    let mut files = vec![];
+    let mut pre_scans = vec![];
+    for (path, grep, pre_scan) in items {
+      let file_content = grep.source().to_string();
+      files.push((path, grep, file_content));
+      pre_scans.push(pre_scan);
+    }
+    let dedupe = self.arg.output.dedupe;
+    let mut by_rule: BTreeMap<&str, RuleFindings> = BTreeMap::new();
+    let mut unused_suppressions = vec![];
+    for ((path, grep, file_content), pre_scan) in files.iter().zip(pre_scans) {
+      let rules = self.configs.get_rule_from_lang(path, *grep.lang());
+      let combined = CombinedScan::new(rules);
+      let scanned = combined.scan(grep, pre_scan, true);
+      let line_ending = LineEnding::of(file_content);
+      let mut diffs: BTreeMap<&str, Vec<_>> = BTreeMap::new();
+      for (idx, nm) in dedupe_diffs(scanned.diffs, dedupe) {
+        let rule = combined.get_rule(idx);
+        self.summary.add_findings(path, rule, 1);
+        diffs.entry(&rule.id).or_default().push(nm);
+      }
+      for (id, nms) in diffs {
+        let rule = self.configs.get_rule(id).expect("rule must exist");
+        let findings = by_rule.entry(id).or_insert_with(|| RuleFindings::new(rule));
+        findings.count += nms.len();
+        findings.diffs.push((path, line_ending, nms));
+      }
+      for (idx, matches) in scanned.matches {
+        let rule = combined.get_rule(idx);
+        let matches = dedupe.apply(matches, |m| m.range());
+        finding_count.add(&rule.severity, matches.len());
+        self.summary.add_findings(path, rule, matches.len());
+        let findings = by_rule
+          .entry(&rule.id)
+          .or_insert_with(|| RuleFindings::new(rule));
+        findings.count += matches.len();
+        findings.matches.push((path, file_content, matches));
+      }
+      unused_suppressions.push((path, file_content, scanned.unused_suppressions));
+    }
+    for findings in by_rule.into_values() {
+      let rule = findings.rule;
+      self.printer.before_rule(rule, findings.count)?;
+      for (path, line_ending, nms) in findings.diffs {
+        let diffs = nms.into_iter().map(|nm| (nm, rule)).collect();
+        match_rule_diff_on_file(path, diffs, line_ending, &self.printer)?;
+      }
+      for (path, file_content, matches) in findings.matches {
+        match_rule_on_file(path, matches, rule, file_content, &self.printer)?;
+      }
+    }
+    for (path, file_content, matches) in unused_suppressions {
+      print_unused_suppressions(path, matches, file_content, &self.printer)?;
+    }
+    Ok(())
+  }
+}
+
+type FileMatches<'a> = Vec<NodeMatch<'a, StrDoc<SgLang>>>;
+
+/// Findings of one rule in all files, see `ScanWithConfig::scan_by_rule`.
+struct RuleFindings<'r, 'a> {
+  rule: &'r RuleConfig<SgLang>,
+  count: usize,
+  diffs: Vec<(&'a PathBuf, LineEnding, FileMatches<'a>)>,
+  matches: Vec<(&'a PathBuf, &'a String, FileMatches<'a>)>,
+}
+
+impl<'r, 'a> RuleFindings<'r, 'a> {
+  fn new(rule: &'r RuleConfig<SgLang>) -> Self {
+    Self {
+      rule,
+      count: 0,
+      diffs: vec![],
+      matches: vec![],
+    }
+  }
+}
+
+impl<P: Printer> Worker for ScanWithConfig<P> {
+  type Item = (PathBuf, AstGrep, PreScan);
+  fn consume_items(&self, items: Items<Self::Item>) -> Result<()> {
+    self.printer.before_print()?;
+    let mut finding_count = FindingCount::default();
+    let by_rule = matches!(self.arg.group_by, Some(GroupBy::Rule));
+    if by_rule && self.arg.output.needs_interactive() {
+      self.scan_by_rule(items, &mut finding_count)?;
+    } else {
+      self.scan_by_file(items, &mut finding_count)?;
+    }
+    let file_trace = &self.trace.file_trace;
+    self.arg.output.print_io_errors(&self.printer, file_trace)?;
     self.printer.after_print()?;
     if let Some(trace) = self.trace.print(self.arg.output.json.is_some()) {
       eprintln!("{}", trace);
     }
-    if error_count > 0 {
-      Err(anyhow::anyhow!(EC::DiagnosticError(error_count)))
-    } else {
-      Ok(())
+    self.check_rule_languages()?;
+    if !self.arg.no_summary {
+      let files = self.trace.file_trace.files_scanned();
+      let report = self.summary.report(
+        files,
+        self.printer.applied_edits(),
+        self.printer.stale_files(),
+      );
+      eprintln!("{report}");
     }
+    self.arg.output.report_io_errors(&self.trace.file_trace)?;
+    finding_count.into_result(&self.arg.exit_code_for)
   }
 }
 
+/// Deduplicate the diffs of every rule in one file, see `--dedupe`.
+fn dedupe_diffs(
+  diffs: Vec<(usize, NodeMatch<StrDoc<SgLang>>)>,
+  dedupe: Dedupe,
+) -> Vec<(usize, NodeMatch<StrDoc<SgLang>>)> {
+  if dedupe == Dedupe::None {
+    return diffs;
+  }
+  let mut by_rule: BTreeMap<usize, Vec<_>> = BTreeMap::new();
+  for (idx, nm) in diffs {
+    by_rule.entry(idx).or_default().push(nm);
+  }
+  by_rule
+    .into_iter()
+    .flat_map(|(idx, nms)| {
+      let nms = dedupe.apply(nms, |m| m.range());
+      nms.into_iter().map(move |nm| (idx, nm))
+    })
+    .collect()
+}
+
 fn print_unused_suppressions(
   path: &Path,
   matches: Vec<NodeMatch<StrDoc<SgLang>>>,
@@ -205,6 +649,7 @@ fn print_unused_suppressions(
     language: "rust".parse().unwrap(),
     message: "Unused '@ast-grep-ignore' directive.".into(),
     metadata: None,
+    labels: None,
     note: None,
     rewriters: None,
     severity: Severity::Hint,
@@ -221,26 +666,81 @@ impl<P: Printer> PathWorker for ScanWithConfig<P> {
   fn build_walk(&self) -> Result<WalkParallel> {
     self.arg.input.walk()
   }
+  fn get_input(&self) -> &InputArgs {
+    &self.arg.input
+  }
   fn produce_item(&self, path: &Path) -> Option<Vec<Self::Item>> {
-    filter_file_interactive(path, &self.configs)
+    let lang = self.arg.input.infer_lang(path, self.get_trace())?;
+    self
+      .file_langs
+      .lock()
+      .expect("should not fail")
+      .insert(lang);
+    let prefilter = self
+      .prefilter
+      .as_ref()
+      .map(|p| (p, &self.trace.inner.prefilter));
+    filter_file_interactive(path, lang, &self.configs, prefilter, self.get_trace())
   }
 }
 
 struct ScanWithRule<Printer> {
   printer: Printer,
-  rules: Vec<RuleConfig<SgLang>>,
+  configs: RuleCollection<SgLang>,
+  /// the path of code from StdIn, see `--stdin-filepath`
+  stdin_path: Option<PathBuf>,
+  /// every record has its own path, see `--stdin-batch`
+  batch: bool,
+  print_fixed: bool,
+  dedupe: Dedupe,
+  exit_codes: Vec<(SeverityLevel, u8)>,
+  summary: Option<ScanSummary>,
 }
 impl<P: Printer> ScanWithRule<P> {
-  fn try_new(arg: ScanArg, printer: P) -> Result<Self> {
-    let rules = if let Some(path) = &arg.rule {
-      read_rule_file(path, None)?
+  fn try_new(mut arg: ScanArg, printer: P) -> Result<Self> {
+    let mut overwrite = RuleOverwrite::new(&arg.severity, arg.filter.take())?;
+    overwrite.duplicate_rules = arg.allow_duplicate_rules;
+    overwrite.no_strict_yaml = arg.no_strict_yaml;
+    let configs = if let Some(path) = &arg.rule {
+      let (rules, _) = read_rule_file(path, &overwrite)?;
+      RuleCollection::try_new(rules).context(EC::GlobPattern)?
     } else if let Some(text) = &arg.inline_rules {
-      from_yaml_string(text, &Default::default())
-        .with_context(|| EC::ParseRule("INLINE_RULES".into()))?
+      let (rules, _) = read_rule_yaml(text, Path::new("INLINE_RULES"), &overwrite)?;
+      RuleCollection::try_new(rules).context(EC::GlobPattern)?
+    } else if arg.stdin_filepath.is_some() || arg.input.stdin_batch {
+      find_rules(arg.config.take(), &overwrite)?.0
     } else {
       return Err(anyhow::anyhow!(EC::RuleNotSpecified));
     };
-    Ok(Self { printer, rules })
+    if let Some(path) = &arg.stdin_filepath {
+      if SgLang::from_path(path).is_none() {
+        return Err(anyhow::anyhow!(EC::UnknownFileLanguage(path.clone())));
+      }
+    }
+    Ok(Self {
+      printer,
+      configs,
+      stdin_path: arg.stdin_filepath,
+      batch: arg.input.stdin_batch,
+      print_fixed: arg.print_fixed,
+      dedupe: arg.output.dedupe,
+      exit_codes: arg.exit_code_for,
+      summary: (!arg.no_summary).then(ScanSummary::default),
+    })
+  }
+
+  /// Rules applicable to the code from StdIn and its language.
+  /// The path of a batch record takes precedence over `--stdin-filepath`.
+  fn stdin_rules(&self, path: Option<&Path>) -> Option<(Vec<&RuleConfig<SgLang>>, SgLang)> {
+    if let Some(path) = path.or(self.stdin_path.as_deref()) {
+      let lang = SgLang::from_path(path)?;
+      Some((self.configs.get_rule_from_lang(path, lang), lang))
+    } else {
+      // rules can be empty if all of them are excluded by --only-severity
+      let rules: Vec<_> = self.configs.iter().collect();
+      let lang = rules.first()?.language;
+      Some((rules, lang))
+    }
   }
 }
 
@@ -248,53 +748,111 @@ impl<P: Printer> Worker for ScanWithRule<P> {
   type Item = (PathBuf, AstGrep, PreScan);
   fn consume_items(&self, items: Items<Self::Item>) -> Result<()> {
     self.printer.before_print()?;
-    let mut error_count = 0usize;
-    let combined = CombinedScan::new(self.rules.iter().collect());
+    let mut finding_count = FindingCount::default();
+    let mut files = 0;
     for (path, grep, pre_scan) in items {
+      files += 1;
+      let rules = self.stdin_rules(self.batch.then_some(path.as_path()));
+      let combined = CombinedScan::new(rules.map(|(rules, _)| rules).unwrap_or_default());
       let file_content = grep.source().to_string();
-      // do not exclude_fix rule in run_with_rule
-      let scanned = combined.scan(&grep, pre_scan, false);
-      for (idx, matches) in scanned.matches {
+      // do not exclude_fix rule in run_with_rule unless fixes are applied
+      let scanned = combined.scan(&grep, pre_scan, self.print_fixed);
+      let mut fixes = vec![];
+      let line_ending = LineEnding::of(&file_content);
+      for (idx, nm) in dedupe_diffs(scanned.diffs, self.dedupe) {
         let rule = combined.get_rule(idx);
-        if matches!(rule.severity, Severity::Error) {
-          error_count = error_count.saturating_add(matches.len());
+        finding_count.add(&rule.severity, 1);
+        if let Some(summary) = &self.summary {
+          summary.add_findings(&path, rule, 1);
+        }
+        if let Some(fixer) = &rule.matcher.fixer {
+          fixes.push((Diff::generate(nm, &rule.matcher, fixer, line_ending), rule));
         }
-        match_rule_on_file(&path, matches, rule, &file_content, &self.printer)?;
+      }
+      let matches: Vec<_> = scanned
+        .matches
+        .into_iter()
+        .map(|(idx, matches)| {
+          let rule = combined.get_rule(idx);
+          let matches = self.dedupe.apply(matches, |m| m.range());
+          finding_count.add(&rule.severity, matches.len());
+          if let Some(summary) = &self.summary {
+            summary.add_findings(&path, rule, matches.len());
+          }
+          (rule, matches)
+        })
+        .collect();
+      if !self.print_fixed {
+        let count = matches.iter().map(|(_, m)| m.len()).sum();
+        self.printer.before_file(&path, count)?;
+        for (rule, matches) in matches {
+          match_rule_on_file(&path, matches, rule, &file_content, &self.printer)?;
+        }
+      } else {
+        let fixes = fixes.iter().map(|(d, r)| Fix::new(d, Some(*r))).collect();
+        let (fixed, _) = apply_fixes(&file_content, fixes, &path);
+        std::io::stdout().write_all(fixed.as_bytes())?;
       }
     }
     self.printer.after_print()?;
-    if error_count > 0 {
-      Err(anyhow::anyhow!(EC::DiagnosticError(error_count)))
-    } else {
-      Ok(())
+    if let Some(summary) = &self.summary {
+      eprintln!(
+        "{}",
+        summary.report(
+          files,
+          self.printer.applied_edits(),
+          self.printer.stale_files()
+        )
+      );
     }
+    finding_count.into_result(&self.exit_codes)
   }
 }
 
 impl<P: Printer> StdInWorker for ScanWithRule<P> {
   fn parse_stdin(&self, src: String) -> Option<Self::Item> {
-    use ast_grep_core::Language;
-    let lang = self.rules[0].language;
-    let combined = CombinedScan::new(self.rules.iter().collect());
+    let (rules, lang) = self.stdin_rules(None)?;
+    let combined = CombinedScan::new(rules);
     let grep = lang.ast_grep(src);
     let pre_scan = combined.find(&grep);
-    if !pre_scan.hit_set.is_empty() {
-      Some((PathBuf::from("STDIN"), grep, pre_scan))
+    let path = self
+      .stdin_path
+      .clone()
+      .unwrap_or_else(|| PathBuf::from("STDIN"));
+    // the fixed code is always printed even if nothing is found
+    if !pre_scan.hit_set.is_empty() || self.print_fixed {
+      Some((path, grep, pre_scan))
     } else {
       None
     }
   }
 }
+impl<P: Printer> StdInBatchWorker for ScanWithRule<P> {
+  fn parse_record(&self, path: PathBuf, src: String) -> Result<Option<Self::Item>> {
+    let Some((rules, lang)) = self.stdin_rules(Some(&path)) else {
+      return Err(anyhow::anyhow!(EC::UnknownFileLanguage(path)));
+    };
+    let combined = CombinedScan::new(rules);
+    let grep = lang.ast_grep(src);
+    let pre_scan = combined.find(&grep);
+    Ok((!pre_scan.hit_set.is_empty()).then_some((path, grep, pre_scan)))
+  }
+  fn print_record(&self, record: &BatchOutput) -> Result<()> {
+    self.printer.print_record(record)
+  }
+}
+
 fn match_rule_diff_on_file(
   path: &Path,
   matches: Vec<(NodeMatch<StrDoc<SgLang>>, &RuleConfig<SgLang>)>,
+  line_ending: LineEnding,
   reporter: &impl Printer,
 ) -> Result<()> {
   let diffs = matches
     .into_iter()
     .filter_map(|(m, rule)| {
       let fix = rule.matcher.fixer.as_ref()?;
-      let diff = Diff::generate(m, &rule.matcher, fix);
+      let diff = Diff::generate(m, &rule.matcher, fix, line_ending);
       Some((diff, rule))
     })
     .collect();
@@ -312,8 +870,9 @@ fn match_rule_on_file(
   let matches = matches.into_iter();
   let file = SimpleFile::new(path.to_string_lossy(), file_content);
   if let Some(fixer) = &rule.matcher.fixer {
+    let line_ending = LineEnding::of(file_content);
     let diffs = matches
-      .map(|m| (Diff::generate(m, &rule.matcher, fixer), rule))
+      .map(|m| (Diff::generate(m, &rule.matcher, fixer, line_ending), rule))
       .collect();
     reporter.print_rule_diffs(diffs, path)?;
   } else {
@@ -325,7 +884,7 @@ fn match_rule_on_file(
 #[cfg(test)]
 mod test {
   use super::*;
-  use crate::print::ColorArg;
+  use crate::print::{ColorArg, DiffStyle, PathStyle};
   use std::fs::File;
   use std::io::Write;
   use tempfile::TempDir;
@@ -357,16 +916,45 @@ rule:
     ScanArg {
       config: None,
       filter: None,
+      no_prefilter: false,
       rule: None,
       inline_rules: None,
+      check_rules: false,
+      allow_duplicate_rules: None,
+      no_strict_yaml: false,
       report_style: ReportStyle::Rich,
+      diff: false,
+      stdin_filepath: None,
+      print_fixed: false,
+      group_by: None,
+      heading: Heading::Never,
+      context: 0,
+      no_summary: false,
+      strict_rules: false,
+      exit_code_for: vec![],
       input: InputArgs {
         no_ignore: vec![],
+        hidden: false,
+        ignore_file: vec![],
         paths: vec![PathBuf::from(".")],
         stdin: false,
+        stdin_batch: false,
+        files_from: None,
+        files_from0: None,
         follow: false,
+        one_file_system: false,
         globs: vec![],
+        no_glob_normalization: false,
+        file_type: vec![],
+        type_not: vec![],
+        type_list: false,
+        lang_glob: vec![],
+        no_shebang_detection: false,
         threads: 0,
+        force_exclude: false,
+        max_file_size: None,
+        skip_minified: false,
+        minified_line_threshold: 500,
       },
       severity: SeverityArg {
         error: None,
@@ -374,13 +962,28 @@ rule:
         info: None,
         hint: None,
         off: None,
+        only_severity: vec![],
       },
       output: OutputArgs {
         interactive: false,
         json: None,
+        json_include_rewritten: false,
         update_all: false,
+        session: None,
+        session_report: None,
         color: ColorArg::Never,
+        color_theme: None,
+        highlight: false,
+        tab_width: 4,
+        diff_style: DiffStyle::Inline,
+        context_separator: None,
+        field_separator: ":".into(),
+        path_style: PathStyle::Relative,
+        pager: None,
         tracing: Default::default(),
+        no_messages: false,
+        strict_io: false,
+        dedupe: Default::default(),
       },
       format: None,
     }
@@ -437,4 +1040,48 @@ rule:
     assert!(err.is::<EC>());
     assert_eq!(err.to_string(), "Cannot parse rule INLINE_RULES");
   }
+
+  #[test]
+  fn test_parse_exit_code() {
+    assert_eq!(parse_exit_code("error=2"), Ok((SeverityLevel::Error, 2)));
+    assert_eq!(
+      parse_exit_code("Warning=255"),
+      Ok((SeverityLevel::Warning, 255))
+    );
+    assert!(parse_exit_code("error=0").is_err());
+    assert!(parse_exit_code("error=256").is_err());
+    assert!(parse_exit_code("error").is_err());
+    assert!(parse_exit_code("off=1").is_err());
+  }
+
+  fn exit_code(count: FindingCount, exit_codes: &[(SeverityLevel, u8)]) -> i32 {
+    match count.into_result(exit_codes) {
+      Ok(_) => 0,
+      Err(e) => e.downcast_ref::<EC>().expect("should be EC").exit_code(),
+    }
+  }
+
+  #[test]
+  fn test_finding_exit_code() {
+    let mut count = FindingCount::default();
+    count.add(&Severity::Warning, 2);
+    count.add(&Severity::Hint, 1);
+    assert_eq!(exit_code(count, &[]), 0);
+    let mut count = FindingCount::default();
+    count.add(&Severity::Warning, 2);
+    count.add(&Severity::Hint, 1);
+    let codes = [(SeverityLevel::Hint, 3), (SeverityLevel::Warning, 4)];
+    assert_eq!(exit_code(count, &codes), 4);
+    let mut count = FindingCount::default();
+    count.add(&Severity::Error, 1);
+    count.add(&Severity::Hint, 1);
+    assert_eq!(exit_code(count, &codes), 1);
+    let mut count = FindingCount::default();
+    count.add(&Severity::Error, 1);
+    let codes = [(SeverityLevel::Error, 3), (SeverityLevel::Error, 5)];
+    assert_eq!(exit_code(count, &codes), 5);
+    let mut count = FindingCount::default();
+    count.add(&Severity::Off, 1);
+    assert_eq!(exit_code(count, &codes), 0);
+  }
 }
@@ -1,17 +1,29 @@
-use crate::lang::SgLang;
-use crate::print::{ColorArg, JsonStyle};
+use crate::config::read_color_theme;
+use crate::lang::{LanguageGlobs, SgLang};
+use crate::print::{BatchOutput, ColorArg, ColorTheme, DiffStyle, JsonStyle, PathStyle, Printer};
 use crate::utils::ErrorContext as EC;
-use crate::utils::Tracing;
+use crate::utils::{FileTrace, SeverityLevel, Tracing};
 
 use anyhow::{Context, Result};
+use ast_grep_config::suggest_closest;
+use ast_grep_language::Language;
 use clap::{Args, ValueEnum};
 use ignore::{
+  gitignore::GitignoreBuilder,
   overrides::{Override, OverrideBuilder},
+  types::Types,
   WalkBuilder, WalkParallel,
 };
 use serde::{Deserialize, Serialize};
 
-use std::path::PathBuf;
+use std::borrow::Cow;
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
+use std::ffi::OsString;
+use std::io::Write;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
 
 /// input related options
 #[derive(Args)]
@@ -29,18 +41,81 @@ pub struct InputArgs {
   #[clap(long)]
   pub follow: bool,
 
+  /// Do not descend into directories on other file systems than the path being searched.
+  ///
+  /// Each path in the command line starts from its own file system, so mounted network
+  /// shares or container overlays under it are not searched. This is supported on Unix
+  /// and Windows, where file systems are told apart by device number and volume
+  /// respectively. Skipped directories are counted in --tracing on Unix.
+  #[clap(long)]
+  pub one_file_system: bool,
+
   /// Do not respect hidden file system or ignore files (.gitignore, .ignore, etc.).
   ///
   /// You can suppress multiple ignore files by passing `no-ignore` multiple times.
-  #[clap(long, action = clap::ArgAction::Append, value_name = "FILE_TYPE")]
+  /// A bare `--no-ignore` without FILE_TYPE is a shorthand for `--no-ignore=all`,
+  /// which searches hidden files and disregards all ignore files.
+  /// `--no-ignore=hidden` is the same as --hidden.
+  /// FILE_TYPE must be written as `--no-ignore=FILE_TYPE` to tell it from the positional paths.
+  #[clap(
+    long,
+    action = clap::ArgAction::Append,
+    value_name = "FILE_TYPE",
+    num_args(0..=1),
+    require_equals = true,
+    default_missing_value = "all"
+  )]
   pub no_ignore: Vec<IgnoreFile>,
 
+  /// Search hidden files and directories, the same as `--no-ignore=hidden`.
+  ///
+  /// Ignore files are still respected unless other --no-ignore values are passed.
+  #[clap(long)]
+  pub hidden: bool,
+
+  /// Ignore paths matching the gitignore-style patterns in FILE.
+  ///
+  /// Unlike .gitignore, the patterns are not relative to the directory containing FILE.
+  /// They are matched against the searched paths as given, e.g. relative to the current
+  /// directory when searching the default path `.`.
+  /// They have lower precedence than other ignore files, and --globs overrides them.
+  /// The option can be passed multiple times.
+  /// Besides, .sgignore files are respected like .ignore files, see --no-ignore=dot.
+  #[clap(long, action = clap::ArgAction::Append, value_name = "FILE")]
+  pub ignore_file: Vec<PathBuf>,
+
   /// Enable search code from StdIn.
   ///
   /// Use this if you need to take code stream from standard input.
   #[clap(long)]
   pub stdin: bool,
 
+  /// Search many in-memory files from StdIn, one NDJSON record per file.
+  ///
+  /// Every line is a record like `{"path": "a.ts", "source": "..."}`. The path infers the
+  /// language and scopes rules like --stdin-filepath, and it does not need to exist.
+  /// Records are processed as they arrive. Results are printed as `--json=stream`,
+  /// followed by an `{"error": ...}` record for every malformed record and a
+  /// `{"summary": ...}` record with the totals after StdIn is closed.
+  #[clap(
+    long,
+    conflicts_with_all = ["paths", "stdin", "files_from", "files_from0", "interactive", "update_all"],
+  )]
+  pub stdin_batch: bool,
+
+  /// Read the paths to search from FILE, one path per line. `-` reads them from StdIn.
+  ///
+  /// The listed paths replace the positional paths, so a script can pass any number of
+  /// files without hitting the command line length limit. Like paths in the command line,
+  /// listed files are searched even if ignore files exclude them, but --globs still apply.
+  /// Nonexistent paths are reported and skipped.
+  #[clap(long, value_name = "FILE", conflicts_with_all = ["paths", "stdin", "files_from0"])]
+  pub files_from: Option<PathBuf>,
+
+  /// Like --files-from, but the paths are separated by NUL, like the output of `find -print0`.
+  #[clap(long, value_name = "FILE", conflicts_with_all = ["paths", "stdin"])]
+  pub files_from0: Option<PathBuf>,
+
   /// Include or exclude file paths.
   ///
   /// Include or exclude files and directories for searching that match the
@@ -51,6 +126,61 @@ pub struct InputArgs {
   #[clap(long, action = clap::ArgAction::Append)]
   pub globs: Vec<String>,
 
+  /// Keep backslashes in --globs as escapes on Windows.
+  ///
+  /// On Windows, backslashes in globs are path separators by default, so `src\**\*.ts`
+  /// works like `src/**/*.ts`. Pass this flag to escape special characters with backslashes
+  /// instead. On other platforms backslashes are always escapes, and a warning is printed
+  /// for globs with backslashes unless this flag is passed.
+  #[clap(long)]
+  pub no_glob_normalization: bool,
+
+  /// Only search files of type NAME, like `rust` or `ts`. Can be passed multiple times.
+  ///
+  /// File types are ripgrep's types extended with the file extensions of ast-grep
+  /// languages, see --type-list. Types are applied before --globs, so a glob can
+  /// still include or exclude a file. With --lang, files must match both the
+  /// language and the types.
+  #[clap(short = 't', long = "type", value_name = "NAME", action = clap::ArgAction::Append)]
+  pub file_type: Vec<String>,
+
+  /// Do not search files of type NAME. Can be passed multiple times, see --type.
+  #[clap(short = 'T', long, value_name = "NAME", action = clap::ArgAction::Append)]
+  pub type_not: Vec<String>,
+
+  /// Print all file types supported by --type and their globs, then exit.
+  #[clap(long)]
+  pub type_list: bool,
+
+  /// Treat files matching GLOB as the language LANG, e.g. `groovy:Jenkinsfile`.
+  ///
+  /// This works like languageGlobs in sgconfig.yml for one run, and takes precedence
+  /// over languageGlobs if a file matches globs of several languages.
+  /// The option can be passed multiple times.
+  #[clap(
+    long,
+    value_name = "LANG:GLOB",
+    value_parser = parse_lang_glob,
+    action = clap::ArgAction::Append,
+  )]
+  pub lang_glob: Vec<(String, String)>,
+
+  /// Do not detect the language of files without extension by their shebang line.
+  ///
+  /// By default, a walked file without extension is read up to its first 256 bytes,
+  /// and a shebang like `#!/usr/bin/env python3` or `#!/bin/bash` decides its language.
+  /// Files with NUL bytes are regarded as binary and skipped. This flag saves the reads.
+  #[clap(long)]
+  pub no_shebang_detection: bool,
+
+  /// Apply exclusion globs and ignore files to paths passed explicitly in the command line.
+  ///
+  /// By default, ast-grep searches every explicit path even if it is excluded by --globs
+  /// or by ignore files like .gitignore. This flag makes ast-grep skip such paths, which is
+  /// useful when a script passes a list of files to ast-grep.
+  #[clap(long)]
+  pub force_exclude: bool,
+
   /// Set the approximate number of threads to use.
   ///
   /// This flag sets the approximate number of threads to use. A value of 0
@@ -58,46 +188,381 @@ pub struct InputArgs {
   /// heuristics.
   #[clap(short = 'j', long, default_value = "0", value_name = "NUM")]
   pub threads: usize,
+
+  /// Skip files larger than NUM bytes.
+  ///
+  /// Files exceeding the limit are skipped before they are read. The size accepts
+  /// suffixes K, M and G for kibibytes, mebibytes and gibibytes, e.g. `500K` or `2M`.
+  /// A warning is printed if a file passed explicitly in the command line is skipped.
+  /// There is no limit by default.
+  #[clap(long, value_name = "NUM+SUFFIX", value_parser = parse_file_size)]
+  pub max_file_size: Option<u64>,
+
+  /// Skip minified JavaScript, TypeScript and CSS files.
+  ///
+  /// ast-grep samples the beginning of each file and skips it if the average line length
+  /// exceeds the --minified-line-threshold or if no newline is found in the sample.
+  /// Files passed explicitly in the command line are never skipped by this heuristic.
+  #[clap(long)]
+  pub skip_minified: bool,
+
+  /// Average line length above which a file is considered minified by --skip-minified.
+  #[clap(long, default_value = "500", value_name = "NUM")]
+  pub minified_line_threshold: usize,
 }
 
-impl InputArgs {
-  fn get_threads(&self) -> usize {
-    if self.threads == 0 {
-      std::thread::available_parallelism()
-        .map_or(1, |n| n.get())
-        .min(12)
+fn parse_lang_glob(s: &str) -> Result<(String, String), String> {
+  match s.split_once(':') {
+    Some((lang, glob)) if !lang.is_empty() && !glob.is_empty() => {
+      Ok((lang.to_string(), glob.to_string()))
+    }
+    _ => Err(format!("expected LANG:GLOB but found `{s}`")),
+  }
+}
+
+fn parse_file_size(size: &str) -> Result<u64, String> {
+  let size = size.trim();
+  let (num, unit) = match size.char_indices().last() {
+    Some((idx, c)) if c.is_ascii_alphabetic() => (&size[..idx], c.to_ascii_uppercase()),
+    _ => (size, 'B'),
+  };
+  let multiplier: u64 = match unit {
+    'B' => 1,
+    'K' => 1 << 10,
+    'M' => 1 << 20,
+    'G' => 1 << 30,
+    _ => return Err(format!("unknown size suffix `{unit}`, expect K, M or G")),
+  };
+  let num: u64 = num
+    .parse()
+    .map_err(|_| format!("`{size}` is not a valid file size"))?;
+  num
+    .checked_mul(multiplier)
+    .ok_or_else(|| format!("`{size}` is too large"))
+}
+
+/// Interpret the escapes `\t`, `\n`, `\r`, `\0` and `\\` in separators.
+fn parse_separator(separator: &str) -> Result<String, String> {
+  let mut ret = String::with_capacity(separator.len());
+  let mut chars = separator.chars();
+  while let Some(c) = chars.next() {
+    if c != '\\' {
+      ret.push(c);
+      continue;
+    }
+    let unescaped = match chars.next() {
+      Some('t') => '\t',
+      Some('n') => '\n',
+      Some('r') => '\r',
+      Some('0') => '\0',
+      Some('\\') => '\\',
+      Some(c) => {
+        return Err(format!(
+          "unknown escape `\\{c}`, expect \\t, \\n, \\r, \\0 or \\\\"
+        ))
+      }
+      None => return Err("trailing `\\` should be escaped as `\\\\`".into()),
+    };
+    ret.push(unescaped);
+  }
+  Ok(ret)
+}
+
+/// Print the file types of `--type-list`, one type and its globs per line.
+pub fn print_type_list() -> Result<()> {
+  let mut stdout = std::io::stdout().lock();
+  let customs = SgLang::custom_type_names();
+  for def in SgLang::all_file_types().definitions() {
+    let globs = def.globs().join(", ");
+    if customs.iter().any(|c| c == def.name()) {
+      writeln!(stdout, "{}: {globs} (custom language)", def.name())?;
     } else {
-      self.threads
+      writeln!(stdout, "{}: {globs}", def.name())?;
     }
   }
+  Ok(())
+}
+
+/// Resolve the `--threads` option. 0 means choosing the thread count by heuristics.
+pub fn thread_count(threads: usize) -> usize {
+  if threads == 0 {
+    std::thread::available_parallelism()
+      .map_or(1, |n| n.get())
+      .min(12)
+  } else {
+    threads
+  }
+}
+
+/// Turn backslashes in the glob into path separators on Windows.
+/// The walked paths are matched with `/` separators on every platform, see globset's `Candidate`.
+fn normalize_glob(glob: &str, windows: bool) -> Cow<'_, str> {
+  if windows && glob.contains('\\') {
+    Cow::Owned(glob.replace('\\', "/"))
+  } else {
+    Cow::Borrowed(glob)
+  }
+}
+
+/// Backslashes in globs are escapes except on Windows, which is likely a mistake.
+fn backslash_warning(glob: &str) -> Option<String> {
+  if cfg!(windows) || !glob.contains('\\') {
+    return None;
+  }
+  Some(format!(
+    "⚠️  Glob `{glob}` contains backslashes, which escape the next character. \
+     Use `/` to separate paths, or pass --no-glob-normalization to silence this warning."
+  ))
+}
+
+/// Read paths separated by the separator from the file, or from StdIn if the file is `-`.
+fn read_path_list(list: &Path, separator: char) -> Result<Vec<PathBuf>> {
+  let content = if list == Path::new("-") {
+    std::io::read_to_string(std::io::stdin()).context(EC::ReadFile(list.to_path_buf()))?
+  } else {
+    std::fs::read_to_string(list).with_context(|| EC::ReadFile(list.to_path_buf()))?
+  };
+  Ok(parse_path_list(&content, separator))
+}
+
+fn parse_path_list(content: &str, separator: char) -> Vec<PathBuf> {
+  content
+    .split(separator)
+    .map(|path| path.strip_suffix('\r').unwrap_or(path))
+    .filter(|path| !path.is_empty())
+    .map(PathBuf::from)
+    .collect()
+}
+
+/// Drop paths given more than once or inside another given path, so that no file is
+/// searched twice. Paths are compared after resolving symlinks in their parents, and
+/// the paths themselves are also resolved if links are followed. A path inside another
+/// one is kept if the walk of the outer path skips it, e.g. if it is hidden or ignored.
+fn dedupe_paths(paths: &[PathBuf], follow: bool, no_ignore: &NoIgnore) -> Vec<PathBuf> {
+  if paths.len() < 2 {
+    return paths.to_vec();
+  }
+  let keys: Vec<_> = paths.iter().map(|p| normalize_path(p, follow)).collect();
+  // paths are ordered by components, so every path comes right after the paths containing it
+  let mut sorted: Vec<_> = keys
+    .iter()
+    .enumerate()
+    .filter_map(|(i, key)| Some((key.as_deref()?, i)))
+    .collect();
+  sorted.sort();
+  let mut pruned = vec![None; paths.len()];
+  // walked paths containing the current path, the innermost is the last
+  let mut outer: Vec<(&Path, usize)> = vec![];
+  for (key, i) in sorted {
+    while outer.last().map_or(false, |(o, _)| !key.starts_with(o)) {
+      outer.pop();
+    }
+    // a symlink not followed is walked on its own even if it is inside another path
+    let nested = follow || !paths[i].is_symlink();
+    match outer.last() {
+      Some(&(o, j)) if o == key => {
+        pruned[i] = Some(format!("it is the same as {}", paths[j].display()));
+      }
+      Some(&(o, j)) if nested && !no_ignore.is_ignored_under(o, key) => {
+        pruned[i] = Some(format!("it is inside {}", paths[j].display()));
+      }
+      _ => outer.push((key, i)),
+    }
+  }
+  let mut ret = vec![];
+  for (path, pruned) in paths.iter().zip(pruned) {
+    match pruned {
+      Some(reason) => eprintln!("⚠️  {} is skipped because {reason}.", path.display()),
+      None => ret.push(path.clone()),
+    }
+  }
+  ret
+}
+
+fn normalize_path(path: &Path, follow: bool) -> Option<PathBuf> {
+  if follow {
+    return path.canonicalize().ok();
+  }
+  match (path.parent(), path.file_name()) {
+    (Some(parent), Some(name)) => {
+      let parent = if parent.as_os_str().is_empty() {
+        Path::new(".")
+      } else {
+        parent
+      };
+      Some(parent.canonicalize().ok()?.join(name))
+    }
+    _ => path.canonicalize().ok(),
+  }
+}
+
+impl InputArgs {
+  /// Register --lang-glob, which must be called after sgconfig.yml is loaded.
+  pub fn register_lang_globs(&self) -> Result<()> {
+    if self.lang_glob.is_empty() {
+      return Ok(());
+    }
+    let mut globs = LanguageGlobs::default();
+    for (lang, glob) in &self.lang_glob {
+      globs.add(lang.clone(), glob.clone());
+    }
+    SgLang::register_cli_globs(globs)
+  }
+
+  /// The language of a walked file by its path, or by its shebang if it has no extension.
+  pub fn infer_lang(&self, path: &Path, trace: &FileTrace) -> Option<SgLang> {
+    if let Some(lang) = SgLang::from_path(path) {
+      return Some(lang);
+    }
+    if self.no_shebang_detection || path.extension().is_some() {
+      return None;
+    }
+    let lang = SgLang::from_shebang(path)?;
+    trace.add_shebang_file(path, lang.to_string());
+    Some(lang)
+  }
+
+  fn get_threads(&self) -> usize {
+    thread_count(self.threads)
+  }
   pub fn walk(&self) -> Result<WalkParallel> {
     let threads = self.get_threads();
     let globs = self.build_globs().context(EC::BuildGlobs)?;
+    let types = self.build_types()?;
     Ok(
-      NoIgnore::disregard(&self.no_ignore)
-        .walk(&self.paths)
+      self
+        .walk_paths(&globs)?
         .threads(threads)
         .follow_links(self.follow)
+        .same_file_system(self.one_file_system)
+        .types(types)
         .overrides(globs)
         .build_parallel(),
     )
   }
 
-  pub fn walk_lang(&self, lang: SgLang) -> WalkParallel {
+  pub fn walk_lang(&self, lang: SgLang) -> Result<WalkParallel> {
     let threads = self.get_threads();
-    NoIgnore::disregard(&self.no_ignore)
-      .walk(&self.paths)
-      .threads(threads)
-      .follow_links(self.follow)
-      .types(lang.augmented_file_type())
-      .build_parallel()
+    let globs = self.build_globs().context(EC::BuildGlobs)?;
+    let types = self.build_types()?;
+    let mut builder = self.walk_paths(&globs)?;
+    // the walker takes one set of types, which is the language here.
+    // --type narrows the language files, and --globs take precedence over --type
+    if !self.file_type.is_empty() || !self.type_not.is_empty() {
+      builder.filter_entry(move |entry| {
+        let path = entry.path();
+        let is_dir = entry.file_type().map_or(false, |t| t.is_dir());
+        globs.matched(path, is_dir).is_whitelist() || !types.matched(path, is_dir).is_ignore()
+      });
+    }
+    Ok(
+      builder
+        .threads(threads)
+        .follow_links(self.follow)
+        .same_file_system(self.one_file_system)
+        .types(lang.augmented_file_type())
+        .build_parallel(),
+    )
+  }
+
+  /// Build the file types selected by --type and negated by --type-not.
+  fn build_types(&self) -> Result<Types> {
+    let mut builder = SgLang::all_file_types();
+    let defined = builder.definitions();
+    for name in self.file_type.iter().chain(&self.type_not) {
+      if !defined.iter().any(|def| def.name() == name) {
+        let names = defined.iter().map(|def| def.name());
+        let suggestion = suggest_closest(name, names).map(String::from);
+        return Err(anyhow::anyhow!(EC::UnknownFileType(
+          name.clone(),
+          suggestion
+        )));
+      }
+    }
+    for name in &self.file_type {
+      builder.select(name);
+    }
+    for name in &self.type_not {
+      builder.negate(name);
+    }
+    Ok(builder.build()?)
+  }
+
+  /// Walk the positional paths, or the paths listed by --files-from.
+  fn walk_paths(&self, globs: &Override) -> Result<WalkBuilder> {
+    let no_ignore = self.no_ignore()?;
+    let (list, separator) = match (&self.files_from, &self.files_from0) {
+      (Some(list), _) => (list, '\n'),
+      (_, Some(list)) => (list, '\0'),
+      _ => {
+        let paths = dedupe_paths(&self.paths, self.follow, &no_ignore);
+        return Ok(no_ignore.walk(&paths));
+      }
+    };
+    let paths = read_path_list(list, separator)?;
+    let paths: Vec<_> = paths
+      .into_iter()
+      .filter(|path| {
+        let exists = path.exists();
+        if !exists {
+          eprintln!(
+            "⚠️  {} is skipped because it does not exist.",
+            path.display()
+          );
+        }
+        exists && !globs.matched(path, path.is_dir()).is_ignore()
+      })
+      .collect();
+    if paths.is_empty() {
+      // nothing to search, the directory itself is not a file
+      let mut builder = no_ignore.walk(&[PathBuf::from(".")]);
+      builder.max_depth(Some(0));
+      return Ok(builder);
+    }
+    Ok(no_ignore.walk(&dedupe_paths(&paths, self.follow, &no_ignore)))
+  }
+
+  /// Build the matcher for explicit paths if --force-exclude is set.
+  pub fn force_excluder(&self) -> Result<Option<ForceExclude>> {
+    if !self.force_exclude {
+      return Ok(None);
+    }
+    let globs = self.build_globs().context(EC::BuildGlobs)?;
+    Ok(Some(ForceExclude {
+      globs,
+      no_ignore: self.no_ignore()?,
+      follow: self.follow,
+      listed: Mutex::default(),
+    }))
+  }
+
+  /// Ignore settings of the walk. Ignore files are parsed here to report errors early.
+  fn no_ignore(&self) -> Result<NoIgnore> {
+    for file in &self.ignore_file {
+      let mut builder = GitignoreBuilder::new("");
+      if let Some(err) = builder.add(file) {
+        return Err(err).context(EC::InvalidIgnoreFile(file.clone()));
+      }
+      builder
+        .build()
+        .with_context(|| EC::InvalidIgnoreFile(file.clone()))?;
+    }
+    let no_ignore = NoIgnore::disregard(&self.no_ignore, self.hidden);
+    Ok(no_ignore.ignore_files(self.ignore_file.clone()))
   }
 
   fn build_globs(&self) -> Result<Override> {
     let cwd = std::env::current_dir()?;
     let mut builder = OverrideBuilder::new(cwd);
     for glob in &self.globs {
-      builder.add(glob)?;
+      if self.no_glob_normalization {
+        builder.add(glob)?;
+        continue;
+      }
+      if let Some(warning) = backslash_warning(glob) {
+        eprintln!("{warning}");
+      }
+      builder.add(&normalize_glob(glob, cfg!(windows)))?;
     }
     Ok(builder.build()?)
   }
@@ -118,6 +583,19 @@ pub struct OutputArgs {
   #[clap(short = 'U', long)]
   pub update_all: bool,
 
+  /// Record decisions of the interactive session in PATH and resume from it.
+  ///
+  /// Every applied, skipped or deferred fix is appended to PATH as one line of JSON,
+  /// keyed by the fingerprint of the finding. Findings applied or skipped in previous
+  /// sessions with the same file are not shown again, unless an applied fix is missing
+  /// from the current file. Deferred findings are shown again.
+  #[clap(long, value_name = "PATH", conflicts_with = "json")]
+  pub session: Option<PathBuf>,
+
+  /// Print the tally of decisions recorded in a session file and exit, see --session.
+  #[clap(long, value_name = "PATH", conflicts_with = "session")]
+  pub session_report: Option<PathBuf>,
+
   /// Output matches in structured JSON .
   ///
   /// If this flag is set, ast-grep will output matches in JSON format.
@@ -135,6 +613,14 @@ pub struct OutputArgs {
   )]
   pub json: Option<JsonStyle>,
 
+  /// Add the file content after applying all rewrites to the JSON output.
+  ///
+  /// After the matches of every file with rewrites, a `{"file": .., "rewrittenSource": ..}`
+  /// object has the source after applying all non-overlapping rewrites or fixes of the file.
+  /// Files larger than 1MiB get a `rewrittenSourceNotice` field instead.
+  #[clap(long, requires = "json")]
+  pub json_include_rewritten: bool,
+
   /// Controls output color.
   ///
   /// This flag controls when to use colors. The default setting is 'auto', which
@@ -146,6 +632,81 @@ pub struct OutputArgs {
   #[clap(long, default_value = "auto", value_name = "WHEN")]
   pub color: ColorArg,
 
+  /// Customize the colors of matches, paths, line numbers and diffs.
+  ///
+  /// SPEC is a comma-separated list of `key:style`, for example
+  /// `match:red+bold,path:cyan,line:dim,diff.add:green,diff.del:red`.
+  /// A style joins colors, `on-<color>` backgrounds and modifiers like `bold` with `+`.
+  /// Colors can be names, numbers from 0 to 255 or `#rrggbb`.
+  /// The default comes from `output.colorTheme` in sgconfig.yml.
+  /// The theme has no effect if colors are not used, see --color.
+  #[clap(long, value_name = "SPEC")]
+  pub color_theme: Option<ColorTheme>,
+
+  /// Highlight the syntax of printed code in the terminal.
+  ///
+  /// Tokens are colored by the syntax tree of the file, and matches are emphasized
+  /// with bold and underline. Only the printed lines are highlighted.
+  /// It is disabled if colors are not used or stdout is not a terminal.
+  /// With rich diagnostics, scan prints code frames like --context does.
+  #[clap(long)]
+  pub highlight: bool,
+
+  /// Expand tabs to this tab stop when printing matches and diagnostics in terminal.
+  ///
+  /// Columns of other characters follow their display width, so CJK characters
+  /// and emoji take two columns. JSON output reports columns in characters.
+  #[clap(long, default_value = "4", value_name = "WIDTH", value_parser = clap::value_parser!(u16).range(1..))]
+  pub tab_width: u16,
+
+  /// Layout of rewrite diffs in the terminal, including the interactive preview and --diff.
+  ///
+  /// side-by-side prints the original and the replacement in two columns and
+  /// emphasizes changed words. It falls back to inline if stdout is not a terminal
+  /// or the terminal is narrower than 80 columns, so piped --diff output can still be applied.
+  #[clap(long, default_value = "inline", value_name = "STYLE")]
+  pub diff_style: DiffStyle,
+
+  /// The line printed between blocks of context lines, see --context.
+  ///
+  /// The default is `--` in output without heading, and a dashed line under a heading.
+  /// Scan only prints it between code frames if given.
+  /// Escapes like `\t` and `\0` are interpreted. An empty string prints no separator line.
+  #[clap(long, value_name = "STR", value_parser = parse_separator)]
+  pub context_separator: Option<String>,
+
+  /// The delimiter between the path, line and column of a location.
+  ///
+  /// It is used in output without heading and in the locations of scan's rich report.
+  /// Scan's short and medium report styles do not support it.
+  /// Escapes like `\t` and `\0` are interpreted.
+  #[clap(long, default_value = ":", value_name = "STR", value_parser = parse_separator)]
+  pub field_separator: String,
+
+  /// How paths are printed: `relative`, `absolute` or `from:<DIR>`.
+  ///
+  /// relative prints paths as they are found, relative to the current directory unless
+  /// absolute paths are given. absolute prints canonical paths, which editors can open
+  /// from any directory. `from:<DIR>` prints paths relative to DIR, with `..` if a file is
+  /// outside of DIR. It applies to terminal output, JSON and the interactive session.
+  #[clap(long, default_value = "relative", value_name = "STYLE")]
+  pub path_style: PathStyle,
+
+  /// Page terminal output through a pager, like `less`.
+  ///
+  /// CMD is run by the shell. Without CMD, the PAGER environment variable is used,
+  /// falling back to `less -RFX`, which quits if the output fits on one screen.
+  /// Output is only paged if stdout is a terminal, and never with --json or --interactive.
+  /// The option must be written as `--pager=CMD` to tell CMD from the positional arguments.
+  #[clap(
+    long,
+    value_name = "CMD",
+    num_args(0..=1),
+    require_equals = true,
+    default_missing_value = ""
+  )]
+  pub pager: Option<String>,
+
   /// Show tracing information for file/rule discovery and scanning.
   ///
   /// This flag helps user to inspect ast-grep's internal filtering of files and rules.
@@ -153,23 +714,125 @@ pub struct OutputArgs {
   /// tracing information outputs to stderr and does not affect the result of the search.
   #[clap(long, default_value = "nothing", value_name = "LEVEL")]
   pub tracing: Tracing,
+
+  /// Do not print errors of files and directories that cannot be read.
+  ///
+  /// The errors are counted and summarized in one line after the output. They are also
+  /// listed in --tracing, and --json always ends with an `{"errors": [..]}` record of them.
+  #[clap(long)]
+  pub no_messages: bool,
+
+  /// Exit with an error if any file or directory cannot be read.
+  ///
+  /// By default, files that cannot be read, like those without permission, are skipped
+  /// and do not change the exit code.
+  #[clap(long)]
+  pub strict_io: bool,
+
+  /// Remove duplicate matches of the same rule in a file before printing.
+  ///
+  /// Deduplication happens before matches are counted, printed or rewritten.
+  #[clap(long, default_value = "none", value_name = "MODE")]
+  pub dedupe: Dedupe,
+}
+
+/// How to remove duplicate matches, see --dedupe.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug, ValueEnum)]
+pub enum Dedupe {
+  /// Keep all matches.
+  #[default]
+  None,
+  /// Drop matches fully contained in another match, as well as matches with the same range.
+  Contains,
+  /// Drop matches with the same range as a previous match.
+  Exact,
+}
+
+impl Dedupe {
+  /// Deduplicate items of one rule in one file. Items are returned in the order of their range.
+  pub fn apply<T>(self, mut items: Vec<T>, range: impl Fn(&T) -> Range<usize>) -> Vec<T> {
+    if self == Dedupe::None {
+      return items;
+    }
+    // outer items come first if several items share the same start
+    items.sort_by_key(|item| {
+      let r = range(item);
+      (r.start, Reverse(r.end))
+    });
+    // for contains, the last kept item has the largest end among items starting before
+    let mut last: Option<Range<usize>> = None;
+    items.retain(|item| {
+      let r = range(item);
+      let duplicate = match (&last, self) {
+        (Some(prev), Dedupe::Exact) => *prev == r,
+        (Some(prev), _) => r.end <= prev.end,
+        (None, _) => false,
+      };
+      if !duplicate {
+        last = Some(r);
+      }
+      !duplicate
+    });
+    items
+  }
 }
 
 impl OutputArgs {
+  /// Print IO errors as a top level `errors` record of the JSON output. Other printers ignore it.
+  /// Must be called before `after_print` so the record is inside the JSON document.
+  pub fn print_io_errors(&self, printer: &impl Printer, trace: &FileTrace) -> Result<()> {
+    let errors = trace.io_errors();
+    if errors.is_empty() {
+      return Ok(());
+    }
+    printer.print_record(&BatchOutput::Errors(errors))
+  }
+
+  /// Summarize the IO errors suppressed by --no-messages, and fail with --strict-io.
+  pub fn report_io_errors(&self, trace: &FileTrace) -> Result<()> {
+    let errors = trace.io_errors();
+    if errors.is_empty() {
+      return Ok(());
+    }
+    if self.no_messages {
+      eprintln!(
+        "{} files could not be read; rerun without --no-messages for details",
+        errors.len()
+      );
+    }
+    if self.strict_io {
+      Err(anyhow::anyhow!(EC::UnreadableFiles(errors.len())))
+    } else {
+      Ok(())
+    }
+  }
+
   // either explicit interactive or implicit update_all
   pub fn needs_interactive(&self) -> bool {
     self.interactive || self.update_all
   }
+
+  /// The theme from --color-theme, or from the project configuration.
+  pub fn color_theme(&self, config_path: Option<PathBuf>) -> Result<ColorTheme> {
+    if let Some(theme) = &self.color_theme {
+      return Ok(theme.clone());
+    }
+    let Some(spec) = read_color_theme(config_path)? else {
+      return Ok(ColorTheme::default());
+    };
+    spec.parse().context(EC::ParseConfiguration)
+  }
 }
 
 /// File types to ignore, this is mostly the same as ripgrep.
 #[derive(Clone, Copy, Deserialize, Serialize, ValueEnum)]
 pub enum IgnoreFile {
   /// Search hidden files and directories. By default, hidden files and directories are skipped.
+  /// This is the same as --hidden.
   Hidden,
-  /// Don't respect .ignore files.
+  /// Don't respect .ignore and .sgignore files.
   /// This does *not* affect whether ast-grep will ignore files and directories whose names begin with a dot.
-  /// For that, use --no-ignore hidden.
+  /// For that, use --no-ignore=hidden.
   Dot,
   /// Don't respect ignore files that are manually configured for the repository such as git's '.git/info/exclude'.
   Exclude,
@@ -179,12 +842,17 @@ pub enum IgnoreFile {
   /// Don't respect ignore files (.gitignore, .ignore, etc.) in parent directories.
   Parent,
   /// Don't respect version control ignore files (.gitignore, etc.).
-  /// This implies --no-ignore parent for VCS files.
+  /// This implies --no-ignore=parent for VCS files.
   /// Note that .ignore files will continue to be respected.
   Vcs,
+  /// All of the above, the same as a bare --no-ignore.
+  All,
 }
 
-#[derive(Default)]
+/// Ignore file of ast-grep, respected like .ignore files.
+const SG_IGNORE: &str = ".sgignore";
+
+#[derive(Default, Clone)]
 pub struct NoIgnore {
   disregard_hidden: bool,
   disregard_parent: bool,
@@ -192,11 +860,17 @@ pub struct NoIgnore {
   disregard_vcs: bool,
   disregard_global: bool,
   disregard_exclude: bool,
+  /// see --ignore-file
+  ignore_files: Vec<PathBuf>,
 }
 
 impl NoIgnore {
-  pub fn disregard(ignores: &[IgnoreFile]) -> Self {
-    let mut ret = NoIgnore::default();
+  /// Ignore settings of --no-ignore values and --hidden.
+  pub fn disregard(ignores: &[IgnoreFile], hidden: bool) -> Self {
+    let mut ret = NoIgnore {
+      disregard_hidden: hidden,
+      ..Default::default()
+    };
     use IgnoreFile::*;
     for ignore in ignores {
       match ignore {
@@ -206,17 +880,40 @@ impl NoIgnore {
         Global => ret.disregard_global = true,
         Parent => ret.disregard_parent = true,
         Vcs => ret.disregard_vcs = true,
+        All => {
+          return NoIgnore {
+            disregard_hidden: true,
+            disregard_parent: true,
+            disregard_dot: true,
+            disregard_vcs: true,
+            disregard_global: true,
+            disregard_exclude: true,
+            ignore_files: vec![],
+          }
+        }
       }
     }
     ret
   }
 
+  /// Add ignore files, which must have been parsed without errors.
+  pub fn ignore_files(mut self, files: Vec<PathBuf>) -> Self {
+    self.ignore_files = files;
+    self
+  }
+
   pub fn walk(&self, path: &[PathBuf]) -> WalkBuilder {
     let mut paths = path.iter();
     let mut builder = WalkBuilder::new(paths.next().expect("non empty"));
     for path in paths {
       builder.add(path);
     }
+    for file in &self.ignore_files {
+      builder.add_ignore(file);
+    }
+    if !self.disregard_dot {
+      builder.add_custom_ignore_filename(SG_IGNORE);
+    }
     builder
       .hidden(!self.disregard_hidden)
       .parents(!self.disregard_parent)
@@ -226,6 +923,91 @@ impl NoIgnore {
       .git_exclude(!self.disregard_vcs && !self.disregard_exclude);
     builder
   }
+
+  /// Whether the path under the root is skipped by ignore files when walking the root.
+  /// Every component is checked by listing its parent directory, like [`ForceExclude`].
+  /// Paths not on the disk yet, like unsaved files, are not ignored.
+  pub fn is_ignored_under(&self, root: &Path, path: &Path) -> bool {
+    let Ok(relative) = path.strip_prefix(root) else {
+      return false;
+    };
+    let mut dir = root.to_path_buf();
+    for component in relative.components() {
+      let name = component.as_os_str();
+      if !dir.join(name).exists() {
+        return false;
+      }
+      let listed = self
+        .walk(&[dir.clone()])
+        .max_depth(Some(1))
+        .build()
+        .filter_map(Result::ok)
+        .any(|entry| entry.depth() == 1 && entry.file_name() == name);
+      if !listed {
+        return true;
+      }
+      dir.push(name);
+    }
+    false
+  }
+}
+
+/// Check explicit paths against globs and ignore files, see --force-exclude.
+pub struct ForceExclude {
+  globs: Override,
+  no_ignore: NoIgnore,
+  follow: bool,
+  /// names listed in each checked directory, so that every directory is walked once
+  listed: Mutex<HashMap<PathBuf, Arc<HashSet<OsString>>>>,
+}
+
+impl ForceExclude {
+  /// Returns true if the path, or one of its parent directories in the path, is excluded.
+  ///
+  /// The ignore chain is only available when walking a directory, so every component is
+  /// checked by listing its parent directory with the same settings as the real walk.
+  pub fn is_excluded(&self, path: &Path) -> bool {
+    let mut current = path;
+    loop {
+      let Some(name) = current.file_name() else {
+        return false;
+      };
+      let parent = match current.parent() {
+        Some(p) if !p.as_os_str().is_empty() => p,
+        _ => Path::new("."),
+      };
+      if !self.listed_names(parent).contains(name) {
+        return true;
+      }
+      // absolute paths only check the file itself to avoid listing the whole file system
+      if current.is_absolute() || parent == Path::new(".") {
+        return false;
+      }
+      current = parent;
+    }
+  }
+
+  /// Names of the entries in the directory that the real walk does not skip.
+  fn listed_names(&self, dir: &Path) -> Arc<HashSet<OsString>> {
+    if let Some(names) = self.listed.lock().expect("should not fail").get(dir) {
+      return names.clone();
+    }
+    let names: HashSet<_> = self
+      .no_ignore
+      .walk(&[dir.to_path_buf()])
+      .max_depth(Some(1))
+      .follow_links(self.follow)
+      .overrides(self.globs.clone())
+      .build()
+      .filter_map(Result::ok)
+      .filter(|entry| entry.depth() == 1)
+      .map(|entry| entry.file_name().to_os_string())
+      .collect();
+    let names = Arc::new(names);
+    let mut listed = self.listed.lock().expect("should not fail");
+    listed.insert(dir.to_path_buf(), names.clone());
+    names
+  }
 }
 
 #[derive(Args, Debug)]
@@ -240,6 +1022,13 @@ pub struct SeverityArg {
   pub hint: Option<Vec<String>>,
   #[clap(long, action = clap::ArgAction::Append, value_name = "RULE_ID", num_args(0..), require_equals = true)]
   pub off: Option<Vec<String>>,
+  /// Only run rules of the given severity. Can be passed multiple times.
+  ///
+  /// The filter applies after severity overrides like `--warning=RULE_ID`,
+  /// so overridden rules are selected by their new severity.
+  /// Rules excluded by this filter are neither compiled nor executed.
+  #[clap(long, action = clap::ArgAction::Append, value_name = "SEVERITY")]
+  pub only_severity: Vec<SeverityLevel>,
 }
 
 #[cfg(test)]
@@ -251,20 +1040,267 @@ mod test {
     let input = InputArgs {
       paths: vec![],
       follow: true,
+      one_file_system: false,
       no_ignore: vec![IgnoreFile::Dot, IgnoreFile::Exclude],
+      hidden: false,
+      ignore_file: vec![],
       stdin: false,
+      stdin_batch: false,
+      files_from: None,
+      files_from0: None,
       globs: vec!["*.rs".to_string(), "!*.toml".to_string()],
+      no_glob_normalization: false,
+      file_type: vec![],
+      type_not: vec![],
+      type_list: false,
+      lang_glob: vec![],
+      no_shebang_detection: false,
       threads: 0,
+      force_exclude: false,
+      max_file_size: None,
+      skip_minified: false,
+      minified_line_threshold: 500,
     };
     assert!(input.build_globs().is_ok());
     let input = InputArgs {
       paths: vec![],
       follow: true,
+      one_file_system: false,
       no_ignore: vec![IgnoreFile::Dot, IgnoreFile::Exclude],
+      hidden: false,
+      ignore_file: vec![],
       stdin: false,
+      stdin_batch: false,
+      files_from: None,
+      files_from0: None,
       globs: vec!["*.{rs".to_string()],
+      no_glob_normalization: false,
+      file_type: vec![],
+      type_not: vec![],
+      type_list: false,
+      lang_glob: vec![],
+      no_shebang_detection: false,
       threads: 0,
+      force_exclude: false,
+      max_file_size: None,
+      skip_minified: false,
+      minified_line_threshold: 500,
     };
     assert!(input.build_globs().is_err());
   }
+
+  #[test]
+  fn test_walk_hidden() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    std::fs::write(dir.path().join("a.ts"), "")?;
+    std::fs::write(dir.path().join(".b.ts"), "")?;
+    let walk_names = |no_ignore: Vec<IgnoreFile>, hidden: bool| -> Result<Vec<String>> {
+      let input = InputArgs {
+        paths: vec![dir.path().to_path_buf()],
+        follow: false,
+        one_file_system: false,
+        no_ignore,
+        hidden,
+        ignore_file: vec![],
+        stdin: false,
+        stdin_batch: false,
+        files_from: None,
+        files_from0: None,
+        globs: vec![],
+        no_glob_normalization: false,
+        file_type: vec![],
+        type_not: vec![],
+        type_list: false,
+        lang_glob: vec![],
+        no_shebang_detection: false,
+        threads: 1,
+        force_exclude: false,
+        max_file_size: None,
+        skip_minified: false,
+        minified_line_threshold: 500,
+      };
+      let names = std::sync::Mutex::new(vec![]);
+      input.walk()?.run(|| {
+        Box::new(|entry| {
+          let entry = entry.expect("should walk");
+          if entry.file_type().map_or(false, |t| t.is_file()) {
+            let name = entry.file_name().to_string_lossy().to_string();
+            names.lock().expect("should lock").push(name);
+          }
+          ignore::WalkState::Continue
+        })
+      });
+      let mut names = names.into_inner()?;
+      names.sort();
+      Ok(names)
+    };
+    assert_eq!(walk_names(vec![], false)?, ["a.ts"]);
+    assert_eq!(walk_names(vec![], true)?, [".b.ts", "a.ts"]);
+    assert_eq!(
+      walk_names(vec![IgnoreFile::Hidden], false)?,
+      [".b.ts", "a.ts"]
+    );
+    // --hidden combines with other --no-ignore values
+    let no_ignore = vec![IgnoreFile::Hidden, IgnoreFile::Dot];
+    assert_eq!(walk_names(no_ignore, true)?, [".b.ts", "a.ts"]);
+    Ok(())
+  }
+
+  #[test]
+  fn test_force_exclude() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    for name in ["a.ts", "b.ts", "c.ts"] {
+      std::fs::write(dir.path().join(name), "")?;
+    }
+    std::fs::write(dir.path().join(".ignore"), "b.ts")?;
+    let excluder = ForceExclude {
+      globs: Override::empty(),
+      no_ignore: NoIgnore::default(),
+      follow: false,
+      listed: Mutex::default(),
+    };
+    assert!(!excluder.is_excluded(&dir.path().join("a.ts")));
+    assert!(excluder.is_excluded(&dir.path().join("b.ts")));
+    assert!(!excluder.is_excluded(&dir.path().join("c.ts")));
+    // the directory is listed once for all its files
+    assert_eq!(excluder.listed.lock().unwrap().len(), 1);
+    Ok(())
+  }
+
+  #[test]
+  fn test_normalize_glob() {
+    assert_eq!(normalize_glob("src\\**\\*.ts", true), "src/**/*.ts");
+    assert_eq!(normalize_glob("src\\**\\*.ts", false), "src\\**\\*.ts");
+    assert!(matches!(normalize_glob("src/*.ts", true), Cow::Borrowed(_)));
+  }
+
+  #[test]
+  #[cfg(not(windows))]
+  fn test_backslash_warning() {
+    let warning = backslash_warning("src\\*.ts").expect("should warn");
+    assert!(warning.contains("Glob `src\\*.ts` contains backslashes"));
+    assert!(backslash_warning("src/*.ts").is_none());
+  }
+
+  #[test]
+  #[cfg(windows)]
+  fn test_windows_globs() {
+    let input = |no_glob_normalization| InputArgs {
+      paths: vec![],
+      follow: false,
+      one_file_system: false,
+      no_ignore: vec![],
+      hidden: false,
+      ignore_file: vec![],
+      stdin: false,
+      stdin_batch: false,
+      files_from: None,
+      files_from0: None,
+      globs: vec!["src\\**\\*.ts".to_string()],
+      no_glob_normalization,
+      file_type: vec![],
+      type_not: vec![],
+      type_list: false,
+      lang_glob: vec![],
+      no_shebang_detection: false,
+      threads: 0,
+      force_exclude: false,
+      max_file_size: None,
+      skip_minified: false,
+      minified_line_threshold: 500,
+    };
+    assert!(backslash_warning("src\\*.ts").is_none());
+    let globs = input(false).build_globs().expect("should build");
+    assert!(globs.matched("src\\a\\b.ts", false).is_whitelist());
+    assert!(globs.matched("src/a/b.ts", false).is_whitelist());
+    let globs = input(true).build_globs().expect("should build");
+    assert!(!globs.matched("src\\a\\b.ts", false).is_whitelist());
+  }
+
+  #[test]
+  fn test_parse_path_list() {
+    let paths = parse_path_list("a.ts\r\nsrc/b.ts\n\nc d.ts\n", '\n');
+    assert_eq!(paths, ["a.ts", "src/b.ts", "c d.ts"].map(PathBuf::from));
+    let paths = parse_path_list("a.ts\0with\nnewline.ts\0", '\0');
+    assert_eq!(paths, ["a.ts", "with\nnewline.ts"].map(PathBuf::from));
+  }
+
+  #[test]
+  fn test_dedupe_paths() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let root = dir.path();
+    std::fs::create_dir_all(root.join("src/module"))?;
+    let src = root.join("src");
+    let module = root.join("src/module");
+    let dot_src = root.join("./src/../src");
+    let paths = [module.clone(), src.clone(), dot_src, root.join("src-2")];
+    let no_ignore = NoIgnore::default();
+    let deduped = dedupe_paths(&paths, false, &no_ignore);
+    assert_eq!(deduped, [src.clone(), root.join("src-2")]);
+    // files skipped by the walk of src are searched on their own
+    std::fs::create_dir_all(root.join("src/.hidden"))?;
+    std::fs::write(root.join("src/.ignore"), "dist/\n")?;
+    std::fs::create_dir_all(root.join("src/dist"))?;
+    let hidden = root.join("src/.hidden");
+    let dist = root.join("src/dist");
+    let paths = [src.clone(), hidden.clone(), dist.clone(), module.clone()];
+    let deduped = dedupe_paths(&paths, false, &no_ignore);
+    assert_eq!(deduped, [src.clone(), hidden.clone(), dist.clone()]);
+    let all = NoIgnore::disregard(&[IgnoreFile::All], false);
+    assert_eq!(dedupe_paths(&paths, false, &all), &paths[..1]);
+    #[cfg(unix)]
+    {
+      let link = root.join("link");
+      std::os::unix::fs::symlink(&module, &link)?;
+      let paths = [src.clone(), link.clone()];
+      assert_eq!(dedupe_paths(&paths, false, &no_ignore), paths);
+      assert_eq!(dedupe_paths(&paths, true, &no_ignore), [src]);
+    }
+    Ok(())
+  }
+
+  #[test]
+  fn test_dedupe() {
+    let ranges = vec![4..8, 0..10, 0..10, 12..14, 2..5];
+    let none = Dedupe::None.apply(ranges.clone(), |r| r.clone());
+    assert_eq!(none, ranges);
+    let exact = Dedupe::Exact.apply(ranges.clone(), |r| r.clone());
+    assert_eq!(exact, vec![0..10, 2..5, 4..8, 12..14]);
+    let contains = Dedupe::Contains.apply(ranges, |r| r.clone());
+    assert_eq!(contains, vec![0..10, 12..14]);
+  }
+
+  #[test]
+  fn test_parse_file_size() {
+    assert_eq!(parse_file_size("123"), Ok(123));
+    assert_eq!(parse_file_size("500K"), Ok(500 * 1024));
+    assert_eq!(parse_file_size("2m"), Ok(2 * 1024 * 1024));
+    assert_eq!(parse_file_size("1G"), Ok(1 << 30));
+    assert!(parse_file_size("").is_err());
+    assert!(parse_file_size("K").is_err());
+    assert!(parse_file_size("12T").is_err());
+    assert!(parse_file_size("1.5M").is_err());
+  }
+
+  #[test]
+  fn test_parse_lang_glob() {
+    let parsed = parse_lang_glob("groovy:Jenkinsfile");
+    assert_eq!(parsed, Ok(("groovy".into(), "Jenkinsfile".into())));
+    let parsed = parse_lang_glob("ts:src/*.mts.snap");
+    assert_eq!(parsed, Ok(("ts".into(), "src/*.mts.snap".into())));
+    assert!(parse_lang_glob("Jenkinsfile").is_err());
+    assert!(parse_lang_glob(":*.ts").is_err());
+    assert!(parse_lang_glob("ts:").is_err());
+  }
+
+  #[test]
+  fn test_parse_separator() {
+    assert_eq!(parse_separator("--"), Ok("--".into()));
+    assert_eq!(parse_separator(""), Ok("".into()));
+    assert_eq!(parse_separator("\\t"), Ok("\t".into()));
+    assert_eq!(parse_separator("a\\0b"), Ok("a\0b".into()));
+    assert_eq!(parse_separator("\\\\n"), Ok("\\n".into()));
+    assert!(parse_separator("\\x").is_err());
+    assert!(parse_separator("a\\").is_err());
+  }
 }
@@ -4,8 +4,12 @@ use ansi_term::Style;
 use ast_grep_core::{language::TSLanguage, matcher::PatternNode, meta_var::MetaVariable, Pattern};
 use ast_grep_language::Language;
 use clap::ValueEnum;
+use serde::Serialize;
 use tree_sitter as ts;
 
+use std::collections::BTreeSet;
+use std::ops::Range;
+
 #[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
 pub enum DebugFormat {
   /// Print the query parsed in Pattern format
@@ -94,11 +98,16 @@ fn dump_pattern(
   Ok(())
 }
 
+#[derive(Serialize)]
 pub struct DumpNode {
+  #[serde(skip_serializing_if = "Option::is_none")]
   field: Option<String>,
   kind: String,
   start: Pos,
   end: Pos,
+  /// byte offsets of the node in source
+  range: Range<usize>,
+  #[serde(skip)]
   is_named: bool,
   children: Vec<DumpNode>,
 }
@@ -106,6 +115,7 @@ pub struct DumpNode {
 struct DumpFmt {
   kind_style: Style,
   field_style: Style,
+  pos_style: Style,
   named_only: bool,
 }
 
@@ -115,6 +125,7 @@ impl DumpFmt {
     Self {
       kind_style: if colored { style.bold() } else { style },
       field_style: if colored { style.italic() } else { style },
+      pos_style: if colored { style.dimmed() } else { style },
       named_only: true,
     }
   }
@@ -123,6 +134,7 @@ impl DumpFmt {
     Self {
       kind_style: if colored { style.bold() } else { style },
       field_style: if colored { style.italic() } else { style },
+      pos_style: if colored { style.dimmed() } else { style },
       named_only: false,
     }
   }
@@ -159,15 +171,62 @@ impl DumpNode {
       write!(result, "{}: ", field)?;
     }
     write!(result, "{}", fmt.kind_style.paint(&self.kind))?;
-    writeln!(result, " ({:?})-({:?})", self.start, self.end)?;
+    let pos = format!("({:?})-({:?})", self.start, self.end);
+    writeln!(result, " {}", fmt.pos_style.paint(pos))?;
     for child in &self.children {
       child.helper(result, fmt, depth + 1)?;
     }
     Ok(())
   }
+
+  /// Remove unnamed nodes from the tree.
+  pub fn retain_named(&mut self) {
+    self.children.retain(|c| c.is_named);
+    for child in &mut self.children {
+      child.retain_named();
+    }
+  }
+
+  /// Find the innermost named node whose byte range covers `range`.
+  pub fn enclosing(mut self, range: &Range<usize>) -> Option<DumpNode> {
+    if !self.covers(range) {
+      return None;
+    }
+    let inner = self
+      .children
+      .iter()
+      .position(|c| c.is_named && c.covers(range));
+    match inner {
+      Some(i) => self.children.swap_remove(i).enclosing(range),
+      None => Some(self),
+    }
+  }
+
+  fn covers(&self, range: &Range<usize>) -> bool {
+    self.range.start <= range.start && range.end <= self.range.end
+  }
+
+  /// Unique kinds of named nodes in the tree, in alphabetical order.
+  pub fn kinds(&self) -> BTreeSet<&str> {
+    let mut kinds = BTreeSet::new();
+    self.collect_kinds(&mut kinds);
+    kinds
+  }
+
+  fn collect_kinds<'a>(&'a self, kinds: &mut BTreeSet<&'a str>) {
+    if !self.is_named {
+      return;
+    }
+    kinds.insert(&self.kind);
+    for child in &self.children {
+      child.collect_kinds(kinds);
+    }
+  }
 }
 
+#[derive(Serialize)]
 pub struct Pos {
+  #[serde(rename = "line")]
   row: u32,
   column: u32,
 }
@@ -188,7 +247,7 @@ impl From<ts::Point> for Pos {
   }
 }
 
-fn dump_node(node: ts::Node) -> DumpNode {
+pub fn dump_node(node: ts::Node) -> DumpNode {
   let mut cursor = node.walk();
   let mut nodes = vec![];
   dump_one_node(&mut cursor, &mut nodes);
@@ -204,6 +263,7 @@ fn dump_one_node(cursor: &mut ts::TreeCursor, target: &mut Vec<DumpNode>) {
   };
   let start = node.start_position().into();
   let end = node.end_position().into();
+  let range = node.start_byte() as usize..node.end_byte() as usize;
   let field = cursor.field_name().map(|c| c.to_string());
   let mut children = vec![];
   if cursor.goto_first_child() {
@@ -215,6 +275,7 @@ fn dump_one_node(cursor: &mut ts::TreeCursor, target: &mut Vec<DumpNode>) {
     kind,
     start,
     end,
+    range,
     children,
     is_named: node.is_named(),
   })
@@ -247,6 +308,36 @@ program (0,0)-(0,11)
     assert_eq!(DUMPED.trim(), dumped.ast(false).trim());
   }
 
+  #[test]
+  fn test_enclosing_node() {
+    let lang = SgLang::Builtin(TypeScript.into());
+    let root = lang.ast_grep("var a = 123");
+    let dumped = dump_node(root.root().get_ts_node());
+    let node = dumped.enclosing(&(9..10)).expect("should find");
+    assert_eq!(node.ast(false).trim(), "value: number (0,8)-(0,11)");
+    let dumped = dump_node(root.root().get_ts_node());
+    let node = dumped.enclosing(&(4..9)).expect("should find");
+    assert!(node.ast(false).starts_with("variable_declarator"));
+    let dumped = dump_node(root.root().get_ts_node());
+    assert!(dumped.enclosing(&(5..20)).is_none());
+  }
+
+  #[test]
+  fn test_kinds() {
+    let lang = SgLang::Builtin(TypeScript.into());
+    let root = lang.ast_grep("var a = 123; var b = a");
+    let dumped = dump_node(root.root().get_ts_node());
+    let kinds: Vec<_> = dumped.kinds().into_iter().collect();
+    let expected = [
+      "identifier",
+      "number",
+      "program",
+      "variable_declaration",
+      "variable_declarator",
+    ];
+    assert_eq!(kinds, expected);
+  }
+
   const MISSING: &str = r#"
 translation_unit (0,0)-(0,9)
   declaration (0,0)-(0,9)
@@ -0,0 +1,179 @@
+//! Read and write source files with a byte order mark (BOM).
+//! UTF-16 files are transcoded to UTF-8 before parsing, so positions refer to the decoded text.
+//! A UTF-8 BOM is kept in the text, so byte offsets are the same as in the file.
+//! tree-sitter skips the BOM when parsing.
+//! Rewritten files are encoded back with the BOM of the file they overwrite.
+use anyhow::{anyhow, Result};
+
+use std::fmt;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+const UTF8_BOM: &[u8] = &[0xEF, 0xBB, 0xBF];
+const UTF16LE_BOM: &[u8] = &[0xFF, 0xFE];
+const UTF16BE_BOM: &[u8] = &[0xFE, 0xFF];
+
+/// The encoding of a source file, detected by its BOM.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Encoding {
+  Utf8,
+  Utf8Bom,
+  Utf16Le,
+  Utf16Be,
+}
+
+impl Encoding {
+  pub fn detect(bytes: &[u8]) -> Self {
+    if bytes.starts_with(UTF8_BOM) {
+      Self::Utf8Bom
+    } else if bytes.starts_with(UTF16LE_BOM) {
+      Self::Utf16Le
+    } else if bytes.starts_with(UTF16BE_BOM) {
+      Self::Utf16Be
+    } else {
+      Self::Utf8
+    }
+  }
+
+  fn bom(&self) -> &'static [u8] {
+    match self {
+      Self::Utf8 => &[],
+      Self::Utf8Bom => UTF8_BOM,
+      Self::Utf16Le => UTF16LE_BOM,
+      Self::Utf16Be => UTF16BE_BOM,
+    }
+  }
+
+  /// Decode the bytes, dropping a UTF-16 BOM. A UTF-8 BOM is kept as U+FEFF.
+  /// Returns None if the bytes are not valid.
+  pub fn decode(&self, bytes: &[u8]) -> Option<String> {
+    let to_unit = match self {
+      Self::Utf8 | Self::Utf8Bom => return String::from_utf8(bytes.to_vec()).ok(),
+      Self::Utf16Le => u16::from_le_bytes,
+      Self::Utf16Be => u16::from_be_bytes,
+    };
+    let bytes = &bytes[self.bom().len()..];
+    if bytes.len() % 2 != 0 {
+      return None;
+    }
+    let units = bytes.chunks_exact(2).map(|b| to_unit([b[0], b[1]]));
+    char::decode_utf16(units).collect::<Result<_, _>>().ok()
+  }
+
+  /// Encode the text and prepend the BOM, unless the text keeps it from [Self::decode].
+  pub fn encode(&self, text: &str) -> Vec<u8> {
+    let text = text.strip_prefix('\u{feff}').unwrap_or(text);
+    let mut bytes = self.bom().to_vec();
+    match self {
+      Self::Utf8 | Self::Utf8Bom => bytes.extend_from_slice(text.as_bytes()),
+      Self::Utf16Le => bytes.extend(text.encode_utf16().flat_map(u16::to_le_bytes)),
+      Self::Utf16Be => bytes.extend(text.encode_utf16().flat_map(u16::to_be_bytes)),
+    }
+    bytes
+  }
+}
+
+impl fmt::Display for Encoding {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let name = match self {
+      Self::Utf8 => "UTF-8",
+      Self::Utf8Bom => "UTF-8 with BOM",
+      Self::Utf16Le => "UTF-16LE",
+      Self::Utf16Be => "UTF-16BE",
+    };
+    write!(f, "{name}")
+  }
+}
+
+/// The file content is not valid in the detected encoding.
+#[derive(Debug)]
+pub struct DecodeError(pub Encoding);
+
+impl fmt::Display for DecodeError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "file is not valid {}", self.0)
+  }
+}
+
+impl std::error::Error for DecodeError {}
+
+/// Read a source file as UTF-8 text. The error is [DecodeError] if it cannot be decoded.
+pub fn read_source(path: &Path) -> Result<String> {
+  let bytes = std::fs::read(path)?;
+  let encoding = Encoding::detect(&bytes);
+  encoding
+    .decode(&bytes)
+    .ok_or_else(|| anyhow!(DecodeError(encoding)))
+}
+
+/// Write the text to a source file, keeping the encoding of the file it overwrites.
+pub fn write_source(path: &Path, content: &str) -> Result<()> {
+  let mut bom = Vec::with_capacity(UTF8_BOM.len());
+  if let Ok(file) = File::open(path) {
+    file.take(UTF8_BOM.len() as u64).read_to_end(&mut bom)?;
+  }
+  let encoding = Encoding::detect(&bom);
+  std::fs::write(path, encoding.encode(content))?;
+  Ok(())
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  #[test]
+  fn test_round_trip() {
+    let text = "let a = '中文'; // 🦀\r\n";
+    for encoding in [
+      Encoding::Utf8,
+      Encoding::Utf8Bom,
+      Encoding::Utf16Le,
+      Encoding::Utf16Be,
+    ] {
+      let bytes = encoding.encode(text);
+      assert_eq!(Encoding::detect(&bytes), encoding);
+      let decoded = encoding.decode(&bytes).expect("should decode");
+      assert_eq!(decoded.trim_start_matches('\u{feff}'), text);
+      assert_eq!(encoding.encode(&decoded), bytes);
+    }
+  }
+
+  #[test]
+  fn test_keep_utf8_bom() {
+    let bytes = Encoding::Utf8Bom.encode("a");
+    assert_eq!(bytes, b"\xEF\xBB\xBFa");
+    // byte offsets of the decoded text are the same as in the file
+    assert_eq!(
+      Encoding::Utf8Bom.decode(&bytes).as_deref(),
+      Some("\u{feff}a")
+    );
+    assert_eq!(
+      Encoding::Utf16Le.decode(&[0xFF, 0xFE, 0x61, 0]).as_deref(),
+      Some("a")
+    );
+  }
+
+  #[test]
+  fn test_invalid_utf16() {
+    // odd number of bytes
+    assert_eq!(Encoding::Utf16Le.decode(&[0xFF, 0xFE, 0x61]), None);
+    // unpaired surrogate
+    assert_eq!(Encoding::Utf16Le.decode(&[0xFF, 0xFE, 0x00, 0xD8]), None);
+    assert_eq!(Encoding::Utf8.decode(&[0xC3]), None);
+  }
+
+  #[test]
+  fn test_write_keeps_encoding() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("a.ts");
+    std::fs::write(&path, Encoding::Utf16Le.encode("Some(1)"))?;
+    assert_eq!(read_source(&path)?, "Some(1)");
+    write_source(&path, "1")?;
+    assert_eq!(std::fs::read(&path)?, Encoding::Utf16Le.encode("1"));
+    std::fs::write(&path, [0xFF, 0xFE, 0x61])?;
+    let err = read_source(&path).expect_err("should fail");
+    assert_eq!(err.to_string(), "file is not valid UTF-16LE");
+    Ok(())
+  }
+}
@@ -1,7 +1,11 @@
 use ansi_term::{Color, Style};
 use anyhow::{Error, Result};
 
+use super::SeverityLevel;
+use crate::print::{ColorArg, ColorChoice, ColorStream};
+
 use std::fmt;
+use std::io::ErrorKind;
 use std::path::PathBuf;
 
 const DOC_SITE_HOST: &str = "https://ast-grep.github.io";
@@ -25,6 +29,7 @@ pub enum ErrorContext {
   // Config
   ReadConfiguration,
   ParseConfiguration,
+  RegisterCustomLanguage,
   WalkRuleDir(PathBuf),
   ReadRule(PathBuf),
   ParseRule(PathBuf),
@@ -32,7 +37,11 @@ pub enum ErrorContext {
   InvalidGlobalUtils,
   GlobPattern,
   BuildGlobs,
+  InvalidIgnoreFile(PathBuf),
+  /// the file type given to --type or --type-not, and the closest defined type
+  UnknownFileType(String, Option<String>),
   UnrecognizableLanguage(String),
+  UnknownFileLanguage(PathBuf),
   LangInjection,
   // Run
   ParsePattern,
@@ -41,15 +50,38 @@ pub enum ErrorContext {
   PatternHasError,
   // Scan
   DiagnosticError(usize),
+  /// severity, finding count and exit code set by --exit-code-for
+  DiagnosticExitCode(SeverityLevel, usize, u8),
   RuleNotSpecified,
+  /// report style that cannot print a custom --field-separator
+  FieldSeparatorUnsupported(&'static str),
   RuleNotFound(String),
+  /// rule ids defined more than once, with their locations
+  DuplicateRuleId(Vec<(String, Vec<String>)>),
+  /// problem count reported by --check-rules
+  InvalidRules(usize),
+  /// languages of loaded rules and languages of scanned files
+  NoApplicableRule(String, String),
   // LSP
   StartLanguageServer,
+  /// the TCP address or socket path and the reason of the failure
+  ListenLanguageServer(String, ErrorKind),
   // Edit
   OpenEditor,
+  /// the pager command
+  StartPager(String),
+  /// pager command and its exit status
+  PagerFailed(String, String),
   WriteFile(PathBuf),
   // Test
   TestFail(String),
+  TestNotInteractive,
+  NoTestMatched(usize),
+  UncoveredRules(usize),
+  /// rule id given to `sg test` without test config, and the closest test id
+  TestIdNotFound(String, Option<String>),
+  /// test id without loaded rule, its test file and the closest rule id
+  UnknownTestIds(Vec<(String, String, Option<String>)>),
   // New
   ProjectAlreadyExist,
   ProjectNotExist,
@@ -59,27 +91,46 @@ pub enum ErrorContext {
   InsufficientCLIArgument(&'static str),
   // Completions
   CannotInferShell,
+  // Dump AST
+  ReadFile(PathBuf),
+  /// number of files and directories that cannot be read, see --strict-io
+  UnreadableFiles(usize),
+  /// the `--lines` or `--bytes` range outside of the file
+  InvalidRange(String),
 }
 
 impl ErrorContext {
-  fn exit_code(&self) -> i32 {
+  pub(crate) fn exit_code(&self) -> i32 {
     use ErrorContext::*;
     // reference: https://mariadb.com/kb/en/operating-system-error-codes/
     match self {
       DiagnosticError(_) => 1,
-      ProjectNotExist | LanguageNotSpecified | RuleNotSpecified | RuleNotFound(_) => 2,
-      TestFail(_) => 3,
+      DiagnosticExitCode(_, _, code) => *code as i32,
+      ProjectNotExist | LanguageNotSpecified | RuleNotSpecified | RuleNotFound(_)
+      | NoApplicableRule(..) => 2,
+      TestFail(_) | NoTestMatched(_) | UncoveredRules(_) | TestIdNotFound(..)
+      | UnknownTestIds(_) => 3,
       NoTestDirConfigured | NoUtilDirConfigured => 4,
-      ReadConfiguration | ReadRule(_) | WalkRuleDir(_) | WriteFile(_) => 5,
-      StdInIsNotInteractive => 6,
+      ReadConfiguration
+      | ReadRule(_)
+      | WalkRuleDir(_)
+      | WriteFile(_)
+      | ReadFile(_)
+      | UnreadableFiles(_)
+      | RegisterCustomLanguage => 5,
+      StdInIsNotInteractive | TestNotInteractive => 6,
       ParseTest(_) | ParseRule(_) | ParseConfiguration | ParsePattern | InvalidGlobalUtils
-      | LangInjection => 8,
-      GlobPattern | BuildGlobs => 9,
+      | LangInjection | InvalidRules(_) | DuplicateRuleId(_) => 8,
+      GlobPattern | BuildGlobs | InvalidIgnoreFile(_) | UnknownFileType(..) => 9,
       CannotInferShell => 10,
       ProjectAlreadyExist | FileAlreadyExist(_) => 17,
-      InsufficientCLIArgument(_) => 22,
-      UnrecognizableLanguage(_) => 33,
-      OpenEditor | StartLanguageServer => 126,
+      InsufficientCLIArgument(_) | InvalidRange(_) | FieldSeparatorUnsupported(_) => 22,
+      UnrecognizableLanguage(_) | UnknownFileLanguage(_) => 33,
+      OpenEditor
+      | StartPager(_)
+      | PagerFailed(..)
+      | StartLanguageServer
+      | ListenLanguageServer(..) => 126,
       // soft error
       PatternHasError => 0,
     }
@@ -130,6 +181,11 @@ impl ErrorMessage {
         "The sgconfig.yml is not a valid configuration file. Please refer to doc and fix the error.",
         CONFIG_REFERENCE,
       ),
+      RegisterCustomLanguage => Self::new(
+        "Cannot load custom languages.",
+        "Please check `libraryPath` and `languageSymbol` of `customLanguages` in sgconfig.yml. The library must be a tree-sitter parser compiled for this platform.",
+        CONFIG_REFERENCE,
+      ),
       WalkRuleDir(dir) => Self::new(
         format!("Cannot read rule directory {}", dir.display()),
         "The rule directory cannot be read or traversed",
@@ -155,6 +211,19 @@ impl ErrorMessage {
         "The patterns in --globs is invalid. Please refer to doc and fix the error.",
         CLI_USAGE,
       ),
+      InvalidIgnoreFile(file) => Self::new(
+        format!("Cannot read ignore file {}", file.display()),
+        "The file in --ignore-file must exist and contain valid gitignore patterns.",
+        CLI_USAGE,
+      ),
+      UnknownFileType(name, suggestion) => Self::new(
+        format!("File type `{name}` is not defined."),
+        match suggestion {
+          Some(s) => format!("Did you mean `{s}`? Use `--type-list` to see all file types."),
+          None => "Use `--type-list` to see all file types.".to_string(),
+        },
+        CLI_USAGE,
+      ),
       LangInjection => Self::new(
         "Cannot parse languageInjections in config",
         "The rule in languageInjections is not valid. Please refer to doc and fix the error.",
@@ -170,6 +239,11 @@ impl ErrorMessage {
         "Please choose a built-in language or register a custom language in sgconfig.yml.",
         LANGUAGE_LIST,
       ),
+      UnknownFileLanguage(path) => Self::new(
+        format!("Cannot infer language from `{}`.", path.display()),
+        "Please use a file extension of a supported language or configure `languageGlobs` in sgconfig.yml.",
+        LANGUAGE_LIST,
+      ),
       ParseTest(file) => Self::new(
         format!("Cannot parse test case {}", file.display()),
         "The file is not a valid ast-grep test case. Please refer to doc and fix the error.",
@@ -180,6 +254,14 @@ impl ErrorMessage {
         "Scan succeeded and found error level diagnostics in the codebase.",
         None,
       ),
+      DiagnosticExitCode(severity, num, code) => Self::new(
+        format!("{num} {}(s) found in code.", severity.as_str()),
+        format!(
+          "Scan succeeded and exit code {code} is determined by {} findings, see `--exit-code-for`.",
+          severity.as_str()
+        ),
+        None,
+      ),
       ParsePattern => Self::new(
         "Cannot parse query as a valid pattern.",
         "The pattern either fails to parse or contains error. Please refer to pattern syntax guide.",
@@ -205,21 +287,92 @@ impl ErrorMessage {
         "Please use `--rule path/to/rule.yml` to choose the rule.",
         TOOL_OVERVIEW,
       ),
+      FieldSeparatorUnsupported(style) => Self::new(
+        format!("`--field-separator` cannot be used with `--report-style={style}`."),
+        "Short and medium reports always separate the path, line and column by `:`. Please use the rich report style or `--json`.",
+        CLI_USAGE,
+      ),
+      NoApplicableRule(rule_langs, file_langs) => Self::new(
+        "No rule applies to the scanned files.",
+        format!("Loaded rules target {rule_langs} but scanned files are {file_langs}. Please check rule languages and flags like `--filter` that exclude rules."),
+        None,
+      ),
       RuleNotFound(id) => Self::new(
         format!("Rule not found: {}", id),
         format!("Rule with id '{id}' not found in project configuration. Please make sure it exists."),
         TOOL_OVERVIEW,
       ),
+      DuplicateRuleId(duplicates) => {
+        let ids: Vec<_> = duplicates.iter().map(|(id, _)| format!("`{id}`")).collect();
+        let mut description = String::new();
+        for (id, locations) in duplicates {
+          description.push_str(&format!("Rule `{id}` is defined at:\n"));
+          for location in locations {
+            description.push_str(&format!("  {location}\n"));
+          }
+        }
+        description.push_str("Please use unique rule ids or pass `--allow-duplicate-rules last-wins` to keep the last loaded rule.");
+        Self::new(
+          format!("Duplicate rule id {} found.", ids.join(", ")),
+          description,
+          CONFIG_GUIDE,
+        )
+      }
+      InvalidRules(num) => Self::new(
+        format!("{num} problem(s) found in rules."),
+        "Rule check failed. Please fix the problems reported above.",
+        CONFIG_GUIDE,
+      ),
       StartLanguageServer => Self::new(
         "Cannot start language server.",
         "Please see language server logging file.",
         EDITOR_INTEGRATION,
       ),
+      ListenLanguageServer(addr, ErrorKind::AddrInUse) => Self::new(
+        format!("Address {addr} is already in use."),
+        "Please stop the process using it, choose another `--port`, or remove the stale socket file.",
+        EDITOR_INTEGRATION,
+      ),
+      ListenLanguageServer(addr, ErrorKind::PermissionDenied) => Self::new(
+        format!("Permission denied to listen on {addr}."),
+        "Please choose a port or socket path that the current user is allowed to use.",
+        EDITOR_INTEGRATION,
+      ),
+      ListenLanguageServer(addr, _) => Self::new(
+        format!("Cannot listen on {addr}."),
+        "Please check the `--port` or `--socket` argument of the language server.",
+        EDITOR_INTEGRATION,
+      ),
       OpenEditor => Self::new(
         "Cannot open file in editor.",
         "Please check if the editor is installed and the EDITOR environment variable is correctly set.",
         CLI_USAGE,
       ),
+      StartPager(cmd) => Self::new(
+        format!("Cannot start pager `{cmd}`."),
+        "Please check the `--pager` argument or the PAGER environment variable.",
+        CLI_USAGE,
+      ),
+      PagerFailed(cmd, status) => Self::new(
+        format!("Pager `{cmd}` failed with {status}."),
+        "Please check the `--pager` argument or the PAGER environment variable.",
+        CLI_USAGE,
+      ),
+      ReadFile(file) => Self::new(
+        format!("Cannot read file {}", file.display()),
+        "The file either does not exist or cannot be opened.",
+        None,
+      ),
+      UnreadableFiles(count) => Self::new(
+        format!("{count} file(s) could not be read."),
+        "Files that cannot be read fail the run because of `--strict-io`. Their errors are printed unless `--no-messages` is set.",
+        CLI_USAGE,
+      ),
+      InvalidRange(range) => Self::new(
+        format!("No node found in {range}."),
+        "The range is outside of the file. Please check the `--lines` or `--bytes` argument.",
+        CLI_USAGE,
+      ),
       WriteFile(file) => Self::new(
         format!("Cannot rewrite file {}", file.display()),
         "Fail to apply fix to the file. Skip to next file",
@@ -230,6 +383,45 @@ impl ErrorMessage {
         "You can use ast-grep playground to debug your rules and test cases.",
         PLAYGROUND,
       ),
+      NoTestMatched(filtered_out) => Self::new(
+        format!("No test matches the filter. {filtered_out} filtered out."),
+        "`--filter` matches rule ids by regex and `--case` matches test code by substring. Please check if they are correct.",
+        TEST_GUIDE,
+      ),
+      TestIdNotFound(id, suggestion) => Self::new(
+        format!("No test config found for rule `{id}`."),
+        match suggestion {
+          Some(s) => format!("Did you mean `{s}`? Rule ids given to `sg test` must match test config ids exactly."),
+          None => "Rule ids given to `sg test` must match test config ids exactly.".to_string(),
+        },
+        TEST_GUIDE,
+      ),
+      UnknownTestIds(unknown) => {
+        let mut description = String::new();
+        for (id, path, suggestion) in unknown {
+          description.push_str(&format!("Test `{id}` in {path} has no rule."));
+          if let Some(s) = suggestion {
+            description.push_str(&format!(" Did you mean `{s}`?"));
+          }
+          description.push('\n');
+        }
+        description.push_str("Please fix the test ids or pass `--allow-unused-tests` to only warn about them.");
+        Self::new(
+          format!("{} test config(s) refer to unknown rules.", unknown.len()),
+          description,
+          TEST_GUIDE,
+        )
+      }
+      UncoveredRules(count) => Self::new(
+        format!("{count} rule(s) have no test case."),
+        "`--error-on-uncovered` requires every rule to have at least one valid or invalid test case.",
+        TEST_GUIDE,
+      ),
+      TestNotInteractive => Self::new(
+        "Interactive snapshot review requires a terminal.",
+        "`sg test --interactive` cannot prompt without a TTY. Please use `sg test --update-snapshots` to accept all snapshot changes instead.",
+        TEST_GUIDE,
+      ),
       ProjectAlreadyExist => Self::new(
         "ast-grep project already exists.",
         "You are already inside a sub-folder of an ast-grep project. Try finding sgconfig.yml in ancestor directory?",
@@ -242,7 +434,7 @@ impl ErrorMessage {
       ),
       FileAlreadyExist(path) => Self::new(
         format!("File `{}` already exists.", path.display()),
-        "The item you want to create already exists. Try editing the existing file or create a new one with a different name?",
+        "The item you want to create already exists. Try editing the existing file, create a new one with a different name, or pass `--force` to overwrite it.",
         None,
       ),
       NoTestDirConfigured => Self::new(
@@ -256,7 +448,7 @@ impl ErrorMessage {
         UTIL_GUIDE,
       ),
       InsufficientCLIArgument(name) => Self::new(
-        "Insufficient command line argument provided to use `--yes` option.",
+        "Insufficient command line argument provided to run `new` non-interactively.",
         format!("You need to provide `{name}` in command line to use non-interactive `new`."),
         None,
       ),
@@ -269,22 +461,35 @@ impl ErrorMessage {
   }
 }
 
-pub fn exit_with_error(error: Error) -> Result<()> {
+/// `color` is the `--color` of the failed command, or auto if arguments cannot be parsed.
+pub fn exit_with_error(error: Error, color: ColorArg) -> Result<()> {
   if let Some(e) = error.downcast_ref::<clap::Error>() {
     e.exit()
   }
   if let Some(e) = error.downcast_ref::<ErrorContext>() {
-    let error_fmt = ErrorFormat {
-      context: e,
-      inner: &error,
-    };
-    eprintln!("{error_fmt}");
+    print_error(&error, color);
     std::process::exit(e.exit_code())
   }
   // use anyhow's default error reporting
   Err(error)
 }
 
+/// Print the error to stderr without exiting, for commands that keep running after errors.
+pub fn print_error(error: &Error, color: ColorArg) {
+  if let Some(context) = error.downcast_ref::<ErrorContext>() {
+    // errors go to stderr, which may be a terminal even if stdout is piped
+    let colored = color.resolve(ColorStream::Stderr) != ColorChoice::Never;
+    let error_fmt = ErrorFormat {
+      context,
+      inner: error,
+      colored,
+    };
+    eprintln!("{error_fmt}");
+  } else {
+    eprintln!("Error: {error:?}");
+  }
+}
+
 // use raw ansi escape code to render links in terminal. references:
 // https://gist.github.com/egmontkob/eb114294efbcd5adb1944c9f3cb5feda
 // https://github.com/zkat/miette/blob/c25676cb1f4266c2607836e6359f15b9cbd8637e/src/handlers/graphical.rs#L186
@@ -299,6 +504,7 @@ fn ansi_link(url: String) -> String {
 struct ErrorFormat<'a> {
   context: &'a ErrorContext,
   inner: &'a Error,
+  colored: bool,
 }
 
 impl<'a> fmt::Display for ErrorFormat<'a> {
@@ -308,19 +514,21 @@ impl<'a> fmt::Display for ErrorFormat<'a> {
       description,
       link,
     } = ErrorMessage::from_context(self.context);
+    let style = |style: Style| if self.colored { style } else { Style::new() };
     let (notice_style, notice, sign) = if self.context.is_soft_error() {
-      (Color::Yellow, "Warning:", "⚠")
+      (style(Color::Yellow.normal()), "Warning:", "⚠")
     } else {
-      (Color::Red, "Error:", "✖")
+      (style(Color::Red.normal()), "Error:", "✖")
     };
-    let bold = Style::new().bold();
+    let bold = style(Style::new().bold());
     let message = bold.paint(title);
     writeln!(f, "{} {message}", notice_style.paint(notice))?;
-    let help = Color::Blue.paint("Help:");
+    let help = style(Color::Blue.normal()).paint("Help:");
     writeln!(f, "{help} {description}")?;
     if let Some(url) = link {
-      let reference = Style::new().bold().dimmed().paint("See also:");
-      let link = ansi_link(format!("{DOC_SITE_HOST}{url}"));
+      let reference = style(Style::new().bold().dimmed()).paint("See also:");
+      let url = format!("{DOC_SITE_HOST}{url}");
+      let link = if self.colored { ansi_link(url) } else { url };
       writeln!(f, "{reference} {link}")?;
     }
 
@@ -349,6 +557,7 @@ mod test {
     let error_fmt = ErrorFormat {
       context: &ErrorContext::ReadConfiguration,
       inner: &error,
+      colored: true,
     };
     let display = format!("{error_fmt}");
     assert_eq!(display.lines().count(), 6);
@@ -362,12 +571,26 @@ mod test {
     assert!(display.contains('✖'));
   }
 
+  #[test]
+  fn test_display_error_without_color() {
+    let error = anyhow::anyhow!("test error").context(ErrorContext::ReadConfiguration);
+    let error_fmt = ErrorFormat {
+      context: &ErrorContext::ReadConfiguration,
+      inner: &error,
+      colored: false,
+    };
+    let display = format!("{error_fmt}");
+    assert!(display.starts_with("Error: Cannot read configuration."));
+    assert!(!display.contains('\u{1b}'), "{display:?}");
+  }
+
   #[test]
   fn test_display_warning() {
     let error = anyhow::anyhow!("test error");
     let error_fmt = ErrorFormat {
       context: &ErrorContext::PatternHasError,
       inner: &error,
+      colored: true,
     };
     let display = format!("{error_fmt}");
     assert_eq!(display.lines().count(), 3);
@@ -381,6 +604,7 @@ mod test {
     let error_fmt = ErrorFormat {
       context: &ErrorContext::ReadConfiguration,
       inner: &error,
+      colored: true,
     };
     let display = format!("{error_fmt}");
     assert_eq!(display.lines().count(), 3);
@@ -1,19 +1,31 @@
 mod args;
 mod debug_query;
+mod encoding;
 mod error_context;
+mod pager;
+mod prefilter;
 mod rule_overwrite;
+mod summary;
 mod tracing;
 mod worker;
 
-pub use args::{InputArgs, OutputArgs, SeverityArg};
-pub use debug_query::DebugFormat;
-pub use error_context::{exit_with_error, ErrorContext};
-pub use tracing::{FileTrace, RuleTrace, RunTrace, ScanTrace, Tracing};
-pub use worker::{Items, PathWorker, StdInWorker, Worker};
+pub use args::{
+  print_type_list, thread_count, Dedupe, InputArgs, NoIgnore, OutputArgs, SeverityArg,
+};
+pub use debug_query::{dump_node, DebugFormat, DumpNode};
+pub use encoding::{read_source, write_source, Encoding};
+pub use error_context::{exit_with_error, print_error, ErrorContext};
+pub use pager::Pager;
+pub use prefilter::Prefilter;
+pub use rule_overwrite::{DuplicateRules, RuleOverwrite, SeverityLevel};
+pub use summary::ScanSummary;
+pub use tracing::{FileTrace, PrefilterTrace, RuleTrace, RunTrace, ScanTrace, SkipReason, Tracing};
+pub use worker::{Items, PathWorker, StdInBatchWorker, StdInWorker, Worker};
 
 use crate::lang::SgLang;
+use prefilter::LiteralHits;
 
-use anyhow::{Context, Result};
+use anyhow::Result;
 use crossterm::{
   cursor::MoveTo,
   event::{self, Event, KeyCode},
@@ -27,7 +39,6 @@ use ast_grep_core::Pattern;
 use ast_grep_core::{Matcher, StrDoc};
 use ast_grep_language::Language;
 
-use std::fs::read_to_string;
 use std::io::stdout;
 use std::io::Write;
 use std::path::{Path, PathBuf};
@@ -59,7 +70,7 @@ fn prompt_reply_stdout(prompt: &str) -> Result<char> {
 }
 
 // clear screen
-fn clear() -> Result<()> {
+pub fn clear() -> Result<()> {
   execute!(stdout(), Clear(ClearType::All), MoveTo(0, 0))?;
   Ok(())
   // https://github.com/console-rs/console/blob/be1c2879536c90ffc2b54938b5964084f5fef67d/src/common_term.rs#L56
@@ -89,11 +100,57 @@ pub fn prompt(prompt_text: &str, letters: &str, default: Option<char>) -> Result
   }
 }
 
-fn read_file(path: &Path) -> Option<String> {
-  let file_content = read_to_string(path)
-    .with_context(|| format!("Cannot read file {}", path.to_string_lossy()))
-    .map_err(|err| eprintln!("{err:#}"))
-    .ok()?;
+/// Prompts for a line of text on STDOUT. Returns None if the user presses Esc.
+pub fn prompt_line(prompt_text: &str) -> Result<Option<String>> {
+  let mut stdout = std::io::stdout();
+  write!(stdout, "{}", prompt_text)?;
+  stdout.flush()?;
+  terminal::enable_raw_mode()?;
+  let ret = read_line(&mut stdout);
+  terminal::disable_raw_mode()?;
+  writeln!(stdout)?;
+  ret
+}
+
+fn read_line(stdout: &mut impl Write) -> Result<Option<String>> {
+  let mut line = String::new();
+  loop {
+    let Event::Key(evt) = event::read()? else {
+      continue;
+    };
+    match evt.code {
+      KeyCode::Enter => return Ok(Some(line)),
+      KeyCode::Esc => return Ok(None),
+      KeyCode::Backspace => {
+        if line.pop().is_some() {
+          write!(stdout, "\u{8} \u{8}")?;
+        }
+      }
+      KeyCode::Char(c) => {
+        line.push(c);
+        write!(stdout, "{c}")?;
+      }
+      _ => continue,
+    }
+    stdout.flush()?;
+  }
+}
+
+fn read_file(path: &Path, trace: &FileTrace) -> Option<String> {
+  let file_content = match read_source(path) {
+    Ok(content) => content,
+    Err(err) => {
+      if let Some(encoding::DecodeError(encoding)) = err.downcast_ref() {
+        trace.report_skip(path, SkipReason::Undecodable(*encoding));
+      } else {
+        trace.add_io_error(format!(
+          "Cannot read file {}: {err:#}",
+          path.to_string_lossy()
+        ));
+      }
+      return None;
+    }
+  };
   // skip large files or empty file
   if file_too_large(&file_content) || file_content.is_empty() {
     // TODO add output
@@ -108,9 +165,13 @@ fn filter(
   path: &Path,
   lang: SgLang,
   configs: &RuleCollection<SgLang>,
+  literals: Option<&LiteralHits>,
 ) -> Option<PreScan> {
   let rules = configs.get_rule_from_lang(path, lang);
-  let combined = CombinedScan::new(rules);
+  let mut combined = CombinedScan::new(rules);
+  if let Some(literals) = literals {
+    combined.exclude_rules(|rule| !literals.may_match(rule));
+  }
   let pre_scan = combined.find(grep);
   if pre_scan.hit_set.is_empty() {
     None
@@ -121,21 +182,25 @@ fn filter(
 
 pub fn filter_file_interactive(
   path: &Path,
+  lang: SgLang,
   configs: &RuleCollection<SgLang>,
+  prefilter: Option<(&Prefilter, &PrefilterTrace)>,
+  trace: &FileTrace,
 ) -> Option<Vec<(PathBuf, AstGrep, PreScan)>> {
-  let lang = SgLang::from_path(path)?;
-  let file_content = read_file(path)?;
+  let file_content = read_file(path, trace)?;
+  let literals = prefilter.map(|(p, trace)| p.search(&file_content, trace));
+  let literals = literals.as_ref();
   let grep = lang.ast_grep(file_content);
   let mut ret = vec![];
-  let root =
-    filter(&grep, path, lang, configs).map(|pre_scan| (path.to_path_buf(), grep.clone(), pre_scan));
+  let root = filter(&grep, path, lang, configs, literals)
+    .map(|pre_scan| (path.to_path_buf(), grep.clone(), pre_scan));
   ret.extend(root);
   if let Some(injected) = lang.injectable_sg_langs() {
     let docs = grep.inner.get_injections(|s| SgLang::from_str(s).ok());
     let inj = injected.filter_map(|l| {
       let doc = docs.iter().find(|d| *d.lang() == l)?;
       let grep = AstGrep { inner: doc.clone() };
-      let pre_scan = filter(&grep, path, l, configs)?;
+      let pre_scan = filter(&grep, path, l, configs, literals)?;
       Some((path.to_path_buf(), grep, pre_scan))
     });
     ret.extend(inj)
@@ -148,8 +213,9 @@ pub fn filter_file_pattern(
   lang: SgLang,
   root_matcher: Option<Pattern<SgLang>>,
   matchers: impl Iterator<Item = (SgLang, Pattern<SgLang>)>,
+  trace: &FileTrace,
 ) -> Option<Vec<(MatchUnit<Pattern<SgLang>>, SgLang)>> {
-  let file_content = read_file(path)?;
+  let file_content = read_file(path, trace)?;
   let grep = lang.ast_grep(&file_content);
   let do_match = |ast_grep: AstGrep, matcher: Pattern<SgLang>, lang: SgLang| {
     let fixed = matcher.fixed_string();
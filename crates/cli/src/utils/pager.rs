@@ -0,0 +1,225 @@
+//! Page terminal output through a pager like `less`, see `--pager`.
+//! Like git, the stdout of ast-grep is redirected to the pager's stdin,
+//! so printers keep writing to stdout and need not know about paging.
+use super::{ErrorContext as EC, OutputArgs};
+
+use anyhow::{Context, Result};
+
+use std::io::{ErrorKind, Write};
+use std::process::{Child, Command, ExitStatus, Stdio};
+
+/// Used if neither `--pager=CMD` nor the PAGER environment variable is set.
+/// `-F` quits if the output fits on one screen, `-R` keeps colors and
+/// `-X` leaves the output on the screen after quitting.
+const DEFAULT_PAGER: &str = "less -RFX";
+
+/// Pages stdout until the guard is dropped.
+pub struct Pager(Option<Paging>);
+
+struct Paging {
+  cmd: String,
+  child: Child,
+  /// the original stdout, restored when paging ends
+  #[cfg(unix)]
+  stdout: libc::c_int,
+}
+
+impl Pager {
+  /// Start the pager if `--pager` is given and stdout is a terminal.
+  /// JSON and interactive output are never paged.
+  /// Printers must be created before this so they see the terminal, not the pipe.
+  pub fn start(output: &OutputArgs) -> Result<Self> {
+    let Some(cmd) = &output.pager else {
+      return Ok(Self(None));
+    };
+    if output.json.is_some() || output.needs_interactive() || !atty::is(atty::Stream::Stdout) {
+      return Ok(Self(None));
+    }
+    let cmd = pager_command(cmd, std::env::var("PAGER").ok());
+    Ok(Self(Paging::spawn(&cmd)?))
+  }
+
+  /// Run the printing and wait for the user to quit the pager.
+  /// Quitting the pager before all output is written closes the pipe,
+  /// which is not an error, but a pager exiting with a failure is.
+  pub fn run(mut self, print: impl FnOnce() -> Result<()>) -> Result<()> {
+    let ret = print();
+    let Some(paging) = self.0.take() else {
+      return ret;
+    };
+    let status = paging.finish();
+    match ret {
+      Err(e) if !is_broken_pipe(&e) => Err(e),
+      _ => status,
+    }
+  }
+}
+
+/// `--pager` without a command uses PAGER, and then `less -RFX`.
+fn pager_command(cmd: &str, env: Option<String>) -> String {
+  if !cmd.trim().is_empty() {
+    return cmd.to_string();
+  }
+  match env {
+    Some(env) if !env.trim().is_empty() => env,
+    _ => DEFAULT_PAGER.to_string(),
+  }
+}
+
+/// A pager killed by SIGPIPE, directly or under the shell, did not fail by itself.
+fn check_status(cmd: &str, status: ExitStatus) -> Result<()> {
+  if status.success() {
+    return Ok(());
+  }
+  #[cfg(unix)]
+  {
+    use std::os::unix::process::ExitStatusExt;
+    if status.signal() == Some(libc::SIGPIPE) || status.code() == Some(128 + libc::SIGPIPE) {
+      return Ok(());
+    }
+  }
+  Err(anyhow::anyhow!(EC::PagerFailed(
+    cmd.to_string(),
+    status.to_string()
+  )))
+}
+
+fn is_broken_pipe(err: &anyhow::Error) -> bool {
+  err.chain().any(|cause| {
+    cause
+      .downcast_ref::<std::io::Error>()
+      .map_or(false, |e| e.kind() == ErrorKind::BrokenPipe)
+  })
+}
+
+impl Paging {
+  #[cfg(unix)]
+  fn spawn(cmd: &str) -> Result<Option<Self>> {
+    use std::os::unix::io::AsRawFd;
+    let mut command = Command::new("sh");
+    command.arg("-c").arg(cmd).stdin(Stdio::piped());
+    // less options for users who set PAGER=less
+    if std::env::var_os("LESS").is_none() {
+      command.env("LESS", "FRX");
+    }
+    let mut child = command
+      .spawn()
+      .with_context(|| EC::StartPager(cmd.to_string()))?;
+    let stdin = child.stdin.take().expect("pager stdin must be piped");
+    std::io::stdout().flush()?;
+    // SAFETY: dup and dup2 only operate on file descriptors owned by this process
+    let stdout = unsafe { libc::dup(libc::STDOUT_FILENO) };
+    if stdout < 0 || unsafe { libc::dup2(stdin.as_raw_fd(), libc::STDOUT_FILENO) } < 0 {
+      let err = std::io::Error::last_os_error();
+      let _ = child.kill();
+      return Err(err).with_context(|| EC::StartPager(cmd.to_string()));
+    }
+    // fd 1 keeps the pipe open, closing it at drop signals the end of output
+    drop(stdin);
+    Ok(Some(Self {
+      cmd: cmd.to_string(),
+      child,
+      stdout,
+    }))
+  }
+
+  /// Paging needs to redirect stdout, which is not supported on this platform.
+  #[cfg(not(unix))]
+  fn spawn(_cmd: &str) -> Result<Option<Self>> {
+    Ok(None)
+  }
+
+  /// Restore stdout, which also closes the pager's stdin, and wait for the pager.
+  fn wait(&mut self) -> std::io::Result<ExitStatus> {
+    let _ = std::io::stdout().flush();
+    // SAFETY: restore the saved stdout and close the copy, at most once
+    #[cfg(unix)]
+    if self.stdout >= 0 {
+      unsafe {
+        libc::dup2(self.stdout, libc::STDOUT_FILENO);
+        libc::close(self.stdout);
+      }
+      self.stdout = -1;
+    }
+    self.child.wait()
+  }
+
+  fn finish(mut self) -> Result<()> {
+    let status = self
+      .wait()
+      .with_context(|| EC::PagerFailed(self.cmd.clone(), "no exit status".into()))?;
+    check_status(&self.cmd, status)
+  }
+}
+
+impl Drop for Paging {
+  fn drop(&mut self) {
+    let _ = self.wait();
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::print::{ColorArg, DiffStyle, JsonStyle, PathStyle};
+
+  #[test]
+  fn test_pager_command() {
+    assert_eq!(pager_command("bat", None), "bat");
+    assert_eq!(pager_command("bat", Some("more".into())), "bat");
+    assert_eq!(pager_command("", Some("more".into())), "more");
+    assert_eq!(pager_command("", Some(" ".into())), DEFAULT_PAGER);
+    assert_eq!(pager_command("", None), DEFAULT_PAGER);
+  }
+
+  #[test]
+  fn test_broken_pipe() {
+    let err = std::io::Error::from(ErrorKind::BrokenPipe);
+    let err = anyhow::Error::from(err).context("cannot print");
+    assert!(is_broken_pipe(&err));
+    let err = anyhow::anyhow!(EC::StartPager("less".into()));
+    assert!(!is_broken_pipe(&err));
+  }
+
+  #[cfg(unix)]
+  #[test]
+  fn test_pager_status() {
+    let status = |cmd: &str| {
+      let status = Command::new("sh").arg("-c").arg(cmd).status();
+      check_status(cmd, status.expect("sh should run"))
+    };
+    assert!(status("exit 0").is_ok());
+    assert!(status("kill -PIPE $$").is_ok());
+    assert!(status("exit 141").is_ok());
+    let err = status("exit 127").expect_err("missing command should fail");
+    let ec = err.downcast_ref::<EC>().expect("should be pager error");
+    assert!(matches!(ec, EC::PagerFailed(cmd, _) if cmd == "exit 127"));
+    assert!(status("exit 1").is_err());
+  }
+
+  #[test]
+  fn test_no_pager_for_json() {
+    let pager = Pager::start(&OutputArgs {
+      interactive: false,
+      json: Some(JsonStyle::Stream),
+      json_include_rewritten: false,
+      update_all: false,
+      session: None,
+      session_report: None,
+      color: ColorArg::Never,
+      color_theme: None,
+      highlight: false,
+      tab_width: 4,
+      diff_style: DiffStyle::Inline,
+      context_separator: None,
+      field_separator: ":".into(),
+      path_style: PathStyle::Relative,
+      pager: Some(String::new()),
+      tracing: Default::default(),
+      no_messages: false,
+      strict_io: false,
+      dedupe: Default::default(),
+    });
+    assert!(pager.expect("should not fail").0.is_none());
+  }
+}
@@ -0,0 +1,177 @@
+use super::PrefilterTrace;
+use crate::lang::SgLang;
+
+use aho_corasick::AhoCorasick;
+use ast_grep_config::RuleConfig;
+
+use std::collections::{HashMap, HashSet};
+
+/// Prefilter decides which rules can possibly match a file before parsing it.
+///
+/// Most rules contain literals, like function names or keywords, that must appear in the source.
+/// Prefilter collects these literals at load time and runs one multi-pattern search per file.
+/// Rules whose literals are all absent will not be executed against the parsed AST.
+/// Rules without any extractable literal always run.
+pub struct Prefilter {
+  searcher: AhoCorasick,
+  /// rule id to the indices of its literals in the searcher
+  rule_literals: HashMap<String, Vec<usize>>,
+  literal_count: usize,
+}
+
+impl Prefilter {
+  /// Returns None if no rule has literals.
+  pub fn new<'r>(rules: impl Iterator<Item = &'r RuleConfig<SgLang>>) -> Option<Self> {
+    let mut literals: Vec<String> = vec![];
+    let mut rule_literals: HashMap<String, Vec<usize>> = HashMap::new();
+    let mut always_run = HashSet::new();
+    for rule in rules {
+      // rules sharing the same id cannot be told apart, just always run them
+      if rule_literals.remove(&rule.id).is_some() || always_run.contains(&rule.id) {
+        always_run.insert(rule.id.clone());
+        continue;
+      }
+      let Some(lits) = rule.required_literals() else {
+        always_run.insert(rule.id.clone());
+        continue;
+      };
+      let indices = lits
+        .into_iter()
+        .map(|lit| match literals.iter().position(|l| *l == lit) {
+          Some(idx) => idx,
+          None => {
+            literals.push(lit);
+            literals.len() - 1
+          }
+        })
+        .collect();
+      rule_literals.insert(rule.id.clone(), indices);
+    }
+    if rule_literals.is_empty() {
+      return None;
+    }
+    let searcher = AhoCorasick::new(&literals).ok()?;
+    Some(Self {
+      searcher,
+      rule_literals,
+      literal_count: literals.len(),
+    })
+  }
+
+  /// Rule ids that can be skipped by prefilter.
+  pub fn rule_ids(&self) -> impl Iterator<Item = &String> {
+    self.rule_literals.keys()
+  }
+
+  /// Search all literals in the source text.
+  pub fn search<'p>(&'p self, source: &str, trace: &'p PrefilterTrace) -> LiteralHits<'p> {
+    let mut found = vec![false; self.literal_count];
+    let mut remaining = self.literal_count;
+    for mat in self.searcher.find_overlapping_iter(source) {
+      let idx = mat.pattern().as_usize();
+      if !found[idx] {
+        found[idx] = true;
+        remaining -= 1;
+        if remaining == 0 {
+          break;
+        }
+      }
+    }
+    LiteralHits {
+      prefilter: self,
+      trace,
+      found,
+    }
+  }
+}
+
+/// Literals found in one file.
+pub struct LiteralHits<'p> {
+  prefilter: &'p Prefilter,
+  trace: &'p PrefilterTrace,
+  found: Vec<bool>,
+}
+
+impl<'p> LiteralHits<'p> {
+  /// Returns false only if the rule cannot possibly match the file.
+  pub fn may_match(&self, rule: &RuleConfig<SgLang>) -> bool {
+    let Some(indices) = self.prefilter.rule_literals.get(&rule.id) else {
+      return true;
+    };
+    let hit = indices.iter().any(|&idx| self.found[idx]);
+    if hit {
+      self.trace.add_hit(&rule.id);
+    } else {
+      self.trace.add_miss(&rule.id);
+    }
+    hit
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use ast_grep_config::from_yaml_string;
+
+  fn make_rules(rules: &str) -> Vec<RuleConfig<SgLang>> {
+    from_yaml_string(rules, &Default::default()).expect("should parse")
+  }
+
+  const RULES: &str = "
+id: no-eval
+language: JavaScript
+rule: {pattern: eval($A)}
+---
+id: no-alert
+language: JavaScript
+rule:
+  any: [pattern: alert($A), pattern: confirm($A)]
+---
+id: number
+language: JavaScript
+rule: {kind: number}
+";
+
+  #[test]
+  fn test_prefilter() {
+    let rules = make_rules(RULES);
+    let prefilter = Prefilter::new(rules.iter()).expect("should have literals");
+    let mut ids: Vec<_> = prefilter.rule_ids().collect();
+    ids.sort();
+    assert_eq!(ids, ["no-alert", "no-eval"]);
+    let trace = PrefilterTrace::new(prefilter.rule_ids().cloned());
+    let hits = prefilter.search("confirm('sure?')", &trace);
+    assert!(!hits.may_match(&rules[0]));
+    assert!(hits.may_match(&rules[1]));
+    assert!(hits.may_match(&rules[2]));
+    let hits = prefilter.search("let a = 123", &trace);
+    assert!(!hits.may_match(&rules[0]));
+    assert!(!hits.may_match(&rules[1]));
+    assert!(hits.may_match(&rules[2]));
+    let printed = trace.print();
+    assert!(printed.contains("no-eval: hit 0, miss 2"));
+    assert!(printed.contains("no-alert: hit 1, miss 1"));
+  }
+
+  #[test]
+  fn test_no_literal() {
+    let rules = make_rules("{id: a, language: JavaScript, rule: {kind: number}}");
+    assert!(Prefilter::new(rules.iter()).is_none());
+  }
+
+  #[test]
+  fn test_duplicate_id() {
+    let rules = make_rules(
+      "
+id: dup
+language: JavaScript
+rule: {pattern: eval($A)}
+---
+id: dup
+language: JavaScript
+rule: {pattern: alert($A)}
+",
+    );
+    assert!(Prefilter::new(rules.iter()).is_none());
+  }
+}
@@ -3,12 +3,74 @@ use super::SeverityArg;
 use anyhow::Result;
 use ast_grep_config::{SerializableRuleConfig, Severity};
 use ast_grep_core::Language;
+use clap::ValueEnum;
+use regex::Regex;
 
 use std::collections::HashMap;
 
-struct RuleOverwrite {
+/// How to resolve rules sharing the same id, see `--allow-duplicate-rules`.
+#[derive(Clone, Copy, ValueEnum, PartialEq, Debug)]
+pub enum DuplicateRules {
+  /// The rule loaded last replaces earlier rules with the same id.
+  LastWins,
+}
+
+/// Severity levels that can be selected by `--only-severity`.
+#[derive(Clone, Copy, ValueEnum, PartialEq, Debug)]
+pub enum SeverityLevel {
+  Error,
+  Warning,
+  Info,
+  Hint,
+}
+
+impl SeverityLevel {
+  /// all levels from the highest to the lowest
+  pub const DESCENDING: [SeverityLevel; 4] = [
+    SeverityLevel::Error,
+    SeverityLevel::Warning,
+    SeverityLevel::Info,
+    SeverityLevel::Hint,
+  ];
+
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      SeverityLevel::Error => "error",
+      SeverityLevel::Warning => "warning",
+      SeverityLevel::Info => "info",
+      SeverityLevel::Hint => "hint",
+    }
+  }
+
+  /// Returns None for Severity::Off
+  pub fn from_severity(severity: &Severity) -> Option<Self> {
+    Self::DESCENDING.into_iter().find(|l| l.matches(severity))
+  }
+
+  fn matches(&self, severity: &Severity) -> bool {
+    use SeverityLevel as L;
+    matches!(
+      (self, severity),
+      (L::Error, Severity::Error)
+        | (L::Warning, Severity::Warning)
+        | (L::Info, Severity::Info)
+        | (L::Hint, Severity::Hint)
+    )
+  }
+}
+
+/// CLI options that change which rules run and their severities.
+#[derive(Default)]
+pub struct RuleOverwrite {
   default_severity: Option<Severity>,
   by_rule_id: HashMap<String, Severity>,
+  only_severity: Vec<SeverityLevel>,
+  pub rule_filter: Option<Regex>,
+  pub duplicate_rules: Option<DuplicateRules>,
+  /// report unknown keys in rule YAML as warnings instead of errors
+  pub no_strict_yaml: bool,
+  /// load rules with severity off as hints instead of skipping them
+  pub include_off: bool,
 }
 
 fn read_severity(
@@ -28,7 +90,7 @@ fn read_severity(
 }
 
 impl RuleOverwrite {
-  pub fn new(cli: SeverityArg) -> Result<Self> {
+  pub fn new(cli: &SeverityArg, rule_filter: Option<Regex>) -> Result<Self> {
     let mut default_severity = None;
     let mut by_rule_id = HashMap::new();
     read_severity(
@@ -64,9 +126,19 @@ impl RuleOverwrite {
     Ok(Self {
       default_severity,
       by_rule_id,
+      only_severity: cli.only_severity.clone(),
+      rule_filter,
+      duplicate_rules: None,
+      no_strict_yaml: false,
+      include_off: false,
     })
   }
 
+  /// Returns false if the (overwritten) severity is excluded by `--only-severity`.
+  pub fn is_severity_selected(&self, severity: &Severity) -> bool {
+    self.only_severity.is_empty() || self.only_severity.iter().any(|l| l.matches(severity))
+  }
+
   pub fn find(&self, id: &str) -> OverwriteResult {
     let severity = self
       .by_rule_id
@@ -91,3 +163,48 @@ impl OverwriteResult {
     }
   }
 }
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  fn severity_arg(warning: Option<Vec<String>>, only: Vec<SeverityLevel>) -> SeverityArg {
+    SeverityArg {
+      error: None,
+      warning,
+      info: None,
+      hint: None,
+      off: None,
+      only_severity: only,
+    }
+  }
+
+  #[test]
+  fn test_overwrite_severity() {
+    let arg = severity_arg(Some(vec!["my-rule".into()]), vec![]);
+    let overwrite = RuleOverwrite::new(&arg, None).expect("should work");
+    assert!(matches!(
+      overwrite.find("my-rule").severity,
+      Some(Severity::Warning)
+    ));
+    assert!(overwrite.find("other").severity.is_none());
+    let arg = severity_arg(Some(vec![]), vec![]);
+    let overwrite = RuleOverwrite::new(&arg, None).expect("should work");
+    assert!(matches!(
+      overwrite.find("other").severity,
+      Some(Severity::Warning)
+    ));
+  }
+
+  #[test]
+  fn test_only_severity() {
+    let overwrite = RuleOverwrite::default();
+    assert!(overwrite.is_severity_selected(&Severity::Hint));
+    let arg = severity_arg(None, vec![SeverityLevel::Error, SeverityLevel::Warning]);
+    let overwrite = RuleOverwrite::new(&arg, None).expect("should work");
+    assert!(overwrite.is_severity_selected(&Severity::Error));
+    assert!(overwrite.is_severity_selected(&Severity::Warning));
+    assert!(!overwrite.is_severity_selected(&Severity::Hint));
+    assert!(!overwrite.is_severity_selected(&Severity::Off));
+  }
+}
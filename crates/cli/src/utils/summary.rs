@@ -0,0 +1,202 @@
+//! The summary printed to stderr after every scan, unless `--no-summary` is set.
+//! Counters are updated when findings are handed to printers so the summary always
+//! agrees with the printed output.
+
+use super::SeverityLevel;
+use crate::lang::SgLang;
+use ast_grep_config::RuleConfig;
+
+use std::collections::HashSet;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+pub struct ScanSummary {
+  start: Instant,
+  /// finding count indexed by SeverityLevel
+  findings: [AtomicUsize; 4],
+  fixes: AtomicUsize,
+  files_with_findings: Mutex<HashSet<PathBuf>>,
+  rules_fired: Mutex<HashSet<String>>,
+}
+
+impl Default for ScanSummary {
+  fn default() -> Self {
+    Self {
+      start: Instant::now(),
+      findings: Default::default(),
+      fixes: AtomicUsize::new(0),
+      files_with_findings: Default::default(),
+      rules_fired: Default::default(),
+    }
+  }
+}
+
+impl ScanSummary {
+  /// Record `count` findings of the rule in the file.
+  pub fn add_findings(&self, path: &Path, rule: &RuleConfig<SgLang>, count: usize) {
+    let Some(level) = SeverityLevel::from_severity(&rule.severity) else {
+      return;
+    };
+    if count == 0 {
+      return;
+    }
+    self.findings[level as usize].fetch_add(count, Ordering::AcqRel);
+    if rule.fix.is_some() {
+      self.fixes.fetch_add(count, Ordering::AcqRel);
+    }
+    let mut files = self.files_with_findings.lock().expect("should not fail");
+    if !files.contains(path) {
+      files.insert(path.to_path_buf());
+    }
+    let mut rules = self.rules_fired.lock().expect("should not fail");
+    if !rules.contains(&rule.id) {
+      rules.insert(rule.id.clone());
+    }
+  }
+
+  /// Freeze the counters for printing.
+  pub fn report(
+    &self,
+    files_scanned: usize,
+    applied: Option<(usize, usize)>,
+    stale: Vec<PathBuf>,
+  ) -> SummaryReport {
+    let findings =
+      SeverityLevel::DESCENDING.map(|l| self.findings[l as usize].load(Ordering::Acquire));
+    SummaryReport {
+      files_scanned,
+      files_with_findings: self
+        .files_with_findings
+        .lock()
+        .expect("should not fail")
+        .len(),
+      findings,
+      rules_fired: self.rules_fired.lock().expect("should not fail").len(),
+      fixes: self.fixes.load(Ordering::Acquire),
+      elapsed: self.start.elapsed(),
+      applied,
+      stale,
+    }
+  }
+}
+
+pub struct SummaryReport {
+  files_scanned: usize,
+  files_with_findings: usize,
+  /// finding count from the highest severity to the lowest
+  findings: [usize; 4],
+  rules_fired: usize,
+  fixes: usize,
+  elapsed: Duration,
+  /// files modified and edits applied
+  applied: Option<(usize, usize)>,
+  /// files not modified because they changed on disk after scanning
+  stale: Vec<PathBuf>,
+}
+
+fn plural(count: usize, noun: &str) -> String {
+  if count == 1 {
+    format!("{count} {noun}")
+  } else {
+    format!("{count} {noun}s")
+  }
+}
+
+impl fmt::Display for SummaryReport {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let elapsed = self.elapsed.as_secs_f64();
+    write!(
+      f,
+      "Scanned {} in {elapsed:.2}s",
+      plural(self.files_scanned, "file")
+    )?;
+    let total: usize = self.findings.iter().sum();
+    if total == 0 {
+      write!(f, ": no findings.")?;
+    } else {
+      let [error, warning, info, hint] = self.findings;
+      write!(
+        f,
+        ": {} in {}.\nFindings: {}, {}, {info} info, {}. Rules fired: {}. Fixes available: {}.",
+        plural(total, "finding"),
+        plural(self.files_with_findings, "file"),
+        plural(error, "error"),
+        plural(warning, "warning"),
+        plural(hint, "hint"),
+        self.rules_fired,
+        self.fixes,
+      )?;
+    }
+    if let Some((files, edits)) = self.applied {
+      write!(
+        f,
+        "\nApplied {} to {}.",
+        plural(edits, "edit"),
+        plural(files, "file")
+      )?;
+    }
+    if !self.stale.is_empty() {
+      let paths: Vec<_> = self.stale.iter().map(|p| p.display().to_string()).collect();
+      write!(
+        f,
+        "\nSkipped fixes of {} changed on disk after scanning: {}.",
+        plural(self.stale.len(), "file"),
+        paths.join(", ")
+      )?;
+    }
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use ast_grep_config::{from_yaml_string, GlobalRules};
+
+  fn make_rule(src: &str) -> RuleConfig<SgLang> {
+    let globals = GlobalRules::default();
+    from_yaml_string(src, &globals)
+      .expect("should parse")
+      .pop()
+      .unwrap()
+  }
+
+  #[test]
+  fn test_summary() {
+    let summary = ScanSummary::default();
+    let error = make_rule("{id: a, severity: error, language: ts, rule: {pattern: a}, fix: b}");
+    let hint = make_rule("{id: b, language: ts, rule: {pattern: b}}");
+    summary.add_findings(Path::new("a.ts"), &error, 2);
+    summary.add_findings(Path::new("a.ts"), &hint, 1);
+    summary.add_findings(Path::new("b.ts"), &hint, 1);
+    summary.add_findings(Path::new("c.ts"), &hint, 0);
+    let report = summary.report(3, None, vec![]).to_string();
+    assert!(report.starts_with("Scanned 3 files in "), "{report}");
+    assert!(report.contains(": 4 findings in 2 files."), "{report}");
+    assert!(
+      report.contains(
+        "Findings: 2 errors, 0 warnings, 0 info, 2 hints. Rules fired: 2. Fixes available: 2."
+      ),
+      "{report}"
+    );
+    let report = ScanSummary::default()
+      .report(1, Some((1, 3)), vec![])
+      .to_string();
+    assert!(report.contains("Scanned 1 file in"), "{report}");
+    assert!(
+      report.contains(": no findings.\nApplied 3 edits to 1 file."),
+      "{report}"
+    );
+    let stale = vec![PathBuf::from("a.ts"), PathBuf::from("b.ts")];
+    let report = ScanSummary::default()
+      .report(2, Some((0, 0)), stale)
+      .to_string();
+    assert!(
+      report.ends_with("\nSkipped fixes of 2 files changed on disk after scanning: a.ts, b.ts."),
+      "{report}"
+    );
+  }
+}
@@ -6,6 +6,7 @@
 //!   * number file matched
 //!   * number of matches produced or errors/warnings/hints
 //!   * number of fix applied
+//!   * literal prefilter hit/miss counts per rule
 //! - File level: show how a file is scanned
 //!   * reasons if skipped (file too large, does not have fixed string in pattern, no matching rule, etc)
 //!   * number of rules applied
@@ -13,12 +14,18 @@
 //!   * matches produced or errors/warnings/hints
 //! - Detail level: show how a rule runs on a file
 
+use super::Encoding;
+
 use clap::ValueEnum;
 use serde::{Deserialize, Serialize};
 
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::Path;
 use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
 
-#[derive(Clone, Copy, ValueEnum, Serialize, Deserialize, Default, PartialEq, Debug)]
+#[derive(Clone, Copy, ValueEnum, Serialize, Deserialize, Default, PartialEq, PartialOrd, Debug)]
 #[serde(rename_all = "camelCase")]
 pub enum Tracing {
   /// Do not show any tracing information
@@ -26,8 +33,9 @@ pub enum Tracing {
   Nothing = 0,
   /// Show summary about how many files are scanned and skipped
   Summary = 1,
+  /// Show summary and the reason why each file is skipped
+  File = 2,
   // TODO: implement these levels
-  // File,
   // Detail,
 }
 
@@ -36,14 +44,37 @@ impl Tracing {
     RunTrace {
       level: *self,
       inner: (),
-      file_trace: Default::default(),
+      file_trace: FileTrace::new(*self),
     }
   }
   pub fn scan_trace(&self, rule_stats: RuleTrace) -> ScanTrace {
     ScanTrace {
       level: *self,
       inner: rule_stats,
-      file_trace: Default::default(),
+      file_trace: FileTrace::new(*self),
+    }
+  }
+}
+
+/// The reason why a file is skipped before it is read.
+pub enum SkipReason {
+  /// file size in bytes exceeds --max-file-size
+  TooLarge(u64),
+  /// file looks like minified code, see --skip-minified
+  Minified,
+  /// explicit path is excluded by globs or ignore files, see --force-exclude
+  ForceExcluded,
+  /// file content is not valid in the encoding detected by its BOM
+  Undecodable(Encoding),
+}
+
+impl fmt::Display for SkipReason {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      SkipReason::TooLarge(size) => write!(f, "file size {size} bytes exceeds max file size"),
+      SkipReason::Minified => write!(f, "file looks minified"),
+      SkipReason::ForceExcluded => write!(f, "path is excluded by globs or ignore files"),
+      SkipReason::Undecodable(encoding) => write!(f, "file cannot be decoded as {encoding}"),
     }
   }
 }
@@ -55,24 +86,130 @@ impl Tracing {
 pub struct FileTrace {
   files_scanned: AtomicUsize,
   files_skipped: AtomicUsize,
+  /// directories not descended because they are on another file system, see --one-file-system
+  #[serde(default, skip_serializing_if = "is_zero")]
+  dirs_skipped: AtomicUsize,
+  /// files whose language is decided by languageGlobs or --lang-glob, by language
+  #[serde(default, skip_serializing_if = "has_no_counted_file")]
+  lang_glob_files: Mutex<BTreeMap<String, usize>>,
+  /// files without extension whose language is decided by shebang, by language
+  #[serde(default, skip_serializing_if = "has_no_counted_file")]
+  shebang_files: Mutex<BTreeMap<String, usize>>,
+  /// messages of files and directories that cannot be read
+  #[serde(rename = "errors", default, skip_serializing_if = "has_no_error")]
+  io_errors: Mutex<Vec<String>>,
+  /// do not print IO errors when they happen, see --no-messages
+  #[serde(skip)]
+  no_messages: bool,
+  #[serde(skip)]
+  level: Tracing,
 }
 
 impl FileTrace {
+  fn new(level: Tracing) -> Self {
+    Self {
+      level,
+      ..Default::default()
+    }
+  }
   pub fn add_scanned(&self) {
     self.files_scanned.fetch_add(1, Ordering::AcqRel);
   }
   pub fn add_skipped(&self) {
     self.files_skipped.fetch_add(1, Ordering::AcqRel);
   }
+  /// Skip a file with a reason. The reason is printed at file tracing level.
+  pub fn skip_file(&self, path: &Path, reason: SkipReason) {
+    self.add_skipped();
+    self.report_skip(path, reason);
+  }
+  /// Print the reason of a skipped file at file tracing level, without counting it.
+  pub fn report_skip(&self, path: &Path, reason: SkipReason) {
+    if self.level >= Tracing::File {
+      eprintln!("Skipped {}: {reason}", path.display());
+    }
+  }
+  /// Record an error of walking or reading files, printed unless --no-messages is set.
+  pub fn add_io_error(&self, message: String) {
+    if !self.no_messages {
+      eprintln!("{message}");
+    }
+    self
+      .io_errors
+      .lock()
+      .expect("should not fail")
+      .push(message);
+  }
+  /// Count a file whose language is decided by languageGlobs or --lang-glob.
+  pub fn add_lang_glob_file(&self, lang: String) {
+    let mut files = self.lang_glob_files.lock().expect("should not fail");
+    *files.entry(lang).or_default() += 1;
+  }
+  /// Count a file whose language is detected by its shebang, see --no-shebang-detection.
+  pub fn add_shebang_file(&self, path: &Path, lang: String) {
+    if self.level >= Tracing::File {
+      eprintln!("Detected {} as {lang} by shebang", path.display());
+    }
+    let mut files = self.shebang_files.lock().expect("should not fail");
+    *files.entry(lang).or_default() += 1;
+  }
+  pub fn io_errors(&self) -> Vec<String> {
+    self.io_errors.lock().expect("should not fail").clone()
+  }
+  /// Skip a directory on another file system than its root path.
+  pub fn skip_dir(&self, path: &Path) {
+    self.dirs_skipped.fetch_add(1, Ordering::AcqRel);
+    if self.level >= Tracing::File {
+      eprintln!(
+        "Skipped {}: directory is on another file system",
+        path.display()
+      );
+    }
+  }
+  pub fn files_scanned(&self) -> usize {
+    self.files_scanned.load(Ordering::Acquire)
+  }
   pub fn print(&self) -> String {
-    format!(
+    let files = format!(
       "Files scanned: {}, Files skipped: {}",
       self.files_scanned.load(Ordering::Acquire),
       self.files_skipped.load(Ordering::Acquire)
-    )
+    );
+    let files = match self.dirs_skipped.load(Ordering::Acquire) {
+      0 => files,
+      dirs => format!("{files}, Directories on other file systems: {dirs}"),
+    };
+    let files = match self.io_errors.lock().expect("should not fail").len() {
+      0 => files,
+      errors => format!("{files}, Read errors: {errors}"),
+    };
+    let glob_files = self.lang_glob_files.lock().expect("should not fail");
+    let glob_files = glob_files
+      .iter()
+      .map(|(lang, count)| format!("\nLanguage globs {lang}: {count} file(s)"));
+    let shebang_files = self.shebang_files.lock().expect("should not fail");
+    let shebang_files = shebang_files
+      .iter()
+      .map(|(lang, count)| format!("\nShebang {lang}: {count} file(s)"));
+    std::iter::once(files)
+      .chain(glob_files)
+      .chain(shebang_files)
+      .collect()
   }
 }
 
+fn is_zero(count: &AtomicUsize) -> bool {
+  count.load(Ordering::Acquire) == 0
+}
+
+fn has_no_counted_file(files: &Mutex<BTreeMap<String, usize>>) -> bool {
+  files.lock().expect("should not fail").is_empty()
+}
+
+fn has_no_error(errors: &Mutex<Vec<String>>) -> bool {
+  errors.lock().expect("should not fail").is_empty()
+}
+
 #[derive(Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct TraceInfo<T> {
@@ -82,6 +219,14 @@ pub struct TraceInfo<T> {
   #[serde(flatten)]
   pub inner: T,
 }
+impl<T> TraceInfo<T> {
+  /// Do not print IO errors when they happen, see --no-messages.
+  pub fn no_messages(mut self, no_messages: bool) -> Self {
+    self.file_trace.no_messages = no_messages;
+    self
+  }
+}
+
 impl TraceInfo<()> {
   // TODO: support more format?
   pub fn print(&self, is_json: bool) -> Option<String> {
@@ -117,13 +262,75 @@ impl TraceInfo<RuleTrace> {
 pub struct RuleTrace {
   pub effective_rule_count: usize,
   pub skipped_rule_count: usize,
+  /// rules excluded by --only-severity, also counted in skipped rules
+  #[serde(default)]
+  pub severity_excluded_rule_count: usize,
+  #[serde(default, skip_serializing_if = "PrefilterTrace::is_empty")]
+  pub prefilter: PrefilterTrace,
+  /// ids of rules with severity off, skipped unless they are included by the overwrite
+  #[serde(skip)]
+  pub off_rules: Vec<String>,
 }
 impl RuleTrace {
   pub fn print(&self) -> String {
-    format!(
+    let mut rules = format!(
       "Effective rules: {}, Skipped rules: {}",
       self.effective_rule_count, self.skipped_rule_count
-    )
+    );
+    if self.severity_excluded_rule_count > 0 {
+      let excluded = self.severity_excluded_rule_count;
+      rules.push_str(&format!(", Excluded by severity: {excluded}"));
+    }
+    if self.prefilter.is_empty() {
+      rules
+    } else {
+      format!("{rules}\n{}", self.prefilter.print())
+    }
+  }
+}
+
+// hit = literal found in file and rule executed, miss = rule skipped by prefilter
+#[derive(Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PrefilterCount {
+  hit: AtomicUsize,
+  miss: AtomicUsize,
+}
+
+/// Prefilter hit/miss counts per rule id
+#[derive(Default, Serialize, Deserialize)]
+pub struct PrefilterTrace(BTreeMap<String, PrefilterCount>);
+
+impl PrefilterTrace {
+  pub fn new(rule_ids: impl Iterator<Item = String>) -> Self {
+    Self(rule_ids.map(|id| (id, PrefilterCount::default())).collect())
+  }
+  pub fn is_empty(&self) -> bool {
+    self.0.is_empty()
+  }
+  pub fn add_hit(&self, rule_id: &str) {
+    if let Some(count) = self.0.get(rule_id) {
+      count.hit.fetch_add(1, Ordering::AcqRel);
+    }
+  }
+  pub fn add_miss(&self, rule_id: &str) {
+    if let Some(count) = self.0.get(rule_id) {
+      count.miss.fetch_add(1, Ordering::AcqRel);
+    }
+  }
+  pub fn print(&self) -> String {
+    let counts: Vec<_> = self
+      .0
+      .iter()
+      .map(|(id, count)| {
+        format!(
+          "Prefilter {id}: hit {}, miss {}",
+          count.hit.load(Ordering::Acquire),
+          count.miss.load(Ordering::Acquire)
+        )
+      })
+      .collect();
+    counts.join("\n")
   }
 }
 
@@ -149,10 +356,50 @@ mod test {
     );
     let printed = run_trace.print(false).expect("should have output");
     assert_eq!(printed, "Files scanned: 0, Files skipped: 0");
+    run_trace.file_trace.skip_dir(Path::new("mnt"));
+    let printed = run_trace.print(false).expect("should have output");
+    assert_eq!(
+      printed,
+      "Files scanned: 0, Files skipped: 0, Directories on other file systems: 1"
+    );
+    let json = run_trace.print(true).expect("should have output");
+    assert!(json.contains(r#""dirsSkipped":1"#));
+    assert!(!json.contains("errors"));
+    let run_trace = tracing.run_trace().no_messages(true);
+    run_trace.file_trace.add_io_error("cannot read a.ts".into());
+    let printed = run_trace.print(false).expect("should have output");
+    assert_eq!(
+      printed,
+      "Files scanned: 0, Files skipped: 0, Read errors: 1"
+    );
+    let json = run_trace.print(true).expect("should have output");
+    assert!(json.contains(r#""errors":["cannot read a.ts"]"#));
+    let run_trace = tracing.run_trace();
+    run_trace.file_trace.add_lang_glob_file("Groovy".into());
+    run_trace.file_trace.add_lang_glob_file("Groovy".into());
+    let printed = run_trace.print(false).expect("should have output");
+    assert_eq!(
+      printed,
+      "Files scanned: 0, Files skipped: 0\nLanguage globs Groovy: 2 file(s)"
+    );
+    let json = run_trace.print(true).expect("should have output");
+    assert!(json.contains(r#""langGlobFiles":{"Groovy":2}"#));
+    let run_trace = tracing.run_trace();
+    run_trace
+      .file_trace
+      .add_shebang_file(Path::new("deploy"), "Bash".into());
+    let printed = run_trace.print(false).expect("should have output");
+    assert_eq!(
+      printed,
+      "Files scanned: 0, Files skipped: 0\nShebang Bash: 1 file(s)"
+    );
+    let json = run_trace.print(true).expect("should have output");
+    assert!(json.contains(r#""shebangFiles":{"Bash":1}"#));
 
     let rule_stats = RuleTrace {
       effective_rule_count: 10,
       skipped_rule_count: 2,
+      ..Default::default()
     };
     let scan_trace = tracing.scan_trace(rule_stats);
     assert_eq!(scan_trace.level, Tracing::Summary);
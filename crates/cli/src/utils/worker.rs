@@ -1,9 +1,16 @@
-use crate::utils::FileTrace;
+use crate::lang::SgLang;
+use crate::print::{BatchError, BatchOutput, BatchSummary};
+use crate::utils::{FileTrace, InputArgs, SkipReason};
 
-use anyhow::{anyhow, Result};
+use anyhow::{Context, Result};
 use ignore::{DirEntry, WalkParallel, WalkState};
+use serde::Deserialize;
 
+use std::cell::RefCell;
+use std::fs::File;
+use std::io::{BufRead, Read};
 use std::path::{Path, PathBuf};
+use std::rc::Rc;
 use std::sync::{mpsc, Arc};
 
 /// A trait to abstract how ast-grep discovers work Items.
@@ -11,9 +18,10 @@ use std::sync::{mpsc, Arc};
 /// It follows multiple-producer-single-consumer pattern.
 /// ast-grep will produce items in one or more separate thread(s) and
 /// `consume_items` in the main thread, blocking the function return.
-/// Worker at the moment has two main flavors:
+/// Worker at the moment has three main flavors:
 /// * PathWorker: discovers files on the file system, based on ignore
 /// * StdInWorker: parse text content from standard input stream
+/// * StdInBatchWorker: parse NDJSON records of path and source from standard input
 pub trait Worker: Sync + Send {
   /// The item to send between producer/consumer threads.
   /// It is usually parsed tree-sitter Root with optional data.
@@ -32,6 +40,8 @@ pub trait Worker: Sync + Send {
 pub trait PathWorker: Worker {
   /// WalkParallel will determine what files will be processed.
   fn build_walk(&self) -> Result<WalkParallel>;
+  /// Input options to skip files before they are read.
+  fn get_input(&self) -> &InputArgs;
   /// Record trace for the worker.
   fn get_trace(&self) -> &FileTrace;
   /// Parse and find_match can be done in `produce_item`.
@@ -51,72 +61,191 @@ pub trait StdInWorker: Worker {
   fn run_std_in(&self) -> Result<()> {
     let source = std::io::read_to_string(std::io::stdin())?;
     if let Some(item) = self.parse_stdin(source) {
-      self.consume_items(Items::once(item)?)
+      self.consume_items(Items::once(item))
     } else {
       Ok(())
     }
   }
 }
 
-pub struct Items<T>(mpsc::Receiver<T>);
+/// A trait to process many in-memory files in one invocation, see `--stdin-batch`.
+///
+/// Every line of StdIn is a JSON record like `{"path": "a.ts", "source": "..."}`.
+/// Records are parsed in a separate thread as they arrive and sent to the consumer
+/// through a bounded channel, so memory does not grow with the length of the stream.
+/// Malformed records are reported as error records, and a summary record is printed
+/// after StdIn is closed.
+pub trait StdInBatchWorker: Worker {
+  /// Parse the source as the file at path. Returns None if nothing matches.
+  fn parse_record(&self, path: PathBuf, src: String) -> Result<Option<Self::Item>>;
+  /// Print an error or summary record with the printer of the results, see [Printer::print_record].
+  fn print_record(&self, record: &BatchOutput) -> Result<()>;
+
+  fn run_std_in_batch(self) -> Result<()>
+  where
+    Self: Sized + 'static,
+  {
+    run_batch(Arc::new(self))
+  }
+}
+
+pub struct Items<T>(Box<dyn Iterator<Item = T>>);
 impl<T> Iterator for Items<T> {
   type Item = T;
   fn next(&mut self) -> Option<Self::Item> {
-    if let Ok(match_result) = self.0.recv() {
-      Some(match_result)
-    } else {
-      None
-    }
+    self.0.next()
   }
 }
-impl<T> Items<T> {
-  fn once(t: T) -> Result<Self> {
-    let (tx, rx) = mpsc::channel();
-    // use write to avoid send/sync trait bound
-    match tx.send(t) {
-      Ok(_) => (),
-      Err(e) => return Err(anyhow!(e.to_string())),
-    };
-    Ok(Items(rx))
+impl<T: 'static> Items<T> {
+  fn new(rx: mpsc::Receiver<T>) -> Self {
+    Items(Box::new(rx.into_iter()))
+  }
+  fn once(t: T) -> Self {
+    Items(Box::new(std::iter::once(t)))
   }
 }
 
-fn filter_result(result: Result<DirEntry, ignore::Error>) -> Option<PathBuf> {
+fn filter_result(result: Result<DirEntry, ignore::Error>, trace: &FileTrace) -> Option<DirEntry> {
   let entry = match result {
     Ok(entry) => entry,
     Err(err) => {
-      eprintln!("ERROR: {}", err);
+      trace.add_io_error(format!("ERROR: {err}"));
       return None;
     }
   };
   if !entry.file_type()?.is_file() {
     return None;
   }
-  let path = entry.into_path();
+  Some(entry)
+}
+
+fn entry_path(entry: &DirEntry) -> &Path {
+  let path = entry.path();
   // TODO: is it correct here? see https://github.com/ast-grep/ast-grep/issues/1343
-  match path.strip_prefix("./") {
-    Ok(p) => Some(p.to_path_buf()),
-    Err(_) => Some(path),
+  path.strip_prefix("./").unwrap_or(path)
+}
+
+fn should_skip(entry: &DirEntry, input: &InputArgs) -> Option<SkipReason> {
+  // depth 0 means the file is passed explicitly in command line
+  let is_explicit = entry.depth() == 0;
+  // a file without metadata is not skipped by size, but may still be minified
+  let size = input
+    .max_file_size
+    .and_then(|max_size| Some((entry.metadata().ok()?.len(), max_size)));
+  if let Some((size, max_size)) = size {
+    if size > max_size {
+      if is_explicit {
+        eprintln!(
+          "⚠️  {} is skipped because its size {size} bytes exceeds --max-file-size {max_size}.",
+          entry_path(entry).display()
+        );
+      }
+      return Some(SkipReason::TooLarge(size));
+    }
+  }
+  // minified heuristic never applies to explicit paths
+  if input.skip_minified && !is_explicit && is_minified(entry.path(), input) {
+    return Some(SkipReason::Minified);
   }
+  None
+}
+
+const MINIFIABLE_EXTENSIONS: &[&str] = &["js", "mjs", "cjs", "ts", "mts", "cts", "css"];
+// sample size to detect minified file
+const MINIFIED_SAMPLE_SIZE: usize = 64 * 1024;
+
+fn is_minified(path: &Path, input: &InputArgs) -> bool {
+  let Some(ext) = path.extension().and_then(|e| e.to_str()) else {
+    return false;
+  };
+  if !MINIFIABLE_EXTENSIONS.contains(&ext) {
+    return false;
+  }
+  let Ok(file) = File::open(path) else {
+    return false;
+  };
+  let mut sample = Vec::with_capacity(MINIFIED_SAMPLE_SIZE);
+  if file
+    .take(MINIFIED_SAMPLE_SIZE as u64)
+    .read_to_end(&mut sample)
+    .is_err()
+  {
+    return false;
+  }
+  is_minified_sample(&sample, input.minified_line_threshold)
+}
+
+fn is_minified_sample(sample: &[u8], line_threshold: usize) -> bool {
+  let newlines = sample.iter().filter(|&&b| b == b'\n').count();
+  if newlines == 0 {
+    // a full sample without any newline, or a short single line that is too long
+    return sample.len() == MINIFIED_SAMPLE_SIZE || sample.len() > line_threshold;
+  }
+  sample.len() / newlines > line_threshold
+}
+
+/// The walker does not descend into a directory on another file system with
+/// --one-file-system. Such a directory is detected by comparing the device of
+/// it and its parent, which is always on the file system of the root path.
+#[cfg(unix)]
+fn crosses_file_system(entry: &DirEntry) -> bool {
+  use std::os::unix::fs::MetadataExt;
+  if entry.depth() == 0 || !entry.file_type().map_or(false, |t| t.is_dir()) {
+    return false;
+  }
+  let (Some(parent), Ok(meta)) = (entry.path().parent(), entry.metadata()) else {
+    return false;
+  };
+  parent.metadata().map_or(false, |p| p.dev() != meta.dev())
+}
+
+// device numbers are not exposed by std on other platforms
+#[cfg(not(unix))]
+fn crosses_file_system(_entry: &DirEntry) -> bool {
+  false
 }
 
 fn run_worker<W: PathWorker + ?Sized + 'static>(worker: Arc<W>) -> Result<()> {
   let (tx, rx) = mpsc::channel();
   let w = worker.clone();
   let walker = worker.build_walk()?;
+  let excluder = worker.get_input().force_excluder()?.map(Arc::new);
   // walker run will block the thread
   std::thread::spawn(move || {
     let tx = tx;
     walker.run(|| {
       let tx = tx.clone();
       let w = w.clone();
+      let excluder = excluder.clone();
       Box::new(move |result| {
-        let Some(p) = filter_result(result) else {
+        if let (Ok(entry), Some(excluder)) = (&result, &excluder) {
+          // depth 0 means the path is passed explicitly in command line
+          let p = entry_path(entry);
+          if entry.depth() == 0 && excluder.is_excluded(p) {
+            w.get_trace().skip_file(p, SkipReason::ForceExcluded);
+            return WalkState::Skip;
+          }
+        }
+        if let Ok(entry) = &result {
+          if w.get_input().one_file_system && crosses_file_system(entry) {
+            w.get_trace().skip_dir(entry_path(entry));
+            return WalkState::Continue;
+          }
+        }
+        let Some(entry) = filter_result(result, w.get_trace()) else {
           return WalkState::Continue;
         };
         let stats = w.get_trace();
         stats.add_scanned();
-        let Some(items) = w.produce_item(&p) else {
+        let p = entry_path(&entry);
+        if let Some(lang) = SgLang::from_lang_globs(p) {
+          stats.add_lang_glob_file(lang.to_string());
+        }
+        if let Some(reason) = should_skip(&entry, w.get_input()) {
+          stats.skip_file(p, reason);
+          return WalkState::Continue;
+        }
+        let Some(items) = w.produce_item(p) else {
           stats.add_skipped();
           return WalkState::Continue;
         };
@@ -130,5 +259,112 @@ fn run_worker<W: PathWorker + ?Sized + 'static>(worker: Arc<W>) -> Result<()> {
       })
     });
   });
-  worker.consume_items(Items(rx))
+  worker.consume_items(Items::new(rx))
+}
+
+/// records parsed ahead of the consumer
+const BATCH_CHANNEL_SIZE: usize = 64;
+
+#[derive(Deserialize)]
+struct BatchRecord {
+  path: PathBuf,
+  source: String,
+}
+
+enum BatchMessage<T> {
+  Item(T),
+  Error(BatchError),
+}
+
+fn run_batch<W: StdInBatchWorker + ?Sized + 'static>(worker: Arc<W>) -> Result<()> {
+  let (tx, rx) = mpsc::sync_channel(BATCH_CHANNEL_SIZE);
+  let w = worker.clone();
+  let producer = std::thread::spawn(move || -> Result<BatchSummary> {
+    let mut summary = BatchSummary::default();
+    let mut stdin = std::io::stdin().lock();
+    let mut buf = vec![];
+    let mut line = 0;
+    loop {
+      buf.clear();
+      if stdin
+        .read_until(b'\n', &mut buf)
+        .context("cannot read StdIn")?
+        == 0
+      {
+        break;
+      }
+      line += 1;
+      if buf.iter().all(u8::is_ascii_whitespace) {
+        continue;
+      }
+      summary.records += 1;
+      let message = match serde_json::from_slice::<BatchRecord>(&buf) {
+        Ok(BatchRecord { path, source }) => match w.parse_record(path.clone(), source) {
+          Ok(Some(item)) => {
+            summary.matched += 1;
+            BatchMessage::Item(item)
+          }
+          Ok(None) => continue,
+          Err(e) => BatchMessage::Error(BatchError {
+            line,
+            path: Some(path),
+            error: e.to_string(),
+          }),
+        },
+        Err(e) => BatchMessage::Error(BatchError {
+          line,
+          path: None,
+          error: format!("invalid record: {e}"),
+        }),
+      };
+      if matches!(message, BatchMessage::Error(_)) {
+        summary.errors += 1;
+      }
+      // the consumer has stopped
+      if tx.send(message).is_err() {
+        break;
+      }
+    }
+    Ok(summary)
+  });
+  // error records are printed by the consumer thread, between the output of other records.
+  // The consumer stops at the first error of printing, which also stops the producer.
+  let print_error = Rc::new(RefCell::new(None));
+  let w = worker.clone();
+  let failed = print_error.clone();
+  let items = rx.into_iter().filter_map(move |message| match message {
+    BatchMessage::Item(item) => Some(Some(item)),
+    BatchMessage::Error(error) => match w.print_record(&BatchOutput::Error(error)) {
+      Ok(()) => None,
+      Err(e) => {
+        *failed.borrow_mut() = Some(e);
+        Some(None)
+      }
+    },
+  });
+  // findings of error severity fail the scan, but the summary is still printed
+  let result = worker.consume_items(Items(Box::new(items.map_while(|i| i))));
+  let summary = producer.join().expect("batch reader should not panic")?;
+  if let Some(e) = print_error.take() {
+    return Err(e);
+  }
+  worker.print_record(&BatchOutput::Summary(summary))?;
+  result
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  #[test]
+  fn test_minified_sample() {
+    let normal = "function a() {\n  return 1\n}\n".repeat(100);
+    assert!(!is_minified_sample(normal.as_bytes(), 500));
+    let minified = "function a(){return 1};".repeat(100);
+    assert!(is_minified_sample(minified.as_bytes(), 500));
+    let long_lines = format!("{}\n", "a".repeat(600)).repeat(10);
+    assert!(is_minified_sample(long_lines.as_bytes(), 500));
+    assert!(!is_minified_sample(long_lines.as_bytes(), 1000));
+    assert!(!is_minified_sample(b"short()", 500));
+  }
 }
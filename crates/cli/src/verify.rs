@@ -1,147 +1,367 @@
 mod case_result;
+mod coverage;
+mod diagnostic;
+mod diff;
 mod find_file;
+mod json_report;
+mod junit_report;
 mod reporter;
 mod snapshot;
+mod stale;
 mod test_case;
+mod watch;
 
-use crate::config::{find_rules, register_custom_language};
+use crate::config::{find_rule_paths, find_rules, register_custom_language};
 use crate::lang::SgLang;
-use crate::utils::ErrorContext;
+use crate::print::{ColorArg, ColorChoice, ColorStream, JsonStyle};
+use crate::utils::{thread_count, ErrorContext, RuleOverwrite, RuleTrace};
 use anyhow::{anyhow, Result};
-use ast_grep_config::RuleCollection;
+use ast_grep_config::{suggest_closest, RuleCollection};
 use ast_grep_core::{Node as SgNode, StrDoc};
 use clap::Args;
 use regex::Regex;
-use serde_yaml::to_string;
 
+use std::any::Any;
 use std::collections::HashMap;
 use std::io::Write;
+use std::panic::{catch_unwind, AssertUnwindSafe};
 use std::path::PathBuf;
-use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::thread;
+use std::time::Instant;
 
 use case_result::{CaseResult, CaseStatus};
+use coverage::{find_untested_rules, UntestedRule};
 use find_file::TestHarness;
+use json_report::JsonReport;
+use junit_report::{parse_reporter, JunitReport, ReporterKind};
 use reporter::{DefaultReporter, InteractiveReporter, Reporter};
-use snapshot::{SnapshotAction, SnapshotCollection, TestSnapshots};
+use snapshot::{SnapshotCollection, SnapshotStats, SnapshotWriter, TestSnapshots};
+use stale::{find_stale_snapshots, StaleSnapshot};
 use test_case::TestCase;
 
 type Node<'a, L> = SgNode<'a, StrDoc<L>>;
 
-fn parallel_collect<'a, T, R, F>(cases: &'a [T], filter_mapper: F) -> Vec<R>
+/// Run `job` for every item in `threads` threads and keep results in the order of `items`.
+/// Threads pick items one by one so a few slow items do not stall a whole chunk.
+fn parallel_collect<'a, T, R, F>(items: &'a [T], threads: usize, job: F) -> Vec<R>
 where
   T: Sync,
   R: Send,
-  F: FnMut(&'a T) -> Option<R> + Send + Copy,
+  F: Fn(&'a T) -> R + Sync,
 {
-  let threads = std::thread::available_parallelism()
-    .map_or(1, |n| n.get())
-    .min(12);
-  let chunk_size = (cases.len() + threads) / threads;
-  thread::scope(|s| {
-    cases
-      .chunks(chunk_size)
-      .map(|chunk| {
-        s.spawn(move || {
-          chunk
-            .iter()
-            .filter_map(filter_mapper) // apply per case logic
-            .collect::<Vec<_>>() // must collect here eagerly to consume iter in child threads
+  let next = AtomicUsize::new(0);
+  let mut results: Vec<_> = thread::scope(|s| {
+    (0..threads.max(1))
+      .map(|_| {
+        s.spawn(|| {
+          let mut ret = vec![];
+          loop {
+            let i = next.fetch_add(1, Ordering::Relaxed);
+            let Some(item) = items.get(i) else {
+              return ret;
+            };
+            ret.push((i, job(item)));
+          }
         })
       })
       .collect::<Vec<_>>() // must collect here eagerly to enable multi thread
       .into_iter()
-      .flat_map(|sc| sc.join().unwrap())
+      .flat_map(|handle| handle.join().unwrap())
       .collect()
-  })
+  });
+  results.sort_by_key(|(i, _)| *i);
+  results.into_iter().map(|(_, r)| r).collect()
 }
 
-fn run_test_rule_impl<R: Reporter + Send>(arg: TestArg, reporter: R) -> Result<()> {
-  let collections = &find_rules(arg.config.clone(), None)?.0;
+/// Verify every valid and invalid case as a separate job, so that cases within one rule test
+/// also run in parallel. Results are grouped back by rule test in the order of `test_cases`.
+/// None means the rule of the test case is not found.
+/// The message of a panic payload, which is a string for `panic!` with or without format args.
+fn panic_message(payload: Box<dyn Any + Send>) -> String {
+  if let Some(msg) = payload.downcast_ref::<&str>() {
+    msg.to_string()
+  } else if let Some(msg) = payload.downcast_ref::<String>() {
+    msg.clone()
+  } else {
+    "unknown panic".to_string()
+  }
+}
+
+fn verify_test_cases<'a>(
+  test_cases: &'a [TestCase],
+  rules: &RuleCollection<SgLang>,
+  snapshots: Option<&SnapshotCollection>,
+  threads: usize,
+) -> Vec<Option<CaseResult<'a>>> {
+  let rule_configs: Vec<_> = test_cases
+    .iter()
+    .map(|case| rules.get_rule(&case.id))
+    .collect();
+  let jobs: Vec<_> = test_cases
+    .iter()
+    .zip(&rule_configs)
+    .filter_map(|(case, rule)| Some((case, (*rule)?)))
+    .flat_map(|(case, rule)| (0..case.case_count()).map(move |i| (case, rule, i)))
+    .collect();
+  let statuses = parallel_collect(&jobs, threads, |&(case, rule, i)| {
+    // a panicking case should not abort the whole run
+    let verify = AssertUnwindSafe(|| case.verify_case(rule, i, snapshots));
+    catch_unwind(verify).unwrap_or_else(|e| CaseStatus::Panicked(panic_message(e)))
+  });
+  let mut statuses = statuses.into_iter();
+  test_cases
+    .iter()
+    .zip(rule_configs)
+    .map(|(case, rule)| {
+      rule?;
+      let cases = statuses.by_ref().take(case.case_count()).collect();
+      Some(CaseResult {
+        id: &case.id,
+        cases,
+      })
+    })
+    .collect()
+}
+
+/// Report tests whose id is not the id of any loaded rule. Tests of rules that are off are known.
+/// They are an error unless `allow_unused` is set, in which case they are warned and skipped.
+fn check_unknown_test_ids(
+  test_cases: &[TestCase],
+  test_files: &HashMap<String, PathBuf>,
+  collections: &RuleCollection<SgLang>,
+  trace: &RuleTrace,
+  allow_unused: bool,
+) -> Result<()> {
+  let rule_ids = || {
+    collections
+      .iter()
+      .map(|rule| rule.id.as_str())
+      .chain(trace.off_rules.iter().map(String::as_str))
+  };
+  let cwd = std::env::current_dir()?;
+  let mut unknown = vec![];
+  for case in test_cases {
+    let id = &case.id;
+    if rule_ids().any(|rule_id| rule_id == id) {
+      continue;
+    }
+    let path = test_files.get(id).map_or_else(String::new, |path| {
+      let path = path.strip_prefix(&cwd).unwrap_or(path);
+      path.display().to_string()
+    });
+    let suggestion = suggest_closest(id, rule_ids()).map(String::from);
+    unknown.push((id.clone(), path, suggestion));
+  }
+  if unknown.is_empty() {
+    return Ok(());
+  }
+  if !allow_unused {
+    return Err(anyhow!(ErrorContext::UnknownTestIds(unknown)));
+  }
+  for (id, path, suggestion) in unknown {
+    let hint = suggestion.map_or_else(String::new, |s| format!(" Did you mean `{s}`?"));
+    eprintln!("Warning: test `{id}` in {path} has no rule and is skipped.{hint}");
+  }
+  Ok(())
+}
+
+fn run_test_rule_impl<R: Reporter>(arg: TestArg, mut reporter: R) -> Result<()> {
+  let (collections, trace) = &arg.load_rules()?;
+  let off_rules = &trace.off_rules;
+  let check_coverage = arg.coverage || arg.error_on_uncovered;
+  let rule_paths = if check_coverage {
+    Some(find_rule_paths(arg.config.clone())?)
+  } else {
+    None
+  };
+  let case_filter = arg.case.as_deref();
+  let regex_filter = arg.regex_filter()?;
+  let regex_filter = regex_filter.as_ref();
   let TestHarness {
     test_cases,
     snapshots,
     path_map,
-  } = if let Some(test_dirname) = arg.test_dir {
-    let snapshot_dirname = arg.snapshot_dir.as_deref();
-    TestHarness::from_dir(&test_dirname, snapshot_dirname, arg.filter.as_ref())?
+    snapshot_files,
+    test_files,
+    filtered_out,
+  } = arg.load_harness(regex_filter, case_filter)?;
+  check_unknown_test_ids(
+    &test_cases,
+    &test_files,
+    collections,
+    trace,
+    arg.allow_unused_tests,
+  )?;
+  let missing_id = arg
+    .rule_ids
+    .iter()
+    .find(|id| !test_cases.iter().any(|case| &case.id == *id));
+  if let Some(id) = missing_id {
+    let all_cases = arg.load_harness(None, None)?.test_cases;
+    let all_ids = || all_cases.iter().map(|case| case.id.as_str());
+    if !all_ids().any(|test_id| test_id == id) {
+      let suggestion = suggest_closest(id, all_ids()).map(String::from);
+      return Err(anyhow!(ErrorContext::TestIdNotFound(
+        id.clone(),
+        suggestion
+      )));
+    }
+  }
+  let is_filtered = regex_filter.is_some() || case_filter.is_some();
+  if is_filtered && test_cases.is_empty() {
+    return Err(anyhow!(ErrorContext::NoTestMatched(filtered_out)));
+  }
+  let filtered_out = is_filtered.then_some(filtered_out);
+  let untested = if let Some(rules) = rule_paths {
+    let cwd = std::env::current_dir()?;
+    Some(find_untested_rules(rules, &test_cases, &cwd))
   } else {
-    TestHarness::from_config(arg.config, arg.filter.as_ref())?
+    None
   };
+  let stale = (arg.prune_snapshots || arg.list_stale_snapshots)
+    .then(|| find_stale_snapshots(&test_cases, &snapshots, &snapshot_files));
   let snapshots = (!arg.skip_snapshot_tests).then_some(snapshots);
-  let reporter = &Arc::new(Mutex::new(reporter));
-  {
-    reporter.lock().unwrap().before_report(&test_cases)?;
+  reporter.before_report(&test_cases)?;
+  let threads = thread_count(arg.threads);
+  let start = Instant::now();
+  let verified = verify_test_cases(&test_cases, collections, snapshots.as_ref(), threads);
+  let elapsed = start.elapsed();
+  let mut results = vec![];
+  let mut skipped_off = vec![];
+  for (case, result) in test_cases.iter().zip(verified) {
+    match result {
+      Some(result) => results.push(result),
+      None if off_rules.contains(&case.id) => skipped_off.push(case.id.as_str()),
+      // unknown test ids are reported before verification
+      None => (),
+    }
   }
 
-  let check_one_case = |case| {
-    let result = verify_test_case_simple(case, collections, snapshots.as_ref());
-    if result.is_none() {
-      let mut reporter = reporter.lock().unwrap();
+  let should_update = arg.update_all || arg.interactive;
+  let mut writer = snapshots
+    .filter(|_| should_update)
+    .map(|snapshots| SnapshotWriter::new(snapshots, path_map));
+  reporter.report_failed_cases(&mut results, writer.as_mut())?;
+  reporter.report_summaries(&results)?;
+  if let Some(writer) = writer {
+    let stats = writer.finish();
+    let SnapshotStats {
+      created,
+      updated,
+      untouched,
+    } = stats;
+    let output = reporter.get_output();
+    writeln!(
+      output,
+      "Snapshots: {created} created, {updated} updated, {untouched} untouched."
+    )?;
+  }
+  if let Some(untested) = &untested {
+    reporter.report_coverage(untested)?;
+  }
+  if arg.include_off && !off_rules.is_empty() {
+    let count = off_rules.len();
+    writeln!(
+      reporter.get_output(),
+      "Included {count} rule(s) with severity off."
+    )?;
+  } else if !skipped_off.is_empty() {
+    writeln!(
+      reporter.get_output(),
+      "Skipped {} rule(s) with severity off: {}. Use --include-off to test them.",
+      skipped_off.len(),
+      skipped_off.join(", ")
+    )?;
+  }
+  writeln!(
+    reporter.get_output(),
+    "Finished in {:.2}s with {threads} thread(s).",
+    elapsed.as_secs_f64()
+  )?;
+  let (passed, message) = reporter.after_report(&results, filtered_out)?;
+  if let Some(style) = arg.json {
+    let report = JsonReport::new(
+      &test_cases,
+      &results,
+      !arg.skip_snapshot_tests,
+      filtered_out,
+      elapsed,
+      untested.as_deref(),
+    );
+    report.print(std::io::stdout(), style)?;
+  }
+  if let Some(ReporterKind::Junit(path)) = &arg.reporter {
+    let report = JunitReport::new(&test_cases, &results, !arg.skip_snapshot_tests, elapsed);
+    match path {
+      Some(path) => report.write_to(path)?,
+      None => print!("{}", report.to_xml()),
+    }
+  }
+  if let Some(stale) = &stale {
+    report_stale_snapshots(reporter.get_output(), stale)?;
+  }
+  if !passed {
+    if arg.prune_snapshots {
       let output = reporter.get_output();
-      writeln!(output, "Configuration not found! {}", case.id).unwrap();
+      writeln!(
+        output,
+        "Stale snapshots are not pruned because some tests failed."
+      )?;
     }
-    result
-  };
-  let mut results = parallel_collect(&test_cases, check_one_case);
-  let mut reporter = reporter.lock().unwrap();
-
-  reporter.report_failed_cases(&mut results)?;
-  let action = reporter.collect_snapshot_action();
-  apply_snapshot_action(action, &results, snapshots, path_map)?;
-  reporter.report_summaries(&results)?;
-  let (passed, message) = reporter.after_report(&results)?;
-  if passed {
-    writeln!(reporter.get_output(), "{message}",)?;
-    Ok(())
-  } else {
-    Err(anyhow!(ErrorContext::TestFail(message)))
+    return Err(anyhow!(ErrorContext::TestFail(message)));
+  }
+  if let Some(stale) = stale.filter(|_| arg.prune_snapshots) {
+    prune_stale_snapshots(reporter.get_output(), &stale)?;
+  }
+  writeln!(reporter.get_output(), "{message}",)?;
+  match untested {
+    Some(untested) if arg.error_on_uncovered && !untested.is_empty() => {
+      Err(anyhow!(ErrorContext::UncoveredRules(untested.len())))
+    }
+    _ => Ok(()),
   }
 }
 
-fn apply_snapshot_action(
-  action: SnapshotAction,
-  results: &[CaseResult],
-  snapshots: Option<SnapshotCollection>,
-  path_map: HashMap<String, PathBuf>,
-) -> Result<()> {
-  let Some(snapshots) = snapshots else {
-    return Ok(());
-  };
-  let Some(merged) = action.update_snapshot_collection(snapshots, results) else {
-    return Ok(());
-  };
-  write_merged_to_disk(merged, path_map)
-}
-
-fn write_merged_to_disk(
-  merged: SnapshotCollection,
-  path_map: HashMap<String, PathBuf>,
-) -> Result<()> {
-  for (id, snaps) in merged {
-    let path = &path_map[&id];
-    if !path.exists() {
-      std::fs::create_dir(path)?;
+fn report_stale_snapshots(output: &mut impl Write, stale: &[StaleSnapshot]) -> Result<()> {
+  let count: usize = stale.iter().map(|s| s.sources.len()).sum();
+  writeln!(
+    output,
+    "Stale snapshots: {count} snapshot(s) in {} file(s).",
+    stale.len()
+  )?;
+  let cwd = std::env::current_dir()?;
+  for snapshot in stale {
+    let path = snapshot.display_path(&cwd);
+    let whole = if snapshot.whole_file { " (all)" } else { "" };
+    writeln!(output, "  {}{whole}", path.display())?;
+    for source in &snapshot.sources {
+      writeln!(
+        output,
+        "    - {}",
+        source.lines().next().unwrap_or_default()
+      )?;
     }
-    let file = path.join(format!("{id}-snapshot.yml"));
-    std::fs::write(file, to_string(&snaps)?)?;
   }
   Ok(())
 }
 
-fn verify_test_case_simple<'a>(
-  test_case: &'a TestCase,
-  rules: &RuleCollection<SgLang>,
-  snapshots: Option<&SnapshotCollection>,
-) -> Option<CaseResult<'a>> {
-  let rule_config = rules.get_rule(&test_case.id)?;
-  let test_case = if let Some(snapshots) = snapshots {
-    let snaps = snapshots.get(&test_case.id);
-    test_case.verify_with_snapshot(rule_config, snaps)
-  } else {
-    test_case.verify_rule(rule_config)
-  };
-  Some(test_case)
+fn prune_stale_snapshots(output: &mut impl Write, stale: &[StaleSnapshot]) -> Result<()> {
+  let cwd = std::env::current_dir()?;
+  for snapshot in stale {
+    let path = snapshot.display_path(&cwd);
+    if snapshot.prune()? {
+      writeln!(output, "Removed snapshot file {}.", path.display())?;
+    } else {
+      let count = snapshot.sources.len();
+      writeln!(
+        output,
+        "Removed {count} snapshot(s) from {}.",
+        path.display()
+      )?;
+    }
+  }
+  Ok(())
 }
 
 // for result in summary {
@@ -156,14 +376,20 @@ fn verify_test_case_simple<'a>(
 
 #[derive(Args)]
 pub struct TestArg {
+  /// Only run the test configs of these rule ids.
+  #[clap(value_name = "RULE_ID", conflicts_with = "filter")]
+  rule_ids: Vec<String>,
   /// Path to the root ast-grep config YAML
   #[clap(short, long)]
   config: Option<PathBuf>,
   /// the directories to search test YAML files
   #[clap(short, long)]
   test_dir: Option<PathBuf>,
-  /// Specify the directory name storing snapshots. Default to __snapshots__.
-  #[clap(long)]
+  /// The directory storing snapshots, relative to the current directory.
+  ///
+  /// It takes precedence over `snapshotDir` in test configs.
+  /// Default to `__snapshots__` under the test directory.
+  #[clap(long, value_name = "PATH")]
   snapshot_dir: Option<PathBuf>,
   /// Only check if the test code is valid, without checking rule output.
   /// Turn it on when you want to ignore the output of rules.
@@ -171,29 +397,196 @@ pub struct TestArg {
   #[clap(long, conflicts_with = "update_all")]
   skip_snapshot_tests: bool,
   /// Update the content of all snapshots that have changed in test.
-  /// Conflicts with --skip-snapshot-tests.
-  #[clap(short = 'U', long)]
+  /// Snapshots without changes are not rewritten. Conflicts with --skip-snapshot-tests.
+  #[clap(short = 'U', long, visible_alias = "update-snapshots")]
   update_all: bool,
-  /// Start an interactive review to update snapshots selectively
-  #[clap(short, long)]
+  /// Start an interactive review to update snapshots selectively.
+  /// Accepted snapshots are written immediately and rejected ones are reported as failures.
+  /// Requires a terminal; use --update-snapshots in CI instead.
+  #[clap(short, long, conflicts_with_all = ["update_all", "skip_snapshot_tests"])]
   interactive: bool,
-  /// Only run rule test cases that matches REGEX.
+  /// Only run rule tests whose rule id matches REGEX.
   #[clap(short, long, value_name = "REGEX")]
   filter: Option<Regex>,
+  /// Only run valid/invalid cases whose source code contains SUBSTRING.
+  #[clap(long, value_name = "SUBSTRING")]
+  case: Option<String>,
+  /// Report rules in `ruleDirs` that have no valid or invalid test case.
+  #[clap(long, conflicts_with_all = ["rule_ids", "filter", "case"])]
+  coverage: bool,
+  /// Exit with non-zero code if any rule has no test case. Implies --coverage.
+  #[clap(long, conflicts_with_all = ["rule_ids", "filter", "case"])]
+  error_on_uncovered: bool,
+  /// Remove snapshots and snapshot files that no test case refers to.
+  /// Snapshots are only pruned when all tests pass.
+  #[clap(long, conflicts_with_all = ["rule_ids", "filter", "case", "list_stale_snapshots"])]
+  prune_snapshots: bool,
+  /// List snapshots that no test case refers to, without removing them.
+  #[clap(long, conflicts_with_all = ["rule_ids", "filter", "case"])]
+  list_stale_snapshots: bool,
+  /// Run tests again when rule, util or test files change, until Ctrl-C is pressed.
+  ///
+  /// Only tests of the changed rules run again. Snapshots are not updated unless
+  /// --update-snapshots is also given.
+  #[clap(
+    short,
+    long,
+    conflicts_with_all = [
+      "interactive", "json", "reporter", "coverage", "error_on_uncovered",
+      "prune_snapshots", "list_stale_snapshots",
+    ]
+  )]
+  watch: bool,
+  /// Warn about tests whose id matches no rule instead of failing.
+  /// Useful when rules are being renamed or removed.
+  #[clap(long)]
+  allow_unused_tests: bool,
+  /// Test rules with severity `off` as well.
+  /// Without this flag, tests of rules that are off are skipped and listed in the summary.
+  #[clap(long)]
+  include_off: bool,
+  /// Controls output color of the report, including snapshot and fix diffs.
+  ///
+  /// Changed words are highlighted within changed lines when color is used.
+  /// Without color, diffs only have `-`/`+` line markers, e.g. in CI logs.
+  #[clap(long, default_value = "auto", value_name = "WHEN")]
+  color: ColorArg,
+  /// Set the approximate number of threads to use.
+  ///
+  /// Every valid and invalid case runs as a separate job. A value of 0
+  /// (which is the default) causes ast-grep to choose the thread count using
+  /// heuristics. Test output is always printed in a stable order.
+  #[clap(short = 'j', long, default_value = "0", value_name = "NUM")]
+  threads: usize,
+  /// Output test results in structured JSON.
+  ///
+  /// The JSON document contains the results of every valid and invalid case per rule,
+  /// snapshot status and a summary. The human readable report is printed to stderr instead.
+  /// Every style prints a single document; `stream` prints it in one line like `compact`.
+  /// You can pass optional value to this flag by using `--json=<STYLE>` syntax.
+  /// Note, the json flag must use `=` to specify its value.
+  #[clap(
+      long,
+      conflicts_with = "interactive",
+      value_name="STYLE",
+      num_args(0..=1),
+      require_equals = true,
+      default_missing_value = "pretty"
+  )]
+  json: Option<JsonStyle>,
+  /// Choose how test results are reported: `default` or `junit[=PATH]`.
+  ///
+  /// `junit` writes JUnit XML where every rule is a testsuite and every valid, invalid and
+  /// snapshot check is a testcase. The XML is written to PATH if given, or printed to stdout
+  /// with all other output suppressed.
+  #[clap(
+    long,
+    value_name = "REPORTER",
+    value_parser = parse_reporter,
+    conflicts_with_all = ["interactive", "json"]
+  )]
+  reporter: Option<ReporterKind>,
+}
+
+impl TestArg {
+  pub fn color(&self) -> ColorArg {
+    self.color
+  }
+
+  fn load_rules(&self) -> Result<(RuleCollection<SgLang>, RuleTrace)> {
+    let mut overwrite = RuleOverwrite::default();
+    overwrite.include_off = self.include_off;
+    find_rules(self.config.clone(), &overwrite)
+  }
+
+  fn load_harness(
+    &self,
+    regex_filter: Option<&Regex>,
+    case_filter: Option<&str>,
+  ) -> Result<TestHarness> {
+    let snapshot_dir = self.snapshot_dir.as_deref();
+    if let Some(test_dirname) = &self.test_dir {
+      TestHarness::from_dir(test_dirname, snapshot_dir, regex_filter, case_filter)
+    } else {
+      let config = self.config.clone();
+      TestHarness::from_config(config, snapshot_dir, regex_filter, case_filter)
+    }
+  }
+
+  /// `--filter`, or a regex matching the positional rule ids exactly.
+  fn regex_filter(&self) -> Result<Option<Regex>> {
+    if self.rule_ids.is_empty() {
+      return Ok(self.filter.clone());
+    }
+    let ids: Vec<_> = self.rule_ids.iter().map(|id| regex::escape(id)).collect();
+    Ok(Some(Regex::new(&format!("^(?:{})$", ids.join("|")))?))
+  }
+}
+
+/// Test cases of a rule, used as examples in the generated docs.
+pub struct RuleExamples {
+  pub valid: Vec<String>,
+  /// Invalid code and its expected fixed code, from the test case or its snapshot.
+  pub invalid: Vec<(String, Option<String>)>,
+}
+
+/// Find test cases of all rules in the project, keyed by rule id.
+pub fn find_examples(config: Option<PathBuf>) -> Result<HashMap<String, RuleExamples>> {
+  let harness = TestHarness::from_config(config, None, None, None)?;
+  let snapshots = &harness.snapshots;
+  let examples = harness.test_cases.into_iter().map(|case| {
+    let snapshot = snapshots.get(&case.id);
+    let invalid = case.invalid.into_iter().map(|invalid| {
+      let snapshot_fixed = || {
+        let snapshot = snapshot?.snapshots.get(invalid.snapshot_key())?;
+        snapshot.fixed.clone()
+      };
+      let fixed = invalid.fixed.clone().or_else(snapshot_fixed);
+      (invalid.code, fixed)
+    });
+    let examples = RuleExamples {
+      valid: case.valid,
+      invalid: invalid.collect(),
+    };
+    (case.id, examples)
+  });
+  Ok(examples.collect())
 }
 
 pub fn run_test_rule(arg: TestArg) -> Result<()> {
   register_custom_language(arg.config.clone())?;
+  if arg.watch {
+    return watch::watch_tests(arg);
+  }
   if arg.interactive {
+    if !atty::is(atty::Stream::Stdin) || !atty::is(atty::Stream::Stdout) {
+      return Err(anyhow!(ErrorContext::TestNotInteractive));
+    }
     let reporter = InteractiveReporter {
       output: std::io::stdout(),
       should_accept_all: false,
+      color: arg.color.should_use_color(),
+    };
+    run_test_rule_impl(arg, reporter)
+  } else if arg.reporter == Some(ReporterKind::Junit(None)) {
+    let reporter = DefaultReporter {
+      output: std::io::sink(),
+      update_all: arg.update_all,
+      color: arg.color.should_use_color(),
+    };
+    run_test_rule_impl(arg, reporter)
+  } else if arg.json.is_some() {
+    let reporter = DefaultReporter {
+      output: std::io::stderr(),
+      update_all: arg.update_all,
+      color: arg.color.resolve(ColorStream::Stderr) != ColorChoice::Never,
     };
     run_test_rule_impl(arg, reporter)
   } else {
     let reporter = DefaultReporter {
       output: std::io::stdout(),
       update_all: arg.update_all,
+      color: arg.color.should_use_color(),
     };
     run_test_rule_impl(arg, reporter)
   }
@@ -251,6 +644,15 @@ rule:
     }
   }
 
+  fn verify_test_case_simple<'a>(
+    test_case: &'a TestCase,
+    rules: &RuleCollection<SgLang>,
+    snapshots: Option<&SnapshotCollection>,
+  ) -> Option<CaseResult<'a>> {
+    let test_cases = std::slice::from_ref(test_case);
+    verify_test_cases(test_cases, rules, snapshots, 1).pop()?
+  }
+
   fn test_case_result(status: CaseStatus) -> Option<CaseResult> {
     Some(CaseResult {
       id: TEST_RULE,
@@ -258,6 +660,44 @@ rule:
     })
   }
 
+  #[test]
+  fn test_parallel_collect_order() {
+    let items: Vec<_> = (0..100).collect();
+    let doubled = parallel_collect(&items, 4, |i| i * 2);
+    let expected: Vec<_> = (0..100).map(|i| i * 2).collect();
+    assert_eq!(doubled, expected);
+    assert!(parallel_collect(&[] as &[i32], 4, |i| *i).is_empty());
+  }
+
+  #[test]
+  fn test_verify_test_cases() {
+    let rule = always_report_rule();
+    let cases = [
+      TestCase {
+        id: TEST_RULE.into(),
+        valid: vec!["1".into(), "2".into()],
+        invalid: vec!["3".into()],
+      },
+      TestCase {
+        id: "no-such-rule".into(),
+        valid: vec!["4".into()],
+        invalid: vec![],
+      },
+      invalid_case(),
+    ];
+    let results = verify_test_cases(&cases, &rule, None, 3);
+    assert_eq!(results.len(), 3);
+    let first = results[0].as_ref().expect("should find rule");
+    let expected = vec![
+      CaseStatus::Noisy("1"),
+      CaseStatus::Noisy("2"),
+      CaseStatus::Reported,
+    ];
+    assert_eq!(first.cases, expected);
+    assert!(results[1].is_none());
+    assert_eq!(results[2], test_case_result(CaseStatus::Reported));
+  }
+
   #[test]
   fn test_validated() {
     let rule = never_report_rule();
@@ -306,8 +746,10 @@ rule:
     let reporter = DefaultReporter {
       output: Buffer::no_color(),
       update_all: false,
+      color: false,
     };
     let arg = TestArg {
+      rule_ids: vec![],
       config: None,
       interactive: false,
       skip_snapshot_tests: true,
@@ -315,6 +757,18 @@ rule:
       test_dir: None,
       update_all: false,
       filter: None,
+      case: None,
+      coverage: false,
+      error_on_uncovered: false,
+      include_off: false,
+      allow_unused_tests: false,
+      color: ColorArg::Never,
+      watch: false,
+      prune_snapshots: false,
+      list_stale_snapshots: false,
+      json: None,
+      reporter: None,
+      threads: 0,
     };
     assert!(run_test_rule_impl(arg, reporter).is_err());
   }
@@ -339,7 +793,7 @@ fix: 'log($B)'";
     let case = TestCase {
       id: TEST_RULE.into(),
       valid: vec![],
-      invalid: vec!["console.log(123)".to_string()],
+      invalid: vec!["console.log(123)".into()],
     };
     let snapshots = SnapshotCollection::new();
     let mut ret = verify_test_case_simple(&case, &rule, Some(&snapshots)).unwrap();
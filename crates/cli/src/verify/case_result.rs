@@ -5,7 +5,8 @@ ast-grep rule test has several concepts.
 Refer to https://ast-grep.github.io/guide/test-rule.html#basic-concepts
 for general review.
 */
-use super::{snapshot::TestSnapshot, SgLang, TestSnapshots};
+use super::diagnostic::{actual_diagnostics, diagnostics_match, ExpectedDiagnostic};
+use super::{snapshot::TestSnapshot, SgLang, TestCase};
 use ast_grep_config::RuleConfig;
 use ast_grep_language::Language;
 
@@ -38,6 +39,22 @@ pub enum CaseStatus<'a> {
   Noisy(&'a str),
   /// Error occurred when applying fix
   Error,
+  /// Verifying the case panicked, with the panic message
+  Panicked(String),
+  /// Fixed code is different from the `fixed` expectation
+  WrongFix {
+    source: &'a str,
+    expected: &'a str,
+    actual: String,
+  },
+  /// `fixed` is expected but the rule has no fix
+  NoFix(&'a str),
+  /// Reported diagnostics are different from the `expected` ones
+  WrongDiagnostics {
+    source: &'a str,
+    expected: Vec<ExpectedDiagnostic>,
+    actual: Vec<ExpectedDiagnostic>,
+  },
 }
 
 impl<'a> CaseStatus<'a> {
@@ -81,6 +98,59 @@ impl<'a> CaseStatus<'a> {
     }
   }
 
+  /// Apply the rule's fix to every match in the case and compare it with `expected`.
+  /// Returns None if the fix is as expected or nothing is matched,
+  /// so that the case can be verified as a plain invalid case.
+  pub fn verify_fixed(
+    rule_config: &RuleConfig<SgLang>,
+    case: &'a str,
+    expected: &'a str,
+  ) -> Option<Self> {
+    let Some(fixer) = &rule_config.matcher.fixer else {
+      return Some(CaseStatus::NoFix(case));
+    };
+    let sg = rule_config.language.ast_grep(case);
+    let edits = sg.root().replace_all(&rule_config.matcher, fixer);
+    if edits.is_empty() {
+      return None;
+    }
+    let mut actual = String::new();
+    let mut start = 0;
+    for edit in edits {
+      actual.push_str(&case[start..edit.position]);
+      actual.push_str(&String::from_utf8_lossy(&edit.inserted_text));
+      start = edit.position + edit.deleted_length;
+    }
+    actual.push_str(&case[start..]);
+    if actual == expected {
+      return None;
+    }
+    Some(CaseStatus::WrongFix {
+      source: case,
+      expected,
+      actual,
+    })
+  }
+
+  /// Compare reported diagnostics with `expected` ones.
+  /// Returns None if they match or nothing is reported,
+  /// so that the case can be verified as a plain invalid case.
+  pub fn verify_diagnostics(
+    rule_config: &RuleConfig<SgLang>,
+    case: &'a str,
+    expected: Vec<ExpectedDiagnostic>,
+  ) -> Option<Self> {
+    let actual = actual_diagnostics(rule_config, case);
+    if actual.is_empty() || diagnostics_match(&expected, &actual) {
+      return None;
+    }
+    Some(CaseStatus::WrongDiagnostics {
+      source: case,
+      expected,
+      actual,
+    })
+  }
+
   pub fn accept(&mut self) -> bool {
     let CaseStatus::Wrong { source, actual, .. } = self else {
       return false;
@@ -117,20 +187,23 @@ impl<'a> CaseResult<'a> {
   pub fn passed(&self) -> bool {
     self.cases.iter().all(CaseStatus::is_pass)
   }
-  pub fn changed_snapshots(&self) -> TestSnapshots {
-    let snapshots = self
-      .cases
-      .iter()
-      .filter_map(|c| match c {
-        CaseStatus::Updated { source, updated } => Some((source.to_string(), updated.clone())),
-        _ => None,
-      })
-      .collect();
-    TestSnapshots {
-      id: self.id.to_string(),
-      snapshots,
-    }
-  }
+}
+
+/// Pair test cases with their results. `results` must be in the same order as `test_cases`
+/// and test cases whose rule is not found have no result.
+pub fn pair_results<'a>(
+  test_cases: &'a [TestCase],
+  results: &'a [CaseResult<'a>],
+) -> Vec<(&'a TestCase, Option<&'a CaseResult<'a>>)> {
+  let mut results = results.iter().peekable();
+  test_cases
+    .iter()
+    .map(|case| {
+      // results borrow ids from test cases, so pointer equality pairs them up
+      let result = results.next_if(|r| std::ptr::eq(r.id, case.id.as_str()));
+      (case, result)
+    })
+    .collect()
 }
 
 #[cfg(test)]
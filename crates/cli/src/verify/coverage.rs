@@ -0,0 +1,75 @@
+use super::TestCase;
+
+use serde::Serialize;
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+/// A rule that has no valid or invalid test case.
+#[derive(Serialize, Debug, PartialEq, Eq)]
+pub struct UntestedRule {
+  pub id: String,
+  pub path: PathBuf,
+}
+
+/// Cross-reference rules with test cases.
+/// A rule is untested if no test config for its id has any valid or invalid case.
+/// Rule paths are reported relative to `base_dir` if possible.
+pub fn find_untested_rules(
+  rules: Vec<(String, PathBuf)>,
+  test_cases: &[TestCase],
+  base_dir: &Path,
+) -> Vec<UntestedRule> {
+  let tested: HashSet<_> = test_cases
+    .iter()
+    .filter(|case| !case.valid.is_empty() || !case.invalid.is_empty())
+    .map(|case| case.id.as_str())
+    .collect();
+  rules
+    .into_iter()
+    .filter(|(id, _)| !tested.contains(id.as_str()))
+    .map(|(id, path)| {
+      let path = path
+        .strip_prefix(base_dir)
+        .map_or_else(|_| path.clone(), Path::to_path_buf);
+      UntestedRule { id, path }
+    })
+    .collect()
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  fn case(id: &str, valid: &[&str]) -> TestCase {
+    TestCase {
+      id: id.into(),
+      valid: valid.iter().map(|s| s.to_string()).collect(),
+      invalid: vec![],
+    }
+  }
+
+  #[test]
+  fn test_find_untested_rules() {
+    let rules = vec![
+      ("tested".to_string(), PathBuf::from("rules/a.yml")),
+      ("empty".to_string(), PathBuf::from("rules/a.yml")),
+      ("missing".to_string(), PathBuf::from("/root/rules/b.yml")),
+    ];
+    let cases = [case("tested", &["a"]), case("empty", &[])];
+    let untested = find_untested_rules(rules, &cases, Path::new("/root"));
+    assert_eq!(
+      untested,
+      vec![
+        UntestedRule {
+          id: "empty".into(),
+          path: PathBuf::from("rules/a.yml"),
+        },
+        UntestedRule {
+          id: "missing".into(),
+          path: PathBuf::from("rules/b.yml"),
+        },
+      ]
+    );
+  }
+}
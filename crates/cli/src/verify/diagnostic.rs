@@ -0,0 +1,229 @@
+/*!
+Expected diagnostics of invalid test cases.
+
+An invalid case can pin down where the rule reports issues, either with explicit
+`expected` entries or with marker comments under the reported code:
+
+```text
+let a = 123
+// ~~~~~~~~~~~ optional message
+```
+
+The comment token and one following space are removed from a marker line,
+and the remaining `~` columns underline the closest preceding non-marker line.
+Lines and columns are one-based and the end column is exclusive.
+*/
+use crate::lang::SgLang;
+
+use ast_grep_config::RuleConfig;
+use ast_grep_language::Language;
+use serde::{Deserialize, Serialize};
+
+use std::fmt::Write as _;
+
+const COMMENT_TOKENS: &[&str] = &["//", "#", "--"];
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ExpectedDiagnostic {
+  pub line: usize,
+  pub column: usize,
+  pub end_line: usize,
+  pub end_column: usize,
+  /// Compared with the rule message only if present.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub message: Option<String>,
+}
+
+impl ExpectedDiagnostic {
+  fn same_span(&self, other: &Self) -> bool {
+    (self.line, self.column, self.end_line, self.end_column)
+      == (other.line, other.column, other.end_line, other.end_column)
+  }
+
+  /// `actual` always has a message, the expectation may leave it out.
+  fn accepts(&self, actual: &Self) -> bool {
+    self.same_span(actual)
+      && self
+        .message
+        .as_ref()
+        .map_or(true, |m| Some(m) == actual.message.as_ref())
+  }
+}
+
+/// Parse `~~~` marker comments in the case source.
+pub fn parse_markers(code: &str) -> Vec<ExpectedDiagnostic> {
+  let mut ret = vec![];
+  let mut target = None;
+  for (i, line) in code.lines().enumerate() {
+    match (parse_marker(line), target) {
+      (Some((column, len, message)), Some(target)) => ret.push(ExpectedDiagnostic {
+        line: target,
+        column: column + 1,
+        end_line: target,
+        end_column: column + len + 1,
+        message,
+      }),
+      _ => target = Some(i + 1),
+    }
+  }
+  ret
+}
+
+/// Returns zero-based column, length and message of a marker line.
+fn parse_marker(line: &str) -> Option<(usize, usize, Option<String>)> {
+  let indent = line.len() - line.trim_start().len();
+  let comment = line[indent..].trim_start_matches(|c| c != ' ' && c != '~');
+  let token = &line[indent..line.len() - comment.len()];
+  if !COMMENT_TOKENS.contains(&token) {
+    return None;
+  }
+  let rest = comment.strip_prefix(' ').unwrap_or(comment);
+  let spaces = rest.len() - rest.trim_start_matches(' ').len();
+  let marker = &rest[spaces..];
+  let tildes = marker.len() - marker.trim_start_matches('~').len();
+  let message = &marker[tildes..];
+  if tildes == 0 || !(message.is_empty() || message.starts_with(char::is_whitespace)) {
+    return None;
+  }
+  let message = message.trim();
+  let message = (!message.is_empty()).then(|| message.to_string());
+  Some((indent + spaces, tildes, message))
+}
+
+/// Every match of the rule in the case with its message.
+pub fn actual_diagnostics(rule_config: &RuleConfig<SgLang>, case: &str) -> Vec<ExpectedDiagnostic> {
+  let sg = rule_config.language.ast_grep(case);
+  sg.root()
+    .find_all(&rule_config.matcher)
+    .map(|nm| {
+      let (line, column) = nm.start_pos();
+      let (end_line, end_column) = nm.end_pos();
+      ExpectedDiagnostic {
+        line: line + 1,
+        column: column + 1,
+        end_line: end_line + 1,
+        end_column: end_column + 1,
+        message: Some(rule_config.get_message(&nm)),
+      }
+    })
+    .collect()
+}
+
+/// Check that every expected diagnostic is reported and nothing else is, regardless of order.
+pub fn diagnostics_match(expected: &[ExpectedDiagnostic], actual: &[ExpectedDiagnostic]) -> bool {
+  if expected.len() != actual.len() {
+    return false;
+  }
+  let mut used = vec![false; actual.len()];
+  // expectations with message are more specific, let them pick first
+  let (with_message, without): (Vec<_>, Vec<_>) =
+    expected.iter().partition(|e| e.message.is_some());
+  with_message.into_iter().chain(without).all(|e| {
+    let found = actual
+      .iter()
+      .enumerate()
+      .position(|(i, a)| !used[i] && e.accepts(a));
+    found.map(|i| used[i] = true).is_some()
+  })
+}
+
+/// Render diagnostics as underlines against the case source.
+pub fn render_diagnostics(source: &str, diagnostics: &[ExpectedDiagnostic]) -> String {
+  let lines: Vec<_> = source.lines().collect();
+  let mut sorted: Vec<_> = diagnostics.iter().collect();
+  sorted.sort_by_key(|d| (d.line, d.column, d.end_line, d.end_column));
+  let mut ret = String::new();
+  if sorted.is_empty() {
+    ret.push_str("  (none)\n");
+  }
+  for d in sorted {
+    let text = lines.get(d.line.saturating_sub(1)).copied().unwrap_or("");
+    let start = d.column.saturating_sub(1);
+    let end = if d.end_line == d.line {
+      d.end_column.saturating_sub(1)
+    } else {
+      text.len()
+    };
+    let width = end.saturating_sub(start).max(1);
+    // writing to String never fails
+    let _ = writeln!(ret, "{:>4} | {text}", d.line);
+    let _ = write!(ret, "     | {}{}", " ".repeat(start), "~".repeat(width));
+    if d.end_line != d.line {
+      let _ = write!(ret, " (to {}:{})", d.end_line, d.end_column);
+    }
+    if let Some(message) = &d.message {
+      let _ = write!(ret, " {message}");
+    }
+    ret.push('\n');
+  }
+  ret
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::verify::test::get_rule_config;
+
+  fn diagnostic(line: usize, column: usize, end_column: usize) -> ExpectedDiagnostic {
+    ExpectedDiagnostic {
+      line,
+      column,
+      end_line: line,
+      end_column,
+      message: None,
+    }
+  }
+
+  #[test]
+  fn test_parse_markers() {
+    let code = "let a = 1\n// ~~~~~~~~~ no let\n  foo(a)\n  //     ~ arg\n#~~";
+    let markers = parse_markers(code);
+    let mut expected = diagnostic(1, 1, 10);
+    expected.message = Some("no let".into());
+    assert_eq!(markers[0], expected);
+    let mut expected = diagnostic(3, 7, 8);
+    expected.message = Some("arg".into());
+    assert_eq!(markers[1], expected);
+    assert_eq!(markers[2], diagnostic(3, 1, 3));
+    assert_eq!(markers.len(), 3);
+    assert!(parse_markers("// ~~~ nothing above").is_empty());
+    assert!(parse_markers("a\n// ~~~a").is_empty());
+  }
+
+  #[test]
+  fn test_actual_diagnostics() {
+    let rule = get_rule_config("pattern: let a = $A");
+    let actual = actual_diagnostics(&rule, "let a = 1\nlet a = 2");
+    assert_eq!(actual.len(), 2);
+    assert_eq!((actual[1].line, actual[1].column), (2, 1));
+    assert_eq!((actual[1].end_line, actual[1].end_column), (2, 10));
+    assert_eq!(actual[1].message.as_deref(), Some("test"));
+  }
+
+  #[test]
+  fn test_diagnostics_match() {
+    let with_message = |line, message: &str| ExpectedDiagnostic {
+      message: Some(message.into()),
+      ..diagnostic(line, 1, 4)
+    };
+    let actual = [with_message(1, "msg"), with_message(2, "other")];
+    // order-insensitive, message is optional
+    let expected = [diagnostic(2, 1, 4), with_message(1, "msg")];
+    assert!(diagnostics_match(&expected, &actual));
+    let expected = [with_message(1, "wrong"), diagnostic(2, 1, 4)];
+    assert!(!diagnostics_match(&expected, &actual));
+    assert!(!diagnostics_match(&[diagnostic(1, 1, 4)], &actual));
+    let expected = [diagnostic(1, 1, 5), diagnostic(2, 1, 4)];
+    assert!(!diagnostics_match(&expected, &actual));
+  }
+
+  #[test]
+  fn test_render_diagnostics() {
+    let mut d = diagnostic(2, 5, 8);
+    d.message = Some("msg".into());
+    let rendered = render_diagnostics("a\nlet abc = 1", &[d]);
+    assert_eq!(rendered, "   2 | let abc = 1\n     |     ~~~ msg\n");
+    assert_eq!(render_diagnostics("a", &[]), "  (none)\n");
+  }
+}
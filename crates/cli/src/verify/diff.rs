@@ -0,0 +1,259 @@
+//! Render differences between expected and actual test output.
+//!
+//! Lines are diffed first and unchanged regions are elided except for a few context lines.
+//! Replaced lines are diffed again word by word so a single changed identifier stands out.
+//! Without color, the output is a plain line diff with `-`/`+` markers.
+
+use ansi_term::{Color, Style};
+use similar::{ChangeTag, DiffOp, DiffTag, TextDiff};
+
+use std::fmt::Write as _;
+
+const CONTEXT: usize = 3;
+/// Replaced lines less similar than this are shown as a line diff without word highlights.
+const WORD_DIFF_RATIO: f32 = 0.5;
+
+struct DiffStyles {
+  header: Style,
+  delete: Style,
+  delete_emphasis: Style,
+  insert: Style,
+  insert_emphasis: Style,
+}
+
+impl DiffStyles {
+  fn new(color: bool) -> Self {
+    if !color {
+      let plain = Style::new();
+      return Self {
+        header: plain,
+        delete: plain,
+        delete_emphasis: plain,
+        insert: plain,
+        insert_emphasis: plain,
+      };
+    }
+    let delete = Style::new().fg(Color::Red);
+    let insert = Style::new().fg(Color::Green);
+    Self {
+      header: Style::new().fg(Color::Blue),
+      delete,
+      delete_emphasis: delete.on(Color::Fixed(225)).bold(),
+      insert,
+      insert_emphasis: insert.on(Color::Fixed(158)).bold(),
+    }
+  }
+}
+
+/// Render the diff from `expected` to `actual`.
+pub fn render_diff(expected: &str, actual: &str, color: bool) -> String {
+  let styles = DiffStyles::new(color);
+  let diff = TextDiff::from_lines(expected, actual);
+  let old_lines: Vec<_> = diff.old_slices().to_vec();
+  let new_lines: Vec<_> = diff.new_slices().to_vec();
+  let mut ret = String::new();
+  for group in diff.grouped_ops(CONTEXT) {
+    let header = hunk_header(&group);
+    // writing to String never fails
+    let _ = writeln!(ret, "{}", styles.header.paint(header));
+    for op in group {
+      let old = old_lines[op.old_range()].concat();
+      let new = new_lines[op.new_range()].concat();
+      match op.tag() {
+        DiffTag::Equal => write_lines(&mut ret, " ", &[(false, old)], Style::new(), Style::new()),
+        DiffTag::Delete => {
+          write_lines(&mut ret, "-", &[(false, old)], styles.delete, styles.delete)
+        }
+        DiffTag::Insert => {
+          write_lines(&mut ret, "+", &[(false, new)], styles.insert, styles.insert)
+        }
+        DiffTag::Replace => write_replace(&mut ret, &old, &new, color, &styles),
+      }
+    }
+  }
+  ret
+}
+
+fn write_replace(ret: &mut String, old: &str, new: &str, color: bool, styles: &DiffStyles) {
+  let words = TextDiff::from_words(old, new);
+  if !color || words.ratio() < WORD_DIFF_RATIO {
+    write_lines(
+      ret,
+      "-",
+      &[(false, old.into())],
+      styles.delete,
+      styles.delete,
+    );
+    write_lines(
+      ret,
+      "+",
+      &[(false, new.into())],
+      styles.insert,
+      styles.insert,
+    );
+    return;
+  }
+  let mut deleted = vec![];
+  let mut inserted = vec![];
+  for change in words.iter_all_changes() {
+    let value = change.value();
+    match change.tag() {
+      ChangeTag::Equal => {
+        push_piece(&mut deleted, false, value);
+        push_piece(&mut inserted, false, value);
+      }
+      ChangeTag::Delete => push_piece(&mut deleted, true, value),
+      ChangeTag::Insert => push_piece(&mut inserted, true, value),
+    }
+  }
+  write_lines(ret, "-", &deleted, styles.delete, styles.delete_emphasis);
+  write_lines(ret, "+", &inserted, styles.insert, styles.insert_emphasis);
+}
+
+/// Merge adjacent words of the same emphasis so that they are painted together.
+fn push_piece(pieces: &mut Vec<(bool, String)>, emphasized: bool, value: &str) {
+  match pieces.last_mut() {
+    Some((last, text)) if *last == emphasized => text.push_str(value),
+    _ => pieces.push((emphasized, value.to_string())),
+  }
+}
+
+/// Write `(emphasized, text)` pieces, prefixing every line with `sign`.
+fn write_lines(
+  ret: &mut String,
+  sign: &str,
+  pieces: &[(bool, String)],
+  style: Style,
+  emphasis: Style,
+) {
+  let mut line_start = true;
+  for (emphasized, text) in pieces {
+    for part in text.split_inclusive('\n') {
+      if line_start {
+        let _ = write!(ret, "{}", style.paint(sign));
+      }
+      let content = part.strip_suffix('\n');
+      let painter = if *emphasized { emphasis } else { style };
+      let _ = write!(ret, "{}", painter.paint(content.unwrap_or(part)));
+      line_start = content.is_some();
+      if line_start {
+        ret.push('\n');
+      }
+    }
+  }
+  if !line_start {
+    ret.push('\n');
+  }
+}
+
+fn hunk_header(group: &[DiffOp]) -> String {
+  let old_start = group[0].old_range().start + 1;
+  let new_start = group[0].new_range().start + 1;
+  let (old_len, new_len) = group.iter().fold((0, 0), |(o, n), op| {
+    (o + op.old_range().len(), n + op.new_range().len())
+  });
+  format!("@@ -{old_start},{old_len} +{new_start},{new_len} @@")
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  const EXPECTED: &str = "\
+id: test-rule
+snapshots:
+  Some(123):
+    labels:
+    - source: Some(123)
+      message: rule reports this call
+      style: primary
+      start: 0
+      end: 9
+";
+
+  #[test]
+  fn test_plain_diff() {
+    let actual = EXPECTED.replace("reports this call", "reports that call");
+    let diff = render_diff(EXPECTED, &actual, false);
+    assert_eq!(
+      diff,
+      "\
+@@ -3,7 +3,7 @@
+   Some(123):
+     labels:
+     - source: Some(123)
+-      message: rule reports this call
++      message: rule reports that call
+       style: primary
+       start: 0
+       end: 9
+"
+    );
+  }
+
+  #[test]
+  fn test_elide_unchanged() {
+    let expected: String = (0..20).map(|i| format!("line {i}\n")).collect();
+    let actual = expected
+      .replace("line 1\n", "line one\n")
+      .replace("line 18\n", "");
+    let diff = render_diff(&expected, &actual, false);
+    assert_eq!(
+      diff,
+      "\
+@@ -1,5 +1,5 @@
+ line 0
+-line 1
++line one
+ line 2
+ line 3
+ line 4
+@@ -16,5 +16,4 @@
+ line 15
+ line 16
+ line 17
+-line 18
+ line 19
+"
+    );
+  }
+
+  #[test]
+  fn test_word_diff() {
+    let diff = render_diff("a b c\n", "a x c\n", true);
+    let delete = Style::new().fg(Color::Red);
+    let insert = Style::new().fg(Color::Green);
+    let expected = format!(
+      "{}\n{}{}{}{}\n{}{}{}{}\n",
+      Style::new().fg(Color::Blue).paint("@@ -1,1 +1,1 @@"),
+      delete.paint("-"),
+      delete.paint("a "),
+      delete.on(Color::Fixed(225)).bold().paint("b"),
+      delete.paint(" c"),
+      insert.paint("+"),
+      insert.paint("a "),
+      insert.on(Color::Fixed(158)).bold().paint("x"),
+      insert.paint(" c"),
+    );
+    assert_eq!(diff, expected);
+  }
+
+  #[test]
+  fn test_dissimilar_lines() {
+    let diff = render_diff("abc def\n", "xyz\n", true);
+    let delete = Style::new().fg(Color::Red);
+    let insert = Style::new().fg(Color::Green);
+    assert!(diff.contains(&format!(
+      "{}{}\n",
+      delete.paint("-"),
+      delete.paint("abc def")
+    )));
+    assert!(diff.contains(&format!("{}{}\n", insert.paint("+"), insert.paint("xyz"))));
+  }
+
+  #[test]
+  fn test_missing_newline() {
+    let diff = render_diff("a", "b", false);
+    assert_eq!(diff, "@@ -1,1 +1,1 @@\n-a\n+b\n");
+  }
+}
@@ -1,5 +1,6 @@
+use super::test_case::InlineTests;
 use super::{SnapshotCollection, TestCase, TestSnapshots};
-use crate::config::{find_config_path_with_default, AstGrepConfig};
+use crate::config::{find_config_path_with_default, find_rule_files, AstGrepConfig, TestConfig};
 use crate::utils::ErrorContext as EC;
 
 use anyhow::{Context, Result};
@@ -7,45 +8,73 @@ use ast_grep_config::from_str;
 use ast_grep_language::config_file_type;
 use ignore::WalkBuilder;
 use regex::Regex;
-use serde_yaml::{with::singleton_map_recursive::deserialize, Deserializer};
+use serde::Deserialize;
+use serde_yaml::{with::singleton_map_recursive::deserialize, Deserializer, Value};
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fs::read_to_string;
 use std::path::{Path, PathBuf};
 
-const SNAPSHOT_DIR: &str = "__snapshots__";
+pub(super) const SNAPSHOT_DIR: &str = "__snapshots__";
 
 #[derive(Default)]
 pub struct TestHarness {
   pub test_cases: Vec<TestCase>,
   pub snapshots: SnapshotCollection,
   pub path_map: HashMap<String, PathBuf>,
+  /// File where the test of every rule id is first found, a test file or a rule file for inline tests.
+  pub test_files: HashMap<String, PathBuf>,
+  /// Snapshot file read for every rule id.
+  pub snapshot_files: HashMap<String, PathBuf>,
+  /// Number of rule tests excluded by the rule id regex or the case substring.
+  pub filtered_out: usize,
 }
 
 impl TestHarness {
-  pub fn from_config(config_path: Option<PathBuf>, regex_filter: Option<&Regex>) -> Result<Self> {
-    find_tests(config_path, regex_filter)
+  /// `snapshot_dir` overrides `snapshotDir` of every test config. It is relative to cwd.
+  pub fn from_config(
+    config_path: Option<PathBuf>,
+    snapshot_dir: Option<&Path>,
+    regex_filter: Option<&Regex>,
+    case_filter: Option<&str>,
+  ) -> Result<Self> {
+    let snapshot_path = match snapshot_dir {
+      Some(dir) => Some(std::env::current_dir()?.join(dir)),
+      None => None,
+    };
+    find_tests(config_path, snapshot_path, regex_filter, case_filter)
   }
 
+  /// Both `test_dirname` and `snapshot_dir` are relative to cwd.
   pub fn from_dir(
     test_dirname: &Path,
-    snapshot_dirname: Option<&Path>,
+    snapshot_dir: Option<&Path>,
     regex_filter: Option<&Regex>,
+    case_filter: Option<&str>,
   ) -> Result<Self> {
+    let base_dir = std::env::current_dir()?;
+    let test_path = base_dir.join(test_dirname);
+    let snapshot_path = match snapshot_dir {
+      Some(dir) => base_dir.join(dir),
+      None => test_path.join(SNAPSHOT_DIR),
+    };
     let mut builder = HarnessBuilder {
       dest: TestHarness::default(),
-      base_dir: std::env::current_dir()?,
       regex_filter,
+      case_filter,
+      read_snapshot_paths: HashSet::new(),
     };
-    builder.read_test_files(test_dirname, snapshot_dirname)?;
+    builder.read_test_files(&test_path, &snapshot_path)?;
     Ok(builder.dest)
   }
 }
 
 struct HarnessBuilder<'a> {
   dest: TestHarness,
-  base_dir: PathBuf,
   regex_filter: Option<&'a Regex>,
+  case_filter: Option<&'a str>,
+  /// Test configs may share one snapshot directory, read it only once.
+  read_snapshot_paths: HashSet<PathBuf>,
 }
 
 impl<'a> HarnessBuilder<'a> {
@@ -53,19 +82,24 @@ impl<'a> HarnessBuilder<'a> {
     self.regex_filter.map(|r| r.is_match(id)).unwrap_or(true)
   }
 
-  fn read_test_files(
-    &mut self,
-    test_dirname: &Path,
-    snapshot_dirname: Option<&Path>,
-  ) -> Result<()> {
-    let test_path = self.base_dir.join(test_dirname);
-    let snapshot_dirname = snapshot_dirname.unwrap_or_else(|| SNAPSHOT_DIR.as_ref());
-    let snapshot_path = test_path.join(snapshot_dirname);
-    let walker = WalkBuilder::new(&test_path)
+  /// Keep only the cases whose source contains the case filter.
+  /// Returns false if no case is left.
+  fn narrow_cases(&self, test_case: &mut TestCase) -> bool {
+    let Some(needle) = self.case_filter else {
+      return true;
+    };
+    test_case.valid.retain(|code| code.contains(needle));
+    test_case.invalid.retain(|case| case.code.contains(needle));
+    !test_case.valid.is_empty() || !test_case.invalid.is_empty()
+  }
+
+  /// Read tests in `test_path` and snapshots in `snapshot_path`, which may be outside `test_path`.
+  fn read_test_files(&mut self, test_path: &Path, snapshot_path: &Path) -> Result<()> {
+    let walker = WalkBuilder::new(test_path)
       .types(config_file_type())
       .build();
     for dir in walker {
-      let config_file = dir.with_context(|| EC::WalkRuleDir(test_path.clone()))?;
+      let config_file = dir.with_context(|| EC::WalkRuleDir(test_path.to_path_buf()))?;
       // file_type is None only if it is stdin, safe to unwrap here
       if !config_file
         .file_type()
@@ -75,20 +109,73 @@ impl<'a> HarnessBuilder<'a> {
         continue;
       }
       let path = config_file.path();
+      if path.starts_with(snapshot_path) {
+        continue;
+      }
       let yaml = read_to_string(path).with_context(|| EC::ReadRule(path.to_path_buf()))?;
-      if path.starts_with(&snapshot_path) {
-        deserialize_snapshot_yaml(path, yaml, self)?;
-      } else {
-        deserialize_test_yaml(path, yaml, &snapshot_path, self)?;
+      deserialize_test_yaml(path, yaml, snapshot_path, self)?;
+    }
+    self.read_snapshot_dir(snapshot_path)
+  }
+
+  /// Read snapshot files in the directory, if it exists and is not read yet.
+  fn read_snapshot_dir(&mut self, snapshot_path: &Path) -> Result<()> {
+    if !snapshot_path.is_dir() || !self.read_snapshot_paths.insert(snapshot_path.to_path_buf()) {
+      return Ok(());
+    }
+    let walker = WalkBuilder::new(snapshot_path)
+      .types(config_file_type())
+      .build();
+    for entry in walker {
+      let entry = entry.with_context(|| EC::WalkRuleDir(snapshot_path.to_path_buf()))?;
+      let path = entry.path();
+      if !path.is_file() {
+        continue;
       }
+      let yaml = read_to_string(path).with_context(|| EC::ReadRule(path.to_path_buf()))?;
+      deserialize_snapshot_yaml(path, yaml, self)?;
     }
     Ok(())
   }
+
+  /// Add a test, merging it into an already found test of the same rule.
+  fn add_test_case(&mut self, test_case: TestCase, path: &Path, snapshot_path: &Path) {
+    let harness = &mut self.dest;
+    harness
+      .test_files
+      .entry(test_case.id.clone())
+      .or_insert_with(|| path.to_path_buf());
+    let existing = harness.test_cases.iter_mut().find(|t| t.id == test_case.id);
+    let Some(existing) = existing else {
+      harness
+        .path_map
+        .insert(test_case.id.clone(), snapshot_path.to_path_buf());
+      harness.test_cases.push(test_case);
+      return;
+    };
+    let id = test_case.id.clone();
+    let duplicates = existing.merge(test_case);
+    if duplicates > 0 {
+      eprintln!("Warning: skipped {duplicates} duplicate test case(s) for `{id}`");
+    }
+  }
 }
 
+/// Only `id` and `tests` of a rule are needed to find its inline tests.
+#[derive(Deserialize)]
+struct RuleWithTests {
+  id: String,
+  tests: Option<InlineTests>,
+}
+
+/// Find tests of all test configs and inline tests in rule files.
+/// `snapshot_path` overrides `snapshotDir` of test configs, which is relative to the config file.
+/// Without either, snapshots are in `__snapshots__` under each test directory.
 pub fn find_tests(
   config_path: Option<PathBuf>,
+  snapshot_path: Option<PathBuf>,
   regex_filter: Option<&Regex>,
+  case_filter: Option<&str>,
 ) -> Result<TestHarness> {
   let config_path =
     find_config_path_with_default(config_path, None).context(EC::ReadConfiguration)?;
@@ -99,12 +186,36 @@ pub fn find_tests(
     .expect("config file must have parent directory");
   let test_configs = sg_config.test_configs.unwrap_or_default();
   let mut builder = HarnessBuilder {
-    base_dir: base_dir.to_path_buf(),
     regex_filter,
+    case_filter,
     dest: TestHarness::default(),
+    read_snapshot_paths: HashSet::new(),
+  };
+  let resolve_snapshot_path = |test: &TestConfig| {
+    if let Some(path) = &snapshot_path {
+      path.clone()
+    } else if let Some(dir) = &test.snapshot_dir {
+      base_dir.join(dir)
+    } else {
+      base_dir.join(&test.test_dir).join(SNAPSHOT_DIR)
+    }
   };
-  for test in test_configs {
-    builder.read_test_files(&test.test_dir, test.snapshot_dir.as_deref())?;
+  for test in &test_configs {
+    let test_path = base_dir.join(&test.test_dir);
+    builder.read_test_files(&test_path, &resolve_snapshot_path(test))?;
+  }
+  // inline tests share the snapshot directory of the first test config
+  let inline_snapshot_path = match test_configs.first() {
+    Some(test) => resolve_snapshot_path(test),
+    None => {
+      let path = snapshot_path.unwrap_or_else(|| base_dir.join(SNAPSHOT_DIR));
+      builder.read_snapshot_dir(&path)?;
+      path
+    }
+  };
+  for path in find_rule_files(base_dir, sg_config.rule_dirs)? {
+    let yaml = read_to_string(&path).with_context(|| EC::ReadRule(path.clone()))?;
+    deserialize_inline_tests(&path, &yaml, &inline_snapshot_path, &mut builder)?;
   }
   Ok(builder.dest)
 }
@@ -120,7 +231,9 @@ fn deserialize_snapshot_yaml(
     return Ok(());
   }
   let id = snapshot.id.clone();
-  let existing = builder.dest.snapshots.insert(id.clone(), snapshot);
+  let dest = &mut builder.dest;
+  dest.snapshot_files.insert(id.clone(), path.to_path_buf());
+  let existing = dest.snapshots.insert(id.clone(), snapshot);
   if existing.is_some() {
     eprintln!("Warning: found duplicate test case snapshot for `{id}`");
   }
@@ -134,15 +247,40 @@ fn deserialize_test_yaml(
   builder: &mut HarnessBuilder<'_>,
 ) -> Result<()> {
   for deser in Deserializer::from_str(&yaml) {
-    let test_case: TestCase =
+    let mut test_case: TestCase =
       deserialize(deser).with_context(|| EC::ParseTest(path.to_path_buf()))?;
-    if builder.included_in_filter(&test_case.id) {
-      let harness = &mut builder.dest;
-      harness
-        .path_map
-        .insert(test_case.id.clone(), snapshot_path.to_path_buf());
-      harness.test_cases.push(test_case);
+    if !builder.included_in_filter(&test_case.id) || !builder.narrow_cases(&mut test_case) {
+      builder.dest.filtered_out += 1;
+      continue;
     }
+    builder.add_test_case(test_case, path, snapshot_path);
+  }
+  Ok(())
+}
+
+/// Read `tests` blocks in a rule file. A rule without `tests` is skipped.
+fn deserialize_inline_tests(
+  path: &Path,
+  yaml: &str,
+  snapshot_path: &Path,
+  builder: &mut HarnessBuilder<'_>,
+) -> Result<()> {
+  for deser in Deserializer::from_str(yaml) {
+    let value = Value::deserialize(deser).with_context(|| EC::ParseRule(path.to_path_buf()))?;
+    if value.get("tests").is_none() {
+      continue;
+    }
+    let rule: RuleWithTests =
+      deserialize(value).with_context(|| EC::ParseTest(path.to_path_buf()))?;
+    let Some(tests) = rule.tests else {
+      continue;
+    };
+    let mut test_case = tests.into_test_case(rule.id);
+    if !builder.included_in_filter(&test_case.id) || !builder.narrow_cases(&mut test_case) {
+      builder.dest.filtered_out += 1;
+      continue;
+    }
+    builder.add_test_case(test_case, path, snapshot_path);
   }
   Ok(())
 }
@@ -163,8 +301,9 @@ invalid: [a]
   fn read_test(yaml: &str) -> TestHarness {
     let mut builder = HarnessBuilder {
       dest: TestHarness::default(),
-      base_dir: PathBuf::new(),
       regex_filter: None,
+      case_filter: None,
+      read_snapshot_paths: HashSet::new(),
     };
     let path = Path::new(".");
     deserialize_test_yaml(path, yaml.to_string(), path, &mut builder).expect("should ok");
@@ -184,6 +323,66 @@ invalid: [a]
     assert_eq!(harness.test_cases[0].id, "test1");
     assert_eq!(harness.test_cases[1].id, "test2");
   }
+  #[test]
+  fn test_filter_test() {
+    let regex = Regex::new("test2").expect("should parse");
+    let mut builder = HarnessBuilder {
+      dest: TestHarness::default(),
+      regex_filter: Some(&regex),
+      case_filter: Some("b"),
+      read_snapshot_paths: HashSet::new(),
+    };
+    let path = Path::new(".");
+    let yaml = format!("{MULTI}---\nid: test2\nvalid: [a, b]\ninvalid: [ab]");
+    deserialize_test_yaml(path, yaml, path, &mut builder).expect("should ok");
+    let harness = builder.dest;
+    assert_eq!(harness.filtered_out, 2);
+    assert_eq!(harness.test_cases.len(), 1);
+    assert_eq!(harness.test_cases[0].valid, ["b"]);
+    assert_eq!(harness.test_cases[0].invalid, ["ab".into()]);
+  }
+
+  #[test]
+  fn test_inline_tests() {
+    let mut builder = HarnessBuilder {
+      dest: TestHarness::default(),
+      regex_filter: None,
+      case_filter: None,
+      read_snapshot_paths: HashSet::new(),
+    };
+    let path = Path::new(".");
+    deserialize_test_yaml(path, MULTI.to_string(), path, &mut builder).expect("should ok");
+    let rules = "
+id: test1
+language: js
+rule: {pattern: a}
+tests:
+  valid: [a, b]
+  invalid: [c]
+---
+id: no-test
+language: js
+rule: {pattern: a}
+---
+id: test3
+language: js
+rule: {pattern: a}
+tests:
+  invalid: [{code: a, fixed: b}]
+";
+    let snapshot_path = Path::new("inline");
+    deserialize_inline_tests(path, rules, snapshot_path, &mut builder).expect("should ok");
+    let harness = builder.dest;
+    assert_eq!(harness.test_cases.len(), 3);
+    let test1 = &harness.test_cases[0];
+    assert_eq!(test1.valid, ["a", "b"]);
+    assert_eq!(test1.invalid, ["a".into(), "c".into()]);
+    assert_eq!(harness.path_map["test1"], path);
+    let test3 = &harness.test_cases[2];
+    assert_eq!(test3.id, "test3");
+    assert_eq!(test3.invalid[0].fixed.as_deref(), Some("b"));
+    assert_eq!(harness.path_map["test3"], snapshot_path);
+  }
 
   const SNAPSHOTS: &str = "
 id: test-1
@@ -200,8 +399,9 @@ snapshots:
   fn test_read_snapshot() {
     let mut builder = HarnessBuilder {
       dest: TestHarness::default(),
-      base_dir: PathBuf::new(),
       regex_filter: None,
+      case_filter: None,
+      read_snapshot_paths: HashSet::new(),
     };
     let path = Path::new(".");
     deserialize_snapshot_yaml(path, SNAPSHOTS.to_string(), &mut builder).expect("should ok");
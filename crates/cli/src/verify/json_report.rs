@@ -0,0 +1,350 @@
+use super::case_result::pair_results;
+use super::diagnostic::ExpectedDiagnostic;
+use super::snapshot::TestSnapshot;
+use super::{CaseResult, CaseStatus, TestCase, UntestedRule};
+use crate::print::JsonStyle;
+
+use anyhow::Result;
+use serde::Serialize;
+
+use std::io::Write;
+use std::time::Duration;
+
+/// Status of the snapshot of an invalid case.
+#[derive(Serialize, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+enum SnapshotStatus {
+  /// Reported issue is the same as the snapshot.
+  Matched,
+  /// A new snapshot has been accepted.
+  Updated,
+  /// No snapshot is found for the case.
+  Missing,
+  /// Reported issue is different from the snapshot.
+  Mismatched,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct CaseReport<'a> {
+  code: &'a str,
+  passed: bool,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  snapshot: Option<SnapshotStatus>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  actual: Option<&'a TestSnapshot>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  expected: Option<&'a TestSnapshot>,
+  /// Expected fixed code in the test case.
+  #[serde(skip_serializing_if = "Option::is_none")]
+  expected_fixed: Option<&'a str>,
+  /// Fixed code produced by the rule, only reported when it is different from the expectation.
+  #[serde(skip_serializing_if = "Option::is_none")]
+  actual_fixed: Option<&'a str>,
+  /// Expected and reported diagnostics, only reported when they are different.
+  #[serde(skip_serializing_if = "Option::is_none")]
+  expected_diagnostics: Option<&'a [ExpectedDiagnostic]>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  actual_diagnostics: Option<&'a [ExpectedDiagnostic]>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  error: Option<&'static str>,
+  /// Message of the panic raised when verifying the case.
+  #[serde(skip_serializing_if = "Option::is_none")]
+  panic: Option<&'a str>,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct RuleReport<'a> {
+  id: &'a str,
+  passed: bool,
+  valid: Vec<CaseReport<'a>>,
+  invalid: Vec<CaseReport<'a>>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  error: Option<&'static str>,
+}
+
+#[derive(Serialize, Default, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+struct Summary {
+  passed: usize,
+  failed: usize,
+  cases_passed: usize,
+  cases_failed: usize,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  filtered_out: Option<usize>,
+  /// Wall time of running tests in seconds.
+  wall_time: f64,
+}
+
+#[derive(Serialize)]
+struct Trailer<'a> {
+  summary: Summary,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  untested: Option<&'a [UntestedRule]>,
+}
+
+/// Test results of `sg test --json`.
+#[derive(Serialize)]
+pub struct JsonReport<'a> {
+  rules: Vec<RuleReport<'a>>,
+  #[serde(flatten)]
+  trailer: Trailer<'a>,
+}
+
+impl<'a> JsonReport<'a> {
+  /// See [pair_results] for how `results` correspond to `test_cases`.
+  pub fn new(
+    test_cases: &'a [TestCase],
+    results: &'a [CaseResult<'a>],
+    snapshot_tests: bool,
+    filtered_out: Option<usize>,
+    wall_time: Duration,
+    untested: Option<&'a [UntestedRule]>,
+  ) -> Self {
+    let mut rules = vec![];
+    for (case, result) in pair_results(test_cases, results) {
+      let rule = match result {
+        Some(result) => report_rule(case, result, snapshot_tests),
+        None => RuleReport {
+          id: &case.id,
+          passed: false,
+          valid: vec![],
+          invalid: vec![],
+          error: Some("rule not found"),
+        },
+      };
+      rules.push(rule);
+    }
+    let mut summary = Summary {
+      filtered_out,
+      wall_time: wall_time.as_secs_f64(),
+      ..Default::default()
+    };
+    // rules not found are not run, the same as the text report
+    for rule in rules.iter().filter(|r| r.error.is_none()) {
+      if rule.passed {
+        summary.passed += 1;
+      } else {
+        summary.failed += 1;
+      }
+      for case in rule.valid.iter().chain(&rule.invalid) {
+        if case.passed {
+          summary.cases_passed += 1;
+        } else {
+          summary.cases_failed += 1;
+        }
+      }
+    }
+    Self {
+      rules,
+      trailer: Trailer { summary, untested },
+    }
+  }
+
+  /// Print the report as one JSON document.
+  /// The stream style prints the same document as compact in a single line,
+  /// since the summary is only known after all rules are tested.
+  pub fn print(&self, mut output: impl Write, style: JsonStyle) -> Result<()> {
+    match style {
+      JsonStyle::Pretty => serde_json::to_writer_pretty(&mut output, self)?,
+      JsonStyle::Compact | JsonStyle::Stream => serde_json::to_writer(&mut output, self)?,
+    }
+    writeln!(output)?;
+    Ok(())
+  }
+}
+
+fn report_rule<'a>(
+  case: &'a TestCase,
+  result: &'a CaseResult<'a>,
+  snapshot_tests: bool,
+) -> RuleReport<'a> {
+  let (valid, invalid) = result.cases.split_at(case.valid.len());
+  RuleReport {
+    id: &case.id,
+    passed: result.passed(),
+    valid: case
+      .valid
+      .iter()
+      .zip(valid)
+      .map(|(code, status)| report_case(code, status))
+      .collect(),
+    invalid: case
+      .invalid
+      .iter()
+      .zip(invalid)
+      .map(|(invalid, status)| {
+        let mut report = report_case(&invalid.code, status);
+        if !snapshot_tests {
+          report.snapshot = None;
+        }
+        report.expected_fixed = invalid.fixed.as_deref();
+        report
+      })
+      .collect(),
+    error: None,
+  }
+}
+
+fn report_case<'a>(code: &'a str, status: &'a CaseStatus<'a>) -> CaseReport<'a> {
+  let mut report = CaseReport {
+    code,
+    passed: status.is_pass(),
+    snapshot: None,
+    actual: None,
+    expected: None,
+    expected_fixed: None,
+    actual_fixed: None,
+    expected_diagnostics: None,
+    actual_diagnostics: None,
+    error: None,
+    panic: None,
+  };
+  match status {
+    CaseStatus::Validated | CaseStatus::Noisy(_) | CaseStatus::Missing(_) => (),
+    CaseStatus::Reported => report.snapshot = Some(SnapshotStatus::Matched),
+    CaseStatus::Updated { updated, .. } => {
+      report.snapshot = Some(SnapshotStatus::Updated);
+      report.actual = Some(updated);
+    }
+    CaseStatus::Wrong {
+      actual, expected, ..
+    } => {
+      report.snapshot = Some(if expected.is_some() {
+        SnapshotStatus::Mismatched
+      } else {
+        SnapshotStatus::Missing
+      });
+      report.actual = Some(actual);
+      report.expected = expected.as_ref();
+    }
+    CaseStatus::Error => report.error = Some("fail to apply fix"),
+    CaseStatus::Panicked(message) => report.panic = Some(message),
+    CaseStatus::WrongFix { actual, .. } => report.actual_fixed = Some(actual),
+    CaseStatus::NoFix(_) => report.error = Some("rule has no fix"),
+    CaseStatus::WrongDiagnostics {
+      expected, actual, ..
+    } => {
+      report.expected_diagnostics = Some(expected);
+      report.actual_diagnostics = Some(actual);
+    }
+  }
+  report
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::verify::test::TEST_RULE;
+
+  fn snapshot() -> TestSnapshot {
+    TestSnapshot {
+      fixed: None,
+      labels: vec![],
+    }
+  }
+
+  #[test]
+  fn test_json_report() -> Result<()> {
+    let test_cases = [
+      TestCase {
+        id: TEST_RULE.into(),
+        valid: vec!["a".into()],
+        invalid: vec!["b".into(), "c".into()],
+      },
+      TestCase {
+        id: "not-found".into(),
+        valid: vec!["d".into()],
+        invalid: vec![],
+      },
+    ];
+    let results = [CaseResult {
+      id: &test_cases[0].id,
+      cases: vec![
+        CaseStatus::Validated,
+        CaseStatus::Reported,
+        CaseStatus::Wrong {
+          source: "c",
+          actual: snapshot(),
+          expected: None,
+        },
+      ],
+    }];
+    let report = JsonReport::new(
+      &test_cases,
+      &results,
+      true,
+      None,
+      Duration::from_millis(1500),
+      None,
+    );
+    let expected = Summary {
+      passed: 0,
+      failed: 1,
+      cases_passed: 2,
+      cases_failed: 1,
+      filtered_out: None,
+      wall_time: 1.5,
+    };
+    assert_eq!(report.trailer.summary, expected);
+    assert_eq!(report.rules[1].error, Some("rule not found"));
+    let mut output = vec![];
+    report.print(&mut output, JsonStyle::Compact)?;
+    let json: serde_json::Value = serde_json::from_slice(&output)?;
+    assert_eq!(json["rules"][0]["invalid"][0]["snapshot"], "matched");
+    assert_eq!(json["rules"][0]["invalid"][1]["snapshot"], "missing");
+    assert_eq!(
+      json["rules"][0]["invalid"][1]["actual"]["labels"],
+      serde_json::json!([])
+    );
+    assert_eq!(json["summary"]["wallTime"], 1.5);
+    Ok(())
+  }
+
+  #[test]
+  fn test_stream_style() -> Result<()> {
+    let test_cases = [TestCase {
+      id: TEST_RULE.into(),
+      valid: vec!["a".into()],
+      invalid: vec![],
+    }];
+    let results = [CaseResult {
+      id: &test_cases[0].id,
+      cases: vec![CaseStatus::Validated],
+    }];
+    let report = JsonReport::new(&test_cases, &results, false, Some(2), Duration::ZERO, None);
+    let mut output = vec![];
+    report.print(&mut output, JsonStyle::Stream)?;
+    let output = String::from_utf8(output)?;
+    let lines: Vec<_> = output.lines().collect();
+    assert_eq!(lines.len(), 1);
+    let json: serde_json::Value = serde_json::from_str(lines[0])?;
+    assert_eq!(json["rules"][0]["id"], "test-rule");
+    assert_eq!(json["rules"][0]["passed"], true);
+    assert_eq!(json["summary"]["filteredOut"], 2);
+    Ok(())
+  }
+
+  #[test]
+  fn test_panicked_case() -> Result<()> {
+    let test_cases = [TestCase {
+      id: TEST_RULE.into(),
+      valid: vec!["a".into()],
+      invalid: vec![],
+    }];
+    let results = [CaseResult {
+      id: &test_cases[0].id,
+      cases: vec![CaseStatus::Panicked("boom".into())],
+    }];
+    let report = JsonReport::new(&test_cases, &results, false, None, Duration::ZERO, None);
+    let mut output = vec![];
+    report.print(&mut output, JsonStyle::Compact)?;
+    let json: serde_json::Value = serde_json::from_slice(&output)?;
+    let case = &json["rules"][0]["valid"][0];
+    assert_eq!(case["passed"], false);
+    assert_eq!(case["panic"], "boom");
+    assert!(case.get("error").is_none());
+    Ok(())
+  }
+}
@@ -0,0 +1,387 @@
+use super::case_result::pair_results;
+use super::diagnostic::render_diagnostics;
+use super::snapshot::TestSnapshot;
+use super::{CaseResult, CaseStatus, TestCase};
+
+use anyhow::{Context, Result};
+use serde_yaml::to_string;
+use similar::TextDiff;
+
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Value of `sg test --reporter`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ReporterKind {
+  /// Human readable report.
+  Default,
+  /// JUnit XML written to the path, or stdout if no path is given.
+  Junit(Option<PathBuf>),
+}
+
+pub fn parse_reporter(value: &str) -> Result<ReporterKind, String> {
+  match value.split_once('=') {
+    None if value == "default" => Ok(ReporterKind::Default),
+    None if value == "junit" => Ok(ReporterKind::Junit(None)),
+    Some(("junit", path)) if !path.is_empty() => Ok(ReporterKind::Junit(Some(path.into()))),
+    _ => Err(format!(
+      "`{value}` is not a valid reporter. Use `default`, `junit` or `junit=PATH`."
+    )),
+  }
+}
+
+enum Outcome {
+  Passed,
+  Failed(&'static str, String),
+  Skipped,
+  Error(&'static str, String),
+}
+
+struct JunitCase {
+  name: String,
+  outcome: Outcome,
+}
+
+/// JUnit XML where every rule is a testsuite and every valid, invalid
+/// and snapshot check is a testcase.
+pub struct JunitReport {
+  suites: Vec<(String, Vec<JunitCase>)>,
+  wall_time: Duration,
+}
+
+impl JunitReport {
+  /// See [pair_results] for how `results` correspond to `test_cases`.
+  pub fn new(
+    test_cases: &[TestCase],
+    results: &[CaseResult],
+    snapshot_tests: bool,
+    wall_time: Duration,
+  ) -> Self {
+    let suites = pair_results(test_cases, results)
+      .into_iter()
+      .map(|(case, result)| {
+        let cases = match result {
+          Some(result) => junit_cases(case, result, snapshot_tests),
+          None => vec![JunitCase {
+            name: format!("{}::config", case.id),
+            outcome: Outcome::Error("rule not found", String::new()),
+          }],
+        };
+        (case.id.clone(), cases)
+      })
+      .collect();
+    Self { suites, wall_time }
+  }
+
+  pub fn to_xml(&self) -> String {
+    let count = |cases: &[JunitCase], pred: fn(&Outcome) -> bool| {
+      cases.iter().filter(|c| pred(&c.outcome)).count()
+    };
+    let is_failure = |o: &Outcome| matches!(o, Outcome::Failed(..));
+    let is_error = |o: &Outcome| matches!(o, Outcome::Error(..));
+    let is_skipped = |o: &Outcome| matches!(o, Outcome::Skipped);
+    let all: Vec<_> = self.suites.iter().flat_map(|(_, c)| c).collect();
+    let total_failures = all.iter().filter(|c| is_failure(&c.outcome)).count();
+    let total_errors = all.iter().filter(|c| is_error(&c.outcome)).count();
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    // writing to String never fails
+    let _ = writeln!(
+      xml,
+      r#"<testsuites name="ast-grep" tests="{}" failures="{total_failures}" errors="{total_errors}" time="{:.3}">"#,
+      all.len(),
+      self.wall_time.as_secs_f64(),
+    );
+    for (id, cases) in &self.suites {
+      let _ = writeln!(
+        xml,
+        r#"  <testsuite name="{}" tests="{}" failures="{}" errors="{}" skipped="{}">"#,
+        escape(id),
+        cases.len(),
+        count(cases, is_failure),
+        count(cases, is_error),
+        count(cases, is_skipped),
+      );
+      for case in cases {
+        let name = escape(&case.name);
+        let classname = escape(id);
+        let _ = write!(
+          xml,
+          r#"    <testcase name="{name}" classname="{classname}""#
+        );
+        let _ = match &case.outcome {
+          Outcome::Passed => writeln!(xml, "/>"),
+          Outcome::Skipped => writeln!(xml, ">\n      <skipped/>\n    </testcase>"),
+          Outcome::Error(message, text) if text.is_empty() => writeln!(
+            xml,
+            ">\n      <error message=\"{}\"/>\n    </testcase>",
+            escape(message)
+          ),
+          Outcome::Error(message, text) => writeln!(
+            xml,
+            ">\n      <error message=\"{}\">{}</error>\n    </testcase>",
+            escape(message),
+            escape(text)
+          ),
+          Outcome::Failed(message, text) => writeln!(
+            xml,
+            ">\n      <failure message=\"{}\">{}</failure>\n    </testcase>",
+            escape(message),
+            escape(text)
+          ),
+        };
+      }
+      let _ = writeln!(xml, "  </testsuite>");
+    }
+    let _ = writeln!(xml, "</testsuites>");
+    xml
+  }
+
+  /// Write the XML to a temporary file next to `path` and rename it,
+  /// so that readers never see a partially written report.
+  pub fn write_to(&self, path: &Path) -> Result<()> {
+    let file_name = path
+      .file_name()
+      .with_context(|| format!("{} is not a file path", path.display()))?;
+    let mut tmp_name = file_name.to_os_string();
+    tmp_name.push(".tmp");
+    let tmp = path.with_file_name(tmp_name);
+    std::fs::write(&tmp, self.to_xml())
+      .with_context(|| format!("Cannot write JUnit report to {}", tmp.display()))?;
+    std::fs::rename(&tmp, path)
+      .with_context(|| format!("Cannot write JUnit report to {}", path.display()))?;
+    Ok(())
+  }
+}
+
+fn junit_cases(case: &TestCase, result: &CaseResult, snapshot_tests: bool) -> Vec<JunitCase> {
+  let id = &case.id;
+  let (valid, invalid) = result.cases.split_at(case.valid.len());
+  let mut cases = vec![];
+  for (i, (code, status)) in case.valid.iter().zip(valid).enumerate() {
+    let outcome = match status {
+      CaseStatus::Noisy(_) => Outcome::Failed(
+        "Noisy",
+        format!("Expect {id} to report no issue, but some issues found in:\n{code}"),
+      ),
+      CaseStatus::Panicked(message) => Outcome::Error("Panicked", message.clone()),
+      _ => Outcome::Passed,
+    };
+    let name = case_name(id, "valid", i, code);
+    cases.push(JunitCase { name, outcome });
+  }
+  for (i, (invalid, status)) in case.invalid.iter().zip(invalid).enumerate() {
+    let code = &invalid.code;
+    let missing = || {
+      let text = format!("Expect {id} to report issues, but none found in:\n{code}");
+      Outcome::Failed("Missing", text)
+    };
+    let (reported, snapshot) = match status {
+      CaseStatus::Missing(_) => (missing(), Outcome::Skipped),
+      CaseStatus::Error => (
+        Outcome::Passed,
+        Outcome::Error("fail to apply fix", String::new()),
+      ),
+      CaseStatus::Panicked(message) => (
+        Outcome::Error("Panicked", message.clone()),
+        Outcome::Skipped,
+      ),
+      CaseStatus::Wrong {
+        actual, expected, ..
+      } => (Outcome::Passed, snapshot_failure(actual, expected.as_ref())),
+      CaseStatus::WrongFix {
+        expected, actual, ..
+      } => {
+        let diff = TextDiff::from_lines(*expected, actual.as_str())
+          .unified_diff()
+          .header("expected fixed", "actual fixed")
+          .to_string();
+        (Outcome::Failed("Wrong fix", diff), Outcome::Skipped)
+      }
+      CaseStatus::NoFix(_) => {
+        let text = format!("Rule {id} has no fix, but fixed code is expected for:\n{code}");
+        (Outcome::Failed("No fix", text), Outcome::Skipped)
+      }
+      CaseStatus::WrongDiagnostics {
+        source,
+        expected,
+        actual,
+      } => {
+        let text = format!(
+          "Expected:\n{}Actual:\n{}",
+          render_diagnostics(source, expected),
+          render_diagnostics(source, actual)
+        );
+        (Outcome::Failed("Wrong diagnostics", text), Outcome::Skipped)
+      }
+      _ => (Outcome::Passed, Outcome::Passed),
+    };
+    cases.push(JunitCase {
+      name: case_name(id, "invalid", i, code),
+      outcome: reported,
+    });
+    if snapshot_tests {
+      cases.push(JunitCase {
+        name: case_name(id, "snapshot", i, code),
+        outcome: snapshot,
+      });
+    }
+  }
+  cases
+}
+
+fn snapshot_failure(actual: &TestSnapshot, expected: Option<&TestSnapshot>) -> Outcome {
+  let actual = to_string(actual).unwrap_or_default();
+  let Some(expected) = expected else {
+    let text = format!("No snapshot baseline found. Generated snapshot:\n{actual}");
+    return Outcome::Failed("Missing snapshot", text);
+  };
+  let expected = to_string(expected).unwrap_or_default();
+  let diff = TextDiff::from_lines(&expected, &actual)
+    .unified_diff()
+    .header("expected", "actual")
+    .to_string();
+  Outcome::Failed("Snapshot mismatch", diff)
+}
+
+/// Stable and unique name: rule id, case kind and index, plus a short slug of the code.
+fn case_name(id: &str, kind: &str, index: usize, code: &str) -> String {
+  let mut slug = String::new();
+  for c in code.chars() {
+    if slug.len() >= 24 {
+      break;
+    }
+    if c.is_ascii_alphanumeric() {
+      slug.push(c.to_ascii_lowercase());
+    } else if !slug.is_empty() && !slug.ends_with('-') {
+      slug.push('-');
+    }
+  }
+  let slug = slug.trim_end_matches('-');
+  format!("{id}::{kind}[{index}]::{slug}")
+}
+
+/// Escape markup and replace chars that XML 1.0 forbids even as character references,
+/// e.g. control chars in a snippet, so the report stays parsable.
+fn escape(s: &str) -> String {
+  let mut ret = String::with_capacity(s.len());
+  for c in s.chars() {
+    match c {
+      '&' => ret.push_str("&amp;"),
+      '<' => ret.push_str("&lt;"),
+      '>' => ret.push_str("&gt;"),
+      '"' => ret.push_str("&quot;"),
+      '\'' => ret.push_str("&apos;"),
+      '\t' | '\n' | '\r' => ret.push(c),
+      '\u{0}'..='\u{1f}' | '\u{fffe}' | '\u{ffff}' => ret.push(char::REPLACEMENT_CHARACTER),
+      c => ret.push(c),
+    }
+  }
+  ret
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::verify::test::TEST_RULE;
+
+  #[test]
+  fn test_parse_reporter() {
+    assert_eq!(parse_reporter("junit"), Ok(ReporterKind::Junit(None)));
+    assert_eq!(
+      parse_reporter("junit=out.xml"),
+      Ok(ReporterKind::Junit(Some("out.xml".into())))
+    );
+    assert_eq!(parse_reporter("default"), Ok(ReporterKind::Default));
+    assert!(parse_reporter("junit=").is_err());
+    assert!(parse_reporter("tap").is_err());
+  }
+
+  #[test]
+  fn test_case_name() {
+    let name = case_name(TEST_RULE, "invalid", 2, "console.log(<a>, 'b')");
+    assert_eq!(name, "test-rule::invalid[2]::console-log-a-b");
+    let long = case_name(TEST_RULE, "valid", 0, &"a".repeat(100));
+    assert_eq!(long, format!("test-rule::valid[0]::{}", "a".repeat(24)));
+  }
+
+  #[test]
+  fn test_escape() {
+    assert_eq!(escape("a < b && c"), "a &lt; b &amp;&amp; c");
+    assert_eq!(escape("'\"'"), "&apos;&quot;&apos;");
+    assert_eq!(escape("a\tb\r\nc"), "a\tb\r\nc");
+    assert_eq!(
+      escape("a\u{0}b\u{8}c\u{b}\u{c}d\u{1b}[0m"),
+      "a\u{fffd}b\u{fffd}c\u{fffd}\u{fffd}d\u{fffd}[0m"
+    );
+  }
+
+  #[test]
+  fn test_junit_xml() {
+    let test_cases = [TestCase {
+      id: TEST_RULE.into(),
+      valid: vec!["a < b".into()],
+      invalid: vec!["c".into(), "d".into()],
+    }];
+    let snapshot = |source: &str| TestSnapshot {
+      fixed: Some(source.into()),
+      labels: vec![],
+    };
+    let results = [CaseResult {
+      id: &test_cases[0].id,
+      cases: vec![
+        CaseStatus::Noisy("a < b"),
+        CaseStatus::Missing("c"),
+        CaseStatus::Wrong {
+          source: "d",
+          actual: snapshot("new"),
+          expected: Some(snapshot("old")),
+        },
+      ],
+    }];
+    let report = JunitReport::new(&test_cases, &results, true, Duration::ZERO);
+    let xml = report.to_xml();
+    assert!(
+      xml.contains(r#"<testsuite name="test-rule" tests="5" failures="3" errors="0" skipped="1">"#)
+    );
+    assert!(xml.contains(r#"<testcase name="test-rule::valid[0]::a-b" classname="test-rule">"#));
+    assert!(xml.contains("some issues found in:\na &lt; b"));
+    assert!(xml.contains(r#"<testcase name="test-rule::invalid[1]::d" classname="test-rule"/>"#));
+    assert!(xml.contains("-fixed: old\n+fixed: new"));
+  }
+
+  #[test]
+  fn test_junit_panicked() {
+    let test_cases = [TestCase {
+      id: TEST_RULE.into(),
+      valid: vec!["a".into()],
+      invalid: vec!["c".into()],
+    }];
+    let results = [CaseResult {
+      id: &test_cases[0].id,
+      cases: vec![
+        CaseStatus::Panicked("valid boom".into()),
+        CaseStatus::Panicked("invalid boom".into()),
+      ],
+    }];
+    let report = JunitReport::new(&test_cases, &results, true, Duration::ZERO);
+    let xml = report.to_xml();
+    assert!(
+      xml.contains(r#"<testsuite name="test-rule" tests="3" failures="0" errors="2" skipped="1">"#)
+    );
+    assert!(xml.contains(r#"<error message="Panicked">valid boom</error>"#));
+    assert!(xml.contains(r#"<error message="Panicked">invalid boom</error>"#));
+  }
+
+  #[test]
+  fn test_write_atomic() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("junit.xml");
+    let report = JunitReport::new(&[], &[], true, Duration::ZERO);
+    report.write_to(&path)?;
+    let xml = std::fs::read_to_string(&path)?;
+    assert!(xml.contains(r#"<testsuites name="ast-grep" tests="0""#));
+    assert!(!dir.path().join("junit.xml.tmp").exists());
+    Ok(())
+  }
+}
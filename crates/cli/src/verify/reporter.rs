@@ -1,4 +1,3 @@
-use crate::print::{print_diff, ColorChoice, PrintStyles};
 use crate::utils::{prompt, run_in_alternate_screen};
 
 use ansi_term::{Color, Style};
@@ -7,17 +6,26 @@ use serde_yaml::to_string;
 
 use std::io::Write;
 
-use super::{CaseResult, CaseStatus, SnapshotAction, TestCase};
+use super::diagnostic::render_diagnostics;
+use super::diff::render_diff;
+use super::{CaseResult, CaseStatus, SnapshotWriter, TestCase, UntestedRule};
 
 pub(super) trait Reporter {
   type Output: Write;
   fn get_output(&mut self) -> &mut Self::Output;
+  /// Whether the output is colored, decided by `--color` for the output stream.
+  fn use_color(&self) -> bool;
   /// A hook function runs before tests start.
   fn before_report(&mut self, test_cases: &[TestCase]) -> Result<()> {
     report_case_number(self.get_output(), test_cases)
   }
   /// A hook function runs after tests completed.
-  fn after_report(&mut self, results: &[CaseResult]) -> Result<(bool, String)> {
+  /// `filtered_out` is the number of tests excluded by filters, if any filter is used.
+  fn after_report(
+    &mut self,
+    results: &[CaseResult],
+    filtered_out: Option<usize>,
+  ) -> Result<(bool, String)> {
     let mut passed = 0;
     let mut failed = 0;
     for result in results {
@@ -27,16 +35,24 @@ pub(super) trait Reporter {
         failed += 1;
       }
     }
-    let message = format!("{passed} passed; {failed} failed;");
+    let mut message = format!("{passed} passed; {failed} failed;");
+    if let Some(filtered_out) = filtered_out {
+      message.push_str(&format!(" {filtered_out} filtered out;"));
+    }
     if failed > 0 {
       Ok((false, format!("test failed. {message}")))
     } else {
-      let result = Color::Green.paint("ok");
+      let result = styled(self.use_color(), Color::Green.normal()).paint("ok");
       Ok((true, format!("test result: {result}. {message}")))
     }
   }
 
-  fn report_failed_cases(&mut self, results: &mut [CaseResult]) -> Result<()> {
+  /// Accepted snapshots are written by `writer` right after each case is reported.
+  fn report_failed_cases(
+    &mut self,
+    results: &mut [CaseResult],
+    mut writer: Option<&mut SnapshotWriter>,
+  ) -> Result<()> {
     let output = self.get_output();
     writeln!(output)?;
     writeln!(output, "----------- Case Details -----------")?;
@@ -45,7 +61,11 @@ pub(super) trait Reporter {
         continue;
       }
       for status in &mut result.cases {
-        if !self.report_case_detail(result.id, status)? {
+        let should_continue = self.report_case_detail(result.id, status)?;
+        if let Some(writer) = writer.as_deref_mut() {
+          writer.accept(result.id, status)?;
+        }
+        if !should_continue {
           return Ok(());
         }
       }
@@ -62,15 +82,37 @@ pub(super) trait Reporter {
     Ok(())
   }
 
+  fn report_coverage(&mut self, untested: &[UntestedRule]) -> Result<()> {
+    let output = self.get_output();
+    if untested.is_empty() {
+      writeln!(output, "Coverage: all rules have tests.")?;
+      return Ok(());
+    }
+    writeln!(
+      output,
+      "Coverage: {} rule(s) without tests:",
+      untested.len()
+    )?;
+    for rule in untested {
+      writeln!(output, "  {}  {}", rule.id, rule.path.display())?;
+    }
+    writeln!(output)?;
+    Ok(())
+  }
+
   fn report_case_summary(&mut self, case_id: &str, summary: &[CaseStatus]) -> Result<()> {
     let passed = summary.iter().all(CaseStatus::is_pass);
-    let style = Style::new().fg(Color::White).bold();
+    let color = self.use_color();
+    let label = |background: Color, text| {
+      let style = Style::new().fg(Color::White).bold().on(background);
+      styled(color, style).paint(text)
+    };
     let case_status = if summary.is_empty() {
-      style.on(Color::Yellow).paint("SKIP")
+      label(Color::Yellow, "SKIP")
     } else if passed {
-      style.on(Color::Green).paint("PASS")
+      label(Color::Green, "PASS")
     } else {
-      style.on(Color::Red).paint("FAIL")
+      label(Color::Red, "FAIL")
     };
     let summary = report_summary(summary);
     writeln!(self.get_output(), "{case_status} {case_id}  {summary}")?;
@@ -80,7 +122,6 @@ pub(super) trait Reporter {
   /// returns if should continue reporting
   /// user can mutate case_status to Updated in this function
   fn report_case_detail(&mut self, case_id: &str, result: &mut CaseStatus) -> Result<bool>;
-  fn collect_snapshot_action(&self) -> SnapshotAction;
 }
 
 fn report_case_number(output: &mut impl Write, test_cases: &[TestCase]) -> Result<()> {
@@ -96,6 +137,9 @@ fn report_summary(summary: &[CaseStatus]) -> String {
     let mut missing = 0;
     let mut noisy = 0;
     let mut error = 0;
+    let mut panicked = 0;
+    let mut wrong_fix = 0;
+    let mut wrong_diagnostics = 0;
     for s in summary {
       match s {
         CaseStatus::Validated | CaseStatus::Reported => pass += 1,
@@ -104,6 +148,9 @@ fn report_summary(summary: &[CaseStatus]) -> String {
         CaseStatus::Missing(_) => missing += 1,
         CaseStatus::Noisy(_) => noisy += 1,
         CaseStatus::Error => error += 1,
+        CaseStatus::Panicked(_) => panicked += 1,
+        CaseStatus::WrongFix { .. } | CaseStatus::NoFix(_) => wrong_fix += 1,
+        CaseStatus::WrongDiagnostics { .. } => wrong_diagnostics += 1,
       }
     }
     let stats = vec![
@@ -113,6 +160,9 @@ fn report_summary(summary: &[CaseStatus]) -> String {
       ("Missing", missing),
       ("Noisy", noisy),
       ("Error", error),
+      ("Panicked", panicked),
+      ("Wrong Fix", wrong_fix),
+      ("Wrong Diagnostics", wrong_diagnostics),
     ];
     let result: Vec<_> = stats
       .into_iter()
@@ -136,11 +186,23 @@ fn report_summary(summary: &[CaseStatus]) -> String {
         CaseStatus::Missing(_) => 'M',
         CaseStatus::Noisy(_) => 'N',
         CaseStatus::Error => 'E',
+        CaseStatus::Panicked(_) => 'P',
+        CaseStatus::WrongFix { .. } | CaseStatus::NoFix(_) => 'F',
+        CaseStatus::WrongDiagnostics { .. } => 'D',
       })
       .collect()
   }
 }
 
+/// The style itself if colors are used, or a plain style that writes no escape code.
+fn styled(color: bool, style: Style) -> Style {
+  if color {
+    style
+  } else {
+    Style::new()
+  }
+}
+
 fn indented_write<W: Write>(output: &mut W, code: &str) -> Result<()> {
   for line in code.lines() {
     writeln!(output, "  {line}")?;
@@ -152,14 +214,20 @@ fn report_case_detail_impl<W: Write>(
   output: &mut W,
   case_id: &str,
   result: &CaseStatus,
+  color: bool,
 ) -> Result<bool> {
-  let case_id = Style::new().bold().paint(case_id);
-  let noisy = Style::new().underline().paint("Noisy");
-  let missing = Style::new().underline().paint("Missing");
-  let wrong = Style::new().underline().paint("Wrong");
-  let error = Style::new().underline().paint("Error");
-  let update = Style::new().underline().paint("Updated");
-  let styles = PrintStyles::from(ColorChoice::Auto);
+  let underline = styled(color, Style::new().underline());
+  let italic = styled(color, Style::new().italic());
+  let case_id = styled(color, Style::new().bold()).paint(case_id);
+  let noisy = underline.paint("Noisy");
+  let missing = underline.paint("Missing");
+  let wrong = underline.paint("Wrong");
+  let error = underline.paint("Error");
+  let panicked = underline.paint("Panicked");
+  let update = underline.paint("Updated");
+  let wrong_fix = underline.paint("Wrong Fix");
+  let no_fix = underline.paint("No Fix");
+  let wrong_diagnostics = underline.paint("Wrong Diagnostics");
   match result {
     CaseStatus::Validated | CaseStatus::Reported => (),
     CaseStatus::Updated { source, .. } => {
@@ -183,20 +251,16 @@ fn report_case_detail_impl<W: Write>(
         )?;
         let actual_str = to_string(&actual)?;
         let expected_str = to_string(&expected)?;
-        writeln!(output, "{}", Style::new().italic().paint("Diff:"))?;
-        print_diff(&expected_str, &actual_str, &styles, output, 3)?;
+        writeln!(output, "{}", italic.paint("Diff:"))?;
+        write!(output, "{}", render_diff(&expected_str, &actual_str, color))?;
       } else {
         writeln!(output, "[{wrong}] No {case_id} baseline found.")?;
         // TODO: add to print_styles
-        writeln!(
-          output,
-          "{}",
-          Style::new().italic().paint("Generated Snapshot:")
-        )?;
+        writeln!(output, "{}", italic.paint("Generated Snapshot:"))?;
         indented_write(output, &to_string(&actual)?)?;
       }
       // TODO: add to print_styles
-      writeln!(output, "{}", Style::new().italic().paint("For Code:"))?;
+      writeln!(output, "{}", italic.paint("For Code:"))?;
       indented_write(output, source)?;
       writeln!(output)?;
     }
@@ -221,6 +285,51 @@ fn report_case_detail_impl<W: Write>(
     CaseStatus::Error => {
       writeln!(output, "[{error}] Fail to apply fix to {case_id}")?;
     }
+    CaseStatus::Panicked(message) => {
+      writeln!(
+        output,
+        "[{panicked}] Verifying {case_id} panicked: {message}"
+      )?;
+    }
+    CaseStatus::WrongFix {
+      source,
+      expected,
+      actual,
+    } => {
+      writeln!(
+        output,
+        "[{wrong_fix}] {case_id} fixed code is different from expectation."
+      )?;
+      writeln!(output, "{}", italic.paint("Diff:"))?;
+      write!(output, "{}", render_diff(expected, actual, color))?;
+      writeln!(output, "{}", italic.paint("For Code:"))?;
+      indented_write(output, source)?;
+      writeln!(output)?;
+    }
+    CaseStatus::NoFix(s) => {
+      writeln!(
+        output,
+        "[{no_fix}] Rule {case_id} has no fix, but fixed code is expected for:"
+      )?;
+      writeln!(output)?;
+      indented_write(output, s)?;
+      writeln!(output)?;
+    }
+    CaseStatus::WrongDiagnostics {
+      source,
+      expected,
+      actual,
+    } => {
+      writeln!(
+        output,
+        "[{wrong_diagnostics}] {case_id} reported diagnostics are different from expectation."
+      )?;
+      writeln!(output, "{}", italic.paint("Expected:"))?;
+      write!(output, "{}", render_diagnostics(source, expected))?;
+      writeln!(output, "{}", italic.paint("Actual:"))?;
+      write!(output, "{}", render_diagnostics(source, actual))?;
+      writeln!(output)?;
+    }
   }
   // continue
   Ok(true)
@@ -230,6 +339,8 @@ pub struct DefaultReporter<Output: Write> {
   // TODO: visibility
   pub output: Output,
   pub update_all: bool,
+  /// Colorize diffs. Without color they only have `-`/`+` markers.
+  pub color: bool,
 }
 
 impl<O: Write> Reporter for DefaultReporter<O> {
@@ -238,24 +349,21 @@ impl<O: Write> Reporter for DefaultReporter<O> {
   fn get_output(&mut self) -> &mut Self::Output {
     &mut self.output
   }
+  fn use_color(&self) -> bool {
+    self.color
+  }
   fn report_case_detail(&mut self, case_id: &str, result: &mut CaseStatus) -> Result<bool> {
     if self.update_all {
       result.accept();
     }
-    report_case_detail_impl(self.get_output(), case_id, result)
-  }
-  fn collect_snapshot_action(&self) -> SnapshotAction {
-    if self.update_all {
-      SnapshotAction::NeedUpdate
-    } else {
-      SnapshotAction::AcceptNone
-    }
+    report_case_detail_impl(&mut self.output, case_id, result, self.color)
   }
 }
 
 pub struct InteractiveReporter<Output: Write> {
   pub output: Output,
   pub should_accept_all: bool,
+  pub color: bool,
 }
 
 const PROMPT: &str = "Accept new snapshot? (Yes[y], No[n], Accept All[a], Quit[q])";
@@ -265,9 +373,8 @@ impl<O: Write> Reporter for InteractiveReporter<O> {
   fn get_output(&mut self) -> &mut Self::Output {
     &mut self.output
   }
-
-  fn collect_snapshot_action(&self) -> SnapshotAction {
-    SnapshotAction::NeedUpdate
+  fn use_color(&self) -> bool {
+    self.color
   }
 
   fn report_case_detail(&mut self, case_id: &str, status: &mut CaseStatus) -> Result<bool> {
@@ -275,7 +382,7 @@ impl<O: Write> Reporter for InteractiveReporter<O> {
       return Ok(true);
     }
     run_in_alternate_screen(|| {
-      report_case_detail_impl(self.get_output(), case_id, status)?;
+      report_case_detail_impl(&mut self.output, case_id, status, self.color)?;
       if !matches!(status, CaseStatus::Wrong { .. }) {
         let response = prompt("Next[enter], Quit[q]", "q", Some('\n'))?;
         return Ok(response != 'q');
@@ -337,6 +444,7 @@ mod test {
     let mut reporter = DefaultReporter {
       output,
       update_all: false,
+      color: false,
     };
     reporter.report_case_summary(TEST_RULE, &mock_case_status())?;
     let s = String::from_utf8(reporter.output)?;
@@ -344,12 +452,36 @@ mod test {
     Ok(())
   }
 
+  #[test]
+  fn test_report_color() -> Result<()> {
+    let report = |color: bool| -> Result<String> {
+      let mut reporter = DefaultReporter {
+        output: vec![],
+        update_all: false,
+        color,
+      };
+      let mut statuses = mock_case_status();
+      reporter.report_case_summary(TEST_RULE, &statuses)?;
+      reporter.report_case_detail(TEST_RULE, &mut statuses[1])?;
+      let (_, message) = reporter.after_report(&[], None)?;
+      Ok(String::from_utf8(reporter.output)? + &message)
+    };
+    let plain = report(false)?;
+    assert!(plain.contains("FAIL test-rule"));
+    assert!(plain.contains("[Missing]"));
+    assert!(plain.contains("test result: ok."));
+    assert!(!plain.contains('\u{1b}'));
+    assert!(report(true)?.contains('\u{1b}'));
+    Ok(())
+  }
+
   #[test]
   fn test_many_cases() -> Result<()> {
     let output = vec![];
     let mut reporter = DefaultReporter {
       output,
       update_all: false,
+      color: false,
     };
     use std::iter::repeat_with;
     let cases: Vec<_> = repeat_with(mock_case_status).flatten().take(50).collect();
@@ -360,12 +492,32 @@ mod test {
     Ok(())
   }
 
+  #[test]
+  fn test_panicked_case_detail() -> Result<()> {
+    let output = vec![];
+    let mut reporter = DefaultReporter {
+      output,
+      update_all: false,
+      color: false,
+    };
+    let mut status = CaseStatus::Panicked("boom".into());
+    reporter.report_case_summary(TEST_RULE, std::slice::from_ref(&status))?;
+    reporter.report_case_detail(TEST_RULE, &mut status)?;
+    let s = String::from_utf8(reporter.output)?;
+    assert!(s.contains("FAIL"));
+    assert!(s.contains("Panicked"));
+    assert!(s.contains("panicked: boom"));
+    assert!(!s.contains("Fail to apply fix"));
+    Ok(())
+  }
+
   #[test]
   fn test_valid_case_detail() -> Result<()> {
     let output = vec![];
     let mut reporter = DefaultReporter {
       output,
       update_all: false,
+      color: false,
     };
     reporter.report_case_detail(TEST_RULE, &mut CaseStatus::Reported)?;
     reporter.report_case_detail(TEST_RULE, &mut CaseStatus::Validated)?;
@@ -380,6 +532,7 @@ mod test {
     let mut reporter = DefaultReporter {
       output,
       update_all: false,
+      color: false,
     };
     reporter.report_case_detail(TEST_RULE, &mut CaseStatus::Missing(MOCK))?;
     reporter.report_case_detail(TEST_RULE, &mut CaseStatus::Noisy(MOCK))?;
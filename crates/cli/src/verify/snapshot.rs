@@ -1,12 +1,15 @@
+mod format;
+
 use crate::lang::SgLang;
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use ast_grep_config::RuleConfig;
 use ast_grep_core::{Language, NodeMatch, StrDoc};
 
-use super::{CaseResult, Node};
+use super::{CaseStatus, Node};
 use serde::{Deserialize, Serialize, Serializer};
 
 use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
 
 type CaseId = String;
 type Source = String;
@@ -15,44 +18,76 @@ type Source = String;
 /// where each [TestSnapshots] is identified by its rule ID.
 pub type SnapshotCollection = HashMap<CaseId, TestSnapshots>;
 
-fn merge_snapshots(
-  accepted: SnapshotCollection,
-  mut existing: SnapshotCollection,
-) -> SnapshotCollection {
-  for (id, tests) in accepted {
-    if let Some(existing) = existing.get_mut(&id) {
-      existing.snapshots.extend(tests.snapshots);
-    } else {
-      existing.insert(id, tests);
-    }
-  }
-  existing
+/// Number of snapshots created, updated or left untouched by accepted changes.
+#[derive(Default, Debug, PartialEq, Eq)]
+pub struct SnapshotStats {
+  pub created: usize,
+  pub updated: usize,
+  pub untouched: usize,
 }
 
-/// Represents user's decision when [CaseStatus::Wrong].
-/// Snapshot update can be accepted or rejected.
-#[derive(Debug)]
-pub enum SnapshotAction {
-  /// Accept changes
-  NeedUpdate,
-  /// Reject all changes.
-  AcceptNone,
+/// Writes accepted snapshots to disk as soon as they are accepted,
+/// so a review quitted halfway still keeps the accepted changes.
+/// It is only used by the reporting thread, so writes to one snapshot file never race
+/// even though test cases run in parallel.
+pub struct SnapshotWriter {
+  existing: SnapshotCollection,
+  path_map: HashMap<CaseId, PathBuf>,
+  total: usize,
+  stats: SnapshotStats,
 }
 
-impl SnapshotAction {
-  pub fn update_snapshot_collection(
-    self,
-    existing: SnapshotCollection,
-    results: &[CaseResult],
-  ) -> Option<SnapshotCollection> {
-    let accepted = match self {
-      Self::NeedUpdate => results
-        .iter()
-        .map(|result| (result.id.to_string(), result.changed_snapshots()))
-        .collect(),
-      Self::AcceptNone => return None,
+impl SnapshotWriter {
+  pub fn new(existing: SnapshotCollection, path_map: HashMap<CaseId, PathBuf>) -> Self {
+    let total = existing.values().map(|s| s.snapshots.len()).sum();
+    Self {
+      existing,
+      path_map,
+      total,
+      stats: SnapshotStats::default(),
+    }
+  }
+
+  /// Accept the snapshot of an updated case and write the rule's snapshot file.
+  /// Other cases are ignored.
+  pub fn accept(&mut self, id: &str, status: &CaseStatus) -> Result<()> {
+    let CaseStatus::Updated { source, updated } = status else {
+      return Ok(());
+    };
+    let Some(dir) = self.path_map.get(id) else {
+      return Err(anyhow!(
+        "No snapshot directory is found for test case `{id}`."
+      ));
     };
-    Some(merge_snapshots(accepted, existing))
+    let tests = self
+      .existing
+      .entry(id.to_string())
+      .or_insert_with(|| TestSnapshots {
+        id: id.to_string(),
+        snapshots: HashMap::new(),
+      });
+    let previous = tests.snapshots.insert(source.to_string(), updated.clone());
+    if previous.is_some() {
+      self.stats.updated += 1;
+    } else {
+      self.stats.created += 1;
+    }
+    if !dir.exists() {
+      std::fs::create_dir(dir)?;
+    }
+    let file = dir.join(format!("{id}-snapshot.yml"));
+    let yaml = tests.to_yaml();
+    // leave the file untouched if nothing changes, e.g. an accepted snapshot equals the old one
+    if std::fs::read_to_string(&file).map_or(true, |old| old != yaml) {
+      std::fs::write(file, yaml)?;
+    }
+    Ok(())
+  }
+
+  pub fn finish(self) -> SnapshotStats {
+    let mut stats = self.stats;
+    stats.untouched = self.total.saturating_sub(stats.updated);
+    stats
   }
 }
 
@@ -67,6 +102,13 @@ pub struct TestSnapshots {
   pub snapshots: HashMap<Source, TestSnapshot>,
 }
 
+impl TestSnapshots {
+  /// Serialize the snapshots in a stable format. See [format] for details.
+  pub fn to_yaml(&self) -> String {
+    format::to_yaml(self)
+  }
+}
+
 #[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct TestSnapshot {
@@ -229,27 +271,45 @@ mod tests {
   }
 
   #[test]
-  fn test_snapshot_action() -> Result<()> {
-    use crate::verify::CaseStatus;
-    let action = SnapshotAction::NeedUpdate;
+  fn test_snapshot_writer() -> Result<()> {
+    let dir = tempfile::tempdir()?;
     let rule_config = get_rule_config("pattern: let x = $A");
-    let sc = SnapshotCollection::new();
-    let op = action
-      .update_snapshot_collection(
-        sc,
-        &[CaseResult {
-          id: TEST_RULE,
-          cases: vec![CaseStatus::Updated {
-            source: "let x = 123",
-            updated: TestSnapshot::generate(&rule_config, "let x = 123")?.unwrap(),
-          }],
-        }],
-      )
-      .expect("should have new op");
+    let path_map = HashMap::from([(TEST_RULE.to_string(), dir.path().join("__snapshots__"))]);
+    let mut writer = SnapshotWriter::new(SnapshotCollection::new(), path_map);
+    let updated = TestSnapshot::generate(&rule_config, "let x = 123")?.unwrap();
+    let status = CaseStatus::Updated {
+      source: "let x = 123",
+      updated,
+    };
+    writer.accept(TEST_RULE, &status)?;
+    writer.accept(TEST_RULE, &CaseStatus::Validated)?;
+    let file = dir.path().join("__snapshots__/test-rule-snapshot.yml");
+    let written: TestSnapshots = serde_yaml::from_str(&std::fs::read_to_string(file)?)?;
     assert_eq!(
-      op[TEST_RULE].snapshots["let x = 123"].labels[0].source,
+      written.snapshots["let x = 123"].labels[0].source,
       "let x = 123"
     );
+    writer.accept(TEST_RULE, &status)?;
+    let expected = SnapshotStats {
+      created: 1,
+      updated: 1,
+      untouched: 0,
+    };
+    assert_eq!(writer.finish(), expected);
+    Ok(())
+  }
+
+  #[test]
+  fn test_snapshot_writer_unknown_case() -> Result<()> {
+    let rule_config = get_rule_config("pattern: let x = $A");
+    let mut writer = SnapshotWriter::new(SnapshotCollection::new(), HashMap::new());
+    let updated = TestSnapshot::generate(&rule_config, "let x = 123")?.unwrap();
+    let status = CaseStatus::Updated {
+      source: "let x = 123",
+      updated,
+    };
+    assert!(writer.accept(TEST_RULE, &status).is_err());
+    assert_eq!(writer.finish(), SnapshotStats::default());
     Ok(())
   }
 }
@@ -0,0 +1,287 @@
+//! Deterministic YAML for snapshot files, independent of the yaml library version.
+//!
+//! Keys are sorted, strings are written as literal block scalars if they span
+//! multiple lines and as plain scalars otherwise. Strings that cannot be written
+//! in either style are double quoted. The output only uses `\n` line breaks and
+//! ends with exactly one newline.
+//! Keys that are block scalars or longer than YAML's limit of implicit keys use
+//! the explicit `? ` form.
+
+use super::{Label, LabelStyle, TestSnapshot, TestSnapshots};
+
+use serde_yaml::Value;
+
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+
+/// Implicit keys, i.e. `key: value`, are limited to 1024 characters in YAML 1.2.
+const MAX_IMPLICIT_KEY: usize = 1024;
+
+pub fn to_yaml(snapshots: &TestSnapshots) -> String {
+  let mut out = String::new();
+  out.push_str("id: ");
+  write_scalar(&mut out, &snapshots.id, 0);
+  out.push_str("snapshots:");
+  if snapshots.snapshots.is_empty() {
+    out.push_str(" {}\n");
+    return out;
+  }
+  out.push('\n');
+  let ordered: BTreeMap<_, _> = snapshots.snapshots.iter().collect();
+  for (source, snapshot) in ordered {
+    let mut key = String::new();
+    write_scalar(&mut key, source, 2);
+    // the key is written with a trailing newline
+    if is_block(source) || key.len() - 1 > MAX_IMPLICIT_KEY {
+      out.push_str("  ? ");
+      out.push_str(&key);
+      out.push_str("  : ");
+    } else {
+      key.pop();
+      out.push_str("  ");
+      out.push_str(&key);
+      out.push_str(":\n    ");
+    }
+    write_snapshot(&mut out, snapshot);
+  }
+  out
+}
+
+/// Write the snapshot mapping. The first line is already indented.
+fn write_snapshot(out: &mut String, snapshot: &TestSnapshot) {
+  if let Some(fixed) = &snapshot.fixed {
+    out.push_str("fixed: ");
+    write_scalar(out, fixed, 4);
+    out.push_str("    ");
+  }
+  if snapshot.labels.is_empty() {
+    out.push_str("labels: []\n");
+    return;
+  }
+  out.push_str("labels:\n");
+  for label in &snapshot.labels {
+    write_label(out, label);
+  }
+}
+
+fn write_label(out: &mut String, label: &Label) {
+  out.push_str("    - source: ");
+  write_scalar(out, &label.source, 6);
+  if let Some(message) = &label.message {
+    out.push_str("      message: ");
+    write_scalar(out, message, 6);
+  }
+  let style = match label.style {
+    LabelStyle::Primary => "primary",
+    LabelStyle::Secondary => "secondary",
+  };
+  // writing to String never fails
+  let _ = writeln!(out, "      style: {style}");
+  let _ = writeln!(out, "      start: {}", label.start);
+  let _ = writeln!(out, "      end: {}", label.end);
+}
+
+/// Write a string value followed by a newline.
+/// `indent` is the indentation of the mapping containing the value.
+fn write_scalar(out: &mut String, s: &str, indent: usize) {
+  if is_block(s) {
+    write_block(out, s, indent + 2);
+  } else if is_plain(s) {
+    out.push_str(s);
+    out.push('\n');
+  } else {
+    write_double_quoted(out, s);
+    out.push('\n');
+  }
+}
+
+fn is_block(s: &str) -> bool {
+  if !s.contains('\n') {
+    return false;
+  }
+  let printable = s
+    .chars()
+    .all(|c| c == '\n' || c == '\t' || !c.is_control() && c != '\u{feff}');
+  // block indentation is detected from the first non-empty line
+  let first_line = s.lines().find(|line| !line.is_empty()).unwrap_or("");
+  printable && !first_line.starts_with([' ', '\t'])
+}
+
+fn write_block(out: &mut String, s: &str, indent: usize) {
+  let content = s.trim_end_matches('\n');
+  let chomping = match s.len() - content.len() {
+    0 => "-",
+    1 => "",
+    _ => "+",
+  };
+  out.push('|');
+  out.push_str(chomping);
+  out.push('\n');
+  let padding = " ".repeat(indent);
+  for line in s.split_inclusive('\n') {
+    if line != "\n" {
+      out.push_str(&padding);
+    }
+    out.push_str(line);
+  }
+  if chomping == "-" {
+    out.push('\n');
+  }
+}
+
+const INDICATORS: &[char] = &[
+  '-', '?', ':', ',', '[', ']', '{', '}', '#', '&', '*', '!', '|', '>', '\'', '"', '%', '@', '`',
+];
+
+/// Plain scalars must be read back as the same string, not as numbers, booleans or null.
+fn is_plain(s: &str) -> bool {
+  let Some(first) = s.chars().next() else {
+    return false;
+  };
+  let simple = !INDICATORS.contains(&first)
+    && s.trim() == s
+    && !s.ends_with(':')
+    && !s.contains(": ")
+    && !s.contains(" #")
+    && s.chars().all(|c| !c.is_control() && c != '\u{feff}');
+  simple && matches!(serde_yaml::from_str(s), Ok(Value::String(v)) if v == s)
+}
+
+fn write_double_quoted(out: &mut String, s: &str) {
+  out.push('"');
+  for c in s.chars() {
+    match c {
+      '"' => out.push_str("\\\""),
+      '\\' => out.push_str("\\\\"),
+      '\n' => out.push_str("\\n"),
+      '\r' => out.push_str("\\r"),
+      '\t' => out.push_str("\\t"),
+      c if c.is_control() || c == '\u{feff}' => {
+        let _ = write!(out, "\\u{:04x}", c as u32);
+      }
+      c => out.push(c),
+    }
+  }
+  out.push('"');
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use std::collections::HashMap;
+
+  fn label(source: &str) -> Label {
+    Label {
+      source: source.into(),
+      message: None,
+      style: LabelStyle::Primary,
+      start: 0,
+      end: source.len(),
+    }
+  }
+
+  fn snapshots(entries: &[(&str, Option<&str>)]) -> TestSnapshots {
+    let snapshots: HashMap<_, _> = entries
+      .iter()
+      .map(|(source, fixed)| {
+        let snapshot = TestSnapshot {
+          fixed: fixed.map(String::from),
+          labels: vec![label(source)],
+        };
+        (source.to_string(), snapshot)
+      })
+      .collect();
+    TestSnapshots {
+      id: "test-rule".into(),
+      snapshots,
+    }
+  }
+
+  #[test]
+  fn test_format() {
+    let yaml = to_yaml(&snapshots(&[
+      ("let b = 2", None),
+      ("let a = 1\nfoo()", Some("let a = 2\nfoo()\n")),
+    ]));
+    let expected = "\
+id: test-rule
+snapshots:
+  ? |-
+    let a = 1
+    foo()
+  : fixed: |
+      let a = 2
+      foo()
+    labels:
+    - source: |-
+        let a = 1
+        foo()
+      style: primary
+      start: 0
+      end: 15
+  let b = 2:
+    labels:
+    - source: let b = 2
+      style: primary
+      start: 0
+      end: 9
+";
+    assert_eq!(yaml, expected);
+  }
+
+  #[test]
+  fn test_round_trip() {
+    let tricky = [
+      "123",
+      "true",
+      "null",
+      "",
+      " leading space",
+      "trailing space ",
+      "a: b",
+      "a #b",
+      "- item",
+      "'quoted'",
+      "\"double\"",
+      "tab\there",
+      "crlf\r\nline",
+      "\n\nleading newlines",
+      "  indented\nsecond",
+      "trailing newlines\n\n",
+      "empty\n\nline",
+      "back\\slash",
+      "bell\u{7}",
+      "unicode ✓",
+    ];
+    let entries: Vec<_> = tricky.iter().map(|s| (*s, Some(*s))).collect();
+    let expected = snapshots(&entries);
+    let yaml = to_yaml(&expected);
+    assert!(!yaml.contains('\r'));
+    assert!(yaml.ends_with("\n") && !yaml.ends_with("\n\n"));
+    let parsed: TestSnapshots = serde_yaml::from_str(&yaml).expect("should parse");
+    assert_eq!(parsed.snapshots, expected.snapshots);
+    assert_eq!(to_yaml(&parsed), yaml);
+  }
+
+  #[test]
+  fn test_long_key() {
+    let long = "a".repeat(MAX_IMPLICIT_KEY + 1);
+    let quoted = format!("'{}'", "b".repeat(MAX_IMPLICIT_KEY));
+    let short = "c".repeat(MAX_IMPLICIT_KEY);
+    let expected = snapshots(&[(&long, None), (&quoted, None), (&short, None)]);
+    let yaml = to_yaml(&expected);
+    assert!(yaml.contains(&format!("  ? {long}\n  : labels:")));
+    assert!(yaml.contains(&format!("  {short}:\n    labels:")));
+    let parsed: TestSnapshots = serde_yaml::from_str(&yaml).expect("should parse");
+    assert_eq!(parsed.snapshots, expected.snapshots);
+    assert_eq!(to_yaml(&parsed), yaml);
+  }
+
+  #[test]
+  fn test_empty_snapshots() {
+    let yaml = to_yaml(&snapshots(&[]));
+    assert_eq!(yaml, "id: test-rule\nsnapshots: {}\n");
+    let parsed: TestSnapshots = serde_yaml::from_str(&yaml).expect("should parse");
+    assert!(parsed.snapshots.is_empty());
+  }
+}
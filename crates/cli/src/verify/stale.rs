@@ -0,0 +1,144 @@
+use super::{SnapshotCollection, TestCase, TestSnapshots};
+
+use anyhow::{Context, Result};
+use ast_grep_config::from_str;
+
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+/// Snapshot entries in one snapshot file that no test case refers to.
+#[derive(Debug, PartialEq, Eq)]
+pub struct StaleSnapshot {
+  pub id: String,
+  pub path: PathBuf,
+  /// Sources of the stale entries, sorted.
+  pub sources: Vec<String>,
+  /// Every entry in the file is stale.
+  pub whole_file: bool,
+}
+
+/// Find snapshot entries whose key is not the snapshot key of any invalid case
+/// of the test with the same id. See [super::test_case::InvalidCase::snapshot_key].
+pub fn find_stale_snapshots(
+  test_cases: &[TestCase],
+  snapshots: &SnapshotCollection,
+  snapshot_files: &HashMap<String, PathBuf>,
+) -> Vec<StaleSnapshot> {
+  let mut referenced: HashMap<&str, HashSet<&str>> = HashMap::new();
+  for case in test_cases {
+    let keys = referenced.entry(case.id.as_str()).or_default();
+    keys.extend(case.invalid.iter().map(|c| c.snapshot_key()));
+  }
+  let mut ret: Vec<_> = snapshots
+    .values()
+    .filter_map(|snapshot| {
+      let path = snapshot_files.get(&snapshot.id)?;
+      let keys = referenced.get(snapshot.id.as_str());
+      let mut sources: Vec<_> = snapshot
+        .snapshots
+        .keys()
+        .filter(|source| keys.map_or(true, |k| !k.contains(source.as_str())))
+        .cloned()
+        .collect();
+      if sources.is_empty() && keys.is_some() {
+        return None;
+      }
+      sources.sort();
+      Some(StaleSnapshot {
+        id: snapshot.id.clone(),
+        path: path.clone(),
+        whole_file: sources.len() == snapshot.snapshots.len(),
+        sources,
+      })
+    })
+    .collect();
+  ret.sort_by(|a, b| a.path.cmp(&b.path));
+  ret
+}
+
+impl StaleSnapshot {
+  /// Remove the stale entries from the snapshot file, and the file itself if nothing is left.
+  /// The file is read again since accepted snapshots may have been written to it.
+  /// Returns true if the file is removed.
+  pub fn prune(&self) -> Result<bool> {
+    let error_context = || format!("Cannot prune snapshot file {}", self.path.display());
+    let yaml = std::fs::read_to_string(&self.path).with_context(error_context)?;
+    let mut snapshot: TestSnapshots = from_str(&yaml).with_context(error_context)?;
+    for source in &self.sources {
+      snapshot.snapshots.remove(source);
+    }
+    if snapshot.snapshots.is_empty() {
+      std::fs::remove_file(&self.path).with_context(error_context)?;
+      return Ok(true);
+    }
+    std::fs::write(&self.path, snapshot.to_yaml()).with_context(error_context)?;
+    Ok(false)
+  }
+
+  /// Path relative to `base_dir` if possible.
+  pub fn display_path(&self, base_dir: &Path) -> PathBuf {
+    let path = &self.path;
+    path
+      .strip_prefix(base_dir)
+      .map_or_else(|_| path.clone(), Path::to_path_buf)
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::verify::snapshot::TestSnapshot;
+
+  fn snapshots(id: &str, sources: &[&str]) -> TestSnapshots {
+    let snapshot = TestSnapshot {
+      fixed: None,
+      labels: vec![],
+    };
+    TestSnapshots {
+      id: id.into(),
+      snapshots: sources
+        .iter()
+        .map(|s| (s.to_string(), snapshot.clone()))
+        .collect(),
+    }
+  }
+
+  #[test]
+  fn test_find_stale_snapshots() {
+    let test_cases = [TestCase {
+      id: "a".into(),
+      valid: vec!["c".into()],
+      invalid: vec!["x".into()],
+    }];
+    let collection: SnapshotCollection = [
+      snapshots("a", &["x", "y", "c"]),
+      snapshots("b", &["x"]),
+      snapshots("not-read", &["x"]),
+    ]
+    .into_iter()
+    .map(|s| (s.id.clone(), s))
+    .collect();
+    let files = [("a", "snap/a.yml"), ("b", "snap/b.yml")]
+      .into_iter()
+      .map(|(id, path)| (id.to_string(), PathBuf::from(path)))
+      .collect();
+    let stale = find_stale_snapshots(&test_cases, &collection, &files);
+    assert_eq!(
+      stale,
+      vec![
+        StaleSnapshot {
+          id: "a".into(),
+          path: "snap/a.yml".into(),
+          sources: vec!["c".into(), "y".into()],
+          whole_file: false,
+        },
+        StaleSnapshot {
+          id: "b".into(),
+          path: "snap/b.yml".into(),
+          sources: vec!["x".into()],
+          whole_file: true,
+        },
+      ]
+    );
+  }
+}
@@ -1,5 +1,6 @@
-use super::case_result::{CaseResult, CaseStatus};
-use super::snapshot::TestSnapshots;
+use super::case_result::CaseStatus;
+use super::diagnostic::{parse_markers, ExpectedDiagnostic};
+use super::snapshot::SnapshotCollection;
 use crate::lang::SgLang;
 
 use ast_grep_config::RuleConfig;
@@ -10,7 +11,8 @@ use serde::{Deserialize, Serialize};
 /// A rule-test contains these fields:
 /// * id: the id of the rule that will be tested against
 /// * valid: code that we do not expect to have any issues
-/// * invalid: code that we do expect to have some issues
+/// * invalid: code that we do expect to have some issues, optionally with the expected fixed code
+///   and the expected diagnostics
 #[derive(Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct TestCase {
@@ -18,73 +20,205 @@ pub struct TestCase {
   #[serde(default)]
   pub valid: Vec<String>,
   #[serde(default)]
-  pub invalid: Vec<String>,
+  pub invalid: Vec<InvalidCase>,
 }
 
-impl TestCase {
-  pub fn verify_rule(&self, rule_config: &RuleConfig<SgLang>) -> CaseResult {
-    debug_assert_eq!(self.id, rule_config.id);
-    verify_test_case(self, rule_config)
+/// The `tests` block embedded in a rule file. It has the same schema as [TestCase]
+/// except that the id is the rule's own id.
+#[derive(Deserialize)]
+pub struct InlineTests {
+  #[serde(default)]
+  pub valid: Vec<String>,
+  #[serde(default)]
+  pub invalid: Vec<InvalidCase>,
+}
+
+impl InlineTests {
+  pub fn into_test_case(self, id: String) -> TestCase {
+    TestCase {
+      id,
+      valid: self.valid,
+      invalid: self.invalid,
+    }
   }
+}
 
-  pub fn verify_with_snapshot(
-    &self,
-    rule_config: &RuleConfig<SgLang>,
-    snapshots: Option<&TestSnapshots>,
-  ) -> CaseResult {
-    debug_assert_eq!(self.id, rule_config.id);
-    verify_test_case_with_snapshots(self, rule_config, snapshots)
+/// An invalid case can be written as plain code or as an object with `code`, `fixed` and `expected`.
+/// If `fixed` is present, the rule's fix is applied to `code` and compared with it exactly.
+/// If `expected` is present, or `code` has `~~~` marker comments, the reported diagnostics
+/// must be exactly the expected ones. See [super::diagnostic] for the marker syntax.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(from = "SerializableInvalidCase", into = "SerializableInvalidCase")]
+pub struct InvalidCase {
+  pub code: String,
+  pub fixed: Option<String>,
+  pub expected: Vec<ExpectedDiagnostic>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(untagged)]
+enum SerializableInvalidCase {
+  Code(String),
+  #[serde(rename_all = "camelCase")]
+  Object {
+    code: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    fixed: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    expected: Vec<ExpectedDiagnostic>,
+  },
+}
+
+impl From<SerializableInvalidCase> for InvalidCase {
+  fn from(case: SerializableInvalidCase) -> Self {
+    match case {
+      SerializableInvalidCase::Code(code) => code.as_str().into(),
+      SerializableInvalidCase::Object {
+        code,
+        fixed,
+        expected,
+      } => Self {
+        code,
+        fixed,
+        expected,
+      },
+    }
+  }
+}
+
+impl From<InvalidCase> for SerializableInvalidCase {
+  fn from(case: InvalidCase) -> Self {
+    if case.fixed.is_none() && case.expected.is_empty() {
+      return Self::Code(case.code);
+    }
+    Self::Object {
+      code: case.code,
+      fixed: case.fixed,
+      expected: case.expected,
+    }
   }
 }
 
-fn verify_test_case<'a>(
-  test_case: &'a TestCase,
-  rule_config: &RuleConfig<SgLang>,
-) -> CaseResult<'a> {
-  let valid_cases = test_case
-    .valid
-    .iter()
-    .map(|valid| CaseStatus::verify_valid(rule_config, valid));
-  let invalid_cases = test_case
-    .invalid
-    .iter()
-    .map(|invalid| CaseStatus::verify_invalid(rule_config, invalid));
-  CaseResult {
-    id: &test_case.id,
-    cases: valid_cases.chain(invalid_cases).collect(),
+impl From<&str> for InvalidCase {
+  fn from(code: &str) -> Self {
+    Self {
+      code: code.to_string(),
+      fixed: None,
+      expected: vec![],
+    }
   }
 }
 
-fn verify_test_case_with_snapshots<'a>(
-  test_case: &'a TestCase,
-  rule_config: &RuleConfig<SgLang>,
-  snapshots: Option<&TestSnapshots>,
-) -> CaseResult<'a> {
-  let valid_cases = test_case
-    .valid
-    .iter()
-    .map(|valid| CaseStatus::verify_valid(rule_config, valid));
-  let invalid_cases = test_case.invalid.iter().map(|invalid| {
-    let snap = snapshots.and_then(|s| s.snapshots.get(invalid));
+impl InvalidCase {
+  /// Key of the case in the rule's snapshot file.
+  pub fn snapshot_key(&self) -> &str {
+    &self.code
+  }
+
+  /// Explicit `expected` entries take precedence over marker comments.
+  pub fn expected_diagnostics(&self) -> Vec<ExpectedDiagnostic> {
+    if self.expected.is_empty() {
+      parse_markers(&self.code)
+    } else {
+      self.expected.clone()
+    }
+  }
+}
+
+impl TestCase {
+  /// Number of valid and invalid cases.
+  pub fn case_count(&self) -> usize {
+    self.valid.len() + self.invalid.len()
+  }
+
+  /// Append cases of another test for the same rule, skipping cases identical to existing ones.
+  /// Returns the number of skipped duplicates.
+  pub fn merge(&mut self, other: TestCase) -> usize {
+    debug_assert_eq!(self.id, other.id);
+    let mut duplicates = 0;
+    for valid in other.valid {
+      if self.valid.contains(&valid) {
+        duplicates += 1;
+      } else {
+        self.valid.push(valid);
+      }
+    }
+    for invalid in other.invalid {
+      if self.invalid.contains(&invalid) {
+        duplicates += 1;
+      } else {
+        self.invalid.push(invalid);
+      }
+    }
+    duplicates
+  }
+
+  /// Verify the case at `index`. Valid cases come first, followed by invalid cases.
+  /// Invalid cases are compared with snapshots unless `snapshots` is None.
+  pub fn verify_case(
+    &self,
+    rule_config: &RuleConfig<SgLang>,
+    index: usize,
+    snapshots: Option<&SnapshotCollection>,
+  ) -> CaseStatus {
+    debug_assert_eq!(self.id, rule_config.id);
+    if let Some(valid) = self.valid.get(index) {
+      return CaseStatus::verify_valid(rule_config, valid);
+    }
+    let case = &self.invalid[index - self.valid.len()];
+    let invalid = &case.code;
+    if let Some(fixed) = &case.fixed {
+      if let Some(status) = CaseStatus::verify_fixed(rule_config, invalid, fixed) {
+        return status;
+      }
+    }
+    let expected = case.expected_diagnostics();
+    if !expected.is_empty() {
+      if let Some(status) = CaseStatus::verify_diagnostics(rule_config, invalid, expected) {
+        return status;
+      }
+    }
+    let Some(snapshots) = snapshots else {
+      return CaseStatus::verify_invalid(rule_config, invalid);
+    };
+    let snap = snapshots
+      .get(&self.id)
+      .and_then(|s| s.snapshots.get(case.snapshot_key()));
     CaseStatus::verify_snapshot(rule_config, invalid, snap)
-  });
-  CaseResult {
-    id: &test_case.id,
-    cases: valid_cases.chain(invalid_cases).collect(),
   }
 }
 
 #[cfg(test)]
 mod tests {
   use super::*;
-  use crate::verify::snapshot::TestSnapshot;
+  use crate::verify::snapshot::{TestSnapshot, TestSnapshots};
   use crate::verify::test::{get_rule_config, TEST_RULE};
 
+  fn verify_rule<'a>(
+    test_case: &'a TestCase,
+    rule_config: &RuleConfig<SgLang>,
+  ) -> Vec<CaseStatus<'a>> {
+    (0..test_case.case_count())
+      .map(|i| test_case.verify_case(rule_config, i, None))
+      .collect()
+  }
+
+  fn verify_with_snapshot<'a>(
+    test_case: &'a TestCase,
+    rule_config: &RuleConfig<SgLang>,
+    snapshots: Option<TestSnapshots>,
+  ) -> Vec<CaseStatus<'a>> {
+    let collection: SnapshotCollection = snapshots.map(|s| (s.id.clone(), s)).into_iter().collect();
+    (0..test_case.case_count())
+      .map(|i| test_case.verify_case(rule_config, i, Some(&collection)))
+      .collect()
+  }
+
   fn mock_test_case(valid: &[&str], invalid: &[&str]) -> TestCase {
     TestCase {
       id: TEST_RULE.to_string(),
       valid: valid.iter().map(|s| s.to_string()).collect(),
-      invalid: invalid.iter().map(|s| s.to_string()).collect(),
+      invalid: invalid.iter().map(|&s| s.into()).collect(),
     }
   }
 
@@ -101,27 +235,31 @@ mod tests {
       .expect("should generate")
   }
 
+  #[test]
+  fn test_merge() {
+    let mut test_case = mock_test_case(&["a", "b"], &["c"]);
+    let other = mock_test_case(&["b", "d"], &["c", "e"]);
+    assert_eq!(test_case.merge(other), 2);
+    assert_eq!(test_case.valid, ["a", "b", "d"]);
+    assert_eq!(test_case.invalid, ["c".into(), "e".into()]);
+  }
+
   #[test]
   fn test_verify_rule() {
     let rule_config = get_rule_config("pattern: let x = $A");
     let test_case = mock_test_case(&["var x = 123"], &["let x = 123"]);
-    let result = test_case.verify_rule(&rule_config);
-    assert_eq!(result.id, test_case.id);
-    assert!(matches!(result.cases[0], CaseStatus::Validated));
-    assert!(matches!(result.cases[1], CaseStatus::Reported));
+    let result = verify_rule(&test_case, &rule_config);
+    assert!(matches!(result[0], CaseStatus::Validated));
+    assert!(matches!(result[1], CaseStatus::Reported));
   }
 
   #[test]
   fn test_invalid() {
     let rule_config = get_rule_config("pattern: let x = $A");
     let test_case = mock_test_case(&["let x = 123"], &["var x = 123"]);
-    let result = test_case.verify_rule(&rule_config);
-    assert_eq!(result.id, test_case.id);
-    assert!(matches!(result.cases[0], CaseStatus::Noisy("let x = 123")));
-    assert!(matches!(
-      result.cases[1],
-      CaseStatus::Missing("var x = 123")
-    ));
+    let result = verify_rule(&test_case, &rule_config);
+    assert!(matches!(result[0], CaseStatus::Noisy("let x = 123")));
+    assert!(matches!(result[1], CaseStatus::Missing("var x = 123")));
   }
   #[test]
   fn test_verify_snapshot_with_existing() {
@@ -129,8 +267,8 @@ mod tests {
     let test_case = mock_test_case(&[], &["let x = 123"]);
     let snap = mock_snapshot(&rule_config, "let x = 123");
     let snaps = mock_snapshots("let x = 123", snap.clone());
-    let result = test_case.verify_with_snapshot(&rule_config, Some(&snaps));
-    assert_eq!(result.cases[0], CaseStatus::Reported);
+    let result = verify_with_snapshot(&test_case, &rule_config, Some(snaps));
+    assert_eq!(result[0], CaseStatus::Reported);
   }
 
   #[test]
@@ -139,10 +277,10 @@ mod tests {
     let test_case = mock_test_case(&["var x = 123"], &["let x = 123"]);
     let snap = mock_snapshot(&rule_config, "let x = 456");
     let snaps = mock_snapshots("let x = 123", snap.clone());
-    let result = test_case.verify_with_snapshot(&rule_config, Some(&snaps));
-    assert_eq!(result.cases[0], CaseStatus::Validated);
+    let result = verify_with_snapshot(&test_case, &rule_config, Some(snaps));
+    assert_eq!(result[0], CaseStatus::Validated);
     assert_eq!(
-      result.cases[1],
+      result[1],
       CaseStatus::Wrong {
         source: "let x = 123",
         actual: mock_snapshot(&rule_config, "let x = 123"),
@@ -155,10 +293,10 @@ mod tests {
   fn test_verify_snapshot_without_existing() {
     let rule_config = get_rule_config("pattern: let x = $A");
     let test_case = mock_test_case(&["var x = 123"], &["let x = 123"]);
-    let result = test_case.verify_with_snapshot(&rule_config, None);
-    assert_eq!(result.cases[0], CaseStatus::Validated);
+    let result = verify_with_snapshot(&test_case, &rule_config, None);
+    assert_eq!(result[0], CaseStatus::Validated);
     assert_eq!(
-      result.cases[1],
+      result[1],
       CaseStatus::Wrong {
         source: "let x = 123",
         actual: mock_snapshot(&rule_config, "let x = 123"),
@@ -173,10 +311,10 @@ mod tests {
     let test_case = mock_test_case(&["var x = 123"], &["let x = 123"]);
     let snap = mock_snapshot(&rule_config, "let x = 456");
     let snaps = mock_snapshots("let x = 456", snap.clone());
-    let result = test_case.verify_with_snapshot(&rule_config, Some(&snaps));
-    assert_eq!(result.cases[0], CaseStatus::Validated);
+    let result = verify_with_snapshot(&test_case, &rule_config, Some(snaps));
+    assert_eq!(result[0], CaseStatus::Validated);
     assert_eq!(
-      result.cases[1],
+      result[1],
       CaseStatus::Wrong {
         source: "let x = 123",
         actual: mock_snapshot(&rule_config, "let x = 123"),
@@ -185,15 +323,91 @@ mod tests {
     );
   }
 
+  #[test]
+  fn test_deserialize_invalid_case() {
+    let test_case: TestCase = ast_grep_config::from_str(
+      "{id: a, invalid: ['let x = 1', {code: 'let x = 2', fixed: 'let y = 2'}, {code: b}]}",
+    )
+    .expect("should parse");
+    assert_eq!(test_case.invalid[0], "let x = 1".into());
+    assert_eq!(test_case.invalid[1].code, "let x = 2");
+    assert_eq!(test_case.invalid[1].fixed.as_deref(), Some("let y = 2"));
+    assert_eq!(test_case.invalid[2], "b".into());
+  }
+
+  fn fixed_case(code: &str, fixed: &str) -> TestCase {
+    TestCase {
+      id: TEST_RULE.to_string(),
+      valid: vec![],
+      invalid: vec![InvalidCase {
+        fixed: Some(fixed.into()),
+        ..code.into()
+      }],
+    }
+  }
+
+  #[test]
+  fn test_verify_fixed() {
+    let rule_config = get_rule_config("pattern: let x = $A\nfix: let y = $A");
+    let test_case = fixed_case("let x = 1\nlet x = 2", "let y = 1\nlet y = 2");
+    let result = verify_rule(&test_case, &rule_config);
+    assert_eq!(result[0], CaseStatus::Reported);
+    let test_case = fixed_case("let x = 1", "let z = 1");
+    let result = verify_rule(&test_case, &rule_config);
+    assert_eq!(
+      result[0],
+      CaseStatus::WrongFix {
+        source: "let x = 1",
+        expected: "let z = 1",
+        actual: "let y = 1".into(),
+      }
+    );
+    // no match is reported as missing
+    let test_case = fixed_case("var x = 1", "var x = 1");
+    let result = verify_rule(&test_case, &rule_config);
+    assert_eq!(result[0], CaseStatus::Missing("var x = 1"));
+  }
+
+  #[test]
+  fn test_verify_no_fix() {
+    let rule_config = get_rule_config("pattern: let x = $A");
+    let test_case = fixed_case("let x = 1", "let y = 1");
+    let result = verify_rule(&test_case, &rule_config);
+    assert_eq!(result[0], CaseStatus::NoFix("let x = 1"));
+  }
+
+  #[test]
+  fn test_verify_diagnostics() {
+    let rule_config = get_rule_config("pattern: let x = $A");
+    let test_case = mock_test_case(&[], &["let x = 1\n// ~~~~~~~~~\nlet x = 2\n// ~~~~~~~~~"]);
+    let result = verify_rule(&test_case, &rule_config);
+    assert_eq!(result[0], CaseStatus::Reported);
+    let test_case = mock_test_case(&[], &["let x = 1\n// ~~~~"]);
+    let result = verify_rule(&test_case, &rule_config);
+    assert!(matches!(result[0], CaseStatus::WrongDiagnostics { .. }));
+  }
+
+  #[test]
+  fn test_deserialize_expected() {
+    let test_case: TestCase = ast_grep_config::from_str(
+      "{id: a, invalid: [{code: 'let x = 1', expected: [{line: 1, column: 1, endLine: 1, endColumn: 10}]}]}",
+    )
+    .expect("should parse");
+    let expected = &test_case.invalid[0].expected;
+    assert_eq!(expected.len(), 1);
+    assert_eq!((expected[0].end_line, expected[0].end_column), (1, 10));
+    assert_eq!(expected[0].message, None);
+  }
+
   #[test]
   #[should_panic]
   fn test_unmatching_id() {
     let rule_config = get_rule_config("pattern: let x = $A");
     let test_case = TestCase {
       id: "non-matching".into(),
-      valid: vec![],
+      valid: vec!["let x = 123".into()],
       invalid: vec![],
     };
-    test_case.verify_rule(&rule_config);
+    test_case.verify_case(&rule_config, 0, None);
   }
 }
@@ -0,0 +1,343 @@
+//! `sg test --watch` runs tests again when rule, util or test files change.
+
+use super::find_file::SNAPSHOT_DIR;
+use super::reporter::{DefaultReporter, Reporter};
+use super::{verify_test_cases, SnapshotWriter, TestArg, TestHarness};
+use crate::config::{find_config_path_with_default, AstGrepConfig};
+use crate::utils::{clear, print_error, thread_count, ErrorContext as EC};
+
+use ansi_term::{Color, Style};
+use anyhow::{Context, Result};
+use ast_grep_config::from_str;
+use ast_grep_language::config_file_type;
+use ignore::WalkBuilder;
+use serde::Deserialize;
+use serde_yaml::{Deserializer, Value};
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::read_to_string;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::sleep;
+use std::time::{Duration, SystemTime};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+/// Wait until files stop changing, so that rapid successive saves trigger one run.
+const DEBOUNCE: Duration = Duration::from_millis(300);
+
+type Mtimes = BTreeMap<PathBuf, SystemTime>;
+
+pub fn watch_tests(arg: TestArg) -> Result<()> {
+  let stopped = Arc::new(AtomicBool::new(false));
+  let flag = stopped.clone();
+  ctrlc::set_handler(move || flag.store(true, Ordering::SeqCst))
+    .context("Cannot listen to Ctrl-C")?;
+  let watched = WatchedPaths::new(&arg)?;
+  let mut status = BTreeMap::new();
+  let mut runs = 0;
+  let mut affected = None;
+  loop {
+    clear()?;
+    match run_once(&arg, affected.as_ref()) {
+      Ok(results) => {
+        print_delta(&status, &results, arg.color.should_use_color());
+        status.extend(results);
+      }
+      Err(err) => print_error(&err, arg.color),
+    }
+    runs += 1;
+    let (passed, failed) = count(&status);
+    println!("Watching for changes: {passed} passed; {failed} failed. Press Ctrl-C to exit.");
+    let Some(changed) = watched.wait_for_changes(&stopped) else {
+      break;
+    };
+    affected = watched.affected_rules(&changed);
+  }
+  let (passed, failed) = count(&status);
+  println!("\nStopped watching after {runs} run(s): {passed} passed; {failed} failed.");
+  Ok(())
+}
+
+/// Run tests of `affected` rules, or all tests if None. Returns whether each rule passed.
+fn run_once(arg: &TestArg, affected: Option<&BTreeSet<String>>) -> Result<BTreeMap<String, bool>> {
+  let (rules, _) = arg.load_rules()?;
+  let regex_filter = arg.regex_filter()?;
+  let TestHarness {
+    mut test_cases,
+    snapshots,
+    path_map,
+    ..
+  } = arg.load_harness(regex_filter.as_ref(), arg.case.as_deref())?;
+  if let Some(ids) = affected {
+    test_cases.retain(|case| ids.contains(&case.id));
+  }
+  let mut reporter = DefaultReporter {
+    output: std::io::stdout(),
+    update_all: arg.update_all,
+    color: arg.color.should_use_color(),
+  };
+  reporter.before_report(&test_cases)?;
+  let snapshots = (!arg.skip_snapshot_tests).then_some(snapshots);
+  let threads = thread_count(arg.threads);
+  let verified = verify_test_cases(&test_cases, &rules, snapshots.as_ref(), threads);
+  let mut results: Vec<_> = verified.into_iter().flatten().collect();
+  // snapshots are only written with -U, never by watching alone
+  let mut writer = snapshots
+    .filter(|_| arg.update_all)
+    .map(|snapshots| SnapshotWriter::new(snapshots, path_map));
+  reporter.report_failed_cases(&mut results, writer.as_mut())?;
+  reporter.report_summaries(&results)?;
+  let (_, message) = reporter.after_report(&results, None)?;
+  writeln!(reporter.output, "{message}")?;
+  Ok(
+    results
+      .iter()
+      .map(|result| (result.id.to_string(), result.passed()))
+      .collect(),
+  )
+}
+
+fn count(status: &BTreeMap<String, bool>) -> (usize, usize) {
+  let passed = status.values().filter(|p| **p).count();
+  (passed, status.len() - passed)
+}
+
+/// Print rules whose result changed since the last run.
+fn print_delta(previous: &BTreeMap<String, bool>, current: &BTreeMap<String, bool>, color: bool) {
+  let label = |passed: bool| {
+    let (style, text) = if passed {
+      (Color::Green.normal(), "PASS")
+    } else {
+      (Color::Red.normal(), "FAIL")
+    };
+    if color {
+      style.paint(text)
+    } else {
+      Style::new().paint(text)
+    }
+  };
+  for (id, passed) in current {
+    match previous.get(id) {
+      Some(before) if before != passed => {
+        println!("{id}: {} -> {}", label(*before), label(*passed))
+      }
+      _ => (),
+    }
+  }
+}
+
+struct WatchedPaths {
+  config: Option<PathBuf>,
+  util_dirs: Vec<PathBuf>,
+  /// All files and directories to watch, including the above.
+  roots: Vec<PathBuf>,
+  /// Snapshot directories are not watched, `-U` writes them in every run.
+  snapshot_dirs: Vec<PathBuf>,
+}
+
+impl WatchedPaths {
+  fn new(arg: &TestArg) -> Result<Self> {
+    let mut roots = vec![];
+    let mut util_dirs = vec![];
+    let mut snapshot_dirs = vec![];
+    let cwd = std::env::current_dir()?;
+    if let Some(dir) = &arg.snapshot_dir {
+      snapshot_dirs.push(cwd.join(dir));
+    }
+    let config = find_config_path_with_default(arg.config.clone(), None).ok();
+    if let Some(path) = &config {
+      let config_str = read_to_string(path).context(EC::ReadConfiguration)?;
+      let sg_config: AstGrepConfig = from_str(&config_str).context(EC::ParseConfiguration)?;
+      let base_dir = path
+        .parent()
+        .expect("config file must have parent directory");
+      roots.push(path.clone());
+      roots.extend(sg_config.rule_dirs.iter().map(|dir| base_dir.join(dir)));
+      util_dirs.extend(
+        sg_config
+          .util_dirs
+          .iter()
+          .flatten()
+          .map(|dir| base_dir.join(dir)),
+      );
+      roots.extend(util_dirs.iter().cloned());
+      // inline tests use the snapshot directory next to the config without test configs
+      snapshot_dirs.push(base_dir.join(SNAPSHOT_DIR));
+      if arg.test_dir.is_none() {
+        let tests = sg_config.test_configs.unwrap_or_default();
+        for test in &tests {
+          let test_dir = base_dir.join(&test.test_dir);
+          snapshot_dirs.push(match &test.snapshot_dir {
+            Some(dir) => base_dir.join(dir),
+            None => test_dir.join(SNAPSHOT_DIR),
+          });
+          roots.push(test_dir);
+        }
+      }
+    }
+    if let Some(test_dir) = &arg.test_dir {
+      let test_dir = cwd.join(test_dir);
+      snapshot_dirs.push(test_dir.join(SNAPSHOT_DIR));
+      roots.push(test_dir);
+    }
+    Ok(Self {
+      config,
+      util_dirs,
+      roots,
+      snapshot_dirs,
+    })
+  }
+
+  fn is_snapshot(&self, path: &Path) -> bool {
+    self.snapshot_dirs.iter().any(|dir| path.starts_with(dir))
+  }
+
+  fn mtimes(&self) -> Mtimes {
+    let mut ret = Mtimes::new();
+    for root in &self.roots {
+      let walker = WalkBuilder::new(root).types(config_file_type()).build();
+      for entry in walker.flatten() {
+        let path = entry.path();
+        if self.is_snapshot(path) {
+          continue;
+        }
+        if let Ok(modified) = path.metadata().and_then(|m| m.modified()) {
+          if path.is_file() {
+            ret.insert(path.to_path_buf(), modified);
+          }
+        }
+      }
+    }
+    ret
+  }
+
+  /// Block until some files change. Returns None if stopped by Ctrl-C.
+  fn wait_for_changes(&self, stopped: &AtomicBool) -> Option<Vec<PathBuf>> {
+    let before = self.mtimes();
+    let mut latest = loop {
+      if stopped.load(Ordering::SeqCst) {
+        return None;
+      }
+      sleep(POLL_INTERVAL);
+      let now = self.mtimes();
+      if now != before {
+        break now;
+      }
+    };
+    loop {
+      sleep(DEBOUNCE);
+      if stopped.load(Ordering::SeqCst) {
+        return None;
+      }
+      let now = self.mtimes();
+      if now == latest {
+        return Some(changed_paths(&before, &latest));
+      }
+      latest = now;
+    }
+  }
+
+  /// Ids of rules whose tests are affected by the changed files.
+  /// None means all tests are affected, e.g. when the config, a util or a deleted file changes.
+  fn affected_rules(&self, changed: &[PathBuf]) -> Option<BTreeSet<String>> {
+    let mut ids = BTreeSet::new();
+    for path in changed {
+      let is_shared = Some(path) == self.config.as_ref()
+        || self.util_dirs.iter().any(|dir| path.starts_with(dir));
+      if is_shared {
+        return None;
+      }
+      ids.extend(ids_in_file(path)?);
+    }
+    Some(ids)
+  }
+}
+
+fn changed_paths(before: &Mtimes, after: &Mtimes) -> Vec<PathBuf> {
+  let removed = before.keys().filter(|path| !after.contains_key(*path));
+  let modified = after
+    .iter()
+    .filter(|(path, time)| before.get(*path) != Some(time))
+    .map(|(path, _)| path);
+  removed.chain(modified).cloned().collect()
+}
+
+/// Both rule files and test files have an `id` in every YAML document.
+fn ids_in_file(path: &Path) -> Option<Vec<String>> {
+  let yaml = read_to_string(path).ok()?;
+  Deserializer::from_str(&yaml)
+    .map(|doc| {
+      let value = Value::deserialize(doc).ok()?;
+      Some(value.get("id")?.as_str()?.to_string())
+    })
+    .collect()
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  #[test]
+  fn test_changed_paths() {
+    let time = SystemTime::UNIX_EPOCH;
+    let later = time + Duration::from_secs(1);
+    let before: Mtimes = [("a", time), ("b", time), ("c", time)]
+      .into_iter()
+      .map(|(p, t)| (PathBuf::from(p), t))
+      .collect();
+    let after: Mtimes = [("a", time), ("b", later), ("d", time)]
+      .into_iter()
+      .map(|(p, t)| (PathBuf::from(p), t))
+      .collect();
+    let changed = changed_paths(&before, &after);
+    let expected: Vec<PathBuf> = vec!["c".into(), "b".into(), "d".into()];
+    assert_eq!(changed, expected);
+  }
+
+  #[test]
+  fn test_affected_rules() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let rule = dir.path().join("rules/a.yml");
+    let util = dir.path().join("utils/u.yml");
+    std::fs::create_dir_all(rule.parent().expect("has parent"))?;
+    std::fs::write(
+      &rule,
+      "id: a\nrule: {pattern: a}\n---\nid: b\nrule: {pattern: b}",
+    )?;
+    let watched = WatchedPaths {
+      config: None,
+      util_dirs: vec![dir.path().join("utils")],
+      roots: vec![],
+      snapshot_dirs: vec![],
+    };
+    let ids = watched.affected_rules(std::slice::from_ref(&rule));
+    let expected: BTreeSet<_> = ["a".to_string(), "b".to_string()].into();
+    assert_eq!(ids, Some(expected));
+    assert_eq!(watched.affected_rules(&[rule, util]), None);
+    let deleted = dir.path().join("rules/deleted.yml");
+    assert_eq!(watched.affected_rules(&[deleted]), None);
+    Ok(())
+  }
+
+  #[test]
+  fn test_ignore_snapshots() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let test = dir.path().join("tests/a-test.yml");
+    let snapshot = dir.path().join("tests/__snapshots__/a-snapshot.yml");
+    std::fs::create_dir_all(snapshot.parent().expect("has parent"))?;
+    std::fs::write(&test, "id: a\nvalid: [a]")?;
+    std::fs::write(&snapshot, "id: a\nsnapshots: {}")?;
+    let watched = WatchedPaths {
+      config: None,
+      util_dirs: vec![],
+      roots: vec![dir.path().join("tests")],
+      snapshot_dirs: vec![dir.path().join("tests").join(SNAPSHOT_DIR)],
+    };
+    let mtimes = watched.mtimes();
+    assert!(mtimes.contains_key(&test));
+    assert!(!mtimes.contains_key(&snapshot));
+    Ok(())
+  }
+}
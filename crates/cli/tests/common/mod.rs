@@ -8,7 +8,7 @@ pub fn create_test_files<'a>(
 ) -> Result<TempDir> {
   let dir = TempDir::new()?;
   for (name, contents) in names_and_contents {
-    if let Some((sub, _)) = name.split_once('/') {
+    if let Some((sub, _)) = name.rsplit_once('/') {
       let sub_dir = dir.path().join(sub);
       std::fs::create_dir_all(sub_dir)?;
     }
@@ -0,0 +1,287 @@
+mod common;
+
+use anyhow::Result;
+use assert_cmd::Command;
+use common::create_test_files;
+use predicates::str::contains;
+
+const CONFIG: &str = "
+ruleDirs:
+- rules
+";
+
+const NO_AWAIT: &str = "
+id: no-await-in-loop
+message: Avoid await in a loop.
+note: |
+  Awaiting in a loop runs promises sequentially.
+  Use `Promise.all` instead.
+severity: warning
+language: TypeScript
+files:
+- src/**/*.ts
+ignores:
+- src/generated/**
+rule:
+  pattern: await $A
+  inside:
+    kind: for_statement
+    stopBy: end
+";
+
+const NO_CONSOLE: &str = "
+id: no-console
+message: Remove console.log.
+severity: error
+language: JavaScript
+rule:
+  pattern: console.log($A)
+fix: ''
+";
+
+const BROKEN: &str = "
+id: broken-rule
+language: TypeScript
+rule:
+  nthChild: wrong
+";
+
+const EXPECTED: &str = "# Rules
+
+## no-await-in-loop
+
+Avoid await in a loop.
+
+- Severity: warning
+- Language: TypeScript
+- Fix available: no
+- Files: `src/**/*.ts`
+- Ignores: `src/generated/**`
+
+Awaiting in a loop runs promises sequentially.
+Use `Promise.all` instead.
+
+_No examples available._
+
+## no-console
+
+Remove console.log.
+
+- Severity: error
+- Language: JavaScript
+- Fix available: yes
+
+_No examples available._
+";
+
+#[test]
+fn test_docs_markdown() -> Result<()> {
+  let dir = create_test_files([
+    ("sgconfig.yml", CONFIG),
+    // file names are not in id order
+    ("rules/a.yml", NO_CONSOLE),
+    ("rules/b.yml", NO_AWAIT),
+  ])?;
+  Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .arg("docs")
+    .assert()
+    .success()
+    .stdout(EXPECTED);
+  Ok(())
+}
+
+#[test]
+fn test_docs_report_failures() -> Result<()> {
+  let dir = create_test_files([
+    ("sgconfig.yml", CONFIG),
+    ("rules/a.yml", NO_CONSOLE),
+    ("rules/b.yml", NO_AWAIT),
+    ("rules/broken.yml", BROKEN),
+    ("rules/not-yaml.yml", "id: [unclosed"),
+  ])?;
+  Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .arg("docs")
+    .assert()
+    .failure()
+    .stdout(EXPECTED)
+    .stderr(contains("Failed to load 2 rule(s):"))
+    .stderr(contains("broken.yml"))
+    .stderr(contains("not-yaml.yml"));
+  Ok(())
+}
+
+#[test]
+fn test_docs_json() -> Result<()> {
+  let dir = create_test_files([
+    ("sgconfig.yml", CONFIG),
+    ("rules/a.yml", NO_CONSOLE),
+    ("rules/b.yml", NO_AWAIT),
+  ])?;
+  let output = Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args(["docs", "--format", "json", "--hint=no-console"])
+    .output()?;
+  assert!(output.status.success());
+  let json: serde_json::Value = serde_json::from_slice(&output.stdout)?;
+  assert_eq!(json["schemaVersion"], 1);
+  let rules = json["rules"].as_array().expect("should be array");
+  assert_eq!(rules.len(), 2);
+  let keys: Vec<_> = rules[0].as_object().unwrap().keys().collect();
+  let expected_keys = [
+    "id",
+    "severity",
+    "language",
+    "message",
+    "note",
+    "metadata",
+    "fixAvailable",
+    "sourcePath",
+    "files",
+    "ignores",
+  ];
+  // field order must be stable
+  let stdout = String::from_utf8(output.stdout)?;
+  let positions: Vec<_> = expected_keys
+    .iter()
+    .map(|key| {
+      stdout
+        .find(&format!("\"{key}\""))
+        .expect("key should exist")
+    })
+    .collect();
+  assert!(positions.windows(2).all(|w| w[0] < w[1]));
+  assert_eq!(keys.len(), expected_keys.len());
+  assert_eq!(rules[0]["id"], "no-await-in-loop");
+  assert_eq!(rules[0]["files"][0], "src/**/*.ts");
+  assert_eq!(rules[0]["fixAvailable"], false);
+  assert!(rules[0]["sourcePath"].as_str().unwrap().ends_with("b.yml"));
+  assert_eq!(rules[1]["id"], "no-console");
+  // severity overwritten by CLI
+  assert_eq!(rules[1]["severity"], "hint");
+  assert_eq!(rules[1]["fixAvailable"], true);
+  assert_eq!(rules[1]["files"], serde_json::Value::Null);
+  assert!(rules[1].get("schemaVersion").is_none());
+  Ok(())
+}
+
+const EXAMPLE_CONFIG: &str = "
+ruleDirs:
+- rules
+testConfigs:
+- testDir: tests
+";
+
+const NO_CONSOLE_TEST: &str = "
+id: no-console
+valid:
+- console.error(a)
+invalid:
+- code: |
+    let a = 1
+    console.log(a)
+  fixed: |
+    let a = 1
+
+- |
+  a()
+  b()
+  c()
+  console.log(d)
+";
+
+const EXAMPLES: &str = "
+Incorrect code:
+
+```javascript
+let a = 1
+console.log(a)
+^^^^^^^^^^^^^^
+```
+
+Incorrect code:
+
+```javascript
+a()
+b()
+…
+```
+
+Correct code:
+
+```javascript
+console.error(a)
+```
+
+Before fix:
+
+```javascript
+let a = 1
+console.log(a)
+```
+
+After fix:
+
+```javascript
+let a = 1
+```
+";
+
+#[test]
+fn test_docs_examples() -> Result<()> {
+  let dir = create_test_files([
+    ("sgconfig.yml", EXAMPLE_CONFIG),
+    ("rules/a.yml", NO_CONSOLE),
+    ("rules/b.yml", NO_AWAIT),
+    ("tests/a-test.yml", NO_CONSOLE_TEST),
+  ])?;
+  Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args(["docs", "--example-lines", "2"])
+    .assert()
+    .success()
+    .stdout(contains(EXAMPLES))
+    .stdout(contains("_No examples available._").count(1));
+  Ok(())
+}
+
+const UTIL: &str = "
+id: is-literal
+language: JavaScript
+rule:
+  kind: number
+";
+
+#[test]
+fn test_docs_schema() -> Result<()> {
+  let dir = create_test_files([
+    ("sgconfig.yml", "ruleDirs: [rules]\nutilDirs: [utils]"),
+    ("rules/a.yml", NO_CONSOLE),
+    ("utils/a.yml", UTIL),
+  ])?;
+  let output = Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args(["docs", "--schema"])
+    .output()?;
+  assert!(output.status.success());
+  let schema: serde_json::Value = serde_json::from_slice(&output.stdout)?;
+  let languages = schema["definitions"]["Language"]["enum"]
+    .as_array()
+    .expect("should list languages");
+  assert!(languages.contains(&"TypeScript".into()));
+  assert!(languages.contains(&"ts".into()));
+  let matches = &schema["definitions"]["SerializableRule"]["properties"]["matches"];
+  assert_eq!(matches["anyOf"][0]["enum"][0], "is-literal");
+  assert_eq!(matches["anyOf"][1]["type"], "string");
+  // output is stable and can be written to a file
+  Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args(["docs", "--schema", "--output-file", "rule.json"])
+    .assert()
+    .success()
+    .stdout("");
+  let written = std::fs::read(dir.path().join("rule.json"))?;
+  assert_eq!(written, output.stdout);
+  Ok(())
+}
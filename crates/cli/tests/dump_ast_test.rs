@@ -0,0 +1,65 @@
+mod common;
+
+use anyhow::Result;
+use assert_cmd::Command;
+use common::create_test_files;
+use predicates::str::contains;
+
+const SOURCE: &str = "let a = 123
+function foo() {
+  return a
+}
+";
+
+#[test]
+fn test_dump_ast_file() -> Result<()> {
+  let dir = create_test_files([("test.ts", SOURCE)])?;
+  Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args(["dump-ast", "test.ts", "--lines", "3"])
+    .assert()
+    .success()
+    .stdout("return_statement (2,2)-(2,10)\n  identifier (2,9)-(2,10)\n");
+  Ok(())
+}
+
+#[test]
+fn test_dump_ast_stdin() -> Result<()> {
+  Command::cargo_bin("sg")?
+    .args(["dump-ast", "--stdin", "-l", "ts", "--kind-only"])
+    .write_stdin("let a = 123")
+    .assert()
+    .success()
+    .stdout("identifier\nlexical_declaration\nnumber\nprogram\nvariable_declarator\n");
+  Ok(())
+}
+
+#[test]
+fn test_dump_ast_json() -> Result<()> {
+  let dir = create_test_files([("test.ts", SOURCE)])?;
+  let output = Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args(["dump-ast", "test.ts", "--format", "json", "--bytes", "4"])
+    .output()?;
+  assert!(output.status.success());
+  let json: serde_json::Value = serde_json::from_slice(&output.stdout)?;
+  assert_eq!(json["kind"], "identifier");
+  assert_eq!(json["field"], "name");
+  assert_eq!(json["start"]["line"], 0);
+  assert_eq!(json["start"]["column"], 4);
+  assert_eq!(json["range"]["start"], 4);
+  assert_eq!(json["range"]["end"], 5);
+  Ok(())
+}
+
+#[test]
+fn test_dump_ast_invalid_range() -> Result<()> {
+  let dir = create_test_files([("test.ts", SOURCE)])?;
+  Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args(["dump-ast", "test.ts", "--lines", "10"])
+    .assert()
+    .failure()
+    .stderr(contains("No node found in lines 10:10."));
+  Ok(())
+}
@@ -0,0 +1,119 @@
+mod common;
+
+use anyhow::Result;
+use assert_cmd::Command;
+use common::create_test_files;
+use predicates::str::contains;
+
+const RULE: &str = "
+id: no-await-in-loop
+language: TypeScript
+message: no await
+utils:
+  in-loop:
+    any:
+    - kind: for_in_statement
+    - kind: while_statement
+rule:
+  pattern: await $A
+  inside:
+    matches: in-loop
+    stopBy: end
+  not:
+    has:
+      kind: identifier
+      regex: ^ok$
+constraints:
+  A:
+    regex: ^fetch
+";
+
+const TARGET: &str = "for (const a of b) {
+  await fetch(a)
+  await other(a)
+}
+await fetch(c)
+";
+
+const EXPECTED: &str = r#"Inspect rule `no-await-in-loop` on test.ts
+
+2:3-2:17 `await fetch(a)` MATCHED
+  ✔ pattern: await $A
+  ✔ inside: {"matches":"in-loop","stopBy":"end"}
+  ✔ not
+    ✘ has: {"kind":"identifier","regex":"^ok$","stopBy":"neighbor"}
+  ✔ constraints
+
+3:3-3:17 `await other(a)` REJECTED
+  ✔ pattern: await $A
+  ✔ inside: {"matches":"in-loop","stopBy":"end"}
+  ✔ not
+    ✘ has: {"kind":"identifier","regex":"^ok$","stopBy":"neighbor"}
+  ✘ constraints
+
+5:1-5:15 `await fetch(c)` REJECTED
+  ✔ pattern: await $A
+  ✘ inside: {"matches":"in-loop","stopBy":"end"}
+  ✔ not
+    ✘ has: {"kind":"identifier","regex":"^ok$","stopBy":"neighbor"}
+
+Matched 1 node(s):
+  2:3-2:17 `await fetch(a)`
+"#;
+
+#[test]
+fn test_inspect_rule_file() -> Result<()> {
+  let dir = create_test_files([("rule.yml", RULE), ("test.ts", TARGET)])?;
+  Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args(["inspect", "--rule", "rule.yml", "--target", "test.ts"])
+    .args(["--color", "never"])
+    .assert()
+    .success()
+    .stdout(EXPECTED);
+  Ok(())
+}
+
+#[test]
+fn test_inspect_rule_id_json() -> Result<()> {
+  let dir = create_test_files([
+    ("sgconfig.yml", "ruleDirs: [rules]"),
+    ("rules/rule.yml", RULE),
+    ("test.ts", TARGET),
+  ])?;
+  let output = Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args([
+      "inspect",
+      "-r",
+      "no-await-in-loop",
+      "-t",
+      "test.ts",
+      "--json",
+    ])
+    .output()?;
+  assert!(output.status.success());
+  let json: serde_json::Value = serde_json::from_slice(&output.stdout)?;
+  assert_eq!(json["ruleId"], "no-await-in-loop");
+  let candidates = json["candidates"].as_array().expect("should be array");
+  assert_eq!(candidates.len(), 3);
+  assert_eq!(candidates[0]["matched"], true);
+  assert_eq!(candidates[0]["node"]["start"]["line"], 1);
+  assert_eq!(candidates[0]["trace"][2]["rule"], "not");
+  assert_eq!(candidates[0]["trace"][2]["subRules"][0]["matched"], false);
+  assert_eq!(json["matches"][0]["text"], "await fetch(a)");
+  Ok(())
+}
+
+#[test]
+fn test_inspect_unknown_rule() -> Result<()> {
+  let dir = create_test_files([("sgconfig.yml", "ruleDirs: [rules]"), ("test.ts", TARGET)])?;
+  std::fs::create_dir(dir.path().join("rules"))?;
+  Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args(["inspect", "-r", "not-exist", "-t", "test.ts"])
+    .assert()
+    .failure()
+    .stderr(contains("Rule not found: not-exist"));
+  Ok(())
+}
@@ -0,0 +1,68 @@
+mod common;
+
+use anyhow::Result;
+use assert_cmd::cargo::CommandCargoExt;
+use assert_cmd::Command;
+use common::create_test_files;
+use predicates::str::contains;
+
+use std::io::{Read, Write};
+use std::net::TcpListener;
+use std::time::{Duration, Instant};
+
+const CONFIG: &str = "ruleDirs: [rules]";
+
+#[test]
+fn test_lsp_port_in_use() -> Result<()> {
+  let dir = create_test_files([("sgconfig.yml", CONFIG), ("rules/.keep", "")])?;
+  let occupied = TcpListener::bind("127.0.0.1:0")?;
+  let port = occupied.local_addr()?.port().to_string();
+  Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args(["lsp", "--port", &port])
+    .assert()
+    .failure()
+    .code(126)
+    .stderr(contains("is already in use"));
+  Ok(())
+}
+
+#[test]
+fn test_lsp_port_and_socket_conflict() -> Result<()> {
+  Command::cargo_bin("sg")?
+    .args(["lsp", "--port", "9257", "--socket", "sg.sock"])
+    .assert()
+    .failure()
+    .stderr(contains("cannot be used with"));
+  Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn test_lsp_unix_socket() -> Result<()> {
+  use std::os::unix::net::UnixStream;
+  let dir = create_test_files([("sgconfig.yml", CONFIG), ("rules/.keep", "")])?;
+  let socket = dir.path().join("sg.sock");
+  let mut server = std::process::Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .arg("lsp")
+    .arg("--socket")
+    .arg(&socket)
+    .stderr(std::process::Stdio::null())
+    .spawn()?;
+  let start = Instant::now();
+  while !socket.exists() && start.elapsed() < Duration::from_secs(10) {
+    std::thread::sleep(Duration::from_millis(50));
+  }
+  let mut stream = UnixStream::connect(&socket)?;
+  stream.set_read_timeout(Some(Duration::from_secs(10)))?;
+  let request = r#"{"jsonrpc":"2.0","id":1,"method":"initialize","params":{"capabilities":{}}}"#;
+  write!(stream, "Content-Length: {}\r\n\r\n{request}", request.len())?;
+  let mut response = vec![0; 4096];
+  let len = stream.read(&mut response)?;
+  server.kill()?;
+  server.wait()?;
+  let response = String::from_utf8_lossy(&response[..len]);
+  assert!(response.starts_with("Content-Length"));
+  Ok(())
+}
@@ -5,6 +5,9 @@ use assert_cmd::Command;
 use common::create_test_files;
 use predicates::prelude::*;
 use predicates::str::contains;
+use serde_json::{json, Value};
+
+use std::path::Path;
 
 #[test]
 fn test_simple_infer_lang() -> Result<()> {
@@ -47,3 +50,838 @@ fn test_js_in_html() -> Result<()> {
     .stdout(contains("alert(456)"));
   Ok(())
 }
+
+#[test]
+fn test_ts_in_vue() -> Result<()> {
+  let dir = create_test_files([(
+    "App.vue",
+    "<template><div/></template>\n<script lang=\"ts\">\nalert(1)\n</script>",
+  )])?;
+  Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args(["-p", "alert($A)", "-l", "ts", "-r", "confirm($A)", "-U"])
+    .assert()
+    .success();
+  let fixed = std::fs::read_to_string(dir.path().join("App.vue"))?;
+  assert!(fixed.contains("<script lang=\"ts\">\nconfirm(1)\n</script>"));
+  Ok(())
+}
+
+#[test]
+fn test_rewrite_crlf() -> Result<()> {
+  let source = "function a() {\r\n  foo(1, {\r\n    x: 1,\r\n  })\r\n}\r\n";
+  let dir = create_test_files([("a.ts", source)])?;
+  Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args([
+      "run",
+      "-p",
+      "foo($A, $B)",
+      "-r",
+      "bar($A)\nbaz($B)",
+      "--update-all",
+    ])
+    .assert()
+    .success();
+  let fixed = std::fs::read_to_string(dir.path().join("a.ts"))?;
+  assert_eq!(
+    fixed,
+    "function a() {\r\n  bar(1)\r\n  baz({\r\n    x: 1,\r\n  })\r\n}\r\n"
+  );
+  Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args(["run", "-p", "baz($A)", "--json=compact"])
+    .assert()
+    .success()
+    .stdout(contains(r#""start":{"line":2,"column":2}"#))
+    .stdout(contains(r#""end":{"line":4,"column":4}"#));
+  Ok(())
+}
+
+#[test]
+fn test_json_include_rewritten_stdin() -> Result<()> {
+  Command::cargo_bin("sg")?
+    .args([
+      "run", "-p", "foo($A)", "-r", "bar($A)", "-l", "ts", "--stdin",
+    ])
+    .args(["--json=compact", "--json-include-rewritten"])
+    .write_stdin("foo(1); foo(2)")
+    .assert()
+    .success()
+    .stdout(contains(r#""rewrittenSource":"bar(1); bar(2)""#).count(1));
+  Ok(())
+}
+
+#[test]
+fn test_max_file_size() -> Result<()> {
+  let large = format!("console.log(123)\n{}", "// padding\n".repeat(200));
+  let dir = create_test_files([("a.ts", large.as_str()), ("b.ts", "console.log(456)")])?;
+  Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args(["-p", "console.log($A)", "--max-file-size", "1K"])
+    .args(["--tracing", "file"])
+    .assert()
+    .success()
+    .stdout(contains("console.log(123)").not())
+    .stdout(contains("console.log(456)"))
+    .stderr(contains(
+      "Skipped a.ts: file size 2217 bytes exceeds max file size",
+    ))
+    .stderr(contains("is skipped because").not());
+  Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args(["-p", "console.log($A)", "--max-file-size", "1K", "a.ts"])
+    .assert()
+    .success()
+    .stdout(contains("console.log(123)").not())
+    .stderr(contains("a.ts is skipped because its size 2217 bytes"));
+  Ok(())
+}
+
+// a real-ish minified bundle: one long line without newline
+const MINIFIED_JS: &str = r#"!function(e,t){"object"==typeof exports&&"undefined"!=typeof module?module.exports=t():"function"==typeof define&&define.amd?define(t):(e=e||self).lib=t()}(this,function(){"use strict";function e(e){return console.log(e),e}var t={log:e,version:"1.0.0"};return t});"#;
+
+#[test]
+fn test_skip_minified() -> Result<()> {
+  let minified = MINIFIED_JS.repeat(10);
+  // generated but legit file with long lines
+  let generated = format!("console.log({})\n", "1 + ".repeat(200) + "1").repeat(5);
+  let dir = create_test_files([
+    ("bundle.min.js", minified.as_str()),
+    ("generated.js", generated.as_str()),
+    ("app.js", "console.log(456)"),
+  ])?;
+  Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args([
+      "-p",
+      "console.log($A)",
+      "--skip-minified",
+      "--tracing",
+      "file",
+    ])
+    .assert()
+    .success()
+    .stdout(contains("console.log(456)"))
+    .stdout(contains("console.log(e)").not())
+    .stdout(contains("1 + 1").not())
+    .stderr(contains("Skipped bundle.min.js: file looks minified"))
+    .stderr(contains("Skipped generated.js: file looks minified"));
+  // explicit path is never skipped
+  Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args(["-p", "console.log($A)", "--skip-minified", "generated.js"])
+    .assert()
+    .success()
+    .stdout(contains("1 + 1"));
+  // threshold is tunable
+  Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args(["-p", "console.log($A)", "--skip-minified"])
+    .args(["--minified-line-threshold", "2000"])
+    .assert()
+    .success()
+    .stdout(contains("1 + 1"))
+    .stdout(contains("console.log(e)").not());
+  Ok(())
+}
+
+#[test]
+fn test_force_exclude() -> Result<()> {
+  let dir = create_test_files([
+    (".ignore", "vendor/\n"),
+    ("vendor/a.ts", "console.log(123)"),
+    ("b.ts", "console.log(456)"),
+    ("c.ts", "console.log(789)"),
+  ])?;
+  // explicit paths are searched by default
+  Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args(["-p", "console.log($A)", "vendor/a.ts", "b.ts"])
+    .args(["--globs", "!b.ts"])
+    .assert()
+    .success()
+    .stdout(contains("console.log(123)"))
+    .stdout(contains("console.log(456)"));
+  Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args(["-p", "console.log($A)", "vendor/a.ts", "b.ts", "c.ts"])
+    .args(["--globs", "!b.ts", "--force-exclude", "--tracing", "file"])
+    .assert()
+    .success()
+    .stdout(contains("console.log(123)").not())
+    .stdout(contains("console.log(456)").not())
+    .stdout(contains("console.log(789)"))
+    .stderr(contains(
+      "Skipped vendor/a.ts: path is excluded by globs or ignore files",
+    ))
+    .stderr(contains(
+      "Skipped b.ts: path is excluded by globs or ignore files",
+    ));
+  Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args([
+      "-p",
+      "console.log($A)",
+      "-l",
+      "ts",
+      "vendor",
+      "--force-exclude",
+    ])
+    .assert()
+    .success()
+    .stdout(contains("console.log(123)").not());
+  Ok(())
+}
+
+#[test]
+fn test_bare_no_ignore() -> Result<()> {
+  let dir = create_test_files([
+    // .git makes the directory a repository, so that .gitignore is respected
+    (".git/HEAD", "ref: refs/heads/main\n"),
+    (".gitignore", "dist/\n"),
+    ("dist/a.ts", "console.log(123)"),
+    (".hidden.ts", "console.log(456)"),
+    ("b.ts", "console.log(789)"),
+  ])?;
+  Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args(["-p", "console.log($A)"])
+    .assert()
+    .success()
+    .stdout(contains("console.log(123)").not())
+    .stdout(contains("console.log(456)").not())
+    .stdout(contains("console.log(789)"));
+  Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args(["-p", "console.log($A)", "--no-ignore"])
+    .assert()
+    .success()
+    .stdout(contains("console.log(123)"))
+    .stdout(contains("console.log(456)"))
+    .stdout(contains("console.log(789)"));
+  // mixed with a value, bare --no-ignore still means all
+  Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args(["-p", "console.log($A)", "--no-ignore=dot", "--no-ignore"])
+    .assert()
+    .success()
+    .stdout(contains("console.log(123)"))
+    .stdout(contains("console.log(456)"));
+  Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args(["-p", "console.log($A)", "--no-ignore=hidden"])
+    .assert()
+    .success()
+    .stdout(contains("console.log(123)").not())
+    .stdout(contains("console.log(456)"));
+  // a path after a bare --no-ignore is not taken as its value
+  Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args(["-p", "console.log($A)", "--no-ignore", "dist"])
+    .assert()
+    .success()
+    .stdout(contains("console.log(123)"))
+    .stdout(contains("console.log(456)").not())
+    .stdout(contains("console.log(789)").not());
+  Ok(())
+}
+
+#[test]
+fn test_file_type() -> Result<()> {
+  let dir = create_test_files([
+    ("a.ts", "console.log(123)"),
+    ("b.js", "console.log(456)"),
+    ("c.tsx", "console.log(789)"),
+  ])?;
+  Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args(["-p", "console.log($A)", "--type", "js"])
+    .assert()
+    .success()
+    .stdout(contains("console.log(123)").not())
+    .stdout(contains("console.log(456)"));
+  Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args(["-p", "console.log($A)", "-t", "ts", "--globs", "!c.tsx"])
+    .assert()
+    .success()
+    .stdout(contains("console.log(123)"))
+    .stdout(contains("console.log(789)").not());
+  // --globs take precedence over types
+  Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args(["-p", "console.log($A)", "-t", "js", "--globs", "a.ts"])
+    .assert()
+    .success()
+    .stdout(contains("console.log(123)"))
+    .stdout(contains("console.log(456)").not());
+  // types narrow the files of the language
+  Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args(["-p", "console.log($A)", "-l", "ts", "-T", "ts"])
+    .assert()
+    .success()
+    .stdout(contains("console.log(123)").not());
+  Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args(["-p", "console.log($A)", "-t", "typscript"])
+    .assert()
+    .failure()
+    .stderr(contains("Did you mean `typescript`?"));
+  Command::cargo_bin("sg")?
+    .args(["-p", "console.log($A)", "--type-list"])
+    .assert()
+    .success()
+    .stdout(contains("rust: *.rs"));
+  // the pattern is not required to list types
+  Command::cargo_bin("sg")?
+    .args(["run", "--type-list"])
+    .assert()
+    .success()
+    .stdout(contains("rust: *.rs"));
+  Ok(())
+}
+
+fn utf16_le(text: &str) -> Vec<u8> {
+  let mut bytes = vec![0xFF, 0xFE];
+  bytes.extend(text.encode_utf16().flat_map(u16::to_le_bytes));
+  bytes
+}
+
+#[test]
+fn test_utf16_file() -> Result<()> {
+  let dir = create_test_files([("b.ts", "\u{FEFF}Some(2)")])?;
+  let path = dir.path().join("a.ts");
+  std::fs::write(&path, utf16_le("let 中 = Some(1)"))?;
+  std::fs::write(dir.path().join("c.ts"), [0xFF, 0xFE, 0x61])?;
+  Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args(["-p", "Some($A)", "--json=compact", "--tracing", "file"])
+    .assert()
+    .success()
+    .stdout(contains(r#""start":{"line":0,"column":8}"#))
+    .stdout(contains(r#""file":"b.ts","lines":"Some(2)""#))
+    .stderr(contains("Skipped c.ts: file cannot be decoded as UTF-16LE"));
+  Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args(["-p", "Some($A)", "-r", "$A", "-U"])
+    .assert()
+    .success();
+  assert_eq!(std::fs::read(&path)?, utf16_le("let 中 = 1"));
+  let bom = std::fs::read_to_string(dir.path().join("b.ts"))?;
+  assert_eq!(bom, "\u{FEFF}2");
+  Ok(())
+}
+
+#[test]
+fn test_one_file_system() -> Result<()> {
+  let dir = create_test_files([
+    ("a.ts", "console.log(123)"),
+    ("sub/b.ts", "console.log(456)"),
+  ])?;
+  Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args(["-p", "console.log($A)", "--one-file-system"])
+    .assert()
+    .success()
+    .stdout(contains("console.log(123)"))
+    .stdout(contains("console.log(456)"));
+  Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args([
+      "-p",
+      "console.log($A)",
+      "-l",
+      "ts",
+      "--one-file-system",
+      "sub",
+    ])
+    .assert()
+    .success()
+    .stdout(contains("console.log(123)").not())
+    .stdout(contains("console.log(456)"));
+  Ok(())
+}
+
+#[test]
+fn test_overlapping_paths() -> Result<()> {
+  let dir = create_test_files([("src/module/a.ts", "console.log(123)")])?;
+  let output = Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args(["-p", "console.log($A)", "src", "src/module", "./src"])
+    .assert()
+    .success()
+    .stderr(contains("src/module is skipped because it is inside src."))
+    .stderr(contains("./src is skipped because it is the same as src."))
+    .get_output()
+    .stdout
+    .clone();
+  let output = String::from_utf8(output)?;
+  assert_eq!(output.matches("console.log(123)").count(), 1);
+  Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args([
+      "-p",
+      "console.log($A)",
+      "-r",
+      "log($A)",
+      "-U",
+      "src/module",
+      "src",
+    ])
+    .assert()
+    .success();
+  let fixed = std::fs::read_to_string(dir.path().join("src/module/a.ts"))?;
+  assert_eq!(fixed, "log(123)");
+  Ok(())
+}
+
+#[test]
+fn test_path_style() -> Result<()> {
+  let dir = create_test_files([("src/a.ts", "console.log(123)"), ("lib/b.ts", "")])?;
+  let root = dir.path().canonicalize()?;
+  let absolute = root.join("src").join("a.ts");
+  let absolute = serde_json::to_string(&absolute.to_string_lossy())?;
+  Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args([
+      "-p",
+      "console.log($A)",
+      "--json",
+      "--path-style",
+      "absolute",
+    ])
+    .assert()
+    .success()
+    .stdout(contains(format!(r#""file": {absolute}"#)));
+  let relative = Path::new("..").join("src").join("a.ts");
+  Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args(["-p", "console.log($A)", "--path-style", "from:lib"])
+    .assert()
+    .success()
+    .stdout(contains(format!(
+      "{}:1:console.log(123)",
+      relative.display()
+    )));
+  Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args(["-p", "console.log($A)", "--path-style", "from:missing"])
+    .assert()
+    .failure()
+    .stderr(contains("cannot find directory `missing`"));
+  // files are still written to the walked paths
+  Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args(["-p", "console.log($A)", "-r", "log($A)", "-U"])
+    .args(["--path-style", "from:lib"])
+    .assert()
+    .success();
+  let fixed = std::fs::read_to_string(dir.path().join("src/a.ts"))?;
+  assert_eq!(fixed, "log(123)");
+  Ok(())
+}
+
+#[test]
+#[cfg(unix)]
+fn test_no_messages() -> Result<()> {
+  let dir = create_test_files([("a.ts", "console.log(123)")])?;
+  std::os::unix::fs::symlink("missing.ts", dir.path().join("b.ts"))?;
+  let sg = |args: &[&str]| -> Result<Command> {
+    let mut cmd = Command::cargo_bin("sg")?;
+    cmd
+      .current_dir(dir.path())
+      .args(["-p", "console.log($A)", "--follow"])
+      .args(args);
+    Ok(cmd)
+  };
+  sg(&[])?
+    .assert()
+    .success()
+    .stderr(contains("b.ts: No such file or directory"));
+  sg(&["--no-messages"])?
+    .assert()
+    .success()
+    .stdout(contains("console.log(123)"))
+    .stderr(contains("No such file").not())
+    .stderr(contains(
+      "1 files could not be read; rerun without --no-messages for details",
+    ));
+  // errors are a top level record of the JSON output, with or without --no-messages
+  for args in [&["--json"][..], &["--json", "--no-messages"]] {
+    let output = sg(args)?.output()?;
+    assert!(output.status.success());
+    let json: Vec<Value> = serde_json::from_slice(&output.stdout)?;
+    assert_eq!(json.len(), 2);
+    assert_eq!(json[0]["text"], "console.log(123)");
+    let errors = json[1]["errors"].as_array().expect("should have errors");
+    assert_eq!(errors.len(), 1);
+    assert!(errors[0].as_str().unwrap().contains("b.ts: No such file"));
+  }
+  let output = sg(&["--json=stream", "--no-messages"])?.output()?;
+  let last = output
+    .stdout
+    .split(|b| *b == b'\n')
+    .rfind(|l| !l.is_empty());
+  let last: Value = serde_json::from_slice(last.expect("should print lines"))?;
+  assert!(last["errors"][0].as_str().unwrap().contains("b.ts"));
+  sg(&["--no-messages", "--strict-io"])?
+    .assert()
+    .code(5)
+    .stderr(contains("1 file(s) could not be read."));
+  Ok(())
+}
+
+#[test]
+fn test_stdin_batch() -> Result<()> {
+  let records = [
+    r#"{"path": "a.ts", "source": "console.log(123)"}"#,
+    r#"{"path": "b.js", "source": "let a = 1"}"#,
+    "not a record",
+    r#"{"path": "c.unknown", "source": "console.log(456)"}"#,
+  ];
+  let output = Command::cargo_bin("sg")?
+    .args(["run", "-p", "console.log($A)", "--stdin-batch"])
+    .write_stdin(records.join("\n"))
+    .output()?;
+  assert!(output.status.success());
+  let docs: Vec<Value> = output
+    .stdout
+    .split(|b| *b == b'\n')
+    .filter(|l| !l.is_empty())
+    .map(serde_json::from_slice)
+    .collect::<Result<_, _>>()?;
+  assert_eq!(docs.len(), 4);
+  assert_eq!(docs[0]["file"], "a.ts");
+  let error = &docs[1]["error"];
+  assert_eq!(error["line"], 3);
+  assert!(error.get("path").is_none());
+  assert!(error["error"]
+    .as_str()
+    .unwrap()
+    .starts_with("invalid record"));
+  assert_eq!(docs[2]["error"]["line"], 4);
+  assert_eq!(docs[2]["error"]["path"], "c.unknown");
+  let summary = json!({"summary": {"records": 4, "matched": 1, "errors": 2}});
+  assert_eq!(docs[3], summary);
+  // --lang applies to every record
+  Command::cargo_bin("sg")?
+    .args(["run", "-p", "console.log($A)", "-l", "ts", "--stdin-batch"])
+    .write_stdin(records[3])
+    .assert()
+    .success()
+    .stdout(contains(r#""file":"c.unknown""#));
+  Command::cargo_bin("sg")?
+    .args(["run", "-p", "a", "--stdin-batch", "--stdin"])
+    .assert()
+    .failure()
+    .stderr(contains("cannot be used with"));
+  Ok(())
+}
+
+#[test]
+fn test_lang_glob() -> Result<()> {
+  let dir = create_test_files([
+    (
+      "sgconfig.yml",
+      "ruleDirs: []\nlanguageGlobs:\n  js: ['*.tpl']\n  ts: ['*.tpl']",
+    ),
+    ("a.tpl", "foo(1)"),
+    ("Jenkinsfile", "foo(2)"),
+  ])?;
+  Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args(["run", "-p", "foo($A)", "--json=stream", "--tracing=summary"])
+    .assert()
+    .success()
+    .stdout(contains(r#""language":"JavaScript""#))
+    .stdout(contains("Jenkinsfile").not())
+    .stderr(contains(
+      "a.tpl matches languageGlobs of both JavaScript and TypeScript. JavaScript is used",
+    ))
+    .stderr(contains(r#""langGlobFiles":{"JavaScript":1}"#));
+  // --lang-glob takes precedence over languageGlobs
+  Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args(["run", "-p", "foo($A)", "--json=stream"])
+    .args(["--lang-glob", "ts:*.tpl", "--lang-glob", "ts:Jenkinsfile"])
+    .assert()
+    .success()
+    .stdout(contains(r#""language":"TypeScript""#))
+    .stdout(contains(r#""file":"Jenkinsfile""#))
+    .stdout(contains("JavaScript").not());
+  Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args(["run", "-p", "foo($A)", "--lang-glob", "nope:*.tpl"])
+    .assert()
+    .failure()
+    .stderr(contains("nope"));
+  Ok(())
+}
+
+#[test]
+fn test_shebang_detection() -> Result<()> {
+  let dir = create_test_files([
+    ("deploy", "#!/usr/bin/env node\nfoo(1)"),
+    ("notes.txt", "#!/usr/bin/env node\nfoo(2)"),
+    ("blob", "#!/usr/bin/env node\0foo(3)"),
+  ])?;
+  Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args(["run", "-p", "foo($A)", "--json=stream", "--tracing=file"])
+    .assert()
+    .success()
+    .stdout(contains(r#""file":"deploy""#))
+    .stdout(contains(r#""language":"JavaScript""#))
+    .stdout(contains("notes.txt").not())
+    .stdout(contains("blob").not())
+    .stderr(contains("Detected deploy as JavaScript by shebang"))
+    .stderr(contains(r#""shebangFiles":{"JavaScript":1}"#));
+  Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args(["run", "-p", "foo($A)", "--no-shebang-detection"])
+    .assert()
+    .success()
+    .stdout("");
+  Ok(())
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn test_custom_language() -> Result<()> {
+  let lib = Path::new("../../benches/fixtures/json-linux.so").canonicalize()?;
+  let config = format!(
+    "ruleDirs: [rules]\ncustomLanguages:\n  myjson:\n    libraryPath: {}\n    languageSymbol: tree_sitter_json\n    extensions: [myjson]",
+    lib.display()
+  );
+  let rule = "id: no-123\nlanguage: myjson\nrule: {pattern: '123'}\nseverity: warning";
+  let dir = create_test_files([
+    ("sgconfig.yml", config.as_str()),
+    ("rules/no-123.yml", rule),
+    ("a.myjson", r#"{"a": 123}"#),
+  ])?;
+  Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args(["run", "-p", "123", "-l", "myjson"])
+    .assert()
+    .success()
+    .stdout(contains("a.myjson"));
+  Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args(["scan"])
+    .assert()
+    .success()
+    .stdout(contains("no-123"));
+  Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args(["scan", "--type-list"])
+    .assert()
+    .success()
+    .stdout(contains("myjson: *.myjson (custom language)"));
+  let bad_config = config.replace("tree_sitter_json", "tree_sitter_nope");
+  std::fs::write(dir.path().join("sgconfig.yml"), bad_config)?;
+  Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args(["scan"])
+    .assert()
+    .code(5)
+    .stderr(contains("Cannot load custom languages."))
+    .stderr(contains("cannot read symbol `tree_sitter_nope` from"));
+  Ok(())
+}
+
+#[test]
+fn test_bom_byte_offset() -> Result<()> {
+  let dir = create_test_files([("a.ts", "\u{FEFF}let a = 1; foo(1)")])?;
+  let output = Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args(["-p", "foo($A)", "-r", "bar($A)", "--json=compact"])
+    .output()?;
+  assert!(output.status.success());
+  let json: serde_json::Value = serde_json::from_slice(&output.stdout)?;
+  let bytes = std::fs::read(dir.path().join("a.ts"))?;
+  let slice = |range: &serde_json::Value| {
+    let start = range["start"].as_u64().expect("should be offset") as usize;
+    let end = range["end"].as_u64().expect("should be offset") as usize;
+    String::from_utf8(bytes[start..end].to_vec()).expect("should be utf8")
+  };
+  let found = &json[0];
+  // the offsets count the 3 bytes of BOM on disk
+  assert_eq!(found["range"]["byteOffset"]["start"], 14);
+  assert_eq!(slice(&found["range"]["byteOffset"]), "foo(1)");
+  assert_eq!(slice(&found["replacementOffsets"]), "foo(1)");
+  let meta = &found["metaVariables"]["single"]["A"];
+  assert_eq!(slice(&meta["range"]["byteOffset"]), "1");
+  // the BOM is neither a column nor a line content
+  assert_eq!(found["range"]["start"]["column"], 11);
+  assert_eq!(found["lines"], "let a = 1; foo(1)");
+  Ok(())
+}
+
+#[test]
+fn test_ignore_file() -> Result<()> {
+  let dir = create_test_files([
+    (".sgignore", "generated/\n"),
+    ("generated/a.ts", "console.log(123)"),
+    ("b.ts", "console.log(456)"),
+    ("c.ts", "console.log(789)"),
+    ("custom-ignore", "b.ts\nc.ts\n"),
+    ("invalid-ignore", "# comment\na[\n"),
+  ])?;
+  Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args(["-p", "console.log($A)"])
+    .assert()
+    .success()
+    .stdout(contains("console.log(123)").not())
+    .stdout(contains("console.log(456)"));
+  // --globs overrides the ignore file
+  Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args(["-p", "console.log($A)", "--ignore-file", "custom-ignore"])
+    .args(["--globs", "c.ts"])
+    .assert()
+    .success()
+    .stdout(contains("console.log(123)").not())
+    .stdout(contains("console.log(456)").not())
+    .stdout(contains("console.log(789)"));
+  Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args(["-p", "console.log($A)", "--no-ignore=dot"])
+    .assert()
+    .success()
+    .stdout(contains("console.log(123)"));
+  Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args(["-p", "console.log($A)", "--ignore-file", "invalid-ignore"])
+    .assert()
+    .failure()
+    .stderr(contains("Cannot read ignore file invalid-ignore"))
+    .stderr(contains("line 2"));
+  Ok(())
+}
+
+#[test]
+fn test_ignore_file_relative_to_search_path() -> Result<()> {
+  let dir = create_test_files([
+    ("conf/ignore", "/a.ts\n"),
+    ("a.ts", "console.log(123)"),
+    ("conf/a.ts", "console.log(456)"),
+  ])?;
+  Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args(["-p", "console.log($A)", "--ignore-file", "conf/ignore"])
+    .assert()
+    .success()
+    .stdout(contains("console.log(123)").not())
+    .stdout(contains("console.log(456)"));
+  Ok(())
+}
+
+#[test]
+fn test_files_from() -> Result<()> {
+  let dir = create_test_files([
+    (".ignore", "vendor/\n"),
+    ("vendor/a.ts", "console.log(123)"),
+    ("b.ts", "console.log(456)"),
+    ("c.ts", "console.log(789)"),
+    ("list.txt", "vendor/a.ts\nb.ts\nmissing.ts\n"),
+  ])?;
+  // listed files are searched like explicit paths, but not the positional default
+  Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args(["-p", "console.log($A)", "--files-from", "list.txt"])
+    .assert()
+    .success()
+    .stdout(contains("console.log(123)"))
+    .stdout(contains("console.log(456)"))
+    .stdout(contains("console.log(789)").not())
+    .stderr(contains("missing.ts is skipped because it does not exist"));
+  // globs still apply to listed files
+  Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args(["-p", "console.log($A)", "-l", "ts", "--files-from", "-"])
+    .args(["--globs", "!b.ts"])
+    .write_stdin("b.ts\nc.ts\n")
+    .assert()
+    .success()
+    .stdout(contains("console.log(456)").not())
+    .stdout(contains("console.log(789)"));
+  Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args(["-p", "console.log($A)", "--files-from", "list.txt", "b.ts"])
+    .assert()
+    .failure();
+  Ok(())
+}
+
+#[test]
+fn test_dedupe_nested_match() -> Result<()> {
+  let dir = create_test_files([("a.ts", "foo(foo(1))")])?;
+  Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args([
+      "-p", "foo($A)", "-r", "bar($A)", "--dedupe", "contains", "-U",
+    ])
+    .assert()
+    .success();
+  let fixed = std::fs::read_to_string(dir.path().join("a.ts"))?;
+  assert_eq!(fixed, "bar(foo(1))");
+  Ok(())
+}
+
+#[test]
+fn test_field_and_context_separator() -> Result<()> {
+  let dir = create_test_files([("a.ts", "foo(1)\na\nb\nc\nfoo(2)\n")])?;
+  Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args(["-p", "foo($A)", "--heading", "never", "-C", "1"])
+    .args(["--field-separator", "\\t", "--context-separator", "=="])
+    .assert()
+    .success()
+    .stdout("a.ts\t1\tfoo(1)\na.ts\t2\ta\n==\na.ts\t4\tc\na.ts\t5\tfoo(2)\n");
+  Ok(())
+}
+
+#[test]
+fn test_session_file() -> Result<()> {
+  let dir = create_test_files([("a.ts", "Some(1)")])?;
+  let session = dir.path().join("session.jsonl");
+  let session = session.to_str().expect("should be utf8");
+  let rewrite = [
+    "-p",
+    "Some($A)",
+    "-r",
+    "$A",
+    "-U",
+    "a.ts",
+    "--session",
+    session,
+  ];
+  Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args(rewrite)
+    .assert()
+    .success();
+  let records = std::fs::read_to_string(session)?;
+  assert!(records.contains(r#""decision":"applied","ruleId":"rewrite","file":"a.ts","line":1"#));
+  // the applied fix is missing from the file, so it is applied again
+  std::fs::write(dir.path().join("a.ts"), "Some(1)")?;
+  Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args(rewrite)
+    .assert()
+    .success();
+  assert_eq!(std::fs::read_to_string(dir.path().join("a.ts"))?, "1");
+  Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args(["run", "--session-report", session])
+    .assert()
+    .success()
+    .stdout(contains(
+      "Rule `rewrite`: 1 applied, 0 skipped, 0 deferred.",
+    ))
+    .stdout(contains("Total: 1 applied, 0 skipped, 0 deferred."));
+  Ok(())
+}
@@ -6,7 +6,7 @@ use ast_grep::main_with_args;
 use common::create_test_files;
 use predicates::prelude::*;
 use predicates::str::contains;
-use serde_json::{from_slice, Value};
+use serde_json::{from_slice, json, Value};
 use tempfile::TempDir;
 
 const CONFIG: &str = "
@@ -136,3 +136,775 @@ fn test_sg_scan_html() -> Result<()> {
     .stdout(contains("rule-3").not());
   Ok(())
 }
+
+const VUE_SFC: &str = "<template>
+  <div>{{ msg }}</div>
+</template>
+<script setup lang=\"ts\">
+const a = Some(123)
+</script>
+<style>
+.a { color: red; }
+</style>
+";
+
+#[test]
+fn test_sg_scan_vue() -> Result<()> {
+  let dir = create_test_files([("rule.yml", RULE1), ("App.vue", VUE_SFC)])?;
+  Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args(["scan", "-r", "rule.yml"])
+    .assert()
+    .success()
+    .stdout(contains("App.vue:5:11"))
+    .stdout(contains("on-rule"));
+  Ok(())
+}
+
+#[test]
+fn test_sg_scan_vue_fix() -> Result<()> {
+  let rule = "{id: fix-some, language: ts, rule: {pattern: Some($A)}, fix: 'None'}";
+  let dir = create_test_files([("App.vue", VUE_SFC)])?;
+  Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args(["scan", "--inline-rules", rule, "-U"])
+    .assert()
+    .success();
+  let fixed = std::fs::read_to_string(dir.path().join("App.vue"))?;
+  assert_eq!(fixed, VUE_SFC.replace("Some(123)", "None"));
+  Ok(())
+}
+
+#[test]
+fn test_sg_scan_diff() -> Result<()> {
+  let rules = "
+id: to-const
+language: TypeScript
+rule: { pattern: let $A = $B }
+fix: const $A = $B
+---
+id: unwrap
+language: TypeScript
+rule: { pattern: let a = $B }
+fix: a = $B
+";
+  let source = "let a = 1\nlet b = 2\n";
+  let dir = create_test_files([("rule.yml", rules), ("a.ts", source)])?;
+  Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args(["scan", "-r", "rule.yml", "--diff"])
+    .assert()
+    .success()
+    .stdout(contains("--- a/a.ts\n+++ b/a.ts\n"))
+    .stdout(contains(
+      "-let a = 1\n-let b = 2\n+const a = 1\n+const b = 2\n",
+    ))
+    .stderr(contains("Skipped fix of rule `unwrap` in a.ts"));
+  // files are not modified
+  assert_eq!(std::fs::read_to_string(dir.path().join("a.ts"))?, source);
+  Ok(())
+}
+
+#[test]
+fn test_sg_scan_diff_injected() -> Result<()> {
+  let rules = "
+id: fix-some
+language: ts
+severity: error
+rule: { pattern: Some($A) }
+fix: None
+---
+id: fix-red
+language: css
+rule: { kind: plain_value, regex: red }
+fix: blue
+";
+  let dir = create_test_files([("rule.yml", rules), ("App.vue", VUE_SFC)])?;
+  let output = Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args(["scan", "-r", "rule.yml", "--diff"])
+    .output()?;
+  // error findings fail the scan even if they are fixable
+  assert_eq!(output.status.code(), Some(1));
+  let diff = String::from_utf8(output.stdout)?;
+  // fixes of both languages are in one diff of the file
+  assert_eq!(diff.matches("diff --git a/App.vue").count(), 1, "{diff}");
+  assert!(diff.contains("+const a = None\n"), "{diff}");
+  assert!(diff.contains("+.a { color: blue; }\n"), "{diff}");
+  Ok(())
+}
+
+#[test]
+fn test_sg_scan_group_by_rule() -> Result<()> {
+  let dir = create_test_files([
+    ("rule.yml", SEVERITY_RULES),
+    ("a.ts", "Some(1) + None"),
+    ("b.ts", "Some(2)"),
+  ])?;
+  Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args(["scan", "-r", "rule.yml", "--group-by", "rule"])
+    .assert()
+    .failure()
+    .stdout(contains("error[error-rule]: (2 findings)"))
+    .stdout(contains("help[hint-rule]: (1 finding)"));
+  Ok(())
+}
+
+#[test]
+fn test_sg_scan_summary() -> Result<()> {
+  let dir = create_test_files([
+    ("rule.yml", SEVERITY_RULES),
+    ("a.ts", "Some(1) + None"),
+    ("b.ts", "Some(2)"),
+    ("c.ts", "let a = 1"),
+  ])?;
+  Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args(["scan", "-r", "rule.yml", "--json"])
+    .assert()
+    .failure()
+    .stderr(contains("Scanned 4 files in"))
+    .stderr(contains("3 findings in 2 files."))
+    .stderr(contains(
+      "Findings: 2 errors, 0 warnings, 0 info, 1 hint. Rules fired: 2. Fixes available: 0.",
+    ));
+  Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args(["scan", "-r", "rule.yml", "--no-summary"])
+    .assert()
+    .failure()
+    .stderr(contains("Scanned").not());
+  Ok(())
+}
+
+#[test]
+fn test_sg_scan_summary_update_all() -> Result<()> {
+  let rule = "{id: fix-some, language: ts, rule: {pattern: Some($A)}, fix: 'None'}";
+  let dir = create_test_files([("a.ts", "Some(1) + Some(2)"), ("b.ts", "Some(3)")])?;
+  Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args(["scan", "--inline-rules", rule, "-U"])
+    .assert()
+    .success()
+    .stderr(contains("Fixes available: 3."))
+    .stderr(contains("Applied 3 edits to 2 files."));
+  Ok(())
+}
+
+#[test]
+fn test_sg_scan_no_applicable_rule() -> Result<()> {
+  let dir = create_test_files([("rule.yml", SEVERITY_RULES), ("a.py", "print(1)")])?;
+  Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args(["scan", "-r", "rule.yml"])
+    .assert()
+    .success()
+    .stderr(contains(
+      "No rule applies to the scanned files. Rule languages: TypeScript. File languages: Python, Yaml.",
+    ));
+  Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args(["scan", "-r", "rule.yml", "--strict-rules"])
+    .assert()
+    .code(2)
+    .stderr(contains("No rule applies to the scanned files."));
+  // rules excluded by severity
+  std::fs::write(dir.path().join("a.ts"), "let a = 1")?;
+  Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args(["scan", "-r", "rule.yml", "--only-severity", "warning"])
+    .assert()
+    .success()
+    .stderr(contains(
+      "Rule languages: no language. File languages: Python, TypeScript, Yaml.",
+    ));
+  // rules apply to languages embedded in html
+  let dir = create_test_files([("rule.yml", SEVERITY_RULES), ("a.html", "<p></p>")])?;
+  Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args(["scan", "-r", "rule.yml", "--strict-rules"])
+    .assert()
+    .success()
+    .stderr(contains("No rule applies").not());
+  Ok(())
+}
+
+#[test]
+fn test_sg_scan_prefilter() -> Result<()> {
+  let dir = create_test_files([
+    ("rule.yml", MULTI_RULES),
+    ("test.ts", "Some(123)"),
+    ("other.ts", "let a = 123"),
+  ])?;
+  Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args(["scan", "-r", "rule.yml", "--tracing", "summary"])
+    .assert()
+    .success()
+    .stdout(contains("rule-1"))
+    .stderr(contains("Prefilter rule-1: hit 1, miss 1"))
+    .stderr(contains("Prefilter rule-2: hit 0, miss 2"));
+  Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args([
+      "scan",
+      "-r",
+      "rule.yml",
+      "--tracing",
+      "summary",
+      "--no-prefilter",
+    ])
+    .assert()
+    .success()
+    .stdout(contains("rule-1"))
+    .stderr(contains("Prefilter").not());
+  Ok(())
+}
+
+const SEVERITY_RULES: &str = "
+id: error-rule
+severity: error
+language: TypeScript
+rule: { pattern: Some($A) }
+---
+id: hint-rule
+severity: hint
+language: TypeScript
+rule: { pattern: None }
+";
+
+#[test]
+fn test_sg_scan_only_severity() -> Result<()> {
+  let dir = create_test_files([
+    ("rule.yml", SEVERITY_RULES),
+    ("test.ts", "Some(123) + None"),
+  ])?;
+  Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args(["scan", "-r", "rule.yml", "--only-severity", "hint"])
+    .args(["--tracing", "summary"])
+    .assert()
+    .success()
+    .stdout(contains("hint-rule"))
+    .stdout(contains("error-rule").not())
+    .stderr(contains("Excluded by severity: 1"));
+  // severity overwrite applies before the filter
+  Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args(["scan", "-r", "rule.yml", "--warning=hint-rule"])
+    .args(["--only-severity", "warning"])
+    .assert()
+    .success()
+    .stdout(contains("hint-rule"))
+    .stdout(contains("error-rule").not());
+  Ok(())
+}
+
+#[test]
+fn test_sg_scan_exit_code_for() -> Result<()> {
+  let dir = create_test_files([
+    ("rule.yml", SEVERITY_RULES),
+    ("test.ts", "Some(123) + None"),
+  ])?;
+  Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args(["scan", "-r", "rule.yml", "--exit-code-for", "error=7"])
+    .args(["--exit-code-for", "hint=3"])
+    .assert()
+    .code(7)
+    .stderr(contains("exit code 7 is determined by error findings"));
+  Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args(["scan", "-r", "rule.yml", "--only-severity", "hint"])
+    .args(["--exit-code-for", "hint=3"])
+    .assert()
+    .code(3)
+    .stderr(contains("1 hint(s) found in code."));
+  // unspecified severities keep the default exit code
+  Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args(["scan", "-r", "rule.yml", "--exit-code-for", "warning=3"])
+    .assert()
+    .code(1);
+  Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args(["scan", "-r", "rule.yml", "--exit-code-for", "error=0"])
+    .assert()
+    .failure()
+    .stderr(contains("exit code 0 is reserved"));
+  Ok(())
+}
+
+#[test]
+fn test_sg_scan_only_severity_with_filter() -> Result<()> {
+  let dir = setup()?;
+  // on-rule is a warning, so filtering errors excludes it
+  Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args(["scan", "--filter", "on-rule", "--only-severity", "error"])
+    .assert()
+    .success()
+    .stdout(contains("on-rule").not());
+  Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args(["scan", "--filter", "on-rule", "--only-severity", "warning"])
+    .assert()
+    .success()
+    .stdout(contains("on-rule"));
+  Ok(())
+}
+
+const IGNORE_TEST_RULE: &str = "
+id: ignore-test
+language: TypeScript
+ignores: ['**/*.test.ts']
+rule: { pattern: Some($A) }
+fix: $A
+";
+
+#[test]
+fn test_sg_scan_stdin_filepath() -> Result<()> {
+  let dir = setup()?;
+  std::fs::write(dir.path().join("rules/ignore-test.yml"), IGNORE_TEST_RULE)?;
+  Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args(["scan", "--stdin", "--stdin-filepath", "src/a.ts"])
+    .write_stdin("let a = Some(1)")
+    .assert()
+    .success()
+    .stdout(contains("on-rule"))
+    .stdout(contains("ignore-test"))
+    .stdout(contains("src/a.ts"));
+  // ignores glob applies to the virtual path
+  Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args([
+      "scan",
+      "--stdin",
+      "--stdin-filepath",
+      "src/a.test.ts",
+      "--json",
+    ])
+    .write_stdin("let a = Some(1)")
+    .assert()
+    .success()
+    .stdout(contains("\"ruleId\": \"on-rule\""))
+    .stdout(contains("ignore-test").not())
+    .stdout(contains("\"file\": \"src/a.test.ts\""));
+  // language is inferred from the virtual path
+  Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args(["scan", "--stdin", "--stdin-filepath", "a.py"])
+    .write_stdin("Some(1)")
+    .assert()
+    .success()
+    .stdout(contains("on-rule").not());
+  Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args(["scan", "--stdin", "--stdin-filepath", "a.unknown"])
+    .write_stdin("Some(1)")
+    .assert()
+    .failure()
+    .stderr(contains("Cannot infer language from `a.unknown`."));
+  Ok(())
+}
+
+const BASH_RULE: &str = "
+id: no-echo
+message: do not echo
+severity: error
+language: Bash
+rule:
+  pattern: echo $A
+";
+
+#[test]
+fn test_sg_scan_shebang() -> Result<()> {
+  let dir = create_test_files([
+    ("sgconfig.yml", CONFIG),
+    ("rules/no-echo.yml", BASH_RULE),
+    ("install", "#!/bin/bash\necho hi"),
+  ])?;
+  Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args(["scan"])
+    .assert()
+    .code(1)
+    .stdout(contains("install"))
+    .stdout(contains("do not echo"));
+  Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args(["scan", "--no-shebang-detection"])
+    .assert()
+    .success()
+    .stdout(contains("install").not());
+  Ok(())
+}
+
+#[test]
+fn test_sg_scan_stdin_batch() -> Result<()> {
+  let dir = setup()?;
+  std::fs::write(dir.path().join("rules/ignore-test.yml"), IGNORE_TEST_RULE)?;
+  let records = [
+    r#"{"path": "src/a.ts", "source": "Some(1)"}"#,
+    r#"{"path": "src/a.test.ts", "source": "Some(2)"}"#,
+    r#"{"path": "src/a.ts""#,
+  ];
+  let output = Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args(["scan", "--stdin-batch"])
+    .write_stdin(records.join("\n"))
+    .output()?;
+  assert!(output.status.success());
+  let docs: Vec<Value> = output
+    .stdout
+    .split(|b| *b == b'\n')
+    .filter(|l| !l.is_empty())
+    .map(from_slice)
+    .collect::<Result<_, _>>()?;
+  assert_eq!(docs.len(), 5);
+  // ignores glob applies to the path of every record
+  let ignore_test: Vec<_> = docs
+    .iter()
+    .filter(|d| d["ruleId"] == "ignore-test")
+    .collect();
+  assert_eq!(ignore_test.len(), 1);
+  assert_eq!(ignore_test[0]["file"], "src/a.ts");
+  assert_eq!(docs[3]["error"]["line"], 3);
+  assert!(docs[3]["error"]["error"]
+    .as_str()
+    .unwrap()
+    .starts_with("invalid record"));
+  let summary = json!({"summary": {"records": 3, "matched": 2, "errors": 1}});
+  assert_eq!(docs[4], summary);
+  Ok(())
+}
+
+#[test]
+fn test_sg_scan_stdin_print_fixed() -> Result<()> {
+  let dir = setup()?;
+  std::fs::write(dir.path().join("rules/ignore-test.yml"), IGNORE_TEST_RULE)?;
+  Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args([
+      "scan",
+      "--stdin",
+      "--stdin-filepath",
+      "a.ts",
+      "--print-fixed",
+    ])
+    .write_stdin("let a = Some(1)\nlet b = 2\n")
+    .assert()
+    .success()
+    .stdout("let a = 1\nlet b = 2\n");
+  // code is printed unchanged if no fix applies
+  Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args([
+      "scan",
+      "--stdin",
+      "--stdin-filepath",
+      "a.test.ts",
+      "--print-fixed",
+    ])
+    .write_stdin("let a = Some(1)\n")
+    .assert()
+    .success()
+    .stdout("let a = Some(1)\n");
+  Ok(())
+}
+
+#[test]
+fn test_sg_scan_stdin_reject_update() -> Result<()> {
+  let dir = setup()?;
+  for flag in ["--interactive", "--update-all"] {
+    Command::cargo_bin("sg")?
+      .current_dir(dir.path())
+      .args(["scan", "--stdin", "--stdin-filepath", "a.ts", flag])
+      .write_stdin("Some(1)")
+      .assert()
+      .failure()
+      .stderr(contains("Interactive mode is incompatible"));
+  }
+  Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args(["scan", "--stdin-filepath", "a.ts"])
+    .assert()
+    .failure();
+  Ok(())
+}
+
+#[test]
+fn test_sg_scan_dedupe() -> Result<()> {
+  let nested = "{id: nested, language: ts, rule: {pattern: 'foo($A)'}}";
+  let count = |dedupe: &str, rule: &str, src: &str| -> Result<usize> {
+    let output = Command::cargo_bin("sg")?
+      .args(["scan", "--stdin", "--json", "--dedupe", dedupe])
+      .args(["--inline-rules", rule])
+      .write_stdin(src.to_string())
+      .output()?;
+    let json: Value = from_slice(&output.stdout)?;
+    Ok(json.as_array().expect("should be array").len())
+  };
+  assert_eq!(count("none", nested, "foo(foo(1))")?, 2);
+  assert_eq!(count("exact", nested, "foo(foo(1))")?, 2);
+  assert_eq!(count("contains", nested, "foo(foo(1))")?, 1);
+  // different rules are not deduplicated against each other
+  let two_rules =
+    format!("{nested}\n---\n{{id: other, language: ts, rule: {{pattern: 'foo(1)'}}}}");
+  assert_eq!(count("contains", &two_rules, "foo(foo(1))")?, 2);
+  let same_range = "{id: same, language: ts, rule: {any: [{kind: call_expression}, {kind: expression_statement}]}}";
+  assert_eq!(count("none", same_range, "foo(1)")?, 2);
+  assert_eq!(count("exact", same_range, "foo(1)")?, 1);
+  Ok(())
+}
+
+#[test]
+fn test_sg_scan_files_from0() -> Result<()> {
+  let dir = setup()?;
+  std::fs::write(dir.path().join("other.ts"), "Some(456)")?;
+  Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args(["scan", "--files-from0", "-"])
+    .write_stdin("other.ts\0missing.ts\0")
+    .assert()
+    .success()
+    .stdout(contains("Some(456)"))
+    .stdout(contains("Some(123)").not())
+    .stderr(contains("missing.ts is skipped because it does not exist"));
+  // an empty list searches nothing
+  Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args(["scan", "--files-from0", "-"])
+    .write_stdin("")
+    .assert()
+    .success()
+    .stdout(contains("Some(").not());
+  Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args(["scan", "--files-from0", "-", "--stdin"])
+    .assert()
+    .failure();
+  Ok(())
+}
+
+const FIX_RULES: &str = "
+id: unwrap-some
+language: TypeScript
+rule: { pattern: Some($A) }
+fix: $A
+---
+id: none-to-null
+language: TypeScript
+rule: { pattern: None }
+fix: 'null'
+";
+
+#[test]
+fn test_sg_scan_update_all_by_rule() -> Result<()> {
+  let dir = create_test_files([
+    ("rule.yml", FIX_RULES),
+    ("a.ts", "let a = Some(1) + None\nlet b = Some(Some(2))"),
+    ("b.ts", "None"),
+  ])?;
+  Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args(["scan", "-r", "rule.yml", "-U", "--group-by", "rule"])
+    .assert()
+    .success()
+    .stderr(contains("Rule `none-to-null`: 2 applied, 0 skipped."))
+    .stderr(contains("Rule `unwrap-some`: 3 applied, 0 skipped."))
+    .stderr(contains("Applied 4 edits to 2 files."))
+    .stderr(contains("Skipped fix of rule `unwrap-some` in a.ts"));
+  // edits of both rules are written to the same file
+  let a = std::fs::read_to_string(dir.path().join("a.ts"))?;
+  assert_eq!(a, "let a = 1 + null\nlet b = Some(2)");
+  let b = std::fs::read_to_string(dir.path().join("b.ts"))?;
+  assert_eq!(b, "null");
+  Ok(())
+}
+
+#[test]
+fn test_sg_scan_error_color() -> Result<()> {
+  let dir = TempDir::new()?;
+  // errors go to stderr, they follow --color even if it is not a terminal
+  Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args(["scan", "--color", "always"])
+    .env_remove("NO_COLOR")
+    .assert()
+    .failure()
+    .stderr(contains("\u{1b}[31mError:"));
+  Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args(["scan", "--color", "never"])
+    .assert()
+    .failure()
+    .stderr(contains("Error: Cannot read configuration."))
+    .stderr(contains("\u{1b}[").not());
+  Ok(())
+}
+
+#[test]
+fn test_sg_scan_field_separator() -> Result<()> {
+  let dir = setup()?;
+  Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args(["scan", "--field-separator", "\\t"])
+    .assert()
+    .success()
+    .stdout(contains("┌─ test.ts\t1\t1\n"));
+  for style in ["short", "medium"] {
+    Command::cargo_bin("sg")?
+      .current_dir(dir.path())
+      .args(["scan", "--field-separator", "\\t", "--report-style", style])
+      .assert()
+      .failure()
+      .stderr(contains("`--field-separator` cannot be used"));
+  }
+  Ok(())
+}
+
+const INVALID_RULES: &str = "id: bad-util
+language: TypeScript
+rule:
+  matches: missing-util
+---
+id: bad-pattern
+language: TypeScript
+files:
+  - src/[a
+rule:
+  pattern: let a = ;
+fix: foo($B)
+---
+id: bad-severity
+language: TypeScript
+severity: fatal
+rule: { pattern: a }
+";
+
+#[test]
+fn test_sg_scan_check_rules() -> Result<()> {
+  let dir = setup()?;
+  Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args(["scan", "--check-rules"])
+    .assert()
+    .success()
+    .stdout(contains("2 rule(s) checked, no problem found."));
+  let dir = create_test_files([
+    ("sgconfig.yml", CONFIG),
+    ("rules/invalid.yml", INVALID_RULES),
+    ("test.ts", "Some(123)"),
+  ])?;
+  Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args(["scan", "--check-rules"])
+    .assert()
+    .code(8)
+    .stdout(contains("invalid.yml:4:12: rule `bad-util`"))
+    .stdout(contains("Rule `missing-util` is not defined"))
+    .stdout(contains(
+      "invalid.yml:9:5: rule `bad-pattern`: error parsing glob",
+    ))
+    .stdout(contains(
+      "invalid.yml:11:12: rule `bad-pattern`: pattern `let a = ;`",
+    ))
+    .stdout(contains(
+      "invalid.yml:12:10: rule `bad-pattern`: Undefined meta var `B` used in `fix`",
+    ))
+    .stdout(contains("Fail to parse yaml as Rule: Undefined").not())
+    .stdout(contains(
+      "invalid.yml:16:11: severity: unknown variant `fatal`",
+    ))
+    .stdout(contains("Some(123)").not())
+    .stderr(contains("5 problem(s) found in rules."));
+  Ok(())
+}
+
+#[test]
+fn test_sg_scan_duplicate_rule_id() -> Result<()> {
+  let dir = create_test_files([
+    ("sgconfig.yml", "ruleDirs: [rules1, rules2]"),
+    ("rules1/first.yml", RULE1),
+    ("rules2/second.yml", &RULE1.replace("Some($A)", "Some(123)")),
+    ("test.ts", "Some(1); Some(123)"),
+  ])?;
+  Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args(["scan"])
+    .assert()
+    .code(8)
+    .stderr(contains("Duplicate rule id `on-rule` found."))
+    .stderr(contains("first.yml:2"))
+    .stderr(contains("second.yml:2"));
+  // the rule in the later rule directory wins
+  let output = Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args(["scan", "--json", "--allow-duplicate-rules", "last-wins"])
+    .output()?;
+  assert!(output.status.success());
+  let json: Value = from_slice(&output.stdout)?;
+  let matches = json.as_array().expect("should be array");
+  assert_eq!(matches.len(), 1);
+  assert_eq!(matches[0]["text"], "Some(123)");
+  // inline rules are checked too
+  let inline_rules =
+    "{id: dup, language: ts, rule: {pattern: a}}\n---\n{id: dup, language: ts, rule: {pattern: b}}";
+  Command::cargo_bin("sg")?
+    .args(["scan", "--stdin", "--inline-rules", inline_rules])
+    .write_stdin("a")
+    .assert()
+    .code(8)
+    .stderr(contains("INLINE_RULES:1"))
+    .stderr(contains("INLINE_RULES:3"));
+  Ok(())
+}
+
+const TYPO_RULE: &str = "id: typo
+language: TypeScript
+severty: error
+rule:
+  pattern: Some($A)
+constraints:
+  A:
+    regex: '1'
+    paterns: '123'
+";
+
+#[test]
+fn test_sg_scan_unknown_keys() -> Result<()> {
+  let dir = create_test_files([
+    ("sgconfig.yml", CONFIG),
+    ("rules/typo.yml", TYPO_RULE),
+    ("test.ts", "Some(123)"),
+  ])?;
+  Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args(["scan"])
+    .assert()
+    .code(8)
+    .stderr(contains(
+      "typo.yml:3:1: unknown key `severty`. Did you mean `severity`?",
+    ))
+    .stderr(contains(
+      "typo.yml:9:5: unknown key `paterns` in `constraints.A`. Did you mean `pattern`?",
+    ));
+  Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args(["scan", "--no-strict-yaml"])
+    .assert()
+    .success()
+    .stdout(contains("typo"))
+    .stderr(contains("unknown key `severty`"));
+  Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args(["scan", "--check-rules"])
+    .assert()
+    .code(8)
+    .stdout(contains("typo.yml:3:1: unknown key `severty`"))
+    .stdout(contains("typo.yml:9:5: unknown key `paterns`"));
+  Ok(())
+}
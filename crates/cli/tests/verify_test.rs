@@ -1,8 +1,11 @@
 mod common;
 
 use anyhow::Result;
+use assert_cmd::Command;
 use ast_grep::main_with_args;
 use common::create_test_files;
+use predicates::prelude::*;
+use predicates::str::contains;
 use tempfile::TempDir;
 
 const CONFIG: &str = "
@@ -98,7 +101,7 @@ fn test_sg_test_filter() -> Result<()> {
     "sg test -c {} --skip-snapshot-tests -f error-rule",
     config.display()
   ));
-  assert!(ret.is_ok());
+  assert!(ret.is_err());
   let ret = sg(&format!(
     "sg test -c {} --skip-snapshot-tests -f test-rule",
     config.display()
@@ -107,3 +110,561 @@ fn test_sg_test_filter() -> Result<()> {
   drop(dir);
   Ok(())
 }
+
+#[test]
+fn test_sg_test_case_filter() -> Result<()> {
+  let dir = create_test_files([
+    ("sgconfig.yml", CONFIG),
+    ("rules/test-rule.yml", RULE),
+    ("rules/other-rule.yml", OTHER_RULE),
+    ("rule-tests/test-rule-test.yml", WRONG_TEST),
+    ("rule-tests/other-rule-test.yml", OTHER_TEST),
+  ])?;
+  // failing cases of test-rule are filtered out, only `Some(1)` of other-rule runs
+  Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args(["test", "--skip-snapshot-tests", "--case", "Some(1)"])
+    .assert()
+    .success()
+    .stdout(contains("1 passed; 0 failed; 1 filtered out;"))
+    .stdout(contains("thread(s)."));
+  Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args([
+      "test",
+      "--skip-snapshot-tests",
+      "-f",
+      "rule",
+      "--case",
+      "404",
+    ])
+    .assert()
+    .failure()
+    .stderr(contains("No test matches the filter. 2 filtered out."));
+  Ok(())
+}
+
+const OTHER_RULE: &str = "
+id: other-rule
+language: TypeScript
+rule:
+  pattern: None
+";
+
+const OTHER_TEST: &str = "
+id: other-rule
+valid: [Some(1)]
+invalid: [None]
+";
+
+// the comment would be lost if the snapshot is serialized again
+const OTHER_SNAPSHOT: &str = "id: other-rule
+snapshots:
+  # keep this comment
+  None:
+    labels:
+    - source: None
+      style: primary
+      start: 0
+      end: 4
+";
+
+#[test]
+fn test_sg_test_update_snapshots() -> Result<()> {
+  let dir = create_test_files([
+    ("sgconfig.yml", CONFIG),
+    ("rules/test-rule.yml", RULE),
+    ("rules/other-rule.yml", OTHER_RULE),
+    ("rule-tests/test-rule-test.yml", TEST),
+    ("rule-tests/other-rule-test.yml", OTHER_TEST),
+    (
+      "rule-tests/__snapshots__/other-rule-snapshot.yml",
+      OTHER_SNAPSHOT,
+    ),
+  ])?;
+  let snapshot_dir = dir.path().join("rule-tests/__snapshots__");
+  Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args(["test", "--update-snapshots"])
+    .assert()
+    .success()
+    .stdout(contains("Snapshots: 1 created, 0 updated, 1 untouched."));
+  let created = std::fs::read_to_string(snapshot_dir.join("test-rule-snapshot.yml"))?;
+  assert!(created.contains("source: Some(123)"));
+  let other = std::fs::read_to_string(snapshot_dir.join("other-rule-snapshot.yml"))?;
+  assert_eq!(other, OTHER_SNAPSHOT);
+  // a new fix changes the snapshot of test-rule only
+  let fixed_rule = format!("{RULE}fix: None");
+  std::fs::write(dir.path().join("rules/test-rule.yml"), fixed_rule)?;
+  Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args(["test", "-U", "-f", "test-rule"])
+    .assert()
+    .success()
+    .stdout(contains("Snapshots: 0 created, 1 updated, 0 untouched."));
+  let updated = std::fs::read_to_string(snapshot_dir.join("test-rule-snapshot.yml"))?;
+  assert!(updated.contains("fixed: None"));
+  let other = std::fs::read_to_string(snapshot_dir.join("other-rule-snapshot.yml"))?;
+  assert_eq!(other, OTHER_SNAPSHOT);
+  Ok(())
+}
+
+#[test]
+fn test_sg_test_interactive_without_tty() -> Result<()> {
+  let dir = create_test_files([
+    ("sgconfig.yml", CONFIG),
+    ("rules/test-rule.yml", RULE),
+    ("rule-tests/test-rule-test.yml", TEST),
+  ])?;
+  Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args(["test", "--interactive"])
+    .assert()
+    .failure()
+    .stderr(contains("requires a terminal"))
+    .stderr(contains("--update-snapshots"));
+  assert!(!dir.path().join("rule-tests/__snapshots__").exists());
+  Ok(())
+}
+
+#[test]
+fn test_sg_test_coverage() -> Result<()> {
+  let dir = create_test_files([
+    ("sgconfig.yml", CONFIG),
+    ("rules/test-rule.yml", RULE),
+    ("rules/other-rule.yml", OTHER_RULE),
+    ("rule-tests/test-rule-test.yml", TEST),
+  ])?;
+  Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args(["test", "--skip-snapshot-tests", "--coverage"])
+    .assert()
+    .success()
+    .stdout(contains("Coverage: 1 rule(s) without tests:"))
+    .stdout(contains("other-rule  rules/other-rule.yml"));
+  Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args([
+      "test",
+      "--skip-snapshot-tests",
+      "--coverage",
+      "--json=compact",
+    ])
+    .assert()
+    .success()
+    .stdout(contains(
+      r#""untested":[{"id":"other-rule","path":"rules/other-rule.yml"}]"#,
+    ));
+  Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args(["test", "--skip-snapshot-tests", "--error-on-uncovered"])
+    .assert()
+    .failure()
+    .stderr(contains("1 rule(s) have no test case."));
+  Ok(())
+}
+
+#[test]
+fn test_sg_test_json() -> Result<()> {
+  let dir = create_test_files([
+    ("sgconfig.yml", CONFIG),
+    ("rules/test-rule.yml", RULE),
+    ("rule-tests/test-rule-test.yml", WRONG_TEST),
+  ])?;
+  let output = Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args(["test", "--json"])
+    .assert()
+    .failure()
+    .get_output()
+    .stdout
+    .clone();
+  let json: serde_json::Value = serde_json::from_slice(&output)?;
+  let rule = &json["rules"][0];
+  assert_eq!(rule["id"], "test-rule");
+  assert_eq!(rule["valid"][0]["code"], "Some(123)");
+  assert_eq!(rule["valid"][0]["passed"], false);
+  assert_eq!(rule["invalid"][0]["code"], "None");
+  assert_eq!(json["summary"]["failed"], 1);
+  assert_eq!(json["summary"]["casesFailed"], 2);
+  assert!(json["summary"]["wallTime"].is_number());
+  Ok(())
+}
+
+#[test]
+fn test_sg_test_junit() -> Result<()> {
+  let dir = create_test_files([
+    ("sgconfig.yml", CONFIG),
+    ("rules/test-rule.yml", RULE),
+    ("rule-tests/test-rule-test.yml", WRONG_TEST),
+  ])?;
+  let output = Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args(["test", "--skip-snapshot-tests", "--reporter", "junit"])
+    .assert()
+    .failure()
+    .get_output()
+    .stdout
+    .clone();
+  let xml = String::from_utf8(output)?;
+  assert!(xml.starts_with("<?xml"));
+  assert!(xml.contains(r#"<testcase name="test-rule::valid[0]::some-123" classname="test-rule">"#));
+  assert!(!xml.contains("Running 1 tests"));
+  Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args([
+      "test",
+      "--skip-snapshot-tests",
+      "--reporter=junit=report.xml",
+    ])
+    .assert()
+    .failure()
+    .stdout(contains("Running 1 tests"));
+  let xml = std::fs::read_to_string(dir.path().join("report.xml"))?;
+  assert!(xml.contains(r#"failures="2""#));
+  Ok(())
+}
+
+const FIX_RULE: &str = "
+id: test-rule
+language: TypeScript
+rule:
+  pattern: Some($A)
+fix: Ok($A)
+";
+
+const FIX_TEST: &str = "
+id: test-rule
+invalid:
+- code: Some(1)
+  fixed: Ok(1)
+- code: Some(2)
+  fixed: Err(2)
+";
+
+#[test]
+fn test_sg_test_fixed() -> Result<()> {
+  let dir = create_test_files([
+    ("sgconfig.yml", CONFIG),
+    ("rules/test-rule.yml", FIX_RULE),
+    ("rule-tests/test-rule-test.yml", FIX_TEST),
+  ])?;
+  Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args(["test", "--update-snapshots"])
+    .assert()
+    .failure()
+    .stdout(contains("fixed code is different from expectation."))
+    .stdout(contains("Snapshots: 1 created"));
+  let test = std::fs::read_to_string(dir.path().join("rule-tests/test-rule-test.yml"))?;
+  assert_eq!(test, FIX_TEST);
+  std::fs::write(dir.path().join("rules/test-rule.yml"), RULE)?;
+  Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args(["test", "--skip-snapshot-tests"])
+    .assert()
+    .failure()
+    .stdout(contains("has no fix, but fixed code is expected for:"));
+  Ok(())
+}
+
+const DIAGNOSTIC_TEST: &str = "
+id: test-rule
+invalid:
+- |
+  Some(1)
+  // ~~~~~~~
+- code: let a = Some(2)
+  expected:
+  - {line: 1, column: 9, endLine: 1, endColumn: 16}
+- |
+  Some(3)
+  // ~~~~
+";
+
+#[test]
+fn test_sg_test_expected_diagnostics() -> Result<()> {
+  let dir = create_test_files([
+    ("sgconfig.yml", CONFIG),
+    ("rules/test-rule.yml", RULE),
+    ("rule-tests/test-rule-test.yml", DIAGNOSTIC_TEST),
+  ])?;
+  Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args(["test", "--skip-snapshot-tests"])
+    .assert()
+    .failure()
+    .stdout(contains(
+      "reported diagnostics are different from expectation.",
+    ))
+    .stdout(contains("   1 | Some(3)\n     | ~~~~\n"))
+    .stdout(contains("   1 | Some(3)\n     | ~~~~~~~ test rule\n"));
+  Ok(())
+}
+
+#[test]
+fn test_sg_test_include_off() -> Result<()> {
+  let dir = create_test_files([
+    ("sgconfig.yml", CONFIG),
+    ("rules/test-rule.yml", &RULE.replace("warning", "off")),
+    ("rule-tests/test-rule-test.yml", TEST),
+  ])?;
+  Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args(["test", "--skip-snapshot-tests"])
+    .assert()
+    .success()
+    .stdout(contains("Skipped 1 rule(s) with severity off: test-rule."))
+    .stdout(contains("Configuration not found!").not());
+  Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args(["test", "--skip-snapshot-tests", "--include-off"])
+    .assert()
+    .success()
+    .stdout(contains("Included 1 rule(s) with severity off."))
+    .stdout(contains("test-rule  .."));
+  Ok(())
+}
+
+const STALE_SNAPSHOT: &str = "id: test-rule
+snapshots:
+  Some(456):
+    labels:
+    - source: Some(456)
+      style: primary
+      start: 0
+      end: 9
+";
+
+#[test]
+fn test_sg_test_prune_snapshots() -> Result<()> {
+  let dir = create_test_files([
+    ("sgconfig.yml", CONFIG),
+    ("rules/test-rule.yml", RULE),
+    ("rule-tests/test-rule-test.yml", TEST),
+    (
+      "rule-tests/__snapshots__/test-rule-snapshot.yml",
+      STALE_SNAPSHOT,
+    ),
+    (
+      "rule-tests/__snapshots__/other-rule-snapshot.yml",
+      OTHER_SNAPSHOT,
+    ),
+  ])?;
+  let snapshot_dir = dir.path().join("rule-tests/__snapshots__");
+  Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args(["test", "--skip-snapshot-tests", "--list-stale-snapshots"])
+    .assert()
+    .success()
+    .stdout(contains("Stale snapshots: 2 snapshot(s) in 2 file(s)."))
+    .stdout(contains("other-rule-snapshot.yml (all)\n    - None"))
+    .stdout(contains("test-rule-snapshot.yml (all)\n    - Some(456)"));
+  assert!(snapshot_dir.join("other-rule-snapshot.yml").exists());
+  // the snapshot accepted in the same run is kept
+  Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args(["test", "-U", "--prune-snapshots"])
+    .assert()
+    .success()
+    .stdout(contains("Removed snapshot file"))
+    .stdout(contains("Removed 1 snapshot(s) from"));
+  assert!(!snapshot_dir.join("other-rule-snapshot.yml").exists());
+  let snapshot = std::fs::read_to_string(snapshot_dir.join("test-rule-snapshot.yml"))?;
+  assert!(snapshot.contains("Some(123)"));
+  assert!(!snapshot.contains("Some(456)"));
+  Ok(())
+}
+
+#[test]
+fn test_sg_test_rule_ids() -> Result<()> {
+  let dir = create_test_files([
+    ("sgconfig.yml", CONFIG),
+    ("rules/test-rule.yml", RULE),
+    ("rules/other-rule.yml", OTHER_RULE),
+    ("rule-tests/test-rule-test.yml", TEST),
+    (
+      "rule-tests/other-rule-test.yml",
+      WRONG_TEST.replace("test-rule", "other-rule").as_str(),
+    ),
+  ])?;
+  let config = dir.path().join("sgconfig.yml");
+  Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args(["test", "test-rule", "--skip-snapshot-tests", "-c"])
+    .arg(&config)
+    .assert()
+    .success()
+    .stdout(contains("Running 1 tests"));
+  Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args(["test", "test-rul"])
+    .assert()
+    .failure()
+    .stderr(contains("No test config found for rule `test-rul`."))
+    .stderr(contains("Did you mean `test-rule`?"));
+  Ok(())
+}
+
+const INLINE_TEST_RULE: &str = "
+id: test-rule
+message: test rule
+severity: warning
+language: TypeScript
+rule:
+  pattern: Some($A)
+tests:
+  valid:
+  - None
+  invalid:
+  - Some(123)
+  - Some(456)
+";
+
+#[test]
+fn test_sg_test_inline_tests() -> Result<()> {
+  let dir = create_test_files([
+    ("sgconfig.yml", CONFIG),
+    ("rules/test-rule.yml", INLINE_TEST_RULE),
+    ("rule-tests/test-rule-test.yml", TEST),
+  ])?;
+  Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args(["test", "-U"])
+    .assert()
+    .success()
+    .stderr(contains("skipped 2 duplicate test case(s) for `test-rule`"))
+    .stdout(contains("test-rule  .UU"))
+    .stdout(contains("Snapshots: 2 created"));
+  let snapshot_dir = dir.path().join("rule-tests/__snapshots__");
+  let created = std::fs::read_to_string(snapshot_dir.join("test-rule-snapshot.yml"))?;
+  assert!(created.contains("source: Some(123)"));
+  assert!(created.contains("source: Some(456)"));
+  // inline tests alone, without testConfigs
+  let dir = create_test_files([
+    ("sgconfig.yml", "ruleDirs: [rules]"),
+    ("rules/test-rule.yml", INLINE_TEST_RULE),
+  ])?;
+  Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args(["test", "-U"])
+    .assert()
+    .success();
+  let snapshot = dir.path().join("__snapshots__/test-rule-snapshot.yml");
+  assert!(std::fs::read_to_string(snapshot)?.contains("source: Some(456)"));
+  Ok(())
+}
+
+#[test]
+fn test_sg_test_snapshot_dir() -> Result<()> {
+  let config = "
+ruleDirs: [rules]
+testConfigs:
+- testDir: rule-tests
+  snapshotDir: snapshots
+";
+  let dir = create_test_files([
+    ("sgconfig.yml", config),
+    ("rules/test-rule.yml", RULE),
+    ("rule-tests/test-rule-test.yml", TEST),
+  ])?;
+  // snapshotDir is relative to the config file
+  Command::cargo_bin("sg")?
+    .current_dir(dir.path().join("rules"))
+    .args(["test", "-U"])
+    .assert()
+    .success()
+    .stdout(contains("Snapshots: 1 created"));
+  let snapshot = dir.path().join("snapshots/test-rule-snapshot.yml");
+  assert!(snapshot.exists());
+  Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args(["test"])
+    .assert()
+    .success();
+  // the flag wins and is relative to cwd
+  Command::cargo_bin("sg")?
+    .current_dir(dir.path().join("rule-tests"))
+    .args(["test", "-U", "--snapshot-dir", "__snapshots__"])
+    .assert()
+    .success()
+    .stdout(contains("Snapshots: 1 created"));
+  let snapshot = dir
+    .path()
+    .join("rule-tests/__snapshots__/test-rule-snapshot.yml");
+  assert!(snapshot.exists());
+  Ok(())
+}
+
+#[test]
+fn test_sg_test_unknown_test_id() -> Result<()> {
+  let dir = create_test_files([
+    ("sgconfig.yml", CONFIG),
+    ("rules/test-rule.yml", RULE),
+    ("rule-tests/test-rule-test.yml", TEST),
+    (
+      "rule-tests/misspelled-test.yml",
+      TEST.replace("test-rule", "test-rlue").as_str(),
+    ),
+  ])?;
+  Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args(["test", "--skip-snapshot-tests"])
+    .assert()
+    .failure()
+    .code(3)
+    .stderr(contains("1 test config(s) refer to unknown rules."))
+    .stderr(contains(
+      "Test `test-rlue` in rule-tests/misspelled-test.yml has no rule.",
+    ))
+    .stderr(contains("Did you mean `test-rule`?"));
+  Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args(["test", "--skip-snapshot-tests", "--allow-unused-tests"])
+    .assert()
+    .success()
+    .stderr(contains("Warning: test `test-rlue`"))
+    .stdout(contains("1 passed"));
+  Ok(())
+}
+
+#[test]
+fn test_sg_test_stable_snapshots() -> Result<()> {
+  let test = "
+id: test-rule
+valid:
+- None
+invalid:
+- Some(123)
+- |
+  let a = 1
+  Some(a)
+- 'Some(true)'
+";
+  let dir = create_test_files([
+    ("sgconfig.yml", CONFIG),
+    ("rules/test-rule.yml", format!("{RULE}fix: None").as_str()),
+    ("rule-tests/test-rule-test.yml", test),
+  ])?;
+  let snapshot = dir
+    .path()
+    .join("rule-tests/__snapshots__/test-rule-snapshot.yml");
+  Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args(["test", "-U"])
+    .assert()
+    .success()
+    .stdout(contains("Snapshots: 3 created"));
+  let first = std::fs::read(&snapshot)?;
+  let first_modified = std::fs::metadata(&snapshot)?.modified()?;
+  assert!(String::from_utf8(first.clone())?.contains("? |\n    let a = 1\n    Some(a)\n"));
+  Command::cargo_bin("sg")?
+    .current_dir(dir.path())
+    .args(["test", "-U"])
+    .assert()
+    .success()
+    .stdout(contains("Snapshots: 0 created, 0 updated, 3 untouched."));
+  assert_eq!(std::fs::read(&snapshot)?, first);
+  assert_eq!(std::fs::metadata(&snapshot)?.modified()?, first_modified);
+  Ok(())
+}
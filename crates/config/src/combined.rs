@@ -1,3 +1,4 @@
+use crate::suppression::{Suppression as SuppressionComment, IGNORE_TEXT};
 use crate::RuleConfig;
 
 use ast_grep_core::language::Language;
@@ -18,6 +19,9 @@ impl Suppressions {
     if !node.kind().contains("comment") || !node.text().contains(IGNORE_TEXT) {
       return;
     }
+    let Some(comment) = SuppressionComment::parse(node.lang(), &node.text()) else {
+      return;
+    };
     let line = node.start_pos().0;
     let suppress_next_line = if let Some(prev) = node.prev() {
       prev.start_pos().0 != line
@@ -28,7 +32,7 @@ impl Suppressions {
     self.0.insert(
       key,
       Suppression {
-        suppressed: parse_suppression_set(&node.text()),
+        suppressed: comment.rule_set(),
         node_id: node.node_id(),
       },
     );
@@ -77,8 +81,6 @@ impl<'a> MaySuppressed<'a> {
   }
 }
 
-const IGNORE_TEXT: &str = "ast-grep-ignore";
-
 pub struct PreScan {
   pub hit_set: BitSet,
   suppressions: Suppressions,
@@ -120,6 +122,23 @@ impl<'r, L: Language> CombinedScan<'r, L> {
     }
   }
 
+  /// Exclude rules for which `excluded` returns true so they will never be executed.
+  /// Excluded rules will not be in the PreScan hit set, so `scan` also skips them.
+  pub fn exclude_rules(&mut self, mut excluded: impl FnMut(&RuleConfig<L>) -> bool) {
+    let excluded: BitSet = self
+      .rules
+      .iter()
+      .enumerate()
+      .filter_map(|(idx, rule)| excluded(rule).then_some(idx))
+      .collect();
+    if excluded.is_empty() {
+      return;
+    }
+    for rule_idx in self.kind_rule_mapping.iter_mut() {
+      rule_idx.retain(|idx| !excluded.contains(*idx));
+    }
+  }
+
   pub fn find<D>(&self, root: &AstGrep<D>) -> PreScan
   where
     D: Doc<Lang = L>,
@@ -212,22 +231,11 @@ impl<'r, L: Language> CombinedScan<'r, L> {
     result
   }
 
-  pub fn get_rule(&self, idx: usize) -> &RuleConfig<L> {
+  pub fn get_rule(&self, idx: usize) -> &'r RuleConfig<L> {
     self.rules[idx]
   }
 }
 
-fn parse_suppression_set(text: &str) -> Option<HashSet<String>> {
-  let (_, after) = text.trim().split_once(IGNORE_TEXT)?;
-  let after = after.trim();
-  if after.is_empty() {
-    return None;
-  }
-  let (_, rules) = after.split_once(':')?;
-  let set = rules.split(',').map(|r| r.trim().to_string()).collect();
-  Some(set)
-}
-
 #[cfg(test)]
 mod test {
   use super::*;
@@ -294,6 +302,21 @@ language: Tsx",
     assert_eq!(matches[1].text(), "console.log('ignore another')");
   }
 
+  #[test]
+  fn test_malformed_suppression() {
+    let source = r#"
+    // ast-grep-ignore foo: test
+    console.log('not ignored')
+    "#;
+    let root = TypeScript::Tsx.ast_grep(source);
+    let rule = create_rule();
+    let scan = CombinedScan::new(vec![&rule]);
+    let pre = scan.find(&root);
+    assert!(pre.suppressions.0.is_empty());
+    let scanned = scan.scan(&root, pre, false);
+    assert_eq!(scanned.matches[&0].len(), 1);
+  }
+
   #[test]
   fn test_non_used_suppression() {
     let source = r#"
@@ -312,4 +335,17 @@ language: Tsx",
     assert_eq!(unused.len(), 1);
     assert_eq!(unused[0].text(), "// ast-grep-ignore: test");
   }
+
+  #[test]
+  fn test_exclude_rules() {
+    let source = "console.log('excluded')";
+    let root = TypeScript::Tsx.ast_grep(source);
+    let rule = create_rule();
+    let mut scan = CombinedScan::new(vec![&rule]);
+    scan.exclude_rules(|r| r.id == "test");
+    let pre = scan.find(&root);
+    assert!(pre.hit_set.is_empty());
+    let scanned = scan.scan(&root, pre, false);
+    assert!(scanned.matches.is_empty());
+  }
 }
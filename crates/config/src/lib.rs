@@ -6,7 +6,10 @@ mod rule;
 mod rule_collection;
 mod rule_config;
 mod rule_core;
+mod schema;
+mod suppression;
 mod transform;
+mod unknown_key;
 
 use serde::Deserialize;
 use serde_yaml::{with::singleton_map_recursive::deserialize, Deserializer, Error as YamlError};
@@ -15,13 +18,18 @@ use ast_grep_core::language::Language;
 
 pub use combined::{CombinedScan, PreScan};
 pub use fixer::Fixer;
-pub use rule::referent_rule::GlobalRules;
+pub use rule::referent_rule::{GlobalRules, ReferentRuleError};
 pub use rule::DeserializeEnv;
 pub use rule::{Rule, RuleSerializeError, SerializableRule};
 pub use rule_collection::RuleCollection;
-pub use rule_config::{RuleConfig, RuleConfigError, SerializableRuleConfig, Severity};
+pub use rule_config::{
+  Label, LabelConfig, LabelStyle, RuleConfig, RuleConfigError, SerializableRuleConfig, Severity,
+};
 pub use rule_core::{RuleCore, RuleCoreError, SerializableRuleCore};
+pub use schema::rule_schema;
+pub use suppression::Suppression;
 pub use transform::Transformation;
+pub use unknown_key::{find_unknown_keys, remove_unknown_keys, suggest_closest, UnknownKey};
 
 pub fn from_str<'de, T: Deserialize<'de>>(s: &'de str) -> Result<T, YamlError> {
   let deserializer = Deserializer::from_str(s);
@@ -263,6 +263,44 @@ impl<L: Language> Rule<L> {
     }
   }
 
+  /// Literal strings of which at least one must appear in the source for the rule to match.
+  /// None means no such literal can be extracted and the rule must always run.
+  pub fn required_literals(&self) -> Option<Vec<String>> {
+    match self {
+      Rule::Pattern(p) => {
+        // other strictness levels can skip terminal nodes or their text
+        if !matches!(p.strictness, MatchStrictness::Cst | MatchStrictness::Smart) {
+          return None;
+        }
+        let fixed = p.fixed_string();
+        (!fixed.is_empty()).then(|| vec![fixed.into_owned()])
+      }
+      Rule::Kind(_) => None,
+      Rule::Regex(_) => None,
+      Rule::NthChild(_) => None,
+      Rule::Has(c) => c.required_literals(),
+      Rule::Inside(p) => p.required_literals(),
+      Rule::Precedes(f) => f.required_literals(),
+      Rule::Follows(f) => f.required_literals(),
+      // every sub rule must match, pick the most selective literal set
+      Rule::All(sub) => sub
+        .inner()
+        .iter()
+        .filter_map(|r| r.required_literals())
+        .max_by_key(|lits| lits.iter().map(String::len).min()),
+      // any sub rule can match, so all of them must have literals
+      Rule::Any(sub) => {
+        let mut ret = vec![];
+        for rule in sub.inner() {
+          ret.extend(rule.required_literals()?);
+        }
+        (!ret.is_empty()).then_some(ret)
+      }
+      Rule::Not(_) => None,
+      Rule::Matches(_) => None,
+    }
+  }
+
   /// check if util rules used are defined
   pub fn verify_util(&self) -> Result<(), RuleSerializeError> {
     match self {
@@ -666,4 +704,43 @@ inside:
     );
     assert!(root.root().find(rule).is_some());
   }
+
+  fn literals(src: &str) -> Option<Vec<String>> {
+    let rule: SerializableRule = from_str(src).expect("cannot parse rule");
+    let env = DeserializeEnv::new(TypeScript::Tsx);
+    let rule = deserialize_rule(rule, &env).expect("should deserialize");
+    rule.required_literals()
+  }
+
+  #[test]
+  fn test_required_literals() {
+    assert_eq!(
+      literals("pattern: console.log($A)"),
+      Some(vec!["console".into()])
+    );
+    assert_eq!(literals("pattern: $A"), None);
+    assert_eq!(literals("kind: number"), None);
+    assert_eq!(literals("not: {pattern: debugger}"), None);
+    let src = "
+kind: call_expression
+has:
+  pattern: readFileSync
+  stopBy: end";
+    assert_eq!(literals(src), Some(vec!["readFileSync".into()]));
+    let src = "any: [pattern: alert($A), pattern: confirm($A)]";
+    assert_eq!(literals(src), Some(vec!["alert".into(), "confirm".into()]));
+    let src = "any: [pattern: alert($A), kind: number]";
+    assert_eq!(literals(src), None);
+    let src = "all: [pattern: a($A), pattern: setTimeout($A)]";
+    assert_eq!(literals(src), Some(vec!["setTimeout".into()]));
+  }
+
+  #[test]
+  fn test_required_literals_strictness() {
+    let src = "
+pattern:
+  context: foo($A)
+  strictness: signature";
+    assert_eq!(literals(src), None);
+  }
 }
@@ -57,6 +57,10 @@ impl<L: Language> Inside<L> {
       .collect()
   }
 
+  pub fn required_literals(&self) -> Option<Vec<String>> {
+    self.outer.required_literals()
+  }
+
   pub fn verify_util(&self) -> Result<(), RuleSerializeError> {
     self.outer.verify_util()?;
     self.stop_by.verify_util()
@@ -114,6 +118,10 @@ impl<L: Language> Has<L> {
       .collect()
   }
 
+  pub fn required_literals(&self) -> Option<Vec<String>> {
+    self.inner.required_literals()
+  }
+
   pub fn verify_util(&self) -> Result<(), RuleSerializeError> {
     self.inner.verify_util()?;
     self.stop_by.verify_util()
@@ -194,6 +202,10 @@ impl<L: Language> Precedes<L> {
       .collect()
   }
 
+  pub fn required_literals(&self) -> Option<Vec<String>> {
+    self.later.required_literals()
+  }
+
   pub fn verify_util(&self) -> Result<(), RuleSerializeError> {
     self.later.verify_util()?;
     self.stop_by.verify_util()
@@ -235,6 +247,10 @@ impl<L: Language> Follows<L> {
       .collect()
   }
 
+  pub fn required_literals(&self) -> Option<Vec<String>> {
+    self.former.required_literals()
+  }
+
   pub fn verify_util(&self) -> Result<(), RuleSerializeError> {
     self.former.verify_util()?;
     self.stop_by.verify_util()
@@ -138,6 +138,12 @@ impl<L: Language + Eq> RuleCollection<L> {
     None
   }
 
+  /// Iterate over all rules in the collection, regardless of their language or file globs.
+  pub fn iter(&self) -> impl Iterator<Item = &RuleConfig<L>> {
+    let tenured = self.tenured.iter().flat_map(|bucket| bucket.rules.iter());
+    tenured.chain(self.contingent.iter().map(|cont| &cont.rule))
+  }
+
   pub fn total_rule_count(&self) -> usize {
     let mut ret = self.tenured.iter().map(|bucket| bucket.rules.len()).sum();
     ret += self.contingent.len();
@@ -264,6 +270,15 @@ files:
     assert!(collection.get_rule("test").is_some());
   }
 
+  #[test]
+  fn test_rule_collection_iter() {
+    let collection = make_rule("files: [./manage.py]");
+    let ids: Vec<_> = collection.iter().map(|r| r.id.as_str()).collect();
+    assert_eq!(ids, ["test"]);
+    let collection = make_rule("");
+    assert_eq!(collection.iter().count(), 1);
+  }
+
   #[test]
   #[ignore]
   fn test_rules_for_path() {
@@ -7,7 +7,7 @@ use crate::rule_core::{RuleCore, RuleCoreError, SerializableRuleCore};
 
 use ast_grep_core::language::Language;
 use ast_grep_core::replacer::Replacer;
-use ast_grep_core::{Matcher, NodeMatch, StrDoc};
+use ast_grep_core::{Matcher, Node, NodeMatch, StrDoc};
 
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
@@ -48,6 +48,40 @@ pub enum RuleConfigError {
   NoFixInRewriter(String),
   #[error("Rule must specify a set of AST kinds to match. Try adding `kind` rule.")]
   MissingPotentialKinds,
+  #[error("Label meta-variable `{0}` must be defined in `rule` or `constraints`.")]
+  LabelVariable(String),
+}
+
+/// How a label is rendered in the diagnostic.
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub enum LabelStyle {
+  /// Labels that describe the primary cause of a diagnostic.
+  Primary,
+  /// Labels that provide additional context for a diagnostic.
+  Secondary,
+}
+
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
+pub struct LabelConfig {
+  /// The style of the label, either primary or secondary.
+  pub style: LabelStyle,
+  /// Message displayed next to the labeled code.
+  pub message: Option<String>,
+}
+
+/// A labeled sub-span of a match, resolved from a meta-variable.
+pub struct Label<'r, 't, D: ast_grep_core::Doc> {
+  pub style: LabelStyle,
+  pub message: Option<&'r str>,
+  pub start_node: Node<'t, D>,
+  pub end_node: Node<'t, D>,
+}
+
+impl<D: ast_grep_core::Doc> Label<'_, '_, D> {
+  pub fn range(&self) -> std::ops::Range<usize> {
+    self.start_node.range().start..self.end_node.range().end
+  }
 }
 
 #[derive(Serialize, Deserialize, Clone, JsonSchema)]
@@ -85,6 +119,8 @@ pub struct SerializableRuleConfig<L: Language> {
   pub url: Option<String>,
   /// Extra information for the rule
   pub metadata: Option<HashMap<String, String>>,
+  /// Meta-variables to highlight in the diagnostic, keyed by the meta-variable name.
+  pub labels: Option<HashMap<String, LabelConfig>>,
 }
 
 impl<L: Language> SerializableRuleConfig<L> {
@@ -171,6 +207,12 @@ impl<L: Language> RuleConfig<L> {
     if matcher.potential_kinds().is_none() {
       return Err(RuleConfigError::MissingPotentialKinds);
     }
+    if let Some(labels) = &inner.labels {
+      let vars = matcher.defined_vars();
+      if let Some(var) = labels.keys().find(|v| !vars.contains(v.as_str())) {
+        return Err(RuleConfigError::LabelVariable(var.clone()));
+      }
+    }
     Ok(Self { inner, matcher })
   }
 
@@ -191,6 +233,56 @@ impl<L: Language> RuleConfig<L> {
     let bytes = parsed.generate_replacement(node);
     String::from_utf8(bytes).expect("replacement must be valid utf-8")
   }
+  /// Labels of the match sorted by position. Rules without `labels` use the nodes
+  /// matched by relational sub-rules as secondary labels.
+  pub fn get_labels<'t>(&self, node: &NodeMatch<'t, StrDoc<L>>) -> Vec<Label<'_, 't, StrDoc<L>>> {
+    let env = node.get_env();
+    let mut ret = vec![];
+    let Some(labels) = &self.labels else {
+      if let Some(nodes) = env.get_labels("secondary") {
+        ret.extend(nodes.iter().map(|n| Label {
+          style: LabelStyle::Secondary,
+          message: None,
+          start_node: n.clone(),
+          end_node: n.clone(),
+        }));
+      }
+      return ret;
+    };
+    for (var, label) in labels {
+      let (start_node, end_node) = if let Some(n) = env.get_match(var) {
+        (n.clone(), n.clone())
+      } else {
+        let nodes = env.get_multiple_matches(var);
+        let (Some(first), Some(last)) = (nodes.first(), nodes.last()) else {
+          continue;
+        };
+        (first.clone(), last.clone())
+      };
+      ret.push(Label {
+        style: label.style.clone(),
+        message: label.message.as_deref(),
+        start_node,
+        end_node,
+      });
+    }
+    ret.sort_by_key(|l| (l.range().start, l.range().end));
+    ret
+  }
+  /// Literals of which at least one must appear in the source text for the rule to match.
+  /// It returns None if the rule can match without any literal.
+  pub fn required_literals(&self) -> Option<Vec<String>> {
+    let literals = self.matcher.required_literals()?;
+    let meta_char = self.language.meta_var_char();
+    let expando = self.language.expando_char();
+    // literals with meta char are rewritten by pattern preprocessing, skip them to be safe
+    let has_meta = |s: &String| s.contains(meta_char) || s.contains(expando);
+    if literals.iter().any(has_meta) {
+      None
+    } else {
+      Some(literals)
+    }
+  }
   pub fn get_fixer(&self) -> Result<Option<Fixer<L>>, RuleConfigError> {
     if let Some(fix) = &self.fix {
       let env = self.matcher.get_env(self.language.clone());
@@ -235,6 +327,7 @@ mod test {
       ignores: None,
       url: None,
       metadata: None,
+      labels: None,
     }
   }
 
@@ -254,6 +347,32 @@ mod test {
     assert_eq!(config.get_message(&node_match), "Found TestClass");
   }
 
+  #[test]
+  fn test_label_variable() {
+    let globals = GlobalRules::default();
+    let src = r"
+id: test
+language: Tsx
+rule: { pattern: let $A = $B }
+labels:
+  A: { style: primary, message: var }
+  B: { style: secondary }
+";
+    let config: SerializableRuleConfig<TypeScript> = from_str(src).expect("should parse");
+    let config = RuleConfig::try_from(config, &globals).expect("should work");
+    let grep = TypeScript::Tsx.ast_grep("let a = 123");
+    let nm = grep.root().find(&config.matcher).expect("should match");
+    let labels = config.get_labels(&nm);
+    assert_eq!(labels.len(), 2);
+    assert_eq!(labels[0].start_node.text(), "a");
+    assert_eq!(labels[0].message, Some("var"));
+    assert_eq!(labels[1].range(), 8..11);
+    let src = src.replace("B: { style", "C: { style");
+    let config: SerializableRuleConfig<TypeScript> = from_str(&src).expect("should parse");
+    let ret = RuleConfig::try_from(config, &globals);
+    assert!(matches!(ret, Err(RuleConfigError::LabelVariable(v)) if v == "C"));
+  }
+
   #[test]
   fn test_augmented_rule() {
     let globals = GlobalRules::default();
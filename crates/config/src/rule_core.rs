@@ -210,6 +210,11 @@ impl<L: Language> RuleCore<L> {
     ret
   }
 
+  /// See [`Rule::required_literals`].
+  pub fn required_literals(&self) -> Option<Vec<String>> {
+    self.rule.required_literals()
+  }
+
   pub(crate) fn do_match<'tree, D: Doc<Lang = L>>(
     &self,
     node: Node<'tree, D>,
@@ -0,0 +1,74 @@
+use crate::SerializableRuleConfig;
+
+use anyhow::{bail, Context, Result};
+use ast_grep_core::language::Language;
+use schemars::{
+  schema::{RootSchema, Schema},
+  schema_for, JsonSchema,
+};
+
+/// JSON schema of rule files. The schema of `language` is provided by `L`.
+pub fn rule_schema<L: Language + JsonSchema>() -> Result<RootSchema> {
+  let mut schema = schema_for!(SerializableRuleConfig<L>);
+  tweak_schema(&mut schema)?;
+  Ok(schema)
+}
+
+fn tweak_schema(schema: &mut RootSchema) -> Result<()> {
+  // better schema name
+  schema.schema.metadata().title = Some("ast-grep rule".to_string());
+  // stopby's rule does not need to be nested
+  simplify_stop_by(schema)?;
+  // using rule/relation will be too noisy
+  let description = remove_recursive_rule_relation_description(schema)?;
+  // set description to rule
+  let props = &mut schema.schema.object().properties;
+  let Schema::Object(rule) = props.get_mut("rule").context("must have rule")? else {
+    bail!("rule's type is not object!");
+  };
+  rule.metadata().description = description;
+  Ok(())
+}
+
+fn remove_recursive_rule_relation_description(schema: &mut RootSchema) -> Result<Option<String>> {
+  let definitions = &mut schema.definitions;
+  let Schema::Object(relation) = definitions
+    .get_mut("Relation")
+    .context("must have relation")?
+  else {
+    bail!("Relation's type is not object!");
+  };
+  relation.metadata().description = None;
+  let Schema::Object(rule) = definitions
+    .get_mut("SerializableRule")
+    .context("must have rule")?
+  else {
+    bail!("SerializableRule's type is not object!");
+  };
+  Ok(rule.metadata().description.take())
+}
+
+fn simplify_stop_by(schema: &mut RootSchema) -> Result<()> {
+  let definitions = &mut schema.definitions;
+  let Schema::Object(stop_by) = definitions
+    .get_mut("SerializableStopBy")
+    .context("must have stopby")?
+  else {
+    bail!("StopBy's type is not object!");
+  };
+  let one_ofs = stop_by
+    .subschemas()
+    .one_of
+    .as_mut()
+    .context("should have one_of")?;
+  let Schema::Object(rule) = &mut one_ofs[1] else {
+    bail!("type is not object!");
+  };
+  let rule = rule
+    .object()
+    .properties
+    .remove("rule")
+    .context("should have rule")?;
+  one_ofs[1] = rule;
+  Ok(())
+}
@@ -0,0 +1,125 @@
+//! Suppression comments like `// ast-grep-ignore: rule-a, rule-b -- reason`.
+//! The scanner reads them and editors write them, so both share the format here.
+use ast_grep_core::language::Language;
+
+use std::collections::HashSet;
+
+pub(crate) const IGNORE_TEXT: &str = "ast-grep-ignore";
+/// Separates the optional reason from the suppressed rule ids.
+const REASON_SEPARATOR: &str = " --";
+
+/// A parsed suppression comment.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Suppression {
+  /// None suppresses all rules.
+  pub rule_ids: Option<Vec<String>>,
+  pub reason: Option<String>,
+}
+
+impl Suppression {
+  /// Parse the text of a comment node. Returns None if the comment is not a suppression.
+  /// Text after the marker must be rule ids after a colon, so `ast-grep-ignore foo: a`
+  /// is not a suppression, rather than suppressing all rules.
+  pub fn parse<L: Language>(lang: &L, comment: &str) -> Option<Self> {
+    let (_, end) = lang.comment_delimiters();
+    let text = comment.trim();
+    let text = text.strip_suffix(end).unwrap_or(text);
+    let (_, after) = text.split_once(IGNORE_TEXT)?;
+    let (after, reason) = match after.split_once(REASON_SEPARATOR) {
+      Some((after, reason)) => (after, Some(reason.trim().to_string())),
+      None => (after, None),
+    };
+    let after = after.trim();
+    let rule_ids = if after.is_empty() {
+      None
+    } else {
+      let rules = after.strip_prefix(':')?;
+      let ids = rules.split(',').map(|r| r.trim().to_string());
+      Some(ids.filter(|r| !r.is_empty()).collect())
+    };
+    Some(Self { rule_ids, reason })
+  }
+
+  /// The suppressed rule ids, None if all rules are suppressed.
+  pub fn rule_set(&self) -> Option<HashSet<String>> {
+    let ids = self.rule_ids.as_ref()?;
+    Some(ids.iter().cloned().collect())
+  }
+
+  /// Write the suppression as a comment in the language.
+  pub fn to_comment<L: Language>(&self, lang: &L) -> String {
+    let (start, end) = lang.comment_delimiters();
+    let mut comment = format!("{start} {IGNORE_TEXT}");
+    if let Some(ids) = &self.rule_ids {
+      comment.push_str(": ");
+      comment.push_str(&ids.join(", "));
+    }
+    if let Some(reason) = &self.reason {
+      comment.push_str(REASON_SEPARATOR);
+      comment.push(' ');
+      comment.push_str(reason);
+    }
+    if !end.is_empty() {
+      comment.push(' ');
+      comment.push_str(end);
+    }
+    comment
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::test::TypeScript;
+
+  #[derive(Clone)]
+  struct Css;
+  impl Language for Css {
+    fn get_ts_language(&self) -> ast_grep_core::language::TSLanguage {
+      unreachable!("only comment syntax is used")
+    }
+    fn comment_delimiters(&self) -> (&'static str, &'static str) {
+      ("/*", "*/")
+    }
+  }
+
+  fn ids(ids: &[&str]) -> Option<Vec<String>> {
+    Some(ids.iter().map(|s| s.to_string()).collect())
+  }
+
+  #[test]
+  fn test_parse_suppression() {
+    let lang = TypeScript::Tsx;
+    let parse = |s| Suppression::parse(&lang, s).expect("should parse");
+    assert_eq!(parse("// ast-grep-ignore").rule_ids, None);
+    assert_eq!(parse("// ast-grep-ignore: a").rule_ids, ids(&["a"]));
+    assert_eq!(parse("// ast-grep-ignore: a, b").rule_ids, ids(&["a", "b"]));
+    let with_reason = parse("// ast-grep-ignore: a -- legacy code");
+    assert_eq!(with_reason.rule_ids, ids(&["a"]));
+    assert_eq!(with_reason.reason.as_deref(), Some("legacy code"));
+    assert_eq!(parse("// ast-grep-ignore -- all").rule_ids, None);
+    assert!(Suppression::parse(&lang, "// normal comment").is_none());
+    // stray text after the marker does not suppress all rules
+    assert!(Suppression::parse(&lang, "// ast-grep-ignore foo: a").is_none());
+    assert!(Suppression::parse(&lang, "// ast-grep-ignored").is_none());
+    let block = Suppression::parse(&Css, "/* ast-grep-ignore: a */").expect("should parse");
+    assert_eq!(block.rule_ids, ids(&["a"]));
+  }
+
+  #[test]
+  fn test_format_suppression() {
+    let suppression = Suppression {
+      rule_ids: ids(&["a", "b"]),
+      reason: None,
+    };
+    let comment = suppression.to_comment(&TypeScript::Tsx);
+    assert_eq!(comment, "// ast-grep-ignore: a, b");
+    let suppression = Suppression {
+      rule_ids: ids(&["a"]),
+      reason: Some("why".into()),
+    };
+    let comment = suppression.to_comment(&Css);
+    assert_eq!(comment, "/* ast-grep-ignore: a -- why */");
+    assert_eq!(Suppression::parse(&Css, &comment), Some(suppression));
+  }
+}
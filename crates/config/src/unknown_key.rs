@@ -0,0 +1,279 @@
+//! Find misspelled keys like `severty` or `paterns` in rule YAML.
+//! serde's `deny_unknown_fields` does not work with `#[serde(flatten)]` in rule config,
+//! so keys are checked against the known keys of every rule section and
+//! reported with the closest known key.
+
+use serde_yaml::{Mapping, Value};
+
+const RULE_CONFIG_KEYS: &[&str] = &[
+  "id",
+  "language",
+  "rule",
+  "constraints",
+  "utils",
+  "transform",
+  "fix",
+  "rewriters",
+  "message",
+  "note",
+  "severity",
+  "files",
+  "ignores",
+  "url",
+  "metadata",
+  "labels",
+  // inline tests read by `sg test`
+  "tests",
+];
+const REWRITER_KEYS: &[&str] = &["id", "rule", "constraints", "utils", "transform", "fix"];
+const RULE_KEYS: &[&str] = &[
+  "pattern", "kind", "regex", "nthChild", "inside", "has", "precedes", "follows", "all", "any",
+  "not", "matches",
+];
+const RELATION_KEYS: &[&str] = &["stopBy", "field"];
+const PATTERN_KEYS: &[&str] = &["context", "selector", "strictness"];
+const NTH_CHILD_KEYS: &[&str] = &["position", "ofRule", "reverse"];
+
+/// A key in rule YAML that ast-grep does not recognize.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownKey {
+  pub key: String,
+  /// Path of the object containing the key, e.g. `constraints.A`. Empty for the top level.
+  pub parent: String,
+  /// The known key closest to the unknown one by edit distance.
+  pub suggestion: Option<&'static str>,
+}
+
+/// Find unknown keys at the top level of a rule config and inside its rule objects.
+pub fn find_unknown_keys(config: &Value) -> Vec<UnknownKey> {
+  let mut checker = KeyChecker::default();
+  checker.check_config(&mut config.clone());
+  checker.unknown
+}
+
+/// Remove unknown keys from a rule config so that it can be deserialized leniently.
+/// Returns the removed keys.
+pub fn remove_unknown_keys(config: &mut Value) -> Vec<UnknownKey> {
+  let mut checker = KeyChecker {
+    remove: true,
+    ..Default::default()
+  };
+  checker.check_config(config);
+  checker.unknown
+}
+
+fn join(parent: &str, key: &str) -> String {
+  if parent.is_empty() {
+    key.to_string()
+  } else {
+    format!("{parent}.{key}")
+  }
+}
+
+#[derive(Default)]
+struct KeyChecker {
+  remove: bool,
+  unknown: Vec<UnknownKey>,
+}
+
+impl KeyChecker {
+  fn check_config(&mut self, config: &mut Value) {
+    let Value::Mapping(map) = config else {
+      return;
+    };
+    self.check_keys(map, &[RULE_CONFIG_KEYS], "");
+    self.check_rule_core(map, "");
+    if let Some(Value::Sequence(rewriters)) = map.get_mut("rewriters") {
+      for (i, rewriter) in rewriters.iter_mut().enumerate() {
+        let Value::Mapping(rewriter) = rewriter else {
+          continue;
+        };
+        let parent = format!("rewriters[{i}]");
+        self.check_keys(rewriter, &[REWRITER_KEYS], &parent);
+        self.check_rule_core(rewriter, &parent);
+      }
+    }
+  }
+
+  fn check_keys(&mut self, map: &mut Mapping, known: &[&[&'static str]], parent: &str) {
+    let known = || known.iter().flat_map(|keys| keys.iter().copied());
+    let is_known = |key: &Value| key.as_str().map_or(true, |key| known().any(|k| k == key));
+    for key in map.keys().filter(|key| !is_known(key)) {
+      let key = key.as_str().expect("unknown key must be string");
+      self.unknown.push(UnknownKey {
+        key: key.to_string(),
+        parent: parent.to_string(),
+        suggestion: suggest_closest(key, known()),
+      });
+    }
+    if self.remove {
+      map.retain(|key, _| is_known(key));
+    }
+  }
+
+  fn check_rule_core(&mut self, map: &mut Mapping, parent: &str) {
+    if let Some(rule) = map.get_mut("rule") {
+      self.check_rule(rule, &[RULE_KEYS], &join(parent, "rule"));
+    }
+    for section in ["constraints", "utils"] {
+      let Some(Value::Mapping(rules)) = map.get_mut(section) else {
+        continue;
+      };
+      let parent = join(parent, section);
+      for (name, rule) in rules.iter_mut() {
+        let name = name.as_str().unwrap_or_default();
+        self.check_rule(rule, &[RULE_KEYS], &join(&parent, name));
+      }
+    }
+  }
+
+  fn check_rule(&mut self, rule: &mut Value, known: &[&[&'static str]], parent: &str) {
+    let Value::Mapping(map) = rule else {
+      return;
+    };
+    self.check_keys(map, known, parent);
+    for (key, value) in map.iter_mut() {
+      let Some(key) = key.as_str() else {
+        continue;
+      };
+      let path = join(parent, key);
+      match (key, value) {
+        ("pattern", Value::Mapping(pattern)) => self.check_keys(pattern, &[PATTERN_KEYS], &path),
+        ("nthChild", Value::Mapping(nth)) => {
+          self.check_keys(nth, &[NTH_CHILD_KEYS], &path);
+          if let Some(of_rule) = nth.get_mut("ofRule") {
+            self.check_rule(of_rule, &[RULE_KEYS], &join(&path, "ofRule"));
+          }
+        }
+        ("inside" | "has" | "precedes" | "follows", relation) => {
+          self.check_rule(relation, &[RULE_KEYS, RELATION_KEYS], &path);
+        }
+        ("stopBy", stop_by) => self.check_rule(stop_by, &[RULE_KEYS], &path),
+        ("all" | "any", Value::Sequence(rules)) => {
+          for (i, rule) in rules.iter_mut().enumerate() {
+            self.check_rule(rule, &[RULE_KEYS], &format!("{path}[{i}]"));
+          }
+        }
+        ("not", not) => self.check_rule(not, &[RULE_KEYS], &path),
+        _ => (),
+      }
+    }
+  }
+}
+
+/// Suggest the closest known name if the edit distance is small enough.
+pub fn suggest_closest<'a>(key: &str, known: impl Iterator<Item = &'a str>) -> Option<&'a str> {
+  let max_distance = (key.chars().count() / 3).max(1);
+  known
+    .map(|k| (edit_distance(key, k), k))
+    .filter(|(d, _)| *d <= max_distance)
+    .min_by_key(|(d, _)| *d)
+    .map(|(_, k)| k)
+}
+
+/// Levenshtein distance between two strings.
+fn edit_distance(a: &str, b: &str) -> usize {
+  let b: Vec<char> = b.chars().collect();
+  let mut prev: Vec<usize> = (0..=b.len()).collect();
+  for (i, ca) in a.chars().enumerate() {
+    let mut cur = vec![i + 1; b.len() + 1];
+    for (j, cb) in b.iter().enumerate() {
+      let cost = usize::from(ca != *cb);
+      cur[j + 1] = (prev[j] + cost).min(prev[j + 1] + 1).min(cur[j] + 1);
+    }
+    prev = cur;
+  }
+  prev[b.len()]
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::from_str;
+
+  fn unknown(yaml: &str) -> Vec<UnknownKey> {
+    let value: Value = from_str(yaml).expect("should parse");
+    find_unknown_keys(&value)
+  }
+
+  #[test]
+  fn test_edit_distance() {
+    assert_eq!(edit_distance("severty", "severity"), 1);
+    assert_eq!(edit_distance("paterns", "pattern"), 2);
+    assert_eq!(edit_distance("", "id"), 2);
+    assert_eq!(edit_distance("same", "same"), 0);
+  }
+
+  #[test]
+  fn test_top_level_typo() {
+    let keys = unknown("{id: a, language: ts, severty: error, rule: {pattern: a}}");
+    assert_eq!(
+      keys,
+      vec![UnknownKey {
+        key: "severty".into(),
+        parent: "".into(),
+        suggestion: Some("severity"),
+      }]
+    );
+  }
+
+  #[test]
+  fn test_nested_typo() {
+    let keys = unknown(
+      "
+id: a
+language: ts
+rule:
+  all:
+    - pattern: $A
+    - inside: {kind: b, stop_by: end}
+constraints:
+  A: {paterns: a}
+",
+    );
+    assert_eq!(keys.len(), 2);
+    assert_eq!(keys[0].parent, "rule.all[1].inside");
+    assert_eq!(keys[0].suggestion, Some("stopBy"));
+    assert_eq!(keys[1].key, "paterns");
+    assert_eq!(keys[1].parent, "constraints.A");
+    assert_eq!(keys[1].suggestion, Some("pattern"));
+  }
+
+  #[test]
+  fn test_no_suggestion() {
+    let keys = unknown("{id: a, language: ts, rule: {pattern: a, foo: b}}");
+    assert_eq!(keys[0].key, "foo");
+    assert_eq!(keys[0].suggestion, None);
+  }
+
+  #[test]
+  fn test_remove_unknown_keys() {
+    let mut value: Value = from_str(
+      "{id: a, language: ts, severty: error, rule: {pattern: a, has: {kind: b, stop: end}}}",
+    )
+    .expect("should parse");
+    let keys = remove_unknown_keys(&mut value);
+    assert_eq!(keys.len(), 2);
+    assert!(find_unknown_keys(&value).is_empty());
+    assert!(value.get("severty").is_none());
+    assert!(value["rule"]["has"].get("stop").is_none());
+  }
+
+  #[test]
+  fn test_valid_rule() {
+    let keys = unknown(
+      "
+id: a
+language: ts
+rule:
+  pattern: {context: a, selector: b}
+  nthChild: {position: 1, ofRule: {kind: c}}
+  has: {kind: d, stopBy: {kind: e}, field: f}
+rewriters:
+- id: r
+  rule: {kind: g}
+",
+    );
+    assert!(keys.is_empty());
+  }
+}
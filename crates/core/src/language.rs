@@ -60,6 +60,12 @@ pub trait Language: Clone {
     None
   }
 
+  /// The start and end delimiters of a comment, used to read and write suppression comments.
+  /// The end delimiter is empty if the language has line comments.
+  fn comment_delimiters(&self) -> (&'static str, &'static str) {
+    ("//", "")
+  }
+
   /// get injected language regions in the root document. e.g. get JavaScripts in HTML
   /// it will return a list of tuples of (language, regions).
   /// The first item is the embedded region language, e.g. javascript
@@ -126,6 +126,34 @@ mod test {
     Ok(())
   }
 
+  #[test]
+  fn test_edit_incrementally() -> Result {
+    let mut ast_grep = Tsx.ast_grep("function a() {\n  return 1\n}\n");
+    // nodes reused by the incremental parse are misplaced if the old tree is edited twice
+    let edits: &[(usize, usize, &str)] = &[
+      (28, 0, "console.log('😀')"),
+      (27, 4, "a"),
+      (5, 39, "函数()"),
+      (13, 0, "{ x: [1, 2] }\n"),
+      (13, 14, "函数()"),
+      (16, 5, "\n"),
+      (3, 9, "a"),
+      (9, 0, "a"),
+      (10, 0, "/* é */"),
+      (0, 3, "a"),
+    ];
+    for &(position, deleted_length, inserted) in edits {
+      ast_grep.edit(source::Edit {
+        position,
+        deleted_length,
+        inserted_text: inserted.into(),
+      })?;
+      let expected = Tsx.ast_grep(ast_grep.source());
+      assert_eq!(ast_grep.root().to_sexp(), expected.root().to_sexp());
+    }
+    Ok(())
+  }
+
   #[test]
   fn test_replace_unnamed_node() -> Result {
     // ++ and -- is unnamed node in tree-sitter javascript
@@ -97,14 +97,14 @@ struct Inner {
 
 #[derive(Debug, Error)]
 pub enum DynamicLangError {
-  #[error("cannot load lib")]
-  OpenLib(#[source] LibError),
-  #[error("cannot read symbol")]
-  ReadSymbol(#[source] LibError),
-  #[error("Incompatible tree-sitter parser version `{0}`")]
-  IncompatibleVersion(usize),
-  #[error("cannot get the absolute path of dynamic lib")]
-  GetLibPath(#[from] std::io::Error),
+  #[error("cannot load lib `{0}`")]
+  OpenLib(PathBuf, #[source] LibError),
+  #[error("cannot read symbol `{1}` from `{0}`")]
+  ReadSymbol(PathBuf, String, #[source] LibError),
+  #[error("Incompatible tree-sitter parser version `{1}` of `{0}`")]
+  IncompatibleVersion(PathBuf, usize),
+  #[error("cannot get the absolute path of dynamic lib `{0}`")]
+  GetLibPath(PathBuf, #[source] std::io::Error),
 }
 
 /// # Safety: we must keep lib in memory after load it.
@@ -114,17 +114,18 @@ unsafe fn load_ts_language(
   path: PathBuf,
   name: String,
 ) -> Result<(Library, TSLanguage), DynamicLangError> {
-  let abs_path = canonicalize(path)?;
-  let lib = Library::new(abs_path.as_os_str()).map_err(DynamicLangError::OpenLib)?;
+  let abs_path = canonicalize(&path).map_err(|e| DynamicLangError::GetLibPath(path, e))?;
+  let lib = Library::new(abs_path.as_os_str())
+    .map_err(|e| DynamicLangError::OpenLib(abs_path.clone(), e))?;
   // NOTE: func is a symbol with lifetime bound to `lib`.
   // If we drop lib in the scope, func will be a dangling pointer.
   let func: Symbol<unsafe extern "C" fn() -> NativeTS> = lib
     .get(name.as_bytes())
-    .map_err(DynamicLangError::ReadSymbol)?;
+    .map_err(|e| DynamicLangError::ReadSymbol(abs_path.clone(), name.clone(), e))?;
   let lang = func();
   let version = lang.version();
   if !(MIN_COMPATIBLE_LANGUAGE_VERSION..=LANGUAGE_VERSION).contains(&version) {
-    Err(DynamicLangError::IncompatibleVersion(version))
+    Err(DynamicLangError::IncompatibleVersion(abs_path, version))
   } else {
     // ATTENTION: dragon ahead
     // must hold valid reference to NativeTS
@@ -276,6 +277,25 @@ mod test {
     );
   }
 
+  #[test]
+  fn test_load_error() {
+    let path = get_tree_sitter_path();
+    let Err(err) = (unsafe { load_ts_language(path.into(), "tree_sitter_nope".into()) }) else {
+      panic!("symbol should not exist");
+    };
+    let message = err.to_string();
+    assert!(message.starts_with("cannot read symbol `tree_sitter_nope` from `"));
+    assert!(message.ends_with("json-linux.so`") || message.ends_with("json-mac.so`"));
+    let Err(err) = (unsafe { load_ts_language("not-exist.so".into(), "tree_sitter_json".into()) })
+    else {
+      panic!("lib should not exist");
+    };
+    assert_eq!(
+      err.to_string(),
+      "cannot get the absolute path of dynamic lib `not-exist.so`"
+    );
+  }
+
   #[test]
   fn test_register_lang() {
     let registration = Registration {
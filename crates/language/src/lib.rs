@@ -240,6 +240,11 @@ impl SupportLang {
   pub fn file_types(&self) -> Types {
     file_types(*self)
   }
+
+  /// Names and aliases accepted by [FromStr], in lower case.
+  pub fn aliases(&self) -> &'static [&'static str] {
+    alias(*self)
+  }
 }
 
 impl fmt::Display for SupportLang {
@@ -409,6 +414,10 @@ impl Language for SupportLang {
   impl_lang_method!(extract_meta_var, (source: &str) => Option<MetaVariable>);
   impl_lang_method!(injectable_languages, () => Option<&'static [&'static str]>);
 
+  fn comment_delimiters(&self) -> (&'static str, &'static str) {
+    comment_delimiters(*self)
+  }
+
   fn extract_injections<D: Doc>(&self, root: Node<D>) -> HashMap<String, Vec<TSRange>> {
     match self {
       SupportLang::Html => Html.extract_injections(root),
@@ -421,6 +430,18 @@ impl Language for SupportLang {
   }
 }
 
+fn comment_delimiters(lang: SupportLang) -> (&'static str, &'static str) {
+  use SupportLang::*;
+  match lang {
+    Bash | Elixir | Python | Ruby | Yaml => ("#", ""),
+    Haskell | Lua | Sql => ("--", ""),
+    Css => ("/*", "*/"),
+    Html => ("<!--", "-->"),
+    C | Cpp | CSharp | Dart | Go | Java | JavaScript | Json | Kotlin | Php | Rust | Scala
+    | Swift | Tsx | TypeScript => ("//", ""),
+  }
+}
+
 fn extensions(lang: SupportLang) -> &'static [&'static str] {
   use SupportLang::*;
   match lang {
@@ -435,7 +456,7 @@ fn extensions(lang: SupportLang) -> &'static [&'static str] {
     Elixir => &["ex", "exs"],
     Go => &["go"],
     Haskell => &["hs"],
-    Html => &["html", "htm", "xhtml"],
+    Html => &["html", "htm", "xhtml", "vue"],
     Java => &["java"],
     JavaScript => &["cjs", "js", "mjs", "jsx"],
     Json => &["json"],
@@ -543,6 +564,8 @@ mod test {
   fn test_guess_by_extension() {
     let path = Path::new("foo.rs");
     assert_eq!(from_extension(path), Some(SupportLang::Rust));
+    let path = Path::new("App.vue");
+    assert_eq!(from_extension(path), Some(SupportLang::Html));
   }
 
   // TODO: add test for file_types
@@ -0,0 +1,38 @@
+//! Cancellation of scheduled diagnostics. A newer change of the document cancels the
+//! diagnostics of the older version, whether it is waiting for edits to settle or running.
+use tokio::sync::Notify;
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+#[derive(Default)]
+pub struct Cancellation {
+  cancelled: AtomicBool,
+  /// wakes up the computation waiting for edits to settle
+  notify: Notify,
+}
+
+impl Cancellation {
+  pub fn cancel(&self) {
+    self.cancelled.store(true, Ordering::SeqCst);
+    // the permit is stored if the computation is not waiting yet
+    self.notify.notify_one();
+  }
+
+  pub fn is_cancelled(&self) -> bool {
+    self.cancelled.load(Ordering::SeqCst)
+  }
+
+  /// The flag checked by the running computation between rules.
+  pub fn flag(&self) -> &AtomicBool {
+    &self.cancelled
+  }
+
+  /// Wait for the delay, or less if cancelled meanwhile. Returns whether it is cancelled.
+  pub async fn wait(&self, delay: Duration) -> bool {
+    if !delay.is_zero() {
+      let _ = tokio::time::timeout(delay, self.notify.notified()).await;
+    }
+    self.is_cancelled()
+  }
+}
@@ -1,3 +1,9 @@
+mod cancellation;
+mod progress;
+mod project;
+mod settings;
+mod severity;
+mod text_sync;
 mod utils;
 
 use dashmap::DashMap;
@@ -6,35 +12,139 @@ use tower_lsp::jsonrpc::Result;
 use tower_lsp::lsp_types::*;
 use tower_lsp::{Client, LanguageServer};
 
-use ast_grep_config::{CombinedScan, RuleCollection, RuleConfig};
-use ast_grep_core::{language::Language, AstGrep, Doc, StrDoc};
+use ast_grep_config::{CombinedScan, RuleCollection, RuleConfig, Severity};
+use ast_grep_core::{language::Language, AstGrep, Doc, NodeMatch, StrDoc};
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
 
-use utils::{convert_match_to_diagnostic, diagnostic_to_code_action, RewriteData};
+use cancellation::Cancellation;
+use progress::ProgressReporter;
+use project::{find_project, folder_label, project_sources, Project};
+use settings::{Settings, SETTINGS_SECTION};
+use text_sync::{apply_changes, apply_text_changes, LineIndex};
+use utils::{
+  convert_match_to_diagnostic, convert_node_to_range, convert_rule_problem_to_diagnostic,
+  diagnostic_to_code_action, rule_hover, rule_id_range, suppression_code_actions, RewriteData,
+};
 
-pub use tower_lsp::{LspService, Server};
+pub use tower_lsp::{ClientSocket, LspService, LspServiceBuilder, Server};
 
-pub trait LSPLang: Language + Eq + Send + Sync + 'static {}
-impl<T> LSPLang for T where T: Language + Eq + Send + Sync + 'static {}
+pub trait LSPLang: Language + Eq + FromStr + Send + Sync + 'static {}
+impl<T> LSPLang for T where T: Language + Eq + FromStr + Send + Sync + 'static {}
 
 struct VersionedAst<D: Doc> {
   version: i32,
   root: AstGrep<D>,
+  /// line starts of the source to locate incremental changes
+  lines: LineIndex,
 }
 
+/// Rules of a project loaded by a [`ConfigLoader`].
+pub struct ProjectRules<L: LSPLang> {
+  /// the directory of the project configuration, rule globs are relative to it
+  pub base: PathBuf,
+  pub rules: RuleCollection<L>,
+  /// rule files that cannot be loaded and the reasons
+  pub broken_files: Vec<(PathBuf, String)>,
+  /// files defining the rules keyed by rule id, used to open the rule of a diagnostic
+  pub rule_paths: HashMap<String, PathBuf>,
+  /// check rule files and the project configuration when they are edited
+  pub rule_checker: Option<RuleChecker>,
+}
+
+/// Load the rules of a project from the configuration file path, if it is specified.
+/// The loader is called when the server is initialized, and again when rule files
+/// or the configuration path change.
+pub type ConfigLoader<L> =
+  Box<dyn Fn(Option<PathBuf>) -> std::result::Result<ProjectRules<L>, String> + Send + Sync>;
+
+/// Check a document of a rule file or the project configuration.
+/// Returns `None` for other documents, otherwise the 1-based line, column and message
+/// of each problem in the document.
+pub type RuleChecker =
+  Box<dyn Fn(&Path, &str) -> Option<Vec<(usize, usize, String)>> + Send + Sync>;
+
+/// List the files to scan in the workspace folders, e.g. respecting ignore files like `sg scan`.
+/// The walk should stop early once the flag is set, which means the scan is cancelled.
+pub type FileWalker = Box<dyn Fn(&[PathBuf], &AtomicBool) -> Vec<PathBuf> + Send + Sync>;
+
+/// Whether a file under the project root, the first argument, is excluded by ignore files.
+pub type IgnoreFilter = Box<dyn Fn(&Path, &Path) -> bool + Send + Sync>;
+
 pub struct Backend<L: LSPLang> {
   client: Client,
   map: DashMap<String, VersionedAst<StrDoc<L>>>,
-  base: PathBuf,
-  rules: std::result::Result<RuleCollection<L>, String>,
+  /// open rule files and their versions, diagnosed by the rule checker instead of rules
+  rule_docs: DashMap<String, (i32, String)>,
+  /// rules of the whole workspace, or of each workspace folder with its own configuration
+  projects: RwLock<Vec<Arc<Project<L>>>>,
+  /// settings of the client, like severities and the rule filter
+  settings: RwLock<Arc<Settings>>,
+  /// diagnostics of changed documents waiting for edits to settle or running
+  diagnosing: DashMap<String, Arc<Cancellation>>,
+  /// configuration path pinned by the command line, and the loader of the project rules
+  loader: Option<(Option<PathBuf>, ConfigLoader<L>)>,
+  /// messages shown to the user after initialization
+  pending_messages: Mutex<Vec<(MessageType, String)>>,
+  /// state to reload the project rules when rule files or settings change
+  reload: ReloadState,
+  /// state of the workspace scan command
+  workspace: WorkspaceScan,
+  /// progress of workspace scans and rule loading
+  progress: ProgressReporter,
+  /// documents excluded by ignore files are not diagnosed, like `sg scan`
+  ignore_filter: Option<IgnoreFilter>,
 }
 
+/// Rules are reloaded when yaml files in the project or the configuration path change.
+#[derive(Default)]
+struct ReloadState {
+  /// the configuration path of the loaded rules, `None` to load them per workspace folder
+  config_path: Mutex<Option<PathBuf>>,
+  /// the workspace root at initialization, which relative configuration paths are resolved against
+  workspace_root: Mutex<Option<PathBuf>>,
+  /// the workspace folders, updated when the client adds or removes folders
+  folders: Mutex<Vec<PathBuf>>,
+  /// whether the client supports the `workspace/configuration` request
+  can_pull_settings: AtomicBool,
+  /// bumped by every file change event, a reload runs only if no newer event arrives
+  generation: AtomicUsize,
+  /// whether the client can register file watchers dynamically
+  can_watch_files: AtomicBool,
+}
+
+/// Diagnostics of files not open in the editor are published by the workspace scan command.
+#[derive(Default)]
+struct WorkspaceScan {
+  walker: Option<Arc<FileWalker>>,
+  /// files with diagnostics published by the last scan
+  published: Mutex<HashSet<Url>>,
+  /// whether the client can show documents requested by the server
+  can_show_document: AtomicBool,
+  /// whether the client accepts versioned document changes in workspace edits
+  can_edit_documents: AtomicBool,
+}
+
+/// Wait for bursts of file events, like a git checkout, to settle before reloading.
+const RELOAD_DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// Rules scanned together between cancellation checks. A combined scan traverses the
+/// document once for all of its rules, so batches trade cancellation latency for speed.
+const RULES_PER_BATCH: usize = 32;
+
 const FALLBACK_CODE_ACTION_PROVIDER: Option<CodeActionProviderCapability> =
   Some(CodeActionProviderCapability::Simple(true));
 
 const APPLY_ALL_FIXES: &str = "ast-grep.applyAllFixes";
+const SCAN_WORKSPACE: &str = "ast-grep.scanWorkspace";
+const CLEAR_WORKSPACE_DIAGNOSTICS: &str = "ast-grep.clearWorkspaceDiagnostics";
+const GOTO_RULE: &str = "ast-grep.gotoRule";
+const WATCH_RULE_FILES: &str = "ast-grep.watchRuleFiles";
 const QUICKFIX_AST_GREP: &str = "quickfix.ast-grep";
 const FIX_ALL_AST_GREP: &str = "source.fixAll.ast-grep";
 
@@ -64,18 +174,93 @@ fn code_action_provider(
 #[tower_lsp::async_trait]
 impl<L: LSPLang> LanguageServer for Backend<L> {
   async fn initialize(&self, params: InitializeParams) -> Result<InitializeResult> {
+    let root = workspace_root(&params);
+    *self.reload.workspace_root.lock().expect("should lock") = root.clone();
+    let folders = params.workspace_folders.iter().flatten();
+    *self.reload.folders.lock().expect("should lock") = folders
+      .filter_map(|folder| folder.uri.to_file_path().ok())
+      .collect();
+    let options = params.initialization_options.as_ref();
+    let settings = Settings::parse(options, root.as_deref()).unwrap_or_else(|error| {
+      let message = format!("Invalid ast-grep settings: {error}");
+      let mut pending = self.pending_messages.lock().expect("should lock");
+      pending.push((MessageType::WARNING, message));
+      Settings::default()
+    });
+    // progress cannot be created before initialization, the loaded rules are shown after it
+    if let Some(summary) = self.load_project_rules(settings.config_path.clone()) {
+      let mut pending = self.pending_messages.lock().expect("should lock");
+      pending.push((MessageType::INFO, summary));
+    }
+    *self.settings.write().expect("should lock") = Arc::new(settings);
+    let can_pull_settings = params
+      .capabilities
+      .workspace
+      .as_ref()
+      .and_then(|w| w.configuration)
+      .unwrap_or(false);
+    self
+      .reload
+      .can_pull_settings
+      .store(can_pull_settings, Ordering::Relaxed);
+    let can_watch_files = params
+      .capabilities
+      .workspace
+      .as_ref()
+      .and_then(|w| w.did_change_watched_files.as_ref())
+      .and_then(|w| w.dynamic_registration)
+      .unwrap_or(false);
+    self
+      .reload
+      .can_watch_files
+      .store(can_watch_files, Ordering::Relaxed);
+    let can_report_progress = params
+      .capabilities
+      .window
+      .as_ref()
+      .and_then(|w| w.work_done_progress)
+      .unwrap_or(false);
+    self.progress.set_can_create(can_report_progress);
+    let can_show_document = params
+      .capabilities
+      .window
+      .as_ref()
+      .and_then(|w| w.show_document.as_ref())
+      .map_or(false, |s| s.support);
+    self
+      .workspace
+      .can_show_document
+      .store(can_show_document, Ordering::Relaxed);
+    let can_edit_documents = params
+      .capabilities
+      .workspace
+      .as_ref()
+      .and_then(|w| w.workspace_edit.as_ref())
+      .and_then(|e| e.document_changes)
+      .unwrap_or(false);
+    self
+      .workspace
+      .can_edit_documents
+      .store(can_edit_documents, Ordering::Relaxed);
     Ok(InitializeResult {
       server_info: Some(ServerInfo {
         name: "ast-grep language server".to_string(),
         version: None,
       }),
       capabilities: ServerCapabilities {
-        // TODO: change this to incremental
-        text_document_sync: Some(TextDocumentSyncCapability::Kind(TextDocumentSyncKind::FULL)),
+        text_document_sync: Some(TextDocumentSyncCapability::Kind(
+          TextDocumentSyncKind::INCREMENTAL,
+        )),
         code_action_provider: code_action_provider(&params.capabilities)
           .or(FALLBACK_CODE_ACTION_PROVIDER),
+        hover_provider: Some(HoverProviderCapability::Simple(true)),
         execute_command_provider: Some(ExecuteCommandOptions {
-          commands: vec![APPLY_ALL_FIXES.to_string()],
+          commands: vec![
+            APPLY_ALL_FIXES.to_string(),
+            SCAN_WORKSPACE.to_string(),
+            CLEAR_WORKSPACE_DIAGNOSTICS.to_string(),
+            GOTO_RULE.to_string(),
+          ],
           work_done_progress_options: Default::default(),
         }),
         ..ServerCapabilities::default()
@@ -90,23 +275,16 @@ impl<L: LSPLang> LanguageServer for Backend<L> {
       .await;
 
     // Report errors loading config once, upon initialization
-    if let Err(error) = &self.rules {
+    let messages = std::mem::take(&mut *self.pending_messages.lock().expect("should lock"));
+    for (typ, message) in messages {
       // popup message
-      self
-        .client
-        .show_message(
-          MessageType::ERROR,
-          format!("Failed to load rules: {}", error),
-        )
-        .await;
+      self.client.show_message(typ, &message).await;
       // log message
-      self
-        .client
-        .log_message(
-          MessageType::ERROR,
-          format!("Failed to load rules: {}", error),
-        )
-        .await;
+      self.client.log_message(typ, message).await;
+    }
+    self.watch_rule_files().await;
+    if let Some(settings) = self.pull_settings().await {
+      self.apply_settings(&settings).await;
     }
   }
 
@@ -114,25 +292,28 @@ impl<L: LSPLang> LanguageServer for Backend<L> {
     Ok(())
   }
 
-  async fn did_change_workspace_folders(&self, _: DidChangeWorkspaceFoldersParams) {
+  async fn did_change_workspace_folders(&self, params: DidChangeWorkspaceFoldersParams) {
     self
       .client
       .log_message(MessageType::INFO, "workspace folders changed!")
       .await;
+    self.on_workspace_folders_change(params).await;
   }
 
-  async fn did_change_configuration(&self, _: DidChangeConfigurationParams) {
+  async fn did_change_configuration(&self, params: DidChangeConfigurationParams) {
     self
       .client
       .log_message(MessageType::INFO, "configuration changed!")
       .await;
+    self.on_configuration_change(params).await;
   }
 
-  async fn did_change_watched_files(&self, _: DidChangeWatchedFilesParams) {
+  async fn did_change_watched_files(&self, params: DidChangeWatchedFilesParams) {
     self
       .client
       .log_message(MessageType::INFO, "watched files have changed!")
       .await;
+    self.on_watched_files_change(params).await;
   }
   async fn did_open(&self, params: DidOpenTextDocumentParams) {
     self
@@ -168,6 +349,10 @@ impl<L: LSPLang> LanguageServer for Backend<L> {
   async fn execute_command(&self, params: ExecuteCommandParams) -> Result<Option<Value>> {
     Ok(self.on_execute_command(params).await)
   }
+
+  async fn hover(&self, params: HoverParams) -> Result<Option<Hover>> {
+    Ok(self.on_hover(params))
+  }
 }
 
 impl<L: LSPLang> Backend<L> {
@@ -176,47 +361,480 @@ impl<L: LSPLang> Backend<L> {
     base: PathBuf,
     rules: std::result::Result<RuleCollection<L>, String>,
   ) -> Self {
+    let mut pending_messages = vec![];
+    if let Err(error) = &rules {
+      let message = format!("Failed to load rules: {}", error);
+      pending_messages.push((MessageType::ERROR, message));
+    }
+    let project = Project::failed((None, None), base, String::new());
+    let project = Project { rules, ..project };
     Self {
+      progress: ProgressReporter::new(client.clone()),
       client,
-      rules,
-      base,
+      projects: RwLock::new(vec![Arc::new(project)]),
+      settings: RwLock::default(),
+      diagnosing: DashMap::new(),
       map: DashMap::new(),
+      rule_docs: DashMap::new(),
+      loader: None,
+      pending_messages: Mutex::new(pending_messages),
+      reload: ReloadState::default(),
+      workspace: WorkspaceScan::default(),
+      ignore_filter: None,
     }
   }
 
-  fn get_rules(&self, uri: &Url) -> Option<Vec<&RuleConfig<L>>> {
-    let absolute_path = uri.to_file_path().ok()?;
-    // for_path needs relative path, see https://github.com/ast-grep/ast-grep/issues/1272
-    let base = Path::new("./");
-    let path = if let Ok(p) = absolute_path.strip_prefix(&self.base) {
-      base.join(p)
+  /// Create a backend that loads rules when the client initializes the server.
+  /// `config_path` from the command line overrides `initializationOptions.configPath`.
+  pub fn with_loader(
+    client: Client,
+    config_path: Option<PathBuf>,
+    loader: ConfigLoader<L>,
+  ) -> Self {
+    let error = "Rules are not loaded yet.".to_string();
+    let project = Project::failed((None, None), PathBuf::from("./"), error);
+    Self {
+      progress: ProgressReporter::new(client.clone()),
+      client,
+      projects: RwLock::new(vec![Arc::new(project)]),
+      settings: RwLock::default(),
+      diagnosing: DashMap::new(),
+      map: DashMap::new(),
+      rule_docs: DashMap::new(),
+      loader: Some((config_path, loader)),
+      pending_messages: Mutex::new(vec![]),
+      reload: ReloadState::default(),
+      workspace: WorkspaceScan::default(),
+      ignore_filter: None,
+    }
+  }
+
+  /// Use the walker to list files for the workspace scan command.
+  pub fn with_file_walker(mut self, walker: FileWalker) -> Self {
+    self.workspace.walker = Some(Arc::new(walker));
+    self
+  }
+
+  /// Register handlers of methods missing in [`LanguageServer`], like progress cancellation.
+  pub fn register_custom_methods(builder: LspServiceBuilder<Self>) -> LspServiceBuilder<Self> {
+    builder.custom_method("window/workDoneProgress/cancel", Self::on_progress_cancel)
+  }
+
+  async fn on_progress_cancel(&self, params: WorkDoneProgressCancelParams) {
+    self.progress.cancel(&params.token);
+  }
+
+  /// Use the filter to skip documents excluded by ignore files, e.g. `.gitignore`.
+  pub fn with_ignore_filter(mut self, filter: IgnoreFilter) -> Self {
+    self.ignore_filter = Some(filter);
+    self
+  }
+
+  /// The configuration path from the command line overrides the one in settings.
+  /// Returns the summary of loaded rules, or `None` if there is no loader or loading fails.
+  fn load_project_rules(&self, requested: Option<PathBuf>) -> Option<String> {
+    let (config_path, loader) = self.loader.as_ref()?;
+    let config_path = config_path.clone().or(requested);
+    *self.reload.config_path.lock().expect("should lock") = config_path.clone();
+    let folders = self.reload.folders.lock().expect("should lock").clone();
+    let mut messages = vec![];
+    let mut projects = vec![];
+    for source in project_sources(config_path, &folders) {
+      let label = folder_label(&source.0);
+      let base = source.0.clone().unwrap_or_else(|| PathBuf::from("./"));
+      let project = match source.1.clone() {
+        Some(path) if !path.exists() => {
+          let message = format!(
+            "Cannot find ast-grep configuration at {}. Diagnostics are disabled.",
+            path.display()
+          );
+          messages.push((MessageType::WARNING, message.clone()));
+          Project::failed(source, base, message)
+        }
+        path => match loader(path) {
+          Ok(project) => {
+            let warning = broken_warning(&project, &label);
+            messages.extend(warning.map(|w| (MessageType::WARNING, w)));
+            Project::loaded(source, project)
+          }
+          Err(error) => {
+            let message = format!("Failed to load rules{label}: {error}");
+            messages.push((MessageType::ERROR, message));
+            Project::failed(source, base, error)
+          }
+        },
+      };
+      projects.push(Arc::new(project));
+    }
+    let summary = loaded_summary(&projects);
+    *self.projects.write().expect("should lock") = projects;
+    self
+      .pending_messages
+      .lock()
+      .expect("should lock")
+      .extend(messages);
+    summary
+  }
+
+  /// The project whose rules diagnose the document, see [`find_project`].
+  /// Without document, it is the first project.
+  fn project_of(&self, uri: Option<&Url>) -> Option<Arc<Project<L>>> {
+    let path = uri.and_then(|uri| uri.to_file_path().ok());
+    let projects = self.projects.read().ok()?;
+    let folders = self.reload.folders.lock().ok()?;
+    find_project(&projects, &folders, path.as_deref()).cloned()
+  }
+
+  /// Fetch settings by `workspace/configuration` if the client supports it.
+  async fn pull_settings(&self) -> Option<Value> {
+    if !self.reload.can_pull_settings.load(Ordering::Relaxed) {
+      return None;
+    }
+    let item = ConfigurationItem {
+      scope_uri: None,
+      section: Some(SETTINGS_SECTION.into()),
+    };
+    let settings = self.client.configuration(vec![item]).await.ok()?;
+    settings.into_iter().next().filter(|s| !s.is_null())
+  }
+
+  /// Clients supporting `workspace/configuration` are asked for the settings,
+  /// others send them in the notification.
+  async fn on_configuration_change(&self, params: DidChangeConfigurationParams) {
+    let pulled = self.pull_settings().await;
+    self
+      .apply_settings(pulled.as_ref().unwrap_or(&params.settings))
+      .await;
+  }
+
+  /// Apply settings and re-publish diagnostics of open documents. Invalid settings are
+  /// reported and the last working settings are kept.
+  async fn apply_settings(&self, settings: &Value) {
+    let root = self
+      .reload
+      .workspace_root
+      .lock()
+      .expect("should lock")
+      .clone();
+    let applied = match Settings::parse(Some(settings), root.as_deref()) {
+      Ok(settings) => self
+        .switch_config_path(settings.config_path.clone())
+        .await
+        .map(|_| settings),
+      Err(error) => Err(format!("Invalid ast-grep settings: {error}")),
+    };
+    match applied {
+      Ok(settings) => *self.settings.write().expect("should lock") = Arc::new(settings),
+      Err(error) => {
+        let message = format!("{error}\nKeeping the last working settings.");
+        self
+          .client
+          .show_message(MessageType::WARNING, message)
+          .await;
+        return;
+      }
+    }
+    self.republish_all_diagnostics().await;
+  }
+
+  /// Load the projects of the new configuration path if it changes. The path from the
+  /// command line is never changed by settings.
+  async fn switch_config_path(
+    &self,
+    requested: Option<PathBuf>,
+  ) -> std::result::Result<(), String> {
+    let Some((None, loader)) = &self.loader else {
+      return Ok(());
+    };
+    if *self.reload.config_path.lock().expect("should lock") == requested {
+      return Ok(());
+    }
+    if let Some(path) = requested.as_ref().filter(|p| !p.exists()) {
+      let path = path.display();
+      return Err(format!("Cannot find ast-grep configuration at {path}."));
+    }
+    let progress = self
+      .progress
+      .begin(None, "Loading ast-grep rules", false)
+      .await;
+    let folders = self.reload.folders.lock().expect("should lock").clone();
+    let mut projects = vec![];
+    for source in project_sources(requested.clone(), &folders) {
+      let label = folder_label(&source.0);
+      let project = match loader(source.1.clone()) {
+        Ok(project) => project,
+        Err(error) => {
+          let message = format!("Failed to load rules{label}: {error}");
+          self.progress.fail(progress, message.clone()).await;
+          return Err(message);
+        }
+      };
+      if let Some(warning) = broken_warning(&project, &label) {
+        self
+          .client
+          .show_message(MessageType::WARNING, warning)
+          .await;
+      }
+      projects.push(Arc::new(Project::loaded(source, project)));
+    }
+    *self.reload.config_path.lock().expect("should lock") = requested;
+    let summary = loaded_summary(&projects).unwrap_or_default();
+    *self.projects.write().expect("should lock") = projects;
+    self.progress.end(progress, summary).await;
+    Ok(())
+  }
+
+  /// Ask the client to watch yaml files so rule changes are reloaded.
+  async fn watch_rule_files(&self) {
+    if self.loader.is_none() || !self.reload.can_watch_files.load(Ordering::Relaxed) {
+      return;
+    }
+    let options = DidChangeWatchedFilesRegistrationOptions {
+      watchers: vec![FileSystemWatcher {
+        glob_pattern: GlobPattern::String("**/*.{yml,yaml}".into()),
+        kind: None,
+      }],
+    };
+    let registration = Registration {
+      id: WATCH_RULE_FILES.into(),
+      method: "workspace/didChangeWatchedFiles".into(),
+      register_options: serde_json::to_value(options).ok(),
+    };
+    if let Err(e) = self.client.register_capability(vec![registration]).await {
+      let message = format!("Cannot watch rule files: {e}");
+      self.client.log_message(MessageType::WARNING, message).await;
+    }
+  }
+
+  async fn on_watched_files_change(&self, params: DidChangeWatchedFilesParams) {
+    // new configurations of workspace folders are loaded too
+    let mut roots: Vec<_> = self.reload.folders.lock().expect("should lock").clone();
+    let projects = self.projects.read().expect("should lock").clone();
+    roots.extend(projects.iter().map(|project| project.base.clone()));
+    let is_project_yaml = |event: &FileEvent| {
+      let Ok(path) = event.uri.to_file_path() else {
+        return false;
+      };
+      let is_yaml = path
+        .extension()
+        .map_or(false, |ext| ext == "yml" || ext == "yaml");
+      is_yaml && roots.iter().any(|root| path.starts_with(root))
+    };
+    if self.loader.is_none() || !params.changes.iter().any(is_project_yaml) {
+      return;
+    }
+    let generation = self.reload.generation.fetch_add(1, Ordering::SeqCst) + 1;
+    tokio::time::sleep(RELOAD_DEBOUNCE).await;
+    if self.reload.generation.load(Ordering::SeqCst) != generation {
+      // a newer event will reload the rules
+      return;
+    }
+    self.reload_rules().await;
+  }
+
+  /// Reload the project rules and re-publish diagnostics of open documents.
+  /// If a rule file newly fails to load, the last good rules of its project are kept
+  /// until it is fixed.
+  async fn reload_rules(&self) {
+    self.refresh_projects(true).await;
+  }
+
+  /// Load projects of added workspace folders and drop projects of removed ones.
+  async fn on_workspace_folders_change(&self, params: DidChangeWorkspaceFoldersParams) {
+    let paths = |folders: Vec<WorkspaceFolder>| -> Vec<_> {
+      folders
+        .into_iter()
+        .filter_map(|folder| folder.uri.to_file_path().ok())
+        .collect()
+    };
+    let (added, removed) = (paths(params.event.added), paths(params.event.removed));
+    {
+      let mut folders = self.reload.folders.lock().expect("should lock");
+      folders.retain(|folder| !removed.contains(folder));
+      for folder in added {
+        if !folders.contains(&folder) {
+          folders.push(folder);
+        }
+      }
+    }
+    self.refresh_projects(false).await;
+  }
+
+  /// Load the projects of the current workspace folders and configuration path.
+  /// Projects loaded before are reused unless `reload` is set.
+  async fn refresh_projects(&self, reload: bool) {
+    let Some((_, loader)) = &self.loader else {
+      return;
+    };
+    let config_path = self.reload.config_path.lock().expect("should lock").clone();
+    let folders = self.reload.folders.lock().expect("should lock").clone();
+    let sources = project_sources(config_path, &folders);
+    let last_projects = self.projects.read().expect("should lock").clone();
+    let last_sources: Vec<_> = last_projects.iter().map(|p| p.source()).collect();
+    if !reload && sources == last_sources {
+      return;
+    }
+    let (title, verb) = if reload {
+      ("Reloading ast-grep rules", "reload")
     } else {
-      absolute_path
+      ("Loading ast-grep rules", "load")
     };
-    let rules = self.rules.as_ref().ok()?.for_path(&path);
-    Some(rules)
+    let progress = self.progress.begin(None, title, false).await;
+    let mut projects = vec![];
+    let mut failures = vec![];
+    for source in sources {
+      let last = last_projects.iter().find(|p| p.source() == source);
+      if let Some(last) = last.filter(|_| !reload) {
+        projects.push(last.clone());
+        continue;
+      }
+      let label = folder_label(&source.0);
+      let project = match loader(source.1.clone()) {
+        Ok(project) => project,
+        Err(error) => {
+          failures.push((
+            MessageType::ERROR,
+            format!("Failed to {verb} rules{label}: {error}"),
+          ));
+          let base = source.0.clone().unwrap_or_else(|| PathBuf::from("./"));
+          let last = last.cloned();
+          projects.push(last.unwrap_or_else(|| Arc::new(Project::failed(source, base, error))));
+          continue;
+        }
+      };
+      let Some(last) = last else {
+        let warning = broken_warning(&project, &label);
+        failures.extend(warning.map(|w| (MessageType::WARNING, w)));
+        projects.push(Arc::new(Project::loaded(source, project)));
+        continue;
+      };
+      let newly_broken: Vec<_> = project
+        .broken_files
+        .iter()
+        .filter(|(path, _)| !last.broken_files.contains(path))
+        .collect();
+      if newly_broken.is_empty() {
+        projects.push(Arc::new(Project::loaded(source, project)));
+        continue;
+      }
+      let header = format!(
+        "Failed to load {} rule file(s){label}, keeping the last loaded rules:",
+        newly_broken.len()
+      );
+      failures.push((
+        MessageType::WARNING,
+        header + &describe_files(&newly_broken),
+      ));
+      projects.push(last.clone());
+    }
+    let summary = loaded_summary(&projects);
+    *self.projects.write().expect("should lock") = projects;
+    match failures.first() {
+      Some((_, failure)) => {
+        let reason = failure.lines().next().unwrap_or_default().to_string();
+        self.progress.fail(progress, reason).await;
+      }
+      None => {
+        self
+          .progress
+          .end(progress, summary.unwrap_or_default())
+          .await
+      }
+    }
+    for (typ, message) in failures {
+      self.client.show_message(typ, message).await;
+    }
+    self.republish_all_diagnostics().await;
+  }
+
+  async fn republish_all_diagnostics(&self) {
+    // collect first to avoid holding dashmap locks across await
+    let mut documents: Vec<_> = self
+      .map
+      .iter()
+      .filter_map(|entry| {
+        let uri = Url::parse(entry.key()).ok()?;
+        let diagnostics = self.get_diagnostics(&uri, &entry.root).unwrap_or_default();
+        Some((uri, diagnostics, entry.version))
+      })
+      .collect();
+    // rule files are checked again since utility rules may have changed
+    let rule_docs = self.rule_docs.iter().filter_map(|entry| {
+      let uri = Url::parse(entry.key()).ok()?;
+      let (version, text) = entry.value();
+      let diagnostics = self.check_rule_file(&uri, text).unwrap_or_default();
+      Some((uri, diagnostics, *version))
+    });
+    documents.extend(rule_docs.collect::<Vec<_>>());
+    for (uri, diagnostics, version) in documents {
+      self
+        .client
+        .publish_diagnostics(uri, diagnostics, Some(version))
+        .await;
+    }
+  }
+
+  fn get_diagnostics(&self, uri: &Url, root: &AstGrep<StrDoc<L>>) -> Option<Vec<Diagnostic>> {
+    let project = self.project_of(Some(uri))?;
+    let settings = self.settings.read().ok()?.clone();
+    diagnose(&project, &settings, uri, root, &AtomicBool::new(false))
   }
 
-  fn get_diagnostics(
+  /// Scan the document with the rules applying to its path, see [`scan_document`].
+  fn visit_matches(
     &self,
     uri: &Url,
-    versioned: &VersionedAst<StrDoc<L>>,
-  ) -> Option<Vec<Diagnostic>> {
-    let rules = self.get_rules(uri)?;
-    let scan = CombinedScan::new(rules);
-    let pre_scan = scan.find(&versioned.root);
-    let matches = scan.scan(&versioned.root, pre_scan, false).matches;
-    let mut diagnostics = vec![];
-    for (id, ms) in matches {
-      let rule = scan.get_rule(id);
-      let to_diagnostic = |m| convert_match_to_diagnostic(m, rule);
-      diagnostics.extend(ms.into_iter().map(to_diagnostic));
-    }
-    Some(diagnostics)
+    root: &AstGrep<StrDoc<L>>,
+    visit: impl FnMut(NodeMatch<StrDoc<L>>, &RuleConfig<L>, &Severity),
+  ) -> Option<()> {
+    let project = self.project_of(Some(uri))?;
+    let settings = self.settings.read().ok()?.clone();
+    scan_document(
+      &project,
+      &settings,
+      uri,
+      root,
+      &AtomicBool::new(false),
+      visit,
+    )
+  }
+
+  /// Describe the rules of diagnostics at the position. Nothing is returned outside
+  /// diagnostics so hovers of other language servers are not cluttered.
+  fn on_hover(&self, params: HoverParams) -> Option<Hover> {
+    let TextDocumentPositionParams {
+      text_document,
+      position,
+    } = params.text_document_position_params;
+    let versioned = self.map.get(text_document.uri.as_str())?;
+    let mut hovers = vec![];
+    self.visit_matches(&text_document.uri, &versioned.root, |m, rule, severity| {
+      let range = convert_node_to_range(&m, &versioned.lines);
+      if range.start <= position && position < range.end {
+        hovers.push((range, rule.id.clone(), rule_hover(&m, rule, severity)));
+      }
+    })?;
+    hovers
+      .sort_by(|(a, a_id, _), (b, b_id, _)| (a.start, a.end, a_id).cmp(&(b.start, b.end, b_id)));
+    // the overlap of all diagnostics contains the position
+    let range = hovers.iter().map(|(r, _, _)| *r).reduce(|a, b| Range {
+      start: a.start.max(b.start),
+      end: a.end.min(b.end),
+    })?;
+    let contents: Vec<_> = hovers.into_iter().map(|(_, _, hover)| hover).collect();
+    Some(Hover {
+      contents: HoverContents::Markup(MarkupContent {
+        kind: MarkupKind::Markdown,
+        value: contents.join("\n\n---\n\n"),
+      }),
+      range: Some(range),
+    })
   }
 
   async fn publish_diagnostics(&self, uri: Url, versioned: &VersionedAst<StrDoc<L>>) -> Option<()> {
-    let diagnostics = self.get_diagnostics(&uri, versioned).unwrap_or_default();
+    let diagnostics = self
+      .get_diagnostics(&uri, &versioned.root)
+      .unwrap_or_default();
     self
       .client
       .publish_diagnostics(uri, diagnostics, Some(versioned.version))
@@ -224,25 +842,71 @@ impl<L: LSPLang> Backend<L> {
     Some(())
   }
 
-  async fn get_path_of_first_workspace(&self) -> Option<std::path::PathBuf> {
+  async fn get_paths_of_workspace_folders(&self) -> Option<Vec<PathBuf>> {
     let folders = self.client.workspace_folders().await.ok()??;
-    let folder = folders.first()?;
-    folder.uri.to_file_path().ok()
+    let paths = folders.iter().filter_map(|f| f.uri.to_file_path().ok());
+    Some(paths.collect())
   }
 
-  // skip files outside of workspace root #1382, #1402
+  // skip files outside of workspace folders #1382, #1402
   async fn should_skip_file_outside_workspace(&self, text_doc: &TextDocumentItem) -> Option<()> {
-    let workspace_root = self.get_path_of_first_workspace().await?;
+    let folders = self.get_paths_of_workspace_folders().await?;
     let doc_file_path = text_doc.uri.to_file_path().ok()?;
-    if doc_file_path.starts_with(workspace_root) {
+    if folders.is_empty() || folders.iter().any(|f| doc_file_path.starts_with(f)) {
       None
     } else {
       Some(())
     }
   }
 
+  fn is_ignored(&self, uri: &Url) -> bool {
+    let (Some(filter), Ok(path)) = (&self.ignore_filter, uri.to_file_path()) else {
+      return false;
+    };
+    let Some(project) = self.project_of(Some(uri)) else {
+      return false;
+    };
+    filter(&project.base, &path)
+  }
+
+  /// Check the document if it is a rule file or the project configuration.
+  fn check_rule_file(&self, uri: &Url, text: &str) -> Option<Vec<Diagnostic>> {
+    let path = uri.to_file_path().ok()?;
+    let projects = self.projects.read().ok()?;
+    let checkers = projects.iter().filter_map(|p| p.rule_checker.as_ref());
+    let problems = checkers
+      .into_iter()
+      .find_map(|checker| checker(&path, text))?;
+    let diagnostics = problems
+      .into_iter()
+      .map(|problem| convert_rule_problem_to_diagnostic(text, problem))
+      .collect();
+    Some(diagnostics)
+  }
+
+  /// Publish problems of a rule file instead of scanning it with rules.
+  /// Returns `None` if the document is not a rule file.
+  async fn publish_rule_file_diagnostics(&self, uri: Url, version: i32, text: &str) -> Option<()> {
+    let diagnostics = self.check_rule_file(&uri, text)?;
+    self
+      .rule_docs
+      .insert(uri.to_string(), (version, text.to_string()));
+    self
+      .client
+      .publish_diagnostics(uri, diagnostics, Some(version))
+      .await;
+    Some(())
+  }
+
   async fn on_open(&self, params: DidOpenTextDocumentParams) -> Option<()> {
     let text_doc = params.text_document;
+    let (uri, version) = (text_doc.uri.clone(), text_doc.version);
+    let is_rule_file = self
+      .publish_rule_file_diagnostics(uri, version, &text_doc.text)
+      .await;
+    if is_rule_file.is_some() {
+      return Some(());
+    }
     if self
       .should_skip_file_outside_workspace(&text_doc)
       .await
@@ -250,17 +914,30 @@ impl<L: LSPLang> Backend<L> {
     {
       return None;
     }
+    if self.is_ignored(&text_doc.uri) {
+      self
+        .client
+        .log_message(MessageType::LOG, "Skipping doc excluded by ignore files.")
+        .await;
+      return None;
+    }
     let uri = text_doc.uri.as_str().to_owned();
     let text = text_doc.text;
     self
       .client
       .log_message(MessageType::LOG, "Parsing doc.")
       .await;
-    let lang = Self::infer_lang_from_uri(&text_doc.uri)?;
+    let lang = match text_doc.uri.to_file_path() {
+      Ok(path) => L::from_path(path)?,
+      // untitled documents have no path, use the language id from the client instead
+      Err(_) => self.lang_from_id(&text_doc.language_id)?,
+    };
+    let lines = LineIndex::new(&text);
     let root = AstGrep::new(text, lang);
     let versioned = VersionedAst {
       version: text_doc.version,
       root,
+      lines,
     };
     self
       .client
@@ -274,31 +951,98 @@ impl<L: LSPLang> Backend<L> {
   async fn on_change(&self, params: DidChangeTextDocumentParams) -> Option<()> {
     let text_doc = params.text_document;
     let uri = text_doc.uri.as_str();
-    let text = &params.content_changes[0].text;
+    let changes = params.content_changes;
+    let rule_doc = self.rule_docs.get(uri).map(|doc| doc.clone());
+    if let Some((version, mut text)) = rule_doc {
+      // skip old version update
+      if version > text_doc.version {
+        return None;
+      }
+      apply_text_changes(&mut text, changes);
+      let (uri, version) = (text_doc.uri.clone(), text_doc.version);
+      return self
+        .publish_rule_file_diagnostics(uri, version, &text)
+        .await;
+    }
+    {
+      // apply changes before any await, so changes are applied in order
+      let mut versioned = self.map.get_mut(uri)?;
+      // skip old version update
+      if versioned.version > text_doc.version {
+        return None;
+      }
+      let VersionedAst { root, lines, .. } = &mut *versioned;
+      apply_changes(root, lines, changes);
+      versioned.version = text_doc.version;
+    }
     self
       .client
-      .log_message(MessageType::LOG, "Parsing changed doc.")
+      .log_message(MessageType::LOG, "Parsed changed doc.")
       .await;
-    let lang = Self::infer_lang_from_uri(&text_doc.uri)?;
-    let root = AstGrep::new(text, lang);
-    let mut versioned = self.map.get_mut(uri)?;
-    // skip old version update
-    if versioned.version > text_doc.version {
+    self
+      .schedule_diagnostics(text_doc.uri, text_doc.version)
+      .await
+  }
+
+  /// Publish diagnostics of the version once edits pause for the configured delay.
+  /// A newer change cancels the diagnostics, either waiting or running, and schedules
+  /// its own, so only diagnostics of the latest version are published.
+  async fn schedule_diagnostics(&self, uri: Url, version: i32) -> Option<()> {
+    let cancellation = Arc::new(Cancellation::default());
+    let last = self
+      .diagnosing
+      .insert(uri.to_string(), cancellation.clone());
+    if let Some(last) = last {
+      last.cancel();
+    }
+    let settings = self.settings.read().ok()?.clone();
+    if cancellation.wait(settings.diagnostics_delay).await {
+      return None;
+    }
+    let root = self.map.get(uri.as_str())?.root.clone();
+    let project = self.project_of(Some(&uri));
+    let (task_uri, task_cancellation) = (uri.clone(), cancellation.clone());
+    // scan off the async runtime, so newer changes are received and cancel the scan
+    let diagnostics = tokio::task::spawn_blocking(move || {
+      let cancelled = task_cancellation.flag();
+      let project = project?;
+      diagnose(&project, &settings, &task_uri, &root, cancelled)
+    })
+    .await
+    .ok()?;
+    self
+      .diagnosing
+      .remove_if(uri.as_str(), |_, c| Arc::ptr_eq(c, &cancellation));
+    // results of older versions are dropped
+    let is_latest = self
+      .map
+      .get(uri.as_str())
+      .map_or(false, |versioned| versioned.version == version);
+    if cancellation.is_cancelled() || !is_latest {
       return None;
     }
-    *versioned = VersionedAst {
-      version: text_doc.version,
-      root,
-    };
     self
       .client
       .log_message(MessageType::LOG, "Publishing diagnostics.")
       .await;
-    self.publish_diagnostics(text_doc.uri, &versioned).await;
+    let diagnostics = diagnostics.unwrap_or_default();
+    self
+      .client
+      .publish_diagnostics(uri, diagnostics, Some(version))
+      .await;
     Some(())
   }
   async fn on_close(&self, params: DidCloseTextDocumentParams) {
-    self.map.remove(params.text_document.uri.as_str());
+    let uri = params.text_document.uri;
+    if let Some((_, diagnosing)) = self.diagnosing.remove(uri.as_str()) {
+      diagnosing.cancel();
+    }
+    self.map.remove(uri.as_str());
+    self.rule_docs.remove(uri.as_str());
+    // untitled documents are gone once closed, e.g. saved to files and reopened by paths
+    if uri.to_file_path().is_err() {
+      self.client.publish_diagnostics(uri, vec![], None).await;
+    }
   }
 
   fn compute_all_fixes(
@@ -313,26 +1057,10 @@ impl<L: LSPLang> Backend<L> {
       .map
       .get(uri.as_str())
       .ok_or(LspError::UnsupportedFileType)?;
-    let mut diagnostics = self
-      .get_diagnostics(&uri, &versioned)
+    let diagnostics = self
+      .get_diagnostics(&uri, &versioned.root)
       .ok_or(LspError::NoActionableFix)?;
-    diagnostics.sort_by_key(|d| (d.range.start, d.range.end));
-    let mut last = Position {
-      line: 0,
-      character: 0,
-    };
-    let edits: Vec<_> = diagnostics
-      .into_iter()
-      .filter_map(|d| {
-        if d.range.start < last {
-          return None;
-        }
-        let rewrite_data = RewriteData::from_value(d.data?)?;
-        let edit = TextEdit::new(d.range, rewrite_data.fixed);
-        last = d.range.end;
-        Some(edit)
-      })
-      .collect();
+    let edits = merge_fixes(diagnostics);
     if edits.is_empty() {
       return Err(LspError::NoActionableFix);
     }
@@ -342,12 +1070,16 @@ impl<L: LSPLang> Backend<L> {
   }
 
   async fn on_code_action(&self, params: CodeActionParams) -> Option<CodeActionResponse> {
-    if let Some(kinds) = params.context.only.as_ref() {
-      if kinds.contains(&CodeActionKind::SOURCE_FIX_ALL) {
-        return self.fix_all_code_action(params.text_document);
-      }
+    let Some(only) = params.context.only.as_ref() else {
+      return self.quickfix_code_action(params);
+    };
+    if requests_kind(only, FIX_ALL_AST_GREP) {
+      self.fix_all_code_action(params.text_document)
+    } else if requests_kind(only, QUICKFIX_AST_GREP) {
+      self.quickfix_code_action(params)
+    } else {
+      None
     }
-    self.quickfix_code_action(params)
   }
 
   fn fix_all_code_action(
@@ -374,7 +1106,10 @@ impl<L: LSPLang> Backend<L> {
       return None;
     }
     let text_doc = params.text_document;
-    let response = params
+    let range = params.range;
+    let versioned = self.map.get(text_doc.uri.as_str());
+    let mut response = vec![];
+    let diagnostics = params
       .context
       .diagnostics
       .into_iter()
@@ -384,30 +1119,126 @@ impl<L: LSPLang> Backend<L> {
           .map(|s| s.contains("ast-grep"))
           .unwrap_or(false)
       })
-      .filter_map(|d| diagnostic_to_code_action(&text_doc, d))
-      .map(CodeActionOrCommand::from)
-      .collect();
+      .filter(|d| d.range.start <= range.end && range.start <= d.range.end);
+    for diagnostic in diagnostics {
+      // suppression is offered after the fix
+      let suppressions = versioned.as_ref().map(|versioned| {
+        let root = &versioned.root;
+        suppression_code_actions(&text_doc, root.source(), root.lang(), &diagnostic)
+      });
+      let goto_rule = self.goto_rule_code_action(&text_doc.uri, &diagnostic);
+      if let Some(action) = diagnostic_to_code_action(&text_doc, diagnostic) {
+        response.push(CodeActionOrCommand::from(action));
+      }
+      let suppressions = suppressions.into_iter().flatten();
+      response.extend(suppressions.map(CodeActionOrCommand::from));
+      response.extend(goto_rule.map(CodeActionOrCommand::from));
+    }
     Some(response)
   }
 
+  /// Open the file defining the rule by the `ast-grep.gotoRule` command. The document is
+  /// passed too, as rule ids are unique only in the project of a workspace folder.
+  fn goto_rule_code_action(&self, uri: &Url, diagnostic: &Diagnostic) -> Option<CodeAction> {
+    let Some(NumberOrString::String(id)) = &diagnostic.code else {
+      return None;
+    };
+    self.rule_path(id, Some(uri))?;
+    let title = format!("Open the definition of `{id}`");
+    let command = Command {
+      title: title.clone(),
+      command: GOTO_RULE.into(),
+      arguments: Some(vec![
+        Value::String(id.clone()),
+        Value::String(uri.to_string()),
+      ]),
+    };
+    Some(CodeAction {
+      title,
+      command: Some(command),
+      diagnostics: Some(vec![diagnostic.clone()]),
+      edit: None,
+      kind: Some(CodeActionKind::QUICKFIX),
+      is_preferred: Some(false),
+      data: None,
+      disabled: None,
+    })
+  }
+
+  /// The file defining the rule. Rules not loaded from rule files, like inline rules,
+  /// fall back to the project configuration file.
+  fn rule_path(&self, id: &str, uri: Option<&Url>) -> Option<PathBuf> {
+    let project = self.project_of(uri)?;
+    let path = project.rule_paths.get(id).cloned();
+    let path = path.or_else(|| {
+      self.loader.as_ref()?;
+      let config_path = project.config_path.clone();
+      config_path.or_else(|| Some(project.base.join("sgconfig.yml")))
+    })?;
+    if path.is_absolute() {
+      Some(path)
+    } else {
+      Some(std::env::current_dir().ok()?.join(path))
+    }
+  }
+
+  /// Show the `id` key of the rule in its file, and return the location for clients
+  /// that cannot show documents requested by the server.
+  async fn on_goto_rule(&self, arguments: Vec<Value>) -> Option<Value> {
+    let id = arguments.first()?.as_str()?;
+    let uri = arguments
+      .get(1)
+      .and_then(|uri| Url::parse(uri.as_str()?).ok());
+    let Some(path) = self.rule_path(id, uri.as_ref()) else {
+      let message = format!("Cannot find the definition of rule `{id}`.");
+      self
+        .client
+        .show_message(MessageType::WARNING, message)
+        .await;
+      return None;
+    };
+    let yaml = std::fs::read_to_string(&path).unwrap_or_default();
+    let range = rule_id_range(&yaml, id).unwrap_or_default();
+    let location = Location::new(Url::from_file_path(&path).ok()?, range);
+    if self.workspace.can_show_document.load(Ordering::Relaxed) {
+      let params = ShowDocumentParams {
+        uri: location.uri.clone(),
+        external: None,
+        take_focus: Some(true),
+        selection: Some(range),
+      };
+      if let Err(e) = self.client.show_document(params).await {
+        let message = format!("Cannot show the rule file: {e}");
+        self.client.log_message(MessageType::WARNING, message).await;
+      }
+    }
+    serde_json::to_value(location).ok()
+  }
+
   // TODO: support other urls besides file_scheme
-  fn infer_lang_from_uri(uri: &Url) -> Option<L> {
-    let path = uri.to_file_path().ok()?;
-    L::from_path(path)
+  fn lang_from_id(&self, language_id: &str) -> Option<L> {
+    let settings = self.settings.read().ok()?;
+    L::from_str(settings.language_name(language_id)).ok()
   }
 
   async fn on_execute_command(&self, params: ExecuteCommandParams) -> Option<Value> {
     let ExecuteCommandParams {
       arguments,
       command,
-      work_done_progress_params: _,
+      work_done_progress_params,
     } = params;
 
     match command.as_ref() {
-      APPLY_ALL_FIXES => {
-        self.on_apply_all_fix(command, arguments).await?;
+      APPLY_ALL_FIXES => self.on_apply_all_fix(command, arguments).await,
+      SCAN_WORKSPACE => {
+        let token = work_done_progress_params.work_done_token;
+        self.on_scan_workspace(token).await
+      }
+      CLEAR_WORKSPACE_DIAGNOSTICS => {
+        self.clear_workspace_diagnostics().await;
         None
       }
+      GOTO_RULE => self.on_goto_rule(arguments).await,
       _ => {
         self
           .client
@@ -438,7 +1269,8 @@ impl<L: LSPLang> Backend<L> {
     Ok(workspace_edit)
   }
 
-  async fn on_apply_all_fix(&self, command: String, arguments: Vec<Value>) -> Option<()> {
+  /// Fix the document in the arguments, or every file in the workspace without arguments.
+  async fn on_apply_all_fix(&self, command: String, arguments: Vec<Value>) -> Option<Value> {
     self
       .client
       .log_message(
@@ -446,7 +1278,9 @@ impl<L: LSPLang> Backend<L> {
         format!("Running ExecuteCommand {}", command),
       )
       .await;
-    let first = arguments.first()?.clone();
+    let Some(first) = arguments.first().cloned() else {
+      return self.on_apply_workspace_fixes().await;
+    };
     let workspace_edit = match self.on_apply_all_fix_impl(first).await {
       Ok(workspace_edit) => workspace_edit,
       Err(error) => {
@@ -458,6 +1292,226 @@ impl<L: LSPLang> Backend<L> {
     None
   }
 
+  /// Fix every file in the workspace by one workspace edit.
+  /// Open documents are fixed by their text in the editor and carry their versions.
+  async fn on_apply_workspace_fixes(&self) -> Option<Value> {
+    let files = self.workspace_files("fix workspace").await?;
+    let mut fixes = vec![];
+    for path in &files {
+      fixes.extend(self.fix_file(path).await);
+    }
+    if fixes.is_empty() {
+      let message = "No fixable issues found in the workspace.";
+      self.client.show_message(MessageType::INFO, message).await;
+      return Some(serde_json::json!({ "applied": false, "edits": 0, "files": 0 }));
+    }
+    let file_count = fixes.len();
+    let edit_count: usize = fixes.iter().map(|(_, _, edits)| edits.len()).sum();
+    let workspace_edit = if self.workspace.can_edit_documents.load(Ordering::Relaxed) {
+      let edits = fixes
+        .into_iter()
+        .map(|(uri, version, edits)| TextDocumentEdit {
+          text_document: OptionalVersionedTextDocumentIdentifier { uri, version },
+          edits: edits.into_iter().map(OneOf::Left).collect(),
+        })
+        .collect();
+      WorkspaceEdit {
+        changes: None,
+        document_changes: Some(DocumentChanges::Edits(edits)),
+        change_annotations: None,
+      }
+    } else {
+      let changes = fixes
+        .into_iter()
+        .map(|(uri, _, edits)| (uri, edits))
+        .collect();
+      WorkspaceEdit::new(changes)
+    };
+    let response = self.client.apply_edit(workspace_edit).await;
+    let (applied, reason) = match response {
+      Ok(response) => (response.applied, response.failure_reason),
+      Err(error) => (false, Some(error.message.to_string())),
+    };
+    if applied {
+      let message = format!("Applied {edit_count} fix(es) in {file_count} file(s).");
+      self.client.show_message(MessageType::INFO, message).await;
+    } else {
+      let reason = reason.map(|r| format!("\n{r}")).unwrap_or_default();
+      let message =
+        format!("The editor rejected {edit_count} fix(es) in {file_count} file(s).{reason}");
+      self
+        .client
+        .show_message(MessageType::WARNING, message)
+        .await;
+    }
+    Some(serde_json::json!({
+      "applied": applied,
+      "edits": edit_count,
+      "files": file_count,
+    }))
+  }
+
+  /// Compute the fixes of a file, and the version of it if the file is open.
+  async fn fix_file(&self, path: &Path) -> Option<(Url, Option<i32>, Vec<TextEdit>)> {
+    let uri = Url::from_file_path(path).ok()?;
+    let open = self.map.get(uri.as_str()).map(|versioned| {
+      let diagnostics = self.get_diagnostics(&uri, &versioned.root);
+      (versioned.version, diagnostics)
+    });
+    let (version, diagnostics) = match open {
+      Some((version, diagnostics)) => (Some(version), diagnostics?),
+      None => (None, self.scan_file(path, Arc::default()).await?.1),
+    };
+    let edits = merge_fixes(diagnostics);
+    if edits.is_empty() {
+      return None;
+    }
+    Some((uri, version, edits))
+  }
+
+  /// List the files in the workspace folders, or under the base directory if there is none.
+  /// The action is reported to the user if the files cannot be listed or rules are broken.
+  async fn workspace_files(&self, action: &str) -> Option<Vec<PathBuf>> {
+    let (walker, folders) = self.workspace_folders(action).await?;
+    Some(walk(walker, folders, Arc::default()).await)
+  }
+
+  async fn workspace_folders(&self, action: &str) -> Option<(Arc<FileWalker>, Vec<PathBuf>)> {
+    let Some(walker) = self.workspace.walker.clone() else {
+      let message = "Workspace commands are not supported by this server.";
+      self
+        .client
+        .show_message(MessageType::WARNING, message)
+        .await;
+      return None;
+    };
+    let rule_error = {
+      let projects = self.projects.read().ok()?;
+      let errors: Option<Vec<_>> = projects.iter().map(|p| p.rules.as_ref().err()).collect();
+      // a workspace folder with broken rules does not stop scanning the others
+      errors.and_then(|errors| errors.first().map(|e| e.to_string()))
+    };
+    if let Some(error) = rule_error {
+      let message = format!("Cannot {action}: {error}");
+      self.client.show_message(MessageType::ERROR, message).await;
+      return None;
+    }
+    let mut folders = match self.client.workspace_folders().await {
+      Ok(Some(folders)) => folders
+        .into_iter()
+        .filter_map(|folder| folder.uri.to_file_path().ok())
+        .collect(),
+      _ => vec![],
+    };
+    if folders.is_empty() {
+      let projects = self.projects.read().ok()?;
+      folders.extend(projects.iter().map(|project| project.base.clone()));
+    }
+    Some((walker, folders))
+  }
+
+  /// Diagnose every file in the workspace folders and publish diagnostics of files with issues.
+  /// Files with issues found by the last scan but clean now are cleared. If the client cancels
+  /// the progress, the walk and the scan stop and files not scanned keep their diagnostics.
+  async fn on_scan_workspace(&self, token: Option<ProgressToken>) -> Option<Value> {
+    let (walker, folders) = self.workspace_folders("scan workspace").await?;
+    let mut progress = self.progress.begin(token, "Scanning workspace", true).await;
+    let files = walk(walker, folders, progress.cancellation()).await;
+    let mut published = HashSet::new();
+    let mut issue_count = 0;
+    let mut scanned = 0;
+    for path in &files {
+      if progress.is_cancelled() {
+        break;
+      }
+      self
+        .progress
+        .report(&mut progress, scanned, files.len())
+        .await;
+      scanned += 1;
+      let Some((uri, diagnostics)) = self.scan_file(path, progress.cancellation()).await else {
+        continue;
+      };
+      if diagnostics.is_empty() {
+        continue;
+      }
+      issue_count += diagnostics.len();
+      self
+        .client
+        .publish_diagnostics(uri.clone(), diagnostics, None)
+        .await;
+      published.insert(uri);
+    }
+    let cancelled = progress.is_cancelled();
+    let stale: Vec<_> = {
+      let mut last_published = self.workspace.published.lock().ok()?;
+      if cancelled {
+        last_published.extend(published.iter().cloned());
+        vec![]
+      } else {
+        let last_published = std::mem::replace(&mut *last_published, published.clone());
+        last_published.difference(&published).cloned().collect()
+      }
+    };
+    for uri in stale {
+      // open documents keep their live diagnostics
+      if !self.map.contains_key(uri.as_str()) {
+        self.client.publish_diagnostics(uri, vec![], None).await;
+      }
+    }
+    let found = format!(
+      "Found {issue_count} issue(s) in {} file(s).",
+      published.len()
+    );
+    let summary = if cancelled {
+      format!("Workspace scan is cancelled. {found}")
+    } else {
+      found
+    };
+    self.progress.end(progress, summary).await;
+    Some(serde_json::json!({
+      "scannedFiles": scanned,
+      "filesWithIssues": published.len(),
+      "issues": issue_count,
+      "cancelled": cancelled,
+    }))
+  }
+
+  /// Diagnose a file on disk. Open documents are skipped because they are diagnosed on change.
+  /// The file is read and scanned off the async runtime, so cancellation is handled meanwhile.
+  async fn scan_file(
+    &self,
+    path: &Path,
+    cancelled: Arc<AtomicBool>,
+  ) -> Option<(Url, Vec<Diagnostic>)> {
+    let uri = Url::from_file_path(path).ok()?;
+    if self.map.contains_key(uri.as_str()) {
+      return None;
+    }
+    let lang = L::from_path(path)?;
+    let project = self.project_of(Some(&uri))?;
+    let settings = self.settings.read().ok()?.clone();
+    let (path, task_uri) = (path.to_path_buf(), uri.clone());
+    let diagnostics = tokio::task::spawn_blocking(move || {
+      let text = std::fs::read_to_string(path).ok()?;
+      let root = AstGrep::new(text, lang);
+      diagnose(&project, &settings, &task_uri, &root, &cancelled)
+    })
+    .await
+    .ok()??;
+    Some((uri, diagnostics))
+  }
+
+  async fn clear_workspace_diagnostics(&self) {
+    let published = std::mem::take(&mut *self.workspace.published.lock().expect("should lock"));
+    for uri in published {
+      // open documents keep their live diagnostics
+      if !self.map.contains_key(uri.as_str()) {
+        self.client.publish_diagnostics(uri, vec![], None).await;
+      }
+    }
+  }
+
   async fn report_error(&self, error: LspError) {
     match error {
       LspError::JSONDecodeError(e) => {
@@ -485,6 +1539,198 @@ impl<L: LSPLang> Backend<L> {
   }
 }
 
+/// Whether the kinds in `only` include the kind itself or one of its parent kinds,
+/// e.g. both `source` and `source.fixAll` request `source.fixAll.ast-grep`.
+fn requests_kind(only: &[CodeActionKind], kind: &str) -> bool {
+  only.iter().any(|requested| {
+    let requested = requested.as_str();
+    kind == requested
+      || kind
+        .strip_prefix(requested)
+        .map_or(false, |rest| rest.starts_with('.'))
+  })
+}
+
+/// The first workspace folder, or the deprecated root uri.
+fn workspace_root(params: &InitializeParams) -> Option<PathBuf> {
+  #[allow(deprecated)]
+  params
+    .workspace_folders
+    .as_ref()
+    .and_then(|folders| folders.first())
+    .map(|folder| &folder.uri)
+    .or(params.root_uri.as_ref())
+    .and_then(|uri| uri.to_file_path().ok())
+}
+
+/// Diagnostics of the document sorted by position, `None` if it is not diagnosed or cancelled.
+fn diagnose<L: LSPLang>(
+  project: &Project<L>,
+  settings: &Settings,
+  uri: &Url,
+  root: &AstGrep<StrDoc<L>>,
+  cancelled: &AtomicBool,
+) -> Option<Vec<Diagnostic>> {
+  let mut diagnostics = vec![];
+  let index = LineIndex::new(root.source());
+  scan_document(
+    project,
+    settings,
+    uri,
+    root,
+    cancelled,
+    |m, rule, severity| {
+      diagnostics.push(convert_match_to_diagnostic(m, rule, severity, &index));
+    },
+  )?;
+  // publish by position, the sort is stable so rule ids break ties
+  diagnostics.sort_by_key(|d| (d.range.start, d.range.end));
+  Some(diagnostics)
+}
+
+/// Scan the document with the project rules applying to its path and visit every match
+/// with the rule severity changed by the settings. Rules turned off are skipped.
+/// The flag is checked between batches of rules, and the scan stops once it is set.
+fn scan_document<L: LSPLang>(
+  project: &Project<L>,
+  settings: &Settings,
+  uri: &Url,
+  root: &AstGrep<StrDoc<L>>,
+  cancelled: &AtomicBool,
+  mut visit: impl FnMut(NodeMatch<StrDoc<L>>, &RuleConfig<L>, &Severity),
+) -> Option<()> {
+  let rules = project.rules.as_ref().ok()?;
+  let relative_path = uri
+    .to_file_path()
+    .ok()
+    .and_then(|path| Some(path.strip_prefix(&project.base).ok()?.to_path_buf()));
+  let rules = match relative_path {
+    // for_path needs relative path, see https://github.com/ast-grep/ast-grep/issues/1272
+    Some(p) => rules.for_path(&p),
+    // `files` and `ignores` globs are relative to the project root and never match
+    // documents outside of it or untitled documents without paths:
+    // rules scoped by `files` are skipped, the others apply
+    None => {
+      let lang = root.lang();
+      let unscoped = rules.iter().filter(|r| r.files.is_none());
+      unscoped.filter(|r| r.language == *lang).collect()
+    }
+  };
+  let mut rules: Vec<_> = rules
+    .into_iter()
+    .filter(|r| settings.is_rule_enabled(&r.id))
+    .collect();
+  // batches in the order of rule ids, so matches are visited by rule ids
+  rules.sort_by(|a, b| a.id.cmp(&b.id));
+  for batch in rules.chunks(RULES_PER_BATCH) {
+    if cancelled.load(Ordering::SeqCst) {
+      return None;
+    }
+    let scan = CombinedScan::new(batch.to_vec());
+    let pre_scan = scan.find(root);
+    let mut matches: Vec<_> = scan
+      .scan(root, pre_scan, false)
+      .matches
+      .into_iter()
+      .collect();
+    // matches are grouped by rules in arbitrary order
+    matches.sort_by(|(a, _), (b, _)| scan.get_rule(*a).id.cmp(&scan.get_rule(*b).id));
+    for (id, ms) in matches {
+      let rule = scan.get_rule(id);
+      let severity = settings.severity.find(&rule.id).unwrap_or(&rule.severity);
+      if matches!(severity, Severity::Off) {
+        continue;
+      }
+      for m in ms {
+        visit(m, rule, severity);
+      }
+    }
+  }
+  Some(())
+}
+
+/// Walk the folders off the async runtime, so messages like progress cancellation are
+/// handled meanwhile.
+async fn walk(
+  walker: Arc<FileWalker>,
+  folders: Vec<PathBuf>,
+  cancelled: Arc<AtomicBool>,
+) -> Vec<PathBuf> {
+  tokio::task::spawn_blocking(move || walker(&folders, &cancelled))
+    .await
+    .unwrap_or_default()
+}
+
+/// Count rules of loaded projects, `None` if none is loaded.
+fn loaded_summary<L: LSPLang>(projects: &[Arc<Project<L>>]) -> Option<String> {
+  let counts: Vec<_> = projects
+    .iter()
+    .filter_map(|project| project.rules.as_ref().ok())
+    .map(|rules| rules.total_rule_count())
+    .collect();
+  let count: usize = counts.iter().sum();
+  if counts.is_empty() {
+    None
+  } else if projects.iter().any(|project| project.folder.is_some()) {
+    let folders = counts.len();
+    Some(format!(
+      "Loaded {count} ast-grep rule(s) in {folders} workspace folder(s)."
+    ))
+  } else {
+    Some(format!("Loaded {count} ast-grep rule(s)."))
+  }
+}
+
+/// Describe rule files of the project that fail to load.
+fn broken_warning<L: LSPLang>(project: &ProjectRules<L>, label: &str) -> Option<String> {
+  if project.broken_files.is_empty() {
+    return None;
+  }
+  let broken: Vec<_> = project.broken_files.iter().collect();
+  let header = format!("Failed to load {} rule file(s){label}:", broken.len());
+  Some(header + &describe_files(&broken))
+}
+
+/// List files and their errors, one per line.
+fn describe_files(files: &[&(PathBuf, String)]) -> String {
+  let lines = files
+    .iter()
+    .map(|(path, error)| format!("\n{}: {error}", path.display()));
+  lines.collect()
+}
+
+/// Text edits of the fixes in diagnostics, skipping fixes that overlap with an earlier one.
+fn merge_fixes(diagnostics: Vec<Diagnostic>) -> Vec<TextEdit> {
+  let mut fixes: Vec<_> = diagnostics
+    .into_iter()
+    .filter_map(|d| {
+      let NumberOrString::String(id) = d.code? else {
+        return None;
+      };
+      Some((RewriteData::from_value(d.data?)?, id))
+    })
+    .collect();
+  // the first fix by position wins, ties are broken by rule id to be deterministic
+  fixes.sort_by(|(a, a_id), (b, b_id)| {
+    let a_key = (a.range.start, a.range.end, a_id);
+    a_key.cmp(&(b.range.start, b.range.end, b_id))
+  });
+  let mut last = Position {
+    line: 0,
+    character: 0,
+  };
+  let mut edits = vec![];
+  for (rewrite_data, _) in fixes {
+    // skip fixes conflicting with an applied one
+    if rewrite_data.range.start < last {
+      continue;
+    }
+    last = rewrite_data.range.end;
+    edits.push(rewrite_data.into_text_edit());
+  }
+  edits
+}
+
 enum LspError {
   JSONDecodeError(serde_json::Error),
   UnsupportedFileType,
@@ -0,0 +1,167 @@
+//! Work done progress of long operations, like workspace scans and rule reloads.
+//! Clients without work done progress get a message when the operation ends instead.
+use tower_lsp::lsp_types::*;
+use tower_lsp::Client;
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+pub struct ProgressReporter {
+  client: Client,
+  /// whether the client accepts progress created by the server
+  can_create: AtomicBool,
+  /// cancellation flags of ongoing operations keyed by their tokens
+  ongoing: Mutex<HashMap<ProgressToken, Arc<AtomicBool>>>,
+  /// makes tokens created by the server unique
+  next_id: AtomicUsize,
+}
+
+/// An ongoing operation. Without token, the progress is only reported when it ends.
+pub struct Progress {
+  token: Option<ProgressToken>,
+  cancelled: Arc<AtomicBool>,
+  last_percentage: u32,
+}
+
+impl Progress {
+  pub fn is_cancelled(&self) -> bool {
+    self.cancelled.load(Ordering::Relaxed)
+  }
+
+  /// The flag set when the client cancels the progress, for work off the async runtime.
+  pub fn cancellation(&self) -> Arc<AtomicBool> {
+    self.cancelled.clone()
+  }
+}
+
+impl ProgressReporter {
+  pub fn new(client: Client) -> Self {
+    Self {
+      client,
+      can_create: AtomicBool::new(false),
+      ongoing: Mutex::new(HashMap::new()),
+      next_id: AtomicUsize::new(0),
+    }
+  }
+
+  pub fn set_can_create(&self, can_create: bool) {
+    self.can_create.store(can_create, Ordering::Relaxed);
+  }
+
+  /// Begin progress with the token from the client, or create one if the client supports it.
+  pub async fn begin(
+    &self,
+    token: Option<ProgressToken>,
+    title: &str,
+    cancellable: bool,
+  ) -> Progress {
+    let token = match token {
+      Some(token) => Some(token),
+      None if self.can_create.load(Ordering::Relaxed) => self.create().await,
+      None => None,
+    };
+    let progress = Progress {
+      token,
+      cancelled: Arc::new(AtomicBool::new(false)),
+      last_percentage: 0,
+    };
+    let Some(token) = &progress.token else {
+      return progress;
+    };
+    if cancellable {
+      let mut ongoing = self.ongoing.lock().expect("should lock");
+      ongoing.insert(token.clone(), progress.cancelled.clone());
+    }
+    let begin = WorkDoneProgressBegin {
+      title: title.into(),
+      cancellable: Some(cancellable),
+      message: None,
+      percentage: Some(0),
+    };
+    self.notify(token, WorkDoneProgress::Begin(begin)).await;
+    progress
+  }
+
+  async fn create(&self) -> Option<ProgressToken> {
+    let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+    let token = ProgressToken::String(format!("ast-grep/{id}"));
+    let params = WorkDoneProgressCreateParams {
+      token: token.clone(),
+    };
+    let created = self
+      .client
+      .send_request::<request::WorkDoneProgressCreate>(params)
+      .await;
+    created.ok().map(|_| token)
+  }
+
+  /// Report `done` of `total` items, only when the percentage grows to avoid flooding.
+  pub async fn report(&self, progress: &mut Progress, done: usize, total: usize) {
+    let percentage = (done * 100 / total.max(1)) as u32;
+    let Some(token) = progress
+      .token
+      .as_ref()
+      .filter(|_| percentage > progress.last_percentage)
+    else {
+      return;
+    };
+    progress.last_percentage = percentage;
+    let report = WorkDoneProgressReport {
+      cancellable: None,
+      message: Some(format!("{done}/{total} files")),
+      percentage: Some(percentage),
+    };
+    self.notify(token, WorkDoneProgress::Report(report)).await;
+  }
+
+  /// End the progress with the summary, or show the summary if there is no progress token.
+  pub async fn end(&self, progress: Progress, summary: String) {
+    match &progress.token {
+      Some(token) => {
+        self.ongoing.lock().expect("should lock").remove(token);
+        let end = WorkDoneProgressEnd {
+          message: Some(summary.clone()),
+        };
+        self.notify(token, WorkDoneProgress::End(end)).await;
+      }
+      None => {
+        self
+          .client
+          .show_message(MessageType::INFO, summary.clone())
+          .await;
+      }
+    }
+    self.client.log_message(MessageType::INFO, summary).await;
+  }
+
+  /// End the progress of a failed operation. The failure is reported on its own, so
+  /// clients without progress get no summary.
+  pub async fn fail(&self, progress: Progress, reason: String) {
+    if let Some(token) = &progress.token {
+      self.ongoing.lock().expect("should lock").remove(token);
+      let end = WorkDoneProgressEnd {
+        message: Some(reason),
+      };
+      self.notify(token, WorkDoneProgress::End(end)).await;
+    }
+  }
+
+  /// Handle `window/workDoneProgress/cancel` from the client.
+  pub fn cancel(&self, token: &ProgressToken) {
+    if let Some(cancelled) = self.ongoing.lock().expect("should lock").get(token) {
+      cancelled.store(true, Ordering::Relaxed);
+    }
+  }
+
+  async fn notify(&self, token: &ProgressToken, progress: WorkDoneProgress) {
+    let params = ProgressParams {
+      token: token.clone(),
+      value: ProgressParamsValue::WorkDone(progress),
+    };
+    self
+      .client
+      .send_notification::<notification::Progress>(params)
+      .await;
+  }
+}
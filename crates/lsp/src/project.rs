@@ -0,0 +1,112 @@
+//! Projects of the workspace. Every workspace folder with its own `sgconfig.yml` is a
+//! project, and documents are diagnosed by the rules of the folder containing them only.
+//! Without such folders, a single project applies to the whole workspace.
+use crate::{LSPLang, ProjectRules, RuleChecker};
+use ast_grep_config::RuleCollection;
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// The configuration file looked up in the root of every workspace folder.
+const CONFIG_FILE: &str = "sgconfig.yml";
+
+pub struct Project<L: LSPLang> {
+  /// the workspace folder of the project, `None` if it applies to the whole workspace
+  pub folder: Option<PathBuf>,
+  /// the configuration path the project is loaded from, `None` to discover it
+  pub config_path: Option<PathBuf>,
+  /// the directory of the project configuration, rule globs are relative to it
+  pub base: PathBuf,
+  pub rules: Result<RuleCollection<L>, String>,
+  /// files defining the rules, see [`ProjectRules::rule_paths`]
+  pub rule_paths: HashMap<String, PathBuf>,
+  pub rule_checker: Option<RuleChecker>,
+  /// rule files that failed to load, the last good rules are kept if more files break
+  pub broken_files: Vec<PathBuf>,
+}
+
+impl<L: LSPLang> Project<L> {
+  pub fn loaded(source: ProjectSource, project: ProjectRules<L>) -> Self {
+    let (folder, config_path) = source;
+    Self {
+      folder,
+      config_path,
+      base: project.base,
+      rules: Ok(project.rules),
+      rule_paths: project.rule_paths,
+      rule_checker: project.rule_checker,
+      broken_files: project.broken_files.into_iter().map(|(p, _)| p).collect(),
+    }
+  }
+
+  pub fn failed(source: ProjectSource, base: PathBuf, error: String) -> Self {
+    let (folder, config_path) = source;
+    Self {
+      folder,
+      config_path,
+      base,
+      rules: Err(error),
+      rule_paths: HashMap::new(),
+      rule_checker: None,
+      broken_files: vec![],
+    }
+  }
+
+  pub fn source(&self) -> ProjectSource {
+    (self.folder.clone(), self.config_path.clone())
+  }
+}
+
+/// The workspace folder and the configuration path to load a project from.
+pub type ProjectSource = (Option<PathBuf>, Option<PathBuf>);
+
+/// The configuration path from the command line or settings applies to the whole workspace.
+/// Otherwise every workspace folder with its own configuration is a project, and if there
+/// is none the loader discovers the configuration like `sg scan`.
+pub fn project_sources(config_path: Option<PathBuf>, folders: &[PathBuf]) -> Vec<ProjectSource> {
+  if config_path.is_none() {
+    let sources: Vec<_> = folders
+      .iter()
+      .map(|folder| (folder, folder.join(CONFIG_FILE)))
+      .filter(|(_, config)| config.exists())
+      .map(|(folder, config)| (Some(folder.clone()), Some(config)))
+      .collect();
+    if !sources.is_empty() {
+      return sources;
+    }
+  }
+  vec![(None, config_path)]
+}
+
+/// Find the project of a document. The innermost folder containing the document wins.
+/// Documents in workspace folders without their own configuration have no project, and
+/// documents outside every folder, like untitled ones, fall back to the first project.
+pub fn find_project<'a, L: LSPLang>(
+  projects: &'a [Arc<Project<L>>],
+  folders: &[PathBuf],
+  path: Option<&Path>,
+) -> Option<&'a Arc<Project<L>>> {
+  let Some(path) = path else {
+    return projects.first();
+  };
+  let owner = projects
+    .iter()
+    .filter(|p| p.folder.as_ref().map_or(false, |f| path.starts_with(f)))
+    .max_by_key(|p| p.folder.as_ref().map_or(0, |f| f.components().count()));
+  if owner.is_some() {
+    return owner;
+  }
+  let per_folder = projects.iter().all(|p| p.folder.is_some());
+  if per_folder && folders.iter().any(|f| path.starts_with(f)) {
+    return None;
+  }
+  projects.first()
+}
+
+pub fn folder_label(folder: &Option<PathBuf>) -> String {
+  match folder {
+    Some(folder) => format!(" in {}", folder.display()),
+    None => String::new(),
+  }
+}
@@ -0,0 +1,115 @@
+//! Client settings from `initializationOptions`, `workspace/didChangeConfiguration`
+//! or the `workspace/configuration` request.
+use crate::severity::SeverityOverrides;
+use regex::Regex;
+use serde_json::Value;
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// vscode language ids that are not ast-grep language names or aliases.
+/// Other ids, like `typescript` or `python`, are ast-grep language names themselves.
+const LANGUAGE_IDS: &[(&str, &str)] = &[
+  ("javascriptreact", "javascript"),
+  ("jsonc", "json"),
+  ("shellscript", "bash"),
+  ("typescriptreact", "tsx"),
+];
+
+/// Diagnostics are computed once edits pause for this long, unless configured.
+const DIAGNOSTICS_DELAY: Duration = Duration::from_millis(200);
+
+/// Settings may be nested in the section, e.g. `{"astGrep": {"configPath": "sgconfig.yml"}}`.
+pub const SETTINGS_SECTION: &str = "astGrep";
+
+pub struct Settings {
+  /// `configPath`, relative paths are resolved against the workspace root
+  pub config_path: Option<PathBuf>,
+  /// `severity`, see [`SeverityOverrides`]
+  pub severity: SeverityOverrides,
+  /// `ruleFilter`, only rules whose ids match the regex run, like `sg scan --filter`
+  pub rule_filter: Option<Regex>,
+  /// `languageIds`, ast-grep languages of client language ids, e.g. `{"vue": "html"}`.
+  /// They are used for documents without file paths like untitled documents.
+  pub language_ids: HashMap<String, String>,
+  /// `diagnosticsDelay` in milliseconds, how long edits should pause before diagnosing
+  pub diagnostics_delay: Duration,
+}
+
+impl Default for Settings {
+  fn default() -> Self {
+    Self {
+      config_path: None,
+      severity: SeverityOverrides::default(),
+      rule_filter: None,
+      language_ids: HashMap::new(),
+      diagnostics_delay: DIAGNOSTICS_DELAY,
+    }
+  }
+}
+
+impl Settings {
+  /// Missing settings mean defaults. Unknown fields are ignored since clients may send
+  /// settings of other features.
+  pub fn parse(settings: Option<&Value>, root: Option<&Path>) -> Result<Self, String> {
+    let settings = settings
+      .map(|s| s.get(SETTINGS_SECTION).unwrap_or(s))
+      .filter(|s| !s.is_null());
+    let Some(settings) = settings else {
+      return Ok(Self::default());
+    };
+    let config_path = string_field(settings, "configPath")?.map(|path| match root {
+      Some(root) => root.join(path),
+      None => PathBuf::from(path),
+    });
+    let severity =
+      SeverityOverrides::from_options(Some(settings)).map_err(|e| format!("severity: {e}"))?;
+    let rule_filter = string_field(settings, "ruleFilter")?
+      .map(Regex::new)
+      .transpose()
+      .map_err(|e| format!("ruleFilter: {e}"))?;
+    let language_ids = match settings.get("languageIds") {
+      None | Some(Value::Null) => HashMap::new(),
+      Some(ids) => serde_json::from_value(ids.clone())
+        .map_err(|_| "languageIds should map language ids to language names".to_string())?,
+    };
+    let diagnostics_delay = match settings.get("diagnosticsDelay") {
+      None | Some(Value::Null) => DIAGNOSTICS_DELAY,
+      Some(delay) => delay
+        .as_u64()
+        .map(Duration::from_millis)
+        .ok_or("diagnosticsDelay should be a number of milliseconds")?,
+    };
+    Ok(Self {
+      config_path,
+      severity,
+      rule_filter,
+      language_ids,
+      diagnostics_delay,
+    })
+  }
+
+  pub fn is_rule_enabled(&self, id: &str) -> bool {
+    self.rule_filter.as_ref().map_or(true, |f| f.is_match(id))
+  }
+
+  /// The ast-grep language name of a client language id, configured ones take precedence.
+  pub fn language_name<'a>(&'a self, language_id: &'a str) -> &'a str {
+    if let Some(name) = self.language_ids.get(language_id) {
+      return name;
+    }
+    LANGUAGE_IDS
+      .iter()
+      .find(|(id, _)| *id == language_id)
+      .map_or(language_id, |(_, name)| name)
+  }
+}
+
+fn string_field<'a>(settings: &'a Value, key: &str) -> Result<Option<&'a str>, String> {
+  match settings.get(key) {
+    None | Some(Value::Null) => Ok(None),
+    Some(Value::String(s)) => Ok(Some(s)),
+    Some(_) => Err(format!("{key} should be a string")),
+  }
+}
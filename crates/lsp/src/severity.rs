@@ -0,0 +1,80 @@
+//! Change severities of rules by the client configuration, like `--warning=RULE_ID` in `sg scan`.
+use ast_grep_config::Severity;
+use globset::{Glob, GlobMatcher};
+use serde::Deserialize;
+use serde_json::Value;
+
+use std::collections::HashMap;
+
+/// The `severity` field in `initializationOptions` or settings,
+/// e.g. `{"warning": ["no-console", "style-*"], "off": ["my-rule"]}`.
+/// An empty list changes the severity of all rules.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SeverityOptions {
+  error: Option<Vec<String>>,
+  warning: Option<Vec<String>>,
+  info: Option<Vec<String>>,
+  hint: Option<Vec<String>>,
+  off: Option<Vec<String>>,
+}
+
+#[derive(Default)]
+pub struct SeverityOverrides {
+  default_severity: Option<Severity>,
+  by_rule_id: HashMap<String, Severity>,
+  by_glob: Vec<(GlobMatcher, Severity)>,
+}
+
+impl SeverityOverrides {
+  /// Read the `severity` field of the options. No field means no override.
+  pub fn from_options(options: Option<&Value>) -> Result<Self, String> {
+    let Some(severity) = options.and_then(|o| o.get("severity")) else {
+      return Ok(Self::default());
+    };
+    let options: SeverityOptions =
+      serde_json::from_value(severity.clone()).map_err(|e| e.to_string())?;
+    let mut ret = Self::default();
+    // like the CLI flags, later severities overwrite earlier ones for the same id
+    let severities = [
+      (Severity::Error, options.error),
+      (Severity::Warning, options.warning),
+      (Severity::Info, options.info),
+      (Severity::Hint, options.hint),
+      (Severity::Off, options.off),
+    ];
+    for (severity, ids) in severities {
+      let Some(ids) = ids else { continue };
+      if ids.is_empty() {
+        ret.default_severity = Some(severity);
+        continue;
+      }
+      for id in ids {
+        if !is_glob(&id) {
+          ret.by_rule_id.insert(id, severity.clone());
+          continue;
+        }
+        let glob = Glob::new(&id).map_err(|e| format!("invalid glob `{id}`: {e}"))?;
+        ret.by_glob.push((glob.compile_matcher(), severity.clone()));
+      }
+    }
+    Ok(ret)
+  }
+
+  /// Exact rule ids take precedence over globs, which take precedence over empty lists.
+  pub fn find(&self, id: &str) -> Option<&Severity> {
+    let by_glob = || {
+      let mut globs = self.by_glob.iter().rev();
+      globs.find(|(glob, _)| glob.is_match(id)).map(|(_, s)| s)
+    };
+    self
+      .by_rule_id
+      .get(id)
+      .or_else(by_glob)
+      .or(self.default_severity.as_ref())
+  }
+}
+
+fn is_glob(id: &str) -> bool {
+  id.contains(['*', '?', '[', '{'])
+}
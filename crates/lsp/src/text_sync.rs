@@ -0,0 +1,259 @@
+//! Incremental text synchronization. Content changes locate text by lines and UTF-16
+//! characters, which are converted to byte offsets to edit the source and the tree in place.
+use ast_grep_core::{language::Language, source::Edit, AstGrep, StrDoc};
+use tower_lsp::lsp_types::{Position, Range, TextDocumentContentChangeEvent};
+
+/// Byte offsets of line starts, kept for every open document.
+pub struct LineIndex {
+  line_starts: Vec<usize>,
+}
+
+impl LineIndex {
+  pub fn new(text: &str) -> Self {
+    let mut line_starts = vec![0];
+    line_starts.extend(text.match_indices('\n').map(|(i, _)| i + 1));
+    Self { line_starts }
+  }
+
+  /// Convert a position in the indexed text to the byte offset. Characters beyond the line
+  /// end are clamped to the line end, and lines beyond the text to the text end.
+  pub fn offset(&self, text: &str, position: Position) -> usize {
+    let line = position.line as usize;
+    let Some(&start) = self.line_starts.get(line) else {
+      return text.len();
+    };
+    let end = self
+      .line_starts
+      .get(line + 1)
+      .copied()
+      .unwrap_or(text.len());
+    let line_text = text[start..end].trim_end_matches(['\n', '\r']);
+    let mut character = 0;
+    for (i, c) in line_text.char_indices() {
+      if character >= position.character as usize {
+        return start + i;
+      }
+      character += c.len_utf16();
+    }
+    start + line_text.len()
+  }
+
+  fn byte_range(&self, text: &str, range: Range) -> std::ops::Range<usize> {
+    let start = self.offset(text, range.start);
+    let end = self.offset(text, range.end);
+    start..end.max(start)
+  }
+
+  /// Byte offset where the 0-based line starts, None if the text has fewer lines.
+  pub fn line_start(&self, line: usize) -> Option<usize> {
+    self.line_starts.get(line).copied()
+  }
+
+  /// Convert a byte offset in the indexed text to a position, whose character is counted
+  /// in UTF-16 code units. The line is found by binary search.
+  pub fn position(&self, text: &str, offset: usize) -> Position {
+    // the first line starts at 0, so at least one line start is not after the offset
+    let line = self.line_starts.partition_point(|&start| start <= offset) - 1;
+    let start = self.line_starts[line];
+    Position {
+      line: line as u32,
+      character: text[start..offset].encode_utf16().count() as u32,
+    }
+  }
+
+  pub fn range(&self, text: &str, range: std::ops::Range<usize>) -> Range {
+    Range {
+      start: self.position(text, range.start),
+      end: self.position(text, range.end),
+    }
+  }
+}
+
+/// Apply content changes in order to a parsed document. Changes with ranges edit the tree
+/// so tree-sitter re-parses incrementally, a change without range replaces the whole text.
+pub fn apply_changes<L: Language>(
+  root: &mut AstGrep<StrDoc<L>>,
+  lines: &mut LineIndex,
+  changes: Vec<TextDocumentContentChangeEvent>,
+) {
+  for change in changes {
+    let Some(range) = change.range else {
+      *root = AstGrep::new(change.text, root.lang().clone());
+      *lines = LineIndex::new(root.source());
+      continue;
+    };
+    let range = lines.byte_range(root.source(), range);
+    let edit = Edit {
+      position: range.start,
+      deleted_length: range.len(),
+      inserted_text: change.text.into_bytes(),
+    };
+    if root.edit(edit).is_err() {
+      // the source is already edited, parse it from scratch
+      let text = root.source().to_string();
+      *root = AstGrep::new(text, root.lang().clone());
+    }
+    *lines = LineIndex::new(root.source());
+  }
+}
+
+/// Apply content changes in order to a document kept as text, like rule files.
+pub fn apply_text_changes(text: &mut String, changes: Vec<TextDocumentContentChangeEvent>) {
+  for change in changes {
+    match change.range {
+      Some(range) => {
+        let range = LineIndex::new(text).byte_range(text, range);
+        text.replace_range(range, &change.text);
+      }
+      None => *text = change.text,
+    }
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use ast_grep_language::SupportLang::Tsx;
+
+  /// Convert positions by walking UTF-16 code units of the whole text, without line index.
+  fn reference_offset(text: &str, position: Position) -> usize {
+    let mut offset = 0;
+    for (i, line) in text.split('\n').enumerate() {
+      if i == position.line as usize {
+        let line = line.trim_end_matches('\r');
+        let mut character = 0;
+        for (j, c) in line.char_indices() {
+          if character >= position.character as usize {
+            return offset + j;
+          }
+          character += c.len_utf16();
+        }
+        return offset + line.len();
+      }
+      offset += line.len() + 1;
+    }
+    text.len()
+  }
+
+  /// Full synchronization, the text is replaced and parsed from scratch.
+  fn reference_change(text: &str, range: Range, inserted: &str) -> String {
+    let start = reference_offset(text, range.start);
+    let end = reference_offset(text, range.end).max(start);
+    format!("{}{inserted}{}", &text[..start], &text[end..])
+  }
+
+  /// xorshift, a deterministic random generator for edit sequences.
+  struct Random(u64);
+  impl Random {
+    fn next(&mut self, bound: usize) -> usize {
+      self.0 ^= self.0 << 13;
+      self.0 ^= self.0 >> 7;
+      self.0 ^= self.0 << 17;
+      (self.0 % bound as u64) as usize
+    }
+    fn position(&mut self, text: &str) -> Position {
+      // positions can be beyond the line end or the last line
+      let line = self.next(text.lines().count() + 2) as u32;
+      let character = self.next(12) as u32;
+      Position { line, character }
+    }
+  }
+
+  const SNIPPETS: &[&str] = &[
+    "",
+    "a",
+    "\n",
+    "\r\n",
+    "let a = 1;",
+    "console.log('😀')",
+    "函数()",
+    "}",
+    "{ x: [1, 2] }\n",
+    "/* é */",
+  ];
+
+  fn change(range: Range, text: &str) -> TextDocumentContentChangeEvent {
+    TextDocumentContentChangeEvent {
+      range: Some(range),
+      range_length: None,
+      text: text.to_string(),
+    }
+  }
+
+  #[test]
+  fn test_offset() {
+    let text = "a😀b\r\nc\n";
+    let lines = LineIndex::new(text);
+    let offset = |line, character| lines.offset(text, Position { line, character });
+    assert_eq!(offset(0, 1), 1);
+    // 😀 is two UTF-16 code units and four bytes
+    assert_eq!(offset(0, 3), 5);
+    // clamped to the line end before \r\n
+    assert_eq!(offset(0, 10), 6);
+    assert_eq!(offset(1, 0), 8);
+    assert_eq!(offset(2, 0), 10);
+    assert_eq!(offset(5, 0), 10);
+  }
+
+  #[test]
+  fn test_position() {
+    let text = "a\n😀b\r\n\nc";
+    let lines = LineIndex::new(text);
+    let position = |offset| {
+      let Position { line, character } = lines.position(text, offset);
+      (line, character)
+    };
+    assert_eq!(position(0), (0, 0));
+    assert_eq!(position(1), (0, 1));
+    assert_eq!(position(2), (1, 0));
+    // 😀 is two UTF-16 code units and four bytes
+    assert_eq!(position(6), (1, 2));
+    assert_eq!(position(8), (1, 4));
+    assert_eq!(position(9), (2, 0));
+    assert_eq!(position(10), (3, 0));
+    assert_eq!(position(text.len()), (3, 1));
+    assert_eq!(lines.line_start(3), Some(10));
+    assert_eq!(lines.line_start(4), None);
+  }
+
+  #[test]
+  fn test_random_edits_match_full_sync() {
+    for seed in 1..=50 {
+      let mut random = Random(seed);
+      let mut expected = "function a() {\n  return 1\n}\n".to_string();
+      let mut root = AstGrep::new(&expected, Tsx);
+      let mut lines = LineIndex::new(&expected);
+      let mut text = expected.clone();
+      for _ in 0..30 {
+        let (a, b) = (random.position(&expected), random.position(&expected));
+        let range = Range::new(a.min(b), a.max(b));
+        let inserted = SNIPPETS[random.next(SNIPPETS.len())];
+        expected = reference_change(&expected, range, inserted);
+        apply_changes(&mut root, &mut lines, vec![change(range, inserted)]);
+        apply_text_changes(&mut text, vec![change(range, inserted)]);
+        assert_eq!(root.source(), expected, "seed {seed}");
+        assert_eq!(text, expected, "seed {seed}");
+        let reparsed = AstGrep::new(&expected, Tsx);
+        assert_eq!(
+          root.root().to_sexp(),
+          reparsed.root().to_sexp(),
+          "seed {seed}"
+        );
+      }
+    }
+  }
+
+  #[test]
+  fn test_full_change() {
+    let mut root = AstGrep::new("let a = 1", Tsx);
+    let mut lines = LineIndex::new(root.source());
+    let full = TextDocumentContentChangeEvent {
+      range: None,
+      range_length: None,
+      text: "let b = 2\nlet c = 3".into(),
+    };
+    let partial = change(Range::new(Position::new(1, 4), Position::new(1, 5)), "d");
+    apply_changes(&mut root, &mut lines, vec![full, partial]);
+    assert_eq!(root.source(), "let b = 2\nlet d = 3");
+  }
+}
@@ -1,17 +1,21 @@
 //! Provides utility to convert ast-grep data types to lsp data types
 use ast_grep_config::RuleConfig;
 use ast_grep_config::Severity;
-use ast_grep_core::{language::Language, Doc, Node, NodeMatch, StrDoc};
+use ast_grep_config::Suppression;
+use ast_grep_core::{language::Language, Node, NodeMatch, StrDoc};
 
 use serde::{Deserialize, Serialize};
 use tower_lsp::lsp_types::*;
 
+use crate::text_sync::LineIndex;
+
 use std::collections::HashMap;
 
 #[derive(Serialize, Deserialize)]
 pub struct RewriteData {
   pub fixed: String,
-  // maybe we should have fixed range
+  /// the replaced range, which can be larger than the matched node if the fix expands it
+  pub range: Range,
 }
 
 impl RewriteData {
@@ -22,31 +26,44 @@ impl RewriteData {
   fn from_node_match<L: Language>(
     node_match: &NodeMatch<StrDoc<L>>,
     rule: &RuleConfig<L>,
+    index: &LineIndex,
   ) -> Option<Self> {
     let fixer = rule.matcher.fixer.as_ref()?;
     let edit = node_match.replace_by(fixer);
     let rewrite = String::from_utf8(edit.inserted_text).ok()?;
-    Some(Self { fixed: rewrite })
+    let src = node_match.root().get_text();
+    let range = Range {
+      start: index.position(src, edit.position),
+      end: index.position(src, edit.position + edit.deleted_length),
+    };
+    Some(Self {
+      fixed: rewrite,
+      range,
+    })
+  }
+
+  pub fn into_text_edit(self) -> TextEdit {
+    TextEdit::new(self.range, self.fixed)
   }
 }
 
+/// Diagnostics without fix data, like those of a rule without `fix`, have no code action.
 pub fn diagnostic_to_code_action(
   text_doc: &TextDocumentIdentifier,
   diagnostic: Diagnostic,
 ) -> Option<CodeAction> {
-  let rewrite_data = RewriteData::from_value(diagnostic.data?)?;
+  let rewrite_data = RewriteData::from_value(diagnostic.data.clone()?)?;
   let mut changes = HashMap::new();
-  let text_edit = TextEdit::new(diagnostic.range, rewrite_data.fixed);
-  changes.insert(text_doc.uri.clone(), vec![text_edit]);
+  changes.insert(text_doc.uri.clone(), vec![rewrite_data.into_text_edit()]);
 
   let edit = WorkspaceEdit::new(changes);
-  let NumberOrString::String(id) = diagnostic.code? else {
+  let NumberOrString::String(id) = diagnostic.code.as_ref()? else {
     return None;
   };
   let action = CodeAction {
     title: format!("Fix `{id}` with ast-grep"),
     command: None,
-    diagnostics: None,
+    diagnostics: Some(vec![diagnostic.clone()]),
     edit: Some(edit),
     disabled: None,
     kind: Some(CodeActionKind::QUICKFIX),
@@ -56,33 +73,126 @@ pub fn diagnostic_to_code_action(
   Some(action)
 }
 
-fn convert_node_to_range<D: Doc>(node_match: &Node<D>) -> Range {
-  let (start_row, start_col) = node_match.start_pos();
-  let (end_row, end_col) = node_match.end_pos();
-  Range {
-    start: Position {
-      line: start_row as u32,
-      character: start_col as u32,
-    },
-    end: Position {
-      line: end_row as u32,
-      character: end_col as u32,
-    },
+/// Placeholder for the reason in the suppression comment, which users should replace.
+const REASON_PLACEHOLDER: &str = "TODO: explain why";
+
+/// Code actions that suppress the diagnostic's rule on its line, one without reason and one
+/// with a placeholder reason. The rule id is appended to the suppression comment on the line
+/// above if there is one, otherwise a new comment is inserted with the line's indentation.
+pub fn suppression_code_actions<L: Language>(
+  text_doc: &TextDocumentIdentifier,
+  src: &str,
+  lang: &L,
+  diagnostic: &Diagnostic,
+) -> Vec<CodeAction> {
+  let Some(NumberOrString::String(id)) = &diagnostic.code else {
+    return vec![];
+  };
+  let index = LineIndex::new(src);
+  let line_num = diagnostic.range.start.line as usize;
+  let Some(line_start) = index.line_start(line_num) else {
+    return vec![];
+  };
+  let line = src[line_start..].split('\n').next().unwrap_or_default();
+  let existing = line_num
+    .checked_sub(1)
+    .and_then(|prev| existing_suppression(src, lang, index.line_start(prev)?));
+  let mut actions: Vec<CodeAction> = vec![];
+  for reason in [None, Some(REASON_PLACEHOLDER)] {
+    let text_edit = match &existing {
+      Some((range, suppression)) => {
+        let mut suppression = suppression.clone();
+        let ids = suppression.rule_ids.get_or_insert_with(Vec::new);
+        ids.push(id.clone());
+        if suppression.reason.is_none() {
+          suppression.reason = reason.map(String::from);
+        }
+        let range = index.range(src, range.clone());
+        TextEdit::new(range, suppression.to_comment(lang))
+      }
+      None => {
+        let suppression = Suppression {
+          rule_ids: Some(vec![id.clone()]),
+          reason: reason.map(String::from),
+        };
+        let indent = &line[..line.len() - line.trim_start().len()];
+        let newline = if line.ends_with('\r') { "\r\n" } else { "\n" };
+        let comment = suppression.to_comment(lang);
+        let position = index.position(src, line_start);
+        TextEdit::new(
+          Range::new(position, position),
+          format!("{indent}{comment}{newline}"),
+        )
+      }
+    };
+    // an existing reason makes both variants identical
+    let is_duplicate = actions.iter().any(|a| {
+      let edits = a.edit.as_ref().and_then(|e| e.changes.as_ref());
+      edits.map_or(false, |c| c.values().any(|edits| edits[0] == text_edit))
+    });
+    if is_duplicate {
+      continue;
+    }
+    let title = match reason {
+      None => format!("Ignore `{id}` for this line"),
+      Some(_) => format!("Ignore `{id}` for this line with a reason"),
+    };
+    let mut changes = HashMap::new();
+    changes.insert(text_doc.uri.clone(), vec![text_edit]);
+    actions.push(CodeAction {
+      title,
+      command: None,
+      diagnostics: Some(vec![diagnostic.clone()]),
+      edit: Some(WorkspaceEdit::new(changes)),
+      disabled: None,
+      kind: Some(CodeActionKind::QUICKFIX),
+      is_preferred: Some(false),
+      data: None,
+    });
   }
+  actions
 }
 
+/// Find the suppression comment occupying the whole line and listing rule ids.
+/// Returns the byte range of the comment and the parsed suppression.
+fn existing_suppression<L: Language>(
+  src: &str,
+  lang: &L,
+  line_start: usize,
+) -> Option<(std::ops::Range<usize>, Suppression)> {
+  let line = src[line_start..].split('\n').next()?;
+  let content = line.trim();
+  let (comment_start, _) = lang.comment_delimiters();
+  if !content.starts_with(comment_start) {
+    return None;
+  }
+  let suppression = Suppression::parse(lang, content)?;
+  // a suppression of all rules cannot be narrowed by adding an id
+  suppression.rule_ids.as_ref()?;
+  let start = line_start + line.len() - line.trim_start().len();
+  Some((start..start + content.len(), suppression))
+}
+
+/// `index` must be built from the document of the node.
+pub fn convert_node_to_range<L: Language>(node: &Node<StrDoc<L>>, index: &LineIndex) -> Range {
+  index.range(node.root().get_text(), node.range())
+}
+
+/// `index` must be built from the document of the match.
 pub fn convert_match_to_diagnostic<L: Language>(
   node_match: NodeMatch<StrDoc<L>>,
   rule: &RuleConfig<L>,
+  severity: &Severity,
+  index: &LineIndex,
 ) -> Diagnostic {
   // TODO
-  let rewrite_data =
-    RewriteData::from_node_match(&node_match, rule).and_then(|r| serde_json::to_value(r).ok());
+  let rewrite_data = RewriteData::from_node_match(&node_match, rule, index)
+    .and_then(|r| serde_json::to_value(r).ok());
   Diagnostic {
-    range: convert_node_to_range(&node_match),
+    range: convert_node_to_range(&node_match, index),
     code: Some(NumberOrString::String(rule.id.clone())),
     code_description: url_to_code_description(&rule.url),
-    severity: Some(match rule.severity {
+    severity: Some(match severity {
       Severity::Error => DiagnosticSeverity::ERROR,
       Severity::Warning => DiagnosticSeverity::WARNING,
       Severity::Info => DiagnosticSeverity::INFORMATION,
@@ -97,6 +207,36 @@ pub fn convert_match_to_diagnostic<L: Language>(
   }
 }
 
+/// Convert a problem in a rule file, located by 1-based line and column, to a diagnostic.
+/// The range spans the key at the location, or the rest of the line for a value.
+pub fn convert_rule_problem_to_diagnostic(
+  text: &str,
+  (line, column, message): (usize, usize, String),
+) -> Diagnostic {
+  let line = line.saturating_sub(1);
+  let line_text = text.lines().nth(line).unwrap_or_default();
+  let mut start = column.saturating_sub(1).min(line_text.len());
+  while !line_text.is_char_boundary(start) {
+    start -= 1;
+  }
+  let rest = &line_text[start..];
+  let len = rest
+    .find(": ")
+    .or_else(|| rest.strip_suffix(':').map(str::len))
+    .unwrap_or_else(|| rest.trim_end().len());
+  let to_position = |byte: usize| Position {
+    line: line as u32,
+    character: line_text[..byte].encode_utf16().count() as u32,
+  };
+  Diagnostic {
+    range: Range::new(to_position(start), to_position(start + len)),
+    severity: Some(DiagnosticSeverity::ERROR),
+    source: Some(String::from("ast-grep(rule)")),
+    message,
+    ..Default::default()
+  }
+}
+
 fn get_non_empty_message<L: Language>(rule: &RuleConfig<L>, nm: &NodeMatch<StrDoc<L>>) -> String {
   // Note: The LSP client in vscode won't show any diagnostics at all if it receives one with an empty message
   let msg = if rule.message.is_empty() {
@@ -116,3 +256,61 @@ fn url_to_code_description(url: &Option<String>) -> Option<CodeDescription> {
   let href = Url::parse(url.as_ref()?).ok()?;
   Some(CodeDescription { href })
 }
+
+/// Markdown describing the rule of a match, shown when hovering its diagnostic.
+pub fn rule_hover<L: Language>(
+  node_match: &NodeMatch<StrDoc<L>>,
+  rule: &RuleConfig<L>,
+  severity: &Severity,
+) -> String {
+  let severity = match severity {
+    Severity::Error => "error",
+    Severity::Warning => "warning",
+    Severity::Info => "info",
+    Severity::Hint => "hint",
+    Severity::Off => "off",
+  };
+  let mut hover = format!("### {}\n\n*{severity}*", rule.id);
+  if !rule.message.is_empty() {
+    hover.push_str(&format!(": {}", rule.get_message(node_match)));
+  }
+  if let Some(note) = &rule.note {
+    hover.push_str(&format!("\n\n{}", note.trim_end()));
+  }
+  if let Some(url) = doc_url(rule) {
+    hover.push_str(&format!("\n\n[Documentation]({url})"));
+  }
+  hover
+}
+
+/// Keys in the rule metadata that may hold the documentation link, if `url` is not set.
+const DOC_URL_METADATA: [&str; 2] = ["url", "docs"];
+
+fn doc_url<L: Language>(rule: &RuleConfig<L>) -> Option<&str> {
+  if let Some(url) = &rule.url {
+    return Some(url);
+  }
+  let metadata = rule.metadata.as_ref()?;
+  DOC_URL_METADATA
+    .iter()
+    .find_map(|key| metadata.get(*key))
+    .map(|url| url.as_str())
+}
+
+/// Find the top level `id` key of the rule in the yaml text, which may define several rules.
+pub fn rule_id_range(yaml: &str, id: &str) -> Option<Range> {
+  let (line, text) = yaml.lines().enumerate().find(|(_, line)| {
+    let Some(value) = line.strip_prefix("id:") else {
+      return false;
+    };
+    let value = value.split(" #").next().unwrap_or_default().trim();
+    let unquoted = value
+      .strip_prefix('"')
+      .and_then(|v| v.strip_suffix('"'))
+      .or_else(|| value.strip_prefix('\'')?.strip_suffix('\''));
+    unquoted.unwrap_or(value) == id
+  })?;
+  let line = line as u32;
+  let end = text.trim_end().encode_utf16().count() as u32;
+  Some(Range::new(Position::new(line, 0), Position::new(line, end)))
+}
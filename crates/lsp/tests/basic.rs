@@ -5,6 +5,7 @@ use serde_json::Value;
 use tokio::io::{duplex, AsyncReadExt, AsyncWriteExt, DuplexStream};
 
 use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
 
 pub fn req(msg: &str) -> String {
   format!("Content-Length: {}\r\n\r\n{}", msg.len(), msg)
@@ -20,15 +21,18 @@ pub fn parse_jsonrpc(input: &mut &str) -> Option<Value> {
 
   let input_str = input_str.trim_start_matches("\r\n\r\n");
 
-  let body = &input_str[..length];
-  let value = serde_json::from_str(&body[..length]).ok()?;
+  // the message may be partially received
+  let body = input_str.get(..length)?;
+  let value = serde_json::from_str(body).ok()?;
   *input = &input_str[length..];
   value
 }
 
 // A function that takes a byte slice as input and parse them to Vec<serde_json::Value>
 pub fn resp(input: &[u8]) -> Vec<Value> {
-  let mut input_str = std::str::from_utf8(input).unwrap();
+  // a multi-byte character may be split at the end of a partial read
+  let input = String::from_utf8_lossy(input);
+  let mut input_str = input.as_ref();
 
   let mut resp_list = Vec::new();
 
@@ -56,8 +60,7 @@ fn req_resp_should_work() {
 }
 
 pub fn create_lsp() -> (DuplexStream, DuplexStream) {
-  let globals = GlobalRules::default();
-  let config: RuleConfig<SupportLang> = from_yaml_string(
+  create_lsp_with_rules(
     r"
 id: no-console-rule
 message: No console.log
@@ -69,13 +72,14 @@ note: no console.log
 fix: |
   alert($$$A)
 ",
-    &globals,
   )
-  .unwrap()
-  .pop()
-  .unwrap();
+}
+
+fn create_lsp_with_rules(yaml: &str) -> (DuplexStream, DuplexStream) {
+  let globals = GlobalRules::default();
+  let configs: Vec<RuleConfig<SupportLang>> = from_yaml_string(yaml, &globals).unwrap();
   let base = Path::new("./").to_path_buf();
-  let rc: RuleCollection<SupportLang> = RuleCollection::try_new(vec![config]).unwrap();
+  let rc: RuleCollection<SupportLang> = RuleCollection::try_new(configs).unwrap();
   let rc_result: std::result::Result<_, String> = Ok(rc);
   let (service, socket) =
     LspService::build(|client| Backend::new(client, base, rc_result)).finish();
@@ -258,3 +262,1579 @@ fn test_execute_apply_all_fixes() {
     );
   });
 }
+
+fn create_lsp_with_loader(
+  config_path: Option<std::path::PathBuf>,
+  loader: ConfigLoader<SupportLang>,
+) -> (DuplexStream, DuplexStream) {
+  let (service, socket) =
+    LspService::build(|client| Backend::with_loader(client, config_path, loader)).finish();
+  let (req_client, req_server) = duplex(4096);
+  let (resp_server, resp_client) = duplex(4096);
+  tokio::spawn(Server::new(req_server, resp_server, socket).serve(service));
+  (req_client, resp_client)
+}
+
+async fn read_until(resp_client: &mut DuplexStream, pred: impl Fn(&Value) -> bool) -> Vec<Value> {
+  let mut received = vec![];
+  let mut buf = vec![0; 4096];
+  loop {
+    let read = resp_client.read(&mut buf);
+    let len = tokio::time::timeout(std::time::Duration::from_secs(5), read)
+      .await
+      .expect("should receive message")
+      .unwrap();
+    assert!(len > 0, "server closed the connection");
+    received.extend_from_slice(&buf[..len]);
+    let messages = resp(&received);
+    if messages.iter().any(&pred) {
+      return messages;
+    }
+  }
+}
+
+async fn initialize_with_options(
+  req_client: &mut DuplexStream,
+  resp_client: &mut DuplexStream,
+  options: &str,
+) -> Vec<Value> {
+  let initialize = format!(
+    r#"{{"jsonrpc":"2.0","id":1,"method":"initialize","params":{{"capabilities":{{}},"initializationOptions":{options}}}}}"#
+  );
+  req_client
+    .write_all(req(&initialize).as_bytes())
+    .await
+    .unwrap();
+  read_until(resp_client, |m| m["id"] == 1).await;
+  // notifications before the initialize response are dropped
+  let initialized = r#"{"jsonrpc":"2.0","method":"initialized","params":{}}"#;
+  req_client
+    .write_all(req(initialized).as_bytes())
+    .await
+    .unwrap();
+  read_until(resp_client, |m| m["method"] == "window/showMessage").await
+}
+
+fn show_message(messages: &[Value]) -> &Value {
+  messages
+    .iter()
+    .find(|m| m["method"] == "window/showMessage")
+    .map(|m| &m["params"])
+    .expect("should show message")
+}
+
+#[test]
+fn test_missing_config_path() {
+  tokio::runtime::Runtime::new().unwrap().block_on(async {
+    let loader: ConfigLoader<SupportLang> = Box::new(|_| panic!("should not load"));
+    let missing = std::env::temp_dir().join("not-exist/sgconfig.yml");
+    let (mut req_client, mut resp_client) = create_lsp_with_loader(Some(missing), loader);
+    let messages = initialize_with_options(&mut req_client, &mut resp_client, "null").await;
+    let message = show_message(&messages);
+    // warning
+    assert_eq!(message["type"], 2);
+    let text = message["message"].as_str().unwrap();
+    assert!(text.starts_with("Cannot find ast-grep configuration at"));
+  });
+}
+
+#[test]
+fn test_config_path_from_init_options() {
+  tokio::runtime::Runtime::new().unwrap().block_on(async {
+    // use a dedicated directory so other tests do not discover the config
+    let dir = std::env::temp_dir().join(format!("ast-grep-lsp-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let config = dir.join("sgconfig.yml");
+    std::fs::write(&config, "ruleDirs: []").unwrap();
+    let expected = config.clone();
+    let loader: ConfigLoader<SupportLang> = Box::new(move |path| {
+      assert_eq!(path.as_ref(), Some(&expected));
+      Ok(ProjectRules {
+        base: Path::new("./").to_path_buf(),
+        rules: RuleCollection::try_new(vec![]).unwrap(),
+        broken_files: vec![(Path::new("rules/broken.yml").into(), "wrong".into())],
+        rule_paths: Default::default(),
+        rule_checker: None,
+      })
+    });
+    let (mut req_client, mut resp_client) = create_lsp_with_loader(None, loader);
+    let options = serde_json::json!({ "configPath": config }).to_string();
+    let messages = initialize_with_options(&mut req_client, &mut resp_client, &options).await;
+    let message = show_message(&messages);
+    assert_eq!(message["type"], 2);
+    let text = message["message"].as_str().unwrap();
+    assert_eq!(
+      text,
+      "Failed to load 1 rule file(s):\nrules/broken.yml: wrong"
+    );
+    std::fs::remove_dir_all(dir).unwrap();
+  });
+}
+
+const QUICKFIX_RULES: &str = r"
+id: wrap-log
+message: Wrap log in debug
+severity: warning
+language: TypeScript
+rule:
+  pattern: log($A)
+fix: |-
+  if (debug) {
+    log($A)
+  }
+---
+id: no-alert
+message: No alert
+severity: warning
+language: TypeScript
+rule:
+  pattern: alert($A)
+";
+
+async fn send(req_client: &mut DuplexStream, msg: Value) {
+  let msg = msg.to_string();
+  req_client.write_all(req(&msg).as_bytes()).await.unwrap();
+}
+
+/// Find the first message satisfying the predicate, reading more if needed.
+async fn receive(resp_client: &mut DuplexStream, pred: impl Fn(&Value) -> bool) -> Value {
+  let messages = read_until(resp_client, &pred).await;
+  messages.into_iter().find(pred).unwrap()
+}
+
+async fn initialize_and_open(
+  req_client: &mut DuplexStream,
+  resp_client: &mut DuplexStream,
+  uri: &str,
+  text: &str,
+) -> Value {
+  let initialize = serde_json::json!({
+    "jsonrpc": "2.0", "id": 1, "method": "initialize", "params": { "capabilities": {} }
+  });
+  send(req_client, initialize).await;
+  receive(resp_client, |m| m["id"] == 1).await;
+  let initialized = serde_json::json!({"jsonrpc": "2.0", "method": "initialized", "params": {}});
+  send(req_client, initialized).await;
+  let open = serde_json::json!({
+    "jsonrpc": "2.0",
+    "method": "textDocument/didOpen",
+    "params": {
+      "textDocument": { "uri": uri, "languageId": "typescript", "version": 1, "text": text }
+    }
+  });
+  send(req_client, open).await;
+  let folders = receive(resp_client, |m| m["method"] == "workspace/workspaceFolders").await;
+  let no_folder = serde_json::json!({"jsonrpc": "2.0", "id": folders["id"], "result": null});
+  send(req_client, no_folder).await;
+  let published = receive(resp_client, |m| {
+    m["method"] == "textDocument/publishDiagnostics"
+  })
+  .await;
+  published["params"]["diagnostics"].clone()
+}
+
+#[test]
+fn test_quickfix_code_action() {
+  tokio::runtime::Runtime::new().unwrap().block_on(async {
+    let (mut req_client, mut resp_client) = create_lsp_with_rules(QUICKFIX_RULES);
+    let uri = "file:///tmp/quickfix.ts";
+    // non-ASCII characters before the match must be counted in UTF-16 code units
+    let text = "const \u{f1} = '\u{1f600}'; log(\u{f1})\nalert(1)";
+    let diagnostics = initialize_and_open(&mut req_client, &mut resp_client, uri, text).await;
+    let wrap_log = diagnostics
+      .as_array()
+      .unwrap()
+      .iter()
+      .find(|d| d["code"] == "wrap-log")
+      .unwrap();
+    let expected_range = serde_json::json!({
+      "start": { "line": 0, "character": 16 },
+      "end": { "line": 0, "character": 22 },
+    });
+    assert_eq!(wrap_log["range"], expected_range);
+    let code_action = serde_json::json!({
+      "jsonrpc": "2.0",
+      "id": 2,
+      "method": "textDocument/codeAction",
+      "params": {
+        "textDocument": { "uri": uri },
+        "range": { "start": { "line": 0, "character": 0 }, "end": { "line": 1, "character": 8 } },
+        "context": { "diagnostics": diagnostics }
+      }
+    });
+    send(&mut req_client, code_action).await;
+    let response = receive(&mut resp_client, |m| m["id"] == 2).await;
+    // no-alert has no fix and offers no fix action
+    let actions: Vec<_> = response["result"]
+      .as_array()
+      .unwrap()
+      .iter()
+      .filter(|a| a["title"].as_str().unwrap().starts_with("Fix"))
+      .collect();
+    assert_eq!(actions.len(), 1);
+    assert_eq!(actions[0]["title"], "Fix `wrap-log` with ast-grep");
+    assert_eq!(actions[0]["kind"], "quickfix");
+    let edits = &actions[0]["edit"]["changes"][uri];
+    assert_eq!(edits[0]["range"], expected_range);
+    assert_eq!(edits[0]["newText"], "if (debug) {\n  log(\u{f1})\n}");
+  });
+}
+
+const FIX_ALL_RULES: &str = r"
+id: b-print
+message: Use print
+severity: warning
+language: TypeScript
+rule:
+  pattern: log($A)
+fix: print($A)
+---
+id: a-wrap
+message: Wrap log in debug
+severity: warning
+language: TypeScript
+rule:
+  pattern: log($A)
+fix: debug(log($A))
+---
+id: no-var
+message: No var
+severity: warning
+language: TypeScript
+rule:
+  pattern: var $A = $B
+fix: let $A = $B
+";
+
+fn fix_all_request(id: u32, uri: &str, only: &str) -> Value {
+  serde_json::json!({
+    "jsonrpc": "2.0",
+    "id": id,
+    "method": "textDocument/codeAction",
+    "params": {
+      "textDocument": { "uri": uri },
+      "range": { "start": { "line": 0, "character": 0 }, "end": { "line": 0, "character": 0 } },
+      "context": { "diagnostics": [], "only": [only] }
+    }
+  })
+}
+
+#[test]
+fn test_fix_all_code_action() {
+  tokio::runtime::Runtime::new().unwrap().block_on(async {
+    let (mut req_client, mut resp_client) = create_lsp_with_rules(FIX_ALL_RULES);
+    // the file does not exist on disk, fixes are computed from the buffer
+    let uri = "file:///tmp/not-saved/fix-all.ts";
+    initialize_and_open(&mut req_client, &mut resp_client, uri, "log(a)").await;
+    let change = serde_json::json!({
+      "jsonrpc": "2.0",
+      "method": "textDocument/didChange",
+      "params": {
+        "textDocument": { "uri": uri, "version": 2 },
+        "contentChanges": [{ "text": "var a = 1\nlog(a)\nlog(b)" }]
+      }
+    });
+    send(&mut req_client, change).await;
+    receive(&mut resp_client, |m| {
+      m["method"] == "textDocument/publishDiagnostics" && m["params"]["version"] == 2
+    })
+    .await;
+    send(&mut req_client, fix_all_request(2, uri, "source.fixAll")).await;
+    let response = receive(&mut resp_client, |m| m["id"] == 2).await;
+    let actions = response["result"].as_array().unwrap();
+    assert_eq!(actions.len(), 1);
+    assert_eq!(actions[0]["kind"], "source.fixAll.ast-grep");
+    let edits = actions[0]["edit"]["changes"][uri].as_array().unwrap();
+    let texts: Vec<_> = edits.iter().map(|e| e["newText"].clone()).collect();
+    // overlapping fixes of the same node keep the first rule by id
+    assert_eq!(texts, ["let a = 1", "debug(log(a))", "debug(log(b))"]);
+    // quickfix only requests do not include fix all
+    send(&mut req_client, fix_all_request(3, uri, "quickfix")).await;
+    let response = receive(&mut resp_client, |m| m["id"] == 3).await;
+    assert_eq!(response["result"], Value::Null);
+  });
+}
+
+fn suppression_actions(response: &Value, uri: &str) -> Vec<(String, Value)> {
+  let actions = response["result"].as_array().unwrap();
+  actions
+    .iter()
+    .filter(|a| a["title"].as_str().unwrap().starts_with("Ignore"))
+    .map(|a| {
+      let title = a["title"].as_str().unwrap().to_string();
+      (title, a["edit"]["changes"][uri][0].clone())
+    })
+    .collect()
+}
+
+#[test]
+fn test_suppression_code_action() {
+  tokio::runtime::Runtime::new().unwrap().block_on(async {
+    let (mut req_client, mut resp_client) = create_lsp_with_rules(QUICKFIX_RULES);
+    let uri = "file:///tmp/suppression.ts";
+    let text = "function f() {\n  alert(1)\n  // ast-grep-ignore: other\n  log(a)\n}";
+    let diagnostics = initialize_and_open(&mut req_client, &mut resp_client, uri, text).await;
+    let code_action = serde_json::json!({
+      "jsonrpc": "2.0",
+      "id": 2,
+      "method": "textDocument/codeAction",
+      "params": {
+        "textDocument": { "uri": uri },
+        "range": { "start": { "line": 0, "character": 0 }, "end": { "line": 4, "character": 0 } },
+        "context": { "diagnostics": diagnostics }
+      }
+    });
+    send(&mut req_client, code_action).await;
+    let response = receive(&mut resp_client, |m| m["id"] == 2).await;
+    let actions = suppression_actions(&response, uri);
+    assert_eq!(actions.len(), 4);
+    // a new comment keeps the indentation of the line
+    let (title, edit) = &actions[0];
+    assert_eq!(title, "Ignore `no-alert` for this line");
+    let start = serde_json::json!({ "line": 1, "character": 0 });
+    assert_eq!(edit["range"]["start"], start);
+    assert_eq!(edit["range"]["end"], start);
+    assert_eq!(edit["newText"], "  // ast-grep-ignore: no-alert\n");
+    let (title, edit) = &actions[1];
+    assert_eq!(title, "Ignore `no-alert` for this line with a reason");
+    assert_eq!(
+      edit["newText"],
+      "  // ast-grep-ignore: no-alert -- TODO: explain why\n"
+    );
+    // the rule is appended to the existing suppression comment
+    let (title, edit) = &actions[2];
+    assert_eq!(title, "Ignore `wrap-log` for this line");
+    let range = serde_json::json!({
+      "start": { "line": 2, "character": 2 },
+      "end": { "line": 2, "character": 27 },
+    });
+    assert_eq!(edit["range"], range);
+    assert_eq!(edit["newText"], "// ast-grep-ignore: other, wrap-log");
+    let (_, edit) = &actions[3];
+    assert_eq!(
+      edit["newText"],
+      "// ast-grep-ignore: other, wrap-log -- TODO: explain why"
+    );
+  });
+}
+
+fn watched_file_change(path: &Path) -> Value {
+  let uri = tower_lsp::lsp_types::Url::from_file_path(path).unwrap();
+  serde_json::json!({
+    "jsonrpc": "2.0",
+    "method": "workspace/didChangeWatchedFiles",
+    "params": { "changes": [{ "uri": uri, "type": 2 }] }
+  })
+}
+
+#[test]
+fn test_reload_rules_on_change() {
+  use std::sync::{Arc, Mutex};
+  tokio::runtime::Runtime::new().unwrap().block_on(async {
+    let dir = std::env::temp_dir().join(format!("ast-grep-lsp-reload-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let config = dir.join("sgconfig.yml");
+    std::fs::write(&config, "ruleDirs: [rules]").unwrap();
+    // (rule yaml, broken files) returned by the next load
+    let project = Arc::new(Mutex::new((QUICKFIX_RULES.to_string(), vec![])));
+    let load_count = Arc::new(Mutex::new(0));
+    let (loaded, count, base) = (project.clone(), load_count.clone(), dir.clone());
+    let loader: ConfigLoader<SupportLang> = Box::new(move |_| {
+      *count.lock().unwrap() += 1;
+      let (yaml, broken_files) = loaded.lock().unwrap().clone();
+      let configs = from_yaml_string(&yaml, &GlobalRules::default()).unwrap();
+      Ok(ProjectRules {
+        base: base.clone(),
+        rules: RuleCollection::try_new(configs).unwrap(),
+        broken_files,
+        rule_paths: Default::default(),
+        rule_checker: None,
+      })
+    });
+    let (mut req_client, mut resp_client) = create_lsp_with_loader(Some(config), loader);
+    let initialize = serde_json::json!({
+      "jsonrpc": "2.0",
+      "id": 1,
+      "method": "initialize",
+      "params": {
+        "capabilities": { "workspace": { "didChangeWatchedFiles": { "dynamicRegistration": true } } }
+      }
+    });
+    send(&mut req_client, initialize).await;
+    receive(&mut resp_client, |m| m["id"] == 1).await;
+    let initialized = serde_json::json!({"jsonrpc": "2.0", "method": "initialized", "params": {}});
+    send(&mut req_client, initialized).await;
+    let register = receive(&mut resp_client, |m| m["method"] == "client/registerCapability").await;
+    let registration = &register["params"]["registrations"][0];
+    assert_eq!(registration["method"], "workspace/didChangeWatchedFiles");
+    let registered = serde_json::json!({"jsonrpc": "2.0", "id": register["id"], "result": null});
+    send(&mut req_client, registered).await;
+
+    let uri = tower_lsp::lsp_types::Url::from_file_path(dir.join("a.ts")).unwrap();
+    let open = serde_json::json!({
+      "jsonrpc": "2.0",
+      "method": "textDocument/didOpen",
+      "params": {
+        "textDocument": { "uri": uri, "languageId": "typescript", "version": 1, "text": "alert(1)" }
+      }
+    });
+    send(&mut req_client, open).await;
+    let folders = receive(&mut resp_client, |m| m["method"] == "workspace/workspaceFolders").await;
+    let no_folder = serde_json::json!({"jsonrpc": "2.0", "id": folders["id"], "result": null});
+    send(&mut req_client, no_folder).await;
+    let published = receive(&mut resp_client, |m| {
+      m["method"] == "textDocument/publishDiagnostics"
+    })
+    .await;
+    assert_eq!(published["params"]["diagnostics"][0]["code"], "no-alert");
+
+    // a burst of events reloads the rules once
+    let new_rule = "id: new-alert\nmessage: New\nseverity: error\nlanguage: TypeScript\nrule:\n  pattern: alert($A)";
+    project.lock().unwrap().0 = new_rule.to_string();
+    let rule_file = dir.join("rules/a.yml");
+    send(&mut req_client, watched_file_change(&rule_file)).await;
+    send(&mut req_client, watched_file_change(&rule_file)).await;
+    let published = receive(&mut resp_client, |m| {
+      m["method"] == "textDocument/publishDiagnostics"
+    })
+    .await;
+    assert_eq!(published["params"]["diagnostics"][0]["code"], "new-alert");
+    assert_eq!(*load_count.lock().unwrap(), 2);
+
+    // a broken rule file keeps the last good rules
+    let broken = (dir.join("rules/b.yml"), "wrong".to_string());
+    *project.lock().unwrap() = (QUICKFIX_RULES.to_string(), vec![broken]);
+    send(&mut req_client, watched_file_change(&rule_file)).await;
+    let message = receive(&mut resp_client, |m| m["method"] == "window/showMessage").await;
+    let text = message["params"]["message"].as_str().unwrap();
+    assert!(text.contains("keeping the last loaded rules"));
+    assert!(text.contains("b.yml: wrong"));
+    let change = serde_json::json!({
+      "jsonrpc": "2.0",
+      "method": "textDocument/didChange",
+      "params": {
+        "textDocument": { "uri": uri, "version": 2 },
+        "contentChanges": [{ "text": "alert(2)" }]
+      }
+    });
+    send(&mut req_client, change).await;
+    let published = receive(&mut resp_client, |m| {
+      m["method"] == "textDocument/publishDiagnostics" && m["params"]["version"] == 2
+    })
+    .await;
+    assert_eq!(published["params"]["diagnostics"][0]["code"], "new-alert");
+    std::fs::remove_dir_all(dir).unwrap();
+  });
+}
+
+fn list_files(folders: &[std::path::PathBuf], _: &AtomicBool) -> Vec<std::path::PathBuf> {
+  let mut files = vec![];
+  for folder in folders {
+    for entry in std::fs::read_dir(folder).unwrap() {
+      files.push(entry.unwrap().path());
+    }
+  }
+  files.sort();
+  files
+}
+
+/// Run a workspace command and answer the server requests it sends.
+async fn scan_workspace(
+  req_client: &mut DuplexStream,
+  resp_client: &mut DuplexStream,
+  id: i32,
+  command: &str,
+) -> Vec<Value> {
+  let execute = serde_json::json!({
+    "jsonrpc": "2.0",
+    "id": id,
+    "method": "workspace/executeCommand",
+    "params": { "command": command, "arguments": [] }
+  });
+  send(req_client, execute).await;
+  if command == "ast-grep.scanWorkspace" {
+    let folders = receive(resp_client, |m| m["method"] == "workspace/workspaceFolders").await;
+    let no_folder = serde_json::json!({"jsonrpc": "2.0", "id": folders["id"], "result": null});
+    send(req_client, no_folder).await;
+    let create = receive(resp_client, |m| {
+      m["method"] == "window/workDoneProgress/create"
+    })
+    .await;
+    let created = serde_json::json!({"jsonrpc": "2.0", "id": create["id"], "result": null});
+    send(req_client, created).await;
+  }
+  read_until(resp_client, |m| m["id"] == id).await
+}
+
+fn published_files(messages: &[Value]) -> Vec<(String, usize)> {
+  let mut published: Vec<_> = messages
+    .iter()
+    .filter(|m| m["method"] == "textDocument/publishDiagnostics")
+    .map(|m| {
+      let uri = m["params"]["uri"].as_str().unwrap();
+      let name = uri.rsplit('/').next().unwrap().to_string();
+      (name, m["params"]["diagnostics"].as_array().unwrap().len())
+    })
+    .collect();
+  published.sort();
+  published
+}
+
+#[test]
+fn test_scan_workspace() {
+  tokio::runtime::Runtime::new().unwrap().block_on(async {
+    let dir = std::env::temp_dir().join(format!("ast-grep-lsp-scan-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("a.ts"), "alert(1); alert(2)").unwrap();
+    std::fs::write(dir.join("b.ts"), "let a = 1").unwrap();
+    std::fs::write(dir.join("c.ts"), "alert(3)").unwrap();
+    std::fs::write(dir.join("d.txt"), "alert(4)").unwrap();
+    let configs = from_yaml_string::<SupportLang>(QUICKFIX_RULES, &GlobalRules::default()).unwrap();
+    let rules = Ok(RuleCollection::try_new(configs).unwrap());
+    let base = dir.clone();
+    let (service, socket) = LspService::build(|client| {
+      Backend::new(client, base, rules).with_file_walker(Box::new(list_files))
+    })
+    .finish();
+    let (mut req_client, req_server) = duplex(4096);
+    let (resp_server, mut resp_client) = duplex(4096);
+    tokio::spawn(Server::new(req_server, resp_server, socket).serve(service));
+    let initialize = serde_json::json!({
+      "jsonrpc": "2.0",
+      "id": 1,
+      "method": "initialize",
+      "params": { "capabilities": { "window": { "workDoneProgress": true } } }
+    });
+    send(&mut req_client, initialize).await;
+    let initialized = receive(&mut resp_client, |m| m["id"] == 1).await;
+    let commands = &initialized["result"]["capabilities"]["executeCommandProvider"]["commands"];
+    assert!(commands
+      .as_array()
+      .unwrap()
+      .contains(&"ast-grep.scanWorkspace".into()));
+
+    let scan = "ast-grep.scanWorkspace";
+    let messages = scan_workspace(&mut req_client, &mut resp_client, 2, scan).await;
+    let published = published_files(&messages);
+    assert_eq!(published, vec![("a.ts".into(), 2), ("c.ts".into(), 1)]);
+    let progress: Vec<_> = messages
+      .iter()
+      .filter(|m| m["method"] == "$/progress")
+      .map(|m| m["params"]["value"]["kind"].as_str().unwrap())
+      .collect();
+    assert_eq!(progress.first(), Some(&"begin"));
+    assert_eq!(progress.last(), Some(&"end"));
+    let result = &messages.iter().find(|m| m["id"] == 2).unwrap()["result"];
+    assert_eq!(result["filesWithIssues"], 2);
+    assert_eq!(result["issues"], 3);
+
+    // fixed files are cleared by the next scan
+    std::fs::write(dir.join("c.ts"), "let c = 3").unwrap();
+    let messages = scan_workspace(&mut req_client, &mut resp_client, 3, scan).await;
+    let published = published_files(&messages);
+    assert_eq!(published, vec![("a.ts".into(), 2), ("c.ts".into(), 0)]);
+
+    let clear = "ast-grep.clearWorkspaceDiagnostics";
+    let messages = scan_workspace(&mut req_client, &mut resp_client, 4, clear).await;
+    assert_eq!(published_files(&messages), vec![("a.ts".into(), 0)]);
+    std::fs::remove_dir_all(dir).unwrap();
+  });
+}
+
+/// Wait until the scan is cancelled, like walking a huge workspace.
+fn walk_until_cancelled(
+  _: &[std::path::PathBuf],
+  cancelled: &AtomicBool,
+) -> Vec<std::path::PathBuf> {
+  for _ in 0..500 {
+    if cancelled.load(Ordering::Relaxed) {
+      break;
+    }
+    std::thread::sleep(std::time::Duration::from_millis(10));
+  }
+  vec![]
+}
+
+#[test]
+fn test_cancel_scan_workspace() {
+  tokio::runtime::Runtime::new().unwrap().block_on(async {
+    let configs = from_yaml_string::<SupportLang>(QUICKFIX_RULES, &GlobalRules::default()).unwrap();
+    let rules = Ok(RuleCollection::try_new(configs).unwrap());
+    let builder = LspService::build(|client| {
+      Backend::new(client, "./".into(), rules).with_file_walker(Box::new(walk_until_cancelled))
+    });
+    let (service, socket) = Backend::register_custom_methods(builder).finish();
+    let (mut req_client, req_server) = duplex(4096);
+    let (resp_server, mut resp_client) = duplex(4096);
+    tokio::spawn(Server::new(req_server, resp_server, socket).serve(service));
+    let initialize = serde_json::json!({
+      "jsonrpc": "2.0",
+      "id": 1,
+      "method": "initialize",
+      "params": { "capabilities": { "window": { "workDoneProgress": true } } }
+    });
+    send(&mut req_client, initialize).await;
+    receive(&mut resp_client, |m| m["id"] == 1).await;
+    let execute = serde_json::json!({
+      "jsonrpc": "2.0",
+      "id": 2,
+      "method": "workspace/executeCommand",
+      "params": { "command": "ast-grep.scanWorkspace", "arguments": [] }
+    });
+    send(&mut req_client, execute).await;
+    let folders = receive(&mut resp_client, |m| {
+      m["method"] == "workspace/workspaceFolders"
+    })
+    .await;
+    let no_folder = serde_json::json!({"jsonrpc": "2.0", "id": folders["id"], "result": null});
+    send(&mut req_client, no_folder).await;
+    let create = receive(&mut resp_client, |m| {
+      m["method"] == "window/workDoneProgress/create"
+    })
+    .await;
+    let created = serde_json::json!({"jsonrpc": "2.0", "id": create["id"], "result": null});
+    send(&mut req_client, created).await;
+    let begin = receive(&mut resp_client, |m| m["method"] == "$/progress").await;
+    assert_eq!(begin["params"]["value"]["cancellable"], true);
+    let cancel = serde_json::json!({
+      "jsonrpc": "2.0",
+      "method": "window/workDoneProgress/cancel",
+      "params": { "token": begin["params"]["token"] }
+    });
+    send(&mut req_client, cancel).await;
+    let messages = read_until(&mut resp_client, |m| m["id"] == 2).await;
+    let end = messages
+      .iter()
+      .find(|m| m["params"]["value"]["kind"] == "end")
+      .unwrap();
+    let summary = end["params"]["value"]["message"].as_str().unwrap();
+    assert!(summary.starts_with("Workspace scan is cancelled."));
+    let result = &messages.iter().find(|m| m["id"] == 2).unwrap()["result"];
+    assert_eq!(result["cancelled"], true);
+    assert_eq!(result["scannedFiles"], 0);
+  });
+}
+
+#[test]
+fn test_apply_workspace_fixes() {
+  tokio::runtime::Runtime::new().unwrap().block_on(async {
+    let dir = std::env::temp_dir().join(format!("ast-grep-lsp-fix-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("a.ts"), "log(1); log(2)").unwrap();
+    std::fs::write(dir.join("b.ts"), "alert(1)").unwrap();
+    std::fs::write(dir.join("c.ts"), "let c = 3").unwrap();
+    let configs = from_yaml_string::<SupportLang>(QUICKFIX_RULES, &GlobalRules::default()).unwrap();
+    let rules = Ok(RuleCollection::try_new(configs).unwrap());
+    let base = dir.clone();
+    let (service, socket) = LspService::build(|client| {
+      Backend::new(client, base, rules).with_file_walker(Box::new(list_files))
+    })
+    .finish();
+    let (mut req_client, req_server) = duplex(4096);
+    let (resp_server, mut resp_client) = duplex(4096);
+    tokio::spawn(Server::new(req_server, resp_server, socket).serve(service));
+    let initialize = serde_json::json!({
+      "jsonrpc": "2.0",
+      "id": 1,
+      "method": "initialize",
+      "params": {
+        "capabilities": { "workspace": { "workspaceEdit": { "documentChanges": true } } }
+      }
+    });
+    send(&mut req_client, initialize).await;
+    receive(&mut resp_client, |m| m["id"] == 1).await;
+    // the open document is fixed by its unsaved text
+    let open_uri = tower_lsp::lsp_types::Url::from_file_path(dir.join("c.ts")).unwrap();
+    let open = serde_json::json!({
+      "jsonrpc": "2.0",
+      "method": "textDocument/didOpen",
+      "params": {
+        "textDocument": {
+          "uri": open_uri, "languageId": "typescript", "version": 7, "text": "log(3)"
+        }
+      }
+    });
+    send(&mut req_client, open).await;
+    let folders = receive(&mut resp_client, |m| {
+      m["method"] == "workspace/workspaceFolders"
+    })
+    .await;
+    let no_folder = serde_json::json!({"jsonrpc": "2.0", "id": folders["id"], "result": null});
+    send(&mut req_client, no_folder).await;
+    receive(&mut resp_client, |m| {
+      m["method"] == "textDocument/publishDiagnostics"
+    })
+    .await;
+
+    let execute = serde_json::json!({
+      "jsonrpc": "2.0",
+      "id": 2,
+      "method": "workspace/executeCommand",
+      "params": { "command": "ast-grep.applyAllFixes", "arguments": [] }
+    });
+    send(&mut req_client, execute).await;
+    let folders = receive(&mut resp_client, |m| {
+      m["method"] == "workspace/workspaceFolders"
+    })
+    .await;
+    let no_folder = serde_json::json!({"jsonrpc": "2.0", "id": folders["id"], "result": null});
+    send(&mut req_client, no_folder).await;
+    let apply = receive(&mut resp_client, |m| m["method"] == "workspace/applyEdit").await;
+    let changes = apply["params"]["edit"]["documentChanges"]
+      .as_array()
+      .unwrap();
+    let mut files: Vec<_> = changes
+      .iter()
+      .map(|change| {
+        let uri = change["textDocument"]["uri"].as_str().unwrap();
+        let name = uri.rsplit('/').next().unwrap().to_string();
+        let version = change["textDocument"]["version"].clone();
+        (name, version, change["edits"].as_array().unwrap().len())
+      })
+      .collect();
+    files.sort_by(|a, b| a.0.cmp(&b.0));
+    assert_eq!(
+      files,
+      vec![
+        ("a.ts".into(), Value::Null, 2),
+        ("c.ts".into(), Value::from(7), 1)
+      ]
+    );
+    let rejected = serde_json::json!({
+      "jsonrpc": "2.0",
+      "id": apply["id"],
+      "result": { "applied": false, "failureReason": "file changed" }
+    });
+    send(&mut req_client, rejected).await;
+    let messages = read_until(&mut resp_client, |m| m["id"] == 2).await;
+    let result = &messages.iter().find(|m| m["id"] == 2).unwrap()["result"];
+    assert_eq!(
+      result,
+      &serde_json::json!({ "applied": false, "edits": 3, "files": 2 })
+    );
+    let message = show_message(&messages)["message"].as_str().unwrap();
+    assert!(message.starts_with("The editor rejected 3 fix(es) in 2 file(s)."));
+    assert!(message.ends_with("file changed"));
+    std::fs::remove_dir_all(dir).unwrap();
+  });
+}
+
+const HOVER_RULES: &str = r"
+id: no-alert
+message: Do not alert $A
+severity: error
+language: TypeScript
+rule:
+  pattern: alert($A)
+note: Use a **modal** instead.
+url: https://example.com/no-alert
+---
+id: no-call
+message: No call
+severity: hint
+language: TypeScript
+rule:
+  kind: call_expression
+metadata:
+  docs: https://example.com/no-call
+";
+
+async fn hover(
+  req_client: &mut DuplexStream,
+  resp_client: &mut DuplexStream,
+  id: i32,
+  line: u32,
+  character: u32,
+) -> Value {
+  let hover = serde_json::json!({
+    "jsonrpc": "2.0",
+    "id": id,
+    "method": "textDocument/hover",
+    "params": {
+      "textDocument": { "uri": "file:///tmp/hover.ts" },
+      "position": { "line": line, "character": character }
+    }
+  });
+  send(req_client, hover).await;
+  let response = receive(resp_client, |m| m["id"] == id).await;
+  response["result"].clone()
+}
+
+#[test]
+fn test_hover() {
+  tokio::runtime::Runtime::new().unwrap().block_on(async {
+    let (mut req_client, mut resp_client) = create_lsp_with_rules(HOVER_RULES);
+    let uri = "file:///tmp/hover.ts";
+    let text = "let a = 1\nalert(a)\nfoo()";
+    initialize_and_open(&mut req_client, &mut resp_client, uri, text).await;
+
+    let result = hover(&mut req_client, &mut resp_client, 2, 1, 3).await;
+    let expected = "### no-alert\n\n*error*: Do not alert a\n\nUse a **modal** instead.\n\n\
+      [Documentation](https://example.com/no-alert)\n\n---\n\n\
+      ### no-call\n\n*hint*: No call\n\n[Documentation](https://example.com/no-call)";
+    assert_eq!(result["contents"]["kind"], "markdown");
+    assert_eq!(result["contents"]["value"], expected);
+    assert_eq!(
+      result["range"]["start"],
+      serde_json::json!({"line": 1, "character": 0})
+    );
+    assert_eq!(
+      result["range"]["end"],
+      serde_json::json!({"line": 1, "character": 8})
+    );
+
+    let result = hover(&mut req_client, &mut resp_client, 3, 2, 1).await;
+    let value = result["contents"]["value"].as_str().unwrap();
+    assert!(value.starts_with("### no-call"));
+
+    // no hover outside of diagnostics
+    let result = hover(&mut req_client, &mut resp_client, 4, 0, 2).await;
+    assert!(result.is_null());
+  });
+}
+
+fn change_configuration(settings: Value) -> Value {
+  serde_json::json!({
+    "jsonrpc": "2.0",
+    "method": "workspace/didChangeConfiguration",
+    "params": { "settings": settings }
+  })
+}
+
+fn diagnostic_severities(published: &Value) -> Vec<(String, i64)> {
+  let diagnostics = published["params"]["diagnostics"].as_array().unwrap();
+  let mut severities: Vec<_> = diagnostics
+    .iter()
+    .map(|d| {
+      let code = d["code"].as_str().unwrap().to_string();
+      (code, d["severity"].as_i64().unwrap())
+    })
+    .collect();
+  severities.sort();
+  severities
+}
+
+#[test]
+fn test_severity_options() {
+  tokio::runtime::Runtime::new().unwrap().block_on(async {
+    let (mut req_client, mut resp_client) = create_lsp_with_rules(QUICKFIX_RULES);
+    let initialize = serde_json::json!({
+      "jsonrpc": "2.0",
+      "id": 1,
+      "method": "initialize",
+      "params": {
+        "capabilities": {},
+        "initializationOptions": { "severity": { "error": ["no-*"], "off": ["wrap-log"] } }
+      }
+    });
+    send(&mut req_client, initialize).await;
+    receive(&mut resp_client, |m| m["id"] == 1).await;
+    let initialized = serde_json::json!({"jsonrpc": "2.0", "method": "initialized", "params": {}});
+    send(&mut req_client, initialized).await;
+    let open = serde_json::json!({
+      "jsonrpc": "2.0",
+      "method": "textDocument/didOpen",
+      "params": {
+        "textDocument": {
+          "uri": "file:///tmp/severity.ts",
+          "languageId": "typescript",
+          "version": 1,
+          "text": "alert(1)\nlog(2)"
+        }
+      }
+    });
+    send(&mut req_client, open).await;
+    let folders = receive(&mut resp_client, |m| {
+      m["method"] == "workspace/workspaceFolders"
+    })
+    .await;
+    let no_folder = serde_json::json!({"jsonrpc": "2.0", "id": folders["id"], "result": null});
+    send(&mut req_client, no_folder).await;
+    let is_published = |m: &Value| m["method"] == "textDocument/publishDiagnostics";
+    let published = receive(&mut resp_client, is_published).await;
+    assert_eq!(
+      diagnostic_severities(&published),
+      vec![("no-alert".into(), 1)]
+    );
+
+    // configuration change re-publishes diagnostics
+    let settings = serde_json::json!({ "severity": { "hint": [] } });
+    send(&mut req_client, change_configuration(settings)).await;
+    let published = receive(&mut resp_client, is_published).await;
+    let expected = vec![("no-alert".into(), 4), ("wrap-log".into(), 4)];
+    assert_eq!(diagnostic_severities(&published), expected);
+
+    let settings = serde_json::json!({ "severity": { "warning": "no-alert" } });
+    send(&mut req_client, change_configuration(settings)).await;
+    let message = receive(&mut resp_client, |m| m["method"] == "window/showMessage").await;
+    assert_eq!(message["params"]["type"], 2);
+    let text = message["params"]["message"].as_str().unwrap();
+    assert!(text.starts_with("Invalid ast-grep settings: severity:"));
+  });
+}
+
+#[test]
+fn test_invalid_severity_options() {
+  tokio::runtime::Runtime::new().unwrap().block_on(async {
+    let (mut req_client, mut resp_client) = create_lsp_with_rules(QUICKFIX_RULES);
+    let options = r#"{ "severity": { "warning": ["no-[alert"] } }"#;
+    let messages = initialize_with_options(&mut req_client, &mut resp_client, options).await;
+    let message = show_message(&messages);
+    assert_eq!(message["type"], 2);
+    let text = message["message"].as_str().unwrap();
+    assert!(text.contains("invalid glob `no-[alert`"));
+  });
+}
+
+const SCOPED_RULES: &str = r"
+id: scoped
+message: Scoped
+severity: warning
+language: TypeScript
+files: [src/**]
+ignores: [src/gen/**]
+rule:
+  pattern: alert($A)
+---
+id: unscoped
+message: Unscoped
+severity: warning
+language: TypeScript
+ignores: [test/**]
+rule:
+  pattern: alert($A)
+";
+
+/// Open the document and return the codes of published diagnostics.
+async fn open_and_diagnose(
+  req_client: &mut DuplexStream,
+  resp_client: &mut DuplexStream,
+  path: &Path,
+) -> Vec<String> {
+  let uri = tower_lsp::lsp_types::Url::from_file_path(path).unwrap();
+  let open = serde_json::json!({
+    "jsonrpc": "2.0",
+    "method": "textDocument/didOpen",
+    "params": {
+      "textDocument": { "uri": uri, "languageId": "typescript", "version": 1, "text": "alert(1)" }
+    }
+  });
+  send(req_client, open).await;
+  let folders = receive(resp_client, |m| m["method"] == "workspace/workspaceFolders").await;
+  let no_folder = serde_json::json!({"jsonrpc": "2.0", "id": folders["id"], "result": null});
+  send(req_client, no_folder).await;
+  let published = receive(resp_client, |m| {
+    m["method"] == "textDocument/publishDiagnostics"
+  })
+  .await;
+  assert_eq!(published["params"]["uri"], uri.as_str());
+  let diagnostics = published["params"]["diagnostics"].as_array().unwrap();
+  let mut codes: Vec<_> = diagnostics
+    .iter()
+    .map(|d| d["code"].as_str().unwrap().to_string())
+    .collect();
+  codes.sort();
+  codes
+}
+
+#[test]
+fn test_rule_file_scope() {
+  tokio::runtime::Runtime::new().unwrap().block_on(async {
+    let base = std::env::temp_dir().join(format!("ast-grep-lsp-scope-{}", std::process::id()));
+    let configs = from_yaml_string::<SupportLang>(SCOPED_RULES, &GlobalRules::default()).unwrap();
+    let rules = Ok(RuleCollection::try_new(configs).unwrap());
+    let project = base.clone();
+    let (service, socket) = LspService::build(|client| {
+      let ignore_filter = Box::new(|base: &Path, path: &Path| {
+        path.starts_with(base.join("dist"))
+      });
+      Backend::new(client, project, rules).with_ignore_filter(ignore_filter)
+    })
+    .finish();
+    let (mut req_client, req_server) = duplex(4096);
+    let (resp_server, mut resp_client) = duplex(4096);
+    tokio::spawn(Server::new(req_server, resp_server, socket).serve(service));
+    initialize_lsp(&mut req_client, &mut resp_client).await;
+
+    let (req, resp) = (&mut req_client, &mut resp_client);
+    let codes = open_and_diagnose(req, resp, &base.join("src/a.ts")).await;
+    assert_eq!(codes, ["scoped", "unscoped"]);
+    let codes = open_and_diagnose(req, resp, &base.join("src/gen/a.ts")).await;
+    assert_eq!(codes, ["unscoped"]);
+    let codes = open_and_diagnose(req, resp, &base.join("test/a.ts")).await;
+    assert!(codes.is_empty());
+    // documents outside of the project only run rules not scoped by `files`
+    let outside = std::env::temp_dir().join("ast-grep-lsp-outside/src/a.ts");
+    let codes = open_and_diagnose(req, resp, &outside).await;
+    assert_eq!(codes, ["unscoped"]);
+    // ignored documents are not diagnosed, the next published one is src/b.ts
+    let ignored = tower_lsp::lsp_types::Url::from_file_path(base.join("dist/a.ts")).unwrap();
+    let open = serde_json::json!({
+      "jsonrpc": "2.0",
+      "method": "textDocument/didOpen",
+      "params": {
+        "textDocument": { "uri": ignored, "languageId": "typescript", "version": 1, "text": "alert(1)" }
+      }
+    });
+    send(req, open).await;
+    let folders = receive(resp, |m| m["method"] == "workspace/workspaceFolders").await;
+    let no_folder = serde_json::json!({"jsonrpc": "2.0", "id": folders["id"], "result": null});
+    send(req, no_folder).await;
+    let codes = open_and_diagnose(req, resp, &base.join("src/b.ts")).await;
+    assert_eq!(codes, ["scoped", "unscoped"]);
+  });
+}
+
+/// Answer the next `workspace/configuration` request with the settings.
+async fn answer_configuration(
+  req_client: &mut DuplexStream,
+  resp_client: &mut DuplexStream,
+  settings: Value,
+) {
+  let request = receive(resp_client, |m| m["method"] == "workspace/configuration").await;
+  assert_eq!(request["params"]["items"][0]["section"], "astGrep");
+  let response = serde_json::json!({"jsonrpc": "2.0", "id": request["id"], "result": [settings]});
+  send(req_client, response).await;
+}
+
+#[test]
+fn test_configuration_change() {
+  tokio::runtime::Runtime::new().unwrap().block_on(async {
+    let base = std::env::temp_dir().join(format!("ast-grep-lsp-settings-{}", std::process::id()));
+    std::fs::create_dir_all(&base).unwrap();
+    let (log_rule, alert_rule) = QUICKFIX_RULES.split_once("---").unwrap();
+    std::fs::write(base.join("log.yml"), log_rule).unwrap();
+    std::fs::write(base.join("alert.yml"), alert_rule).unwrap();
+    // the configuration file itself contains the rules in this test
+    let project = base.clone();
+    let loader: ConfigLoader<SupportLang> = Box::new(move |path| {
+      let yaml = match path {
+        Some(path) => std::fs::read_to_string(path).unwrap(),
+        None => QUICKFIX_RULES.to_string(),
+      };
+      let configs = from_yaml_string(&yaml, &GlobalRules::default()).unwrap();
+      Ok(ProjectRules {
+        base: project.clone(),
+        rules: RuleCollection::try_new(configs).unwrap(),
+        broken_files: vec![],
+        rule_paths: Default::default(),
+        rule_checker: None,
+      })
+    });
+    let (mut req_client, mut resp_client) = create_lsp_with_loader(None, loader);
+    let (req, resp) = (&mut req_client, &mut resp_client);
+    let root = tower_lsp::lsp_types::Url::from_file_path(&base).unwrap();
+    let initialize = serde_json::json!({
+      "jsonrpc": "2.0",
+      "id": 1,
+      "method": "initialize",
+      "params": {
+        "rootUri": root,
+        "capabilities": { "workspace": { "configuration": true } },
+        "initializationOptions": { "configPath": "alert.yml" }
+      }
+    });
+    send(req, initialize).await;
+    receive(resp, |m| m["id"] == 1).await;
+    let initialized = serde_json::json!({"jsonrpc": "2.0", "method": "initialized", "params": {}});
+    send(req, initialized).await;
+    // no pulled settings keep the initialization options
+    answer_configuration(req, resp, Value::Null).await;
+    let codes = open_and_diagnose(req, resp, &base.join("a.ts")).await;
+    assert_eq!(codes, ["no-alert"]);
+    let uri = tower_lsp::lsp_types::Url::from_file_path(base.join("a.ts")).unwrap();
+    let change = serde_json::json!({
+      "jsonrpc": "2.0",
+      "method": "textDocument/didChange",
+      "params": {
+        "textDocument": { "uri": uri, "version": 2 },
+        "contentChanges": [{ "text": "alert(1)\nlog(2)" }]
+      }
+    });
+    send(req, change).await;
+    let is_published = |m: &Value| m["method"] == "textDocument/publishDiagnostics";
+    receive(resp, is_published).await;
+
+    let changed = change_configuration(Value::Null);
+    send(req, changed.clone()).await;
+    answer_configuration(req, resp, serde_json::json!({ "configPath": "log.yml" })).await;
+    let published = receive(resp, is_published).await;
+    assert_eq!(published["params"]["diagnostics"][0]["code"], "wrap-log");
+
+    // a missing configuration keeps the last working settings
+    send(req, changed.clone()).await;
+    answer_configuration(
+      req,
+      resp,
+      serde_json::json!({ "configPath": "missing.yml" }),
+    )
+    .await;
+    let message = receive(resp, |m| m["method"] == "window/showMessage").await;
+    let text = message["params"]["message"].as_str().unwrap();
+    assert!(text.starts_with("Cannot find ast-grep configuration"));
+    assert!(text.ends_with("Keeping the last working settings."));
+
+    // removing configPath loads the default configuration, filtered by ruleFilter
+    send(req, changed).await;
+    answer_configuration(req, resp, serde_json::json!({ "ruleFilter": "^no-" })).await;
+    let published = receive(resp, is_published).await;
+    assert_eq!(published["params"]["diagnostics"][0]["code"], "no-alert");
+    assert_eq!(
+      published["params"]["diagnostics"].as_array().unwrap().len(),
+      1
+    );
+    std::fs::remove_dir_all(base).unwrap();
+  });
+}
+
+/// Execute `ast-grep.gotoRule` and answer the `window/showDocument` request.
+async fn goto_rule(
+  req_client: &mut DuplexStream,
+  resp_client: &mut DuplexStream,
+  id: i32,
+  arguments: &Value,
+) -> (Value, Value) {
+  let execute = serde_json::json!({
+    "jsonrpc": "2.0",
+    "id": id,
+    "method": "workspace/executeCommand",
+    "params": { "command": "ast-grep.gotoRule", "arguments": arguments }
+  });
+  send(req_client, execute).await;
+  let show = receive(resp_client, |m| m["method"] == "window/showDocument").await;
+  let shown =
+    serde_json::json!({"jsonrpc": "2.0", "id": show["id"], "result": { "success": true }});
+  send(req_client, shown).await;
+  let response = receive(resp_client, |m| m["id"] == id).await;
+  (show["params"].clone(), response["result"].clone())
+}
+
+#[test]
+fn test_goto_rule() {
+  tokio::runtime::Runtime::new().unwrap().block_on(async {
+    let dir = std::env::temp_dir().join(format!("ast-grep-lsp-goto-{}", std::process::id()));
+    std::fs::create_dir_all(dir.join("rules")).unwrap();
+    let config = dir.join("sgconfig.yml");
+    std::fs::write(&config, "ruleDirs: [rules]").unwrap();
+    let rule_file = dir.join("rules/rules.yml");
+    std::fs::write(&rule_file, QUICKFIX_RULES).unwrap();
+    let (base, path) = (dir.clone(), rule_file.clone());
+    let loader: ConfigLoader<SupportLang> = Box::new(move |_| {
+      let configs = from_yaml_string(QUICKFIX_RULES, &GlobalRules::default()).unwrap();
+      // wrap-log is not loaded from a rule file, like inline rules
+      let rule_paths = [("no-alert".to_string(), path.clone())].into();
+      Ok(ProjectRules {
+        base: base.clone(),
+        rules: RuleCollection::try_new(configs).unwrap(),
+        broken_files: vec![],
+        rule_paths,
+        rule_checker: None,
+      })
+    });
+    let (mut req_client, mut resp_client) = create_lsp_with_loader(Some(config.clone()), loader);
+    let (req, resp) = (&mut req_client, &mut resp_client);
+    let initialize = serde_json::json!({
+      "jsonrpc": "2.0",
+      "id": 1,
+      "method": "initialize",
+      "params": { "capabilities": { "window": { "showDocument": { "support": true } } } }
+    });
+    send(req, initialize).await;
+    let initialized = receive(resp, |m| m["id"] == 1).await;
+    let commands = &initialized["result"]["capabilities"]["executeCommandProvider"]["commands"];
+    assert!(commands
+      .as_array()
+      .unwrap()
+      .contains(&"ast-grep.gotoRule".into()));
+    let initialized = serde_json::json!({"jsonrpc": "2.0", "method": "initialized", "params": {}});
+    send(req, initialized).await;
+    let uri = tower_lsp::lsp_types::Url::from_file_path(dir.join("a.ts")).unwrap();
+    let open = serde_json::json!({
+      "jsonrpc": "2.0",
+      "method": "textDocument/didOpen",
+      "params": {
+        "textDocument": { "uri": uri, "languageId": "typescript", "version": 1, "text": "alert(1)" }
+      }
+    });
+    send(req, open).await;
+    let folders = receive(resp, |m| m["method"] == "workspace/workspaceFolders").await;
+    let no_folder = serde_json::json!({"jsonrpc": "2.0", "id": folders["id"], "result": null});
+    send(req, no_folder).await;
+    let published = receive(resp, |m| m["method"] == "textDocument/publishDiagnostics").await;
+    let code_action = serde_json::json!({
+      "jsonrpc": "2.0",
+      "id": 2,
+      "method": "textDocument/codeAction",
+      "params": {
+        "textDocument": { "uri": uri },
+        "range": { "start": { "line": 0, "character": 0 }, "end": { "line": 0, "character": 1 } },
+        "context": { "diagnostics": published["params"]["diagnostics"] }
+      }
+    });
+    send(req, code_action).await;
+    let response = receive(resp, |m| m["id"] == 2).await;
+    let actions = response["result"].as_array().unwrap();
+    let goto = actions
+      .iter()
+      .find(|a| a["title"] == "Open the definition of `no-alert`")
+      .expect("should offer going to rule");
+    assert_eq!(goto["command"]["command"], "ast-grep.gotoRule");
+
+    let (shown, location) = goto_rule(req, resp, 3, &goto["command"]["arguments"]).await;
+    let rule_uri = tower_lsp::lsp_types::Url::from_file_path(&rule_file).unwrap();
+    assert_eq!(shown["uri"], rule_uri.as_str());
+    let line = QUICKFIX_RULES
+      .lines()
+      .position(|l| l == "id: no-alert")
+      .unwrap();
+    let selection = serde_json::json!({
+      "start": { "line": line, "character": 0 },
+      "end": { "line": line, "character": 12 }
+    });
+    assert_eq!(shown["selection"], selection);
+    assert_eq!(location["uri"], rule_uri.as_str());
+    assert_eq!(location["range"], selection);
+
+    // rules without rule files open the configuration
+    let (shown, _) = goto_rule(req, resp, 4, &serde_json::json!(["wrap-log"])).await;
+    let config_uri = tower_lsp::lsp_types::Url::from_file_path(&config).unwrap();
+    assert_eq!(shown["uri"], config_uri.as_str());
+    std::fs::remove_dir_all(dir).unwrap();
+  });
+}
+
+#[test]
+fn test_rule_file_diagnostics() {
+  tokio::runtime::Runtime::new().unwrap().block_on(async {
+    let loader: ConfigLoader<SupportLang> = Box::new(|_| {
+      let configs = from_yaml_string(QUICKFIX_RULES, &GlobalRules::default()).unwrap();
+      let rule_checker: RuleChecker = Box::new(|path, yaml| {
+        if path.extension()? != "yml" {
+          return None;
+        }
+        let typo = yaml.lines().position(|l| l.starts_with("severty"));
+        let problems = typo.map(|line| (line + 1, 1, "unknown key `severty`.".into()));
+        Some(problems.into_iter().collect())
+      });
+      Ok(ProjectRules {
+        base: "./".into(),
+        rules: RuleCollection::try_new(configs).unwrap(),
+        broken_files: vec![],
+        rule_paths: Default::default(),
+        rule_checker: Some(rule_checker),
+      })
+    });
+    let (mut req_client, mut resp_client) = create_lsp_with_loader(None, loader);
+    let (req, resp) = (&mut req_client, &mut resp_client);
+    let initialize = serde_json::json!({
+      "jsonrpc": "2.0", "id": 1, "method": "initialize", "params": { "capabilities": {} }
+    });
+    send(req, initialize).await;
+    receive(resp, |m| m["id"] == 1).await;
+    let uri = "file:///tmp/rules/typo.yml";
+    let open = serde_json::json!({
+      "jsonrpc": "2.0",
+      "method": "textDocument/didOpen",
+      "params": {
+        "textDocument": {
+          "uri": uri, "languageId": "yaml", "version": 1, "text": "id: typo\nseverty: error"
+        }
+      }
+    });
+    send(req, open).await;
+    let published = receive(resp, |m| m["method"] == "textDocument/publishDiagnostics").await;
+    let diagnostic = &published["params"]["diagnostics"][0];
+    assert_eq!(diagnostic["source"], "ast-grep(rule)");
+    assert_eq!(diagnostic["message"], "unknown key `severty`.");
+    let range = serde_json::json!({
+      "start": { "line": 1, "character": 0 },
+      "end": { "line": 1, "character": 7 }
+    });
+    assert_eq!(diagnostic["range"], range);
+
+    let change = serde_json::json!({
+      "jsonrpc": "2.0",
+      "method": "textDocument/didChange",
+      "params": {
+        "textDocument": { "uri": uri, "version": 2 },
+        "contentChanges": [{ "text": "id: typo\nseverity: error" }]
+      }
+    });
+    send(req, change).await;
+    let published = receive(resp, |m| m["method"] == "textDocument/publishDiagnostics").await;
+    assert_eq!(published["params"]["version"], 2);
+    assert_eq!(published["params"]["diagnostics"], serde_json::json!([]));
+  });
+}
+
+async fn open_untitled(
+  req_client: &mut DuplexStream,
+  resp_client: &mut DuplexStream,
+  uri: &str,
+  language_id: &str,
+) -> Value {
+  let open = serde_json::json!({
+    "jsonrpc": "2.0",
+    "method": "textDocument/didOpen",
+    "params": {
+      "textDocument": { "uri": uri, "languageId": language_id, "version": 1, "text": "alert(1)" }
+    }
+  });
+  send(req_client, open).await;
+  let folders = receive(resp_client, |m| m["method"] == "workspace/workspaceFolders").await;
+  let no_folder = serde_json::json!({"jsonrpc": "2.0", "id": folders["id"], "result": null});
+  send(req_client, no_folder).await;
+  receive(resp_client, |m| {
+    m["method"] == "textDocument/publishDiagnostics"
+  })
+  .await
+}
+
+#[test]
+fn test_untitled_document() {
+  tokio::runtime::Runtime::new().unwrap().block_on(async {
+    let (mut req_client, mut resp_client) = create_lsp_with_rules(SCOPED_RULES);
+    let (req, resp) = (&mut req_client, &mut resp_client);
+    let initialize = serde_json::json!({
+      "jsonrpc": "2.0",
+      "id": 1,
+      "method": "initialize",
+      "params": {
+        "capabilities": {},
+        "initializationOptions": { "languageIds": { "my-script": "ts" } }
+      }
+    });
+    send(req, initialize).await;
+    receive(resp, |m| m["id"] == 1).await;
+
+    // rules scoped by `files` cannot match untitled documents
+    let uri = "untitled:Untitled-1";
+    let published = open_untitled(req, resp, uri, "typescript").await;
+    assert_eq!(published["params"]["uri"], uri);
+    let codes: Vec<_> = published["params"]["diagnostics"]
+      .as_array()
+      .unwrap()
+      .iter()
+      .map(|d| d["code"].as_str().unwrap())
+      .collect();
+    assert_eq!(codes, ["unscoped"]);
+    let change = serde_json::json!({
+      "jsonrpc": "2.0",
+      "method": "textDocument/didChange",
+      "params": {
+        "textDocument": { "uri": uri, "version": 2 },
+        "contentChanges": [{ "text": "alert(1); alert(2)" }]
+      }
+    });
+    send(req, change).await;
+    let published = receive(resp, |m| m["method"] == "textDocument/publishDiagnostics").await;
+    assert_eq!(
+      published["params"]["diagnostics"].as_array().unwrap().len(),
+      2
+    );
+
+    // language ids are mapped by the settings
+    let published = open_untitled(req, resp, "untitled:Untitled-2", "my-script").await;
+    assert_eq!(
+      published["params"]["diagnostics"].as_array().unwrap().len(),
+      1
+    );
+
+    // diagnostics of closed untitled documents are cleared, e.g. when saved to files
+    let close = serde_json::json!({
+      "jsonrpc": "2.0",
+      "method": "textDocument/didClose",
+      "params": { "textDocument": { "uri": uri } }
+    });
+    send(req, close).await;
+    let published = receive(resp, |m| m["method"] == "textDocument/publishDiagnostics").await;
+    assert_eq!(published["params"]["uri"], uri);
+    assert_eq!(published["params"]["diagnostics"], serde_json::json!([]));
+  });
+}
+
+#[test]
+fn test_incremental_change() {
+  tokio::runtime::Runtime::new().unwrap().block_on(async {
+    let (mut req_client, mut resp_client) = create_lsp_with_rules(QUICKFIX_RULES);
+    let (req, resp) = (&mut req_client, &mut resp_client);
+    let uri = "file:///tmp/incremental.ts";
+    initialize_and_open(req, resp, uri, "const \u{1f600} = 1; alert(1)").await;
+    // replace `1` in `alert(1)`, the emoji is two UTF-16 code units
+    let change = serde_json::json!({
+      "jsonrpc": "2.0",
+      "method": "textDocument/didChange",
+      "params": {
+        "textDocument": { "uri": uri, "version": 2 },
+        "contentChanges": [
+          {
+            "range": { "start": { "line": 0, "character": 20 }, "end": { "line": 0, "character": 21 } },
+            "text": "2);\nlog(3"
+          },
+          {
+            "range": { "start": { "line": 0, "character": 0 }, "end": { "line": 0, "character": 5 } },
+            "text": "let"
+          }
+        ]
+      }
+    });
+    send(req, change).await;
+    let published = receive(resp, |m| {
+      m["method"] == "textDocument/publishDiagnostics" && m["params"]["version"] == 2
+    })
+    .await;
+    let ranges: Vec<_> = published["params"]["diagnostics"]
+      .as_array()
+      .unwrap()
+      .iter()
+      .map(|d| (d["code"].as_str().unwrap(), d["range"]["start"].clone()))
+      .collect();
+    assert_eq!(
+      ranges,
+      vec![
+        ("no-alert", serde_json::json!({ "line": 0, "character": 12 })),
+        ("wrap-log", serde_json::json!({ "line": 1, "character": 0 })),
+      ]
+    );
+  });
+}
+
+#[test]
+fn test_burst_of_changes() {
+  tokio::runtime::Runtime::new().unwrap().block_on(async {
+    let (mut req_client, mut resp_client) = create_lsp_with_rules(QUICKFIX_RULES);
+    let (req, resp) = (&mut req_client, &mut resp_client);
+    let uri = "file:///tmp/burst.ts";
+    initialize_and_open(req, resp, uri, "").await;
+    // type `alert(1)` and more alerts faster than the diagnostics delay
+    let mut text = String::new();
+    for version in 2..=11 {
+      text += &format!("alert({version});");
+      let change = serde_json::json!({
+        "jsonrpc": "2.0",
+        "method": "textDocument/didChange",
+        "params": {
+          "textDocument": { "uri": uri, "version": version },
+          "contentChanges": [{ "text": text }]
+        }
+      });
+      send(req, change).await;
+    }
+    let mut messages = read_until(resp, |m| m["params"]["version"] == 11).await;
+    // a request after the last diagnostics flushes messages published meanwhile
+    let hover = serde_json::json!({
+      "jsonrpc": "2.0",
+      "id": 2,
+      "method": "textDocument/hover",
+      "params": { "textDocument": { "uri": uri }, "position": { "line": 0, "character": 0 } }
+    });
+    send(req, hover).await;
+    messages.extend(read_until(resp, |m| m["id"] == 2).await);
+    let published: Vec<_> = messages
+      .iter()
+      .filter(|m| m["method"] == "textDocument/publishDiagnostics")
+      .map(|m| {
+        (
+          m["params"]["version"].clone(),
+          m["params"]["diagnostics"].as_array().unwrap().len(),
+        )
+      })
+      .collect();
+    assert_eq!(published, vec![(11.into(), 10)]);
+  });
+}
+
+#[test]
+fn test_workspace_folders() {
+  tokio::runtime::Runtime::new().unwrap().block_on(async {
+    let dir = std::env::temp_dir().join(format!("ast-grep-lsp-folders-{}", std::process::id()));
+    // a and b use the same rule id for different rules, c has no configuration
+    let rule = |pattern: &str| {
+      format!("id: same-id\nmessage: m\nseverity: warning\nlanguage: TypeScript\nrule:\n  pattern: {pattern}")
+    };
+    for (name, config) in [("a", Some(rule("alert($A)"))), ("b", Some(rule("log($A)"))), ("c", None)] {
+      std::fs::create_dir_all(dir.join(name)).unwrap();
+      if let Some(config) = config {
+        std::fs::write(dir.join(name).join("sgconfig.yml"), config).unwrap();
+      }
+    }
+    // the rules of a folder are in its configuration for the test
+    let loader: ConfigLoader<SupportLang> = Box::new(|path| {
+      let path = path.expect("should load the configuration of a folder");
+      let yaml = std::fs::read_to_string(&path).unwrap();
+      let configs = from_yaml_string(&yaml, &GlobalRules::default()).unwrap();
+      Ok(ProjectRules {
+        base: path.parent().unwrap().to_path_buf(),
+        rules: RuleCollection::try_new(configs).unwrap(),
+        broken_files: vec![],
+        rule_paths: Default::default(),
+        rule_checker: None,
+      })
+    });
+    let (mut req_client, mut resp_client) = create_lsp_with_loader(None, loader);
+    let folder = |name: &str| {
+      let uri = tower_lsp::lsp_types::Url::from_file_path(dir.join(name)).unwrap();
+      serde_json::json!({ "uri": uri, "name": name })
+    };
+    let initialize = serde_json::json!({
+      "jsonrpc": "2.0",
+      "id": 1,
+      "method": "initialize",
+      "params": { "capabilities": {}, "workspaceFolders": [folder("a"), folder("b"), folder("c")] }
+    });
+    send(&mut req_client, initialize).await;
+    receive(&mut resp_client, |m| m["id"] == 1).await;
+    let initialized = serde_json::json!({"jsonrpc": "2.0", "method": "initialized", "params": {}});
+    send(&mut req_client, initialized).await;
+    let loaded = receive(&mut resp_client, |m| m["method"] == "window/showMessage").await;
+    assert_eq!(
+      loaded["params"]["message"],
+      "Loaded 2 ast-grep rule(s) in 2 workspace folder(s)."
+    );
+
+    let diagnose = |path: &str| dir.join(path);
+    let codes = open_and_diagnose(&mut req_client, &mut resp_client, &diagnose("a/x.ts")).await;
+    assert_eq!(codes, vec!["same-id"]);
+    // rules of folder a never apply to folder b
+    let codes = open_and_diagnose(&mut req_client, &mut resp_client, &diagnose("b/x.ts")).await;
+    assert!(codes.is_empty());
+    let codes = open_and_diagnose(&mut req_client, &mut resp_client, &diagnose("c/x.ts")).await;
+    assert!(codes.is_empty());
+
+    // folder d is loaded when added, folder a is unloaded when removed
+    std::fs::create_dir_all(dir.join("d")).unwrap();
+    std::fs::write(dir.join("d/sgconfig.yml"), rule("alert($A)")).unwrap();
+    let change = serde_json::json!({
+      "jsonrpc": "2.0",
+      "method": "workspace/didChangeWorkspaceFolders",
+      "params": { "event": { "added": [folder("d")], "removed": [folder("a")] } }
+    });
+    send(&mut req_client, change).await;
+    // open documents are diagnosed again, a/x.ts is outside every folder now
+    let mut messages = vec![];
+    let is_published = |m: &Value| m["method"] == "textDocument/publishDiagnostics";
+    while messages.iter().filter(|m| is_published(m)).count() < 3 {
+      messages.extend(read_until(&mut resp_client, is_published).await);
+    }
+    let loaded = messages
+      .iter()
+      .find(|m| m["method"] == "window/showMessage")
+      .unwrap();
+    assert_eq!(
+      loaded["params"]["message"],
+      "Loaded 2 ast-grep rule(s) in 2 workspace folder(s)."
+    );
+    let uri = tower_lsp::lsp_types::Url::from_file_path(dir.join("a/x.ts")).unwrap();
+    let republished = messages
+      .iter()
+      .find(|m| is_published(m) && m["params"]["uri"] == uri.as_str())
+      .unwrap();
+    assert_eq!(republished["params"]["diagnostics"], serde_json::json!([]));
+    let codes = open_and_diagnose(&mut req_client, &mut resp_client, &diagnose("d/x.ts")).await;
+    assert_eq!(codes, vec!["same-id"]);
+    // documents outside every folder fall back to the first project, which is b now
+    let codes = open_and_diagnose(&mut req_client, &mut resp_client, &diagnose("a/y.ts")).await;
+    assert!(codes.is_empty());
+    std::fs::remove_dir_all(dir).unwrap();
+  });
+}